@@ -174,6 +174,17 @@ local wezterm = require 'wezterm';
 
 at the top of your configuration file to enable it.
 
+## Available functions, constants
+""",
+                    ),
+                    Gen(
+                        "module: wezterm.mux",
+                        "config/lua/wezterm.mux",
+                        index="""
+# `wezterm.mux` module
+
+The `wezterm.mux` module exposes the multiplexer to your event handlers.
+
 ## Available functions, constants
 """,
                     ),
@@ -222,6 +233,22 @@ clear and convenient.
                     Page("object: SpawnCommand", "config/lua/SpawnCommand.md"),
                     Page("object: TlsDomainClient", "config/lua/TlsDomainClient.md"),
                     Page("object: TlsDomainServer", "config/lua/TlsDomainServer.md"),
+                    Gen(
+                        "object: MuxDomain",
+                        "config/lua/MuxDomain",
+                        index="""
+# `MuxDomain` object
+
+A MuxDomain object cannot be created in lua code; it is obtained from
+[wezterm.mux.all_domains()](../wezterm.mux/all_domains.md) or passed
+to the [domain-state-changed](../window-events/domain-state-changed.md)
+event.  A MuxDomain object is a handle to a domain that is known to the
+multiplexer.
+
+## Available methods
+
+""",
+                    ),
                     Gen(
                         "object: Pane",
                         "config/lua/pane",
@@ -239,6 +266,20 @@ introspect the state of the terminal emulation for that pane.
 
 ## Available methods
 
+""",
+                    ),
+                    Gen(
+                        "object: Tab",
+                        "config/lua/tab",
+                        index="""
+# `Tab` object
+
+A Tab object cannot be created in lua code; it is obtained from a
+[Pane](../pane/index.md) using [pane:tab()](../pane/tab.md).  A Tab object
+is a handle to a live instance of a tab that is known to the wezterm process.
+
+## Available methods
+
 """,
                     ),
                     Gen(
@@ -261,6 +302,15 @@ TermWindow running in the wezterm process.
                         index="""
 # Events emitted by the `Window` object
 
+The following events can be handled using [wezterm.on](../wezterm/on.md):
+""",
+                    ),
+                    Gen(
+                        "events: Multiplexer",
+                        "config/lua/mux-events",
+                        index="""
+# Events emitted by the multiplexer
+
 The following events can be handled using [wezterm.on](../wezterm/on.md):
 """,
                     ),
@@ -16,9 +16,10 @@ use leb128;
 use mux::domain::DomainId;
 use mux::pane::PaneId;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
-use mux::tab::{PaneNode, SerdeUrl, SplitDirection, TabId};
+use mux::session::SessionState;
+use mux::tab::{PaneArrangement, PaneNode, SerdeUrl, SplitDirection, TabId};
 use mux::window::WindowId;
-use portable_pty::{CommandBuilder, PtySize};
+use portable_pty::{CommandBuilder, ExitStatus, PtySize};
 use rangeset::*;
 use serde::{Deserialize, Serialize};
 use smol::io::AsyncWriteExt;
@@ -27,6 +28,7 @@ use std::convert::TryInto;
 use std::io::Cursor;
 use std::ops::Range;
 use std::sync::Arc;
+use termwiz::cell::Cell;
 use termwiz::hyperlink::Hyperlink;
 use termwiz::surface::{Line, SequenceNo};
 use varbincode;
@@ -265,13 +267,22 @@ pub struct DecodedPdu {
 /// If the serialized size is larger than this, then we'll consider compressing it
 const COMPRESS_THRESH: usize = 32;
 
-fn serialize<T: serde::Serialize>(t: &T) -> Result<(Vec<u8>, bool), Error> {
+/// Serializes `t`, compressing it if it is larger than `threshold`
+/// and that makes it smaller.
+/// Returns the data, whether it is compressed, and its size before
+/// compression.
+fn serialize<T: serde::Serialize>(
+    t: &T,
+    threshold: Option<usize>,
+) -> Result<(Vec<u8>, bool, usize), Error> {
     let mut uncompressed = Vec::new();
     let mut encode = varbincode::Serializer::new(&mut uncompressed);
     t.serialize(&mut encode)?;
+    let uncompressed_len = uncompressed.len();
 
-    if uncompressed.len() <= COMPRESS_THRESH {
-        return Ok((uncompressed, false));
+    match threshold {
+        Some(threshold) if uncompressed_len > threshold => {}
+        _ => return Ok((uncompressed, false, uncompressed_len)),
     }
     // It's a little heavy; let's try compressing it
     let mut compressed = Vec::new();
@@ -284,13 +295,13 @@ fn serialize<T: serde::Serialize>(t: &T) -> Result<(Vec<u8>, bool), Error> {
     log::debug!(
         "serialized+compress len {} vs {}",
         compressed.len(),
-        uncompressed.len()
+        uncompressed_len
     );
 
-    if compressed.len() < uncompressed.len() {
-        Ok((compressed, true))
+    if compressed.len() < uncompressed_len {
+        Ok((compressed, true, uncompressed_len))
     } else {
-        Ok((uncompressed, false))
+        Ok((uncompressed, false, uncompressed_len))
     }
 }
 
@@ -308,6 +319,87 @@ fn deserialize<T: serde::de::DeserializeOwned, R: std::io::Read>(
     }
 }
 
+/// A PDU that has been serialized, ready to be framed
+struct Serialized {
+    ident: u64,
+    name: &'static str,
+    data: Vec<u8>,
+    is_compressed: bool,
+    /// The size of `data` before compression
+    uncompressed_len: usize,
+}
+
+/// Returns the size of the frame of a PDU
+fn frame_len(ident: u64, serial: u64, data_len: usize, is_compressed: bool) -> usize {
+    let len = data_len + encoded_length(ident) + encoded_length(serial);
+    let masked_len = if is_compressed {
+        (len as u64) | COMPRESSED_MASK
+    } else {
+        len as u64
+    };
+    len + encoded_length(masked_len)
+}
+
+/// The number of bytes of PDUs that have passed over a connection
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct CompressionStats {
+    /// The size of the frames that were sent
+    pub bytes_out: u64,
+    /// The size that those frames would have been without compression
+    pub uncompressed_bytes_out: u64,
+    pub compressed_pdus_out: u64,
+    /// The size of the frames that were received
+    pub bytes_in: u64,
+    /// The size that those frames would have been without compression
+    pub uncompressed_bytes_in: u64,
+    pub compressed_pdus_in: u64,
+}
+
+impl CompressionStats {
+    /// The size of the frames that passed in either direction relative
+    /// to their size without compression, or 1.0 before anything has passed
+    pub fn ratio(&self) -> f64 {
+        let uncompressed = self.uncompressed_bytes_out + self.uncompressed_bytes_in;
+        if uncompressed == 0 {
+            1.0
+        } else {
+            (self.bytes_out + self.bytes_in) as f64 / uncompressed as f64
+        }
+    }
+}
+
+/// Whether the PDUs sent over a connection are compressed, along with
+/// the statistics of the connection.
+/// PDUs are only compressed once the peer has said that it can decode
+/// them, by way of `ClientHello`, which means that peers that predate
+/// it are sent uncompressed PDUs.  Compressed PDUs that are received
+/// are always decoded.
+#[derive(Debug, Clone, Default)]
+pub struct Compression {
+    threshold: Option<usize>,
+    stats: CompressionStats,
+}
+
+impl Compression {
+    /// Compresses the PDUs that are sent from now on, when they
+    /// are large enough for that to be worthwhile
+    pub fn enable(&mut self) {
+        self.threshold = Some(COMPRESS_THRESH);
+    }
+
+    pub fn disable(&mut self) {
+        self.threshold = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold.is_some()
+    }
+
+    pub fn stats(&self) -> CompressionStats {
+        self.stats
+    }
+}
+
 macro_rules! pdu {
     ($( $name:ident:$vers:expr),* $(,)?) => {
         #[derive(PartialEq, Debug)]
@@ -319,62 +411,25 @@ macro_rules! pdu {
         }
 
         impl Pdu {
-            pub fn encode<W: std::io::Write>(&self, w: W, serial: u64) -> Result<(), Error> {
+            fn serialize_pdu(&self, threshold: Option<usize>) -> Result<Serialized, Error> {
                 match self {
                     Pdu::Invalid{..} => bail!("attempted to serialize Pdu::Invalid"),
                     $(
                         Pdu::$name(s) => {
-                            let (data, is_compressed) = serialize(s)?;
-                            let encoded_size = encode_raw($vers, serial, &data, is_compressed, w)?;
-                            metrics::histogram!("pdu.size", encoded_size as f64, "pdu" => stringify!($name));
-                            Ok(())
-                        }
-                    ,)*
-                }
-            }
-
-            pub async fn encode_async<W: Unpin + AsyncWriteExt>(&self, w: &mut W, serial: u64) -> Result<(), Error> {
-                match self {
-                    Pdu::Invalid{..} => bail!("attempted to serialize Pdu::Invalid"),
-                    $(
-                        Pdu::$name(s) => {
-                            let (data, is_compressed) = serialize(s)?;
-                            let encoded_size = encode_raw_async($vers, serial, &data, is_compressed, w).await?;
-                            metrics::histogram!("pdu.size", encoded_size as f64, "pdu" => stringify!($name));
-                            Ok(())
-                        }
-                    ,)*
-                }
-            }
-
-            pub fn decode<R: std::io::Read>(r: R) -> Result<DecodedPdu, Error> {
-                let decoded = decode_raw(r).context("decoding a PDU")?;
-                match decoded.ident {
-                    $(
-                        $vers => {
-                            metrics::histogram!("pdu.size", decoded.data.len() as f64, "pdu" => stringify!($name));
-                            Ok(DecodedPdu {
-                                serial: decoded.serial,
-                                pdu: Pdu::$name(deserialize(decoded.data.as_slice(), decoded.is_compressed)?)
+                            let (data, is_compressed, uncompressed_len) = serialize(s, threshold)?;
+                            Ok(Serialized {
+                                ident: $vers,
+                                name: stringify!($name),
+                                data,
+                                is_compressed,
+                                uncompressed_len,
                             })
                         }
                     ,)*
-                    _ => {
-                        metrics::histogram!("pdu.size", decoded.data.len() as f64, "pdu" => "??");
-                        Ok(DecodedPdu {
-                            serial: decoded.serial,
-                            pdu: Pdu::Invalid{ident:decoded.ident}
-                        })
-                    }
                 }
             }
 
-            pub async fn decode_async<R>(r: &mut R) -> Result<DecodedPdu, Error>
-                where R: std::marker::Unpin,
-                      R: AsyncRead,
-                      R: std::fmt::Debug
-            {
-                let decoded = decode_raw_async(r).await.context("decoding a PDU")?;
+            fn from_decoded(decoded: Decoded) -> Result<DecodedPdu, Error> {
                 match decoded.ident {
                     $(
                         $vers => {
@@ -401,7 +456,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 10;
+pub const CODEC_VERSION: usize = 15;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -440,9 +495,118 @@ pdu! {
     PaneRemoved: 37,
     SetPalette: 38,
     NotifyAlert: 39,
+    GetLogEntries: 40,
+    GetLogEntriesResponse: 41,
+    RearrangePanes: 42,
+    MovePaneToNewTab: 43,
+    MovePaneToNewTabResponse: 44,
+    MovePane: 45,
+    SaveSession: 46,
+    SaveSessionResponse: 47,
+    RestoreSession: 48,
+    MoveTabToWindow: 49,
+    MoveTabToWindowResponse: 50,
+    GetPaneText: 51,
+    GetPaneTextResponse: 52,
+    ActivatePane: 53,
+    ActivatePaneDirection: 54,
+    ActivateTab: 55,
+    ActivatePaneResponse: 56,
+    ListSshForwards: 57,
+    ListSshForwardsResponse: 58,
+    ListTlsCertificates: 59,
+    ListTlsCertificatesResponse: 60,
+    ClientHello: 61,
+    ClientHelloResponse: 62,
+    ListClients: 63,
+    ListClientsResponse: 64,
+    GetPaneRenderDeltaResponse: 65,
 }
 
 impl Pdu {
+    pub fn encode<W: std::io::Write>(&self, w: W, serial: u64) -> Result<(), Error> {
+        let s = self.serialize_pdu(Some(COMPRESS_THRESH))?;
+        let encoded_size = encode_raw(s.ident, serial, &s.data, s.is_compressed, w)?;
+        metrics::histogram!("pdu.size", encoded_size as f64, "pdu" => s.name);
+        Ok(())
+    }
+
+    pub async fn encode_async<W: Unpin + AsyncWriteExt>(
+        &self,
+        w: &mut W,
+        serial: u64,
+    ) -> Result<(), Error> {
+        let s = self.serialize_pdu(Some(COMPRESS_THRESH))?;
+        let encoded_size = encode_raw_async(s.ident, serial, &s.data, s.is_compressed, w).await?;
+        metrics::histogram!("pdu.size", encoded_size as f64, "pdu" => s.name);
+        Ok(())
+    }
+
+    /// Like `encode_async`, but only compresses when `compression` is
+    /// enabled, and records the size of the frame in its stats
+    pub async fn encode_async_with<W: Unpin + AsyncWriteExt>(
+        &self,
+        w: &mut W,
+        serial: u64,
+        compression: &mut Compression,
+    ) -> Result<(), Error> {
+        let s = self.serialize_pdu(compression.threshold)?;
+        let encoded_size = encode_raw_async(s.ident, serial, &s.data, s.is_compressed, w).await?;
+        metrics::histogram!("pdu.size", encoded_size as f64, "pdu" => s.name);
+
+        let stats = &mut compression.stats;
+        stats.bytes_out += encoded_size as u64;
+        stats.uncompressed_bytes_out +=
+            frame_len(s.ident, serial, s.uncompressed_len, false) as u64;
+        if s.is_compressed {
+            stats.compressed_pdus_out += 1;
+        }
+        Ok(())
+    }
+
+    pub fn decode<R: std::io::Read>(r: R) -> Result<DecodedPdu, Error> {
+        let decoded = decode_raw(r).context("decoding a PDU")?;
+        Self::from_decoded(decoded)
+    }
+
+    pub async fn decode_async<R>(r: &mut R) -> Result<DecodedPdu, Error>
+    where
+        R: std::marker::Unpin,
+        R: AsyncRead,
+        R: std::fmt::Debug,
+    {
+        let decoded = decode_raw_async(r).await.context("decoding a PDU")?;
+        Self::from_decoded(decoded)
+    }
+
+    /// Like `decode_async`, but records the size of the frame
+    /// in the stats of `compression`
+    pub async fn decode_async_with<R>(
+        r: &mut R,
+        compression: &mut Compression,
+    ) -> Result<DecodedPdu, Error>
+    where
+        R: std::marker::Unpin,
+        R: AsyncRead,
+        R: std::fmt::Debug,
+    {
+        let mut decoded = decode_raw_async(r).await.context("decoding a PDU")?;
+        let (ident, serial) = (decoded.ident, decoded.serial);
+        let stats = &mut compression.stats;
+        stats.bytes_in +=
+            frame_len(ident, serial, decoded.data.len(), decoded.is_compressed) as u64;
+        if decoded.is_compressed {
+            // Decompress up front, rather than while deserializing,
+            // so that we know how large it was
+            decoded.data =
+                zstd::stream::decode_all(decoded.data.as_slice()).context("decompressing a PDU")?;
+            decoded.is_compressed = false;
+            stats.compressed_pdus_in += 1;
+        }
+        stats.uncompressed_bytes_in += frame_len(ident, serial, decoded.data.len(), false) as u64;
+        Self::from_decoded(decoded)
+    }
+
     pub fn stream_decode(buffer: &mut Vec<u8>) -> anyhow::Result<Option<DecodedPdu>> {
         let mut cursor = Cursor::new(buffer.as_slice());
         match Self::decode(&mut cursor) {
@@ -512,6 +676,10 @@ impl Pdu {
     pub fn pane_id(&self) -> Option<PaneId> {
         match self {
             Pdu::GetPaneRenderChangesResponse(GetPaneRenderChangesResponse { pane_id, .. })
+            | Pdu::GetPaneRenderDeltaResponse(GetPaneRenderDeltaResponse {
+                changes: GetPaneRenderChangesResponse { pane_id, .. },
+                ..
+            })
             | Pdu::SetPalette(SetPalette { pane_id, .. })
             | Pdu::NotifyAlert(NotifyAlert { pane_id, .. })
             | Pdu::SetClipboard(SetClipboard { pane_id, .. })
@@ -704,6 +872,104 @@ pub struct SetPaneZoomed {
     pub zoomed: bool,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RearrangePanes {
+    pub containing_tab_id: TabId,
+    /// The active pane of the tab
+    pub pane_id: PaneId,
+    pub arrangement: PaneArrangement,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneToNewTab {
+    pub pane_id: PaneId,
+    /// If None, move the pane into a new window
+    pub window_id: Option<WindowId>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneToNewTabResponse {
+    pub tab_id: TabId,
+    pub window_id: WindowId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePane {
+    pub pane_id: PaneId,
+    pub tab_id: TabId,
+    pub direction: config::keyassignment::PaneDirection,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MoveTabToWindow {
+    pub tab_id: TabId,
+    /// If None, move the tab into a new window
+    pub window_id: Option<WindowId>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MoveTabToWindowResponse {
+    pub window_id: WindowId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneText {
+    pub pane_id: PaneId,
+    /// The first line, relative to the top of the screen; negative
+    /// numbers address the scrollback.  If None, the top of the screen.
+    pub start_line: Option<isize>,
+    /// The last line.  If None, the bottom of the screen.
+    pub end_line: Option<isize>,
+    /// Include the attributes of the text as SGR escape sequences
+    pub escapes: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneTextResponse {
+    pub text: String,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ActivatePane {
+    pub pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ActivatePaneDirection {
+    pub pane_id: PaneId,
+    pub direction: config::keyassignment::PaneDirection,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ActivateTab {
+    /// The pane whose tab `relative` is relative to
+    pub pane_id: PaneId,
+    pub tab_id: Option<TabId>,
+    pub relative: Option<isize>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ActivatePaneResponse {
+    /// The pane that is now active
+    pub pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SaveSession {
+    /// Record the command line of the foreground process in each pane
+    pub include_commands: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SaveSessionResponse {
+    pub session: SessionState,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RestoreSession {
+    pub session: SessionState,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct GetPaneRenderChanges {
     pub pane_id: PaneId,
@@ -730,6 +996,12 @@ pub struct GetPaneRenderChangesResponse {
 
     pub input_serial: Option<InputSerial>,
     pub seqno: SequenceNo,
+    /// Whether the application is using the alternate screen, where
+    /// the client doesn't predict the echo of what is typed
+    pub is_alt_screen_active: bool,
+    /// The status of the process, if it has exited and the pane
+    /// is being held open by `exit_behavior`
+    pub exit_status: Option<ExitStatus>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -887,6 +1159,285 @@ pub struct SearchScrollbackResponse {
     pub results: Vec<mux::pane::SearchResult>,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetLogEntries {
+    /// Only return entries with a sequence number greater than this;
+    /// 0 returns all retained entries
+    pub since_seq: u64,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetLogEntriesResponse {
+    pub entries: Vec<env_bootstrap::ringlog::Entry>,
+    /// Pass this as `since_seq` to resume from this point
+    pub latest_seq: u64,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListSshForwards {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListSshForwardsResponse {
+    pub forwards: Vec<mux::sshforward::SshForwardStatus>,
+}
+
+/// The certificate that a TLS listener of the mux server presents
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct TlsCertificateStatus {
+    pub bind_address: String,
+    pub cert_path: String,
+    /// The CN of the certificate
+    pub subject: String,
+    pub not_after: String,
+    /// Negative once the certificate has expired
+    pub days_remaining: i64,
+    /// Whether the certificate expires within the warning period
+    /// of the listener
+    pub expiring: bool,
+    /// Set when the files changed but could not be loaded, in which
+    /// case the listener keeps presenting the previous certificate
+    pub reload_error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListTlsCertificates {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListTlsCertificatesResponse {
+    pub certificates: Vec<TlsCertificateStatus>,
+}
+
+/// Sent by clients when they connect, to say who they are and which
+/// optional parts of the protocol they understand.
+/// Servers that predate this reply with an `ErrorResponse`, in which
+/// case the client carries on without it; this doesn't need a new
+/// `CODEC_VERSION` for that reason.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ClientHello {
+    pub username: String,
+    pub hostname: String,
+    pub pid: u32,
+    /// Whether the client would like PDUs to be compressed
+    pub compression: bool,
+    /// Whether the client can apply `GetPaneRenderDeltaResponse`
+    pub delta_lines: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ClientHelloResponse {
+    /// Whether the PDUs are compressed in both directions from now on
+    pub compression: bool,
+    /// Whether the server will send `GetPaneRenderDeltaResponse`
+    /// rather than `GetPaneRenderChangesResponse` for pushed changes
+    pub delta_lines: bool,
+}
+
+/// A connection to the mux server
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct ClientInfo {
+    pub connection_id: usize,
+    /// Who is connected, as told by the `ClientHello` of the client;
+    /// empty for clients that don't send one
+    pub username: String,
+    pub hostname: String,
+    pub pid: u32,
+    /// How long ago the client connected
+    pub connected_secs: u64,
+    /// How long ago a PDU was last received from the client
+    pub idle_secs: u64,
+    pub compression: bool,
+    pub stats: CompressionStats,
+    /// See `CompressionStats::ratio`
+    pub compression_ratio: f64,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListClients {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListClientsResponse {
+    pub clients: Vec<ClientInfo>,
+}
+
+/// A run of cells that replace those of a line, starting at column `start`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellRange {
+    pub start: usize,
+    pub cells: Vec<Cell>,
+}
+
+/// How the line at `row` changed since the client was sent the
+/// version that it knows as `base_generation`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineDelta {
+    pub row: StableRowIndex,
+    pub base_generation: u64,
+    /// The generation of the line once the delta has been applied
+    pub generation: u64,
+    /// The number of cells in the line once the delta has been applied
+    pub len: usize,
+    pub ranges: Vec<CellRange>,
+}
+
+/// Unchanged cells between two changed ones that are resent rather
+/// than starting another range, as a range costs about as much
+const DELTA_MERGE_GAP: usize = 2;
+
+impl LineDelta {
+    /// Computes the delta that turns `old` into `new`.
+    /// Returns None if the lines differ in a way that can't be expressed
+    /// in terms of their cells, in which case the whole line must be sent.
+    pub fn compute(
+        row: StableRowIndex,
+        base_generation: u64,
+        generation: u64,
+        old: &Line,
+        new: &Line,
+    ) -> Option<Self> {
+        if old.is_reverse() != new.is_reverse()
+            || old.is_double_width() != new.is_double_width()
+            || old.is_double_height_top() != new.is_double_height_top()
+            || old.is_double_height_bottom() != new.is_double_height_bottom()
+            || old.has_hyperlink() != new.has_hyperlink()
+        {
+            return None;
+        }
+
+        let old_cells = old.cells();
+        let new_cells = new.cells();
+        let unchanged = |x: usize| old_cells.get(x) == Some(&new_cells[x]);
+
+        let mut ranges = vec![];
+        let mut x = 0;
+        while x < new_cells.len() {
+            if unchanged(x) {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            let mut end = x + 1;
+            let mut probe = end;
+            while probe < new_cells.len() && probe - end <= DELTA_MERGE_GAP {
+                if !unchanged(probe) {
+                    end = probe + 1;
+                }
+                probe += 1;
+            }
+            ranges.push(CellRange {
+                start,
+                cells: new_cells[start..end].to_vec(),
+            });
+            x = end;
+        }
+
+        Some(Self {
+            row,
+            base_generation,
+            generation,
+            len: new_cells.len(),
+            ranges,
+        })
+    }
+
+    /// Applies the delta to the line of generation `base_generation`
+    pub fn apply(&self, line: &mut Line, seqno: SequenceNo) {
+        line.invalidate_implicit_hyperlinks(seqno);
+        line.resize(self.len, seqno);
+        let cells = line.cells_mut_for_attr_changes_only();
+        for range in &self.ranges {
+            // The cells are stored as they are, rather than by set_cell,
+            // which would blank out the cells that follow wide ones; the
+            // delta already holds those as they are on the server
+            for (cell, new) in cells.iter_mut().skip(range.start).zip(range.cells.iter()) {
+                *cell = new.clone();
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+struct LineDeltaHeader {
+    row: StableRowIndex,
+    base_generation: u64,
+    generation: u64,
+    len: usize,
+    starts: Vec<usize>,
+}
+
+/// The serialized form of a set of `LineDelta`s.  The cells of each range
+/// are carried as a line of `SerializedLines`, so that their hyperlinks
+/// are sent the same way as those of whole lines.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SerializedLineDeltas {
+    headers: Vec<LineDeltaHeader>,
+    cells: SerializedLines,
+}
+
+impl SerializedLineDeltas {
+    pub fn deltas(self) -> Vec<LineDelta> {
+        let mut cells = self.cells.lines().into_iter();
+        self.headers
+            .into_iter()
+            .map(|header| LineDelta {
+                row: header.row,
+                base_generation: header.base_generation,
+                generation: header.generation,
+                len: header.len,
+                ranges: header
+                    .starts
+                    .into_iter()
+                    .map(|start| CellRange {
+                        start,
+                        cells: cells
+                            .next()
+                            .map(|(_, line)| line.cells().to_vec())
+                            .unwrap_or_default(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<LineDelta>> for SerializedLineDeltas {
+    fn from(deltas: Vec<LineDelta>) -> Self {
+        let mut headers = vec![];
+        let mut cells = vec![];
+        for delta in deltas {
+            let mut starts = vec![];
+            for range in delta.ranges {
+                starts.push(range.start);
+                cells.push((delta.row, Line::from_cells(range.cells)));
+            }
+            headers.push(LineDeltaHeader {
+                row: delta.row,
+                base_generation: delta.base_generation,
+                generation: delta.generation,
+                len: delta.len,
+                starts,
+            });
+        }
+        Self {
+            headers,
+            cells: cells.into(),
+        }
+    }
+}
+
+/// Sent in place of `GetPaneRenderChangesResponse` to clients that
+/// negotiated `delta_lines` in their `ClientHello`.
+/// Every line of the viewport that the client doesn't already have the
+/// current version of is either in `changes.bonus_lines`, or is described
+/// by one of `deltas` relative to the generation that it was last sent.
+/// Lines that are in neither are unchanged since they were last sent.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneRenderDeltaResponse {
+    pub changes: GetPaneRenderChangesResponse,
+    /// The generations of the lines in `changes.bonus_lines`
+    pub generations: Vec<(StableRowIndex, u64)>,
+    pub deltas: SerializedLineDeltas,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1007,4 +1558,154 @@ mod test {
             Pdu::decode(encoded.as_slice()).unwrap()
         );
     }
+
+    fn compressible() -> Pdu {
+        Pdu::ErrorResponse(ErrorResponse {
+            reason: "compress me ".repeat(1000),
+        })
+    }
+
+    /// Hands out its data a few bytes at a time, as a TCP
+    /// connection might
+    #[derive(Debug)]
+    struct Trickle {
+        data: Vec<u8>,
+        pos: usize,
+        chunk: usize,
+    }
+
+    impl AsyncRead for Trickle {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let n = buf.len().min(self.chunk).min(self.data.len() - self.pos);
+            let pos = self.pos;
+            buf[..n].copy_from_slice(&self.data[pos..pos + n]);
+            self.pos += n;
+            std::task::Poll::Ready(Ok(n))
+        }
+    }
+
+    #[test]
+    fn compressed_frames_split_across_reads() {
+        let mut sent = Compression::default();
+        sent.enable();
+        let mut encoded = vec![];
+        smol::block_on(async {
+            compressible()
+                .encode_async_with(&mut encoded, 1, &mut sent)
+                .await
+                .unwrap();
+            Pdu::Ping(Ping {})
+                .encode_async_with(&mut encoded, 2, &mut sent)
+                .await
+                .unwrap();
+        });
+        let stats = sent.stats();
+        assert_eq!(stats.compressed_pdus_out, 1);
+        assert_eq!(stats.bytes_out, encoded.len() as u64);
+        assert!(stats.uncompressed_bytes_out > 10 * stats.bytes_out);
+
+        let expected = vec![
+            DecodedPdu {
+                serial: 1,
+                pdu: compressible(),
+            },
+            DecodedPdu {
+                serial: 2,
+                pdu: Pdu::Ping(Ping {}),
+            },
+        ];
+
+        for &chunk in &[1, 3, 7, 4096] {
+            let mut reader = Trickle {
+                data: encoded.clone(),
+                pos: 0,
+                chunk,
+            };
+            let mut received = Compression::default();
+            let decoded = smol::block_on(async {
+                let mut decoded = vec![];
+                for _ in 0..2 {
+                    decoded.push(
+                        Pdu::decode_async_with(&mut reader, &mut received)
+                            .await
+                            .unwrap(),
+                    );
+                }
+                decoded
+            });
+            assert_eq!(decoded, expected);
+            let received = received.stats();
+            assert_eq!(received.bytes_in, stats.bytes_out);
+            assert_eq!(received.uncompressed_bytes_in, stats.uncompressed_bytes_out);
+            assert_eq!(received.compressed_pdus_in, 1);
+
+            let mut buffer = vec![];
+            let mut decoded = vec![];
+            for piece in encoded.chunks(chunk) {
+                buffer.extend_from_slice(piece);
+                while let Some(pdu) = Pdu::stream_decode(&mut buffer).unwrap() {
+                    decoded.push(pdu);
+                }
+            }
+            assert_eq!(decoded, expected);
+            assert!(buffer.is_empty());
+        }
+    }
+
+    #[test]
+    fn uncompressed_until_enabled() {
+        let mut compression = Compression::default();
+        let mut encoded = vec![];
+        smol::block_on(compressible().encode_async_with(&mut encoded, 1, &mut compression))
+            .unwrap();
+        let stats = compression.stats();
+        assert_eq!(stats.compressed_pdus_out, 0);
+        assert_eq!(stats.bytes_out, stats.uncompressed_bytes_out);
+        // Which peers that don't know about compression can decode
+        let decoded = decode_raw(encoded.as_slice()).unwrap();
+        assert!(!decoded.is_compressed);
+        assert_eq!(Pdu::decode(encoded.as_slice()).unwrap().pdu, compressible());
+    }
+
+    #[test]
+    fn line_delta() {
+        use termwiz::cell::CellAttributes;
+
+        let plain = CellAttributes::default();
+        let mut linked = plain.clone();
+        linked.set_hyperlink(Some(Arc::new(Hyperlink::new("https://example.com"))));
+
+        let mut old = Line::from_text("hello world, see the docs", &plain);
+        old.overlay_text_with_attribute(21, "docs", linked.clone(), 1);
+        let mut new = old.clone();
+        new.overlay_text_with_attribute(0, "J", plain.clone(), 2);
+        new.overlay_text_with_attribute(6, "世界", linked, 2);
+        new.resize(30, 2);
+
+        let delta = LineDelta::compute(4, 1, 2, &old, &new).unwrap();
+        assert_eq!(delta.len, 30);
+        assert_eq!(delta.ranges.len(), 3);
+        let delta = SerializedLineDeltas::from(vec![delta.clone()])
+            .deltas()
+            .pop()
+            .unwrap();
+        let mut patched = old.clone();
+        delta.apply(&mut patched, 2);
+        assert_eq!(patched.cells(), new.cells());
+
+        let short = Line::from_text("hi", &plain);
+        let plain_old = Line::from_text("hello", &plain);
+        let delta = LineDelta::compute(4, 1, 2, &plain_old, &short).unwrap();
+        let mut patched = plain_old.clone();
+        delta.apply(&mut patched, 2);
+        assert_eq!(patched.cells(), short.cells());
+
+        let mut reversed = plain_old.clone();
+        reversed.set_reverse(true, 2);
+        assert!(LineDelta::compute(4, 1, 2, &plain_old, &reversed).is_none());
+    }
 }
@@ -50,13 +50,23 @@ pub struct VisualBell {
     pub fade_out_function: EasingFunction,
     #[serde(default)]
     pub target: VisualBellTarget,
+    /// The color to flash; overrides `colors.visual_bell`
+    #[serde(default)]
+    pub color: Option<RgbColor>,
 }
 impl_lua_conversion!(VisualBell);
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub enum VisualBellTarget {
+    /// Flash the background of the pane that rang the bell
+    #[serde(alias = "Pane")]
     BackgroundColor,
+    /// Flash the cursor of the pane that rang the bell
     CursorColor,
+    /// Flash the background of the whole window
+    Window,
+    /// Tint the tab that contains the pane that rang the bell
+    TabBarOnly,
 }
 impl_lua_conversion!(VisualBellTarget);
 
@@ -4,7 +4,7 @@ use luahelper::impl_lua_conversion;
 use termwiz::cell::CellAttributes;
 pub use termwiz::color::{ColorSpec, RgbColor};
 
-#[derive(Debug, Copy, Deserialize, Serialize, Clone)]
+#[derive(Debug, Copy, Deserialize, Serialize, Clone, PartialEq)]
 pub struct HsbTransform {
     #[serde(default = "default_one_point_oh")]
     pub hue: f32,
@@ -24,6 +24,19 @@ impl Default for HsbTransform {
     }
 }
 
+impl HsbTransform {
+    /// Returns a transform that is `amount` of the way from the
+    /// identity transform (0.0) to this one (1.0)
+    pub fn interpolate(&self, amount: f32) -> Self {
+        let lerp = |value: f32| 1. + (value - 1.) * amount;
+        Self {
+            hue: lerp(self.hue),
+            saturation: lerp(self.saturation),
+            brightness: lerp(self.brightness),
+        }
+    }
+}
+
 fn de_indexed<'de, D>(deserializer: D) -> Result<HashMap<u8, RgbColor>, D::Error>
 where
     D: Deserializer<'de>,
@@ -71,6 +84,9 @@ pub struct Palette {
     /// The color of the "thumb" of the scrollbar; the segment that
     /// represents the current viewable area
     pub scrollbar_thumb: Option<RgbColor>,
+    /// The color of the scrollbar thumb while the mouse is over it,
+    /// or it is being dragged.  If unspecified, scrollbar_thumb is used.
+    pub scrollbar_thumb_hover: Option<RgbColor>,
     /// The color of the split line between panes
     pub split: Option<RgbColor>,
     /// The color of the visual bell. If unspecified, the foreground
@@ -151,6 +167,41 @@ impl TabBarColor {
     }
 }
 
+/// Specifies the styling of the text that is being composed by an
+/// input method, prior to it being committed to the pane.
+/// Colors that are not specified are taken from the pane, so that
+/// the preedit text blends in with the color scheme.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ImePreeditColors {
+    /// The forgeground/text color for the preedit text
+    #[serde(default)]
+    pub fg_color: Option<RgbColor>,
+    /// The background color for the preedit text
+    #[serde(default)]
+    pub bg_color: Option<RgbColor>,
+    /// The color of the underline that marks the preedit text
+    #[serde(default)]
+    pub underline_color: Option<RgbColor>,
+}
+impl_lua_conversion!(ImePreeditColors);
+
+impl ImePreeditColors {
+    pub fn as_cell_attributes(&self) -> CellAttributes {
+        let mut attr = CellAttributes::default();
+        attr.set_underline(wezterm_term::Underline::Single);
+        if let Some(fg) = self.fg_color {
+            attr.set_foreground(ColorSpec::TrueColor(fg));
+        }
+        if let Some(bg) = self.bg_color {
+            attr.set_background(ColorSpec::TrueColor(bg));
+        }
+        if let Some(underline) = self.underline_color {
+            attr.set_underline_color(ColorSpec::TrueColor(underline));
+        }
+        attr
+    }
+}
+
 /// Specifies the colors to use for the tab bar portion of the UI.
 /// These are not part of the terminal model and cannot be updated
 /// in the same way that the dynamic color schemes are.
@@ -307,6 +358,7 @@ fn default_title_font() -> TextStyle {
     TextStyle {
         foreground: None,
         font: fonts,
+        harfbuzz_features: None,
     }
 }
 
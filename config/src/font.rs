@@ -297,6 +297,76 @@ impl FreeTypeLoadFlags {
     }
 }
 
+/// An inclusive range of codepoints, written as eg: `"U+0900-U+097F"`,
+/// or `"U+2600"` for a single codepoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CodepointRange {
+    pub first: u32,
+    pub last: u32,
+}
+
+impl CodepointRange {
+    pub fn contains(&self, c: char) -> bool {
+        let c = c as u32;
+        c >= self.first && c <= self.last
+    }
+}
+
+impl std::str::FromStr for CodepointRange {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        fn codepoint(s: &str) -> Option<u32> {
+            let s = s.trim();
+            let hex = s
+                .strip_prefix("U+")
+                .or_else(|| s.strip_prefix("u+"))
+                .unwrap_or(s);
+            u32::from_str_radix(hex, 16).ok()
+        }
+
+        let (first, last) = match s.find('-') {
+            Some(idx) => (codepoint(&s[..idx]), codepoint(&s[idx + 1..])),
+            None => (codepoint(s), codepoint(s)),
+        };
+        match (first, last) {
+            (Some(first), Some(last)) if first <= last => Ok(Self { first, last }),
+            _ => Err(format!(
+                "invalid codepoint range {:?}; expected something like \"U+0900-U+097F\"",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for CodepointRange {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.first == self.last {
+            write!(fmt, "U+{:04X}", self.first)
+        } else {
+            write!(fmt, "U+{:04X}-U+{:04X}", self.first, self.last)
+        }
+    }
+}
+
+impl Serialize for CodepointRange {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for CodepointRange {
+    fn deserialize<D>(deserializer: D) -> Result<CodepointRange, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct FontAttributes {
     /// The font family name
@@ -311,6 +381,28 @@ pub struct FontAttributes {
     pub italic: bool,
     pub is_fallback: bool,
     pub is_synthetic: bool,
+    /// Whether bold may be emulated by emboldening the glyphs of
+    /// a font that has no bold variant.  `None` applies the default
+    /// heuristic; `Some(true)` emboldens whenever the matched font
+    /// is lighter than the requested bold weight, and `Some(false)`
+    /// never emboldens.
+    #[serde(default)]
+    pub synthesize_bold: Option<bool>,
+    /// Whether italic may be emulated by shearing the glyphs of
+    /// a font that has no italic variant.  `None` and `Some(true)`
+    /// shear when italic is requested but the matched font is
+    /// upright; `Some(false)` never shears.
+    #[serde(default)]
+    pub synthesize_italic: Option<bool>,
+    /// If non-empty, this font is only used for codepoints that
+    /// fall within these ranges
+    #[serde(default)]
+    pub ranges: Vec<CodepointRange>,
+    /// If non-empty, this font is only used for codepoints that
+    /// belong to these scripts, specified using their ISO 15924
+    /// names, eg: `"Deva"`
+    #[serde(default)]
+    pub scripts: Vec<String>,
 }
 impl_lua_conversion!(FontAttributes);
 
@@ -333,6 +425,10 @@ impl FontAttributes {
             italic: false,
             is_fallback: false,
             is_synthetic: false,
+            synthesize_bold: None,
+            synthesize_italic: None,
+            ranges: vec![],
+            scripts: vec![],
         }
     }
 
@@ -344,6 +440,10 @@ impl FontAttributes {
             italic: false,
             is_fallback: true,
             is_synthetic: false,
+            synthesize_bold: None,
+            synthesize_italic: None,
+            ranges: vec![],
+            scripts: vec![],
         }
     }
 }
@@ -357,6 +457,10 @@ impl Default for FontAttributes {
             italic: false,
             is_fallback: false,
             is_synthetic: false,
+            synthesize_bold: None,
+            synthesize_italic: None,
+            ranges: vec![],
+            scripts: vec![],
         }
     }
 }
@@ -372,6 +476,11 @@ pub struct TextStyle {
     /// useful in a `[[font_rules]]` section to implement changing
     /// the text color for eg: bold text.
     pub foreground: Option<RgbColor>,
+
+    /// If set, these features are used in place of the global
+    /// `harfbuzz_features` when shaping text in this style.
+    #[serde(default)]
+    pub harfbuzz_features: Option<Vec<String>>,
 }
 impl_lua_conversion!(TextStyle);
 
@@ -380,6 +489,7 @@ impl Default for TextStyle {
         Self {
             foreground: None,
             font: vec![FontAttributes::default()],
+            harfbuzz_features: None,
         }
     }
 }
@@ -430,6 +540,7 @@ impl TextStyle {
         }
         Self {
             foreground: self.foreground,
+            harfbuzz_features: self.harfbuzz_features.clone(),
             font: self
                 .font
                 .iter()
@@ -449,6 +560,7 @@ impl TextStyle {
     pub fn make_bold(&self) -> Self {
         Self {
             foreground: self.foreground,
+            harfbuzz_features: self.harfbuzz_features.clone(),
             font: self
                 .font
                 .iter()
@@ -465,6 +577,7 @@ impl TextStyle {
     pub fn make_half_bright(&self) -> Self {
         Self {
             foreground: self.foreground,
+            harfbuzz_features: self.harfbuzz_features.clone(),
             font: self
                 .font
                 .iter()
@@ -482,6 +595,7 @@ impl TextStyle {
     pub fn make_italic(&self) -> Self {
         Self {
             foreground: self.foreground,
+            harfbuzz_features: self.harfbuzz_features.clone(),
             font: self
                 .font
                 .iter()
@@ -564,6 +678,31 @@ pub struct StyleRule {
 }
 impl_lua_conversion!(StyleRule);
 
+/// Overrides `harfbuzz_features` for a pane while a particular
+/// program is its foreground process
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct HarfbuzzFeaturesRule {
+    /// The name of the program, eg: "vim", or the full path to it
+    pub foreground_process_name: String,
+    /// The features to use in place of `harfbuzz_features`
+    pub harfbuzz_features: Vec<String>,
+}
+impl_lua_conversion!(HarfbuzzFeaturesRule);
+
+impl HarfbuzzFeaturesRule {
+    /// Returns true if the rule matches the full path of a process
+    pub fn matches(&self, process_path: &str) -> bool {
+        if self.foreground_process_name == process_path {
+            return true;
+        }
+        // Also match eg: "vim" against "/usr/bin/vim" or "vim.exe"
+        let name = Some(self.foreground_process_name.as_str());
+        let path = std::path::Path::new(process_path);
+        path.file_name().and_then(|n| n.to_str()) == name
+            || path.file_stem().and_then(|n| n.to_str()) == name
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum AllowSquareGlyphOverflow {
     Never,
@@ -703,9 +842,31 @@ mod test {
             let style = TextStyle {
                 font: vec![FontAttributes::new(family)],
                 foreground: None,
+                harfbuzz_features: None,
             };
             let style = style.reduce_first_font_to_family();
             assert_eq!(style.font[0].family, "Inconsolata");
         }
     }
+
+    #[test]
+    fn codepoint_range() {
+        let range: CodepointRange = "U+0900-U+097F".parse().unwrap();
+        assert_eq!(
+            range,
+            CodepointRange {
+                first: 0x900,
+                last: 0x97f
+            }
+        );
+        assert!(range.contains('\u{0915}'));
+        assert!(!range.contains('a'));
+        assert_eq!(range.to_string(), "U+0900-U+097F");
+
+        let single: CodepointRange = "u+2600".parse().unwrap();
+        assert_eq!(single.to_string(), "U+2600");
+
+        assert!("U+097F-U+0900".parse::<CodepointRange>().is_err());
+        assert!("Devanagari".parse::<CodepointRange>().is_err());
+    }
 }
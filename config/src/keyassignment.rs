@@ -7,6 +7,7 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use wezterm_input_types::{KeyCode, Modifiers};
 use wezterm_term::input::MouseButton;
+use wezterm_term::SemanticType;
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum SelectionMode {
@@ -103,6 +104,7 @@ pub struct SpawnCommand {
     #[serde(default)]
     pub domain: SpawnTabDomain,
 }
+impl_lua_conversion!(SpawnCommand);
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
 pub enum PaneDirection {
@@ -112,6 +114,41 @@ pub enum PaneDirection {
     Right,
 }
 
+/// The preset arrangements of the panes in a tab, named after their
+/// tmux equivalents
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PaneLayout {
+    /// The active pane occupies the left half of the tab, and the
+    /// other panes are stacked in the right half
+    #[serde(rename = "main-vertical")]
+    MainVertical,
+    /// The panes are arranged in a grid
+    #[serde(rename = "tiled")]
+    Tiled,
+    /// The panes are arranged side by side
+    #[serde(rename = "even-horizontal")]
+    EvenHorizontal,
+}
+
+/// Which panes receive the input that is broadcast by `ToggleInputBroadcast`
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum BroadcastScope {
+    /// All of the panes in the active tab
+    Tab,
+    /// All of the panes in all of the tabs of the window
+    Window,
+    /// The panes that were added to the broadcast individually;
+    /// toggling adds or removes the active pane
+    Selected,
+}
+
+/// Which way to move the panes of a tab through their positions
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum RotationDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ScrollbackEraseMode {
     ScrollbackOnly,
@@ -124,11 +161,13 @@ impl Default for ScrollbackEraseMode {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ClipboardCopyDestination {
     Clipboard,
     PrimarySelection,
     ClipboardAndPrimarySelection,
+    /// A named register that belongs to the window
+    Register(String),
 }
 
 impl Default for ClipboardCopyDestination {
@@ -137,10 +176,12 @@ impl Default for ClipboardCopyDestination {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ClipboardPasteSource {
     Clipboard,
     PrimarySelection,
+    /// A named register that belongs to the window
+    Register(String),
 }
 
 impl Default for ClipboardPasteSource {
@@ -176,6 +217,7 @@ pub enum KeyAssignment {
     ReloadConfiguration,
     MoveTabRelative(isize),
     MoveTab(usize),
+    MoveTabToNewWindow,
     ScrollByPage(isize),
     ScrollByLine(isize),
     ScrollToPrompt(isize),
@@ -183,6 +225,8 @@ pub enum KeyAssignment {
     ScrollToBottom,
     ShowTabNavigator,
     ShowDebugOverlay,
+    ShowKeyDebugOverlay,
+    ListKeyBindings,
     HideApplication,
     QuitApplication,
     SpawnCommandInNewTab(SpawnCommand),
@@ -190,6 +234,9 @@ pub enum KeyAssignment {
     SplitHorizontal(SpawnCommand),
     SplitVertical(SpawnCommand),
     ShowLauncher,
+    ShowDomainStatus,
+    AttachDomain(String),
+    DetachDomain(SpawnTabDomain),
     ClearScrollback(ScrollbackEraseMode),
     Search(Pattern),
     ActivateCopyMode,
@@ -203,10 +250,27 @@ pub enum KeyAssignment {
 
     AdjustPaneSize(PaneDirection, usize),
     ActivatePaneDirection(PaneDirection),
+    ActivateLastPane,
     TogglePaneZoomState,
+    BalancePanes,
+    ApplyPaneLayout(PaneLayout),
+    RotatePanes(RotationDirection),
+    SwapActivePaneWithIndex(usize),
+    ToggleInputBroadcast { scope: BroadcastScope },
+    MovePaneToNewTab,
+    MovePaneToNewWindow,
+    MovePane { tab_id: usize, direction: PaneDirection },
     CloseCurrentPane { confirm: bool },
     EmitEvent(String),
     QuickSelect,
+    ActivateCommandPalette,
+    StartKeyRecording(String),
+    StopKeyRecording,
+    ReplayKeyRecording(String),
+    RepeatLastAssignment,
+    MoveBackwardSemanticZone(SemanticType),
+    MoveForwardSemanticZone(SemanticType),
+    SelectSemanticZone(SemanticType),
 
     Multiple(Vec<KeyAssignment>),
 }
@@ -214,8 +278,14 @@ impl_lua_conversion!(KeyAssignment);
 
 pub struct InputMap {
     keys: HashMap<(KeyCode, Modifiers), KeyAssignment>,
+    keys_up: HashMap<(KeyCode, Modifiers), KeyAssignment>,
     mouse: HashMap<(MouseEventTrigger, Modifiers), KeyAssignment>,
     leader: Option<LeaderKey>,
+    /// The descriptions of the bindings that have them, keyed
+    /// in the same way as `keys`, `keys_up` and `mouse`
+    key_descriptions: HashMap<(KeyCode, Modifiers), String>,
+    key_up_descriptions: HashMap<(KeyCode, Modifiers), String>,
+    mouse_descriptions: HashMap<(MouseEventTrigger, Modifiers), String>,
 }
 
 impl InputMap {
@@ -223,9 +293,32 @@ impl InputMap {
         let mut mouse = config.mouse_bindings();
 
         let mut keys = config.key_bindings();
+        let keys_up = config.key_up_bindings();
 
         let leader = config.leader.clone();
 
+        let mut key_descriptions = HashMap::new();
+        let mut key_up_descriptions = HashMap::new();
+        for k in &config.keys {
+            if let Some(description) = &k.description {
+                let descriptions = if k.key_up {
+                    &mut key_up_descriptions
+                } else {
+                    &mut key_descriptions
+                };
+                descriptions.insert(k.key.normalize_shift(k.mods), description.clone());
+            }
+        }
+        let mouse_descriptions = config
+            .mouse_bindings
+            .iter()
+            .filter_map(|m| {
+                m.description
+                    .as_ref()
+                    .map(|description| ((m.event.clone(), m.mods), description.clone()))
+            })
+            .collect();
+
         macro_rules! k {
             ($([$mod:expr, $code:expr, $action:expr]),* $(,)?) => {
                 $(
@@ -309,6 +402,7 @@ impl InputMap {
                 ],
                 [Modifiers::CTRL, KeyCode::Char('L'), ShowDebugOverlay],
                 [ctrl_shift, KeyCode::Char(' '), QuickSelect],
+                [Modifiers::CTRL, KeyCode::Char('P'), ActivateCommandPalette],
                 // Font size manipulation
                 [Modifiers::CTRL, KeyCode::Char('-'), DecreaseFontSize],
                 [Modifiers::CTRL, KeyCode::Char('0'), ResetFontSize],
@@ -578,8 +672,12 @@ impl InputMap {
 
         Self {
             keys,
+            keys_up,
             leader,
             mouse,
+            key_descriptions,
+            key_up_descriptions,
+            mouse_descriptions,
         }
     }
 
@@ -598,12 +696,71 @@ impl InputMap {
         mods - (Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT)
     }
 
+    /// Returns all of the key assignments, including the defaults
+    pub fn key_assignments(
+        &self,
+    ) -> impl Iterator<Item = (&KeyCode, Modifiers, &KeyAssignment)> + '_ {
+        self.keys
+            .iter()
+            .map(|((key, mods), action)| (key, *mods, action))
+    }
+
+    /// Returns all of the assignments that are performed when a key
+    /// is released
+    pub fn key_up_assignments(
+        &self,
+    ) -> impl Iterator<Item = (&KeyCode, Modifiers, &KeyAssignment)> + '_ {
+        self.keys_up
+            .iter()
+            .map(|((key, mods), action)| (key, *mods, action))
+    }
+
+    /// Returns all of the mouse assignments, including the defaults
+    pub fn mouse_assignments(
+        &self,
+    ) -> impl Iterator<Item = (&MouseEventTrigger, Modifiers, &KeyAssignment)> + '_ {
+        self.mouse
+            .iter()
+            .map(|((event, mods), action)| (event, *mods, action))
+    }
+
+    /// Returns the description that the user gave to the binding
+    /// of key, as listed by `key_assignments`, if any
+    pub fn key_description(&self, key: &KeyCode, mods: Modifiers) -> Option<&str> {
+        self.key_descriptions
+            .get(&(key.clone(), mods))
+            .map(String::as_str)
+    }
+
+    /// Returns the description that the user gave to the release
+    /// binding of key, as listed by `key_up_assignments`, if any
+    pub fn key_up_description(&self, key: &KeyCode, mods: Modifiers) -> Option<&str> {
+        self.key_up_descriptions
+            .get(&(key.clone(), mods))
+            .map(String::as_str)
+    }
+
+    /// Returns the description that the user gave to the mouse
+    /// binding, as listed by `mouse_assignments`, if any
+    pub fn mouse_description(&self, event: &MouseEventTrigger, mods: Modifiers) -> Option<&str> {
+        self.mouse_descriptions
+            .get(&(event.clone(), mods))
+            .map(String::as_str)
+    }
+
     pub fn lookup_key(&self, key: &KeyCode, mods: Modifiers) -> Option<KeyAssignment> {
         self.keys
             .get(&key.normalize_shift(Self::remove_positional_alt(mods)))
             .cloned()
     }
 
+    /// Returns the assignment to perform when the key is released
+    pub fn lookup_key_up(&self, key: &KeyCode, mods: Modifiers) -> Option<KeyAssignment> {
+        self.keys_up
+            .get(&key.normalize_shift(Self::remove_positional_alt(mods)))
+            .cloned()
+    }
+
     pub fn lookup_mouse(&self, event: MouseEventTrigger, mods: Modifiers) -> Option<KeyAssignment> {
         self.mouse
             .get(&(event, Self::remove_positional_alt(mods)))
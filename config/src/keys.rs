@@ -1,33 +1,53 @@
 use crate::{KeyAssignment, MouseEventTrigger};
 use luahelper::impl_lua_conversion;
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 use wezterm_input_types::{KeyCode, Modifiers};
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct KeyNoAction {
-    #[serde(deserialize_with = "de_keycode")]
+    #[serde(deserialize_with = "de_keycode", serialize_with = "ser_keycode")]
     pub key: KeyCode,
-    #[serde(deserialize_with = "de_modifiers", default)]
+    #[serde(
+        deserialize_with = "de_modifiers",
+        serialize_with = "ser_modifiers",
+        default
+    )]
     pub mods: Modifiers,
 }
 impl_lua_conversion!(KeyNoAction);
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Key {
-    #[serde(deserialize_with = "de_keycode")]
+    #[serde(deserialize_with = "de_keycode", serialize_with = "ser_keycode")]
     pub key: KeyCode,
-    #[serde(deserialize_with = "de_modifiers", default)]
+    #[serde(
+        deserialize_with = "de_modifiers",
+        serialize_with = "ser_modifiers",
+        default
+    )]
     pub mods: Modifiers,
     pub action: KeyAssignment,
+    /// If true, the action is performed when the key is released,
+    /// rather than when it is pressed
+    #[serde(default)]
+    pub key_up: bool,
+    /// Describes what the binding does, for display in the command
+    /// palette and the list of key bindings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 impl_lua_conversion!(Key);
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LeaderKey {
-    #[serde(deserialize_with = "de_keycode")]
+    #[serde(deserialize_with = "de_keycode", serialize_with = "ser_keycode")]
     pub key: KeyCode,
-    #[serde(deserialize_with = "de_modifiers", default)]
+    #[serde(
+        deserialize_with = "de_modifiers",
+        serialize_with = "ser_modifiers",
+        default
+    )]
     pub mods: Modifiers,
     #[serde(default = "default_leader_timeout")]
     pub timeout_milliseconds: u64,
@@ -38,12 +58,54 @@ fn default_leader_timeout() -> u64 {
     1000
 }
 
+/// Controls whether dead keys are used to compose characters.
+/// This is either a boolean that applies to every keyboard layout,
+/// or a table that maps keyboard layout names to booleans, where
+/// the `default` entry, if any, applies to layouts that are not
+/// otherwise listed.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum UseDeadKeys {
+    All(bool),
+    PerLayout(HashMap<String, bool>),
+}
+impl_lua_conversion!(UseDeadKeys);
+
+impl Default for UseDeadKeys {
+    fn default() -> Self {
+        Self::All(true)
+    }
+}
+
+impl UseDeadKeys {
+    /// Returns true if dead keys should be composed when the
+    /// named keyboard layout is active
+    pub fn enabled_for_layout(&self, layout: &str) -> bool {
+        match self {
+            Self::All(enabled) => *enabled,
+            Self::PerLayout(layouts) => layouts
+                .get(layout)
+                .or_else(|| layouts.get("default"))
+                .copied()
+                .unwrap_or(true),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Mouse {
     pub event: MouseEventTrigger,
-    #[serde(deserialize_with = "de_modifiers", default)]
+    #[serde(
+        deserialize_with = "de_modifiers",
+        serialize_with = "ser_modifiers",
+        default
+    )]
     pub mods: Modifiers,
     pub action: KeyAssignment,
+    /// Describes what the binding does, for display in the list
+    /// of key bindings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 impl_lua_conversion!(Mouse);
 
@@ -127,6 +189,8 @@ fn make_map() -> HashMap<String, KeyCode> {
     map.insert("Enter".to_string(), KeyCode::Char('\r'));
     map.insert("Escape".to_string(), KeyCode::Char('\u{1b}'));
     map.insert("Tab".to_string(), KeyCode::Char('\t'));
+    map.insert("MediaNext".to_string(), KeyCode::MediaNextTrack);
+    map.insert("MediaPrev".to_string(), KeyCode::MediaPrevTrack);
 
     for i in 0..=9 {
         map.insert(format!("Numpad{}", i), KeyCode::Numpad(i));
@@ -174,6 +238,174 @@ where
     }
 }
 
+/// Returns the name by which `key` can be specified in the
+/// configuration, or None if it cannot be bound by the configuration
+pub fn keycode_to_config_name(key: &KeyCode) -> Option<String> {
+    match key {
+        KeyCode::Char('\u{8}') => Some("Backspace".to_string()),
+        KeyCode::Char('\u{7f}') => Some("Delete".to_string()),
+        KeyCode::Char('\r') => Some("Enter".to_string()),
+        KeyCode::Char('\u{1b}') => Some("Escape".to_string()),
+        KeyCode::Char('\t') => Some("Tab".to_string()),
+        KeyCode::Char(c) => Some(c.to_string()),
+        KeyCode::RawCode(n) => Some(format!("raw:{}", n)),
+        _ => KEYCODE_MAP
+            .iter()
+            .filter(|(_, code)| *code == key)
+            .map(|(name, _)| name)
+            // Aliases such as MediaNext are shorter than the
+            // names that they stand in for
+            .max_by_key(|name| name.len())
+            .cloned(),
+    }
+}
+
+/// Returns the modifiers in the form accepted by the `mods`
+/// field of a key binding, eg: `CTRL|SHIFT`
+pub fn modifiers_to_config_string(mods: Modifiers) -> String {
+    let mut names = vec![];
+    for (modifier, name) in &[
+        (Modifiers::SHIFT, "SHIFT"),
+        (Modifiers::ALT, "ALT"),
+        (Modifiers::CTRL, "CTRL"),
+        (Modifiers::SUPER, "SUPER"),
+        (Modifiers::LEADER, "LEADER"),
+    ] {
+        if mods.contains(*modifier) {
+            names.push(*name);
+        }
+    }
+    if names.is_empty() {
+        "NONE".to_string()
+    } else {
+        names.join("|")
+    }
+}
+
+/// Returns lua source that evaluates to `action` when used as the
+/// action of a key binding, eg: `wezterm.action{CopyTo="Clipboard"}`
+pub fn key_assignment_to_lua(action: &KeyAssignment) -> anyhow::Result<String> {
+    let mut lua = String::new();
+    match serde_json::to_value(action)? {
+        serde_json::Value::Object(map) => {
+            lua.push_str("wezterm.action");
+            write_lua_value(&serde_json::Value::Object(map), &mut lua);
+        }
+        value => write_lua_value(&value, &mut lua),
+    }
+    Ok(lua)
+}
+
+/// Returns a lua table constructor for the key binding, in the form
+/// used by the `keys` configuration option
+pub fn key_binding_to_lua(binding: &Key) -> anyhow::Result<String> {
+    let mut lua = String::from("{key=");
+    let key = keycode_to_config_name(&binding.key)
+        .ok_or_else(|| anyhow::anyhow!("{:?} has no configuration name", binding.key))?;
+    write_lua_string(&key, &mut lua);
+    lua.push_str(", mods=");
+    write_lua_string(&modifiers_to_config_string(binding.mods), &mut lua);
+    lua.push_str(", action=");
+    lua.push_str(&key_assignment_to_lua(&binding.action)?);
+    if binding.key_up {
+        lua.push_str(", key_up=true");
+    }
+    if let Some(description) = &binding.description {
+        lua.push_str(", description=");
+        write_lua_string(description, &mut lua);
+    }
+    lua.push('}');
+    Ok(lua)
+}
+
+fn write_lua_string(s: &str, lua: &mut String) {
+    lua.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => lua.push_str("\\\""),
+            '\\' => lua.push_str("\\\\"),
+            '\n' => lua.push_str("\\n"),
+            '\r' => lua.push_str("\\r"),
+            '\t' => lua.push_str("\\t"),
+            c if c.is_control() => lua.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => lua.push(c),
+        }
+    }
+    lua.push('"');
+}
+
+fn is_lua_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn write_lua_value(value: &serde_json::Value, lua: &mut String) {
+    use serde_json::Value;
+    match value {
+        Value::Null => lua.push_str("nil"),
+        Value::Bool(b) => lua.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => lua.push_str(&n.to_string()),
+        Value::String(s) => write_lua_string(s, lua),
+        Value::Array(items) => {
+            lua.push('{');
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    lua.push_str(", ");
+                }
+                write_lua_value(item, lua);
+            }
+            lua.push('}');
+        }
+        Value::Object(map) => {
+            lua.push('{');
+            let mut first = true;
+            for (key, item) in map {
+                if item.is_null() {
+                    // Omitting the field has the same effect, and
+                    // keeps the output shorter
+                    continue;
+                }
+                if !first {
+                    lua.push_str(", ");
+                }
+                first = false;
+                if is_lua_identifier(key) {
+                    lua.push_str(key);
+                } else {
+                    lua.push('[');
+                    write_lua_string(key, lua);
+                    lua.push(']');
+                }
+                lua.push('=');
+                write_lua_value(item, lua);
+            }
+            lua.push('}');
+        }
+    }
+}
+
+fn ser_keycode<S>(key: &KeyCode, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    // Keys with no config name, such as composed text, fall back to
+    // their debug representation rather than failing to serialize
+    // the whole config
+    let name = keycode_to_config_name(key).unwrap_or_else(|| format!("{:?}", key));
+    serializer.serialize_str(&name)
+}
+
+pub(crate) fn ser_modifiers<S>(mods: &Modifiers, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&modifiers_to_config_string(*mods))
+}
+
 pub(crate) fn de_modifiers<'de, D>(deserializer: D) -> Result<Modifiers, D::Error>
 where
     D: Deserializer<'de>,
@@ -206,3 +438,91 @@ where
     }
     Ok(mods)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keyassignment::{ClipboardCopyDestination, SpawnCommand, SpawnTabDomain};
+    use std::path::Path;
+    use wezterm_term::SemanticType;
+
+    #[test]
+    fn key_binding_lua_round_trip() -> anyhow::Result<()> {
+        let lua = crate::lua::make_lua_context(Path::new("testing"))?;
+
+        let bindings = vec![
+            (KeyCode::Char('c'), Modifiers::SUPER, KeyAssignment::Copy),
+            (
+                KeyCode::Char('C'),
+                Modifiers::CTRL | Modifiers::SHIFT,
+                KeyAssignment::CopyTo(ClipboardCopyDestination::Register("a".to_string())),
+            ),
+            (
+                KeyCode::Char('\r'),
+                Modifiers::ALT,
+                KeyAssignment::SendString("\"quoted\"\\\n\u{1b}[A".to_string()),
+            ),
+            (
+                KeyCode::PageUp,
+                Modifiers::NONE,
+                KeyAssignment::ActivateTabRelative(-1),
+            ),
+            (
+                KeyCode::Function(5),
+                Modifiers::LEADER,
+                KeyAssignment::SpawnCommandInNewTab(SpawnCommand {
+                    args: Some(vec!["top".to_string()]),
+                    domain: SpawnTabDomain::DefaultDomain,
+                    ..Default::default()
+                }),
+            ),
+            (
+                KeyCode::Char(']'),
+                Modifiers::CTRL,
+                KeyAssignment::MoveForwardSemanticZone(SemanticType::Prompt),
+            ),
+            (
+                KeyCode::Char('a'),
+                Modifiers::CTRL,
+                KeyAssignment::SendKey(KeyNoAction {
+                    key: KeyCode::Char('a'),
+                    mods: Modifiers::CTRL,
+                }),
+            ),
+        ];
+
+        for (key, mods, action) in bindings {
+            let binding = Key {
+                key,
+                mods,
+                action,
+                key_up: false,
+                description: Some("a \"binding\"".to_string()),
+            };
+            let code = format!(
+                "local wezterm = require 'wezterm';\nreturn {}",
+                key_binding_to_lua(&binding)?
+            );
+            let value: mlua::Value = lua.load(&code).eval()?;
+            let parsed: Key = luahelper::from_lua_value(value)?;
+            assert_eq!(parsed.key, binding.key, "{}", code);
+            assert_eq!(parsed.mods, binding.mods, "{}", code);
+            assert_eq!(parsed.action, binding.action, "{}", code);
+            assert!(!parsed.key_up);
+            assert_eq!(parsed.description, binding.description, "{}", code);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn unnamed_keycode_serializes() -> anyhow::Result<()> {
+        let binding = KeyNoAction {
+            key: KeyCode::Composed("ab".to_string()),
+            mods: Modifiers::NONE,
+        };
+        let value = serde_json::to_value(&binding)?;
+        assert_eq!(value["key"], "Composed(\"ab\")");
+        Ok(())
+    }
+}
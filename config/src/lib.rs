@@ -36,6 +36,9 @@ mod frontend;
 pub mod keyassignment;
 mod keys;
 pub mod lua;
+mod predict;
+mod reconnect;
+mod serial;
 mod ssh;
 mod terminal;
 mod tls;
@@ -50,6 +53,9 @@ pub use daemon::*;
 pub use font::*;
 pub use frontend::*;
 pub use keys::*;
+pub use predict::*;
+pub use reconnect::*;
+pub use serial::*;
 pub use ssh::*;
 pub use terminal::*;
 pub use tls::*;
@@ -64,6 +70,8 @@ lazy_static! {
     pub static ref HOME_DIR: PathBuf = dirs_next::home_dir().expect("can't find HOME dir");
     pub static ref CONFIG_DIR: PathBuf = xdg_config_home();
     pub static ref RUNTIME_DIR: PathBuf = compute_runtime_dir().unwrap();
+    pub static ref CACHE_DIR: PathBuf = compute_cache_dir().unwrap();
+    pub static ref DATA_DIR: PathBuf = compute_data_dir().unwrap();
     static ref CONFIG: Configuration = Configuration::new();
     static ref CONFIG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref CONFIG_OVERRIDES: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
@@ -499,6 +507,9 @@ impl ConfigInner {
                     ConfigInner::accumulate_watch_paths(&lua, &mut watch_paths);
                     LUA_PIPE.sender.try_send(lua).ok();
                 }
+                for tls_server in &self.config.tls_servers {
+                    watch_paths.extend(tls_server.certificate_paths());
+                }
 
                 log::debug!("Reloaded configuration! generation={}", self.generation);
                 self.notify();
@@ -660,6 +671,22 @@ impl ConfigHandle {
     pub fn generation(&self) -> usize {
         self.generation
     }
+
+    /// Returns a copy of this configuration in which `features` are
+    /// used in place of `harfbuzz_features` for all of the font
+    /// and font_rules text styles.
+    pub fn with_harfbuzz_features(&self, features: &[String]) -> Self {
+        let mut config = (*self.config).clone();
+        config.harfbuzz_features = features.to_vec();
+        config.font.harfbuzz_features = Some(features.to_vec());
+        for rule in &mut config.font_rules {
+            rule.font.harfbuzz_features = Some(features.to_vec());
+        }
+        Self {
+            config: Arc::new(config),
+            generation: self.generation,
+        }
+    }
 }
 
 impl std::ops::Deref for ConfigHandle {
@@ -718,6 +745,24 @@ impl Default for ExitBehavior {
     }
 }
 
+/// Where new tabs and panes start when the spawn command doesn't
+/// specify a working directory
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CwdInheritance {
+    /// Start in the current working directory of the active pane,
+    /// provided that the new pane is in the same domain
+    CurrentPaneDir,
+    /// Start in the default directory of the domain, which is
+    /// `default_cwd` or the home directory for the local domain
+    DomainDefault,
+}
+
+impl Default for CwdInheritance {
+    fn default() -> Self {
+        CwdInheritance::CurrentPaneDir
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     /// The font size, measured in points
@@ -727,6 +772,20 @@ pub struct Config {
     #[serde(default = "default_one_point_oh_f64")]
     pub line_height: f64,
 
+    /// Overrides the underline thickness reported by the font.
+    /// Percentages are relative to the cell height.
+    #[serde(default, deserialize_with = "de_opt_pixels")]
+    pub underline_thickness: Option<Dimension>,
+
+    /// Overrides the position of the underline reported by the font,
+    /// measured downwards from the baseline to the top of the underline.
+    /// Percentages are relative to the cell height.
+    #[serde(default, deserialize_with = "de_opt_pixels")]
+    pub underline_position: Option<Dimension>,
+
+    #[serde(default)]
+    pub curly_underline: CurlyUnderline,
+
     #[serde(default)]
     pub allow_square_glyphs_to_overflow_width: AllowSquareGlyphOverflow,
 
@@ -770,6 +829,10 @@ pub struct Config {
     #[serde(default)]
     pub tab_bar_style: TabBarStyle,
 
+    /// Styling for the text that is being composed by an input method
+    #[serde(default)]
+    pub ime_preedit_colors: ImePreeditColors,
+
     #[serde(skip)]
     pub resolved_palette: Palette,
 
@@ -785,6 +848,25 @@ pub struct Config {
     #[serde(default = "default_scrollback_lines")]
     pub scrollback_lines: usize,
 
+    /// If set, limits the memory used by the scrollback of each pane
+    /// to approximately this many bytes; the oldest lines are discarded
+    /// once the limit is exceeded, even if there are fewer than
+    /// scrollback_lines of them
+    #[serde(default)]
+    pub scrollback_max_bytes: Option<usize>,
+
+    /// If set, the scrollback of a pane that has had no output or
+    /// input for this long is moved to a compressed file in the runtime
+    /// directory, freeing its memory, until the pane is scrolled or
+    /// searched, or produces output.  Either a number of seconds or a
+    /// string such as "30m" or "1h".
+    #[serde(
+        default,
+        deserialize_with = "de_opt_duration",
+        serialize_with = "ser_opt_duration"
+    )]
+    pub hibernate_idle_panes_after: Option<Duration>,
+
     /// If no `prog` is specified on the command line, use this
     /// instead of running the user's shell.
     /// For example, to have `wezterm` always run `top` by default,
@@ -802,11 +884,27 @@ pub struct Config {
     #[serde(default = "default_gui_startup_args")]
     pub default_gui_startup_args: Vec<String>,
 
+    /// When the gui starts without a program to run, recreate the
+    /// windows, tabs and panes that were saved by
+    /// `wezterm cli save-session`, rather than spawning a new tab
+    #[serde(default)]
+    pub restore_session_on_startup: bool,
+
     /// Specifies the default current working directory if none is specified
     /// through configuration or OSC 7 (see docs for `default_cwd` for more
     /// info!)
     pub default_cwd: Option<PathBuf>,
 
+    /// Controls whether new tabs and panes start in the working
+    /// directory of the active pane
+    #[serde(default)]
+    pub cwd_inheritance: CwdInheritance,
+
+    /// Overrides `cwd_inheritance` when the active pane belongs
+    /// to the named domain
+    #[serde(default)]
+    pub cwd_inheritance_by_domain: HashMap<String, CwdInheritance>,
+
     #[serde(default)]
     pub exit_behavior: ExitBehavior,
 
@@ -823,6 +921,23 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub enable_kitty_graphics: bool,
 
+    /// The maximum size, in bytes, of a decoded sixel image.
+    /// Larger images are truncated or rejected.
+    #[serde(default = "default_max_image_size_bytes")]
+    pub max_image_size_bytes: usize,
+
+    /// The maximum width or height, in pixels, of a sixel image.
+    /// Larger images are truncated or rejected.
+    #[serde(default = "default_max_image_dimensions")]
+    pub max_image_dimensions: u32,
+
+    /// Whether sixel images scroll the display by default,
+    /// prior to any DECSDM sequence from the application.
+    /// When false, sixel images are drawn at the top left of
+    /// the screen and do not move the cursor.
+    #[serde(default = "default_true")]
+    pub sixel_scrolling: bool,
+
     /// Specifies the width of a new window, expressed in character cells
     #[serde(default = "default_initial_cols")]
     pub initial_cols: u16,
@@ -890,6 +1005,12 @@ pub struct Config {
     #[serde(default = "default_harfbuzz_features")]
     pub harfbuzz_features: Vec<String>,
 
+    /// Override `harfbuzz_features` for panes while a particular
+    /// program is the foreground process; the first matching
+    /// rule is used.
+    #[serde(default)]
+    pub harfbuzz_features_rules: Vec<HarfbuzzFeaturesRule>,
+
     #[serde(default)]
     pub front_end: FrontEndSelection,
 
@@ -903,6 +1024,10 @@ pub struct Config {
     #[serde(default)]
     pub ssh_backend: SshBackend,
 
+    /// The set of serial ports that can be connected to by name
+    #[serde(default)]
+    pub serial_ports: Vec<SerialDomain>,
+
     /// When running in server mode, defines configuration for
     /// each of the endpoints that we'll listen for connections
     #[serde(default)]
@@ -934,7 +1059,8 @@ pub struct Config {
     pub keys: Vec<Key>,
     #[serde(
         default = "default_bypass_mouse_reporting_modifiers",
-        deserialize_with = "crate::keys::de_modifiers"
+        deserialize_with = "crate::keys::de_modifiers",
+        serialize_with = "crate::keys::ser_modifiers"
     )]
     pub bypass_mouse_reporting_modifiers: Modifiers,
 
@@ -948,7 +1074,7 @@ pub struct Config {
     #[serde(default)]
     pub disable_default_quick_select_patterns: bool,
     #[serde(default)]
-    pub quick_select_patterns: Vec<String>,
+    pub quick_select_patterns: Vec<QuickSelectPattern>,
     #[serde(default = "default_alphabet")]
     pub quick_select_alphabet: String,
 
@@ -994,6 +1120,12 @@ pub struct Config {
     #[serde(default)]
     pub tab_bar_at_bottom: bool,
 
+    /// Where to place the tab bar.  When not set, the tab bar is at
+    /// the top of the window, or at the bottom if `tab_bar_at_bottom`
+    /// is true.  Left and Right require `use_fancy_tab_bar`.
+    #[serde(default)]
+    pub tab_bar_position: Option<TabBarPosition>,
+
     /// If true, tab bar titles are prefixed with the tab index
     #[serde(default = "default_true")]
     pub show_tab_index_in_tab_bar: bool,
@@ -1015,6 +1147,11 @@ pub struct Config {
     #[serde(default)]
     pub enable_scroll_bar: bool,
 
+    /// The minimum height of the scroll bar thumb, so that it remains
+    /// possible to grab it when there is a lot of scrollback
+    #[serde(default = "default_half_cell", deserialize_with = "de_pixels")]
+    pub min_scroll_bar_height: Dimension,
+
     /// If false, do not try to use a Wayland protocol connection
     /// when starting the gui frontend, and instead use X11.
     /// This option is only considered on X11/Wayland systems and
@@ -1098,6 +1235,19 @@ pub struct Config {
     #[serde(default = "default_inactive_pane_hsb")]
     pub inactive_pane_hsb: HsbTransform,
 
+    /// Overrides inactive_pane_hsb for the background of inactive panes
+    #[serde(default)]
+    pub inactive_pane_background_hsb: Option<HsbTransform>,
+
+    /// Overrides inactive_pane_hsb for images displayed in inactive panes
+    #[serde(default)]
+    pub inactive_pane_image_hsb: Option<HsbTransform>,
+
+    /// How long it takes for a pane to fade between its active
+    /// and inactive appearance.  0 switches instantly.
+    #[serde(default)]
+    pub inactive_pane_dim_time_ms: u64,
+
     #[serde(default = "default_one_point_oh")]
     pub text_background_opacity: f32,
 
@@ -1110,6 +1260,10 @@ pub struct Config {
     #[serde(default = "default_cursor_blink_rate")]
     pub cursor_blink_rate: u64,
 
+    /// Controls whether the cursor glides between cells when it moves
+    #[serde(default)]
+    pub cursor_animation: CursorAnimation,
+
     #[serde(default)]
     pub force_reverse_video_cursor: bool,
 
@@ -1157,11 +1311,11 @@ pub struct Config {
 
     #[serde(default)]
     pub use_ime: bool,
-    #[serde(default = "default_true")]
-    pub use_dead_keys: bool,
+    #[serde(default)]
+    pub use_dead_keys: UseDeadKeys,
 
     #[serde(default)]
-    pub launch_menu: Vec<SpawnCommand>,
+    pub launch_menu: Vec<LaunchMenuItem>,
 
     /// When true, watch the config file and reload it automatically
     /// when it is detected as changing.
@@ -1207,9 +1361,28 @@ pub struct Config {
     #[serde(default = "default_alternate_buffer_wheel_scroll_speed")]
     pub alternate_buffer_wheel_scroll_speed: u8,
 
+    /// How much horizontal scrolling is accumulated before it is
+    /// treated as a single WheelLeft or WheelRight event, so that
+    /// high resolution touchpads don't trigger a binding many times
+    /// over the course of a single gesture
+    #[serde(default = "default_horizontal_wheel_ticks_per_event")]
+    pub horizontal_wheel_ticks_per_event: u16,
+
     #[serde(default = "default_status_update_interval")]
     pub status_update_interval: u64,
 
+    /// How many milliseconds to pause between each key when
+    /// replaying a key recording.  The default of 0 sends all
+    /// of the keys at once.
+    #[serde(default)]
+    pub key_replay_delay: u64,
+
+    /// The names of assignments that open an overlay, such as
+    /// `Search`, that RepeatLastAssignment may repeat.  Such
+    /// assignments are not repeated by default.
+    #[serde(default)]
+    pub repeatable_overlay_assignments: Vec<String>,
+
     #[serde(default)]
     pub experimental_shape_post_processing: bool,
 
@@ -1237,6 +1410,11 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub unzoom_on_switch_pane: bool,
 
+    /// When unzoom_on_switch_pane has unzoomed the active pane in order
+    /// to switch to another pane, zoom the newly active pane
+    #[serde(default)]
+    pub rezoom_on_switch_pane: bool,
+
     #[serde(default = "default_max_fps")]
     pub max_fps: u8,
 
@@ -1251,9 +1429,78 @@ pub struct Config {
 
     #[serde(default = "default_unicode_version")]
     pub unicode_version: u8,
+
+    /// If set, log records are appended to this file in addition
+    /// to being written to stderr
+    #[serde(default)]
+    pub log_file: Option<PathBuf>,
+
+    /// When the log file exceeds this many bytes, it is rotated.
+    /// 0 disables rotation.
+    #[serde(default = "default_log_file_max_size")]
+    pub log_file_max_size: u64,
+
+    /// How many rotated log files to keep
+    #[serde(default = "default_log_file_keep")]
+    pub log_file_keep: usize,
+
+    /// Show a toast notification when an error is logged
+    #[serde(default)]
+    pub notify_on_error_log: bool,
+
+    /// Show a toast notification when a pane that isn't being looked
+    /// at has produced no output for this many seconds after having
+    /// produced some
+    #[serde(default)]
+    pub notify_when_idle_seconds: Option<u64>,
+
+    /// Errors logged by targets that start with any of these
+    /// prefixes don't produce a notification
+    #[serde(default)]
+    pub notify_on_error_log_exclude_targets: Vec<String>,
+
+    /// Display log timestamps in UTC rather than the local timezone
+    #[serde(default)]
+    pub log_timestamps_utc: bool,
+
+    /// An strftime format used to display log timestamps
+    #[serde(default)]
+    pub log_timestamp_format: Option<String>,
+
+    /// How many lines of debug overlay repl history to keep
+    /// across sessions. 0 disables saving the history.
+    #[serde(default = "default_debug_key_history_limit")]
+    pub debug_key_history_limit: usize,
+
+    /// When rendering text, the foreground color is adjusted towards
+    /// white or black until its WCAG contrast ratio against the
+    /// background is at least this value.  The default of 1.0
+    /// disables the adjustment.
+    #[serde(default = "default_minimum_contrast_ratio")]
+    pub minimum_contrast_ratio: f32,
+
+    /// Controls the appearance of the dividers between panes
+    #[serde(default)]
+    pub pane_split: PaneSplit,
 }
 impl_lua_conversion!(Config);
 
+fn default_log_file_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_log_file_keep() -> usize {
+    3
+}
+
+fn default_debug_key_history_limit() -> usize {
+    1000
+}
+
+fn default_minimum_contrast_ratio() -> f32 {
+    1.0
+}
+
 // Coupled with term/src/config.rs:TerminalConfiguration::unicode_version
 fn default_unicode_version() -> u8 {
     9
@@ -1300,6 +1547,10 @@ fn default_alternate_buffer_wheel_scroll_speed() -> u8 {
     3
 }
 
+fn default_horizontal_wheel_ticks_per_event() -> u16 {
+    3
+}
+
 fn default_alphabet() -> String {
     "asdfqwerzxcvjklmiuopghtybn".to_string()
 }
@@ -1405,6 +1656,56 @@ impl Default for WindowPadding {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default)]
+pub struct PaneSplit {
+    /// How thick to draw the divider; 0 uses the underline
+    /// thickness of the font.  The divider cannot be thicker
+    /// than the cell that separates the panes.
+    #[serde(deserialize_with = "de_points", default)]
+    pub thickness: Dimension,
+    /// Overrides the `split` color from the color scheme
+    #[serde(default)]
+    pub color: Option<RgbColor>,
+    /// The color of the dividers that border the active pane
+    #[serde(default)]
+    pub active_color: Option<RgbColor>,
+    /// The color of the divider when the mouse is over it,
+    /// or it is being dragged
+    #[serde(default)]
+    pub hover_color: Option<RgbColor>,
+    /// The color of the dividers that border panes that receive
+    /// broadcast input.  The default is the red of the color scheme.
+    #[serde(default)]
+    pub broadcast_color: Option<RgbColor>,
+}
+impl_lua_conversion!(PaneSplit);
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default)]
+pub struct CurlyUnderline {
+    /// The distance from the peak to the trough of the wave.
+    /// Percentages are relative to the cell height.
+    /// The default is half of the space below the underline position.
+    #[serde(default, deserialize_with = "de_opt_pixels")]
+    pub height: Option<Dimension>,
+    /// The horizontal length of one cycle of the wave.
+    /// Percentages are relative to the cell width.
+    /// The default is one cell.
+    #[serde(default, deserialize_with = "de_opt_pixels")]
+    pub period: Option<Dimension>,
+}
+impl_lua_conversion!(CurlyUnderline);
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct CursorAnimation {
+    /// How long it takes the cursor to travel to its new position.
+    /// 0 disables the animation.
+    #[serde(default)]
+    pub duration_ms: u64,
+    #[serde(default)]
+    pub easing: EasingFunction,
+}
+impl_lua_conversion!(CursorAnimation);
+
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
 pub enum WindowCloseConfirmation {
     AlwaysPrompt,
@@ -1420,6 +1721,86 @@ impl Default for WindowCloseConfirmation {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TabBarPosition {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+impl_lua_conversion!(TabBarPosition);
+
+impl TabBarPosition {
+    /// Returns true if the tab bar is a strip down the side of the window
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+}
+
+/// An entry in `launch_menu`; either a command to spawn, or a labelled
+/// submenu that holds further entries
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum LaunchMenuItem {
+    Submenu {
+        label: String,
+        children: Vec<LaunchMenuItem>,
+    },
+    Spawn(SpawnCommand),
+}
+impl_lua_conversion!(LaunchMenuItem);
+
+/// An entry in `quick_select_patterns`; either a plain regex, which
+/// is matched together with the default patterns, or a rule that
+/// specifies how its matches are labeled and what happens when
+/// one is selected
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum QuickSelectPattern {
+    Regex(String),
+    Rule(QuickSelectRule),
+}
+impl_lua_conversion!(QuickSelectPattern);
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct QuickSelectRule {
+    pub regex: String,
+    /// Overrides `quick_select_alphabet` for the labels of matches
+    /// of this rule
+    #[serde(default)]
+    pub alphabet: Option<String>,
+    /// When matches from different patterns overlap, the match from
+    /// the pattern with the higher priority is used.  Rules with the
+    /// same priority are preferred in the order that they are listed,
+    /// and are preferred over the plain patterns.
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub action: QuickSelectAction,
+}
+impl_lua_conversion!(QuickSelectRule);
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum QuickSelectAction {
+    /// Copy the text to the clipboard; typing the label in
+    /// uppercase also pastes it
+    Copy,
+    /// Paste the text into the pane
+    Paste,
+    /// Open the text as a URL, via the `open-uri` event
+    Open,
+    /// Emit the named event with the window, pane and text as its
+    /// parameters.  This is what `wezterm.action_callback` produces.
+    EmitEvent(String),
+}
+impl_lua_conversion!(QuickSelectAction);
+
+impl Default for QuickSelectAction {
+    fn default() -> Self {
+        Self::Copy
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         // Ask serde to provide the defaults based on the attributes
@@ -1595,7 +1976,19 @@ impl Config {
     pub fn key_bindings(&self) -> HashMap<(KeyCode, Modifiers), KeyAssignment> {
         let mut map = HashMap::new();
 
-        for k in &self.keys {
+        for k in self.keys.iter().filter(|k| !k.key_up) {
+            let (key, mods) = k.key.normalize_shift(k.mods);
+            map.insert((key, mods), k.action.clone());
+        }
+
+        map
+    }
+
+    /// Returns the bindings that are performed when the key is released
+    pub fn key_up_bindings(&self) -> HashMap<(KeyCode, Modifiers), KeyAssignment> {
+        let mut map = HashMap::new();
+
+        for k in self.keys.iter().filter(|k| k.key_up) {
             let (key, mods) = k.key.normalize_shift(k.mods);
             map.insert((key, mods), k.action.clone());
         }
@@ -1776,7 +2169,12 @@ impl Config {
 
     pub fn resolve_color_scheme(&self) -> Option<&Palette> {
         let scheme_name = self.color_scheme.as_ref()?;
+        self.color_scheme_by_name(scheme_name)
+    }
 
+    /// Looks up a color scheme by name, considering both the schemes
+    /// defined in the configuration and the built-in schemes
+    pub fn color_scheme_by_name(&self, scheme_name: &str) -> Option<&Palette> {
         if let Some(palette) = self.color_schemes.get(scheme_name) {
             Some(palette)
         } else {
@@ -1784,6 +2182,30 @@ impl Config {
         }
     }
 
+    /// Returns the colors to use for the tab bar.
+    /// Those from the color scheme take precedence; if the scheme doesn't
+    /// specify any then those from `colors` are used.
+    pub fn tab_bar_colors(&self) -> TabBarColors {
+        self.resolved_palette
+            .tab_bar
+            .as_ref()
+            .or_else(|| self.colors.as_ref().and_then(|c| c.tab_bar.as_ref()))
+            .cloned()
+            .unwrap_or_else(TabBarColors::default)
+    }
+
+    /// Resolves `tab_bar_position` and `tab_bar_at_bottom` into the
+    /// position at which the tab bar is to be displayed.
+    /// The retro tab bar cannot be displayed vertically, so it
+    /// falls back to being horizontal.
+    pub fn resolved_tab_bar_position(&self) -> TabBarPosition {
+        match self.tab_bar_position {
+            Some(position) if !position.is_vertical() || self.use_fancy_tab_bar => position,
+            _ if self.tab_bar_at_bottom => TabBarPosition::Bottom,
+            _ => TabBarPosition::Top,
+        }
+    }
+
     pub fn initial_size(&self) -> PtySize {
         PtySize {
             rows: self.initial_rows,
@@ -1827,6 +2249,15 @@ impl Config {
         Ok(cmd)
     }
 
+    /// Returns the cwd inheritance that applies to new panes that are
+    /// spawned from a pane in the named domain
+    pub fn cwd_inheritance_for_domain(&self, domain_name: &str) -> CwdInheritance {
+        self.cwd_inheritance_by_domain
+            .get(domain_name)
+            .copied()
+            .unwrap_or(self.cwd_inheritance)
+    }
+
     pub fn apply_cmd_defaults(&self, cmd: &mut CommandBuilder) {
         // Apply `default_cwd` only if `cwd` is not already set, allows `--cwd`
         // option to take precedence
@@ -1877,6 +2308,14 @@ impl Config {
     }
 }
 
+fn default_max_image_size_bytes() -> usize {
+    100_000_000
+}
+
+fn default_max_image_dimensions() -> u32 {
+    10_000
+}
+
 fn default_mux_output_parser_buffer_size() -> usize {
     128 * 1024
 }
@@ -1953,6 +2392,29 @@ fn compute_runtime_dir() -> Result<PathBuf, Error> {
     Ok(HOME_DIR.join(".local/share/wezterm"))
 }
 
+/// The path of the link through which the panes of the mux server reach
+/// the ssh agent that was forwarded to the most recently started
+/// `wezterm cli proxy`
+pub fn forwarded_agent_path() -> PathBuf {
+    RUNTIME_DIR.join("agent")
+}
+
+fn compute_data_dir() -> Result<PathBuf, Error> {
+    if let Some(data) = dirs_next::data_dir() {
+        return Ok(data.join("wezterm"));
+    }
+
+    Ok(HOME_DIR.join(".local/share/wezterm"))
+}
+
+fn compute_cache_dir() -> Result<PathBuf, Error> {
+    if let Some(cache) = dirs_next::cache_dir() {
+        return Ok(cache.join("wezterm"));
+    }
+
+    compute_runtime_dir()
+}
+
 pub fn pki_dir() -> anyhow::Result<PathBuf> {
     compute_runtime_dir().map(|d| d.join("pki"))
 }
@@ -1965,6 +2427,10 @@ fn default_write_timeout() -> Duration {
     Duration::from_secs(60)
 }
 
+fn default_local_echo_threshold_ms() -> u64 {
+    100
+}
+
 fn default_bypass_mouse_reporting_modifiers() -> Modifiers {
     Modifiers::SHIFT
 }
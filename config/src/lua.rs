@@ -1,5 +1,5 @@
 use crate::Gradient;
-use crate::{FontAttributes, FontStretch, FontWeight, TextStyle};
+use crate::{CodepointRange, FontAttributes, FontStretch, FontWeight, TextStyle};
 use anyhow::anyhow;
 use bstr::BString;
 pub use luahelper::*;
@@ -7,6 +7,7 @@ use mlua::{FromLua, Lua, Table, ToLua, ToLuaMulti, Value, Variadic};
 use serde::*;
 use smol::prelude::*;
 use std::path::Path;
+use std::sync::Mutex;
 use termwiz::cell::{grapheme_column_width, unicode_column_width, AttributeChange, CellAttributes};
 use termwiz::color::{AnsiColor, ColorAttribute, ColorSpec, RgbColor};
 use termwiz::input::Modifiers;
@@ -15,6 +16,44 @@ use unicode_segmentation::UnicodeSegmentation;
 
 static LUA_REGISTRY_USER_CALLBACK_COUNT: &str = "wezterm-user-callback-count";
 
+pub type SetupFunc = fn(&Lua) -> anyhow::Result<()>;
+
+lazy_static::lazy_static! {
+    static ref SETUP_FUNCS: Mutex<Vec<SetupFunc>> = Mutex::new(vec![]);
+}
+
+/// Registers a function that will be called to populate
+/// additional functions into each newly created lua context.
+/// This allows crates that cannot be depended upon by this
+/// crate to extend the `wezterm` module.
+pub fn add_context_setup_func(func: SetupFunc) {
+    SETUP_FUNCS.lock().unwrap().push(func);
+}
+
+/// Returns the named module from `package.loaded`, creating
+/// and registering an empty table for it if it doesn't exist.
+pub fn get_or_create_module<'lua>(lua: &'lua Lua, name: &str) -> anyhow::Result<Table<'lua>> {
+    let globals = lua.globals();
+    let package: Table = globals.get("package")?;
+    let loaded: Table = package.get("loaded")?;
+
+    let module = loaded.get(name)?;
+    match module {
+        Value::Nil => {
+            let module = lua.create_table()?;
+            loaded.set(name, module.clone())?;
+            Ok(module)
+        }
+        Value::Table(table) => Ok(table),
+        wat => anyhow::bail!(
+            "cannot register module {} as package.loaded.{} is already set to a value of type {}",
+            name,
+            name,
+            wat.type_name()
+        ),
+    }
+}
+
 /// Set up a lua context for executing some code.
 /// The path to the directory containing the configuration is
 /// passed in and is used to pre-set some global values in
@@ -272,6 +311,11 @@ pub fn make_lua_context(config_file: &Path) -> anyhow::Result<Lua> {
         loaded.set("wezterm", wezterm_mod)?;
     }
 
+    let setup_funcs = SETUP_FUNCS.lock().unwrap().clone();
+    for func in setup_funcs {
+        func(&lua)?;
+    }
+
     Ok(lua)
 }
 
@@ -472,6 +516,12 @@ struct TextStyleAttributes {
     /// useful in a `[[font_rules]]` section to implement changing
     /// the text color for eg: bold text.
     pub foreground: Option<termwiz::color::RgbColor>,
+    /// Controls bold/italic synthesis for all of the fonts in the
+    /// style, unless overridden for a specific font
+    #[serde(default)]
+    pub synthesize_bold: Option<bool>,
+    #[serde(default)]
+    pub synthesize_italic: Option<bool>,
 }
 impl_lua_conversion!(TextStyleAttributes);
 
@@ -487,6 +537,16 @@ struct LuaFontAttributes {
     /// Whether the font should be an italic variant
     #[serde(default)]
     pub italic: bool,
+    #[serde(default)]
+    pub synthesize_bold: Option<bool>,
+    #[serde(default)]
+    pub synthesize_italic: Option<bool>,
+    /// Restricts the font to these codepoint ranges
+    #[serde(default)]
+    pub ranges: Vec<CodepointRange>,
+    /// Restricts the font to these scripts
+    #[serde(default)]
+    pub scripts: Vec<String>,
 }
 impl<'lua> FromLua<'lua> for LuaFontAttributes {
     fn from_lua(value: Value<'lua>, _lua: &'lua Lua) -> Result<Self, mlua::Error> {
@@ -525,6 +585,8 @@ fn font<'lua>(
         };
         attrs.stretch = map_defaults.stretch;
         attrs.italic = map_defaults.italic;
+        attrs.synthesize_bold = attrs.synthesize_bold.or(map_defaults.synthesize_bold);
+        attrs.synthesize_italic = attrs.synthesize_italic.or(map_defaults.synthesize_italic);
         text_style.foreground = map_defaults.foreground;
     }
 
@@ -535,6 +597,10 @@ fn font<'lua>(
         italic: attrs.italic,
         is_fallback: false,
         is_synthetic: false,
+        synthesize_bold: attrs.synthesize_bold,
+        synthesize_italic: attrs.synthesize_italic,
+        ranges: attrs.ranges,
+        scripts: attrs.scripts,
     });
 
     Ok(text_style)
@@ -563,6 +629,8 @@ fn font_with_fallback<'lua>(
             };
             attrs.stretch = map_defaults.stretch;
             attrs.italic = map_defaults.italic;
+            attrs.synthesize_bold = attrs.synthesize_bold.or(map_defaults.synthesize_bold);
+            attrs.synthesize_italic = attrs.synthesize_italic.or(map_defaults.synthesize_italic);
             text_style.foreground = map_defaults.foreground;
         }
 
@@ -573,6 +641,10 @@ fn font_with_fallback<'lua>(
             italic: attrs.italic,
             is_fallback: idx != 0,
             is_synthetic: false,
+            synthesize_bold: attrs.synthesize_bold,
+            synthesize_italic: attrs.synthesize_italic,
+            ranges: attrs.ranges,
+            scripts: attrs.scripts,
         });
     }
 
@@ -748,6 +820,17 @@ pub async fn emit_event<'lua>(
     }
 }
 
+/// Returns true if any handlers have been registered for the
+/// named event via `wezterm.on`
+pub fn has_event_handlers(lua: &Lua, name: &str) -> bool {
+    let decorated_name = format!("wezterm-event-{}", name);
+    let tbl: mlua::Result<mlua::Value> = lua.named_registry_value(&decorated_name);
+    match tbl {
+        Ok(mlua::Value::Table(tbl)) => tbl.raw_len() > 0,
+        _ => false,
+    }
+}
+
 pub fn emit_sync_callback<'lua, A>(
     lua: &'lua Lua,
     (name, args): (String, A),
@@ -0,0 +1,22 @@
+use crate::*;
+
+/// Controls whether the panes of a client domain echo what is typed
+/// into them before the server does, so that typing doesn't have to
+/// wait for the round trip to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PredictiveEcho {
+    /// Never echo locally
+    Off,
+    /// Echo locally once key presses take at least
+    /// `local_echo_threshold_ms` to round trip to the server
+    Auto,
+    /// Always echo locally
+    Always,
+}
+impl_lua_conversion!(PredictiveEcho);
+
+impl Default for PredictiveEcho {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
@@ -0,0 +1,115 @@
+use crate::*;
+
+/// Controls whether and how a client domain re-establishes its
+/// connection to the mux server when the connection is lost
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AutoReconnect {
+    /// Whether to try to reconnect at all
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// How many attempts to make before giving up and detaching
+    /// the domain.  0 means that there is no limit.
+    #[serde(default)]
+    pub max_attempts: usize,
+
+    /// The delay before the first attempt.  The delay doubles
+    /// with each subsequent attempt, up to `max_delay_ms`.
+    #[serde(default = "default_initial_delay_ms")]
+    pub initial_delay_ms: u64,
+
+    /// The longest delay between two attempts
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+impl_lua_conversion!(AutoReconnect);
+
+impl Default for AutoReconnect {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 0,
+            initial_delay_ms: default_initial_delay_ms(),
+            max_delay_ms: default_max_delay_ms(),
+        }
+    }
+}
+
+fn default_initial_delay_ms() -> u64 {
+    500
+}
+
+fn default_max_delay_ms() -> u64 {
+    30_000
+}
+
+impl AutoReconnect {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if `attempt`, counting from 1, is beyond
+    /// the configured number of attempts
+    pub fn exhausted(&self, attempt: usize) -> bool {
+        self.max_attempts > 0 && attempt > self.max_attempts
+    }
+
+    /// Computes the delay before `attempt`, counting from 1.
+    /// `jitter` is a value in the range 0.0..1.0, and is used to pick
+    /// the delay from the upper half of the backoff interval, so that
+    /// several clients that lost their connections at the same time
+    /// don't all try to reconnect at the same time.
+    pub fn delay_for_attempt(&self, attempt: usize, jitter: f64) -> Duration {
+        let max_delay = self.max_delay_ms.max(1);
+        let shift = attempt.saturating_sub(1).min(32) as u32;
+        let backoff = self
+            .initial_delay_ms
+            .max(1)
+            .saturating_mul(1u64 << shift)
+            .min(max_delay);
+        let half = backoff / 2;
+        let jitter = jitter.max(0.0).min(1.0);
+        Duration::from_millis(backoff - half + (half as f64 * jitter) as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let settings = AutoReconnect::default();
+        assert_eq!(
+            settings.delay_for_attempt(1, 0.0),
+            Duration::from_millis(250)
+        );
+        assert_eq!(
+            settings.delay_for_attempt(1, 1.0),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            settings.delay_for_attempt(3, 1.0),
+            Duration::from_millis(2000)
+        );
+        assert_eq!(
+            settings.delay_for_attempt(100, 1.0),
+            Duration::from_millis(30_000)
+        );
+        assert_eq!(
+            settings.delay_for_attempt(100, 0.5),
+            Duration::from_millis(22_500)
+        );
+
+        assert!(!settings.exhausted(1000));
+        let settings = AutoReconnect {
+            max_attempts: 3,
+            ..settings
+        };
+        assert!(!settings.exhausted(3));
+        assert!(settings.exhausted(4));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::*;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum SerialFlowControl {
+    None,
+    /// RTS/CTS
+    Hardware,
+    /// XON/XOFF
+    Software,
+}
+impl_lua_conversion!(SerialFlowControl);
+
+impl Default for SerialFlowControl {
+    fn default() -> Self {
+        Self::Software
+    }
+}
+
+impl SerialFlowControl {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["None", "Hardware", "Software"]
+    }
+}
+
+impl std::str::FromStr for SerialFlowControl {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "none" => Ok(Self::None),
+            "hardware" => Ok(Self::Hardware),
+            "software" => Ok(Self::Software),
+            _ => Err(anyhow!(
+                "{} is not a valid SerialFlowControl variant, possible values are {:?}",
+                s,
+                Self::variants()
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum SerialParity {
+    None,
+    Odd,
+    Even,
+}
+impl_lua_conversion!(SerialParity);
+
+impl Default for SerialParity {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl SerialParity {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["None", "Odd", "Even"]
+    }
+}
+
+impl std::str::FromStr for SerialParity {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "none" => Ok(Self::None),
+            "odd" => Ok(Self::Odd),
+            "even" => Ok(Self::Even),
+            _ => Err(anyhow!(
+                "{} is not a valid SerialParity variant, possible values are {:?}",
+                s,
+                Self::variants()
+            )),
+        }
+    }
+}
+
+/// A serial port that can be connected to by name, either
+/// through `wezterm serial NAME` or by spawning into the domain
+/// of the same name
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct SerialDomain {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    pub name: String,
+
+    /// The device to open, such as `/dev/ttyUSB0` or `COM3`
+    #[serde(default)]
+    pub port: Option<String>,
+
+    /// Identifies the device by the serial number of its USB adapter,
+    /// so that it is found even when it re-enumerates under a different
+    /// path.  Only supported on Linux.
+    #[serde(default)]
+    pub usb_serial_number: Option<String>,
+
+    /// The baud rate; 9600 if not set
+    #[serde(default)]
+    pub baud: Option<usize>,
+
+    #[serde(default)]
+    pub flow_control: SerialFlowControl,
+
+    #[serde(default)]
+    pub parity: SerialParity,
+
+    /// 1 or 2; 1 if not set
+    #[serde(default)]
+    pub stop_bits: Option<u8>,
+
+    /// 5 through 8; 8 if not set
+    #[serde(default)]
+    pub data_bits: Option<u8>,
+
+    /// Whether to wait for the device to come back when it is
+    /// disconnected, rather than closing the pane
+    #[serde(default)]
+    pub reconnect: bool,
+}
+impl_lua_conversion!(SerialDomain);
@@ -28,6 +28,11 @@ pub struct SshDomain {
     #[serde(default)]
     pub no_agent_auth: bool,
 
+    /// Whether to forward the local ssh agent to the remote host;
+    /// equivalent to `ForwardAgent yes`
+    #[serde(default)]
+    pub forward_agent: bool,
+
     /// The username to use for authenticating with the remote host
     pub username: Option<String>,
 
@@ -46,9 +51,89 @@ pub struct SshDomain {
     /// ssh_config option values
     #[serde(default)]
     pub ssh_option: HashMap<String, String>,
+
+    /// Ports on this host to forward to the remote host, like `ssh -L`
+    #[serde(default)]
+    pub local_forwards: Vec<SshForward>,
+
+    /// Ports on the remote host to forward to this host, like `ssh -R`
+    #[serde(default)]
+    pub remote_forwards: Vec<SshForward>,
+
+    /// How often, in seconds, to send a keepalive to the remote host
+    /// when nothing has been received from it; equivalent to
+    /// `ServerAliveInterval`.  0 disables the keepalives.
+    pub server_alive_interval: Option<u64>,
+
+    /// How many keepalives can go unanswered before the connection
+    /// is considered to be dead; equivalent to `ServerAliveCountMax`
+    pub server_alive_count_max: Option<u64>,
+
+    /// Whether and how to reconnect after losing the connection.
+    /// Reconnecting is disabled unless this is set, because it may
+    /// need to prompt for authentication again.
+    pub auto_reconnect: Option<AutoReconnect>,
+
+    /// Whether to ask the server to compress the PDUs of this
+    /// connection, which helps over slow links.  The default is true.
+    #[serde(default = "default_true")]
+    pub compression: bool,
+
+    /// Whether to echo what is typed into the panes of this domain
+    /// before the server does
+    #[serde(default)]
+    pub predictive_echo: PredictiveEcho,
+
+    /// The round trip time of key presses, in milliseconds, from which
+    /// `predictive_echo = "Auto"` echoes locally, and from which the
+    /// locally echoed text is underlined until the server confirms it
+    #[serde(default = "default_local_echo_threshold_ms")]
+    pub local_echo_threshold_ms: u64,
 }
 impl_lua_conversion!(SshDomain);
 
+/// A port forward that is set up when an ssh domain connects.
+/// The connections accepted on `bind` are connected to `target`
+/// from the other side of the connection.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct SshForward {
+    /// The address to listen on, as "host:port" or just "port",
+    /// which listens on the loopback interface
+    pub bind: String,
+
+    /// The "host:port" to connect to
+    pub target: String,
+}
+impl_lua_conversion!(SshForward);
+
+impl SshForward {
+    /// Returns the host and port to listen on
+    pub fn bind_host_and_port(&self) -> anyhow::Result<(String, u16)> {
+        match self.bind.parse::<u16>() {
+            Ok(port) => Ok(("127.0.0.1".to_string(), port)),
+            Err(_) => split_host_and_port(&self.bind)
+                .with_context(|| format!("invalid forward bind address `{}`", self.bind)),
+        }
+    }
+
+    /// Returns the host and port to connect to
+    pub fn target_host_and_port(&self) -> anyhow::Result<(String, u16)> {
+        split_host_and_port(&self.target)
+            .with_context(|| format!("invalid forward target `{}`", self.target))
+    }
+}
+
+/// Splits "host:port", where the host may be a bracketed IPv6 address
+fn split_host_and_port(s: &str) -> anyhow::Result<(String, u16)> {
+    let idx = s.rfind(':').ok_or_else(|| anyhow!("expected host:port"))?;
+    let host = s[..idx].trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        bail!("expected host:port");
+    }
+    let port = s[idx + 1..].parse::<u16>()?;
+    Ok((host.to_string(), port))
+}
+
 #[derive(Clone, Debug)]
 pub struct SshParameters {
     pub username: Option<String>,
@@ -95,3 +180,34 @@ impl FromStr for SshParameters {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn forward_addresses() {
+        let forward = SshForward {
+            bind: "8080".to_string(),
+            target: "localhost:80".to_string(),
+        };
+        assert_eq!(
+            forward.bind_host_and_port().unwrap(),
+            ("127.0.0.1".to_string(), 8080)
+        );
+        assert_eq!(
+            forward.target_host_and_port().unwrap(),
+            ("localhost".to_string(), 80)
+        );
+
+        let forward = SshForward {
+            bind: "[::1]:8080".to_string(),
+            target: "db".to_string(),
+        };
+        assert_eq!(
+            forward.bind_host_and_port().unwrap(),
+            ("::1".to_string(), 8080)
+        );
+        assert!(forward.target_host_and_port().is_err());
+    }
+}
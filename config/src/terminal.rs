@@ -44,6 +44,10 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         self.configuration().scrollback_lines
     }
 
+    fn scrollback_max_bytes(&self) -> Option<usize> {
+        self.configuration().scrollback_max_bytes
+    }
+
     fn hyperlink_rules(&self) -> (usize, Vec<HyperlinkRule>) {
         let config = self.configuration();
         (config.generation(), config.hyperlink_rules.clone())
@@ -71,6 +75,10 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         self.configuration().enable_kitty_graphics
     }
 
+    fn sixel_scrolling(&self) -> bool {
+        self.configuration().sixel_scrolling
+    }
+
     fn canonicalize_pasted_newlines(&self) -> bool {
         self.configuration().canonicalize_pasted_newlines
     }
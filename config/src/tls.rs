@@ -22,9 +22,34 @@ pub struct TlsDomainServer {
     /// to the trust store.
     #[serde(default)]
     pub pem_root_certs: Vec<PathBuf>,
+
+    /// A warning is logged when the certificate expires within
+    /// this many days
+    #[serde(default = "default_certificate_expiry_warning_days")]
+    pub certificate_expiry_warning_days: u64,
 }
 impl_lua_conversion!(TlsDomainServer);
 
+fn default_certificate_expiry_warning_days() -> u64 {
+    14
+}
+
+impl TlsDomainServer {
+    /// The files from which the certificates and key of the listener
+    /// are loaded.  These are watched along with the configuration
+    /// file, so that a rotated certificate is picked up without
+    /// restarting the server.
+    pub fn certificate_paths(&self) -> Vec<PathBuf> {
+        self.pem_private_key
+            .iter()
+            .chain(self.pem_cert.iter())
+            .chain(self.pem_ca.iter())
+            .chain(self.pem_root_certs.iter())
+            .cloned()
+            .collect()
+    }
+}
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct TlsDomainClient {
     /// The name of this specific domain.  Must be unique amongst
@@ -82,6 +107,27 @@ pub struct TlsDomainClient {
 
     /// The path to the wezterm binary on the remote host
     pub remote_wezterm_path: Option<String>,
+
+    /// Whether and how to reconnect after losing the connection.
+    /// Reconnecting is enabled with the default settings unless
+    /// this is set.
+    pub auto_reconnect: Option<AutoReconnect>,
+
+    /// Whether to ask the server to compress the PDUs of this
+    /// connection, which helps over slow links.  The default is true.
+    #[serde(default = "default_true")]
+    pub compression: bool,
+
+    /// Whether to echo what is typed into the panes of this domain
+    /// before the server does
+    #[serde(default)]
+    pub predictive_echo: PredictiveEcho,
+
+    /// The round trip time of key presses, in milliseconds, from which
+    /// `predictive_echo = "Auto"` echoes locally, and from which the
+    /// locally echoed text is underlined until the server confirms it
+    #[serde(default = "default_local_echo_threshold_ms")]
+    pub local_echo_threshold_ms: u64,
 }
 impl_lua_conversion!(TlsDomainClient);
 
@@ -1,4 +1,5 @@
 use serde::{Deserializer, Serialize, Serializer};
+use std::time::Duration;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DefaultUnit {
@@ -186,3 +187,136 @@ where
 {
     de_dimension(DefaultUnit::Cells, deserializer)
 }
+
+pub fn de_opt_pixels<'de, D>(deserializer: D) -> Result<Option<Dimension>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    de_dimension(DefaultUnit::Pixels, deserializer).map(Some)
+}
+
+/// Parses a duration such as "90s", "30m", "1h" or "2d"; a bare
+/// number is a number of seconds
+fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value.parse().ok()?;
+    let scale = match unit.trim() {
+        "" | "s" => 1.,
+        "m" => 60.,
+        "h" => 60. * 60.,
+        "d" => 24. * 60. * 60.,
+        _ => return None,
+    };
+    let secs = value * scale;
+    if secs.is_finite() {
+        Some(Duration::from_secs_f64(secs))
+    } else {
+        None
+    }
+}
+
+struct DurationVisitor;
+
+impl<'de> serde::de::Visitor<'de> for DurationVisitor {
+    type Value = Option<Duration>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a number of seconds, or a string like '30m' or '1h'")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(Some(Duration::from_secs(value)))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        if value < 0 {
+            return Err(E::custom(format!("expected a duration, but got {}", value)));
+        }
+        Ok(Some(Duration::from_secs(value as u64)))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        parse_duration(s).map(Some).ok_or_else(|| {
+            E::custom(format!(
+                "expected either a number of seconds or a string of \
+                    the form '30m' where 'm' is a unit and can be one \
+                    of 's', 'm', 'h' or 'd', but got {}",
+                s
+            ))
+        })
+    }
+}
+
+/// Deserializes an optional duration, given either as a number of
+/// seconds or as a string with a unit, such as "30m" or "1h"
+pub fn de_opt_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(DurationVisitor)
+}
+
+pub fn ser_opt_duration<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(duration) => serializer.serialize_str(&format!("{}s", duration.as_secs_f64())),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Some(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration(" 1h "), Some(Duration::from_secs(60 * 60)));
+        assert_eq!(parse_duration("1.5h"), Some(Duration::from_secs(90 * 60)));
+        assert_eq!(
+            parse_duration("2 d"),
+            Some(Duration::from_secs(48 * 60 * 60))
+        );
+        assert_eq!(parse_duration("1w"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("-1h"), None);
+    }
+}
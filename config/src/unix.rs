@@ -48,6 +48,22 @@ pub struct UnixDomain {
 
     #[serde(default = "default_write_timeout")]
     pub write_timeout: Duration,
+
+    /// Whether to ask the server to compress the PDUs of this
+    /// connection, which helps over slow links.  The default is true.
+    #[serde(default = "default_true")]
+    pub compression: bool,
+
+    /// Whether to echo what is typed into the panes of this domain
+    /// before the server does
+    #[serde(default)]
+    pub predictive_echo: PredictiveEcho,
+
+    /// The round trip time of key presses, in milliseconds, from which
+    /// `predictive_echo = "Auto"` echoes locally, and from which the
+    /// locally echoed text is underlined until the server confirms it
+    #[serde(default = "default_local_echo_threshold_ms")]
+    pub local_echo_threshold_ms: u64,
 }
 impl_lua_conversion!(UnixDomain);
 
@@ -63,6 +79,9 @@ impl Default for UnixDomain {
             read_timeout: default_read_timeout(),
             write_timeout: default_write_timeout(),
             proxy_command: None,
+            compression: true,
+            predictive_echo: PredictiveEcho::default(),
+            local_echo_threshold_ms: default_local_echo_threshold_ms(),
         }
     }
 }
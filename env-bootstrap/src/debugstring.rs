@@ -0,0 +1,54 @@
+//! On Windows, a GUI subsystem executable has no stderr, so this
+//! module provides a logger that sends records to the debugger via
+//! `OutputDebugStringW`, which allows tools such as DebugView or
+//! WinDbg to observe the log in real time.
+use env_logger::filter::Filter;
+use log::{Metadata, Record};
+use winapi::um::debugapi::OutputDebugStringW;
+
+/// OutputDebugString silently truncates long messages, as the shared
+/// buffer used to pass them to the debugger is 4096 bytes, so longer
+/// records are emitted in chunks of this many UTF-16 code units.
+const MAX_CHUNK: usize = 1024;
+
+pub struct DebugStringLogger {
+    filter: Filter,
+}
+
+impl DebugStringLogger {
+    pub fn new(filter: Filter) -> Self {
+        Self { filter }
+    }
+}
+
+fn output_debug_string(text: &str) {
+    let wide: Vec<u16> = text.encode_utf16().collect();
+    let mut start = 0;
+    while start < wide.len() {
+        let mut end = (start + MAX_CHUNK).min(wide.len());
+        // Don't split a surrogate pair across chunks
+        if end < wide.len() && (0xD800..0xDC00).contains(&wide[end - 1]) {
+            end -= 1;
+        }
+        let mut chunk = wide[start..end].to_vec();
+        chunk.push(0);
+        unsafe {
+            OutputDebugStringW(chunk.as_ptr());
+        }
+        start = end;
+    }
+}
+
+impl log::Log for DebugStringLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.filter.matches(record) {
+            output_debug_string(&crate::logfile::format_record(record));
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -1,3 +1,7 @@
+#[cfg(windows)]
+mod debugstring;
+mod logfile;
+mod lua;
 pub mod ringlog;
 pub use ringlog::setup_logger;
 use std::path::{Path, PathBuf};
@@ -135,4 +139,7 @@ pub fn bootstrap() {
     fixup_appimage();
 
     setup_logger();
+    ringlog::register_panic_hook();
+
+    config::lua::add_context_setup_func(lua::register);
 }
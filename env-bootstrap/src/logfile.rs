@@ -0,0 +1,255 @@
+//! A sink that appends formatted log records to a file.
+//! Problems writing to the file are reported directly to stderr
+//! rather than via the `log` crate, to avoid recursing into the
+//! logger while it is already busy logging.
+use chrono::prelude::*;
+use log::{Level, Record};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long to wait before trying to re-open the file after
+/// a failure, and how often to check whether it has been
+/// removed or renamed out from under us.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Rotate the file once it exceeds this many bytes, unless overridden
+/// by `set_rotation`
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// The number of rotated generations to keep, unless overridden
+/// by `set_rotation`
+pub const DEFAULT_KEEP: usize = 3;
+
+lazy_static::lazy_static! {
+    static ref SINK: Mutex<Option<FileSink>> = Mutex::new(None);
+    static ref ROTATION: Mutex<(u64, usize)> = Mutex::new((DEFAULT_MAX_SIZE, DEFAULT_KEEP));
+}
+
+struct FileSink {
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+    next_check: Instant,
+    reported_error: bool,
+    /// The size of the file, including data that is still buffered
+    size: u64,
+    /// 0 disables rotation
+    max_size: u64,
+    keep: usize,
+}
+
+impl FileSink {
+    fn new(path: PathBuf, max_size: u64, keep: usize) -> Self {
+        let mut sink = Self {
+            path,
+            file: None,
+            next_check: Instant::now(),
+            reported_error: false,
+            size: 0,
+            max_size,
+            keep,
+        };
+        sink.open();
+        sink
+    }
+
+    fn open(&mut self) {
+        match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+        {
+            Ok(file) => {
+                self.size = file.metadata().map(|m| m.len()).unwrap_or(0);
+                self.file.replace(BufWriter::new(file));
+                self.reported_error = false;
+            }
+            Err(err) => self.failed(err),
+        }
+        self.next_check = Instant::now() + CHECK_INTERVAL;
+    }
+
+    fn failed(&mut self, err: std::io::Error) {
+        if !self.reported_error {
+            let _ = writeln!(
+                std::io::stderr(),
+                "wezterm: unable to write to log file {}: {:#}",
+                self.path.display(),
+                err
+            );
+            self.reported_error = true;
+        }
+        self.file.take();
+        self.next_check = Instant::now() + CHECK_INTERVAL;
+    }
+
+    /// Periodically re-open the file if we previously failed
+    /// to write to it, or if it has been renamed or deleted.
+    fn check(&mut self) {
+        let now = Instant::now();
+        if now < self.next_check {
+            return;
+        }
+        if self.file.is_some() && !self.path.exists() {
+            self.flush();
+            self.file.take();
+        }
+        if self.file.is_none() {
+            self.open();
+        } else {
+            self.next_check = now + CHECK_INTERVAL;
+        }
+    }
+
+    /// Returns the path used for the specified rotated generation,
+    /// eg: `wezterm.log.1`
+    fn generation_path(&self, generation: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{}", generation));
+        PathBuf::from(name)
+    }
+
+    /// Shuffle the existing generations up by one and move the
+    /// current file into the first generation.
+    /// The file must be closed for this to work on Windows, so
+    /// we flush and close it first and re-open it afterwards.
+    fn rotate(&mut self) {
+        self.flush();
+        self.file.take();
+
+        if self.keep == 0 {
+            let _ = std::fs::remove_file(&self.path);
+        } else {
+            let _ = std::fs::remove_file(self.generation_path(self.keep));
+            for generation in (1..self.keep).rev() {
+                let _ = std::fs::rename(
+                    self.generation_path(generation),
+                    self.generation_path(generation + 1),
+                );
+            }
+            if let Err(err) = std::fs::rename(&self.path, self.generation_path(1)) {
+                let _ = writeln!(
+                    std::io::stderr(),
+                    "wezterm: unable to rotate log file {}: {:#}",
+                    self.path.display(),
+                    err
+                );
+            }
+        }
+
+        self.open();
+    }
+
+    fn write_line(&mut self, line: &str) -> bool {
+        self.check();
+        if self.file.is_some()
+            && self.max_size > 0
+            && self.size > 0
+            && self.size + line.len() as u64 > self.max_size
+        {
+            self.rotate();
+        }
+        if let Some(file) = self.file.as_mut() {
+            if let Err(err) = file.write_all(line.as_bytes()) {
+                self.failed(err);
+                return false;
+            }
+            self.size += line.len() as u64;
+            return true;
+        }
+        false
+    }
+
+    fn flush(&mut self) {
+        if let Some(file) = self.file.as_mut() {
+            if let Err(err) = file.flush() {
+                self.failed(err);
+            }
+        }
+    }
+}
+
+pub(crate) fn format_record(record: &Record) -> String {
+    let now = crate::ringlog::format_timestamp(&Utc::now(), "%Y-%m-%dT%H:%M:%S%.3f");
+    if crate::ringlog::log_thread_names() {
+        let thread = std::thread::current();
+        format!(
+            "{} {:<5} {} {} > {}\n",
+            now,
+            record.level(),
+            thread.name().unwrap_or("<unnamed>"),
+            record.target(),
+            record.args()
+        )
+    } else {
+        format!(
+            "{} {:<5} {} > {}\n",
+            now,
+            record.level(),
+            record.target(),
+            record.args()
+        )
+    }
+}
+
+/// Changes the file that log records are appended to.
+/// Passing `None` closes and stops using the current file.
+pub fn set_path(path: Option<&Path>) {
+    let mut sink = SINK.lock().unwrap();
+    if sink.as_ref().map(|s| s.path.as_path()) == path {
+        return;
+    }
+    if let Some(sink) = sink.as_mut() {
+        sink.flush();
+    }
+    let (max_size, keep) = *ROTATION.lock().unwrap();
+    *sink = path.map(|p| FileSink::new(p.to_path_buf(), max_size, keep));
+}
+
+/// Configures rotation; once the file exceeds `max_size` bytes it is
+/// renamed with a `.1` suffix and up to `keep` such generations are
+/// retained.  A `max_size` of 0 disables rotation.
+pub fn set_rotation(max_size: u64, keep: usize) {
+    *ROTATION.lock().unwrap() = (max_size, keep);
+    if let Some(sink) = SINK.lock().unwrap().as_mut() {
+        sink.max_size = max_size;
+        sink.keep = keep;
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    SINK.lock().unwrap().as_ref().map(|s| s.path.clone())
+}
+
+pub fn write(record: &Record) {
+    let mut sink = SINK.lock().unwrap();
+    if let Some(sink) = sink.as_mut() {
+        let line = format_record(record);
+        if sink.write_line(&line) && record.level() <= Level::Warn {
+            // Make sure that problems are visible even if
+            // we are about to crash or be killed
+            sink.flush();
+        }
+    }
+}
+
+/// Like `write`, but gives up rather than waiting if the sink is busy.
+/// This is used when recording panics, as the panic may have been
+/// raised while the sink was locked.
+pub fn try_write(record: &Record) {
+    if let Ok(mut sink) = SINK.try_lock() {
+        if let Some(sink) = sink.as_mut() {
+            let line = format_record(record);
+            if sink.write_line(&line) {
+                sink.flush();
+            }
+        }
+    }
+}
+
+pub fn flush() {
+    if let Some(sink) = SINK.lock().unwrap().as_mut() {
+        sink.flush();
+    }
+}
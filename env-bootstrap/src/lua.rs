@@ -0,0 +1,58 @@
+//! Exposes the captured log ring to the lua config
+use crate::ringlog;
+use config::lua::get_or_create_module;
+use log::Level;
+use mlua::{Lua, Table};
+
+pub fn register(lua: &Lua) -> anyhow::Result<()> {
+    let wezterm_mod = get_or_create_module(lua, "wezterm")?;
+    wezterm_mod.set(
+        "clear_log_history",
+        lua.create_function(|_, ()| {
+            ringlog::clear_entries();
+            Ok(())
+        })?,
+    )?;
+    wezterm_mod.set(
+        "set_log_level",
+        lua.create_function(|_, spec: String| {
+            ringlog::set_stderr_filters(&spec);
+            Ok(())
+        })?,
+    )?;
+    wezterm_mod.set("log_history", lua.create_function(log_history)?)?;
+    Ok(())
+}
+
+/// Returns the captured log entries as an array of tables.
+/// `opts` may specify `min_level` (eg: "Warn") and `max_count`
+/// to limit the results to the most recent matching entries.
+fn log_history<'lua>(lua: &'lua Lua, opts: Option<Table<'lua>>) -> mlua::Result<Table<'lua>> {
+    let mut min_level = Level::Trace;
+    let mut max_count = None;
+    if let Some(opts) = opts {
+        if let Some(level) = opts.get::<_, Option<String>>("min_level")? {
+            min_level = level.parse().map_err(|_| {
+                mlua::Error::external(format!("log_history: invalid min_level {}", level))
+            })?;
+        }
+        max_count = opts.get::<_, Option<usize>>("max_count")?;
+    }
+
+    let entries = match max_count {
+        Some(count) => ringlog::get_recent_entries(min_level, count),
+        None => ringlog::get_entries_filtered(min_level, None).0,
+    };
+
+    let result = lua.create_table()?;
+    for (idx, entry) in entries.into_iter().enumerate() {
+        let item = lua.create_table()?;
+        item.set("level", entry.level.as_str())?;
+        item.set("target", entry.target)?;
+        item.set("msg", entry.msg)?;
+        item.set("timestamp", entry.timestamp.to_rfc3339())?;
+        item.set("seq", entry.seq)?;
+        result.set(idx + 1, item)?;
+    }
+    Ok(result)
+}
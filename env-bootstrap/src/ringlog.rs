@@ -3,48 +3,253 @@
 //! a pretty logger on stderr.
 //! This allows other code to collect the ring buffer and display it
 //! within the application.
+//! To keep the cost of logging low, entries are passed to the ring
+//! via a bounded queue that is drained by a background thread;
+//! the functions that read the ring drain the queue first so that
+//! they always observe the most recent entries.
+use crate::logfile;
 use chrono::prelude::*;
+use crossbeam::channel::{bounded, Receiver as QueueReceiver, Sender as QueueSender};
+use env_logger::filter::Filter;
 use log::{Level, LevelFilter, Record};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// The number of entries retained per level unless overridden
+/// via `WEZTERM_LOG_RING_SIZE` or `set_ring_capacity`.
+pub const DEFAULT_RING_CAPACITY: usize = 16;
+
+/// Identical consecutive messages logged within this window
+/// are collapsed into a single entry
+const REPEAT_WINDOW_SECONDS: i64 = 5;
+
+/// How many entries may be waiting to be moved into the ring
+/// before new entries are dropped
+const QUEUE_CAPACITY: usize = 1024;
+
+const LEVELS: &[Level] = &[
+    Level::Error,
+    Level::Warn,
+    Level::Info,
+    Level::Debug,
+    Level::Trace,
+];
 
 lazy_static::lazy_static! {
     static ref RINGS: Mutex<Rings> = Mutex::new(Rings::new());
+    static ref OBSERVERS: Mutex<Observers> = Mutex::new(Observers::default());
+    /// When set, controls which records are captured into the ring,
+    /// independently of what is shown on stderr.
+    static ref RING_FILTER: RwLock<Option<Filter>> = RwLock::new(None);
+    /// The max level required by the stderr logger
+    static ref PRETTY_MAX_LEVEL: Mutex<LevelFilter> = Mutex::new(LevelFilter::Info);
+    static ref LOGGER: Logger = Logger::new();
+    static ref QUEUE: (QueueSender<Entry>, QueueReceiver<Entry>) = bounded(QUEUE_CAPACITY);
+    static ref TIMESTAMP_STYLE: RwLock<TimestampStyle> = RwLock::new(TimestampStyle::default());
 }
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+/// Controls how timestamps are displayed by the debug overlay
+/// and the log file
+#[derive(Default)]
+struct TimestampStyle {
+    utc: bool,
+    /// An strftime format that overrides the default
+    /// format chosen by the caller
+    format: Option<String>,
+}
+
+/// The number of entries dropped because the queue was full,
+/// indexed by `Level as usize`
+static QUEUE_DROPPED: [AtomicU64; 6] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+/// Allows skipping the copy of each entry when nobody is listening
+static HAVE_OBSERVERS: AtomicBool = AtomicBool::new(false);
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+fn current_thread_id() -> u64 {
+    THREAD_ID.with(|id| *id)
+}
+
+/// Returns true if `WEZTERM_LOG_THREADS=1` requests that the thread
+/// name be included in the stderr and log file output
+pub(crate) fn log_thread_names() -> bool {
+    lazy_static::lazy_static! {
+        static ref ENABLED: bool = std::env::var("WEZTERM_LOG_THREADS")
+            .map(|s| s == "1")
+            .unwrap_or(false);
+    }
+    *ENABLED
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Entry {
+    pub timestamp: DateTime<Utc>,
+    /// The same instant as `timestamp`, in the local timezone
+    #[deprecated(note = "use `timestamp`, or `format_timestamp` for display")]
+    #[serde(rename = "local_timestamp")]
     pub then: DateTime<Local>,
+    /// Monotonically increasing across all levels; breaks ties
+    /// between entries logged within the same timestamp and
+    /// allows detecting gaps caused by eviction.
+    pub seq: u64,
+    #[serde(with = "level_serde")]
     pub level: Level,
     pub target: String,
+    #[serde(rename = "message")]
     pub msg: String,
+    pub module_path: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    /// The number of times that this message was logged in close
+    /// succession; `timestamp` and `seq` reflect the most recent occurrence
+    pub repeats: u32,
+    pub thread_name: Option<String>,
+    /// Identifies the logging thread; this is assigned by this module
+    /// as std doesn't provide a stable numeric thread id
+    pub thread_id: u64,
+}
+
+/// `log::Level` only implements serde when the `log/serde` feature
+/// is enabled; we represent it using its name instead
+mod level_serde {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(level: &Level, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(level.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Level, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid log level {}", name)))
+    }
 }
 
 struct LevelRing {
     entries: Vec<Entry>,
     first: usize,
     last: usize,
+    /// The number of entries that were evicted to make room
+    dropped: u64,
 }
 
-impl LevelRing {
-    fn new(level: Level) -> Self {
-        let mut entries = vec![];
-        let now = Local::now();
-        for _ in 0..16 {
-            entries.push(Entry {
-                then: now,
-                level,
-                target: String::new(),
-                msg: String::new(),
-            });
+/// Describes the state of the ring for a given level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingStats {
+    pub capacity: usize,
+    pub len: usize,
+    /// The number of entries that were evicted since the
+    /// ring was last cleared
+    pub dropped: u64,
+}
+
+impl Entry {
+    #[allow(deprecated)]
+    fn from_record(record: &Record) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            timestamp,
+            then: timestamp.with_timezone(&Local),
+            // Assigned by `Rings::push`
+            seq: 0,
+            level: record.level(),
+            target: record.target().to_string(),
+            msg: record.args().to_string(),
+            module_path: record.module_path().map(|s| s.to_string()),
+            file: record.file().map(|s| s.to_string()),
+            line: record.line(),
+            repeats: 1,
+            thread_name: std::thread::current().name().map(|s| s.to_string()),
+            thread_id: current_thread_id(),
+        }
+    }
+
+    /// Formats the timestamp according to `log_timestamps_utc` and
+    /// `log_timestamp_format`, using `default_format` if no format
+    /// has been configured
+    pub fn format_timestamp(&self, default_format: &str) -> String {
+        format_timestamp(&self.timestamp, default_format)
+    }
+
+    #[allow(deprecated)]
+    fn placeholder(level: Level) -> Self {
+        let timestamp = Utc::now();
+        Self {
+            timestamp,
+            then: timestamp.with_timezone(&Local),
+            seq: 0,
+            level,
+            target: String::new(),
+            msg: String::new(),
+            module_path: None,
+            file: None,
+            line: None,
+            repeats: 1,
+            thread_name: None,
+            thread_id: 0,
         }
+    }
+}
+
+impl LevelRing {
+    fn new(level: Level, capacity: usize) -> Self {
+        // One slot is always kept free so that first == last
+        // unambiguously means that the ring is empty.
+        let capacity = capacity.max(1);
+        let entries = vec![Entry::placeholder(level); capacity + 1];
         Self {
             entries,
             first: 0,
             last: 0,
+            dropped: 0,
         }
     }
 
+    fn capacity(&self) -> usize {
+        self.entries.len() - 1
+    }
+
+    /// Change the number of entries retained by the ring.
+    /// The existing entries are preserved in order; if the
+    /// new capacity is smaller, the most recent entries are kept.
+    fn set_capacity(&mut self, level: Level, capacity: usize) {
+        let capacity = capacity.max(1);
+        if capacity == self.capacity() {
+            return;
+        }
+
+        let mut entries = Vec::with_capacity(capacity + 1);
+        self.append_to_vec(&mut entries);
+        if entries.len() > capacity {
+            let excess = entries.len() - capacity;
+            entries.drain(0..excess);
+            self.dropped += excess as u64;
+        }
+        let last = entries.len();
+        entries.resize(capacity + 1, Entry::placeholder(level));
+
+        self.entries = entries;
+        self.first = 0;
+        self.last = last;
+    }
+
     // Returns the number of entries in the ring
     fn len(&self) -> usize {
         if self.last >= self.first {
@@ -65,14 +270,13 @@ impl LevelRing {
     }
 
     fn push(&mut self, entry: Entry) {
-        if self.len() == self.entries.len() {
+        if self.len() == self.capacity() {
             // We are full; effectively pop the first entry to
             // make room
-            self.entries[self.first] = entry;
             self.first = self.rolling_inc(self.first);
-        } else {
-            self.entries[self.last] = entry;
+            self.dropped += 1;
         }
+        self.entries[self.last] = entry;
         self.last = self.rolling_inc(self.last);
     }
 
@@ -84,25 +288,91 @@ impl LevelRing {
             target.extend_from_slice(&self.entries[..self.last]);
         }
     }
+
+    fn last_mut(&mut self) -> Option<&mut Entry> {
+        if self.len() == 0 {
+            None
+        } else if self.last == 0 {
+            self.entries.last_mut()
+        } else {
+            Some(&mut self.entries[self.last - 1])
+        }
+    }
+
+    fn clear(&mut self) {
+        self.first = 0;
+        self.last = 0;
+        self.dropped = 0;
+    }
+
+    fn stats(&self) -> RingStats {
+        RingStats {
+            capacity: self.capacity(),
+            len: self.len(),
+            dropped: self.dropped,
+        }
+    }
+
+    /// Iterates the entries from oldest to newest
+    fn iter(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        let (head, tail) = if self.last >= self.first {
+            (&self.entries[self.first..self.last], &self.entries[0..0])
+        } else {
+            (&self.entries[self.first..], &self.entries[..self.last])
+        };
+        head.iter().chain(tail.iter())
+    }
 }
 
 struct Rings {
     rings: HashMap<Level, LevelRing>,
+    latest_seq: u64,
+    /// The number of times that entries have been cleared
+    clears: u64,
 }
 
 impl Rings {
     fn new() -> Self {
         let mut rings = HashMap::new();
-        for level in &[
-            Level::Error,
-            Level::Warn,
-            Level::Info,
-            Level::Debug,
-            Level::Trace,
-        ] {
-            rings.insert(*level, LevelRing::new(*level));
+        for level in LEVELS {
+            rings.insert(*level, LevelRing::new(*level, DEFAULT_RING_CAPACITY));
+        }
+        Self {
+            rings,
+            latest_seq: 0,
+            clears: 0,
+        }
+    }
+
+    fn set_capacity(&mut self, level: Level, capacity: usize) {
+        if let Some(ring) = self.rings.get_mut(&level) {
+            ring.set_capacity(level, capacity);
+        }
+    }
+
+    fn capacity(&self, level: Level) -> usize {
+        self.rings.get(&level).map(LevelRing::capacity).unwrap_or(0)
+    }
+
+    fn stats(&self) -> HashMap<Level, RingStats> {
+        self.rings
+            .iter()
+            .map(|(level, ring)| {
+                let mut stats = ring.stats();
+                stats.dropped += QUEUE_DROPPED[*level as usize].load(Ordering::Relaxed);
+                (*level, stats)
+            })
+            .collect()
+    }
+
+    fn clear(&mut self, level: Option<Level>) {
+        self.clears += 1;
+        for (ring_level, ring) in self.rings.iter_mut() {
+            if level.map(|l| l == *ring_level).unwrap_or(true) {
+                ring.clear();
+                QUEUE_DROPPED[*ring_level as usize].store(0, Ordering::Relaxed);
+            }
         }
-        Self { rings }
     }
 
     fn get_entries(&self) -> Vec<Entry> {
@@ -113,79 +383,622 @@ impl Rings {
         results
     }
 
-    fn log(&mut self, record: &Record) {
-        if let Some(ring) = self.rings.get_mut(&record.level()) {
-            ring.push(Entry {
-                then: Local::now(),
-                level: record.level(),
-                target: record.target().to_string(),
-                msg: record.args().to_string(),
-            });
+    fn get_matching_entries<F: Fn(&Entry) -> bool>(&self, pred: F) -> Vec<Entry> {
+        let mut results = vec![];
+        for ring in self.rings.values() {
+            results.extend(ring.iter().filter(|entry| pred(entry)).cloned());
+        }
+        results
+    }
+
+    /// Returns up to `count` of the most recent entries whose level is
+    /// at least as severe as `min_level`, sorted by time and then by
+    /// sequence number.
+    /// Each ring is ordered by sequence number, so only the newest `count`
+    /// entries of each ring need to be considered.
+    fn get_recent_entries(&self, min_level: Level, count: usize) -> Vec<Entry> {
+        let mut results = vec![];
+        for (level, ring) in &self.rings {
+            if *level > min_level {
+                continue;
+            }
+            results.extend(ring.iter().rev().take(count).cloned());
+        }
+        results.sort();
+        let excess = results.len().saturating_sub(count);
+        results.drain(0..excess);
+        results
+    }
+
+    /// Returns the sequence number of the most recently recorded
+    /// entry, or 0 if nothing has been recorded
+    fn cursor(&self) -> u64 {
+        self.latest_seq
+    }
+
+    /// Records the entry, returning a copy of it if `want_copy` is true.
+    /// The sequence number is assigned here rather than when the entry
+    /// is created, as entries from different threads may be queued out
+    /// of order; this keeps the sequence in the order that entries
+    /// become visible to `get_entries_since`.
+    #[allow(deprecated)]
+    fn push(&mut self, mut entry: Entry, want_copy: bool) -> Option<Entry> {
+        let ring = self.rings.get_mut(&entry.level)?;
+        self.latest_seq += 1;
+        entry.seq = self.latest_seq;
+
+        if let Some(prior) = ring.last_mut() {
+            if prior.msg == entry.msg
+                && prior.target == entry.target
+                && entry.timestamp.signed_duration_since(prior.timestamp)
+                    < chrono::Duration::seconds(REPEAT_WINDOW_SECONDS)
+            {
+                prior.repeats = prior.repeats.saturating_add(1);
+                prior.timestamp = entry.timestamp;
+                prior.then = entry.then;
+                // Take on the new sequence number so that incremental
+                // consumers see the updated count
+                prior.seq = entry.seq;
+                return if want_copy { Some(prior.clone()) } else { None };
+            }
+        }
+
+        let copy = if want_copy { Some(entry.clone()) } else { None };
+        ring.push(entry);
+        copy
+    }
+}
+
+/// Queues an entry to be moved into the ring.
+/// This never blocks; if the queue is full, the entry is
+/// counted as dropped.
+fn enqueue(entry: Entry) {
+    let level = entry.level;
+    if QUEUE.0.try_send(entry).is_err() {
+        QUEUE_DROPPED[level as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Moves any queued entries into the ring, then notifies
+/// the observers about them.  `first` is an entry that was
+/// already taken from the queue by the caller.
+fn drain_queue(first: Option<Entry>) {
+    let want_copy = HAVE_OBSERVERS.load(Ordering::Acquire);
+    let mut copies = vec![];
+    {
+        let mut rings = RINGS.lock().unwrap();
+        for entry in first.into_iter().chain(QUEUE.1.try_iter()) {
+            if let Some(copy) = rings.push(entry, want_copy) {
+                copies.push(copy);
+            }
         }
     }
+    // The lock must not be held while calling the observers,
+    // as they may log themselves
+    for entry in copies {
+        notify_observers(&entry);
+    }
+}
+
+fn start_drain_thread() {
+    let spawned = std::thread::Builder::new()
+        .name("ringlog".to_string())
+        .spawn(|| {
+            while let Ok(entry) = QUEUE.1.recv() {
+                drain_queue(Some(entry));
+            }
+        });
+    if let Err(err) = spawned {
+        // The queue will still be drained by the readers,
+        // it will just be more likely to fill up
+        let _ = writeln!(
+            std::io::stderr(),
+            "wezterm: failed to start log ring thread: {:#}",
+            err
+        );
+    }
+}
+
+/// Identifies an observer registered via `register_observer`
+pub type ObserverId = usize;
+
+enum Observer {
+    Func(Box<dyn Fn(&Entry) + Send + Sync>),
+    Channel(Mutex<Sender<Entry>>),
+}
+
+impl Observer {
+    /// Returns false if the observer is no longer interested
+    /// in receiving entries
+    fn notify(&self, entry: &Entry) -> bool {
+        match self {
+            Self::Func(func) => {
+                func(entry);
+                true
+            }
+            Self::Channel(tx) => tx.lock().unwrap().send(entry.clone()).is_ok(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Observers {
+    next_id: ObserverId,
+    observers: HashMap<ObserverId, Arc<Observer>>,
+}
+
+impl Observers {
+    fn add(&mut self, observer: Observer) -> ObserverId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.observers.insert(id, Arc::new(observer));
+        HAVE_OBSERVERS.store(true, Ordering::Release);
+        id
+    }
+
+    fn remove(&mut self, id: ObserverId) {
+        self.observers.remove(&id);
+        HAVE_OBSERVERS.store(!self.observers.is_empty(), Ordering::Release);
+    }
+}
+
+fn notify_observers(entry: &Entry) {
+    // Take a snapshot so that the observers are called without
+    // holding any lock; an observer is then free to log or
+    // to register/unregister observers itself.
+    let observers: Vec<(ObserverId, Arc<Observer>)> = OBSERVERS
+        .lock()
+        .unwrap()
+        .observers
+        .iter()
+        .map(|(id, obs)| (*id, Arc::clone(obs)))
+        .collect();
+
+    let mut dead = vec![];
+    for (id, observer) in observers {
+        if !observer.notify(entry) {
+            dead.push(id);
+        }
+    }
+
+    if !dead.is_empty() {
+        let mut observers = OBSERVERS.lock().unwrap();
+        for id in dead {
+            observers.remove(id);
+        }
+    }
+}
+
+/// Registers a function to be called for each new entry after
+/// it has been recorded in the ring.
+/// The function is called on a background thread, or on a thread
+/// that is reading the ring; it may itself log, but should avoid
+/// doing anything expensive.
+pub fn register_observer<F: Fn(&Entry) + Send + Sync + 'static>(func: F) -> ObserverId {
+    OBSERVERS
+        .lock()
+        .unwrap()
+        .add(Observer::Func(Box::new(func)))
+}
+
+/// Unregisters an observer added via `register_observer` or `subscribe`
+pub fn unregister_observer(id: ObserverId) {
+    OBSERVERS.lock().unwrap().remove(id);
+}
+
+/// Returns a channel that will receive a copy of each new entry.
+/// The subscription is dropped automatically on the next entry after
+/// the receiver has been dropped.
+pub fn subscribe() -> Receiver<Entry> {
+    let (tx, rx) = channel();
+    OBSERVERS
+        .lock()
+        .unwrap()
+        .add(Observer::Channel(Mutex::new(tx)));
+    rx
 }
 
 struct Logger {
-    pretty: Option<Box<dyn log::Log>>,
+    /// The stderr logger; this can be replaced at runtime
+    /// in order to change the filters
+    pretty: RwLock<Option<Box<dyn log::Log>>>,
 }
 
 impl Logger {
-    fn new(pretty: Option<Box<dyn log::Log>>) -> Self {
-        Self { pretty }
+    fn new() -> Self {
+        Self {
+            pretty: RwLock::new(None),
+        }
+    }
+
+    fn set_pretty(&self, pretty: Option<Box<dyn log::Log>>) {
+        *self.pretty.write().unwrap() = pretty;
+    }
+
+    /// Returns true if the stderr logger (and by extension, the
+    /// log file) should receive records with this metadata
+    fn pretty_enabled(&self, metadata: &log::Metadata) -> bool {
+        if let Some(pretty) = self.pretty.read().unwrap().as_ref() {
+            pretty.enabled(metadata)
+        } else {
+            metadata.level() <= *PRETTY_MAX_LEVEL.lock().unwrap()
+        }
     }
 }
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        if let Some(pretty) = self.pretty.as_ref() {
-            pretty.enabled(metadata)
-        } else {
-            match metadata.level() {
-                Level::Error | Level::Warn | Level::Info => true,
-                _ => false,
+        if let Some(filter) = RING_FILTER.read().unwrap().as_ref() {
+            if filter.enabled(metadata) {
+                return true;
             }
         }
+        self.pretty_enabled(metadata)
     }
 
     fn flush(&self) {
-        if let Some(pretty) = self.pretty.as_ref() {
+        drain_queue(None);
+        if let Some(pretty) = self.pretty.read().unwrap().as_ref() {
             pretty.flush()
         }
+        logfile::flush();
     }
 
     fn log(&self, record: &Record) {
-        RINGS.lock().unwrap().log(record);
-        if let Some(pretty) = self.pretty.as_ref() {
+        let capture = match RING_FILTER.read().unwrap().as_ref() {
+            Some(filter) => filter.matches(record),
+            None => true,
+        };
+        if capture {
+            enqueue(Entry::from_record(record));
+        }
+        if let Some(pretty) = self.pretty.read().unwrap().as_ref() {
             pretty.log(record);
         }
+        if self.pretty_enabled(record.metadata()) {
+            logfile::write(record);
+        }
     }
 }
 
 /// Returns the current set of log information, sorted by time
+/// and then by sequence number
 pub fn get_entries() -> Vec<Entry> {
+    drain_queue(None);
     let mut entries = RINGS.lock().unwrap().get_entries();
     entries.sort();
     entries
 }
 
-fn setup_pretty() -> (LevelFilter, Option<Box<dyn log::Log>>) {
+/// Returns the same entries as `get_entries`, serialized as a JSON
+/// array.  Timestamps are formatted as RFC3339.
+pub fn get_entries_json() -> String {
+    entries_to_json(&get_entries())
+}
+
+/// Serializes entries, such as those received from another process,
+/// in the same way as `get_entries_json`
+pub fn entries_to_json(entries: &[Entry]) -> String {
+    serde_json::to_string(entries).expect("log entries to be serializable as JSON")
+}
+
+/// Returns the entries recorded after the entry with sequence number
+/// `since`, sorted by time and then by sequence number, along with
+/// the cursor that should be passed as `since` in a subsequent call
+/// in order to resume from this point.
+/// Passing 0 returns all retained entries.
+pub fn get_entries_since(since: u64) -> (Vec<Entry>, u64) {
+    drain_queue(None);
+    let (mut entries, cursor) = {
+        let rings = RINGS.lock().unwrap();
+        let entries = rings.get_matching_entries(|entry| entry.seq > since);
+        (entries, rings.cursor())
+    };
+    entries.sort();
+    (entries, cursor)
+}
+
+/// Returns the entries whose level is at least as severe as
+/// `min_level` and, if `target_prefix` is specified, whose target starts
+/// with that prefix.  The entries are sorted by time and then sequence
+/// number and are returned along with the cursor of the most recently
+/// recorded entry, which can be passed to `get_entries_since`.
+pub fn get_entries_filtered(min_level: Level, target_prefix: Option<&str>) -> (Vec<Entry>, u64) {
+    drain_queue(None);
+    let (mut entries, cursor) = {
+        let rings = RINGS.lock().unwrap();
+        let entries = rings.get_matching_entries(|entry| {
+            entry.level <= min_level
+                && target_prefix
+                    .map(|prefix| entry.target.starts_with(prefix))
+                    .unwrap_or(true)
+        });
+        (entries, rings.cursor())
+    };
+    entries.sort();
+    (entries, cursor)
+}
+
+/// Returns up to `max_count` of the most recently recorded entries whose
+/// level is at least as severe as `min_level`, sorted by time and then
+/// sequence number.  Unlike `get_entries`, this only copies the entries
+/// that are returned, so it is suitable for calling frequently.
+pub fn get_recent_entries(min_level: Level, max_count: usize) -> Vec<Entry> {
+    drain_queue(None);
+    RINGS
+        .lock()
+        .unwrap()
+        .get_recent_entries(min_level, max_count)
+}
+
+/// Returns the capacity, length and number of evicted
+/// entries for each level
+pub fn get_stats() -> HashMap<Level, RingStats> {
+    drain_queue(None);
+    RINGS.lock().unwrap().stats()
+}
+
+/// Discards all captured entries.
+/// Sequence numbers are not reset, so entries recorded after
+/// clearing continue to sort after those seen before.
+pub fn clear_entries() {
+    drain_queue(None);
+    RINGS.lock().unwrap().clear(None);
+}
+
+/// Discards the captured entries for the specified level
+pub fn clear_level(level: Level) {
+    drain_queue(None);
+    RINGS.lock().unwrap().clear(Some(level));
+}
+
+/// Returns a count that changes whenever entries are cleared via
+/// `clear_entries` or `clear_level`.  Consumers that keep their own
+/// copy of the entries can compare it against the value seen
+/// before fetching, and discard their copy when it changes.
+pub fn clear_generation() -> u64 {
+    RINGS.lock().unwrap().clears
+}
+
+/// Changes the number of entries retained for the specified level.
+/// Existing entries are preserved; when shrinking, the most recent
+/// entries are kept.
+pub fn set_ring_capacity(level: Level, capacity: usize) {
+    RINGS.lock().unwrap().set_capacity(level, capacity);
+}
+
+/// Returns the number of entries retained for the specified level
+pub fn ring_capacity(level: Level) -> usize {
+    RINGS.lock().unwrap().capacity(level)
+}
+
+fn parse_ring_filter(spec: &str) -> Filter {
+    let mut builder = env_logger::filter::Builder::new();
+    // Start from the levels that the ring captures by default
+    // so that the spec only needs to describe the differences
+    builder.filter(None, LevelFilter::Info);
+    builder.parse(spec);
+    builder.build()
+}
+
+/// Recompute the global max level so that records required by
+/// either the stderr logger or the ring filter are generated
+fn update_max_level() {
+    let pretty = *PRETTY_MAX_LEVEL.lock().unwrap();
+    let ring = RING_FILTER
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(Filter::filter)
+        .unwrap_or(LevelFilter::Off);
+    log::set_max_level(pretty.max(ring));
+}
+
+/// Sets the filter that controls which records are captured into
+/// the ring, using the same syntax as `WEZTERM_LOG`; for example:
+/// `wezterm_ssh=trace,wgpu_core=off`.
+/// Passing an empty string restores the default behavior of
+/// capturing whatever is logged to stderr.
+pub fn set_ring_filter(spec: &str) {
+    let filter = if spec.is_empty() {
+        None
+    } else {
+        Some(parse_ring_filter(spec))
+    };
+    *RING_FILTER.write().unwrap() = filter;
+    update_max_level();
+}
+
+fn record_panic(info: &std::panic::PanicInfo) {
+    let payload = info.payload();
+    let msg = if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    };
+
+    let thread = std::thread::current();
+    let thread_name = thread.name().unwrap_or("<unnamed>");
+    let mut text = match info.location() {
+        Some(loc) => format!(
+            "thread '{}' panicked at '{}', {}:{}:{}",
+            thread_name,
+            msg,
+            loc.file(),
+            loc.line(),
+            loc.column()
+        ),
+        None => format!("thread '{}' panicked at '{}'", thread_name, msg),
+    };
+
+    let want_backtrace = std::env::var_os("RUST_BACKTRACE")
+        .map(|v| v != "0")
+        .unwrap_or(false);
+    if want_backtrace {
+        text.push_str(&format!("\n{:?}", backtrace::Backtrace::new()));
+    }
+
+    record_without_blocking(
+        &Record::builder()
+            .level(Level::Error)
+            .target("panic")
+            .file(info.location().map(|loc| loc.file()))
+            .line(info.location().map(|loc| loc.line()))
+            .args(format_args!("{}", text))
+            .build(),
+    );
+}
+
+/// Records into the ring and the log file, but not stderr.
+/// The panic may have been raised while the log file was locked,
+/// so avoid deadlocking by giving up if it is busy.
+fn record_without_blocking(record: &Record) {
+    enqueue(Entry::from_record(record));
+    logfile::try_write(record);
+}
+
+/// Installs a panic hook that records panics into the ring and the
+/// log file, using the `panic` target, and then delegates to the
+/// previously installed hook, which takes care of printing to stderr
+/// and aborting if so configured.
+/// If `RUST_BACKTRACE` is set, the backtrace is included in the entry.
+pub fn register_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        record_panic(info);
+        default_hook(info);
+    }));
+}
+
+/// Sets the file that log records are appended to, in addition to
+/// stderr.  The same records that are logged to stderr are written
+/// to the file.  Passing `None` stops logging to a file.
+pub fn set_log_file(path: Option<&Path>) {
+    logfile::set_path(path);
+}
+
+/// Returns the path to the file that log records are being appended to
+pub fn log_file_path() -> Option<PathBuf> {
+    logfile::path()
+}
+
+/// Applies the logging related options from the configuration.
+pub fn apply_config(config: &config::Config) {
+    logfile::set_rotation(config.log_file_max_size, config.log_file_keep);
+    if let Err(err) = set_timestamp_style(
+        log_utc_from_env() || config.log_timestamps_utc,
+        config.log_timestamp_format.as_deref(),
+    ) {
+        log::error!("{:#}", err);
+    }
+    // The environment takes precedence over the config file
+    if std::env::var_os("WEZTERM_LOG_FILE").is_none() {
+        set_log_file(config.log_file.as_deref());
+    }
+}
+
+/// Formats `timestamp` according to `log_timestamps_utc` and
+/// `log_timestamp_format`, using `default_format` if no format
+/// has been configured
+pub fn format_timestamp(timestamp: &DateTime<Utc>, default_format: &str) -> String {
+    let style = TIMESTAMP_STYLE.read().unwrap();
+    let format = style.format.as_deref().unwrap_or(default_format);
+    if style.utc {
+        timestamp.format(format).to_string()
+    } else {
+        timestamp.with_timezone(&Local).format(format).to_string()
+    }
+}
+
+/// Changes how timestamps are displayed.  Returns an error, leaving
+/// the style unchanged, if `format` is not a valid strftime format;
+/// chrono would otherwise panic when displaying with it.
+pub fn set_timestamp_style(utc: bool, format: Option<&str>) -> anyhow::Result<()> {
+    if let Some(format) = format {
+        if chrono::format::StrftimeItems::new(format)
+            .any(|item| item == chrono::format::Item::Error)
+        {
+            anyhow::bail!("invalid log_timestamp_format {:?}", format);
+        }
+    }
+    let mut style = TIMESTAMP_STYLE.write().unwrap();
+    style.utc = utc;
+    style.format = format.map(|s| s.to_string());
+    Ok(())
+}
+
+/// Returns true if `WEZTERM_LOG_UTC=1` requests that timestamps
+/// be displayed in UTC
+fn log_utc_from_env() -> bool {
+    std::env::var("WEZTERM_LOG_UTC")
+        .map(|s| s == "1")
+        .unwrap_or(false)
+}
+
+/// Builds the stderr logger using the same syntax as `WEZTERM_LOG`;
+/// `None` selects the default filters
+fn setup_pretty(spec: Option<&str>) -> (LevelFilter, Option<Box<dyn log::Log>>) {
     #[cfg(windows)]
     {
         use winapi::um::winbase::STD_ERROR_HANDLE;
         // Working around <https://github.com/rust-lang/rust/issues/88576>
         // wherein Rust 1.56 panics in the case that stderr is NULL.
         // That can legitimately occur in a Windows subsystem executable.
-        // We detect that here and avoid initializing the pretty env logger.
+        // We detect that here and avoid initializing the pretty env logger;
+        // the records are sent to the debugger instead.
         if unsafe { winapi::um::processenv::GetStdHandle(STD_ERROR_HANDLE).is_null() } {
-            return (LevelFilter::Info, None);
+            let mut builder = env_logger::filter::Builder::new();
+            builder.filter(None, LevelFilter::Info);
+            builder.filter(Some("wgpu_core"), LevelFilter::Error);
+            builder.filter(Some("gfx_backend_metal"), LevelFilter::Error);
+            if let Some(spec) = spec {
+                builder.parse(spec);
+            }
+            let filter = builder.build();
+            let max_level = filter.filter();
+            return (
+                max_level,
+                Some(Box::new(crate::debugstring::DebugStringLogger::new(filter))),
+            );
         }
     }
 
     let mut builder = pretty_env_logger::formatted_timed_builder();
+    if log_thread_names() {
+        // This is the same as the pretty_env_logger timed format,
+        // with the addition of the thread name
+        builder.format(|f, record| {
+            use env_logger::fmt::Color;
+
+            let mut level_style = f.style();
+            level_style.set_color(match record.level() {
+                Level::Error => Color::Red,
+                Level::Warn => Color::Yellow,
+                Level::Info => Color::Green,
+                Level::Debug => Color::Blue,
+                Level::Trace => Color::Magenta,
+            });
+            let mut target_style = f.style();
+            target_style.set_bold(true);
+            let thread = std::thread::current();
+
+            writeln!(
+                f,
+                " {} {:<5} {} {} > {}",
+                f.timestamp_millis(),
+                level_style.value(record.level()),
+                thread.name().unwrap_or("<unnamed>"),
+                target_style.value(record.target()),
+                record.args(),
+            )
+        });
+    }
     builder.filter(Some("wgpu_core"), LevelFilter::Error);
     builder.filter(Some("gfx_backend_metal"), LevelFilter::Error);
-    if let Ok(s) = std::env::var("WEZTERM_LOG") {
-        builder.parse_filters(&s);
+    if let Some(spec) = spec {
+        builder.parse_filters(spec);
     } else {
         builder.filter(None, LevelFilter::Info);
     }
@@ -197,11 +1010,243 @@ fn setup_pretty() -> (LevelFilter, Option<Box<dyn log::Log>>) {
     (max_level, Some(pretty))
 }
 
+/// Replaces the filters used by the stderr logger (and the log file)
+/// using the same syntax as `WEZTERM_LOG`; for example:
+/// `info,wezterm_gui=debug`.
+/// This takes effect immediately, without restarting.
+pub fn set_stderr_filters(spec: &str) {
+    let (max_level, pretty) = setup_pretty(Some(spec));
+    LOGGER.set_pretty(pretty);
+    *PRETTY_MAX_LEVEL.lock().unwrap() = max_level;
+    update_max_level();
+}
+
+/// Sets the level at which records are logged to stderr
+/// (and the log file), replacing any per-target filters.
+pub fn set_max_level(level: LevelFilter) {
+    set_stderr_filters(&level.to_string());
+}
+
 pub fn setup_logger() {
-    let (max_level, pretty) = setup_pretty();
-    let logger = Logger::new(pretty);
+    if let Some(capacity) = std::env::var("WEZTERM_LOG_RING_SIZE")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+    {
+        for level in LEVELS {
+            set_ring_capacity(*level, capacity);
+        }
+    }
+
+    TIMESTAMP_STYLE.write().unwrap().utc = log_utc_from_env();
+
+    let (max_level, pretty) = setup_pretty(std::env::var("WEZTERM_LOG").ok().as_deref());
+    LOGGER.set_pretty(pretty);
+
+    if let Ok(spec) = std::env::var("WEZTERM_LOG_RING") {
+        if !spec.is_empty() {
+            RING_FILTER
+                .write()
+                .unwrap()
+                .replace(parse_ring_filter(&spec));
+        }
+    }
+
+    if let Some(path) = std::env::var_os("WEZTERM_LOG_FILE") {
+        if !path.is_empty() {
+            logfile::set_path(Some(Path::new(&path)));
+        }
+    }
+
+    if log::set_logger(&*LOGGER).is_ok() {
+        *PRETTY_MAX_LEVEL.lock().unwrap() = max_level;
+        update_max_level();
+        start_drain_thread();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(msg: &str) -> Entry {
+        Entry {
+            msg: msg.to_string(),
+            ..Entry::placeholder(Level::Info)
+        }
+    }
+
+    fn msgs(ring: &LevelRing) -> Vec<String> {
+        let mut entries = vec![];
+        ring.append_to_vec(&mut entries);
+        entries.into_iter().map(|e| e.msg).collect()
+    }
+
+    fn fill(ring: &mut LevelRing, range: std::ops::Range<usize>) {
+        for i in range {
+            ring.push(entry(&i.to_string()));
+        }
+    }
+
+    fn log(rings: &mut Rings, level: Level, target: &str, msg: &str) {
+        let entry = Entry::from_record(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", msg))
+                .build(),
+        );
+        rings.push(entry, false);
+    }
+
+    #[test]
+    fn matching_entries() {
+        let mut rings = Rings::new();
+        log(&mut rings, Level::Info, "wezterm_gui", "one");
+        log(&mut rings, Level::Error, "wezterm_ssh", "two");
+        log(&mut rings, Level::Debug, "wezterm_ssh", "three");
+        assert_eq!(rings.cursor(), 3);
+
+        let mut since: Vec<u64> = rings
+            .get_matching_entries(|e| e.seq > 1)
+            .into_iter()
+            .map(|e| e.seq)
+            .collect();
+        since.sort();
+        assert_eq!(since, vec![2, 3]);
+
+        let severe: Vec<String> = rings
+            .get_matching_entries(|e| e.level <= Level::Info && e.target.starts_with("wezterm_"))
+            .into_iter()
+            .map(|e| e.msg)
+            .collect();
+        assert_eq!(severe.len(), 2);
+        assert!(severe.contains(&"one".to_string()));
+        assert!(severe.contains(&"two".to_string()));
+    }
+
+    #[test]
+    fn seq_follows_push_order() {
+        let mut rings = Rings::new();
+        let record = |msg: &str| {
+            Entry::from_record(
+                &Record::builder()
+                    .level(Level::Info)
+                    .args(format_args!("{}", msg))
+                    .build(),
+            )
+        };
+        // Simulate another thread's entry reaching the queue late
+        let early = record("early");
+        let late = record("late");
+        rings.push(late, false);
+        let cursor = rings.cursor();
+        rings.push(early, false);
+
+        let since: Vec<String> = rings
+            .get_matching_entries(|e| e.seq > cursor)
+            .into_iter()
+            .map(|e| e.msg)
+            .collect();
+        assert_eq!(since, vec!["early"]);
+    }
+
+    #[test]
+    fn invalid_timestamp_format() {
+        assert!(set_timestamp_style(false, Some("%Y-%m-%d %Q")).is_err());
+    }
+
+    #[test]
+    fn recent_entries() {
+        let mut rings = Rings::new();
+        log(&mut rings, Level::Error, "wezterm", "one");
+        log(&mut rings, Level::Info, "wezterm", "two");
+        log(&mut rings, Level::Debug, "wezterm", "three");
+        log(&mut rings, Level::Warn, "wezterm", "four");
+        log(&mut rings, Level::Error, "wezterm", "five");
+
+        let recent = |min_level, count| -> Vec<String> {
+            rings
+                .get_recent_entries(min_level, count)
+                .into_iter()
+                .map(|e| e.msg)
+                .collect()
+        };
+        assert_eq!(recent(Level::Trace, 2), vec!["four", "five"]);
+        assert_eq!(recent(Level::Info, 3), vec!["two", "four", "five"]);
+        assert_eq!(recent(Level::Error, 10), vec!["one", "five"]);
+        assert!(recent(Level::Trace, 0).is_empty());
+    }
+
+    #[test]
+    fn clear_then_push() {
+        let mut ring = LevelRing::new(Level::Info, 3);
+        fill(&mut ring, 0..5);
+        ring.clear();
+        assert_eq!(ring.len(), 0);
+        fill(&mut ring, 5..6);
+        assert_eq!(msgs(&ring), vec!["5"]);
+    }
+
+    #[test]
+    fn collapse_repeats() {
+        let mut rings = Rings::new();
+        log(&mut rings, Level::Warn, "wgpu_core", "oops");
+        log(&mut rings, Level::Warn, "wgpu_core", "oops");
+        log(&mut rings, Level::Warn, "wgpu_core", "oops");
+        log(&mut rings, Level::Warn, "wgpu_core", "different");
+        log(&mut rings, Level::Warn, "wgpu_core", "oops");
+
+        let mut entries = rings.get_entries();
+        entries.sort();
+        let summary: Vec<(String, u32, u64)> = entries
+            .into_iter()
+            .map(|e| (e.msg, e.repeats, e.seq))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("oops".to_string(), 3, 3),
+                ("different".to_string(), 1, 4),
+                ("oops".to_string(), 1, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn ring_evicts_oldest() {
+        let mut ring = LevelRing::new(Level::Info, 3);
+        fill(&mut ring, 0..5);
+        assert_eq!(ring.len(), 3);
+        assert_eq!(msgs(&ring), vec!["2", "3", "4"]);
+        assert_eq!(
+            ring.stats(),
+            RingStats {
+                capacity: 3,
+                len: 3,
+                dropped: 2
+            }
+        );
+        ring.clear();
+        assert_eq!(ring.stats().dropped, 0);
+    }
+
+    #[test]
+    fn shrink_keeps_most_recent() {
+        let mut ring = LevelRing::new(Level::Info, 4);
+        fill(&mut ring, 0..6);
+        ring.set_capacity(Level::Info, 2);
+        assert_eq!(msgs(&ring), vec!["4", "5"]);
+        fill(&mut ring, 6..7);
+        assert_eq!(msgs(&ring), vec!["5", "6"]);
+    }
 
-    if log::set_boxed_logger(Box::new(logger)).is_ok() {
-        log::set_max_level(max_level);
+    #[test]
+    fn grow_preserves_order_across_wrap() {
+        let mut ring = LevelRing::new(Level::Info, 3);
+        fill(&mut ring, 0..5);
+        ring.set_capacity(Level::Info, 5);
+        assert_eq!(msgs(&ring), vec!["2", "3", "4"]);
+        fill(&mut ring, 5..8);
+        assert_eq!(msgs(&ring), vec!["3", "4", "5", "6", "7"]);
     }
 }
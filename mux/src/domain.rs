@@ -13,9 +13,11 @@ use crate::Mux;
 use anyhow::{bail, Error};
 use async_trait::async_trait;
 use config::configuration;
+use config::keyassignment::PaneDirection;
 use downcast_rs::{impl_downcast, Downcast};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize, PtySystem};
 use std::rc::Rc;
+use std::time::Instant;
 
 static DOMAIN_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 pub type DomainId = usize;
@@ -50,6 +52,44 @@ pub trait Domain: Downcast {
         split_direction: SplitDirection,
     ) -> anyhow::Result<Rc<dyn Pane>>;
 
+    /// Moves the pane into a new tab in the specified window, or into
+    /// a new window if `window_id` is None.
+    /// Domains that don't need to be involved return Ok(None), and the
+    /// mux then moves the pane itself.
+    async fn move_pane_to_new_tab(
+        &self,
+        _pane_id: PaneId,
+        _window_id: Option<WindowId>,
+    ) -> anyhow::Result<Option<(Rc<Tab>, WindowId)>> {
+        Ok(None)
+    }
+
+    /// Moves the pane into the specified tab, splitting the active pane
+    /// of that tab and placing the pane on the side given by `direction`.
+    /// Domains that don't need to be involved return Ok(false), and the
+    /// mux then moves the pane itself.
+    async fn move_pane_to_tab(
+        &self,
+        _pane_id: PaneId,
+        _tab_id: TabId,
+        _direction: PaneDirection,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    /// Moves the tab into the specified window, or into a new window if
+    /// `window_id` is None.
+    /// Domains that don't need to be involved return Ok(None), and the
+    /// mux then moves the tab itself.  Otherwise the domain moves the tab
+    /// and returns the window that now contains it.
+    async fn move_tab_to_window(
+        &self,
+        _tab_id: TabId,
+        _window_id: Option<WindowId>,
+    ) -> anyhow::Result<Option<WindowId>> {
+        Ok(None)
+    }
+
     /// Returns false if the `spawn` method will never succeed.
     /// There are some internal placeholder domains that are
     /// pre-created with local UI that we do not want to allow
@@ -79,6 +119,13 @@ pub trait Domain: Downcast {
 
     /// Indicates the state of the domain
     fn state(&self) -> DomainState;
+
+    /// Returns the time at which the domain will next try to
+    /// re-establish a lost connection, or None if it isn't
+    /// waiting to reconnect
+    fn reconnect_deadline(&self) -> Option<Instant> {
+        None
+    }
 }
 impl_downcast!(Domain);
 
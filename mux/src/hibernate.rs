@@ -0,0 +1,340 @@
+//! Hibernation of the scrollback of idle panes.  Once a pane has had no
+//! output or input for `hibernate_idle_panes_after`, the lines of its
+//! scrollback are written to a compressed file under the runtime
+//! directory and dropped from memory.  They are read back in as soon as
+//! the pane produces output, or its scrollback is scrolled through or
+//! searched.
+//!
+//! Cells refer to their hyperlinks and images through an `Arc`, which
+//! serde would write out again for every cell, so each hyperlink and image
+//! is stored once, in a table, and the cells that use it refer to it by
+//! its position in the table.
+use crate::pane::PaneId;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use termwiz::hyperlink::Hyperlink;
+use termwiz::image::{ImageCell, ImageData, TextureCoordinate};
+use wezterm_term::Line;
+
+/// Written at the start of each file, and changed whenever the layout
+/// of `HibernatedScrollback` changes
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct HibernatedImage {
+    /// The position of the image data in `HibernatedScrollback::images`
+    image: usize,
+    top_left: TextureCoordinate,
+    bottom_right: TextureCoordinate,
+    z_index: i32,
+    display_offset: (u32, u32),
+    image_id: Option<u32>,
+    placement_id: Option<u32>,
+}
+
+/// The hyperlink and images of a cell, which are removed from the
+/// cell itself before it is serialized
+#[derive(Serialize, Deserialize)]
+struct HibernatedCell {
+    line_idx: usize,
+    col: usize,
+    hyperlink: Option<usize>,
+    images: Vec<HibernatedImage>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HibernatedScrollback {
+    hyperlinks: Vec<Arc<Hyperlink>>,
+    images: Vec<Arc<ImageData>>,
+    cells: Vec<HibernatedCell>,
+    lines: Vec<Line>,
+}
+
+impl HibernatedScrollback {
+    fn new(lines: &[Line]) -> Self {
+        let mut lines = lines.to_vec();
+        let mut hyperlinks = vec![];
+        let mut hyperlink_idx: HashMap<*const Hyperlink, usize> = HashMap::new();
+        let mut images = vec![];
+        let mut image_idx: HashMap<*const ImageData, usize> = HashMap::new();
+        let mut cells = vec![];
+
+        for (line_idx, line) in lines.iter_mut().enumerate() {
+            for (col, cell) in line
+                .cells_mut_for_attr_changes_only()
+                .iter_mut()
+                .enumerate()
+            {
+                let attrs = cell.attrs_mut();
+                let hyperlink = attrs.hyperlink().map(|link| {
+                    *hyperlink_idx.entry(Arc::as_ptr(link)).or_insert_with(|| {
+                        hyperlinks.push(Arc::clone(link));
+                        hyperlinks.len() - 1
+                    })
+                });
+                let cell_images: Vec<HibernatedImage> = attrs
+                    .images()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|im| {
+                        let data = im.image_data();
+                        let image = *image_idx.entry(Arc::as_ptr(data)).or_insert_with(|| {
+                            images.push(Arc::clone(data));
+                            images.len() - 1
+                        });
+                        HibernatedImage {
+                            image,
+                            top_left: im.top_left(),
+                            bottom_right: im.bottom_right(),
+                            z_index: im.z_index(),
+                            display_offset: im.display_offset(),
+                            image_id: im.image_id(),
+                            placement_id: im.placement_id(),
+                        }
+                    })
+                    .collect();
+                if hyperlink.is_none() && cell_images.is_empty() {
+                    continue;
+                }
+
+                attrs.set_hyperlink(None);
+                attrs.clear_images();
+                cells.push(HibernatedCell {
+                    line_idx,
+                    col,
+                    hyperlink,
+                    images: cell_images,
+                });
+            }
+        }
+
+        Self {
+            hyperlinks,
+            images,
+            cells,
+            lines,
+        }
+    }
+
+    fn into_lines(self) -> Vec<Line> {
+        let Self {
+            hyperlinks,
+            images,
+            cells,
+            mut lines,
+        } = self;
+
+        for cell in cells {
+            let target = match lines
+                .get_mut(cell.line_idx)
+                .and_then(|line| line.cells_mut_for_attr_changes_only().get_mut(cell.col))
+            {
+                Some(target) => target,
+                None => continue,
+            };
+            let attrs = target.attrs_mut();
+            if let Some(link) = cell.hyperlink.and_then(|idx| hyperlinks.get(idx)) {
+                attrs.set_hyperlink(Some(Arc::clone(link)));
+            }
+            for im in cell.images {
+                if let Some(data) = images.get(im.image) {
+                    attrs.attach_image(Box::new(ImageCell::with_z_index(
+                        im.top_left,
+                        im.bottom_right,
+                        Arc::clone(data),
+                        im.z_index,
+                        im.display_offset.0,
+                        im.display_offset.1,
+                        im.image_id,
+                        im.placement_id,
+                    )));
+                }
+            }
+        }
+
+        lines
+    }
+}
+
+/// The scrollback of a pane, held in a file while the pane is idle.
+/// The file is removed when this is dropped.
+#[derive(Debug)]
+pub struct Hibernation {
+    path: PathBuf,
+    lines: usize,
+}
+
+impl Hibernation {
+    /// Writes `lines`, the scrollback of `pane_id`, to a file in the
+    /// runtime directory.  If this fails, nothing was stored, so the
+    /// lines have to be kept.
+    pub fn store(pane_id: PaneId, lines: &[Line]) -> anyhow::Result<Self> {
+        Self::store_in(&config::RUNTIME_DIR.join("hibernate"), pane_id, lines)
+    }
+
+    fn store_in(dir: &Path, pane_id: PaneId, lines: &[Line]) -> anyhow::Result<Self> {
+        config::create_user_owned_dirs(dir)?;
+        let path = dir.join(format!("pane-{}-{}.zst", std::process::id(), pane_id));
+
+        // The file is written under another name and then renamed, so that
+        // a crash part of the way through can't leave a file that looks
+        // like the whole scrollback
+        let temp = path.with_extension("tmp");
+        let write = || -> anyhow::Result<()> {
+            let file = std::fs::File::create(&temp)?;
+            let mut compress = zstd::Encoder::new(
+                std::io::BufWriter::new(file),
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            )?;
+            let mut encode = varbincode::Serializer::new(&mut compress);
+            FORMAT_VERSION.serialize(&mut encode)?;
+            HibernatedScrollback::new(lines).serialize(&mut encode)?;
+            drop(encode);
+            compress.finish()?.flush()?;
+            std::fs::rename(&temp, &path)?;
+            Ok(())
+        };
+        if let Err(err) = write() {
+            std::fs::remove_file(&temp).ok();
+            return Err(err).with_context(|| format!("writing {}", path.display()));
+        }
+
+        Ok(Self {
+            path,
+            lines: lines.len(),
+        })
+    }
+
+    /// The number of lines that were stored
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// Reads the lines back in and removes the file.  If the file has
+    /// gone missing or is damaged, the scrollback is lost and this
+    /// returns no lines, so that the pane carries on without it.
+    pub fn restore(self) -> Vec<Line> {
+        match self.read() {
+            Ok(lines) => lines,
+            Err(err) => {
+                log::warn!(
+                    "The scrollback of an idle pane could not be read back from {}, \
+                     so it is now empty: {:#}",
+                    self.path.display(),
+                    err
+                );
+                vec![]
+            }
+        }
+    }
+
+    fn read(&self) -> anyhow::Result<Vec<Line>> {
+        let file = std::fs::File::open(&self.path).context("opening the file")?;
+        let mut decompress = zstd::Decoder::new(file)?;
+        let mut decode = varbincode::Deserializer::new(&mut decompress);
+        let version = u32::deserialize(&mut decode).context("reading the format version")?;
+        if version != FORMAT_VERSION {
+            bail!(
+                "the file is in format {}, rather than {}",
+                version,
+                FORMAT_VERSION
+            );
+        }
+        let scrollback =
+            HibernatedScrollback::deserialize(&mut decode).context("reading the scrollback")?;
+        Ok(scrollback.into_lines())
+    }
+}
+
+impl Drop for Hibernation {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::cell::{Cell, CellAttributes};
+    use termwiz::image::ImageDataType;
+    use termwiz::surface::SEQ_ZERO;
+
+    fn test_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("wezterm-hibernate-test-{}", std::process::id()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let link = Arc::new(Hyperlink::new("https://wezfurlong.org/wezterm/"));
+        let image = Arc::new(ImageData::with_data(ImageDataType::new_single_frame(
+            1,
+            1,
+            vec![0xff, 0, 0, 0xff],
+        )));
+
+        let mut lines = vec![];
+        for row in 0..3 {
+            let mut line = Line::from_text(&format!("line {}", row), &CellAttributes::default());
+            let mut attrs = CellAttributes::default();
+            attrs.set_hyperlink(Some(Arc::clone(&link)));
+            line.set_cell(0, Cell::new('l', attrs), SEQ_ZERO);
+            let mut attrs = CellAttributes::default();
+            attrs.attach_image(Box::new(ImageCell::with_z_index(
+                TextureCoordinate::new_f32(0., row as f32 / 3.),
+                TextureCoordinate::new_f32(1., (row + 1) as f32 / 3.),
+                Arc::clone(&image),
+                -1,
+                2,
+                3,
+                Some(7),
+                Some(row),
+            )));
+            line.set_cell(5, Cell::new('x', attrs), SEQ_ZERO);
+            lines.push(line);
+        }
+
+        let hibernation = Hibernation::store_in(&test_dir(), 1, &lines).unwrap();
+        assert_eq!(hibernation.lines(), 3);
+        let path = hibernation.path.clone();
+        let restored = hibernation.restore();
+        assert!(!path.exists());
+        assert_eq!(restored.len(), 3);
+
+        for (row, (restored, line)) in restored.iter().zip(lines.iter()).enumerate() {
+            assert_eq!(restored.as_str(), line.as_str());
+            assert_eq!(
+                restored.cells()[0].attrs().hyperlink().map(|l| l.uri()),
+                Some(link.uri())
+            );
+            let images = restored.cells()[5].attrs().images().unwrap();
+            assert_eq!(images, line.cells()[5].attrs().images().unwrap());
+            assert!(images[0].matches_placement(7, Some(row as u32)));
+            assert_eq!(restored.cells()[6].attrs(), line.cells()[6].attrs());
+        }
+        // The cells share the hyperlink and image again
+        let first = &restored[0].cells()[5].attrs().images().unwrap()[0];
+        let last = &restored[2].cells()[5].attrs().images().unwrap()[0];
+        assert!(Arc::ptr_eq(first.image_data(), last.image_data()));
+        assert!(Arc::ptr_eq(
+            restored[0].cells()[0].attrs().hyperlink().unwrap(),
+            restored[2].cells()[0].attrs().hyperlink().unwrap()
+        ));
+    }
+
+    #[test]
+    fn damaged() {
+        let lines = vec![Line::from_text("hello", &CellAttributes::default())];
+
+        let hibernation = Hibernation::store_in(&test_dir(), 2, &lines).unwrap();
+        std::fs::write(&hibernation.path, b"not the scrollback").unwrap();
+        assert!(hibernation.restore().is_empty());
+
+        let hibernation = Hibernation::store_in(&test_dir(), 3, &lines).unwrap();
+        std::fs::remove_file(&hibernation.path).unwrap();
+        assert!(hibernation.restore().is_empty());
+    }
+}
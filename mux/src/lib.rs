@@ -1,7 +1,8 @@
 use crate::pane::{Pane, PaneId};
-use crate::tab::{Tab, TabId};
+use crate::tab::{SplitDirection, Tab, TabId};
 use crate::window::{Window, WindowId};
 use anyhow::{anyhow, Context, Error};
+use config::keyassignment::PaneDirection;
 use config::{configuration, ExitBehavior};
 use domain::{Domain, DomainId};
 use filedescriptor::{socketpair, AsRawSocketDescriptor, FileDescriptor};
@@ -9,16 +10,18 @@ use filedescriptor::{socketpair, AsRawSocketDescriptor, FileDescriptor};
 use libc::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 use log::error;
 use metrics::histogram;
+use monitor::{MonitorKind, PaneMonitors};
 use portable_pty::ExitStatus;
 use std::cell::{Ref, RefCell, RefMut};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use termwiz::escape::csi::{DecPrivateMode, DecPrivateModeCode, Device, Mode};
+use termwiz::escape::parser::SixelLimits;
 use termwiz::escape::{Action, CSI};
 use thiserror::*;
 #[cfg(windows)]
@@ -27,10 +30,16 @@ use winapi::um::winsock2::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 pub mod activity;
 pub mod connui;
 pub mod domain;
+pub mod hibernate;
 pub mod localpane;
+pub mod monitor;
 pub mod pane;
 pub mod renderable;
+pub mod session;
 pub mod ssh;
+pub mod sshcli;
+pub mod sshforward;
+pub mod sshprompt;
 pub mod tab;
 pub mod termwiztermtab;
 pub mod tmux;
@@ -46,15 +55,43 @@ pub enum MuxNotification {
     WindowCreated(WindowId),
     WindowRemoved(WindowId),
     WindowInvalidated(WindowId),
+    /// A monitor that was armed for the pane has fired
+    PaneMonitor {
+        pane_id: PaneId,
+        kind: MonitorKind,
+        /// The time since the monitor was armed
+        elapsed: Duration,
+    },
+    PaneMoved {
+        pane_id: PaneId,
+        /// The window that contained the pane before it was moved
+        old_window_id: WindowId,
+        old_tab_id: TabId,
+        new_tab_id: TabId,
+    },
     Alert {
         pane_id: PaneId,
         alert: wezterm_term::Alert,
     },
+    /// The domain was attached or detached, or it started or
+    /// stopped waiting to reconnect
+    DomainStateChanged(DomainId),
+    /// The domain re-established its lost connection to the server
+    /// after `attempts` attempts
+    DomainReconnected {
+        domain_id: DomainId,
+        attempts: usize,
+    },
     Empty,
 }
 
 static SUB_ID: AtomicUsize = AtomicUsize::new(0);
 
+/// Setting a pane user var whose name starts with this prefix also
+/// sets the user var of the tab that contains the pane, with the
+/// prefix removed from its name
+pub const TAB_USER_VAR_PREFIX: &str = "WEZTERM_TAB_";
+
 pub struct Mux {
     tabs: RefCell<HashMap<TabId, Rc<Tab>>>,
     panes: RefCell<HashMap<PaneId, Rc<dyn Pane>>>,
@@ -64,10 +101,22 @@ pub struct Mux {
     domains_by_name: RefCell<HashMap<String, Arc<dyn Domain>>>,
     subscribers: RefCell<HashMap<usize, Box<dyn Fn(MuxNotification) -> bool>>>,
     banner: RefCell<Option<String>>,
+    /// Panes that have produced output while they were not the
+    /// active pane, and have not been activated since
+    unseen_output: RefCell<HashSet<PaneId>>,
+    monitors: RefCell<HashMap<PaneId, PaneMonitors>>,
 }
 
 const BUFSIZE: usize = 1024 * 1024;
 
+/// Returns the idle time after which an idle notification is raised
+/// for a pane, if enabled
+fn idle_notification_duration() -> Option<Duration> {
+    configuration()
+        .notify_when_idle_seconds
+        .map(Duration::from_secs)
+}
+
 /// This function bounces parsed actions over to the main thread to feed to
 /// the pty in the mux.
 /// It blocks until the mux has finished consuming the data, which provides
@@ -115,6 +164,11 @@ fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDesc
                 break;
             }
             Ok(size) => {
+                let config = configuration();
+                parser.set_sixel_limits(SixelLimits {
+                    max_image_size_bytes: config.max_image_size_bytes,
+                    max_image_dimension: config.max_image_dimensions,
+                });
                 parser.parse(&buf[0..size], |action| {
                     let mut flush = false;
                     match &action {
@@ -150,7 +204,7 @@ fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDesc
                     send_actions_to_mux(pane_id, dead, std::mem::take(&mut actions));
                 }
 
-                buf.resize(configuration().mux_output_parser_buffer_size, 0);
+                buf.resize(config.mux_output_parser_buffer_size, 0);
             }
         }
     }
@@ -317,6 +371,8 @@ impl Mux {
             domains: RefCell::new(domains),
             subscribers: RefCell::new(HashMap::new()),
             banner: RefCell::new(None),
+            unseen_output: RefCell::new(HashSet::new()),
+            monitors: RefCell::new(HashMap::new()),
         }
     }
 
@@ -331,6 +387,20 @@ impl Mux {
     }
 
     pub fn notify(&self, notification: MuxNotification) {
+        match &notification {
+            MuxNotification::PaneOutput(pane_id) => self.record_pane_output(*pane_id),
+            MuxNotification::Alert {
+                pane_id,
+                alert: wezterm_term::Alert::SetUserVar { name, value },
+            } => {
+                if let Some(name) = name.strip_prefix(TAB_USER_VAR_PREFIX) {
+                    if let Some((_domain_id, _window_id, tab_id)) = self.resolve_pane_id(*pane_id) {
+                        self.set_tab_user_var(tab_id, name.to_string(), value.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
         let mut subscribers = self.subscribers.borrow_mut();
         subscribers.retain(|_, notify| notify(notification.clone()));
     }
@@ -391,6 +461,164 @@ impl Mux {
         self.panes.borrow().get(&pane_id).map(Rc::clone)
     }
 
+    /// Returns true if the pane has produced output since it
+    /// was last the active pane
+    pub fn has_unseen_output(&self, pane_id: PaneId) -> bool {
+        self.unseen_output.borrow().contains(&pane_id)
+    }
+
+    /// Arms a monitor that fires once the pane has produced no
+    /// output for `duration`
+    pub fn watch_for_silence(&self, pane_id: PaneId, duration: Duration) {
+        let mut monitors = self.monitors.borrow_mut();
+        let entry = monitors.entry(pane_id).or_default();
+        entry.arm_silence(Instant::now(), duration);
+        Self::schedule_monitor_timer(pane_id, entry, idle_notification_duration());
+    }
+
+    /// Arms a monitor that fires when the pane next produces output
+    pub fn watch_for_activity(&self, pane_id: PaneId) {
+        let mut monitors = self.monitors.borrow_mut();
+        monitors
+            .entry(pane_id)
+            .or_default()
+            .arm_activity(Instant::now());
+    }
+
+    fn record_pane_output(&self, pane_id: PaneId) {
+        let idle = idle_notification_duration();
+        let fired = {
+            let mut monitors = self.monitors.borrow_mut();
+            if !monitors.contains_key(&pane_id)
+                && (idle.is_none() || !self.panes.borrow().contains_key(&pane_id))
+            {
+                return;
+            }
+            let entry = monitors.entry(pane_id).or_default();
+            let fired = entry.output(Instant::now(), idle.is_some());
+            Self::schedule_monitor_timer(pane_id, entry, idle);
+            fired
+        };
+        self.fire_monitors(pane_id, fired);
+    }
+
+    /// Ensures that a timer will check the monitors by the time
+    /// that the earliest of them is due to fire
+    fn schedule_monitor_timer(pane_id: PaneId, entry: &mut PaneMonitors, idle: Option<Duration>) {
+        let deadline = match entry.next_deadline(idle) {
+            Some(deadline) => deadline,
+            None => return,
+        };
+        if let Some(timer) = entry.timer {
+            if timer <= deadline {
+                // That timer will reschedule as needed
+                return;
+            }
+        }
+        entry.timer.replace(deadline);
+        promise::spawn::spawn(async move {
+            smol::Timer::at(deadline).await;
+            if let Some(mux) = Mux::get() {
+                mux.expire_monitors(pane_id, deadline);
+            }
+        })
+        .detach();
+    }
+
+    fn expire_monitors(&self, pane_id: PaneId, deadline: Instant) {
+        let idle = idle_notification_duration();
+        let fired = {
+            let mut monitors = self.monitors.borrow_mut();
+            let entry = match monitors.get_mut(&pane_id) {
+                Some(entry) => entry,
+                None => return,
+            };
+            if entry.timer == Some(deadline) {
+                entry.timer.take();
+            }
+            let fired = entry.expire(Instant::now(), idle);
+            Self::schedule_monitor_timer(pane_id, entry, idle);
+            fired
+        };
+        self.fire_monitors(pane_id, fired);
+    }
+
+    fn fire_monitors(&self, pane_id: PaneId, fired: Vec<(MonitorKind, Duration)>) {
+        for (kind, elapsed) in fired {
+            log::debug!(
+                "pane {} {:?} monitor fired after {:?}",
+                pane_id,
+                kind,
+                elapsed
+            );
+            self.notify(MuxNotification::PaneMonitor {
+                pane_id,
+                kind,
+                elapsed,
+            });
+        }
+    }
+
+    /// Returns true if the input that is typed into the pane is
+    /// broadcast to other panes, either because its window or its
+    /// tab is broadcasting, or because it was added to the broadcast
+    pub fn is_input_broadcast_target(&self, pane_id: PaneId) -> bool {
+        let (_domain_id, window_id, tab_id) = match self.resolve_pane_id(pane_id) {
+            Some(ids) => ids,
+            None => return false,
+        };
+        let window_broadcast = self
+            .get_window(window_id)
+            .map(|window| window.is_broadcasting_input())
+            .unwrap_or(false);
+        window_broadcast
+            || self
+                .get_tab(tab_id)
+                .map(|tab| tab.is_input_broadcast_member(pane_id))
+                .unwrap_or(false)
+    }
+
+    /// Returns the panes, other than the pane itself, that also
+    /// receive the input that is typed into the pane
+    pub fn input_broadcast_targets(&self, pane_id: PaneId) -> Vec<Rc<dyn Pane>> {
+        if !self.is_input_broadcast_target(pane_id) {
+            return vec![];
+        }
+        let (_domain_id, window_id, tab_id) = match self.resolve_pane_id(pane_id) {
+            Some(ids) => ids,
+            None => return vec![],
+        };
+
+        let mut targets: Vec<Rc<dyn Pane>> = vec![];
+        if let Some(window) = self.get_window(window_id) {
+            if window.is_broadcasting_input() {
+                for tab in window.iter() {
+                    targets.extend(tab.iter_panes_ignoring_zoom().into_iter().map(|p| p.pane));
+                }
+            }
+        }
+        if let Some(tab) = self.get_tab(tab_id) {
+            for pane in tab.input_broadcast_members() {
+                if !targets.iter().any(|p| p.pane_id() == pane.pane_id()) {
+                    targets.push(pane);
+                }
+            }
+        }
+        targets.retain(|p| p.pane_id() != pane_id);
+        targets
+    }
+
+    /// Records whether the pane has output that the user hasn't seen.
+    /// Returns true if that changed the state.
+    pub fn set_unseen_output(&self, pane_id: PaneId, unseen: bool) -> bool {
+        let mut unseen_output = self.unseen_output.borrow_mut();
+        if unseen {
+            unseen_output.insert(pane_id)
+        } else {
+            unseen_output.remove(&pane_id)
+        }
+    }
+
     pub fn get_tab(&self, tab_id: TabId) -> Option<Rc<Tab>> {
         self.tabs.borrow().get(&tab_id).map(Rc::clone)
     }
@@ -421,6 +649,8 @@ impl Mux {
 
     fn remove_pane_internal(&self, pane_id: PaneId) {
         log::debug!("removing pane {}", pane_id);
+        self.unseen_output.borrow_mut().remove(&pane_id);
+        self.monitors.borrow_mut().remove(&pane_id);
         if let Some(pane) = self.panes.borrow_mut().remove(&pane_id) {
             log::debug!("killing pane {}", pane_id);
             pane.kill();
@@ -565,6 +795,255 @@ impl Mux {
         Ok(())
     }
 
+    /// Moves the pane into a new tab in the specified window, or into
+    /// a new window if `window_id` is None.  The pane keeps its id.
+    /// Returns the new tab and the window that contains it.
+    pub async fn move_pane_to_new_tab(
+        &self,
+        pane_id: PaneId,
+        window_id: Option<WindowId>,
+    ) -> anyhow::Result<(Rc<Tab>, WindowId)> {
+        let (domain_id, old_window_id, old_tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        let old_tab = self
+            .get_tab(old_tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", old_tab_id))?;
+        if old_tab.count_panes() == 1 {
+            let alone_in_window = self
+                .get_window(old_window_id)
+                .map(|w| w.len() == 1)
+                .unwrap_or(false);
+            if window_id == Some(old_window_id) || (window_id.is_none() && alone_in_window) {
+                anyhow::bail!("pane {} is already the only pane in its tab", pane_id);
+            }
+        }
+
+        let domain = self
+            .get_domain(domain_id)
+            .ok_or_else(|| anyhow!("domain {} of pane {} not found", domain_id, pane_id))?;
+        let (tab, window_id) = match domain.move_pane_to_new_tab(pane_id, window_id).await? {
+            Some(result) => result,
+            None => {
+                let pane = old_tab
+                    .remove_pane(pane_id)
+                    .ok_or_else(|| anyhow!("pane {} not found in tab {}", pane_id, old_tab_id))?;
+                let size = old_tab.get_size();
+                let tab = Rc::new(Tab::new(&size));
+                tab.assign_pane(&pane);
+                pane.resize(size)?;
+                self.add_tab_no_panes(&tab);
+
+                match window_id {
+                    Some(window_id) => {
+                        self.add_tab_to_window(&tab, window_id)?;
+                        (tab, window_id)
+                    }
+                    None => {
+                        let window = self.new_empty_window();
+                        self.add_tab_to_window(&tab, *window)?;
+                        (tab, *window)
+                    }
+                }
+            }
+        };
+
+        if let Some(mut window) = self.get_window_mut(window_id) {
+            if let Some(idx) = window.idx_by_id(tab.tab_id()) {
+                window.save_and_then_set_active(idx);
+            }
+        }
+        if old_tab.is_dead() {
+            self.remove_tab(old_tab_id);
+        }
+
+        self.notify(MuxNotification::PaneMoved {
+            pane_id,
+            old_window_id,
+            old_tab_id,
+            new_tab_id: tab.tab_id(),
+        });
+        Ok((tab, window_id))
+    }
+
+    /// Moves the pane into the specified tab, splitting the active pane
+    /// of that tab and placing the pane on the side given by `direction`.
+    /// The pane keeps its id, and becomes the active pane of that tab.
+    pub async fn move_pane_to_tab(
+        &self,
+        pane_id: PaneId,
+        tab_id: TabId,
+        direction: PaneDirection,
+    ) -> anyhow::Result<()> {
+        let (domain_id, old_window_id, old_tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        if old_tab_id == tab_id {
+            anyhow::bail!("pane {} is already in tab {}", pane_id, tab_id);
+        }
+        let old_tab = self
+            .get_tab(old_tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", old_tab_id))?;
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+        let target = tab
+            .get_active_pane()
+            .ok_or_else(|| anyhow!("tab {} has no active pane", tab_id))?;
+        if target.domain_id() != domain_id {
+            anyhow::bail!(
+                "cannot move pane {} into tab {}, which belongs to a different domain",
+                pane_id,
+                tab_id
+            );
+        }
+
+        let domain = self
+            .get_domain(domain_id)
+            .ok_or_else(|| anyhow!("domain {} of pane {} not found", domain_id, pane_id))?;
+        if !domain.move_pane_to_tab(pane_id, tab_id, direction).await? {
+            // Check that there is room before removing the pane from
+            // its current tab, so that a failed move leaves it intact
+            let split_direction = match direction {
+                PaneDirection::Left | PaneDirection::Right => SplitDirection::Horizontal,
+                PaneDirection::Up | PaneDirection::Down => SplitDirection::Vertical,
+            };
+            let target_idx = tab.get_active_idx();
+            tab.check_split_size(target_idx, split_direction)?;
+
+            let pane = old_tab
+                .remove_pane(pane_id)
+                .ok_or_else(|| anyhow!("pane {} not found in tab {}", pane_id, old_tab_id))?;
+            tab.split_and_insert_toward(target_idx, direction, pane)?;
+        }
+
+        if let Some(window_id) = self.window_containing_tab(tab_id) {
+            if let Some(mut window) = self.get_window_mut(window_id) {
+                if let Some(idx) = window.idx_by_id(tab_id) {
+                    window.save_and_then_set_active(idx);
+                }
+            }
+        }
+        if old_tab.is_dead() {
+            self.remove_tab(old_tab_id);
+        }
+
+        self.notify(MuxNotification::PaneMoved {
+            pane_id,
+            old_window_id,
+            old_tab_id,
+            new_tab_id: tab_id,
+        });
+        Ok(())
+    }
+
+    /// Moves the tab into the specified window, or into a new window if
+    /// `window_id` is None.  The tab is placed at `tab_idx`, or after the
+    /// other tabs of the window if that is None, and becomes the active
+    /// tab of the window.  The tab and its panes keep their ids.
+    /// Returns the window that now contains the tab.
+    pub async fn move_tab_to_window(
+        &self,
+        tab_id: TabId,
+        window_id: Option<WindowId>,
+        tab_idx: Option<usize>,
+    ) -> anyhow::Result<WindowId> {
+        let old_window_id = self
+            .window_containing_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} is not in any window", tab_id))?;
+        let alone_in_window = self
+            .get_window(old_window_id)
+            .map(|w| w.len() == 1)
+            .unwrap_or(false);
+        if window_id == Some(old_window_id) {
+            anyhow::bail!("tab {} is already in window {}", tab_id, old_window_id);
+        }
+        if window_id.is_none() && alone_in_window {
+            anyhow::bail!("tab {} is already the only tab in its window", tab_id);
+        }
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+        let domain_id = tab
+            .get_active_pane()
+            .ok_or_else(|| anyhow!("tab {} has no active pane", tab_id))?
+            .domain_id();
+
+        let domain = self
+            .get_domain(domain_id)
+            .ok_or_else(|| anyhow!("domain {} of tab {} not found", domain_id, tab_id))?;
+        let window_id = match domain.move_tab_to_window(tab_id, window_id).await? {
+            Some(window_id) => window_id,
+            None => self.reparent_tab(&tab, old_window_id, window_id)?,
+        };
+
+        if let Some(mut window) = self.get_window_mut(window_id) {
+            if let Some(idx) = window.idx_by_id(tab_id) {
+                let idx = match tab_idx {
+                    Some(tab_idx) if tab_idx < window.len() && tab_idx != idx => {
+                        let tab = window.remove_by_idx(idx);
+                        window.insert(tab_idx, &tab);
+                        tab_idx
+                    }
+                    _ => idx,
+                };
+                window.save_and_then_set_active(idx);
+            }
+        }
+        self.prune_dead_windows();
+        self.notify(MuxNotification::WindowInvalidated(old_window_id));
+        Ok(window_id)
+    }
+
+    /// Removes the tab from `old_window_id` and adds it to the specified
+    /// window, or to a new window if `window_id` is None.  When the tab
+    /// joins a window that has other tabs it is resized to match them.
+    /// This only affects the local mux; it is used by `move_tab_to_window`
+    /// and by domains that need to update the local mux after the move.
+    pub fn reparent_tab(
+        &self,
+        tab: &Rc<Tab>,
+        old_window_id: WindowId,
+        window_id: Option<WindowId>,
+    ) -> anyhow::Result<WindowId> {
+        let size = match window_id {
+            Some(window_id) => self
+                .get_window(window_id)
+                .ok_or_else(|| anyhow!("no such window_id {}", window_id))?
+                .get_by_idx(0)
+                .map(|t| t.get_size()),
+            None => None,
+        };
+        if let Some(mut window) = self.get_window_mut(old_window_id) {
+            window.remove_by_id(tab.tab_id());
+        }
+        if let Some(size) = size {
+            tab.resize(size);
+        }
+        match window_id {
+            Some(window_id) => {
+                self.add_tab_to_window(tab, window_id)?;
+                Ok(window_id)
+            }
+            None => {
+                let window = self.new_empty_window();
+                self.add_tab_to_window(tab, *window)?;
+                Ok(*window)
+            }
+        }
+    }
+
+    /// Sets a user var of the tab, and causes the window that contains
+    /// the tab to be repainted so that the tab bar reflects it
+    pub fn set_tab_user_var(&self, tab_id: TabId, name: String, value: String) {
+        if let Some(tab) = self.get_tab(tab_id) {
+            tab.set_user_var(name, value);
+            if let Some(window_id) = self.window_containing_tab(tab_id) {
+                self.notify(MuxNotification::WindowInvalidated(window_id));
+            }
+        }
+    }
+
     pub fn window_containing_tab(&self, tab_id: TabId) -> Option<WindowId> {
         for w in self.windows.borrow().values() {
             for t in w.iter() {
@@ -576,6 +1055,119 @@ impl Mux {
         None
     }
 
+    /// Makes the pane the active pane of its tab, and that tab the active
+    /// tab of its window.  If the tab is zoomed onto a different pane, the
+    /// tab is unzoomed.
+    pub fn activate_pane(&self, pane_id: PaneId) -> anyhow::Result<()> {
+        let (_domain_id, window_id, tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+
+        // The tab takes care of the focus of its own panes, but not of
+        // the pane in the tab that was previously active
+        let prior = self
+            .get_active_tab_for_window(window_id)
+            .filter(|prior| prior.tab_id() != tab_id)
+            .and_then(|prior| prior.get_active_pane());
+        {
+            let mut window = self
+                .get_window_mut(window_id)
+                .ok_or_else(|| anyhow!("window {} not found", window_id))?;
+            if let Some(idx) = window.idx_by_id(tab_id) {
+                window.save_and_then_set_active(idx);
+            }
+        }
+        if let Some(zoomed) = tab.get_zoomed_pane() {
+            if zoomed.pane_id() != pane_id {
+                tab.set_zoomed(false);
+            }
+        }
+        tab.set_active_pane(&pane);
+        if let Some(prior) = prior {
+            prior.focus_changed(false);
+            pane.focus_changed(true);
+        }
+
+        self.notify(MuxNotification::WindowInvalidated(window_id));
+        Ok(())
+    }
+
+    /// Activates the pane that is adjacent to the specified pane in
+    /// the specified direction, and returns its id.
+    /// It is an error for there to be no pane in that direction.
+    pub fn activate_pane_direction(
+        &self,
+        pane_id: PaneId,
+        direction: PaneDirection,
+    ) -> anyhow::Result<PaneId> {
+        let (_domain_id, _window_id, tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+
+        let panes = tab.iter_panes_ignoring_zoom();
+        let target = panes
+            .iter()
+            .find(|p| p.pane.pane_id() == pane_id)
+            .and_then(|p| tab.get_pane_direction(p.index, direction))
+            .and_then(|index| panes.iter().find(|p| p.index == index))
+            .map(|p| p.pane.pane_id())
+            .ok_or_else(|| anyhow!("there is no pane {:?} of pane {}", direction, pane_id))?;
+
+        self.activate_pane(target)?;
+        Ok(target)
+    }
+
+    /// Activates a tab, along with its active pane, and returns the id of
+    /// that pane.  The tab is either `tab_id`, or the tab that is `relative`
+    /// tabs away from the tab that contains `pane_id`, wrapping around at
+    /// the ends of its window.
+    pub fn activate_tab(
+        &self,
+        pane_id: PaneId,
+        tab_id: Option<TabId>,
+        relative: Option<isize>,
+    ) -> anyhow::Result<PaneId> {
+        let tab = match (tab_id, relative) {
+            (Some(tab_id), _) => self
+                .get_tab(tab_id)
+                .ok_or_else(|| anyhow!("tab {} not found", tab_id))?,
+            (None, Some(delta)) => {
+                let (_domain_id, window_id, tab_id) = self
+                    .resolve_pane_id(pane_id)
+                    .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+                let window = self
+                    .get_window(window_id)
+                    .ok_or_else(|| anyhow!("window {} not found", window_id))?;
+                let idx = window
+                    .idx_by_id(tab_id)
+                    .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+                let max = window.len() as isize;
+                let idx = (idx as isize + delta).rem_euclid(max) as usize;
+                window
+                    .get_by_idx(idx)
+                    .map(Rc::clone)
+                    .ok_or_else(|| anyhow!("no tab at index {}", idx))?
+            }
+            (None, None) => anyhow::bail!("either a tab id or a relative offset is required"),
+        };
+
+        let pane = tab
+            .get_active_pane()
+            .ok_or_else(|| anyhow!("tab {} has no panes", tab.tab_id()))?;
+        let pane_id = pane.pane_id();
+        self.activate_pane(pane_id)?;
+        Ok(pane_id)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.panes.borrow().is_empty()
     }
@@ -599,7 +1191,7 @@ impl Mux {
     pub fn resolve_pane_id(&self, pane_id: PaneId) -> Option<(DomainId, WindowId, TabId)> {
         let mut ids = None;
         for tab in self.tabs.borrow().values() {
-            for p in tab.iter_panes() {
+            for p in tab.iter_panes_ignoring_zoom() {
                 if p.pane.pane_id() == pane_id {
                     ids = Some((tab.tab_id(), p.pane.domain_id()));
                     break;
@@ -634,6 +1226,7 @@ impl Mux {
         }
 
         self.prune_dead_windows();
+        self.notify(MuxNotification::DomainStateChanged(domain));
     }
 
     pub fn set_banner(&self, banner: Option<String>) {
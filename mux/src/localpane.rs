@@ -1,4 +1,5 @@
 use crate::domain::DomainId;
+use crate::hibernate::Hibernation;
 use crate::pane::{Pane, PaneId, Pattern, SearchResult};
 use crate::renderable::*;
 use crate::tmux::{TmuxDomain, TmuxDomainState};
@@ -10,18 +11,20 @@ use config::{configuration, ExitBehavior};
 use portable_pty::{Child, ChildKiller, ExitStatus, MasterPty, PtySize};
 use rangeset::RangeSet;
 use smol::channel::{bounded, Receiver, TryRecvError};
-use std::cell::{RefCell, RefMut};
+use std::cell::{Cell, RefCell, RefMut};
 use std::collections::{HashMap, HashSet};
 use std::io::Result as IoResult;
 use std::ops::Range;
 use std::sync::Arc;
+use std::time::Instant;
+use termwiz::escape::osc::Progress;
 use termwiz::escape::DeviceControlMode;
 use termwiz::surface::{Line, SequenceNo, SEQ_ZERO};
 use url::Url;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
     Alert, AlertHandler, CellAttributes, Clipboard, KeyCode, KeyModifiers, MouseEvent,
-    SemanticZone, StableRowIndex, Terminal, TerminalConfiguration,
+    ScrollbackStats, SemanticZone, StableRowIndex, Terminal, TerminalConfiguration,
 };
 
 #[derive(Debug)]
@@ -33,8 +36,11 @@ enum ProcessState {
         // Whether we've explicitly killed the child
         killed: bool,
     },
+    /// The process has terminated, but exit_behavior is holding the
+    /// pane open until the user closes it
     DeadPendingClose {
         killed: bool,
+        status: ExitStatus,
     },
     Dead,
 }
@@ -46,6 +52,12 @@ pub struct LocalPane {
     pty: RefCell<Box<dyn MasterPty>>,
     domain_id: DomainId,
     tmux_domain: RefCell<Option<Arc<TmuxDomainState>>>,
+    /// The scrollback, while the pane is idle and it is hibernating
+    hibernation: RefCell<Option<Hibernation>>,
+    /// When the pane last had output or input
+    last_activity: Cell<Instant>,
+    /// Whether a timer is due to check whether the pane is idle
+    hibernation_timer: Cell<bool>,
 }
 
 #[async_trait(?Send)]
@@ -75,6 +87,13 @@ impl Pane for LocalPane {
     }
 
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
+        if self.hibernation.borrow().is_some() {
+            let dims = terminal_get_dimensions(&mut self.terminal.borrow_mut());
+            if lines.start < dims.scrollback_top {
+                // Scrolled into the scrollback
+                self.wake();
+            }
+        }
         let (first, mut lines) = terminal_get_lines(&mut self.terminal.borrow_mut(), lines);
 
         if self.tmux_domain.borrow().is_some() {
@@ -96,7 +115,18 @@ impl Pane for LocalPane {
     }
 
     fn get_dimensions(&self) -> RenderableDimensions {
-        terminal_get_dimensions(&mut self.terminal.borrow_mut())
+        let mut terminal = self.terminal.borrow_mut();
+        let mut dims = terminal_get_dimensions(&mut terminal);
+        match self.hibernation.borrow().as_ref() {
+            // Present the scrollback as though it were still there,
+            // so that it can be scrolled back through
+            Some(hibernation) if !terminal.is_alt_screen_active() => {
+                dims.scrollback_rows += hibernation.lines();
+                dims.scrollback_top -= hibernation.lines() as StableRowIndex;
+            }
+            _ => {}
+        }
+        dims
     }
 
     fn copy_user_vars(&self) -> HashMap<String, String> {
@@ -117,20 +147,26 @@ impl Pane for LocalPane {
                 let _ = signaller.kill();
                 *killed = true;
             }
-            ProcessState::DeadPendingClose { killed } => {
+            ProcessState::DeadPendingClose { killed, .. } => {
                 *killed = true;
             }
             _ => {}
         }
     }
 
+    fn get_exit_status(&self) -> Option<ExitStatus> {
+        match &*self.process.borrow() {
+            ProcessState::DeadPendingClose { status, .. } => Some(status.clone()),
+            _ => None,
+        }
+    }
+
+    fn get_scrollback_stats(&self) -> Option<ScrollbackStats> {
+        Some(self.terminal.borrow().get_scrollback_stats())
+    }
+
     fn is_dead(&self) -> bool {
         let mut proc = self.process.borrow_mut();
-        let mut notify = None;
-
-        const EXIT_BEHAVIOR: &str = "\x1b]8;;https://wezfurlong.org/wezterm/\
-                                     config/lua/config/exit_behavior.html\
-                                     \x1b\\exit_behavior\x1b]8;;\x1b\\";
 
         match &mut *proc {
             ProcessState::Running {
@@ -144,36 +180,34 @@ impl Pane for LocalPane {
                     _ => Some(ExitStatus::with_exit_code(1)),
                 };
                 if let Some(status) = status {
-                    match (configuration().exit_behavior, status.success(), killed) {
-                        (ExitBehavior::Close, _, _) => *proc = ProcessState::Dead,
-                        (ExitBehavior::CloseOnCleanExit, false, false) => {
-                            notify = Some(format!(
-                                "\r\n[Process didn't exit cleanly. ({}=\"CloseOnCleanExit\")]\r\n",
-                                EXIT_BEHAVIOR
-                            ));
-                            *proc = ProcessState::DeadPendingClose { killed: false }
+                    // While the pane is held open, the gui draws a banner
+                    // that reports the status
+                    let hold = match (configuration().exit_behavior, *killed) {
+                        (ExitBehavior::Close, _) | (_, true) => false,
+                        (ExitBehavior::CloseOnCleanExit, false) => !status.success(),
+                        (ExitBehavior::Hold, false) => true,
+                    };
+                    *proc = if hold {
+                        ProcessState::DeadPendingClose {
+                            killed: false,
+                            status,
                         }
-                        (ExitBehavior::CloseOnCleanExit, ..) => *proc = ProcessState::Dead,
-                        (ExitBehavior::Hold, success, false) => {
-                            if success {
-                                notify = Some(format!(
-                                    "\r\n[Process completed. ({}=\"Hold\")]\r\n",
-                                    EXIT_BEHAVIOR
-                                ));
-                            } else {
-                                notify = Some(format!(
-                                    "\r\n[Process didn't exit cleanly. ({}=\"Hold\")]\r\n",
-                                    EXIT_BEHAVIOR
-                                ));
-                            }
-                            *proc = ProcessState::DeadPendingClose { killed: false }
-                        }
-                        (ExitBehavior::Hold, _, true) => *proc = ProcessState::Dead,
-                    }
+                    } else {
+                        ProcessState::Dead
+                    };
                     log::debug!("child terminated, new state is {:?}", proc);
+                    if hold {
+                        // Let the gui know that it needs to show the banner
+                        let pane_id = self.pane_id;
+                        promise::spawn::spawn_into_main_thread(async move {
+                            let mux = Mux::get().unwrap();
+                            mux.notify(MuxNotification::PaneOutput(pane_id));
+                        })
+                        .detach();
+                    }
                 }
             }
-            ProcessState::DeadPendingClose { killed } => {
+            ProcessState::DeadPendingClose { killed, .. } => {
                 if *killed {
                     *proc = ProcessState::Dead;
                     log::debug!("child state -> {:?}", proc);
@@ -182,21 +216,6 @@ impl Pane for LocalPane {
             ProcessState::Dead => {}
         }
 
-        if let Some(notify) = notify {
-            let pane_id = self.pane_id;
-            promise::spawn::spawn_into_main_thread(async move {
-                let mux = Mux::get().unwrap();
-                if let Some(pane) = mux.get_pane(pane_id) {
-                    let mut parser = termwiz::escape::parser::Parser::new();
-                    let mut actions = vec![];
-                    parser.parse(notify.as_bytes(), |action| actions.push(action));
-                    pane.perform_actions(actions);
-                    mux.notify(MuxNotification::PaneOutput(pane_id));
-                }
-            })
-            .detach();
-        }
-
         match &*proc {
             ProcessState::Running { .. } => false,
             ProcessState::DeadPendingClose { .. } => false,
@@ -217,14 +236,29 @@ impl Pane for LocalPane {
     }
 
     fn perform_actions(&self, actions: Vec<termwiz::escape::Action>) {
+        self.wake();
         self.terminal.borrow_mut().perform_actions(actions)
     }
 
     fn mouse_event(&self, event: MouseEvent) -> Result<(), Error> {
+        self.record_activity();
         self.terminal.borrow_mut().mouse_event(event)
     }
 
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
+        self.record_activity();
+        if let ProcessState::DeadPendingClose { killed, .. } = &mut *self.process.borrow_mut() {
+            // There is nothing to send the key to; Enter closes the pane
+            if key == KeyCode::Enter && mods == KeyModifiers::NONE {
+                *killed = true;
+                promise::spawn::spawn_into_main_thread(async move {
+                    let mux = Mux::get().unwrap();
+                    mux.prune_dead_windows();
+                })
+                .detach();
+            }
+            return Ok(());
+        }
         if self.tmux_domain.borrow().is_some() {
             log::error!("key: {:?}", key);
             if key == KeyCode::Char('q') {
@@ -237,6 +271,8 @@ impl Pane for LocalPane {
     }
 
     fn resize(&self, size: PtySize) -> Result<(), Error> {
+        // The scrollback is rewrapped to the new width
+        self.wake();
         self.pty.borrow_mut().resize(size)?;
         self.terminal.borrow_mut().resize(
             size.rows as usize,
@@ -248,6 +284,7 @@ impl Pane for LocalPane {
     }
 
     fn writer(&self) -> RefMut<dyn std::io::Write> {
+        self.record_activity();
         self.pty.borrow_mut()
     }
 
@@ -256,6 +293,7 @@ impl Pane for LocalPane {
     }
 
     fn send_paste(&self, text: &str) -> Result<(), Error> {
+        self.record_activity();
         if self.tmux_domain.borrow().is_some() {
             Ok(())
         } else {
@@ -276,6 +314,8 @@ impl Pane for LocalPane {
     }
 
     fn erase_scrollback(&self, erase_mode: ScrollbackEraseMode) {
+        // There's no need to read back what is about to be erased
+        self.hibernation.borrow_mut().take();
         match erase_mode {
             ScrollbackEraseMode::ScrollbackOnly => {
                 self.terminal.borrow_mut().erase_scrollback();
@@ -306,6 +346,10 @@ impl Pane for LocalPane {
         }
     }
 
+    fn get_progress(&self) -> Progress {
+        self.terminal.borrow().get_progress()
+    }
+
     fn get_current_working_dir(&self) -> Option<Url> {
         self.terminal
             .borrow()
@@ -314,7 +358,25 @@ impl Pane for LocalPane {
             .or_else(|| self.divine_current_working_dir())
     }
 
+    fn get_foreground_process_name(&self) -> Option<String> {
+        if self.tmux_domain.borrow().is_some() {
+            return None;
+        }
+        self.divine_foreground_process()
+    }
+
+    fn get_foreground_process_argv(&self) -> Option<Vec<String>> {
+        if self.tmux_domain.borrow().is_some() {
+            return None;
+        }
+        self.divine_foreground_argv()
+    }
+
     fn can_close_without_prompting(&self) -> bool {
+        self.get_processes_preventing_close().is_empty()
+    }
+
+    fn get_processes_preventing_close(&self) -> Vec<String> {
         let proc_list = self.divine_process_list();
         if !proc_list.is_empty() {
             log::trace!("can_close_without_prompting? procs in pane {:?}", proc_list);
@@ -325,13 +387,13 @@ impl Pane for LocalPane {
                 .cloned()
                 .collect::<HashSet<_>>();
 
-            for proc in &proc_list {
-                if !skip.contains(proc) {
-                    return false;
+            let mut names = vec![];
+            for proc in proc_list {
+                if !skip.contains(&proc) && !names.contains(&proc) {
+                    names.push(proc);
                 }
             }
-
-            return true;
+            names
         } else {
             #[cfg(unix)]
             {
@@ -339,20 +401,22 @@ impl Pane for LocalPane {
                 // window, we don't need to prompt to confirm closing.
                 // That is detectable as no longer having a process group leader.
                 if self.pty.borrow().process_group_leader().is_none() {
-                    return true;
+                    return vec![];
                 }
             }
 
-            false
+            vec![self.get_title()]
         }
     }
 
     fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
+        self.wake();
         let term = self.terminal.borrow();
         term.get_semantic_zones()
     }
 
     async fn search(&self, mut pattern: Pattern) -> anyhow::Result<Vec<SearchResult>> {
+        self.wake();
         let term = self.terminal.borrow();
         let screen = term.screen();
 
@@ -654,6 +718,102 @@ impl LocalPane {
             pty: RefCell::new(pty),
             domain_id,
             tmux_domain: RefCell::new(None),
+            hibernation: RefCell::new(None),
+            last_activity: Cell::new(Instant::now()),
+            hibernation_timer: Cell::new(false),
+        }
+    }
+
+    /// Records that the pane had output or input, which restarts the
+    /// wait until it is considered to be idle
+    fn record_activity(&self) {
+        self.last_activity.set(Instant::now());
+        self.schedule_hibernation();
+    }
+
+    /// Brings the scrollback back out of hibernation, if it is hibernating.
+    /// It isn't hibernated again until the pane is once more left idle.
+    fn wake(&self) {
+        self.record_activity();
+        let hibernation = match self.hibernation.borrow_mut().take() {
+            Some(hibernation) => hibernation,
+            None => return,
+        };
+        let lines = hibernation.restore();
+        log::debug!(
+            "pane {} restored {} lines of scrollback from hibernation",
+            self.pane_id,
+            lines.len()
+        );
+        self.terminal.borrow_mut().restore_scrollback(lines);
+    }
+
+    /// Arranges to check whether the pane has become idle once
+    /// `hibernate_idle_panes_after` has passed since its last activity
+    fn schedule_hibernation(&self) {
+        let after = match configuration().hibernate_idle_panes_after {
+            Some(after) => after,
+            None => return,
+        };
+        if self.hibernation_timer.replace(true) {
+            // That timer will reschedule as needed
+            return;
+        }
+        let pane_id = self.pane_id;
+        let deadline = self.last_activity.get() + after;
+        promise::spawn::spawn(async move {
+            smol::Timer::at(deadline).await;
+            let mux = match Mux::get() {
+                Some(mux) => mux,
+                None => return,
+            };
+            if let Some(pane) = mux.get_pane(pane_id) {
+                if let Some(pane) = pane.downcast_ref::<LocalPane>() {
+                    pane.hibernation_timer.set(false);
+                    pane.hibernate_if_idle();
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Moves the scrollback into hibernation if the pane has been idle
+    /// for `hibernate_idle_panes_after`, or waits until it could have been
+    fn hibernate_if_idle(&self) {
+        let after = match configuration().hibernate_idle_panes_after {
+            Some(after) => after,
+            None => return,
+        };
+        if self.last_activity.get().elapsed() < after {
+            self.schedule_hibernation();
+            return;
+        }
+        if self.hibernation.borrow().is_some() {
+            return;
+        }
+
+        let mut terminal = self.terminal.borrow_mut();
+        let lines = terminal.take_scrollback();
+        if lines.is_empty() {
+            return;
+        }
+        match Hibernation::store(self.pane_id, &lines) {
+            Ok(hibernation) => {
+                log::debug!(
+                    "pane {} hibernated {} lines of scrollback",
+                    self.pane_id,
+                    lines.len()
+                );
+                self.hibernation.borrow_mut().replace(hibernation);
+            }
+            Err(err) => {
+                log::warn!(
+                    "pane {} is idle, but its scrollback could not be hibernated: {:#}",
+                    self.pane_id,
+                    err
+                );
+                terminal.restore_scrollback(lines);
+            }
         }
     }
 
@@ -754,6 +914,75 @@ impl LocalPane {
         None
     }
 
+    #[cfg(target_os = "macos")]
+    fn divine_foreground_process(&self) -> Option<String> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        extern "C" {
+            fn proc_pidpath(
+                pid: libc::c_int,
+                buffer: *mut libc::c_void,
+                buffersize: u32,
+            ) -> libc::c_int;
+        }
+        const PROC_PIDPATHINFO_MAXSIZE: usize = 4096;
+        let mut buffer = vec![0u8; PROC_PIDPATHINFO_MAXSIZE];
+        let len = unsafe {
+            proc_pidpath(
+                pid,
+                buffer.as_mut_ptr() as *mut _,
+                PROC_PIDPATHINFO_MAXSIZE as u32,
+            )
+        };
+        if len <= 0 {
+            return None;
+        }
+        buffer.truncate(len as usize);
+        String::from_utf8(buffer).ok()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn divine_foreground_process(&self) -> Option<String> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        let path = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    fn divine_foreground_process(&self) -> Option<String> {
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    fn divine_foreground_argv(&self) -> Option<Vec<String>> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        // While it waits for a command, the shell that we spawned
+        // is the foreground process; that isn't interesting
+        if let ProcessState::Running {
+            pid: Some(shell), ..
+        } = &*self.process.borrow()
+        {
+            if *shell as libc::pid_t == pid {
+                return None;
+            }
+        }
+        let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+        let cmdline = cmdline.strip_suffix(&[0]).unwrap_or(&cmdline);
+        if cmdline.is_empty() {
+            return None;
+        }
+        Some(
+            cmdline
+                .split(|&b| b == 0)
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect(),
+        )
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn divine_foreground_argv(&self) -> Option<Vec<String>> {
+        None
+    }
+
     fn divine_process_list(&self) -> Vec<String> {
         #[allow(unused_mut)]
         let mut proc_names = vec![];
@@ -0,0 +1,148 @@
+//! Monitors notice when a pane goes quiet, or when it starts to produce
+//! output, so that the user can be told about panes that they are not
+//! looking at.  The output path only records the time of the most recent
+//! output; the silence monitors are checked by a timer that is scheduled
+//! for the time at which the pane would have been quiet for long enough.
+//! Each monitor disarms itself when it fires.
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorKind {
+    /// Armed by `pane:watch_for_silence`
+    Silence,
+    /// Armed by `pane:watch_for_activity`
+    Activity,
+    /// Armed by any output when `notify_when_idle_seconds` is set
+    Idle,
+}
+
+#[derive(Default, Debug)]
+pub struct PaneMonitors {
+    last_output: Option<Instant>,
+    /// When the silence monitor was armed, and how long the
+    /// pane has to be quiet for it to fire
+    silence: Option<(Instant, Duration)>,
+    /// When the activity monitor was armed
+    activity: Option<Instant>,
+    /// True if the pane has produced output since the idle
+    /// monitor last fired
+    idle: bool,
+    /// The deadline of the earliest timer that is scheduled
+    pub timer: Option<Instant>,
+}
+
+impl PaneMonitors {
+    pub fn arm_silence(&mut self, now: Instant, duration: Duration) {
+        self.silence.replace((now, duration));
+    }
+
+    pub fn arm_activity(&mut self, now: Instant) {
+        self.activity.replace(now);
+    }
+
+    /// Records that the pane produced output.
+    /// Returns the monitors that fired as a result, along with the
+    /// time that elapsed since they were armed.
+    pub fn output(&mut self, now: Instant, idle_enabled: bool) -> Vec<(MonitorKind, Duration)> {
+        self.last_output.replace(now);
+        if idle_enabled {
+            self.idle = true;
+        }
+        match self.activity.take() {
+            Some(armed) => vec![(MonitorKind::Activity, now.saturating_duration_since(armed))],
+            None => vec![],
+        }
+    }
+
+    fn silence_deadline(&self) -> Option<Instant> {
+        let (armed, duration) = self.silence?;
+        let quiet_since = match self.last_output {
+            Some(last) if last > armed => last,
+            _ => armed,
+        };
+        Some(quiet_since + duration)
+    }
+
+    fn idle_deadline(&self, idle: Option<Duration>) -> Option<Instant> {
+        if !self.idle {
+            return None;
+        }
+        Some(self.last_output? + idle?)
+    }
+
+    /// Returns the time at which a timer needs to check the monitors
+    pub fn next_deadline(&self, idle: Option<Duration>) -> Option<Instant> {
+        match (self.silence_deadline(), self.idle_deadline(idle)) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Called by the timer to fire the silence monitors whose
+    /// deadline has passed.
+    /// Returns the monitors that fired, along with the time that
+    /// elapsed since they were armed, or, for the idle monitor,
+    /// since the most recent output.
+    pub fn expire(&mut self, now: Instant, idle: Option<Duration>) -> Vec<(MonitorKind, Duration)> {
+        let mut fired = vec![];
+        if let Some(deadline) = self.silence_deadline() {
+            if deadline <= now {
+                if let Some((armed, _)) = self.silence.take() {
+                    fired.push((MonitorKind::Silence, now.saturating_duration_since(armed)));
+                }
+            }
+        }
+        if idle.is_none() {
+            self.idle = false;
+        }
+        if let (Some(deadline), Some(last)) = (self.idle_deadline(idle), self.last_output) {
+            if deadline <= now {
+                self.idle = false;
+                fired.push((MonitorKind::Idle, now.saturating_duration_since(last)));
+            }
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn monitors() {
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        let mut monitors = PaneMonitors::default();
+        assert_eq!(monitors.next_deadline(None), None);
+
+        monitors.arm_silence(start, secs(5));
+        assert_eq!(monitors.next_deadline(None), Some(start + secs(5)));
+        // Output postpones the silence monitor
+        assert!(monitors.output(start + secs(3), false).is_empty());
+        assert_eq!(monitors.next_deadline(None), Some(start + secs(8)));
+        assert!(monitors.expire(start + secs(5), None).is_empty());
+        assert_eq!(
+            monitors.expire(start + secs(8), None),
+            vec![(MonitorKind::Silence, secs(8))]
+        );
+        // and it has disarmed itself
+        assert_eq!(monitors.next_deadline(None), None);
+
+        monitors.arm_activity(start + secs(10));
+        assert_eq!(
+            monitors.output(start + secs(12), false),
+            vec![(MonitorKind::Activity, secs(2))]
+        );
+        assert!(monitors.output(start + secs(13), false).is_empty());
+
+        let idle = Some(secs(30));
+        monitors.output(start + secs(20), true);
+        assert_eq!(monitors.next_deadline(idle), Some(start + secs(50)));
+        assert_eq!(
+            monitors.expire(start + secs(51), idle),
+            vec![(MonitorKind::Idle, secs(31))]
+        );
+        assert_eq!(monitors.next_deadline(idle), None);
+    }
+}
@@ -4,19 +4,20 @@ use crate::Mux;
 use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
 use downcast_rs::{impl_downcast, Downcast};
-use portable_pty::PtySize;
+use portable_pty::{ExitStatus, PtySize};
 use rangeset::RangeSet;
 use serde::{Deserialize, Serialize};
 use std::cell::RefMut;
 use std::collections::HashMap;
 use std::ops::Range;
 use std::sync::{Arc, Mutex};
+use termwiz::escape::osc::Progress;
 use termwiz::hyperlink::Rule;
 use termwiz::surface::{Line, SequenceNo, SEQ_ZERO};
 use url::Url;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Clipboard, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex,
+    Clipboard, KeyCode, KeyModifiers, MouseEvent, ScrollbackStats, SemanticZone, StableRowIndex,
     TerminalConfiguration,
 };
 
@@ -151,6 +152,11 @@ impl LogicalLine {
     }
 }
 
+/// Returns true if the text of a line looks like a password prompt
+pub fn looks_like_password_prompt(text: &str) -> bool {
+    text.contains("sword")
+}
+
 /// A Pane represents a view on a terminal
 #[async_trait(?Send)]
 pub trait Pane: Downcast {
@@ -187,6 +193,39 @@ pub trait Pane: Downcast {
     /// have its dirty bit set appropriately.
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>);
 
+    /// Returns the text of the lines from `start` through to `end`,
+    /// with a newline after each line.  Line 0 is the top line of the
+    /// screen and negative numbers address the scrollback.  The default
+    /// is to return the lines of the screen.
+    /// If `escapes` is true, the attributes of the text are included as
+    /// SGR escape sequences; otherwise trailing whitespace is trimmed
+    /// from each line.
+    fn get_text(&self, start: Option<isize>, end: Option<isize>, escapes: bool) -> String {
+        let dims = self.get_dimensions();
+        let first = dims.scrollback_top;
+        let last = dims.physical_top + dims.viewport_rows as StableRowIndex - 1;
+        let start = (dims.physical_top + start.unwrap_or(0)).max(first);
+        let end = end
+            .map(|end| dims.physical_top + end)
+            .unwrap_or(last)
+            .min(last);
+
+        let mut text = String::new();
+        if start > end {
+            return text;
+        }
+        let (_, lines) = self.get_lines(start..end + 1);
+        for line in lines {
+            if escapes {
+                text.push_str(&line.as_escaped_str());
+            } else {
+                text.push_str(line.as_str().trim_end());
+            }
+            text.push('\n');
+        }
+        text
+    }
+
     fn get_logical_lines(&self, lines: Range<StableRowIndex>) -> Vec<LogicalLine> {
         let (mut first, mut phys) = self.get_lines(lines);
 
@@ -266,6 +305,17 @@ pub trait Pane: Downcast {
         lines
     }
 
+    /// Returns true if the line that contains the cursor looks like
+    /// a password prompt
+    fn is_at_password_prompt(&self) -> bool {
+        let cursor = self.get_cursor_position();
+        let (_, lines) = self.get_lines(cursor.y..cursor.y + 1);
+        lines
+            .first()
+            .map(|line| looks_like_password_prompt(&line.as_str()))
+            .unwrap_or(false)
+    }
+
     fn get_lines_with_hyperlinks_applied(
         &self,
         lines: Range<StableRowIndex>,
@@ -310,11 +360,24 @@ pub trait Pane: Downcast {
     /// Called as a hint that the pane is being resized as part of
     /// a zoom-to-fill-all-the-tab-space operation.
     fn set_zoomed(&self, _zoomed: bool) {}
+    /// Called on the active pane as a hint that the panes of the
+    /// containing tab are being rearranged.
+    fn set_arrangement(&self, _arrangement: crate::tab::PaneArrangement) {}
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()>;
     fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()>;
     fn perform_actions(&self, _actions: Vec<termwiz::escape::Action>) {}
     fn is_dead(&self) -> bool;
     fn kill(&self) {}
+    /// Returns the exit status of the process if it has terminated
+    /// but the pane is being held open by `exit_behavior`
+    fn get_exit_status(&self) -> Option<ExitStatus> {
+        None
+    }
+    /// Returns information about the memory used by the scrollback,
+    /// if the pane has its own terminal model
+    fn get_scrollback_stats(&self) -> Option<ScrollbackStats> {
+        None
+    }
     fn palette(&self) -> ColorPalette;
     fn domain_id(&self) -> DomainId;
 
@@ -324,6 +387,12 @@ pub trait Pane: Downcast {
 
     fn erase_scrollback(&self, _erase_mode: ScrollbackEraseMode) {}
 
+    /// Returns the progress that the application in the pane has
+    /// reported using the ConEmu style OSC 9;4 escape sequence
+    fn get_progress(&self) -> Progress {
+        Progress::None
+    }
+
     /// Called to advise on whether this tab has focus
     fn focus_changed(&self, _focused: bool) {}
 
@@ -332,6 +401,17 @@ pub trait Pane: Downcast {
         false
     }
 
+    /// Returns the names of the processes in the pane that mean that
+    /// closing it should be confirmed.  Panes that can't determine their
+    /// processes use their title if they can't be closed without prompting.
+    fn get_processes_preventing_close(&self) -> Vec<String> {
+        if self.can_close_without_prompting() {
+            vec![]
+        } else {
+            vec![self.get_title()]
+        }
+    }
+
     /// Performs a search.
     /// If the result is empty then there are no matches.
     /// Otherwise, the result shall contain all possible matches.
@@ -359,6 +439,18 @@ pub trait Pane: Downcast {
 
     fn get_current_working_dir(&self) -> Option<Url>;
 
+    /// Returns the path to the executable of the foreground
+    /// process in the pane, if it can be determined
+    fn get_foreground_process_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the command line of the foreground process in the pane,
+    /// if it is something other than the program that the pane spawned
+    fn get_foreground_process_argv(&self) -> Option<Vec<String>> {
+        None
+    }
+
     fn trickle_paste(&self, text: String) -> anyhow::Result<()> {
         if text.len() <= PASTE_CHUNK_SIZE {
             // Send it all now
@@ -418,7 +510,14 @@ mod test {
             )
         }
         fn get_dimensions(&self) -> RenderableDimensions {
-            unimplemented!()
+            // The last two lines are on the screen
+            RenderableDimensions {
+                cols: 20,
+                viewport_rows: 2,
+                scrollback_rows: self.lines.len(),
+                physical_top: self.lines.len() as StableRowIndex - 2,
+                scrollback_top: 0,
+            }
         }
 
         fn get_title(&self) -> String {
@@ -877,4 +976,22 @@ mod test {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn get_text() {
+        let pane = FakePane {
+            lines: ["one", "two  ", "three", "four"]
+                .iter()
+                .map(|s| Line::from_text(s, &Default::default()))
+                .collect(),
+        };
+        assert_eq!(pane.get_text(None, None, false), "three\nfour\n");
+        assert_eq!(pane.get_text(Some(-1), Some(0), false), "two\nthree\n");
+        // The range is clamped to the lines that exist
+        assert_eq!(
+            pane.get_text(Some(-100), Some(100), false),
+            "one\ntwo\nthree\nfour\n"
+        );
+        assert_eq!(pane.get_text(Some(1), Some(0), false), "");
+    }
 }
@@ -0,0 +1,489 @@
+//! A session records the windows, tabs and panes of the mux, along with
+//! the sizes of the splits and the working directory of each pane, so
+//! that the same structure can be recreated after a restart, for example
+//! by `wezterm cli save-session` and `wezterm cli restore-session`.
+//! Only the structure is recreated: the panes spawn new programs.
+//! Tabs that belong to a remote domain that isn't connected when the
+//! session is restored are recreated when the domain is attached.
+use crate::domain::{Domain, DomainId, DomainState, LocalDomain};
+use crate::pane::Pane;
+use crate::tab::{PaneNode, SplitDirectionAndSize, Tab};
+use crate::window::WindowId;
+use crate::Mux;
+use anyhow::{anyhow, Context};
+use portable_pty::{CommandBuilder, PtySize};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use url::Url;
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug, Default)]
+pub struct SessionState {
+    pub windows: Vec<WindowState>,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct WindowState {
+    pub tabs: Vec<TabState>,
+    pub active_tab: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct TabState {
+    /// The name of the domain in which the panes are spawned
+    pub domain: String,
+    pub size: PtySize,
+    pub root: PaneTree,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub enum PaneTree {
+    Split {
+        node: SplitDirectionAndSize,
+        first: Box<PaneTree>,
+        second: Box<PaneTree>,
+    },
+    Leaf(PaneState),
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct PaneState {
+    /// The working directory, as a url
+    pub cwd: Option<String>,
+    /// The command line of the foreground process, if it was recorded
+    pub command: Option<Vec<String>>,
+    pub is_active: bool,
+    pub is_zoomed: bool,
+}
+
+impl PaneTree {
+    fn first_leaf(&self) -> &PaneState {
+        match self {
+            PaneTree::Split { first, .. } => first.first_leaf(),
+            PaneTree::Leaf(pane) => pane,
+        }
+    }
+
+    /// Returns the splits in preorder
+    fn splits(&self, result: &mut Vec<SplitDirectionAndSize>) {
+        if let PaneTree::Split {
+            node,
+            first,
+            second,
+        } = self
+        {
+            result.push(*node);
+            first.splits(result);
+            second.splits(result);
+        }
+    }
+}
+
+thread_local! {
+    /// The windows that were restored for each detached domain,
+    /// holding only the tabs that belong to that domain
+    static PENDING: RefCell<HashMap<String, Vec<WindowState>>> = RefCell::new(HashMap::new());
+}
+
+/// Returns the path of the file that holds the session if no
+/// other file is specified
+pub fn default_session_file() -> PathBuf {
+    config::DATA_DIR.join("session.json")
+}
+
+pub fn read_session_file(path: &Path) -> anyhow::Result<SessionState> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))
+}
+
+pub fn write_session_file(path: &Path, session: &SessionState) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("creating directory {}", dir.display()))?;
+    }
+    let data = serde_json::to_vec_pretty(session)?;
+    std::fs::write(path, data).with_context(|| format!("writing {}", path.display()))
+}
+
+/// Records the windows, tabs and panes of the mux.
+/// If `include_commands` is true, the command line of the foreground
+/// process of each pane is recorded so that it can be run again.
+pub fn save_session(mux: &Mux, include_commands: bool) -> SessionState {
+    let mut window_ids = mux.iter_windows();
+    window_ids.sort();
+
+    let mut windows = vec![];
+    for window_id in window_ids {
+        let (tabs, active_idx) = match mux.get_window(window_id) {
+            Some(window) => (
+                window.iter().cloned().collect::<Vec<_>>(),
+                window.get_active_idx(),
+            ),
+            None => continue,
+        };
+
+        let mut state = WindowState {
+            tabs: vec![],
+            active_tab: 0,
+        };
+        for (idx, tab) in tabs.iter().enumerate() {
+            if let Some(tab) = save_tab(mux, tab, include_commands) {
+                if idx == active_idx {
+                    state.active_tab = state.tabs.len();
+                }
+                state.tabs.push(tab);
+            }
+        }
+        if !state.tabs.is_empty() {
+            windows.push(state);
+        }
+    }
+
+    SessionState { windows }
+}
+
+fn save_tab(mux: &Mux, tab: &Rc<Tab>, include_commands: bool) -> Option<TabState> {
+    let pane = tab.get_active_pane()?;
+    let domain = mux.get_domain(pane.domain_id())?;
+    if !domain.spawnable() {
+        return None;
+    }
+
+    fn convert(mux: &Mux, node: PaneNode, include_commands: bool) -> Option<PaneTree> {
+        match node {
+            PaneNode::Empty => None,
+            PaneNode::Split { left, right, node } => Some(PaneTree::Split {
+                node,
+                first: Box::new(convert(mux, *left, include_commands)?),
+                second: Box::new(convert(mux, *right, include_commands)?),
+            }),
+            PaneNode::Leaf(entry) => {
+                let command = if include_commands {
+                    mux.get_pane(entry.pane_id)
+                        .and_then(|pane| pane.get_foreground_process_argv())
+                } else {
+                    None
+                };
+                Some(PaneTree::Leaf(PaneState {
+                    cwd: entry.working_dir.map(|dir| dir.url.to_string()),
+                    command,
+                    is_active: entry.is_active_pane,
+                    is_zoomed: entry.is_zoomed_pane,
+                }))
+            }
+        }
+    }
+
+    Some(TabState {
+        domain: domain.domain_name().to_string(),
+        size: tab.get_size(),
+        root: convert(mux, tab.codec_pane_tree(), include_commands)?,
+    })
+}
+
+/// Recreates the windows, tabs and panes of the session.
+/// Tabs whose domain is detached are recreated when it is attached,
+/// by `restore_pending_session`.
+pub async fn restore_session(session: SessionState) -> anyhow::Result<()> {
+    let mux = Mux::get().ok_or_else(|| anyhow!("must be called on main thread"))?;
+
+    for window in session.windows {
+        let mut available = vec![];
+        let mut pending: HashMap<String, Vec<TabState>> = HashMap::new();
+        for tab in window.tabs.iter() {
+            match mux.get_domain_by_name(&tab.domain) {
+                Some(domain) if domain.state() == DomainState::Detached => {
+                    pending
+                        .entry(tab.domain.clone())
+                        .or_default()
+                        .push(tab.clone());
+                }
+                Some(_) => available.push(tab),
+                None => log::error!(
+                    "session restore: skipping a tab in domain {}, which doesn't exist",
+                    tab.domain
+                ),
+            }
+        }
+
+        for (domain_name, tabs) in pending {
+            log::info!(
+                "session restore: {} tabs will be restored when domain {} is attached",
+                tabs.len(),
+                domain_name
+            );
+            PENDING.with(|pending| {
+                pending
+                    .borrow_mut()
+                    .entry(domain_name)
+                    .or_default()
+                    .push(WindowState {
+                        tabs,
+                        active_tab: 0,
+                    })
+            });
+        }
+
+        if available.is_empty() {
+            continue;
+        }
+        let active_tab = window.tabs.get(window.active_tab);
+        restore_window(&mux, available, active_tab).await?;
+    }
+
+    Ok(())
+}
+
+/// Recreates the tabs of `domain_id` that were recorded when the
+/// session was restored while that domain was detached.
+/// If the domain already has panes, such as when it is a mux server
+/// that kept running, they are left to represent the session instead.
+pub async fn restore_pending_session(domain_id: DomainId) -> anyhow::Result<()> {
+    let mux = Mux::get().ok_or_else(|| anyhow!("must be called on main thread"))?;
+    let domain = match mux.get_domain(domain_id) {
+        Some(domain) => domain,
+        None => return Ok(()),
+    };
+    let windows = match PENDING.with(|pending| pending.borrow_mut().remove(domain.domain_name())) {
+        Some(windows) => windows,
+        None => return Ok(()),
+    };
+
+    if mux
+        .iter_panes()
+        .iter()
+        .any(|pane| pane.domain_id() == domain_id)
+    {
+        log::info!(
+            "session restore: domain {} already has panes, so its tabs are not restored",
+            domain.domain_name()
+        );
+        return Ok(());
+    }
+
+    for window in windows {
+        restore_window(&mux, window.tabs.iter().collect(), None).await?;
+    }
+    Ok(())
+}
+
+async fn restore_window(
+    mux: &Rc<Mux>,
+    tabs: Vec<&TabState>,
+    active_tab: Option<&TabState>,
+) -> anyhow::Result<()> {
+    let window_builder = mux.new_empty_window();
+    let window_id = *window_builder;
+
+    let mut active_tab_id = None;
+    for tab in tabs {
+        let restored = match restore_tab(mux, window_id, tab).await {
+            Ok(restored) => restored,
+            Err(err) => {
+                log::error!("session restore: failed to restore a tab: {:#}", err);
+                continue;
+            }
+        };
+        if active_tab == Some(tab) {
+            active_tab_id = Some(restored.tab_id());
+        }
+    }
+
+    if let Some(tab_id) = active_tab_id {
+        if let Some(mut window) = mux.get_window_mut(window_id) {
+            if let Some(idx) = window.idx_by_id(tab_id) {
+                window.set_active_without_saving(idx);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn restore_tab(
+    mux: &Rc<Mux>,
+    window_id: WindowId,
+    tab: &TabState,
+) -> anyhow::Result<Rc<Tab>> {
+    let domain = mux
+        .get_domain_by_name(&tab.domain)
+        .ok_or_else(|| anyhow!("domain {} doesn't exist", tab.domain))?;
+
+    let first = tab.root.first_leaf();
+    let restored = domain
+        .spawn(
+            tab.size,
+            command(first),
+            working_dir(&domain, first),
+            window_id,
+        )
+        .await?;
+    let pane = restored
+        .get_active_pane()
+        .ok_or_else(|| anyhow!("newly spawned tab to have a pane"))?;
+
+    // Splitting a pane replaces it with a split that holds it in the
+    // first position, so splitting the pane that occupies each split
+    // recreates the shape of the tree
+    let mut active = None;
+    let mut zoomed = None;
+    let mut stack = vec![(&tab.root, pane)];
+    while let Some((node, pane)) = stack.pop() {
+        match node {
+            PaneTree::Leaf(state) => {
+                if state.is_active {
+                    active = Some(Rc::clone(&pane));
+                }
+                if state.is_zoomed {
+                    zoomed = Some(pane);
+                }
+            }
+            PaneTree::Split {
+                node,
+                first,
+                second,
+            } => {
+                let leaf = second.first_leaf();
+                let split = domain
+                    .split_pane(
+                        command(leaf),
+                        working_dir(&domain, leaf),
+                        restored.tab_id(),
+                        pane.pane_id(),
+                        node.direction,
+                    )
+                    .await?;
+                stack.push((second.as_ref(), split));
+                stack.push((first.as_ref(), pane));
+            }
+        }
+    }
+
+    let mut splits = vec![];
+    tab.root.splits(&mut splits);
+    if !restored.restore_split_sizes(tab.size, &splits) {
+        log::warn!(
+            "session restore: tab {} doesn't have the expected splits",
+            restored.tab_id()
+        );
+    }
+    if let Some(pane) = zoomed.as_ref().or_else(|| active.as_ref()) {
+        restored.set_active_pane(pane);
+    }
+    if zoomed.is_some() {
+        restored.set_zoomed(true);
+    }
+    Ok(restored)
+}
+
+fn command(pane: &PaneState) -> Option<CommandBuilder> {
+    let argv = pane.command.as_ref()?;
+    if argv.is_empty() {
+        return None;
+    }
+    Some(CommandBuilder::from_argv(
+        argv.iter().map(Into::into).collect(),
+    ))
+}
+
+/// Returns the directory in which to spawn the pane.
+/// For local panes, a directory that no longer exists is replaced
+/// by the home directory, so that the rest of the session can still
+/// be restored.
+fn working_dir(domain: &Arc<dyn Domain>, pane: &PaneState) -> Option<String> {
+    let url = Url::parse(pane.cwd.as_ref()?).ok()?;
+    if url.scheme() != "file" {
+        return None;
+    }
+    let path = percent_encoding::percent_decode_str(url.path())
+        .decode_utf8()
+        .ok()?
+        .into_owned();
+    // On Windows, the path of a file url looks like `/C:/Users`
+    let path = if matches!(path.as_bytes(), [b'/', _, b':', ..]) {
+        path[1..].to_string()
+    } else {
+        path
+    };
+
+    if domain.downcast_ref::<LocalDomain>().is_some() && !Path::new(&path).is_dir() {
+        log::warn!(
+            "session restore: {} no longer exists, using {} instead",
+            path,
+            config::HOME_DIR.display()
+        );
+        return Some(config::HOME_DIR.to_string_lossy().into_owned());
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tab::SplitDirection;
+
+    #[test]
+    fn pane_tree() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        let leaf = |cwd: &str, is_active| {
+            Box::new(PaneTree::Leaf(PaneState {
+                cwd: Some(cwd.to_string()),
+                command: None,
+                is_active,
+                is_zoomed: false,
+            }))
+        };
+        let split = |direction, first, second| SplitDirectionAndSize {
+            direction,
+            first,
+            second,
+        };
+        let vertical = split(
+            SplitDirection::Vertical,
+            PtySize { rows: 11, ..size },
+            PtySize { rows: 12, ..size },
+        );
+        let horizontal = split(
+            SplitDirection::Horizontal,
+            PtySize { cols: 40, ..size },
+            PtySize { cols: 39, ..size },
+        );
+        let tree = PaneTree::Split {
+            node: horizontal,
+            first: Box::new(PaneTree::Split {
+                node: vertical,
+                first: leaf("file:///a", false),
+                second: leaf("file:///b", true),
+            }),
+            second: leaf("file:///c", false),
+        };
+
+        assert_eq!(tree.first_leaf().cwd.as_deref(), Some("file:///a"));
+        let mut splits = vec![];
+        tree.splits(&mut splits);
+        assert_eq!(splits, vec![horizontal, vertical]);
+
+        let session = SessionState {
+            windows: vec![WindowState {
+                tabs: vec![TabState {
+                    domain: "local".to_string(),
+                    size,
+                    root: tree,
+                }],
+                active_tab: 0,
+            }],
+        };
+        let json = serde_json::to_string(&session).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SessionState>(&json).unwrap(),
+            session
+        );
+    }
+}
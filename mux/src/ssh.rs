@@ -2,6 +2,8 @@ use crate::connui::ConnectionUI;
 use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState};
 use crate::localpane::LocalPane;
 use crate::pane::{alloc_pane_id, Pane, PaneId};
+use crate::sshcli::SshCliRelay;
+use crate::sshprompt::{ssh_prompt_handler, SshPromptHandler};
 use crate::tab::{SplitDirection, Tab, TabId};
 use crate::window::WindowId;
 use crate::Mux;
@@ -24,7 +26,10 @@ use termwiz::lineedit::*;
 use termwiz::render::terminfo::TerminfoRenderer;
 use termwiz::surface::Change;
 use termwiz::terminal::{ScreenSize, Terminal, TerminalWaker};
-use wezterm_ssh::{ConfigMap, Session, SessionEvent, SshChildProcess, SshPty};
+use wezterm_ssh::{
+    ConfigMap, HostVerificationDecision, HostVerificationEvent, Session, SessionEvent,
+    SshChildProcess, SshPty,
+};
 
 #[derive(Default)]
 struct PasswordPromptHost {
@@ -56,6 +61,18 @@ impl LineEditorHost for PasswordPromptHost {
     }
 }
 
+/// Asks about an unknown host in the modal prompt, treating a failure
+/// to show the prompt as declining to trust the host
+fn verify_host_in_modal(
+    modal: &Arc<dyn SshPromptHandler>,
+    verify: &HostVerificationEvent,
+) -> HostVerificationDecision {
+    modal.verify_host(verify).unwrap_or_else(|err| {
+        log::error!("while verifying ssh host {}: {:#}", verify.host, err);
+        HostVerificationDecision::Reject
+    })
+}
+
 pub fn ssh_connect_with_ui(
     ssh_config: wezterm_ssh::ConfigMap,
     ui: &mut ConnectionUI,
@@ -67,6 +84,7 @@ pub fn ssh_connect_with_ui(
             .expect("ssh config to always set hostname");
         ui.output_str(&format!("Connecting to {} using SSH\n", remote_address));
         let (session, events) = Session::connect(ssh_config.clone())?;
+        let modal = ssh_prompt_handler();
 
         while let Ok(event) = smol::block_on(events.recv()) {
             match event {
@@ -75,6 +93,11 @@ pub fn ssh_connect_with_ui(
                         ui.output_str(&format!("{}\n", banner));
                     }
                 }
+                SessionEvent::HostVerify(verify) if modal.is_some() => {
+                    ui.output_str(&format!("{}\n", verify.message));
+                    let decision = verify_host_in_modal(modal.as_ref().unwrap(), &verify);
+                    smol::block_on(verify.decide(decision)).context("send verify response")?;
+                }
                 SessionEvent::HostVerify(verify) => {
                     ui.output_str(&format!("{}\n", verify.message));
                     let ok = if let Ok(line) = ui.input("Enter [y/n]> ") {
@@ -87,8 +110,14 @@ pub fn ssh_connect_with_ui(
                     };
                     smol::block_on(verify.answer(ok)).context("send verify response")?;
                 }
+                SessionEvent::Authenticate(auth) if modal.is_some() => {
+                    let answers = modal.as_ref().unwrap().authenticate(&auth)?;
+                    smol::block_on(auth.answer(answers))?;
+                }
                 SessionEvent::Authenticate(auth) => {
-                    if !auth.username.is_empty() {
+                    if let Some(label) = &auth.label {
+                        ui.output_str(&format!("Authentication for {}\n", label));
+                    } else if !auth.username.is_empty() {
                         ui.output_str(&format!("Authentication for {}\n", auth.username));
                     }
                     if !auth.instructions.is_empty() {
@@ -115,7 +144,9 @@ pub fn ssh_connect_with_ui(
                     smol::block_on(auth.answer(answers))?;
                 }
                 SessionEvent::Error(err) => {
-                    anyhow::bail!("Error: {}", err);
+                    // Keep the error intact so that callers can tell
+                    // why the session failed
+                    return Err(err.into());
                 }
                 SessionEvent::Authenticated => return Ok(session),
             }
@@ -132,11 +163,16 @@ pub fn ssh_connect_with_ui(
 /// pane, we play some tricks with wrapped versions of the pty, child
 /// and the reader and writer instances so that we can inject the
 /// interactive setup.  The bulk of that is driven by `connect_ssh_session`.
+/// When the GUI has registered an `SshPromptHandler`, the prompts are
+/// shown in its modal instead, and the pane only shows the progress.
+/// Once connected, the domain relays `wezterm cli` in its panes to the
+/// mux; see `sshcli`.
 pub struct RemoteSshDomain {
     session: Session,
     id: DomainId,
     name: String,
     events: RefCell<Option<smol::channel::Receiver<SessionEvent>>>,
+    cli_relay: Arc<Mutex<Option<SshCliRelay>>>,
 }
 
 impl RemoteSshDomain {
@@ -148,6 +184,7 @@ impl RemoteSshDomain {
             name: format!("SSH to {}", name),
             session,
             events: RefCell::new(Some(events)),
+            cli_relay: Arc::new(Mutex::new(None)),
         })
     }
 
@@ -156,6 +193,14 @@ impl RemoteSshDomain {
     fn take_events(&self) -> Option<smol::channel::Receiver<SessionEvent>> {
         self.events.borrow_mut().take()
     }
+
+    /// Tells `wezterm cli` in a new pane how to reach the mux,
+    /// once the session has connected
+    fn apply_cli_relay_env(&self, env: &mut HashMap<String, String>) {
+        if let Some(relay) = self.cli_relay.lock().unwrap().as_ref() {
+            relay.apply_env(env);
+        }
+    }
 }
 
 /// Carry out the authentication process and create the initial pty.
@@ -170,7 +215,8 @@ fn connect_ssh_session(
     pty_tx: Sender<SshPty>,
     size: Arc<Mutex<PtySize>>,
     command_line: Option<String>,
-    env: HashMap<String, String>,
+    mut env: HashMap<String, String>,
+    cli_relay: Arc<Mutex<Option<SshCliRelay>>>,
 ) -> anyhow::Result<()> {
     struct StdoutShim<'a> {
         size: Arc<Mutex<PtySize>>,
@@ -331,6 +377,7 @@ fn connect_ssh_session(
     }
 
     // Process authentication related events
+    let modal = ssh_prompt_handler();
     while let Ok(event) = smol::block_on(events.recv()) {
         match event {
             SessionEvent::Banner(banner) => {
@@ -338,6 +385,11 @@ fn connect_ssh_session(
                     shim.output_line(&banner)?;
                 }
             }
+            SessionEvent::HostVerify(verify) if modal.is_some() => {
+                shim.output_line(&verify.message)?;
+                let decision = verify_host_in_modal(modal.as_ref().unwrap(), &verify);
+                smol::block_on(verify.decide(decision)).context("send verify response")?;
+            }
             SessionEvent::HostVerify(verify) => {
                 shim.output_line(&verify.message)?;
                 let mut editor = LineEditor::new(&mut shim);
@@ -354,8 +406,14 @@ fn connect_ssh_session(
                 };
                 smol::block_on(verify.answer(ok)).context("send verify response")?;
             }
+            SessionEvent::Authenticate(auth) if modal.is_some() => {
+                let answers = modal.as_ref().unwrap().authenticate(&auth)?;
+                smol::block_on(auth.answer(answers))?;
+            }
             SessionEvent::Authenticate(auth) => {
-                if !auth.username.is_empty() {
+                if let Some(label) = &auth.label {
+                    shim.output_line(&format!("Authentication for {}", label))?;
+                } else if !auth.username.is_empty() {
                     shim.output_line(&format!("Authentication for {}", auth.username))?;
                 }
                 if !auth.instructions.is_empty() {
@@ -385,7 +443,21 @@ fn connect_ssh_session(
             }
             SessionEvent::Authenticated => {
                 // Our session has been authenticated: we can now
-                // set up the real pty for the pane
+                // set up the relay for wezterm cli and the real
+                // pty for the pane
+                match SshCliRelay::establish(&session) {
+                    Ok(relay) => {
+                        relay.apply_env(&mut env);
+                        cli_relay.lock().unwrap().replace(relay);
+                    }
+                    Err(err) => {
+                        log::warn!(
+                            "wezterm cli will not be able to reach the mux \
+                             from the panes of this domain: {:#}",
+                            err
+                        );
+                    }
+                }
                 match smol::block_on(session.request_pty(
                     &config::configuration().term,
                     *size.lock().unwrap(),
@@ -434,13 +506,41 @@ fn connect_ssh_session(
     Ok(())
 }
 
+/// Returns the command line to run on the remote host, or None to run
+/// the login shell of the user.
+/// The ssh protocol has no way to specify the working directory of the
+/// remote command, so when `command_dir` is set the command is wrapped
+/// up in a shell that changes to that directory first.  If the directory
+/// doesn't exist, the command runs in the home directory instead.
+fn remote_command_line(
+    cmd: &CommandBuilder,
+    command_dir: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    let command_line = if cmd.is_default_prog() {
+        None
+    } else {
+        Some(cmd.as_unix_command_line()?)
+    };
+    let dir = match command_dir {
+        Some(dir) => dir,
+        None => return Ok(command_line),
+    };
+    let script = format!(
+        "cd \"$0\" 2>/dev/null; exec {}",
+        command_line.as_deref().unwrap_or("\"${SHELL:-sh}\" -l")
+    );
+    let wrapper =
+        CommandBuilder::from_argv(vec!["sh".into(), "-c".into(), script.into(), dir.into()]);
+    Ok(Some(wrapper.as_unix_command_line()?))
+}
+
 #[async_trait(?Send)]
 impl Domain for RemoteSshDomain {
     async fn spawn(
         &self,
         size: PtySize,
         command: Option<CommandBuilder>,
-        _command_dir: Option<String>,
+        command_dir: Option<String>,
         window: WindowId,
     ) -> Result<Rc<Tab>, Error> {
         let pane_id = alloc_pane_id();
@@ -450,16 +550,13 @@ impl Domain for RemoteSshDomain {
             None => CommandBuilder::new_default_prog(),
         };
 
-        let command_line = if cmd.is_default_prog() {
-            None
-        } else {
-            Some(cmd.as_unix_command_line()?)
-        };
+        let command_line = remote_command_line(&cmd, command_dir.as_deref())?;
         let mut env: HashMap<String, String> = cmd
             .iter_extra_env_as_str()
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect();
         env.insert("WEZTERM_PANE".to_string(), pane_id.to_string());
+        self.apply_cli_relay_env(&mut env);
 
         let pty: Box<dyn portable_pty::MasterPty>;
         let child: Box<dyn portable_pty::Child + Send>;
@@ -519,6 +616,7 @@ impl Domain for RemoteSshDomain {
             // to perform the blocking (from its perspective) terminal
             // UI to carry out any authentication.
             let session = self.session.clone();
+            let cli_relay = Arc::clone(&self.cli_relay);
             let mut stdout_write = BufWriter::new(stdout_write);
             std::thread::spawn(move || {
                 if let Err(err) = connect_ssh_session(
@@ -533,6 +631,7 @@ impl Domain for RemoteSshDomain {
                     size,
                     command_line,
                     env,
+                    cli_relay,
                 ) {
                     let _ = write!(stdout_write, "{:#}", err);
                     log::error!("Failed to connect ssh: {:#}", err);
@@ -581,7 +680,7 @@ impl Domain for RemoteSshDomain {
     async fn split_pane(
         &self,
         command: Option<CommandBuilder>,
-        _command_dir: Option<String>,
+        command_dir: Option<String>,
         tab: TabId,
         pane_id: PaneId,
         direction: SplitDirection,
@@ -616,16 +715,13 @@ impl Domain for RemoteSshDomain {
         };
         let pane_id = alloc_pane_id();
 
-        let command_line = if cmd.is_default_prog() {
-            None
-        } else {
-            Some(cmd.as_unix_command_line()?)
-        };
+        let command_line = remote_command_line(&cmd, command_dir.as_deref())?;
         let mut env: HashMap<String, String> = cmd
             .iter_extra_env_as_str()
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect();
         env.insert("WEZTERM_PANE".to_string(), pane_id.to_string());
+        self.apply_cli_relay_env(&mut env);
 
         let (pty, child) = self
             .session
@@ -0,0 +1,353 @@
+//! Lets `wezterm cli` be used in the panes of ssh domains.  Those panes
+//! run on the remote host, where there is no mux that the cli could talk
+//! to, so when an ssh domain connects, a relay to the mux of this wezterm
+//! is set up:
+//!
+//! * a listener on the loopback interface of this host, which connects
+//!   the connections that it accepts to the unix socket of the mux
+//! * a remote forward from the loopback interface of the remote host to
+//!   that listener
+//!
+//! The address of the remote end of the relay, and a random token that
+//! has to be presented before a connection is relayed, are passed to the
+//! panes of the domain in the `WEZTERM_SSH_MUX` and `WEZTERM_SSH_MUX_TOKEN`
+//! environment variables, which the cli uses when `WEZTERM_UNIX_SOCKET` is
+//! not set.  The token keeps out the other users of either host, who can
+//! also connect to their loopback interfaces.
+use crate::sshforward::{LocalListener, RemoteListener};
+use anyhow::{anyhow, bail, Context};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use wezterm_ssh::Session;
+
+/// The environment variable that holds the address of the relay
+pub const ADDRESS_ENV: &str = "WEZTERM_SSH_MUX";
+/// The environment variable that holds the token of the relay
+pub const TOKEN_ENV: &str = "WEZTERM_SSH_MUX_TOKEN";
+
+/// How long either end waits for the other during the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The longest line that is accepted during the handshake
+const MAX_LINE: usize = 1024;
+/// The reply of the relay once it has connected to the mux
+const ACCEPTED: &str = "OK";
+/// The prefix of the reply of the relay when it refuses a connection
+const REFUSED: &str = "ERR ";
+
+/// Where the cli can reach the mux of the wezterm whose ssh
+/// domain the pane that it is running in belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshCliRelayTarget {
+    address: String,
+    token: String,
+}
+
+impl SshCliRelayTarget {
+    /// Returns the relay that was passed to this process by its
+    /// ssh domain, if any
+    pub fn from_env() -> Option<Self> {
+        let address = std::env::var(ADDRESS_ENV).ok()?;
+        let token = std::env::var(TOKEN_ENV).ok()?;
+        if address.is_empty() || token.is_empty() {
+            return None;
+        }
+        Some(Self { address, token })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Connects to the relay and presents the token, returning the
+    /// stream once the relay has connected it to the mux
+    pub fn connect(&self) -> anyhow::Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.address)
+            .with_context(|| format!("connecting to the wezterm relay at {}", self.address))?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        stream.write_all(format!("{}\n", self.token).as_bytes())?;
+        let reply = read_line(&mut stream).with_context(|| {
+            format!("reading the reply of the wezterm relay at {}", self.address)
+        })?;
+        stream.set_read_timeout(None)?;
+
+        if reply == ACCEPTED {
+            Ok(stream)
+        } else if let Some(reason) = reply.strip_prefix(REFUSED) {
+            bail!(
+                "the wezterm relay at {} refused the connection: {}",
+                self.address,
+                reason
+            );
+        } else {
+            bail!(
+                "unexpected reply {:?} from the wezterm relay at {}",
+                reply,
+                self.address
+            );
+        }
+    }
+}
+
+/// The relay of a connected ssh domain, which lives for as long
+/// as the domain
+pub struct SshCliRelay {
+    target: SshCliRelayTarget,
+    // Declared before the local listener so that the remote host
+    // stops forwarding connections before the listener goes away
+    _remote: RemoteListener,
+    _local: LocalListener,
+}
+
+impl std::fmt::Debug for SshCliRelay {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "SshCliRelay {{ address: {} }}", self.target.address)
+    }
+}
+
+impl SshCliRelay {
+    /// Sets up the relay through `session`, which requires the remote
+    /// host to allow forwarding TCP connections, and the session to use
+    /// a backend that supports remote forwards
+    #[cfg(unix)]
+    pub fn establish(session: &Session) -> anyhow::Result<Self> {
+        use std::net::{Ipv4Addr, TcpListener};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let token = new_token()?;
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .context("listening on the loopback interface")?;
+        let addr = listener.local_addr()?;
+
+        // Ask for the remote forward before starting to accept, so that
+        // there is nothing to tear down if the forward can't be made
+        let bind_host = Ipv4Addr::LOCALHOST.to_string();
+        let port = smol::block_on(session.listen_forward(&bind_host, 0, &bind_host, addr.port()))
+            .context("asking the remote host to forward a port to the mux")?;
+        let remote = RemoteListener {
+            session: session.clone(),
+            bind_host: bind_host.clone(),
+            bind_port: port,
+        };
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let shutdown = Arc::clone(&shutdown);
+            let token = token.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            log::error!("accepting connection on {}: {:#}", addr, err);
+                            continue;
+                        }
+                    };
+                    let token = token.clone();
+                    std::thread::spawn(move || {
+                        if let Err(err) = relay_connection(stream, &token, mux_socket_path()) {
+                            log::error!("wezterm cli relay on {}: {:#}", addr, err);
+                        }
+                    });
+                }
+            })
+        };
+        let local = LocalListener {
+            addr,
+            shutdown,
+            thread: Some(thread),
+        };
+
+        Ok(Self {
+            target: SshCliRelayTarget {
+                address: format!("{}:{}", bind_host, port),
+                token,
+            },
+            _remote: remote,
+            _local: local,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn establish(_session: &Session) -> anyhow::Result<Self> {
+        bail!("relaying wezterm cli to the mux is not supported on this system");
+    }
+
+    /// Adds the variables that tell `wezterm cli` where to find the
+    /// relay to the environment of a pane
+    pub fn apply_env(&self, env: &mut HashMap<String, String>) {
+        env.insert(ADDRESS_ENV.to_string(), self.target.address.clone());
+        env.insert(TOKEN_ENV.to_string(), self.target.token.clone());
+    }
+}
+
+/// Returns the unix socket of the mux of this process, which both the
+/// GUI and the mux server set in their environment
+#[cfg(unix)]
+fn mux_socket_path() -> Option<std::path::PathBuf> {
+    std::env::var_os("WEZTERM_UNIX_SOCKET")
+        .map(Into::into)
+        .or_else(|| {
+            config::configuration()
+                .unix_domains
+                .first()
+                .map(|dom| dom.socket_path())
+        })
+}
+
+/// Returns 32 random bytes from the operating system as hex
+#[cfg(unix)]
+fn new_token() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 32];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut bytes))
+        .context("reading /dev/urandom")?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Compares the tokens without bailing out at the first difference,
+/// so that the time taken doesn't reveal how much of it was right
+#[cfg(unix)]
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Reads a line, a byte at a time so that nothing after it is consumed
+fn read_line<R: Read>(reader: &mut R) -> anyhow::Result<String> {
+    let mut line = vec![];
+    loop {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            bail!("the connection was closed");
+        }
+        if byte[0] == b'\n' {
+            break;
+        }
+        if line.len() >= MAX_LINE {
+            bail!("the line is too long");
+        }
+        line.push(byte[0]);
+    }
+    String::from_utf8(line).map_err(|_| anyhow!("the line is not UTF-8"))
+}
+
+/// Checks the token presented by `stream`, then relays it to the
+/// mux listening on `socket`
+#[cfg(unix)]
+fn relay_connection(
+    mut stream: TcpStream,
+    token: &str,
+    socket: Option<std::path::PathBuf>,
+) -> anyhow::Result<()> {
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+
+    let refuse = |stream: &mut TcpStream, reason: &str| {
+        stream
+            .write_all(format!("{}{}\n", REFUSED, reason).as_bytes())
+            .ok();
+    };
+
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let presented = read_line(&mut stream).context("reading the token")?;
+    if !tokens_match(&presented, token) {
+        refuse(&mut stream, "invalid token");
+        bail!("refused a connection that presented an invalid token");
+    }
+
+    let mux = socket
+        .ok_or_else(|| anyhow!("the unix socket of the mux is not known"))
+        .and_then(|path| {
+            UnixStream::connect(&path).with_context(|| format!("connecting to {}", path.display()))
+        });
+    let mux = match mux {
+        Ok(mux) => mux,
+        Err(err) => {
+            refuse(&mut stream, &format!("{:#}", err));
+            return Err(err);
+        }
+    };
+    stream.set_read_timeout(None)?;
+    stream.write_all(format!("{}\n", ACCEPTED).as_bytes())?;
+
+    let mut from_client = stream.try_clone()?;
+    let mut to_mux = mux.try_clone()?;
+    let upstream = std::thread::spawn(move || {
+        std::io::copy(&mut from_client, &mut to_mux).ok();
+        to_mux.shutdown(Shutdown::Write).ok();
+    });
+
+    let (mut to_client, mut from_mux) = (stream, mux);
+    std::io::copy(&mut from_mux, &mut to_client).ok();
+    to_client.shutdown(Shutdown::Both).ok();
+    upstream.join().ok();
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use std::os::unix::net::UnixListener;
+
+    /// Starts a relay that accepts one connection, in front of a mux
+    /// that reverses whatever line is sent to it
+    fn start_relay(socket: &std::path::Path) -> SshCliRelayTarget {
+        std::fs::remove_file(socket).ok();
+        let mux = UnixListener::bind(socket).unwrap();
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = mux.accept() {
+                let line = read_line(&mut stream).unwrap();
+                let reply: String = line.chars().rev().collect();
+                stream.write_all(format!("{}\n", reply).as_bytes()).unwrap();
+            }
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let socket = socket.to_path_buf();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            relay_connection(stream, "secret", Some(socket)).ok();
+        });
+
+        SshCliRelayTarget {
+            address,
+            token: "secret".to_string(),
+        }
+    }
+
+    #[test]
+    fn relay() {
+        let socket =
+            std::env::temp_dir().join(format!("wezterm-sshcli-test-{}", std::process::id()));
+        let target = start_relay(&socket);
+        let mut stream = target.connect().unwrap();
+        stream.write_all(b"hello\n").unwrap();
+        assert_eq!(read_line(&mut stream).unwrap(), "olleh");
+
+        let mut target = start_relay(&socket);
+        target.token = "guess".to_string();
+        let err = target.connect().unwrap_err();
+        assert!(format!("{:#}", err).contains("invalid token"), "{:#}", err);
+
+        std::fs::remove_file(&socket).ok();
+    }
+
+    #[test]
+    fn matching() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abc", "abd"));
+        assert!(!tokens_match("abc", "abcd"));
+        assert!(!tokens_match("", "abc"));
+    }
+}
@@ -0,0 +1,316 @@
+//! Port forwards for ssh domains.  The forwards are set up each time
+//! that the domain connects to the remote host, and are torn down when
+//! the `SshForwards` that holds them is dropped, which happens when the
+//! domain is detached or before it reconnects.
+//! The status of each forward is kept in a registry so that it can be
+//! reported by `wezterm cli list-ssh-forwards`.
+use crate::connui::ConnectionUI;
+use anyhow::anyhow;
+use config::{SshDomain, SshForward};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use wezterm_ssh::Session;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SshForwardKind {
+    /// Listens on this host and connects from the remote host,
+    /// like `ssh -L`
+    Local,
+    /// Listens on the remote host and connects from this host,
+    /// like `ssh -R`
+    Remote,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SshForwardState {
+    Listening,
+    /// The forward could not be set up, for example because
+    /// its port is already in use
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SshForwardStatus {
+    /// The name of the ssh domain
+    pub domain: String,
+    pub kind: SshForwardKind,
+    /// The address that is listened on.  Once listening, this
+    /// holds the port that is actually in use.
+    pub bind: String,
+    pub target: String,
+    pub state: SshForwardState,
+    /// The number of connections that have been forwarded.
+    /// Only local forwards count their connections.
+    pub connections: usize,
+}
+
+lazy_static::lazy_static! {
+    static ref FORWARDS: Mutex<BTreeMap<usize, SshForwardStatus>> = Mutex::new(BTreeMap::new());
+}
+static FORWARD_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the status of the forwards of the connected ssh domains
+pub fn ssh_forward_status() -> Vec<SshForwardStatus> {
+    FORWARDS.lock().unwrap().values().cloned().collect()
+}
+
+fn update_status<F: FnOnce(&mut SshForwardStatus)>(id: usize, func: F) {
+    if let Some(status) = FORWARDS.lock().unwrap().get_mut(&id) {
+        func(status);
+    }
+}
+
+/// The entry of a forward in the registry, which is removed
+/// when this is dropped
+struct Registration {
+    id: usize,
+}
+
+impl Registration {
+    fn new(status: SshForwardStatus) -> Self {
+        let id = FORWARD_ID.fetch_add(1, Ordering::Relaxed);
+        FORWARDS.lock().unwrap().insert(id, status);
+        Self { id }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        FORWARDS.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub(crate) struct LocalListener {
+    pub(crate) addr: SocketAddr,
+    pub(crate) shutdown: Arc<AtomicBool>,
+    pub(crate) thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for LocalListener {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // Wake up the thread, which is blocked accepting connections,
+        // so that it sees the shutdown and closes the listener before
+        // we return, leaving the port free for the next connection
+        let mut addr = self.addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        TcpStream::connect(addr).ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+pub(crate) struct RemoteListener {
+    pub(crate) session: Session,
+    pub(crate) bind_host: String,
+    pub(crate) bind_port: u16,
+}
+
+impl Drop for RemoteListener {
+    fn drop(&mut self) {
+        self.session
+            .cancel_forward(&self.bind_host, self.bind_port)
+            .ok();
+    }
+}
+
+struct Forward {
+    // Declared before the registration so that the listener
+    // is torn down before the forward leaves the registry
+    _local: Option<LocalListener>,
+    _remote: Option<RemoteListener>,
+    _registration: Registration,
+}
+
+/// The forwards of a connected ssh domain
+pub struct SshForwards {
+    _forwards: Vec<Forward>,
+}
+
+impl std::fmt::Debug for SshForwards {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "SshForwards {{...}}")
+    }
+}
+
+impl SshForwards {
+    /// Sets up the `local_forwards` and `remote_forwards` of `ssh_dom`
+    /// through `session`.  A forward that cannot be set up, for example
+    /// because its port is already in use, is reported and recorded as
+    /// failed in its status, but doesn't prevent the others from being
+    /// set up.
+    pub fn establish(ssh_dom: &SshDomain, session: &Session, ui: &ConnectionUI) -> Self {
+        let local = ssh_dom
+            .local_forwards
+            .iter()
+            .map(|forward| (SshForwardKind::Local, forward));
+        let remote = ssh_dom
+            .remote_forwards
+            .iter()
+            .map(|forward| (SshForwardKind::Remote, forward));
+        let forwards = local
+            .chain(remote)
+            .map(|(kind, forward)| establish_one(&ssh_dom.name, kind, forward, session, ui))
+            .collect();
+        Self {
+            _forwards: forwards,
+        }
+    }
+}
+
+fn establish_one(
+    domain: &str,
+    kind: SshForwardKind,
+    forward: &SshForward,
+    session: &Session,
+    ui: &ConnectionUI,
+) -> Forward {
+    let registration = Registration::new(SshForwardStatus {
+        domain: domain.to_string(),
+        kind,
+        bind: forward.bind.clone(),
+        target: forward.target.clone(),
+        state: SshForwardState::Listening,
+        connections: 0,
+    });
+
+    let listener = match kind {
+        SshForwardKind::Local => {
+            start_local(registration.id, forward, session).map(|local| (Some(local), None))
+        }
+        SshForwardKind::Remote => {
+            start_remote(registration.id, forward, session).map(|remote| (None, Some(remote)))
+        }
+    };
+
+    match listener {
+        Ok((local, remote)) => {
+            ui.output_str(&format!(
+                "Forwarding {:?} {} to {}\n",
+                kind, forward.bind, forward.target
+            ));
+            Forward {
+                _local: local,
+                _remote: remote,
+                _registration: registration,
+            }
+        }
+        Err(err) => {
+            let reason = format!("{:#}", err);
+            log::error!(
+                "{}: {:?} forward of {} to {} failed: {}",
+                domain,
+                kind,
+                forward.bind,
+                forward.target,
+                reason
+            );
+            ui.output_str(&format!(
+                "{:?} forward of {} to {} failed: {}\n",
+                kind, forward.bind, forward.target, reason
+            ));
+            update_status(registration.id, |status| {
+                status.state = SshForwardState::Failed(reason)
+            });
+            Forward {
+                _local: None,
+                _remote: None,
+                _registration: registration,
+            }
+        }
+    }
+}
+
+fn start_local(
+    id: usize,
+    forward: &SshForward,
+    session: &Session,
+) -> anyhow::Result<LocalListener> {
+    let (bind_host, bind_port) = forward.bind_host_and_port()?;
+    let (target_host, target_port) = forward.target_host_and_port()?;
+
+    let listener = TcpListener::bind((bind_host.as_str(), bind_port)).map_err(|err| {
+        if err.kind() == std::io::ErrorKind::AddrInUse {
+            anyhow!("{}:{} is already in use", bind_host, bind_port)
+        } else {
+            anyhow!("listening on {}:{}: {}", bind_host, bind_port, err)
+        }
+    })?;
+    let addr = listener.local_addr()?;
+    update_status(id, |status| status.bind = addr.to_string());
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let shutdown = Arc::clone(&shutdown);
+        let session = session.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if shutdown.load(Ordering::Relaxed) {
+                    break;
+                }
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        log::error!("accepting connection on {}: {:#}", addr, err);
+                        continue;
+                    }
+                };
+                update_status(id, |status| status.connections += 1);
+                if let Err(err) =
+                    smol::block_on(session.forward_socket(&target_host, target_port, stream))
+                {
+                    log::error!(
+                        "forwarding connection on {} to {}:{}: {:#}",
+                        addr,
+                        target_host,
+                        target_port,
+                        err
+                    );
+                }
+            }
+        })
+    };
+
+    Ok(LocalListener {
+        addr,
+        shutdown,
+        thread: Some(thread),
+    })
+}
+
+fn start_remote(
+    id: usize,
+    forward: &SshForward,
+    session: &Session,
+) -> anyhow::Result<RemoteListener> {
+    let (bind_host, bind_port) = forward.bind_host_and_port()?;
+    let (target_host, target_port) = forward.target_host_and_port()?;
+
+    let port =
+        smol::block_on(session.listen_forward(&bind_host, bind_port, &target_host, target_port))
+            .map_err(|err| {
+                anyhow!(
+                    "{:#}; {}:{} may already be in use on the remote host",
+                    err,
+                    bind_host,
+                    bind_port
+                )
+            })?;
+    update_status(id, |status| status.bind = format!("{}:{}", bind_host, port));
+
+    Ok(RemoteListener {
+        session: session.clone(),
+        bind_host,
+        bind_port: port,
+    })
+}
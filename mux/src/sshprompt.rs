@@ -0,0 +1,32 @@
+//! The GUI registers an `SshPromptHandler` so that the host verification
+//! and authentication prompts of ssh sessions are presented in a modal
+//! window of their own, which works even when no pane is visible yet.
+//! When there is no handler, such as in the mux server, the prompts are
+//! made in the terminal of the pane or connection window that is
+//! connecting.
+use std::sync::{Arc, Mutex};
+use wezterm_ssh::{AuthenticationEvent, HostVerificationDecision, HostVerificationEvent};
+
+pub trait SshPromptHandler: Send + Sync {
+    /// Asks whether to trust a host that is not in known_hosts
+    fn verify_host(
+        &self,
+        verify: &HostVerificationEvent,
+    ) -> anyhow::Result<HostVerificationDecision>;
+
+    /// Asks for the answers to the prompts of `auth`, one prompt at
+    /// a time, returning them in the same order as the prompts
+    fn authenticate(&self, auth: &AuthenticationEvent) -> anyhow::Result<Vec<String>>;
+}
+
+lazy_static::lazy_static! {
+    static ref HANDLER: Mutex<Option<Arc<dyn SshPromptHandler>>> = Mutex::new(None);
+}
+
+pub fn set_ssh_prompt_handler(handler: Arc<dyn SshPromptHandler>) {
+    HANDLER.lock().unwrap().replace(handler);
+}
+
+pub fn ssh_prompt_handler() -> Option<Arc<dyn SshPromptHandler>> {
+    HANDLER.lock().unwrap().clone()
+}
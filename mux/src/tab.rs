@@ -3,14 +3,16 @@ use crate::pane::*;
 use crate::{Mux, WindowId};
 use bintree::PathBranch;
 use config::configuration;
-use config::keyassignment::PaneDirection;
+use config::keyassignment::{PaneDirection, PaneLayout, RotationDirection};
 use portable_pty::PtySize;
 use rangeset::range_intersection;
 use serde::{Deserialize, Serialize};
 use std::cell::{RefCell, RefMut};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::rc::Rc;
 use url::Url;
+use wezterm_term::ScrollbackStats;
 
 pub type Tree = bintree::Tree<Rc<dyn Pane>, SplitDirectionAndSize>;
 pub type Cursor = bintree::Cursor<Rc<dyn Pane>, SplitDirectionAndSize>;
@@ -18,6 +20,9 @@ pub type Cursor = bintree::Cursor<Rc<dyn Pane>, SplitDirectionAndSize>;
 static TAB_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 pub type TabId = usize;
 
+/// The number of previously active panes, or tabs, that are remembered
+pub(crate) const FOCUS_HISTORY_LIMIT: usize = 32;
+
 /// A Tab is a container of Panes
 pub struct Tab {
     id: TabId,
@@ -25,6 +30,20 @@ pub struct Tab {
     size: RefCell<PtySize>,
     active: RefCell<usize>,
     zoomed: RefCell<Option<Rc<dyn Pane>>>,
+    broadcast: RefCell<Option<InputBroadcast>>,
+    user_vars: RefCell<HashMap<String, String>>,
+    /// The ids of the panes that have been active, most recent first.
+    /// This may refer to panes that have since been closed.
+    focus_history: RefCell<Vec<PaneId>>,
+}
+
+/// Which of the panes of a tab receive the input that is typed
+/// into any one of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputBroadcast {
+    AllPanes,
+    /// Only the panes that the user added to the broadcast
+    Selected(HashSet<PaneId>),
 }
 
 #[derive(Clone)]
@@ -126,6 +145,20 @@ impl SplitDirectionAndSize {
     }
 }
 
+/// Describes how the panes of a tab are to be rearranged
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum PaneArrangement {
+    /// Equalize the sizes of the panes, retaining their arrangement
+    Balanced,
+    /// Move the panes into a preset layout
+    Layout(PaneLayout),
+    /// Move each pane into the position of its neighbor
+    Rotate(RotationDirection),
+    /// Exchange the positions of the active pane and the pane
+    /// with the specified index
+    SwapWithActive(usize),
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct PositionedSplit {
     /// The topological node index that can be used to reference this split
@@ -182,6 +215,7 @@ fn pane_tree(
                     pixel_width: 0,
                 },
                 working_dir: working_dir.map(Into::into),
+                scrollback: pane.get_scrollback_stats(),
             })
         }
     }
@@ -392,6 +426,261 @@ fn apply_sizes_from_splits(tree: &Tree, size: &PtySize) {
     }
 }
 
+/// Replaces the panes in the leaves of the tree with those yielded
+/// by `panes`, in topological order
+fn replace_leaves(tree: &mut Tree, panes: &mut std::vec::IntoIter<Rc<dyn Pane>>) {
+    match tree {
+        Tree::Empty => {}
+        Tree::Node { left, right, .. } => {
+            replace_leaves(left, panes);
+            replace_leaves(right, panes);
+        }
+        Tree::Leaf(pane) => {
+            if let Some(replacement) = panes.next() {
+                *pane = replacement;
+            }
+        }
+    }
+}
+
+/// Replaces the sizes of the split nodes with those in `splits`, which
+/// are listed in preorder.  Returns false, leaving the tree unchanged,
+/// if the splits don't match the structure of the tree.
+fn replace_split_sizes(tree: &mut Tree, splits: &[SplitDirectionAndSize]) -> bool {
+    fn directions(tree: &Tree, result: &mut Vec<SplitDirection>) {
+        if let Tree::Node { left, right, data } = tree {
+            if let Some(data) = data {
+                result.push(data.direction);
+            }
+            directions(left, result);
+            directions(right, result);
+        }
+    }
+
+    fn assign(tree: &mut Tree, splits: &mut std::slice::Iter<SplitDirectionAndSize>) {
+        if let Tree::Node { left, right, data } = tree {
+            *data = splits.next().copied();
+            assign(left, splits);
+            assign(right, splits);
+        }
+    }
+
+    let mut existing = vec![];
+    directions(tree, &mut existing);
+    if existing.len() != splits.len()
+        || existing
+            .iter()
+            .zip(splits.iter())
+            .any(|(direction, split)| *direction != split.direction)
+    {
+        return false;
+    }
+    assign(tree, &mut splits.iter());
+    true
+}
+
+fn axis_extent(size: &PtySize, direction: SplitDirection) -> usize {
+    match direction {
+        SplitDirection::Horizontal => size.cols as usize,
+        SplitDirection::Vertical => size.rows as usize,
+    }
+}
+
+/// Returns the sizes of the (first, second) children of a split of
+/// `size` in `direction`, where the first child has the specified
+/// extent along that direction.
+fn split_size(
+    size: &PtySize,
+    direction: SplitDirection,
+    first_extent: usize,
+    cell_dimensions: &PtySize,
+) -> (PtySize, PtySize) {
+    let mut first = *size;
+    let mut second = *size;
+    match direction {
+        SplitDirection::Horizontal => {
+            first.cols = first_extent as u16;
+            second.cols = size.cols.saturating_sub(first.cols + 1);
+        }
+        SplitDirection::Vertical => {
+            first.rows = first_extent as u16;
+            second.rows = size.rows.saturating_sub(first.rows + 1);
+        }
+    }
+    first.pixel_width = first.cols.saturating_mul(cell_dimensions.pixel_width);
+    first.pixel_height = first.rows.saturating_mul(cell_dimensions.pixel_height);
+    second.pixel_width = second.cols.saturating_mul(cell_dimensions.pixel_width);
+    second.pixel_height = second.rows.saturating_mul(cell_dimensions.pixel_height);
+    (first, second)
+}
+
+/// Consecutive splits in the same direction divide their space between
+/// a number of items; for example, splitting a pane side by side and
+/// then splitting the right hand pane side by side again divides the
+/// width between three items.  This returns the number of items in the
+/// run of splits in `direction` that starts at `tree`.
+fn run_len(tree: &Tree, direction: SplitDirection) -> usize {
+    match tree {
+        Tree::Node {
+            left,
+            right,
+            data: Some(data),
+        } if data.direction == direction => run_len(left, direction) + run_len(right, direction),
+        _ => 1,
+    }
+}
+
+/// Collects the minimum extent, along `direction`, of each of the items
+/// in the run of splits in `direction` that starts at `tree`.
+fn run_min_extents(tree: &mut Tree, direction: SplitDirection, mins: &mut Vec<usize>) {
+    match tree {
+        Tree::Node {
+            left,
+            right,
+            data: Some(data),
+        } if data.direction == direction => {
+            run_min_extents(&mut *left, direction, mins);
+            run_min_extents(&mut *right, direction, mins);
+        }
+        _ => {
+            let (min_x, min_y) = compute_min_size(tree);
+            mins.push(match direction {
+                SplitDirection::Horizontal => min_x,
+                SplitDirection::Vertical => min_y,
+            });
+        }
+    }
+}
+
+/// Updates the split nodes so that `size` is divided equally between
+/// the items of each run of splits, recursively.  Cells that cannot be
+/// divided equally are given to the leftmost/topmost items.
+/// Returns false if a run was too small to be divided equally between
+/// its items, in which case that run retains its prior proportions.
+/// The panes are not resized.
+fn balance_tree(tree: &mut Tree, size: PtySize, cell_dimensions: &PtySize) -> bool {
+    let direction = match tree {
+        Tree::Node {
+            data: Some(data), ..
+        } => data.direction,
+        _ => return true,
+    };
+
+    let mut mins = vec![];
+    run_min_extents(tree, direction, &mut mins);
+    let count = mins.len();
+    let available = axis_extent(&size, direction).saturating_sub(count - 1);
+    let shares: Vec<usize> = (0..count)
+        .map(|idx| available / count + if idx < available % count { 1 } else { 0 })
+        .collect();
+
+    if shares
+        .iter()
+        .zip(mins.iter())
+        .any(|(share, min)| share < min)
+    {
+        if let Tree::Node {
+            left,
+            right,
+            data: Some(data),
+        } = tree
+        {
+            let first = data.first;
+            let second = data.second;
+            balance_tree(&mut *left, first, cell_dimensions);
+            balance_tree(&mut *right, second, cell_dimensions);
+        }
+        return false;
+    }
+
+    balance_run(tree, direction, size, &shares, cell_dimensions)
+}
+
+/// Assigns the extents in `shares` to the items of the run of
+/// splits in `direction` that starts at `tree`, and balances the
+/// items themselves.
+fn balance_run(
+    tree: &mut Tree,
+    direction: SplitDirection,
+    size: PtySize,
+    shares: &[usize],
+    cell_dimensions: &PtySize,
+) -> bool {
+    match tree {
+        Tree::Node {
+            left,
+            right,
+            data: Some(data),
+        } if data.direction == direction => {
+            let left_len = run_len(left, direction);
+            let (left_shares, right_shares) = shares.split_at(left_len);
+            // Each item is followed by a split divider, except the last
+            let first_extent = left_shares.iter().sum::<usize>() + left_len - 1;
+            let (first, second) = split_size(&size, direction, first_extent, cell_dimensions);
+            data.first = first;
+            data.second = second;
+
+            let left_balanced =
+                balance_run(&mut *left, direction, first, left_shares, cell_dimensions);
+            let right_balanced = balance_run(
+                &mut *right,
+                direction,
+                second,
+                right_shares,
+                cell_dimensions,
+            );
+            left_balanced && right_balanced
+        }
+        _ => balance_tree(tree, size, cell_dimensions),
+    }
+}
+
+/// Builds a run of splits in `direction` that divides the space
+/// between `items`, which must not be empty.  The sizes of the splits
+/// are placeholders that are expected to be computed by balance_tree.
+fn build_run(mut items: Vec<Tree>, direction: SplitDirection) -> Tree {
+    let last = items.pop().expect("build_run requires at least one item");
+    items
+        .into_iter()
+        .rev()
+        .fold(last, |right, left| Tree::Node {
+            left: Box::new(left),
+            right: Box::new(right),
+            data: Some(SplitDirectionAndSize {
+                direction,
+                first: PtySize::default(),
+                second: PtySize::default(),
+            }),
+        })
+}
+
+/// Builds the tree for `layout`.  The panes appear in the tree in the
+/// same order as they appear in `panes`.
+fn build_layout(layout: PaneLayout, panes: Vec<Rc<dyn Pane>>) -> Tree {
+    let mut leaves: Vec<Tree> = panes.into_iter().map(Tree::Leaf).collect();
+    match layout {
+        PaneLayout::EvenHorizontal => build_run(leaves, SplitDirection::Horizontal),
+        PaneLayout::MainVertical => {
+            let others = leaves.split_off(1);
+            leaves.push(build_run(others, SplitDirection::Vertical));
+            build_run(leaves, SplitDirection::Horizontal)
+        }
+        PaneLayout::Tiled => {
+            let mut cols = 1;
+            while cols * cols < leaves.len() {
+                cols += 1;
+            }
+            let mut rows = vec![];
+            while !leaves.is_empty() {
+                let rest = leaves.split_off(cols.min(leaves.len()));
+                rows.push(build_run(leaves, SplitDirection::Horizontal));
+                leaves = rest;
+            }
+            build_run(rows, SplitDirection::Vertical)
+        }
+    }
+}
+
 fn cell_dimensions(size: &PtySize) -> PtySize {
     PtySize {
         rows: 1,
@@ -409,6 +698,9 @@ impl Tab {
             size: RefCell::new(*size),
             active: RefCell::new(0),
             zoomed: RefCell::new(None),
+            broadcast: RefCell::new(None),
+            user_vars: RefCell::new(HashMap::new()),
+            focus_history: RefCell::new(vec![]),
         }
     }
 
@@ -513,6 +805,11 @@ impl Tab {
         }
     }
 
+    /// Returns the pane that is zoomed to fill the tab, if any
+    pub fn get_zoomed_pane(&self) -> Option<Rc<dyn Pane>> {
+        self.zoomed.borrow().as_ref().map(Rc::clone)
+    }
+
     pub fn set_zoomed(&self, zoomed: bool) {
         if self.zoomed.borrow().is_some() == zoomed {
             // Current zoom state matches intended zoom state,
@@ -544,6 +841,77 @@ impl Tab {
         }
     }
 
+    /// Sets a user var that belongs to the tab, rather than to one
+    /// of its panes.  Use Mux::set_tab_user_var so that the windows
+    /// learn about the change.
+    pub fn set_user_var(&self, name: String, value: String) {
+        self.user_vars.borrow_mut().insert(name, value);
+    }
+
+    pub fn copy_user_vars(&self) -> HashMap<String, String> {
+        self.user_vars.borrow().clone()
+    }
+
+    /// Toggles broadcasting the input typed into any pane of the tab
+    /// to all of its panes
+    pub fn toggle_input_broadcast(&self) {
+        let mut broadcast = self.broadcast.borrow_mut();
+        if *broadcast == Some(InputBroadcast::AllPanes) {
+            broadcast.take();
+        } else {
+            broadcast.replace(InputBroadcast::AllPanes);
+        }
+    }
+
+    /// Adds the pane to the set of panes that the input typed into
+    /// any of them is broadcast to, or removes it if it was already
+    /// part of it.  If all panes were receiving the input, every pane
+    /// but this one remains in the set.
+    pub fn toggle_input_broadcast_for_pane(&self, pane_id: PaneId) {
+        let mut broadcast = self.broadcast.borrow_mut();
+        let mut selected = match broadcast.take() {
+            Some(InputBroadcast::Selected(selected)) => selected,
+            Some(InputBroadcast::AllPanes) => self
+                .iter_panes_ignoring_zoom()
+                .iter()
+                .map(|p| p.pane.pane_id())
+                .collect(),
+            None => HashSet::new(),
+        };
+        if !selected.remove(&pane_id) {
+            selected.insert(pane_id);
+        }
+        if !selected.is_empty() {
+            broadcast.replace(InputBroadcast::Selected(selected));
+        }
+    }
+
+    pub fn get_input_broadcast(&self) -> Option<InputBroadcast> {
+        self.broadcast.borrow().clone()
+    }
+
+    /// Returns true if the input typed into the pane is broadcast to
+    /// the other panes of this tab that are part of the broadcast
+    pub fn is_input_broadcast_member(&self, pane_id: PaneId) -> bool {
+        match &*self.broadcast.borrow() {
+            Some(InputBroadcast::AllPanes) => self.contains_pane(pane_id),
+            Some(InputBroadcast::Selected(selected)) => selected.contains(&pane_id),
+            None => false,
+        }
+    }
+
+    /// Returns the panes of this tab that are part of the broadcast
+    pub fn input_broadcast_members(&self) -> Vec<Rc<dyn Pane>> {
+        if self.broadcast.borrow().is_none() {
+            return vec![];
+        }
+        self.iter_panes_ignoring_zoom()
+            .into_iter()
+            .map(|p| p.pane)
+            .filter(|pane| self.is_input_broadcast_member(pane.pane_id()))
+            .collect()
+    }
+
     pub fn contains_pane(&self, pane: PaneId) -> bool {
         fn contains(tree: &Tree, pane: PaneId) -> bool {
             match tree {
@@ -794,6 +1162,32 @@ impl Tab {
         }
     }
 
+    /// Applies the sizes of the splits of a tab that had the specified
+    /// `size`, such as one that is being restored from a saved session,
+    /// and then fits them to the current size of this tab.
+    /// `splits` lists the splits in preorder, and must match the
+    /// structure of this tab, otherwise nothing is changed.
+    pub fn restore_split_sizes(&self, size: PtySize, splits: &[SplitDirectionAndSize]) -> bool {
+        if self.zoomed.borrow().is_some() {
+            return false;
+        }
+        let current_size = *self.size.borrow();
+        {
+            let mut root = self.pane.borrow_mut();
+            let root = match root.as_mut() {
+                Some(root) => root,
+                None => return false,
+            };
+            if !replace_split_sizes(root, splits) {
+                return false;
+            }
+            *self.size.borrow_mut() = size;
+            apply_sizes_from_splits(root, &size);
+        }
+        self.resize(current_size);
+        true
+    }
+
     /// Called when running in the mux server after an individual pane
     /// has been resized.
     /// Because the split manipulation happened on the GUI we "lost"
@@ -1033,21 +1427,164 @@ impl Tab {
         }
     }
 
+    pub fn rearrange(&self, arrangement: PaneArrangement) {
+        match arrangement {
+            PaneArrangement::Balanced => self.balance_panes(),
+            PaneArrangement::Layout(layout) => self.apply_pane_layout(layout),
+            PaneArrangement::Rotate(direction) => self.rotate_panes(direction),
+            PaneArrangement::SwapWithActive(pane_index) => self.swap_active_with_index(pane_index),
+        }
+    }
+
+    /// Resizes the panes so that the panes at each level of the split
+    /// tree are of equal size.  The sizes are computed first so that
+    /// each pane is resized at most once.
+    pub fn balance_panes(&self) {
+        if self.zoomed.borrow().is_some() {
+            return;
+        }
+        if let Some(pane) = self.get_active_pane() {
+            pane.set_arrangement(PaneArrangement::Balanced);
+        }
+
+        let size = *self.size.borrow();
+        let mut root = self.pane.borrow_mut();
+        if let Some(root) = root.as_mut() {
+            balance_tree(root, size, &cell_dimensions(&size));
+            apply_sizes_from_splits(root, &size);
+        }
+    }
+
+    /// Moves the existing panes into the specified layout, and sizes
+    /// them equally.  For `MainVertical`, the active pane is the main
+    /// pane; otherwise, the panes retain their relative order.
+    pub fn apply_pane_layout(&self, layout: PaneLayout) {
+        if self.zoomed.borrow().is_some() {
+            return;
+        }
+        let active = match self.get_active_pane() {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        let mut panes: Vec<Rc<dyn Pane>> = self.iter_panes().into_iter().map(|p| p.pane).collect();
+        if panes.len() < 2 {
+            return;
+        }
+        if layout == PaneLayout::MainVertical {
+            if let Some(idx) = panes.iter().position(|p| p.pane_id() == active.pane_id()) {
+                let main = panes.remove(idx);
+                panes.insert(0, main);
+            }
+        }
+        let active_idx = panes
+            .iter()
+            .position(|p| p.pane_id() == active.pane_id())
+            .unwrap_or(0);
+        let num_panes = panes.len();
+
+        let size = *self.size.borrow();
+        let mut tree = build_layout(layout, panes);
+        if !balance_tree(&mut tree, size, &cell_dimensions(&size)) {
+            log::error!(
+                "tab {} is too small to arrange its {} panes in the {:?} layout",
+                self.id,
+                num_panes,
+                layout
+            );
+            return;
+        }
+
+        active.set_arrangement(PaneArrangement::Layout(layout));
+
+        let mut root = self.pane.borrow_mut();
+        apply_sizes_from_splits(&tree, &size);
+        root.replace(tree);
+        *self.active.borrow_mut() = active_idx;
+    }
+
+    /// Moves each pane into the position of the next pane in the
+    /// topological order, or of the previous pane for `CounterClockwise`.
+    /// The positions retain their sizes, so the panes are resized to fit.
+    /// The active pane remains active, and remains zoomed if it was zoomed.
+    pub fn rotate_panes(&self, direction: RotationDirection) {
+        let mut panes: Vec<Rc<dyn Pane>> = self
+            .iter_panes_ignoring_zoom()
+            .into_iter()
+            .map(|p| p.pane)
+            .collect();
+        if panes.len() < 2 {
+            return;
+        }
+        let active = match self.get_active_pane() {
+            Some(pane) => pane,
+            None => return,
+        };
+        active.set_arrangement(PaneArrangement::Rotate(direction));
+
+        match direction {
+            RotationDirection::Clockwise => panes.rotate_right(1),
+            RotationDirection::CounterClockwise => panes.rotate_left(1),
+        }
+        self.replace_panes(panes, &active);
+    }
+
+    /// Exchanges the positions of the active pane and the pane with
+    /// the specified index.  The positions retain their sizes, and the
+    /// active pane remains active.
+    pub fn swap_active_with_index(&self, pane_index: usize) {
+        let mut panes: Vec<Rc<dyn Pane>> = self
+            .iter_panes_ignoring_zoom()
+            .into_iter()
+            .map(|p| p.pane)
+            .collect();
+        let active_idx = self.get_active_idx();
+        if pane_index >= panes.len() || active_idx >= panes.len() || pane_index == active_idx {
+            return;
+        }
+        let active = Rc::clone(&panes[active_idx]);
+        active.set_arrangement(PaneArrangement::SwapWithActive(pane_index));
+
+        panes.swap(active_idx, pane_index);
+        self.replace_panes(panes, &active);
+    }
+
+    /// Places `panes` into the leaves of the tree, in topological
+    /// order, and resizes them to fit their new positions
+    fn replace_panes(&self, panes: Vec<Rc<dyn Pane>>, active: &Rc<dyn Pane>) {
+        let active_idx = panes
+            .iter()
+            .position(|p| p.pane_id() == active.pane_id())
+            .unwrap_or(0);
+        let size = *self.size.borrow();
+        {
+            let mut root = self.pane.borrow_mut();
+            if let Some(root) = root.as_mut() {
+                replace_leaves(root, &mut panes.into_iter());
+                apply_sizes_from_splits(root, &size);
+            }
+        }
+        if let Some(zoomed) = self.zoomed.borrow().as_ref() {
+            zoomed.resize(size).ok();
+        }
+        *self.active.borrow_mut() = active_idx;
+    }
+
     /// Activate an adjacent pane in the specified direction.
     /// In cases where there are multiple adjacent panes in the
     /// intended direction, we take the pane that has the largest
     /// edge intersection.
     pub fn activate_pane_direction(&self, direction: PaneDirection) {
-        if self.zoomed.borrow().is_some() {
+        let was_zoomed = self.zoomed.borrow().is_some();
+        if was_zoomed {
             if !configuration().unzoom_on_switch_pane {
                 return;
             }
             self.toggle_zoom();
         }
-        let panes = self.iter_panes();
 
-        let active = match panes.iter().find(|pane| pane.is_active) {
-            Some(p) => p,
+        let active = match self.iter_panes().iter().find(|pane| pane.is_active) {
+            Some(p) => p.index,
             None => {
                 // No active pane somehow...
                 self.set_active_idx(0);
@@ -1055,6 +1592,26 @@ impl Tab {
             }
         };
 
+        let target = self.get_pane_direction(active, direction);
+        if let Some(index) = target {
+            self.set_active_idx(index);
+        }
+        if was_zoomed && (target.is_none() || configuration().rezoom_on_switch_pane) {
+            // Either there was nowhere to go, in which case the pane
+            // is restored to its zoomed state, or the newly active
+            // pane takes over the zoom
+            self.toggle_zoom();
+        }
+    }
+
+    /// Returns the index of the pane that is adjacent to the pane at
+    /// `pane_index` in the specified direction, using the same rules
+    /// as activate_pane_direction.  The positions of the panes are those
+    /// that they have when the tab isn't zoomed.
+    pub fn get_pane_direction(&self, pane_index: usize, direction: PaneDirection) -> Option<usize> {
+        let panes = self.iter_panes_ignoring_zoom();
+        let active = panes.iter().find(|pane| pane.index == pane_index)?;
+
         let mut best = None;
 
         /// Compute the edge intersection size between two touching panes
@@ -1113,9 +1670,7 @@ impl Tab {
             }
         }
 
-        if let Some((_, target)) = best.take() {
-            self.set_active_idx(target.index);
-        }
+        best.take().map(|(_, target)| target.index)
     }
 
     pub fn prune_dead_panes(&self) -> bool {
@@ -1130,7 +1685,42 @@ impl Tab {
         self.remove_pane_if(|_, pane| pane.domain_id() == domain)
     }
 
+    /// Removes the pane from the tab without killing it, so that it
+    /// can be placed elsewhere.  The space that it occupied is given
+    /// to its neighbor.
+    pub fn remove_pane(&self, pane_id: PaneId) -> Option<Rc<dyn Pane>> {
+        self.remove_panes_if(|_, pane| pane.pane_id() == pane_id)
+            .into_iter()
+            .next()
+    }
+
     fn remove_pane_if<F>(&self, f: F) -> bool
+    where
+        F: Fn(usize, &Rc<dyn Pane>) -> bool,
+    {
+        let dead_panes: Vec<PaneId> = self
+            .remove_panes_if(f)
+            .iter()
+            .map(|pane| pane.pane_id())
+            .collect();
+
+        if !dead_panes.is_empty() {
+            promise::spawn::spawn_into_main_thread(async move {
+                let mux = Mux::get().unwrap();
+                for pane_id in dead_panes.into_iter() {
+                    mux.remove_pane(pane_id);
+                }
+            })
+            .detach();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes the panes that match `f` from the tree, resizing their
+    /// neighbors to fill the space, and returns the removed panes.
+    fn remove_panes_if<F>(&self, f: F) -> Vec<Rc<dyn Pane>>
     where
         F: Fn(usize, &Rc<dyn Pane>) -> bool,
     {
@@ -1171,7 +1761,7 @@ impl Tab {
                         let parent;
                         match cursor.unsplit_leaf() {
                             Ok((c, dead, p)) => {
-                                dead_panes.push(dead.pane_id());
+                                dead_panes.push(dead);
                                 parent = p.unwrap();
                                 cursor = c;
                             }
@@ -1179,7 +1769,7 @@ impl Tab {
                                 // We might be the root, for example
                                 if c.is_top() && c.is_leaf() {
                                     root.replace(Tree::Empty);
-                                    dead_panes.push(pane.pane_id());
+                                    dead_panes.push(pane);
                                 } else {
                                     root.replace(c.tree());
                                 }
@@ -1221,18 +1811,7 @@ impl Tab {
             *self.active.borrow_mut() = active_idx;
         }
 
-        if !dead_panes.is_empty() {
-            promise::spawn::spawn_into_main_thread(async move {
-                let mux = Mux::get().unwrap();
-                for pane_id in dead_panes.into_iter() {
-                    mux.remove_pane(pane_id);
-                }
-            })
-            .detach();
-            true
-        } else {
-            false
-        }
+        dead_panes
     }
 
     pub fn can_close_without_prompting(&self) -> bool {
@@ -1290,10 +1869,15 @@ impl Tab {
         let current = self.get_active_pane();
         match (prior, current) {
             (Some(prior), Some(current)) if prior.pane_id() != current.pane_id() => {
+                // The prior pane may have become active without passing
+                // through here, such as by being split off
+                self.remember_focus(prior.pane_id());
+                self.remember_focus(current.pane_id());
                 prior.focus_changed(false);
                 current.focus_changed(true);
             }
             (None, Some(current)) => {
+                self.remember_focus(current.pane_id());
                 current.focus_changed(true);
             }
             (Some(prior), None) => {
@@ -1305,6 +1889,60 @@ impl Tab {
         }
     }
 
+    fn remember_focus(&self, pane_id: PaneId) {
+        let mut history = self.focus_history.borrow_mut();
+        history.retain(|id| *id != pane_id);
+        history.insert(0, pane_id);
+        history.truncate(FOCUS_HISTORY_LIMIT);
+    }
+
+    /// Returns the ids of the panes of this tab in the order in which
+    /// they were most recently active, starting with the active pane.
+    /// Panes that have never been active are not included.
+    pub fn focus_history(&self) -> Vec<PaneId> {
+        let live: HashSet<PaneId> = self
+            .iter_panes_ignoring_zoom()
+            .iter()
+            .filter(|p| !p.pane.is_dead())
+            .map(|p| p.pane.pane_id())
+            .collect();
+        let mut history = vec![];
+        if let Some(active) = self.get_active_pane() {
+            history.push(active.pane_id());
+        }
+        for id in self.focus_history.borrow().iter() {
+            if live.contains(id) && !history.contains(id) {
+                history.push(*id);
+            }
+        }
+        history
+    }
+
+    /// Activates the pane that was active before the active pane.
+    /// The zoom state is handled in the same way as for
+    /// activate_pane_direction.
+    pub fn activate_last_pane(&self) {
+        let last = match self.focus_history().get(1) {
+            Some(id) => *id,
+            None => return,
+        };
+
+        let was_zoomed = self.zoomed.borrow().is_some();
+        if was_zoomed {
+            if !configuration().unzoom_on_switch_pane {
+                return;
+            }
+            self.toggle_zoom();
+        }
+
+        if let Some(pos) = self.iter_panes().iter().find(|p| p.pane.pane_id() == last) {
+            self.set_active_idx(pos.index);
+        }
+        if was_zoomed && configuration().rezoom_on_switch_pane {
+            self.toggle_zoom();
+        }
+    }
+
     pub fn set_active_idx(&self, pane_index: usize) {
         let prior = self.get_active_pane();
         *self.active.borrow_mut() = pane_index;
@@ -1319,6 +1957,7 @@ impl Tab {
             Ok(c) => *self.pane.borrow_mut() = Some(c.tree()),
             Err(_) => panic!("tried to assign root pane to non-empty tree"),
         }
+        self.remember_focus(pane.pane_id());
     }
 
     fn cell_dimensions(&self) -> PtySize {
@@ -1379,6 +2018,38 @@ impl Tab {
         })
     }
 
+    /// Like compute_split_size, except that it fails if there is
+    /// not enough space for the split.
+    pub fn check_split_size(
+        &self,
+        pane_index: usize,
+        direction: SplitDirection,
+    ) -> anyhow::Result<SplitDirectionAndSize> {
+        let split_info = self
+            .compute_split_size(pane_index, direction)
+            .ok_or_else(|| anyhow::anyhow!("invalid pane_index {}; cannot split!", pane_index))?;
+        let tab_size = *self.size.borrow();
+        if split_info.first.rows == 0
+            || split_info.first.cols == 0
+            || split_info.second.rows == 0
+            || split_info.second.cols == 0
+            || split_info.top_of_second() as u16 + split_info.second.rows > tab_size.rows
+            || split_info.left_of_second() as u16 + split_info.second.cols > tab_size.cols
+        {
+            log::error!(
+                "No splace for split!!! {:#?} height={} width={} top_of_second={} left_of_second={} tab_size={:?}",
+                split_info,
+                split_info.height(),
+                split_info.width(),
+                split_info.top_of_second(),
+                split_info.left_of_second(),
+                tab_size
+            );
+            anyhow::bail!("No space for split!");
+        }
+        Ok(split_info)
+    }
+
     /// Split the pane that has pane_index in the given direction and assign
     /// the right/bottom pane of the newly created split to the provided Pane
     /// instance.  Returns the resultant index of the newly inserted pane.
@@ -1388,36 +2059,45 @@ impl Tab {
         pane_index: usize,
         direction: SplitDirection,
         pane: Rc<dyn Pane>,
+    ) -> anyhow::Result<usize> {
+        self.split_and_insert_impl(pane_index, direction, pane, false)
+    }
+
+    /// Split the pane that has pane_index and assign the side of the
+    /// split that is given by `direction` to the provided Pane instance.
+    /// Returns the resultant index of the newly inserted pane.
+    /// Both the split and the inserted pane will be resized.
+    pub fn split_and_insert_toward(
+        &self,
+        pane_index: usize,
+        direction: PaneDirection,
+        pane: Rc<dyn Pane>,
+    ) -> anyhow::Result<usize> {
+        let (split_direction, before) = match direction {
+            PaneDirection::Left => (SplitDirection::Horizontal, true),
+            PaneDirection::Right => (SplitDirection::Horizontal, false),
+            PaneDirection::Up => (SplitDirection::Vertical, true),
+            PaneDirection::Down => (SplitDirection::Vertical, false),
+        };
+        self.split_and_insert_impl(pane_index, split_direction, pane, before)
+    }
+
+    fn split_and_insert_impl(
+        &self,
+        pane_index: usize,
+        direction: SplitDirection,
+        pane: Rc<dyn Pane>,
+        before: bool,
     ) -> anyhow::Result<usize> {
         if self.zoomed.borrow().is_some() {
             anyhow::bail!("cannot split while zoomed");
         }
 
+        let new_index = if before { pane_index } else { pane_index + 1 };
+        let new_pane_id = pane.pane_id();
+
         {
-            let split_info = self
-                .compute_split_size(pane_index, direction)
-                .ok_or_else(|| {
-                    anyhow::anyhow!("invalid pane_index {}; cannot split!", pane_index)
-                })?;
-            let tab_size = *self.size.borrow();
-            if split_info.first.rows == 0
-                || split_info.first.cols == 0
-                || split_info.second.rows == 0
-                || split_info.second.cols == 0
-                || split_info.top_of_second() as u16 + split_info.second.rows > tab_size.rows
-                || split_info.left_of_second() as u16 + split_info.second.cols > tab_size.cols
-            {
-                log::error!(
-                    "No splace for split!!! {:#?} height={} width={} top_of_second={} left_of_second={} tab_size={:?}",
-                    split_info,
-                    split_info.height(),
-                    split_info.width(),
-                    split_info.top_of_second(),
-                    split_info.left_of_second(),
-                    tab_size
-                );
-                anyhow::bail!("No space for split!");
-            }
+            let split_info = self.check_split_size(pane_index, direction)?;
 
             let mut root = self.pane.borrow_mut();
             let mut cursor = root.take().unwrap().cursor();
@@ -1432,10 +2112,17 @@ impl Tab {
 
             let existing_pane = Rc::clone(cursor.leaf_mut().unwrap());
 
-            existing_pane.resize(split_info.first)?;
-            pane.resize(split_info.second.clone())?;
+            let split = if before {
+                existing_pane.resize(split_info.second)?;
+                pane.resize(split_info.first)?;
+                cursor.split_leaf_and_insert_left(pane)
+            } else {
+                existing_pane.resize(split_info.first)?;
+                pane.resize(split_info.second.clone())?;
+                cursor.split_leaf_and_insert_right(pane)
+            };
 
-            match cursor.split_leaf_and_insert_right(pane) {
+            match split {
                 Ok(c) => cursor = c,
                 Err(c) => {
                     root.replace(c.tree());
@@ -1449,13 +2136,14 @@ impl Tab {
                 Err(c) | Ok(c) => root.replace(c.tree()),
             };
 
-            *self.active.borrow_mut() = pane_index + 1;
+            *self.active.borrow_mut() = new_index;
         }
+        self.remember_focus(new_pane_id);
 
         log::debug!("split info after split: {:#?}", self.iter_splits());
         log::debug!("pane info after split: {:#?}", self.iter_panes());
 
-        Ok(pane_index + 1)
+        Ok(new_index)
     }
 }
 
@@ -1517,6 +2205,7 @@ pub struct PaneEntry {
     pub working_dir: Option<SerdeUrl>,
     pub is_active_pane: bool,
     pub is_zoomed_pane: bool,
+    pub scrollback: Option<ScrollbackStats>,
 }
 
 #[derive(Deserialize, Clone, Serialize, PartialEq, Debug)]
@@ -1807,4 +2496,341 @@ mod test {
         assert_eq!(390, panes[2].pixel_width);
         assert_eq!(600, panes[2].pixel_height);
     }
+
+    #[test]
+    fn balance_and_layout() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+        let horz_size = tab
+            .compute_split_size(1, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            1,
+            SplitDirection::Horizontal,
+            FakePane::new(3, horz_size.second),
+        )
+        .unwrap();
+
+        fn geometry(tab: &Tab) -> Vec<(PaneId, usize, usize, usize, usize)> {
+            tab.iter_panes()
+                .iter()
+                .map(|p| (p.pane.pane_id(), p.left, p.top, p.width, p.height))
+                .collect()
+        }
+
+        assert_eq!(
+            geometry(&tab),
+            vec![(1, 0, 0, 40, 24), (2, 41, 0, 19, 24), (3, 61, 0, 19, 24)]
+        );
+
+        tab.balance_panes();
+        assert_eq!(
+            geometry(&tab),
+            vec![(1, 0, 0, 26, 24), (2, 27, 0, 26, 24), (3, 54, 0, 26, 24)]
+        );
+        let panes = tab.iter_panes();
+        assert_eq!(260, panes[0].pixel_width);
+        assert_eq!(600, panes[0].pixel_height);
+
+        tab.apply_pane_layout(PaneLayout::Tiled);
+        assert_eq!(
+            geometry(&tab),
+            vec![(1, 0, 0, 40, 12), (2, 41, 0, 39, 12), (3, 0, 13, 80, 11)]
+        );
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 3);
+
+        tab.apply_pane_layout(PaneLayout::MainVertical);
+        assert_eq!(
+            geometry(&tab),
+            vec![(3, 0, 0, 40, 24), (1, 41, 0, 39, 12), (2, 41, 13, 39, 11)]
+        );
+        assert_eq!(tab.get_active_idx(), 0);
+
+        tab.apply_pane_layout(PaneLayout::EvenHorizontal);
+        assert_eq!(
+            geometry(&tab),
+            vec![(3, 0, 0, 26, 24), (1, 27, 0, 26, 24), (2, 54, 0, 26, 24)]
+        );
+    }
+
+    #[test]
+    fn remove_and_insert_toward() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+
+        fn geometry(tab: &Tab) -> Vec<(PaneId, usize, usize, usize, usize)> {
+            tab.iter_panes()
+                .iter()
+                .map(|p| (p.pane.pane_id(), p.left, p.top, p.width, p.height))
+                .collect()
+        }
+
+        // The neighbor absorbs the space of the removed pane
+        let pane = tab.remove_pane(1).unwrap();
+        assert_eq!(pane.pane_id(), 1);
+        assert_eq!(geometry(&tab), vec![(2, 0, 0, 80, 24)]);
+        assert!(tab.remove_pane(1).is_none());
+
+        let idx = tab
+            .split_and_insert_toward(0, PaneDirection::Left, pane)
+            .unwrap();
+        assert_eq!(idx, 0);
+        assert_eq!(geometry(&tab), vec![(1, 0, 0, 40, 24), (2, 41, 0, 39, 24)]);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 1);
+
+        let pane = tab.remove_pane(2).unwrap();
+        let idx = tab
+            .split_and_insert_toward(0, PaneDirection::Down, pane)
+            .unwrap();
+        assert_eq!(idx, 1);
+        assert_eq!(geometry(&tab), vec![(1, 0, 0, 80, 12), (2, 0, 13, 80, 11)]);
+    }
+
+    #[test]
+    fn rotate_and_swap() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        for idx in 0..2 {
+            let horz_size = tab
+                .compute_split_size(idx, SplitDirection::Horizontal)
+                .unwrap();
+            tab.split_and_insert(
+                idx,
+                SplitDirection::Horizontal,
+                FakePane::new(idx + 2, horz_size.second),
+            )
+            .unwrap();
+        }
+        tab.set_active_idx(0);
+
+        fn geometry(tab: &Tab) -> Vec<(PaneId, usize, usize)> {
+            tab.iter_panes()
+                .iter()
+                .map(|p| (p.pane.pane_id(), p.left, p.width))
+                .collect()
+        }
+        assert_eq!(geometry(&tab), vec![(1, 0, 40), (2, 41, 19), (3, 61, 19)]);
+
+        tab.rotate_panes(RotationDirection::Clockwise);
+        assert_eq!(geometry(&tab), vec![(3, 0, 40), (1, 41, 19), (2, 61, 19)]);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 1);
+        let moved = &tab.iter_panes()[0].pane;
+        assert_eq!(
+            moved.downcast_ref::<FakePane>().unwrap().size.borrow().cols,
+            40
+        );
+
+        tab.rotate_panes(RotationDirection::CounterClockwise);
+        assert_eq!(geometry(&tab), vec![(1, 0, 40), (2, 41, 19), (3, 61, 19)]);
+
+        tab.swap_active_with_index(2);
+        assert_eq!(geometry(&tab), vec![(3, 0, 40), (2, 41, 19), (1, 61, 19)]);
+        assert_eq!(tab.get_active_idx(), 2);
+
+        // The zoomed pane remains zoomed, at the size of the tab
+        tab.toggle_zoom();
+        tab.rotate_panes(RotationDirection::Clockwise);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 1);
+        assert_eq!(tab.get_active_idx(), 0);
+        assert_eq!(geometry(&tab), vec![(1, 0, 80)]);
+        tab.toggle_zoom();
+        assert_eq!(geometry(&tab), vec![(1, 0, 40), (3, 41, 19), (2, 61, 19)]);
+    }
+
+    #[test]
+    fn input_broadcast() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+
+        fn members(tab: &Tab) -> Vec<PaneId> {
+            tab.input_broadcast_members()
+                .iter()
+                .map(|pane| pane.pane_id())
+                .collect()
+        }
+        assert!(members(&tab).is_empty());
+        assert!(!tab.is_input_broadcast_member(1));
+
+        tab.toggle_input_broadcast();
+        assert_eq!(members(&tab), vec![1, 2]);
+        assert!(!tab.is_input_broadcast_member(3));
+
+        // Removing a pane from a broadcast to all panes leaves the others
+        tab.toggle_input_broadcast_for_pane(1);
+        assert_eq!(members(&tab), vec![2]);
+        tab.toggle_input_broadcast_for_pane(2);
+        assert_eq!(tab.get_input_broadcast(), None);
+
+        tab.toggle_input_broadcast_for_pane(2);
+        tab.toggle_input_broadcast();
+        assert_eq!(tab.get_input_broadcast(), Some(InputBroadcast::AllPanes));
+        tab.toggle_input_broadcast();
+        assert_eq!(tab.get_input_broadcast(), None);
+    }
+
+    #[test]
+    fn restore_split_sizes() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+        assert_eq!(tab.iter_panes()[0].width, 40);
+
+        let sized = |first_cols, second_cols| SplitDirectionAndSize {
+            direction: SplitDirection::Horizontal,
+            first: PtySize {
+                cols: first_cols,
+                ..size
+            },
+            second: PtySize {
+                cols: second_cols,
+                ..size
+            },
+        };
+
+        // A vertical split doesn't match the tree
+        let mut vertical = sized(60, 19);
+        vertical.direction = SplitDirection::Vertical;
+        assert!(!tab.restore_split_sizes(size, &[vertical]));
+        assert_eq!(tab.iter_panes()[0].width, 40);
+
+        assert!(tab.restore_split_sizes(size, &[sized(60, 19)]));
+        let panes = tab.iter_panes();
+        assert_eq!(panes[0].width, 60);
+        assert_eq!(panes[1].left, 61);
+        assert_eq!(panes[1].width, 19);
+        assert_eq!(
+            panes[1]
+                .pane
+                .downcast_ref::<FakePane>()
+                .unwrap()
+                .size
+                .borrow()
+                .cols,
+            19
+        );
+
+        // Sizes recorded for a larger tab are fitted to this one
+        let larger = PtySize { cols: 160, ..size };
+        assert!(tab.restore_split_sizes(larger, &[sized(100, 59)]));
+        assert_eq!(tab.get_size().cols, 80);
+        let panes = tab.iter_panes();
+        assert_eq!(panes[0].width + panes[1].width + 1, 80);
+    }
+
+    #[test]
+    fn focus_history() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        for idx in 0..2 {
+            let horz_size = tab
+                .compute_split_size(idx, SplitDirection::Horizontal)
+                .unwrap();
+            tab.split_and_insert(
+                idx,
+                SplitDirection::Horizontal,
+                FakePane::new(idx + 2, horz_size.second),
+            )
+            .unwrap();
+        }
+        // Splitting activates the new pane
+        assert_eq!(tab.focus_history(), vec![3, 2, 1]);
+
+        tab.activate_last_pane();
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 2);
+        assert_eq!(tab.focus_history(), vec![2, 3, 1]);
+
+        tab.set_active_idx(0);
+        assert_eq!(tab.focus_history(), vec![1, 2, 3]);
+        tab.activate_last_pane();
+        tab.activate_last_pane();
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 1);
+
+        // Closed panes are skipped
+        tab.set_active_idx(2);
+        tab.set_active_idx(1);
+        assert_eq!(tab.focus_history(), vec![2, 3, 1]);
+        tab.remove_pane(3);
+        assert_eq!(tab.focus_history(), vec![2, 1]);
+        tab.activate_last_pane();
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 1);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::tab::FOCUS_HISTORY_LIMIT;
 use crate::{Mux, MuxNotification, Tab, TabId};
 use std::rc::Rc;
 use std::sync::Arc;
@@ -10,8 +11,13 @@ pub struct Window {
     id: WindowId,
     tabs: Vec<Rc<Tab>>,
     active: usize,
-    last_active: Option<TabId>,
+    /// The ids of the tabs that were previously active, most recent
+    /// first.  This may refer to tabs that have since been closed.
+    last_active: Vec<TabId>,
     clipboard: Option<Arc<dyn Clipboard>>,
+    /// If true, the input typed into any pane of the window is
+    /// sent to all of its panes
+    broadcast_input: bool,
 }
 
 impl Window {
@@ -20,8 +26,9 @@ impl Window {
             id: WIN_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed),
             tabs: vec![],
             active: 0,
-            last_active: None,
+            last_active: vec![],
             clipboard: None,
+            broadcast_input: false,
         }
     }
 
@@ -33,6 +40,15 @@ impl Window {
         self.id
     }
 
+    pub fn toggle_input_broadcast(&mut self) {
+        self.broadcast_input = !self.broadcast_input;
+        self.invalidate();
+    }
+
+    pub fn is_broadcasting_input(&self) -> bool {
+        self.broadcast_input
+    }
+
     fn check_that_tab_isnt_already_in_window(&self, tab: &Rc<Tab>) {
         for t in &self.tabs {
             assert_ne!(t.tab_id(), tab.tab_id(), "tab already added to this window");
@@ -138,16 +154,21 @@ impl Window {
     }
 
     pub fn save_last_active(&mut self) {
-        self.last_active = self.get_by_idx(self.active).map(|tab| tab.tab_id());
+        if let Some(tab_id) = self.get_by_idx(self.active).map(|tab| tab.tab_id()) {
+            self.last_active.retain(|id| *id != tab_id);
+            self.last_active.insert(0, tab_id);
+            self.last_active.truncate(FOCUS_HISTORY_LIMIT);
+        }
     }
 
-    #[inline]
+    /// Returns the index of the most recently active tab, other than
+    /// the active tab, that hasn't been closed
     pub fn get_last_active_idx(&self) -> Option<usize> {
-        if let Some(tab_id) = self.last_active {
-            self.idx_by_id(tab_id)
-        } else {
-            None
-        }
+        let active = self.get_by_idx(self.active).map(|tab| tab.tab_id());
+        self.last_active
+            .iter()
+            .filter(|id| Some(**id) != active)
+            .find_map(|id| self.idx_by_id(*id))
     }
 
     /// If `idx` is different from the current active tab,
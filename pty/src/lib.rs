@@ -153,31 +153,69 @@ pub trait SlavePty {
 }
 
 /// Represents the exit status of a child process.
-/// This is rather anemic in the current version of this crate,
-/// holding only an indicator of success or failure.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
 pub struct ExitStatus {
-    successful: bool,
+    code: u32,
+    signal: Option<i32>,
 }
 
 impl ExitStatus {
     /// Construct an ExitStatus from a process return code
     pub fn with_exit_code(code: u32) -> Self {
+        Self { code, signal: None }
+    }
+
+    /// Construct an ExitStatus for a process that was terminated
+    /// by the specified signal
+    pub fn with_signal(signal: i32) -> Self {
         Self {
-            successful: code == 0,
+            code: 1,
+            signal: Some(signal),
         }
     }
 
     pub fn success(&self) -> bool {
-        self.successful
+        self.signal.is_none() && self.code == 0
+    }
+
+    /// Returns the exit code of the process.  If the process was
+    /// terminated by a signal this is a non-zero placeholder value.
+    pub fn exit_code(&self) -> u32 {
+        self.code
+    }
+
+    /// Returns the signal that terminated the process, if any
+    pub fn signal(&self) -> Option<i32> {
+        self.signal
+    }
+}
+
+impl std::fmt::Display for ExitStatus {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.signal {
+            Some(signal) => write!(fmt, "terminated by signal {}", signal),
+            None => write!(fmt, "exited with code {}", self.code),
+        }
     }
 }
 
 impl From<std::process::ExitStatus> for ExitStatus {
     fn from(status: std::process::ExitStatus) -> ExitStatus {
-        ExitStatus {
-            successful: status.success(),
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return ExitStatus::with_signal(signal);
+            }
         }
+
+        let code = match status.code() {
+            Some(code) => code as u32,
+            None if status.success() => 0,
+            None => 1,
+        };
+        ExitStatus::with_exit_code(code)
     }
 }
 
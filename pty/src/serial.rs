@@ -5,6 +5,11 @@
 //! `openpty` method.
 //! On most (all?) systems, attempting to open multiple instances of
 //! the same serial port will fail.
+//! When reconnecting is enabled, losing the device, such as when a USB
+//! serial adapter is unplugged or re-enumerates under a different name,
+//! doesn't end the connection: a banner is shown in its output and the
+//! device is reopened once it reappears.  Where it is available, the
+//! device is found again by its USB serial number rather than by its path.
 use crate::{
     Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtyPair, PtySize, PtySystem,
     SlavePty,
@@ -17,10 +22,15 @@ use serial::{
 use std::ffi::{OsStr, OsString};
 use std::io::Result as IoResult;
 use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-type Handle = Arc<Mutex<SystemPort>>;
+type Handle = Arc<Port>;
+
+/// How often to look for a device that went away, and how often
+/// to check whether the device is still present while it is idle
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
 
 pub struct SerialTty {
     port: OsString,
@@ -29,6 +39,8 @@ pub struct SerialTty {
     parity: Parity,
     stop_bits: StopBits,
     flow_control: FlowControl,
+    reconnect: bool,
+    usb_serial_number: Option<String>,
 }
 
 impl SerialTty {
@@ -40,9 +52,25 @@ impl SerialTty {
             parity: Parity::ParityNone,
             stop_bits: StopBits::Stop1,
             flow_control: FlowControl::FlowSoftware,
+            reconnect: false,
+            usb_serial_number: None,
         }
     }
 
+    /// Whether to wait for the device to come back when it goes away,
+    /// rather than ending the connection
+    pub fn set_reconnect(&mut self, reconnect: bool) {
+        self.reconnect = reconnect;
+    }
+
+    /// Identifies the device by the serial number of its USB adapter,
+    /// which is used to find it rather than its path.  If it isn't set,
+    /// the serial number of the device at `port` is used, if it has one.
+    /// Serial numbers are only supported on Linux.
+    pub fn set_usb_serial_number(&mut self, serial_number: Option<String>) {
+        self.usb_serial_number = serial_number;
+    }
+
     pub fn set_baud_rate(&mut self, baud: BaudRate) {
         self.baud = baud;
     }
@@ -66,9 +94,6 @@ impl SerialTty {
 
 impl PtySystem for SerialTty {
     fn openpty(&self, _size: PtySize) -> anyhow::Result<PtyPair> {
-        let mut port = serial::open(&self.port)
-            .with_context(|| format!("openpty on serial port {:?}", self.port))?;
-
         let settings = PortSettings {
             baud_rate: self.baud,
             char_size: self.char_size,
@@ -77,16 +102,31 @@ impl PtySystem for SerialTty {
             flow_control: self.flow_control,
         };
         log::debug!("serial settings: {:#?}", settings);
-        port.configure(&settings)?;
 
-        // The timeout needs to be rather short because, at least on Windows,
-        // a read with a long timeout will block a concurrent write from
-        // happening.  In wezterm we tend to have a thread looping on read
-        // while writes happen occasionally from the gui thread, and if we
-        // make this timeout too long we can block the gui thread.
-        port.set_timeout(Duration::from_millis(50))?;
+        let path = match &self.usb_serial_number {
+            Some(serial_number) => find_usb_serial_number(serial_number).ok_or_else(|| {
+                anyhow::anyhow!("no serial port with USB serial number {}", serial_number)
+            })?,
+            None => self.port.clone(),
+        };
+        let usb_serial_number = self
+            .usb_serial_number
+            .clone()
+            .or_else(|| usb_serial_number_of(&path));
+
+        let device = Device {
+            path: Mutex::new(path.clone()),
+            usb_serial_number,
+            settings,
+            reconnect: self.reconnect,
+        };
+        let port = device.open(&path)?;
 
-        let port: Handle = Arc::new(Mutex::new(port));
+        let port: Handle = Arc::new(Port {
+            device,
+            port: Mutex::new(Some(port)),
+            closed: AtomicBool::new(false),
+        });
 
         Ok(PtyPair {
             slave: Box::new(Slave {
@@ -97,6 +137,148 @@ impl PtySystem for SerialTty {
     }
 }
 
+/// What is needed to open the device again after losing it
+struct Device {
+    /// The device node that was most recently opened
+    path: Mutex<OsString>,
+    usb_serial_number: Option<String>,
+    settings: PortSettings,
+    reconnect: bool,
+}
+
+impl Device {
+    fn open(&self, path: &OsStr) -> anyhow::Result<SystemPort> {
+        let mut port =
+            serial::open(path).with_context(|| format!("openpty on serial port {:?}", path))?;
+        port.configure(&self.settings)?;
+
+        // The timeout needs to be rather short because, at least on Windows,
+        // a read with a long timeout will block a concurrent write from
+        // happening.  In wezterm we tend to have a thread looping on read
+        // while writes happen occasionally from the gui thread, and if we
+        // make this timeout too long we can block the gui thread.
+        port.set_timeout(Duration::from_millis(50))?;
+        Ok(port)
+    }
+
+    fn path(&self) -> OsString {
+        self.path.lock().unwrap().clone()
+    }
+
+    /// Whether the device node has gone away.  A hung up tty reads
+    /// as though there is no data, so this is how its loss is noticed
+    /// on unix systems; on Windows, reading from it fails instead.
+    fn is_gone(&self) -> bool {
+        #[cfg(unix)]
+        {
+            !std::path::Path::new(&self.path()).exists()
+        }
+        #[cfg(windows)]
+        {
+            false
+        }
+    }
+
+    /// Tries to open the device again, which may now have a different
+    /// path if it can be found by its USB serial number
+    fn reopen(&self) -> Option<SystemPort> {
+        let path = self
+            .usb_serial_number
+            .as_ref()
+            .and_then(|serial_number| find_usb_serial_number(serial_number))
+            .unwrap_or_else(|| self.path());
+        match self.open(&path) {
+            Ok(port) => {
+                *self.path.lock().unwrap() = path;
+                Some(port)
+            }
+            Err(err) => {
+                log::trace!("reopening serial port: {:#}", err);
+                None
+            }
+        }
+    }
+}
+
+/// The open device, shared by the reader and the writers
+struct Port {
+    device: Device,
+    /// None while the device is disconnected
+    port: Mutex<Option<SystemPort>>,
+    /// Set when the master is dropped, which tells the reader to
+    /// stop waiting for the device to come back
+    closed: AtomicBool,
+}
+
+impl Port {
+    fn write(&self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        match self.port.lock().unwrap().as_mut() {
+            Some(port) => match port.write(buf) {
+                Err(err) if self.device.reconnect => {
+                    // The reader notices that the device is gone
+                    // and waits for it to come back
+                    log::debug!("serial write error: {}", err);
+                    Ok(buf.len())
+                }
+                result => result,
+            },
+            // Input is discarded while the device is disconnected
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&self) -> Result<(), std::io::Error> {
+        match self.port.lock().unwrap().as_mut() {
+            Some(port) => match port.flush() {
+                Err(_) if self.device.reconnect => Ok(()),
+                result => result,
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the serial number of the USB device that provides the
+/// tty at `path`, if it has one.
+/// The tty is linked to its device in sysfs; the USB device is the
+/// nearest ancestor of that which has an idVendor.
+#[cfg(target_os = "linux")]
+fn usb_serial_number_of(path: &OsStr) -> Option<String> {
+    let path = std::fs::canonicalize(path).ok()?;
+    let device = std::path::Path::new("/sys/class/tty")
+        .join(path.file_name()?)
+        .join("device");
+    let device = std::fs::canonicalize(device).ok()?;
+    let usb_device = device
+        .ancestors()
+        .find(|dir| dir.join("idVendor").exists())?;
+    std::fs::read_to_string(usb_device.join("serial"))
+        .ok()
+        .map(|serial_number| serial_number.trim().to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn usb_serial_number_of(_path: &OsStr) -> Option<String> {
+    None
+}
+
+/// Returns the path of the tty provided by the USB device with the
+/// given serial number
+#[cfg(target_os = "linux")]
+fn find_usb_serial_number(serial_number: &str) -> Option<OsString> {
+    std::fs::read_dir("/sys/class/tty")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| std::path::Path::new("/dev").join(entry.file_name()))
+        .find(|path| usb_serial_number_of(path.as_os_str()).as_deref() == Some(serial_number))
+        .map(|path| path.into_os_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_usb_serial_number(_serial_number: &str) -> Option<OsString> {
+    None
+}
+
 struct Slave {
     port: Handle,
 }
@@ -175,13 +357,36 @@ struct Master {
     port: Handle,
 }
 
+impl Drop for Master {
+    fn drop(&mut self) {
+        // The master lives for as long as the pane does
+        self.port.closed.store(true, Ordering::Relaxed);
+    }
+}
+
 impl Write for Master {
     fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
-        self.port.lock().unwrap().write(buf)
+        self.port.write(buf)
     }
 
     fn flush(&mut self) -> Result<(), std::io::Error> {
-        self.port.lock().unwrap().flush()
+        self.port.flush()
+    }
+}
+
+/// A writer cloned from the master, which doesn't close the port
+/// when it is dropped
+struct Writer {
+    port: Handle,
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.port.flush()
     }
 }
 
@@ -200,13 +405,21 @@ impl MasterPty for Master {
         // We rely on the fact that SystemPort implements the traits
         // that expose the underlying file descriptor, and that direct
         // reads from that return the raw data that we want
-        let fd = FileDescriptor::dup(&*self.port.lock().unwrap())?;
-        Ok(Box::new(Reader { fd }))
+        let fd = match self.port.port.lock().unwrap().as_ref() {
+            Some(port) => Some(FileDescriptor::dup(port)?),
+            None => None,
+        };
+        Ok(Box::new(Reader {
+            port: Arc::clone(&self.port),
+            fd,
+            banner: vec![],
+            last_check: Instant::now(),
+        }))
     }
 
     fn try_clone_writer(&self) -> anyhow::Result<Box<dyn std::io::Write + Send>> {
         let port = Arc::clone(&self.port);
-        Ok(Box::new(Master { port }))
+        Ok(Box::new(Writer { port }))
     }
 
     #[cfg(unix)]
@@ -217,22 +430,106 @@ impl MasterPty for Master {
 }
 
 struct Reader {
-    fd: FileDescriptor,
+    port: Handle,
+    /// None while the device is disconnected
+    fd: Option<FileDescriptor>,
+    /// Text that is to be returned ahead of the data read from the port
+    banner: Vec<u8>,
+    last_check: Instant,
+}
+
+impl Reader {
+    fn show_banner(&mut self, message: &str) {
+        self.banner = format!("\r\n\x1b[1m[{}]\x1b[0m\r\n", message).into_bytes();
+    }
+
+    fn disconnect(&mut self) {
+        let path = self.port.device.path();
+        log::error!(
+            "serial port {:?} disconnected; waiting for it to reappear",
+            path
+        );
+        self.fd.take();
+        self.port.port.lock().unwrap().take();
+        self.show_banner(&format!(
+            "serial port {} disconnected; waiting for it to reappear",
+            path.to_string_lossy()
+        ));
+    }
+
+    /// Waits for the device to reappear and reopens it, returning false
+    /// if the pane went away while waiting
+    fn reconnect(&mut self) -> bool {
+        loop {
+            if self.port.closed.load(Ordering::Relaxed) {
+                return false;
+            }
+            std::thread::sleep(RECONNECT_INTERVAL);
+
+            let port = match self.port.device.reopen() {
+                Some(port) => port,
+                None => continue,
+            };
+            match FileDescriptor::dup(&port) {
+                Ok(fd) => {
+                    self.fd.replace(fd);
+                    self.port.port.lock().unwrap().replace(port);
+                    let path = self.port.device.path();
+                    log::info!("serial port reconnected as {:?}", path);
+                    self.show_banner(&format!(
+                        "serial port reconnected as {}",
+                        path.to_string_lossy()
+                    ));
+                    return true;
+                }
+                Err(err) => log::error!("serial port reconnected, but: {:#}", err),
+            }
+        }
+    }
 }
 
 impl Read for Reader {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
         loop {
-            match self.fd.read(buf) {
+            if !self.banner.is_empty() {
+                let size = buf.len().min(self.banner.len());
+                buf[..size].copy_from_slice(&self.banner[..size]);
+                self.banner.drain(..size);
+                return Ok(size);
+            }
+
+            let fd = match self.fd.as_mut() {
+                Some(fd) => fd,
+                None => {
+                    if self.reconnect() {
+                        continue;
+                    }
+                    return Ok(0);
+                }
+            };
+
+            match fd.read(buf) {
                 Ok(size) => {
                     if size == 0 {
                         // Read timeout, but we expect to mostly hit this.
                         // It just means that there was no data available
                         // right now.
+                        if self.port.device.reconnect
+                            && self.last_check.elapsed() >= RECONNECT_INTERVAL
+                        {
+                            self.last_check = Instant::now();
+                            if self.port.device.is_gone() {
+                                self.disconnect();
+                            }
+                        }
                         continue;
                     }
                     return Ok(size);
                 }
+                Err(e) if self.port.device.reconnect => {
+                    log::error!("serial read error: {}", e);
+                    self.disconnect();
+                }
                 Err(e) => {
                     log::error!("serial read error: {}", e);
                     return Err(e);
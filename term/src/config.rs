@@ -21,6 +21,14 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         3500
     }
 
+    /// Returns the maximum number of bytes of memory that the lines in
+    /// the scrollback may use, as estimated by `Line::estimated_size`.
+    /// When exceeded, the oldest lines are removed from the scrollback.
+    /// `None` means that only `scrollback_size` limits the scrollback.
+    fn scrollback_max_bytes(&self) -> Option<usize> {
+        None
+    }
+
     /// Return true if the embedding application wants to use CSI-u encoding
     /// for keys that would otherwise be ambiguous.
     /// <http://www.leonerd.org.uk/hacks/fixterms/>
@@ -85,6 +93,12 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         false
     }
 
+    /// Whether sixel images scroll the display prior to
+    /// the application changing it via DECSDM
+    fn sixel_scrolling(&self) -> bool {
+        true
+    }
+
     /// The default unicode version to assume.
     /// This affects how the width of certain sequences is interpreted.
     /// At the time of writing, we default to 9 even though the current
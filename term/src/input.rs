@@ -21,6 +21,10 @@ pub enum MouseButton {
     WheelUp(usize),
     WheelDown(usize),
     None,
+    // The horizontal wheel variants come after None so that the
+    // serialized form of the other variants is unchanged
+    WheelLeft(usize),
+    WheelRight(usize),
 }
 
 #[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
@@ -126,6 +126,24 @@ pub struct SemanticZone {
     pub semantic_type: SemanticType,
 }
 
+/// Describes the lines held in the scrollback of the primary screen,
+/// and the lines that have been removed from it to stay within the
+/// configured limits.
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct ScrollbackStats {
+    /// The number of lines in the scrollback
+    pub lines: usize,
+    /// The estimated number of bytes used by those lines
+    pub estimated_bytes: usize,
+    /// The number of lines that have been removed from the top of
+    /// the scrollback because either `scrollback_size` or
+    /// `scrollback_max_bytes` was exceeded
+    pub evicted_lines: usize,
+    /// The estimated number of bytes used by the removed lines
+    pub evicted_bytes: usize,
+}
+
 pub mod color;
 
 #[cfg(test)]
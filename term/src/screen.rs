@@ -37,6 +37,15 @@ pub struct Screen {
     pub physical_rows: usize,
     /// Physical, visible width of the screen
     pub physical_cols: usize,
+
+    /// The estimated size of the lines in the scrollback.  This is
+    /// adjusted as lines move into and out of the scrollback, and
+    /// recomputed when the lines are rewrapped.
+    scrollback_bytes: usize,
+    /// The number of lines, and their estimated size, that have been
+    /// removed from the top of the scrollback to stay within its limits
+    evicted_lines: usize,
+    evicted_bytes: usize,
 }
 
 fn scrollback_size(config: &Arc<dyn TerminalConfiguration>, allow_scrollback: bool) -> usize {
@@ -73,6 +82,9 @@ impl Screen {
             physical_rows,
             physical_cols,
             stable_row_index_offset: 0,
+            scrollback_bytes: 0,
+            evicted_lines: 0,
+            evicted_bytes: 0,
         }
     }
 
@@ -80,6 +92,44 @@ impl Screen {
         scrollback_size(&self.config, self.allow_scrollback)
     }
 
+    /// Returns the number of lines that have scrolled off the top
+    /// of the visible portion of the screen
+    fn scrollback_len(&self) -> usize {
+        self.lines.len().saturating_sub(self.physical_rows)
+    }
+
+    fn recompute_scrollback_bytes(&mut self) {
+        let len = self.scrollback_len();
+        self.scrollback_bytes = self.lines.range(0..len).map(Line::estimated_size).sum();
+    }
+
+    /// Removes the oldest lines from the scrollback until the
+    /// remainder fits within the configured scrollback_max_bytes
+    fn enforce_scrollback_max_bytes(&mut self) {
+        let max_bytes = match self.config.scrollback_max_bytes() {
+            Some(max_bytes) if self.allow_scrollback => max_bytes,
+            _ => return,
+        };
+        while self.scrollback_bytes > max_bytes && self.scrollback_len() > 0 {
+            if let Some(line) = self.lines.pop_front() {
+                let size = line.estimated_size();
+                self.scrollback_bytes = self.scrollback_bytes.saturating_sub(size);
+                self.stable_row_index_offset += 1;
+                self.evicted_lines += 1;
+                self.evicted_bytes += size;
+            }
+        }
+    }
+
+    pub fn scrollback_stats(&self) -> ScrollbackStats {
+        ScrollbackStats {
+            lines: self.scrollback_len(),
+            estimated_bytes: self.scrollback_bytes,
+            evicted_lines: self.evicted_lines,
+            evicted_bytes: self.evicted_bytes,
+        }
+    }
+
     fn rewrap_lines(
         &mut self,
         physical_cols: usize,
@@ -249,6 +299,10 @@ impl Screen {
 
         self.physical_rows = physical_rows;
         self.physical_cols = physical_cols;
+        if self.allow_scrollback {
+            self.recompute_scrollback_bytes();
+            self.enforce_scrollback_max_bytes();
+        }
         CursorPosition {
             x: cursor_x,
             y: new_cursor_y,
@@ -595,6 +649,26 @@ impl Screen {
             phys_scroll.start
         };
 
+        if self.allow_scrollback && scroll_region.start == 0 {
+            // The top rows of the region are about to move into the
+            // scrollback, and lines_removed lines are about to be
+            // removed from the top of it
+            let first_visible = self.scrollback_len();
+            let entering: usize = self
+                .lines
+                .range(first_visible..first_visible + num_rows)
+                .map(Line::estimated_size)
+                .sum();
+            let leaving: usize = self
+                .lines
+                .range(0..lines_removed)
+                .map(Line::estimated_size)
+                .sum();
+            self.scrollback_bytes = (self.scrollback_bytes + entering).saturating_sub(leaving);
+            self.evicted_lines += lines_removed;
+            self.evicted_bytes += leaving;
+        }
+
         // To avoid thrashing the heap, prefer to move lines that were
         // scrolled off the top and re-use them at the bottom.
         let to_move = lines_removed.min(num_rows);
@@ -643,6 +717,34 @@ impl Screen {
                 );
             }
         }
+
+        if remove_idx == 0 {
+            self.enforce_scrollback_max_bytes();
+        }
+    }
+
+    /// Removes the lines of the scrollback, returning them oldest first,
+    /// so that they can be stored elsewhere while the terminal is idle.
+    /// The stable row indices of the lines that remain are unchanged.
+    pub fn take_scrollback(&mut self) -> Vec<Line> {
+        let len = self.scrollback_len();
+        self.stable_row_index_offset += len;
+        self.scrollback_bytes = 0;
+        self.lines.drain(0..len).collect()
+    }
+
+    /// Puts back lines that were removed by `take_scrollback`, above any
+    /// scrollback that has accumulated since.  The oldest of them are
+    /// discarded if they no longer fit within the configured limits.
+    pub fn restore_scrollback(&mut self, lines: Vec<Line>) {
+        let room = self.scrollback_size().saturating_sub(self.scrollback_len());
+        let skip = lines.len().saturating_sub(room);
+        for line in lines.into_iter().skip(skip).rev() {
+            self.stable_row_index_offset = self.stable_row_index_offset.saturating_sub(1);
+            self.lines.push_front(line);
+        }
+        self.recompute_scrollback_bytes();
+        self.enforce_scrollback_max_bytes();
     }
 
     pub fn erase_scrollback(&mut self) {
@@ -652,6 +754,7 @@ impl Screen {
             self.lines.pop_front();
             self.stable_row_index_offset += 1;
         }
+        self.scrollback_bytes = 0;
     }
 
     /// ```text
@@ -50,6 +50,11 @@ pub enum Alert {
     TitleMaybeChanged,
     /// When the color palette has been updated
     PaletteChanged,
+    /// When a user var has been set, via `OSC 1337 ; SetUserVar`
+    SetUserVar {
+        name: String,
+        value: String,
+    },
 }
 
 pub trait AlertHandler {
@@ -10,11 +10,11 @@ use std::sync::Arc;
 use std::time::Duration;
 use termwiz::escape::apc::KittyImageData;
 use termwiz::escape::apc::{
-    KittyFrameCompositionMode, KittyImage, KittyImageCompression, KittyImageDelete,
-    KittyImageFormat, KittyImageFrame, KittyImageFrameCompose, KittyImagePlacement,
-    KittyImageTransmit, KittyImageVerbosity,
+    KittyAnimationState, KittyFrameCompositionMode, KittyImage, KittyImageAnimation,
+    KittyImageCompression, KittyImageDelete, KittyImageFormat, KittyImageFrame,
+    KittyImageFrameCompose, KittyImagePlacement, KittyImageTransmit, KittyImageVerbosity,
 };
-use termwiz::image::ImageDataType;
+use termwiz::image::{AnimationState, ImageDataType};
 use termwiz::surface::change::ImageData;
 
 #[derive(Debug, Default)]
@@ -41,6 +41,14 @@ impl KittyImageState {
         self.id_to_data.insert(image_id, data);
     }
 
+    /// Accounts for an image whose frames were added or removed in place.
+    /// If that takes us over budget, unreferenced images are pruned;
+    /// animations are only ever evicted as a whole.
+    fn record_resized(&mut self, before: usize, after: usize) {
+        self.used_memory = self.used_memory.saturating_sub(before) + after;
+        self.prune_unreferenced();
+    }
+
     fn prune_unreferenced(&mut self) {
         let budget = 320 * 1024 * 1024; // FIXME: make this configurable
         if self.used_memory > budget {
@@ -264,6 +272,22 @@ impl TerminalState {
             } => {
                 self.kitty_remove_all_placements(delete);
             }
+            KittyImage::Delete {
+                what:
+                    KittyImageDelete::AnimationFrames {
+                        image_id,
+                        image_number,
+                        frame_number,
+                        delete: _,
+                    },
+                verbosity,
+            } => {
+                if let Err(err) =
+                    self.kitty_frame_delete(image_id, image_number, frame_number, verbosity)
+                {
+                    log::error!("Error {:#} while deleting kitty animation frame", err);
+                }
+            }
             KittyImage::Delete { what, verbosity } => {
                 log::warn!("unhandled KittyImage::Delete {:?} {:?}", what, verbosity);
             }
@@ -281,6 +305,14 @@ impl TerminalState {
                     log::error!("Error {:#} while handling KittyImage::ComposeFrame", err);
                 }
             }
+            KittyImage::AnimationControl { control, verbosity } => {
+                if let Err(err) = self.kitty_animation_control(control, verbosity) {
+                    log::error!(
+                        "Error {:#} while handling KittyImage::AnimationControl",
+                        err
+                    );
+                }
+            }
         };
 
         Ok(())
@@ -384,6 +416,129 @@ impl TerminalState {
         self.writer.flush().ok();
     }
 
+    /// Resolves the image referenced by an animation command,
+    /// sending an ENOENT response if there is no such image
+    fn kitty_resolve_image(
+        &mut self,
+        image_id: Option<u32>,
+        image_number: Option<u32>,
+        verbosity: KittyImageVerbosity,
+    ) -> anyhow::Result<(u32, Arc<ImageData>)> {
+        let id = match image_number {
+            Some(no) => self.kitty_img.number_to_id.get(&no).copied(),
+            None => image_id,
+        };
+        match id.and_then(|id| self.kitty_img.id_to_data.get(&id).map(|img| (id, img))) {
+            Some((id, img)) => Ok((id, Arc::clone(img))),
+            None => {
+                self.kitty_send_response(
+                    verbosity,
+                    false,
+                    image_id,
+                    image_number,
+                    "ENOENT".to_string(),
+                );
+                anyhow::bail!(
+                    "no matching image for image_id {:?} image_number {:?}",
+                    image_id,
+                    image_number
+                )
+            }
+        }
+    }
+
+    fn kitty_animation_control(
+        &mut self,
+        control: KittyImageAnimation,
+        verbosity: KittyImageVerbosity,
+    ) -> anyhow::Result<()> {
+        let (_, img) =
+            self.kitty_resolve_image(control.image_id, control.image_number, verbosity)?;
+
+        if let (Some(frame_no), Some(gap)) = (control.frame_number, control.duration_ms) {
+            let gap = Duration::from_millis(gap.max(0) as u64);
+            match &mut *img.data() {
+                ImageDataType::AnimRgba8 { durations, .. } => {
+                    let frame_no = frame_no as usize;
+                    anyhow::ensure!(
+                        frame_no <= durations.len(),
+                        "attempted to change the gap of frame {} which is outside range 1-{}",
+                        frame_no,
+                        durations.len()
+                    );
+                    durations[frame_no - 1] = gap;
+                }
+                // The gap of a single frame has no effect
+                _ => {}
+            }
+        }
+
+        if control.state.is_some() || control.loops.is_some() || control.current_frame.is_some() {
+            let mut anim = img.animation();
+            if let Some(state) = control.state {
+                anim.state = match state {
+                    KittyAnimationState::Stopped => AnimationState::Stopped,
+                    KittyAnimationState::Loading => AnimationState::Loading,
+                    KittyAnimationState::Running => AnimationState::Running,
+                };
+            }
+            match control.loops {
+                None | Some(0) => {}
+                Some(1) => anim.loops = 0,
+                Some(n) => anim.loops = n - 1,
+            }
+            anim.current_frame = control.current_frame.map(|n| n as usize - 1);
+            anim.generation += 1;
+        }
+
+        Ok(())
+    }
+
+    fn kitty_frame_delete(
+        &mut self,
+        image_id: Option<u32>,
+        image_number: Option<u32>,
+        frame_number: Option<u32>,
+        verbosity: KittyImageVerbosity,
+    ) -> anyhow::Result<()> {
+        let (_, img) = self.kitty_resolve_image(image_id, image_number, verbosity)?;
+        let before = img.len();
+        {
+            let mut data = img.data();
+            let single = match &mut *data {
+                ImageDataType::AnimRgba8 {
+                    width,
+                    height,
+                    frames,
+                    durations,
+                    hashes,
+                } => {
+                    let idx = (frame_number.unwrap_or(1) as usize).min(frames.len()) - 1;
+                    frames.remove(idx);
+                    durations.remove(idx);
+                    hashes.remove(idx);
+                    if frames.len() == 1 {
+                        Some(ImageDataType::Rgba8 {
+                            width: *width,
+                            height: *height,
+                            data: frames.remove(0),
+                            hash: hashes.remove(0),
+                        })
+                    } else {
+                        None
+                    }
+                }
+                // The last remaining frame is never deleted
+                _ => None,
+            };
+            if let Some(single) = single {
+                *data = single;
+            }
+        }
+        self.kitty_img.record_resized(before, img.len());
+        Ok(())
+    }
+
     fn kitty_frame_compose(
         &mut self,
         frame: KittyImageFrameCompose,
@@ -570,8 +725,8 @@ impl TerminalState {
             (background_pixel & 0xff) as u8,
         ]);
 
-        let anim = match self.kitty_img.id_to_data.get(&image_id) {
-            Some(anim) => anim,
+        let anim_data = match self.kitty_img.id_to_data.get(&image_id) {
+            Some(anim) => Arc::clone(anim),
             None => {
                 self.kitty_send_response(
                     verbosity,
@@ -584,7 +739,8 @@ impl TerminalState {
             }
         };
 
-        let mut anim = anim.data();
+        let before = anim_data.len();
+        let mut anim = anim_data.data();
         let x = frame.x.unwrap_or(0);
         let y = frame.y.unwrap_or(0);
         let frame_gap = Duration::from_millis(match frame.duration_ms {
@@ -657,6 +813,10 @@ impl TerminalState {
                             durations,
                             hashes,
                         };
+                        // Until the application says otherwise, play the
+                        // frames as they arrive rather than looping over
+                        // a partially transmitted animation
+                        anim_data.animation().state = AnimationState::Loading;
                     }
                     Some(n) => anyhow::bail!(
                         "attempted to edit frame {} but there is only a single frame",
@@ -728,6 +888,9 @@ impl TerminalState {
             }
         }
 
+        drop(anim);
+        self.kitty_img.record_resized(before, anim_data.len());
+
         Ok(())
     }
 
@@ -15,6 +15,7 @@ use termwiz::escape::csi::{
     EraseInLine, Mode, Sgr, TabulationClear, TerminalMode, TerminalModeCode, Window, XtSmGraphics,
     XtSmGraphicsAction, XtSmGraphicsItem, XtSmGraphicsStatus,
 };
+use termwiz::escape::osc::Progress;
 use termwiz::escape::{OneBased, OperatingSystemCommand, CSI};
 use termwiz::image::ImageData;
 use termwiz::surface::{CursorShape, CursorVisibility, SequenceNo};
@@ -330,6 +331,9 @@ pub struct TerminalState {
 
     current_dir: Option<Url>,
 
+    /// The progress reported via ConEmu's OSC 9;4
+    progress: Progress,
+
     term_program: String,
     term_version: String,
 
@@ -426,6 +430,7 @@ impl TerminalState {
         let color_map = default_color_map();
 
         let unicode_version = UnicodeVersion(config.unicode_version());
+        let sixel_scrolling = config.sixel_scrolling();
 
         TerminalState {
             config,
@@ -445,7 +450,7 @@ impl TerminalState {
             insert: false,
             application_cursor_keys: false,
             dec_ansi_mode: false,
-            sixel_scrolling: true,
+            sixel_scrolling,
             use_private_color_registers_for_each_graphic: false,
             color_map,
             application_keypad: false,
@@ -473,6 +478,7 @@ impl TerminalState {
             device_control_handler: None,
             alert_handler: None,
             current_dir: None,
+            progress: Progress::default(),
             term_program: term_program.to_string(),
             term_version: term_version.to_string(),
             writer: Box::new(std::io::BufWriter::new(writer)),
@@ -541,6 +547,12 @@ impl TerminalState {
         self.current_dir.as_ref()
     }
 
+    /// Returns the progress most recently reported by the
+    /// application using the ConEmu style OSC 9;4 escape sequence.
+    pub fn get_progress(&self) -> Progress {
+        self.progress
+    }
+
     /// Returns a copy of the palette.
     /// By default we don't keep a copy in the terminal state,
     /// preferring to take the config values from the users
@@ -577,6 +589,24 @@ impl TerminalState {
         &mut self.screen
     }
 
+    /// Returns information about the scrollback of the primary screen,
+    /// regardless of which screen is active
+    pub fn get_scrollback_stats(&self) -> ScrollbackStats {
+        self.screen.screen.scrollback_stats()
+    }
+
+    /// Removes the scrollback of the primary screen, returning its lines
+    /// oldest first; see `Screen::take_scrollback`
+    pub fn take_scrollback(&mut self) -> Vec<Line> {
+        self.screen.screen.take_scrollback()
+    }
+
+    /// Puts back the scrollback of the primary screen that was removed
+    /// by `take_scrollback`
+    pub fn restore_scrollback(&mut self, lines: Vec<Line>) {
+        self.screen.screen.restore_scrollback(lines)
+    }
+
     fn set_clipboard_contents(
         &self,
         selection: ClipboardSelection,
@@ -30,6 +30,8 @@ impl TerminalState {
             MouseButton::Right => 2,
             MouseButton::WheelUp(_) => 64,
             MouseButton::WheelDown(_) => 65,
+            MouseButton::WheelLeft(_) => 66,
+            MouseButton::WheelRight(_) => 67,
         };
 
         if event.modifiers.contains(KeyModifiers::SHIFT) {
@@ -68,7 +70,12 @@ impl TerminalState {
                 Self::legacy_mouse_coord(event.y),
             )?;
             self.writer.flush()?;
-        } else if self.screen.is_alt_screen_active() {
+        } else if self.screen.is_alt_screen_active()
+            && matches!(
+                event.button,
+                MouseButton::WheelUp(_) | MouseButton::WheelDown(_)
+            )
+        {
             // Send cursor keys instead (equivalent to xterm's alternateScroll mode)
             for _ in 0..self.config.alternate_buffer_wheel_scroll_speed() {
                 self.key_down(
@@ -208,6 +215,16 @@ impl TerminalState {
                 kind: MouseEventKind::Press,
                 button: MouseButton::WheelDown(_),
                 ..
+            }
+            | MouseEvent {
+                kind: MouseEventKind::Press,
+                button: MouseButton::WheelLeft(_),
+                ..
+            }
+            | MouseEvent {
+                kind: MouseEventKind::Press,
+                button: MouseButton::WheelRight(_),
+                ..
             } => self.mouse_wheel(event),
             MouseEvent {
                 kind: MouseEventKind::Press,
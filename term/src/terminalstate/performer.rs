@@ -181,6 +181,7 @@ impl<'a> Performer<'a> {
             Action::Esc(esc) => self.esc_dispatch(esc),
             Action::CSI(csi) => self.csi_dispatch(csi),
             Action::Sixel(sixel) => self.sixel(sixel),
+            Action::SixelProgress(sixel) => self.sixel_progress(sixel),
             Action::XtGetTcap(names) => self.xt_get_tcap(names),
             Action::KittyImage(img) => {
                 self.flush_print();
@@ -490,7 +491,7 @@ impl<'a> Performer<'a> {
                 self.use_private_color_registers_for_each_graphic = false;
                 self.color_map = default_color_map();
                 self.application_cursor_keys = false;
-                self.sixel_scrolling = true;
+                self.sixel_scrolling = self.config.sixel_scrolling();
                 self.dec_ansi_mode = false;
                 self.application_keypad = false;
                 self.bracketed_paste = false;
@@ -498,6 +499,7 @@ impl<'a> Performer<'a> {
                 self.sgr_mouse = false;
                 self.sixel_scrolls_right = false;
                 self.any_event_mouse = false;
+                self.progress = Default::default();
                 self.button_event_mouse = false;
                 self.current_mouse_buttons.clear();
                 self.cursor_visible = true;
@@ -580,8 +582,9 @@ impl<'a> Performer<'a> {
             OperatingSystemCommand::ITermProprietary(iterm) => match iterm {
                 ITermProprietary::File(image) => self.set_image(*image),
                 ITermProprietary::SetUserVar { name, value } => {
-                    self.user_vars.insert(name, value);
+                    self.user_vars.insert(name.clone(), value.clone());
                     if let Some(handler) = self.alert_handler.as_mut() {
+                        handler.alert(Alert::SetUserVar { name, value });
                         handler.alert(Alert::TitleMaybeChanged);
                     }
                 }
@@ -659,6 +662,14 @@ impl<'a> Performer<'a> {
                     log::info!("Application sends SystemNotification: {}", message);
                 }
             }
+            OperatingSystemCommand::ConEmuProgress(progress) => {
+                if progress != self.progress {
+                    self.progress = progress;
+                    if let Some(handler) = self.alert_handler.as_mut() {
+                        handler.alert(Alert::TitleMaybeChanged);
+                    }
+                }
+            }
             OperatingSystemCommand::RxvtExtension(params) => {
                 if let Some("notify") = params.get(0).map(String::as_str) {
                     let title = params.get(1);
@@ -8,6 +8,17 @@ use termwiz::image::ImageDataType;
 
 impl TerminalState {
     pub(crate) fn sixel(&mut self, sixel: Box<Sixel>) {
+        self.place_sixel(&sixel, false);
+    }
+
+    /// Shows the part of a large image that has arrived so far.
+    /// The cursor isn't moved, so that the complete image is placed
+    /// over it once it arrives.
+    pub(crate) fn sixel_progress(&mut self, sixel: Box<Sixel>) {
+        self.place_sixel(&sixel, true);
+    }
+
+    fn place_sixel(&mut self, sixel: &Sixel, partial: bool) {
         let (width, height) = sixel.dimensions();
 
         let mut private_color_map;
@@ -34,6 +45,9 @@ impl TerminalState {
         let mut foreground_color = RgbColor::new_8bpc(0, 0xff, 0);
 
         let mut emit_sixel = |d: &u8, foreground_color: &RgbColor, x: u32, y: u32| {
+            if x >= width {
+                return;
+            }
             let (red, green, blue) = foreground_color.to_tuple_rgb8();
             for bitno in 0..6 {
                 if y + bitno >= height {
@@ -107,6 +121,19 @@ impl TerminalState {
         let image_data = ImageDataType::new_single_frame(width, height, data);
 
         let image_data = self.raw_image_to_image_data(image_data);
+
+        // When sixel scrolling is disabled, the image is drawn at the
+        // top left of the screen, is cropped at the bottom of the screen
+        // and the cursor doesn't move.
+        let saved_cursor = if self.sixel_scrolling {
+            None
+        } else {
+            let saved = (self.cursor.x, self.cursor.y);
+            self.cursor.x = 0;
+            self.cursor.y = 0;
+            Some(saved)
+        };
+
         if let Err(err) = self.assign_image_to_cells(ImageAttachParams {
             image_width: width,
             image_height: height,
@@ -123,9 +150,14 @@ impl TerminalState {
             z_index: 0,
             image_id: None,
             placement_id: None,
-            do_not_move_cursor: false,
+            do_not_move_cursor: partial || saved_cursor.is_some(),
         }) {
             log::error!("set sixel image: {:#}", err);
         }
+
+        if let Some((x, y)) = saved_cursor {
+            self.cursor.x = x;
+            self.cursor.y = y;
+        }
     }
 }
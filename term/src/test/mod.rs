@@ -46,12 +46,17 @@ struct TestTerm {
 #[derive(Debug)]
 struct TestTermConfig {
     scrollback: usize,
+    scrollback_max_bytes: Option<usize>,
 }
 impl TerminalConfiguration for TestTermConfig {
     fn scrollback_size(&self) -> usize {
         self.scrollback
     }
 
+    fn scrollback_max_bytes(&self) -> Option<usize> {
+        self.scrollback_max_bytes
+    }
+
     fn color_palette(&self) -> ColorPalette {
         ColorPalette::default()
     }
@@ -59,6 +64,17 @@ impl TerminalConfiguration for TestTermConfig {
 
 impl TestTerm {
     fn new(height: usize, width: usize, scrollback: usize) -> Self {
+        Self::with_config(
+            height,
+            width,
+            TestTermConfig {
+                scrollback,
+                scrollback_max_bytes: None,
+            },
+        )
+    }
+
+    fn with_config(height: usize, width: usize, config: TestTermConfig) -> Self {
         let _ = pretty_env_logger::formatted_builder()
             .is_test(true)
             .filter_level(log::LevelFilter::Trace)
@@ -71,7 +87,7 @@ impl TestTerm {
                 pixel_width: width * 8,
                 pixel_height: height * 16,
             },
-            Arc::new(TestTermConfig { scrollback }),
+            Arc::new(config),
             "WezTerm",
             "O_o",
             Box::new(Vec::new()),
@@ -977,6 +993,71 @@ fn test_scrollup() {
     assert_eq!(term.screen().visible_row_to_stable_row(0), 7);
 }
 
+#[test]
+fn test_scrollback_max_bytes() {
+    let line_size = Line::with_width(1).estimated_size();
+    let mut term = TestTerm::with_config(
+        2,
+        1,
+        TestTermConfig {
+            scrollback: 100,
+            scrollback_max_bytes: Some(3 * line_size),
+        },
+    );
+    term.print("1\n2\n3\n4\n");
+    assert_all_contents(&term, file!(), line!(), &["1", "2", "3", "4", " "]);
+    assert_eq!(
+        term.get_scrollback_stats(),
+        ScrollbackStats {
+            lines: 3,
+            estimated_bytes: 3 * line_size,
+            evicted_lines: 0,
+            evicted_bytes: 0,
+        }
+    );
+
+    term.print("5\n6\n");
+    assert_all_contents(&term, file!(), line!(), &["3", "4", "5", "6", " "]);
+    // Evicting lines doesn't change the stable row indices
+    assert_eq!(term.screen().visible_row_to_stable_row(0), 5);
+    assert_eq!(term.screen().stable_row_to_phys(1), None);
+    assert_eq!(
+        term.get_scrollback_stats(),
+        ScrollbackStats {
+            lines: 3,
+            estimated_bytes: 3 * line_size,
+            evicted_lines: 2,
+            evicted_bytes: 2 * line_size,
+        }
+    );
+
+    term.erase_scrollback();
+    assert_eq!(term.get_scrollback_stats().lines, 0);
+    assert_eq!(term.get_scrollback_stats().estimated_bytes, 0);
+}
+
+#[test]
+fn test_take_scrollback() {
+    let mut term = TestTerm::new(2, 1, 2);
+    term.print("1\n2\n3\n");
+    assert_all_contents(&term, file!(), line!(), &["1", "2", "3", " "]);
+
+    let scrollback = term.take_scrollback();
+    assert_eq!(scrollback.len(), 2);
+    assert_all_contents(&term, file!(), line!(), &["3", " "]);
+    assert_eq!(term.screen().visible_row_to_stable_row(0), 2);
+    assert_eq!(term.get_scrollback_stats().lines, 0);
+
+    // Only one of the lines still fits once another has scrolled
+    // into the scrollback
+    term.print("4\n");
+    term.restore_scrollback(scrollback);
+    assert_all_contents(&term, file!(), line!(), &["2", "3", "4", " "]);
+    assert_eq!(term.screen().visible_row_to_stable_row(0), 3);
+    assert_eq!(term.screen().stable_row_to_phys(1), Some(0));
+    assert_eq!(term.get_scrollback_stats().lines, 2);
+}
+
 #[test]
 fn test_ri() {
     let mut term = TestTerm::new(3, 1, 10);
@@ -460,6 +460,20 @@ impl CellAttributes {
         res
     }
 
+    /// Returns the number of bytes that the attributes have allocated
+    /// on the heap.  Hyperlinks and image data are shared between cells
+    /// and are not included.
+    pub fn heap_size(&self) -> usize {
+        match self.fat.as_ref() {
+            Some(fat) => {
+                std::mem::size_of::<FatAttributes>()
+                    + fat.image.capacity() * std::mem::size_of::<Box<ImageCell>>()
+                    + fat.image.len() * std::mem::size_of::<ImageCell>()
+            }
+            None => 0,
+        }
+    }
+
     pub fn hyperlink(&self) -> Option<&Arc<Hyperlink>> {
         self.fat.as_ref().and_then(|fat| fat.hyperlink.as_ref())
     }
@@ -673,6 +687,17 @@ impl TeenyString {
         unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
     }
 
+    /// Returns the number of bytes that have been allocated on the heap
+    /// to hold the text; short strings are stored inline and use none
+    pub fn heap_size(&self) -> usize {
+        if Self::is_marker_bit_set(self.0) {
+            0
+        } else {
+            let heap = self.0 as *const usize as *const TeenyStringHeap;
+            std::mem::size_of::<TeenyStringHeap>() + unsafe { (*heap).bytes.capacity() }
+        }
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         if Self::is_marker_bit_set(self.0) {
             let bytes = &self.0 as *const usize as *const u8;
@@ -818,6 +843,12 @@ impl Cell {
         self.text.width()
     }
 
+    /// Returns the number of bytes that the cell has allocated on the
+    /// heap, in addition to its own size
+    pub fn heap_size(&self) -> usize {
+        self.text.heap_size() + self.attrs.heap_size()
+    }
+
     /// Returns the attributes of the cell
     pub fn attrs(&self) -> &CellAttributes {
         &self.attrs
@@ -933,6 +964,9 @@ mod test {
         let longer = TeenyString::from_str("hellothere", None);
         assert_eq!(longer.as_bytes(), b"hellothere");
 
+        assert_eq!(s.heap_size(), 0);
+        assert!(longer.heap_size() >= "hellothere".len());
+
         assert_eq!(
             TeenyString::from_char(' ').as_bytes(),
             TeenyString::space().as_bytes()
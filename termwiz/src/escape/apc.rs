@@ -505,8 +505,14 @@ pub enum KittyImageDelete {
     AtCursorPosition { delete: bool },
 
     /// d='f' or d='F'
-    /// Delete animation frames
-    AnimationFrames { delete: bool },
+    /// Delete an animation frame from the specified image.
+    /// If frame_number is omitted, the first frame is deleted.
+    AnimationFrames {
+        image_id: Option<u32>,
+        image_number: Option<u32>,
+        frame_number: Option<u32>,
+        delete: bool,
+    },
 
     /// d='p' or d='P'
     /// Delete all placements that intersect the specified
@@ -557,7 +563,15 @@ impl KittyImageDelete {
                 delete,
             }),
             'c' | 'C' => Some(Self::AtCursorPosition { delete }),
-            'f' | 'F' => Some(Self::AnimationFrames { delete }),
+            'f' | 'F' => Some(Self::AnimationFrames {
+                image_id: geti(keys, "i"),
+                image_number: geti(keys, "I"),
+                frame_number: match geti(keys, "r") {
+                    None | Some(0) => None,
+                    n => n,
+                },
+                delete,
+            }),
             'p' | 'P' => Some(Self::DeleteAt {
                 x: geti(keys, "x")?,
                 y: geti(keys, "y")?,
@@ -619,8 +633,16 @@ impl KittyImageDelete {
             Self::AtCursorPosition { delete } => {
                 keys.insert("d", d('c', delete));
             }
-            Self::AnimationFrames { delete } => {
+            Self::AnimationFrames {
+                image_id,
+                image_number,
+                frame_number,
+                delete,
+            } => {
                 keys.insert("d", d('f', delete));
+                set(keys, "i", image_id);
+                set(keys, "I", image_number);
+                set(keys, "r", frame_number);
             }
             Self::DeleteAt { x, y, delete } => {
                 keys.insert("d", d('p', delete));
@@ -797,7 +819,7 @@ impl KittyImageFrame {
                 None | Some(0) => None,
                 n => n,
             },
-            duration_ms: match geti(keys, "Z") {
+            duration_ms: match geti(keys, "z") {
                 None | Some(0) => None,
                 n => n,
             },
@@ -815,7 +837,7 @@ impl KittyImageFrame {
         set(keys, "y", &self.y);
         set(keys, "c", &self.base_frame);
         set(keys, "r", &self.frame_number);
-        set(keys, "Z", &self.duration_ms);
+        set(keys, "z", &self.duration_ms);
         match &self.composition_mode {
             KittyFrameCompositionMode::AlphaBlending => {}
             KittyFrameCompositionMode::Overwrite => {
@@ -826,6 +848,101 @@ impl KittyImageFrame {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KittyAnimationState {
+    /// Stop the animation.
+    /// s=1
+    Stopped,
+    /// Run the animation, but wait for more frames to be
+    /// transmitted when the last frame is reached.
+    /// s=2
+    Loading,
+    /// Run the animation, looping as specified by the
+    /// number of loops.
+    /// s=3
+    Running,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KittyImageAnimation {
+    /// i=...
+    pub image_id: Option<u32>,
+    /// I=...
+    pub image_number: Option<u32>,
+
+    /// s=...
+    pub state: Option<KittyAnimationState>,
+
+    /// 1-based number of the frame whose gap is changed by
+    /// duration_ms.
+    /// r=...
+    pub frame_number: Option<u32>,
+
+    /// Gap in milliseconds of frame_number from the next frame.
+    /// Negative values make the frame gapless, which means
+    /// that it is skipped during playback.
+    /// z=...
+    pub duration_ms: Option<i32>,
+
+    /// 1-based number of the frame that should be made current.
+    /// c=...
+    pub current_frame: Option<u32>,
+
+    /// The number of loops to play.  0 is ignored, 1 loops
+    /// forever and any other value plays that many loops, less one.
+    /// v=...
+    pub loops: Option<u32>,
+}
+
+impl KittyImageAnimation {
+    fn from_keys(keys: &BTreeMap<&str, &str>) -> Option<Self> {
+        Some(Self {
+            image_id: geti(keys, "i"),
+            image_number: geti(keys, "I"),
+            state: match geti(keys, "s") {
+                None | Some(0) => None,
+                Some(1) => Some(KittyAnimationState::Stopped),
+                Some(2) => Some(KittyAnimationState::Loading),
+                Some(3) => Some(KittyAnimationState::Running),
+                _ => return None,
+            },
+            frame_number: match geti(keys, "r") {
+                None | Some(0) => None,
+                n => n,
+            },
+            duration_ms: match geti(keys, "z") {
+                None | Some(0) => None,
+                n => n,
+            },
+            current_frame: match geti(keys, "c") {
+                None | Some(0) => None,
+                n => n,
+            },
+            loops: match geti(keys, "v") {
+                None | Some(0) => None,
+                n => n,
+            },
+        })
+    }
+
+    fn to_keys(&self, keys: &mut BTreeMap<&'static str, String>) {
+        set(keys, "i", &self.image_id);
+        set(keys, "I", &self.image_number);
+        if let Some(state) = &self.state {
+            let s = match state {
+                KittyAnimationState::Stopped => "1",
+                KittyAnimationState::Loading => "2",
+                KittyAnimationState::Running => "3",
+            };
+            keys.insert("s", s.to_string());
+        }
+        set(keys, "r", &self.frame_number);
+        set(keys, "z", &self.duration_ms);
+        set(keys, "c", &self.current_frame);
+        set(keys, "v", &self.loops);
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KittyImage {
     /// a='t'
@@ -864,6 +981,11 @@ pub enum KittyImage {
         frame: KittyImageFrameCompose,
         verbosity: KittyImageVerbosity,
     },
+    /// a='a'
+    AnimationControl {
+        control: KittyImageAnimation,
+        verbosity: KittyImageVerbosity,
+    },
 }
 
 impl KittyImage {
@@ -876,6 +998,7 @@ impl KittyImage {
             Self::Delete { verbosity, .. } => *verbosity,
             Self::TransmitFrame { verbosity, .. } => *verbosity,
             Self::ComposeFrame { verbosity, .. } => *verbosity,
+            Self::AnimationControl { verbosity, .. } => *verbosity,
         }
     }
 
@@ -929,6 +1052,10 @@ impl KittyImage {
                 frame: KittyImageFrameCompose::from_keys(&keys)?,
                 verbosity,
             }),
+            "a" => Some(Self::AnimationControl {
+                control: KittyImageAnimation::from_keys(&keys)?,
+                verbosity,
+            }),
             _ => None,
         }
     }
@@ -993,6 +1120,11 @@ impl KittyImage {
                 frame.to_keys(keys);
                 verbosity.to_keys(keys);
             }
+            Self::AnimationControl { control, verbosity } => {
+                keys.insert("a", "a".to_string());
+                control.to_keys(keys);
+                verbosity.to_keys(keys);
+            }
         }
     }
 }
@@ -1086,5 +1218,34 @@ mod test {
                 },
             }
         );
+
+        assert_eq!(
+            KittyImage::parse_apc("Ga=a,i=3,s=3,v=1,r=2,z=-1".as_bytes()).unwrap(),
+            KittyImage::AnimationControl {
+                control: KittyImageAnimation {
+                    image_id: Some(3),
+                    image_number: None,
+                    state: Some(KittyAnimationState::Running),
+                    frame_number: Some(2),
+                    duration_ms: Some(-1),
+                    current_frame: None,
+                    loops: Some(1),
+                },
+                verbosity: KittyImageVerbosity::Verbose,
+            }
+        );
+
+        assert_eq!(
+            KittyImage::parse_apc("Ga=d,d=F,i=3,r=2".as_bytes()).unwrap(),
+            KittyImage::Delete {
+                what: KittyImageDelete::AnimationFrames {
+                    image_id: Some(3),
+                    image_number: None,
+                    frame_number: Some(2),
+                    delete: true,
+                },
+                verbosity: KittyImageVerbosity::Verbose,
+            }
+        );
     }
 }
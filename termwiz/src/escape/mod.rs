@@ -38,6 +38,11 @@ pub enum Action {
     CSI(CSI),
     Esc(Esc),
     Sixel(Box<Sixel>),
+    /// The part of a large sixel image that has been decoded so far,
+    /// so that it can be shown while the rest of it arrives.  It is
+    /// followed by more progress, and then by `Sixel` with the whole
+    /// image, which is placed over it.
+    SixelProgress(Box<Sixel>),
     /// A list of termcap, terminfo names for which the application
     /// whats information
     XtGetTcap(Vec<String>),
@@ -56,6 +61,8 @@ impl Display for Action {
             Action::CSI(csi) => csi.fmt(f),
             Action::Esc(esc) => esc.fmt(f),
             Action::Sixel(sixel) => sixel.fmt(f),
+            // The whole image is encoded by the `Sixel` that follows
+            Action::SixelProgress(_) => Ok(()),
             Action::XtGetTcap(names) => {
                 write!(f, "\x1bP+q")?;
                 for (i, name) in names.iter().enumerate() {
@@ -244,12 +251,10 @@ pub struct Sixel {
 }
 
 impl Sixel {
-    /// Returns the width, height of the image
+    /// Returns the width, height of the image.
+    /// The raster attributes, if any, specify the minimum size;
+    /// the image is enlarged if the data extends beyond them.
     pub fn dimensions(&self) -> (u32, u32) {
-        if let (Some(w), Some(h)) = (self.pixel_width, self.pixel_height) {
-            return (w, h);
-        }
-
         // Compute it by evaluating the sixel data
         let mut max_x = 0;
         let mut max_y = 0;
@@ -283,7 +288,14 @@ impl Sixel {
             }
         }
 
-        (max_x, max_y)
+        let height = match self.pixel_height {
+            // The last row of sixels is allowed to extend past
+            // the declared height; those pixels are cropped
+            Some(h) if max_y < h.saturating_add(6) => h,
+            _ => max_y,
+        };
+
+        (max_x.max(self.pixel_width.unwrap_or(0)), height)
     }
 }
 
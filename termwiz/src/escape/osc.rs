@@ -5,6 +5,8 @@ use bitflags::bitflags;
 use num_derive::*;
 use num_traits::FromPrimitive;
 use ordered_float::NotNan;
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::{Display, Error as FmtError, Formatter, Result as FmtResult};
 use std::str;
@@ -36,6 +38,7 @@ pub enum OperatingSystemCommand {
     QuerySelection(Selection),
     SetSelection(Selection, String),
     SystemNotification(String),
+    ConEmuProgress(Progress),
     ITermProprietary(ITermProprietary),
     FinalTermSemanticPrompt(FinalTermSemanticPrompt),
     ChangeColorNumber(Vec<ChangeColorPair>),
@@ -48,6 +51,58 @@ pub enum OperatingSystemCommand {
     Unspecified(Vec<Vec<u8>>),
 }
 
+/// The progress state reported by ConEmu's `OSC 9 ; 4 ; st ; pr ST`.
+/// See <https://conemu.github.io/en/AnsiEscapeCodes.html#ConEmu_specific_OSC>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Progress {
+    /// No progress is being reported
+    None,
+    /// The percentage that is complete, in the range 0-100
+    Percentage(u8),
+    /// The operation has failed
+    Error,
+    /// The operation is ongoing, but the amount complete is not known
+    Indeterminate,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Progress {
+    fn parse(osc: &[&[u8]]) -> Result<Self> {
+        ensure!(osc.len() >= 3, "missing progress state");
+        ensure!(osc.len() <= 4, "too many progress params");
+        let percentage = match osc.get(3) {
+            Some(pr) if !pr.is_empty() => str::from_utf8(pr)?.parse::<u8>()?.min(100),
+            _ => 0,
+        };
+        match osc[2] {
+            b"0" => Ok(Self::None),
+            b"1" => Ok(Self::Percentage(percentage)),
+            b"2" => Ok(Self::Error),
+            b"3" => Ok(Self::Indeterminate),
+            // "paused"; we don't distinguish that from normal progress
+            b"4" => Ok(Self::Percentage(percentage)),
+            st => bail!("invalid progress state {:?}", st),
+        }
+    }
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::None => write!(f, "0"),
+            Self::Percentage(pr) => write!(f, "1;{}", pr),
+            Self::Error => write!(f, "2"),
+            Self::Indeterminate => write!(f, "3"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 #[repr(u8)]
 pub enum DynamicColorNumber {
@@ -295,6 +350,9 @@ impl OperatingSystemCommand {
             )),
             SetHyperlink => Ok(OperatingSystemCommand::SetHyperlink(Hyperlink::parse(osc)?)),
             ManipulateSelectionData => Self::parse_selection(osc),
+            SystemNotification if osc.len() >= 3 && osc[1] == b"4" => {
+                Progress::parse(osc).map(OperatingSystemCommand::ConEmuProgress)
+            }
             SystemNotification => single_string!(SystemNotification),
             SetCurrentWorkingDirectory => single_string!(CurrentWorkingDirectory),
             ITermProprietary => {
@@ -493,6 +551,7 @@ impl Display for OperatingSystemCommand {
             QuerySelection(s) => write!(f, "52;{};?", s)?,
             SetSelection(s, val) => write!(f, "52;{};{}", s, base64::encode(val))?,
             SystemNotification(s) => write!(f, "9;{}", s)?,
+            ConEmuProgress(p) => write!(f, "9;4;{}", p)?,
             ITermProprietary(i) => i.fmt(f)?,
             FinalTermSemanticPrompt(i) => i.fmt(f)?,
             ResetColors(colors) => {
@@ -1260,6 +1319,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn conemu_progress() {
+        assert_eq!(
+            parse(&["9", "4", "1", "42"], "\x1b]9;4;1;42\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::Percentage(42))
+        );
+        assert_eq!(
+            parse(&["9", "4", "0"], "\x1b]9;4;0\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::None)
+        );
+        assert_eq!(
+            parse(&["9", "4", "2", "50"], "\x1b]9;4;2\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::Error)
+        );
+        assert_eq!(
+            parse(&["9", "4", "3"], "\x1b]9;4;3\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::Indeterminate)
+        );
+        // Out of range values are clamped
+        assert_eq!(
+            parse(&["9", "4", "1", "200"], "\x1b]9;4;1;100\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::Percentage(100))
+        );
+        // Ordinary notifications are unaffected
+        assert_eq!(
+            parse(&["9", "hello"], "\x1b]9;hello\x1b\\"),
+            OperatingSystemCommand::SystemNotification("hello".into())
+        );
+    }
+
     #[test]
     fn title() {
         assert_eq!(
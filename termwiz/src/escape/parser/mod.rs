@@ -10,9 +10,50 @@ use regex::bytes::Regex;
 use std::cell::RefCell;
 use vtparse::{CsiParam, VTActor, VTParser};
 
+/// Limits that are applied while decoding sixel graphics, so that
+/// a single image cannot consume an unbounded amount of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SixelLimits {
+    /// The maximum size of the decoded image, assuming 4 bytes per pixel
+    pub max_image_size_bytes: usize,
+    /// The maximum width or height of the image, in pixels
+    pub max_image_dimension: u32,
+}
+
+impl Default for SixelLimits {
+    fn default() -> Self {
+        Self {
+            max_image_size_bytes: 400_000_000,
+            max_image_dimension: u32::max_value(),
+        }
+    }
+}
+
+/// The amount of undecoded sixel data that we allow to accumulate
+/// before decoding it.
+const SIXEL_CHUNK_SIZE: usize = 64 * 1024;
+
 struct SixelBuilder {
     sixel: Sixel,
     buf: Vec<u8>,
+    limits: SixelLimits,
+    /// Current x position, in pixels
+    x: u32,
+    /// Current sixel row; each is 6 pixels tall
+    row: u32,
+    /// The widest that the image has been so far
+    max_x: u32,
+    /// Set once the image has grown too large; any further
+    /// data is discarded
+    truncated: bool,
+    /// Set once we have warned about discarding pixels to the
+    /// right of the maximum width
+    clipped: bool,
+    /// Set if the raster attributes describe an image that is too
+    /// large, in which case no image is produced at all
+    rejected: bool,
+    /// The length of `sixel.data` when progress was last reported
+    progress_len: usize,
     repeat_re: Regex,
     raster_re: Regex,
     colordef_re: Regex,
@@ -51,6 +92,7 @@ impl GetTcapBuilder {
 #[derive(Default)]
 struct ParseState {
     sixel: Option<SixelBuilder>,
+    sixel_limits: SixelLimits,
     dcs: Option<ShortDeviceControl>,
     get_tcap: Option<GetTcapBuilder>,
 }
@@ -80,6 +122,11 @@ impl Parser {
         }
     }
 
+    /// Changes the limits applied to subsequently received sixel images
+    pub fn set_sixel_limits(&mut self, limits: SixelLimits) {
+        self.state.borrow_mut().sixel_limits = limits;
+    }
+
     pub fn parse<F: FnMut(Action)>(&mut self, bytes: &[u8], mut callback: F) {
         let mut perform = Performer {
             callback: &mut callback,
@@ -204,7 +251,9 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
         self.state.get_tcap.take();
         self.state.dcs.take();
         if byte == b'q' && intermediates.is_empty() && !ignored_extra_intermediates {
-            self.state.sixel.replace(SixelBuilder::new(params));
+            self.state
+                .sixel
+                .replace(SixelBuilder::new(params, self.state.sixel_limits));
         } else if byte == b'q' && intermediates == [b'+'] {
             self.state.get_tcap.replace(GetTcapBuilder::default());
         } else if !ignored_extra_intermediates && is_short_dcs(intermediates, byte) {
@@ -231,6 +280,9 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
             dcs.data.push(data);
         } else if let Some(sixel) = self.state.sixel.as_mut() {
             sixel.push(data);
+            if let Some(progress) = sixel.take_progress() {
+                (self.callback)(Action::SixelProgress(Box::new(progress)));
+            }
         } else if let Some(tcap) = self.state.get_tcap.as_mut() {
             tcap.push(data);
         } else {
@@ -245,7 +297,9 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
             ));
         } else if let Some(mut sixel) = self.state.sixel.take() {
             sixel.finish();
-            (self.callback)(Action::Sixel(Box::new(sixel.sixel)));
+            if !sixel.rejected {
+                (self.callback)(Action::Sixel(Box::new(sixel.sixel)));
+            }
         } else if let Some(tcap) = self.state.get_tcap.take() {
             (self.callback)(Action::XtGetTcap(tcap.finish()));
         } else {
@@ -286,7 +340,7 @@ impl<'a, F: FnMut(Action)> VTActor for Performer<'a, F> {
 }
 
 impl SixelBuilder {
-    fn new(params: &[i64]) -> Self {
+    fn new(params: &[i64], limits: SixelLimits) -> Self {
         let pan = match params.get(0).unwrap_or(&0) {
             7 | 8 | 9 => 1,
             0 | 1 | 5 | 6 => 2,
@@ -316,6 +370,14 @@ impl SixelBuilder {
                 data: vec![],
             },
             buf: vec![],
+            limits,
+            x: 0,
+            row: 0,
+            max_x: 0,
+            truncated: false,
+            clipped: false,
+            rejected: false,
+            progress_len: 0,
             repeat_re,
             raster_re,
             colordef_re,
@@ -324,10 +386,102 @@ impl SixelBuilder {
     }
 
     fn push(&mut self, data: u8) {
+        if self.truncated {
+            return;
+        }
         self.buf.push(data);
+        if self.buf.len() >= SIXEL_CHUNK_SIZE {
+            // Decode up to and including the last byte that cannot
+            // be part of an incomplete multi-byte command, so that
+            // we don't need to buffer the entire image.
+            if let Some(idx) = self
+                .buf
+                .iter()
+                .rposition(|&b| b == b'$' || b == b'-' || (b >= 0x3f && b <= 0x7e))
+            {
+                let consumed = self.decode(idx + 1);
+                self.buf.drain(0..consumed);
+            }
+        }
+    }
+
+    /// Returns a copy of the image decoded so far, so that it can be
+    /// shown before the rest of the data arrives.  A copy is made each
+    /// time that the decoded data has doubled, which bounds the cost of
+    /// copying and drawing the copies to a small multiple of the cost
+    /// of the complete image.
+    fn take_progress(&mut self) -> Option<Sixel> {
+        let len = self.sixel.data.len();
+        if self.rejected || len == 0 || len < self.progress_len.saturating_mul(2) {
+            return None;
+        }
+        self.progress_len = len;
+        Some(self.sixel.clone())
     }
 
     fn finish(&mut self) {
+        if !self.truncated {
+            self.decode(self.buf.len());
+        }
+        self.buf.clear();
+    }
+
+    /// Stop accepting any further data for this image
+    fn truncate(&mut self, width: u32, height: u32) {
+        log::warn!(
+            "Truncating sixel image at {}x{} because it exceeds {:?}",
+            width,
+            height,
+            self.limits
+        );
+        self.truncated = true;
+    }
+
+    /// Returns how many of the `count` pixels that would be emitted
+    /// at the current position fit within the limits.
+    /// Pixels beyond the maximum width are discarded, and if the
+    /// current row cannot fit at all, the image is truncated.
+    fn clip_pixels(&mut self, count: u32) -> u32 {
+        let height = (self.row + 1).saturating_mul(6);
+        let max_width = (self.limits.max_image_size_bytes / (4 * height as usize))
+            .min(self.limits.max_image_dimension as usize) as u32;
+        if height > self.limits.max_image_dimension || self.max_x > max_width {
+            self.truncate(self.max_x, height - 6);
+            return 0;
+        }
+        let allowed = count.min(max_width.saturating_sub(self.x));
+        if allowed < count && !self.clipped {
+            log::warn!(
+                "Clipping sixel image to {} pixels wide because it exceeds {:?}",
+                max_width,
+                self.limits
+            );
+            self.clipped = true;
+        }
+        allowed
+    }
+
+    /// Emits either a single sixel, or a repeated sixel if
+    /// `repeat_count` is specified, clipped to the limits
+    fn emit_data(&mut self, data: u8, repeat_count: Option<u32>) {
+        let count = self.clip_pixels(repeat_count.unwrap_or(1));
+        if count == 0 {
+            return;
+        }
+        match repeat_count {
+            None => self.sixel.data.push(SixelData::Data(data)),
+            Some(_) => self.sixel.data.push(SixelData::Repeat {
+                repeat_count: count,
+                data,
+            }),
+        }
+        self.x += count;
+        self.max_x = self.max_x.max(self.x);
+    }
+
+    /// Decodes `self.buf[0..len]`, returning the number of bytes
+    /// that were consumed.
+    fn decode(&mut self, len: usize) -> usize {
         fn cap_int<T: std::str::FromStr>(m: regex::bytes::Match) -> Option<T> {
             let bytes = m.as_bytes();
             // Safe because we matched digits from the regex
@@ -335,25 +489,29 @@ impl SixelBuilder {
             s.parse::<T>().ok()
         }
 
-        let mut remainder = &self.buf[..];
+        let buf = std::mem::take(&mut self.buf);
+        let mut remainder = &buf[0..len];
 
-        while !remainder.is_empty() {
+        while !remainder.is_empty() && !self.truncated {
             let data = remainder[0];
 
             if data == b'$' {
                 self.sixel.data.push(SixelData::CarriageReturn);
+                self.x = 0;
                 remainder = &remainder[1..];
                 continue;
             }
 
             if data == b'-' {
                 self.sixel.data.push(SixelData::NewLine);
+                self.x = 0;
+                self.row = self.row.saturating_add(1);
                 remainder = &remainder[1..];
                 continue;
             }
 
             if data >= 0x3f && data <= 0x7e {
-                self.sixel.data.push(SixelData::Data(data - 0x3f));
+                self.emit_data(data - 0x3f, None);
                 remainder = &remainder[1..];
                 continue;
             }
@@ -373,25 +531,26 @@ impl SixelBuilder {
                 self.sixel.pixel_height = pixel_height;
 
                 if let (Some(w), Some(h)) = (pixel_width, pixel_height) {
-                    let size = w as usize * h as usize;
-                    // Ideally we'd just use `try_reserve` here, but that is
-                    // nightly Rust only at the time of writing this comment:
-                    // <https://github.com/rust-lang/rust/issues/48043>
-                    const MAX_SIXEL_SIZE: usize = 100_000_000;
-                    if size > MAX_SIXEL_SIZE {
-                        log::error!(
-                            "Ignoring sixel data {}x{} because {} bytes > max allowed {}",
+                    let size = 4 * w as usize * h as usize;
+                    let max_dim = self.limits.max_image_dimension;
+                    if size > self.limits.max_image_size_bytes || w > max_dim || h > max_dim {
+                        log::warn!(
+                            "Ignoring sixel image {}x{} ({} bytes) because it exceeds {:?}",
                             w,
                             h,
                             size,
-                            MAX_SIXEL_SIZE
+                            self.limits
                         );
                         self.sixel.pixel_width = None;
                         self.sixel.pixel_height = None;
                         self.sixel.data.clear();
-                        return;
+                        self.rejected = true;
+                        self.truncated = true;
+                        break;
                     }
-                    self.sixel.data.reserve(size);
+                    // Pre-size for the worst case of one entry
+                    // per 6-pixel-tall column
+                    self.sixel.data.reserve(w as usize * ((h as usize + 5) / 6));
                 }
 
                 remainder = &remainder[matched_len..];
@@ -451,9 +610,7 @@ impl SixelBuilder {
 
                 let repeat_count = cap_int(c.get(1).unwrap()).unwrap_or(1);
                 let data = c.get(2).unwrap().as_bytes()[0] - 0x3f;
-                self.sixel
-                    .data
-                    .push(SixelData::Repeat { repeat_count, data });
+                self.emit_data(data, Some(repeat_count));
                 remainder = &remainder[matched_len..];
                 continue;
             }
@@ -464,8 +621,14 @@ impl SixelBuilder {
                 std::str::from_utf8(&remainder[0..24.min(remainder.len())])
             );
 
+            // Discard the rest of the image
+            self.truncated = true;
             break;
         }
+
+        let consumed = len - remainder.len();
+        self.buf = buf;
+        consumed
     }
 }
 
@@ -757,6 +920,96 @@ mod test {
         );
     }
 
+    #[test]
+    fn sixel_limits() {
+        let mut p = Parser::new();
+        p.set_sixel_limits(SixelLimits {
+            max_image_size_bytes: 10_000,
+            max_image_dimension: 12,
+        });
+
+        // Raster attributes that exceed the limits reject the image
+        let actions = p.parse_as_vec(b"\x1bPq\"1;1;100;100~\x1b\\");
+        assert_eq!(
+            vec![Action::Esc(Esc::Code(EscCode::StringTerminator))],
+            actions
+        );
+
+        // Pixels beyond the maximum width are clipped, and rows
+        // beyond the maximum height are discarded
+        let actions = p.parse_as_vec(b"\x1bPq!20~-~~-~\x1b\\");
+        use SixelData::*;
+        match &actions[0] {
+            Action::Sixel(sixel) => {
+                assert_eq!(
+                    sixel.data,
+                    vec![
+                        Repeat {
+                            repeat_count: 12,
+                            data: 63
+                        },
+                        NewLine,
+                        Data(63),
+                        Data(63),
+                        NewLine,
+                    ]
+                );
+                assert_eq!(sixel.dimensions(), (12, 12));
+            }
+            wat => panic!("unexpected {:?}", wat),
+        }
+    }
+
+    #[test]
+    fn sixel_chunked() {
+        // Data that spans the chunk size is decoded the same way
+        // as data that arrives all at once
+        let mut data = b"\x1bPq#1".to_vec();
+        data.resize(SIXEL_CHUNK_SIZE - 1, b'~');
+        data.extend_from_slice(b"#2;2;100;0;0#2!10@-\x1b\\");
+
+        let mut p = Parser::new();
+        let all = p.parse_as_vec(&data);
+
+        let mut p = Parser::new();
+        let mut chunked = vec![];
+        for chunk in data.chunks(1000) {
+            chunked.append(&mut p.parse_as_vec(chunk));
+        }
+        assert_eq!(all, chunked);
+
+        // The part decoded once the chunk size was reached is reported
+        // as progress, ahead of the complete image
+        let progress = match &all[0] {
+            Action::SixelProgress(progress) => progress,
+            wat => panic!("unexpected {:?}", wat),
+        };
+        assert_eq!(progress.data.len(), SIXEL_CHUNK_SIZE - 5);
+
+        match &all[1] {
+            Action::Sixel(sixel) => {
+                assert_eq!(&sixel.data[..progress.data.len()], &progress.data[..]);
+                let n = sixel.data.len();
+                assert_eq!(
+                    &sixel.data[n - 4..],
+                    &[
+                        SixelData::DefineColorMapRGB {
+                            color_number: 2,
+                            rgb: RgbColor::new_8bpc(255, 0, 0)
+                        },
+                        SixelData::SelectColorMapEntry(2),
+                        SixelData::Repeat {
+                            repeat_count: 10,
+                            data: 1
+                        },
+                        SixelData::NewLine,
+                    ]
+                );
+            }
+            wat => panic!("unexpected {:?}", wat),
+        }
+    }
+
     #[test]
     fn soft_reset() {
         let mut p = Parser::new();
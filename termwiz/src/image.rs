@@ -156,6 +156,14 @@ impl ImageCell {
     pub fn display_offset(&self) -> (u32, u32) {
         (self.display_offset_x, self.display_offset_y)
     }
+
+    pub fn image_id(&self) -> Option<u32> {
+        self.image_id
+    }
+
+    pub fn placement_id(&self) -> Option<u32> {
+        self.placement_id
+    }
 }
 
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
@@ -361,11 +369,53 @@ impl ImageDataType {
 
 static IMAGE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationState {
+    /// Playback is paused on the current frame
+    Stopped,
+    /// Play through the frames, but wait on the last frame
+    /// rather than looping, as more frames may still be added
+    Loading,
+    /// Play through the frames, looping as specified
+    Running,
+}
+
+/// Controls the playback of an AnimRgba8 image.
+/// Frames whose duration is zero are gapless; they are
+/// skipped over during playback.
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub state: AnimationState,
+    /// The number of times to play through the frames.
+    /// 0 means that the animation loops forever.
+    pub loops: u32,
+    /// The 0-based frame that playback should jump to
+    pub current_frame: Option<usize>,
+    /// Incremented whenever the control is changed, so that
+    /// renderers know to re-evaluate their playback position
+    pub generation: usize,
+}
+
+impl Default for AnimationControl {
+    fn default() -> Self {
+        Self {
+            state: AnimationState::Running,
+            loops: 0,
+            current_frame: None,
+            generation: 0,
+        }
+    }
+}
+
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 #[derive(Debug)]
 pub struct ImageData {
     id: usize,
     data: Mutex<ImageDataType>,
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    animation: Mutex<AnimationControl>,
 }
 
 impl Eq for ImageData {}
@@ -386,6 +436,7 @@ impl ImageData {
         Self {
             id,
             data: Mutex::new(data),
+            animation: Mutex::new(AnimationControl::default()),
         }
     }
 
@@ -402,6 +453,10 @@ impl ImageData {
         self.data.lock().unwrap()
     }
 
+    pub fn animation(&self) -> MutexGuard<AnimationControl> {
+        self.animation.lock().unwrap()
+    }
+
     pub fn id(&self) -> usize {
         self.id
     }
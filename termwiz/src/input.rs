@@ -408,6 +408,30 @@ impl KeyCode {
                 }
             }
 
+            Function(n) if n > 12 && n <= 24 => {
+                // There are no dedicated sequences for F13-F24; xterm
+                // (and the terminfo entries for both xterm and wezterm)
+                // treats them as SHIFT+F1 through SHIFT+F12.
+                let encoded_mods = 1 + encode_modifiers(mods | Modifiers::SHIFT);
+                match n - 12 {
+                    k @ 1..=4 => write!(buf, "\x1b[1;{}{}", encoded_mods, (b'P' + k - 1) as char)?,
+                    k => {
+                        let intro = match k {
+                            5 => "\x1b[15",
+                            6 => "\x1b[17",
+                            7 => "\x1b[18",
+                            8 => "\x1b[19",
+                            9 => "\x1b[20",
+                            10 => "\x1b[21",
+                            11 => "\x1b[23",
+                            12 => "\x1b[24",
+                            _ => unreachable!(),
+                        };
+                        write!(buf, "{};{}~", intro, encoded_mods)?;
+                    }
+                }
+            }
+
             Function(n) => {
                 if mods.is_empty() && n < 5 {
                     // F1-F4 are encoded using SS3 if there are no modifiers
@@ -1541,4 +1565,34 @@ mod test {
             "\x1bOP".to_string()
         );
     }
+
+    #[test]
+    fn encode_high_function_keys() {
+        let mode = KeyCodeEncodeModes {
+            enable_csi_u_key_encoding: false,
+            newline_mode: false,
+            application_cursor_keys: false,
+        };
+
+        assert_eq!(
+            KeyCode::Function(13).encode(Modifiers::NONE, mode).unwrap(),
+            "\x1b[1;2P".to_string()
+        );
+        assert_eq!(
+            KeyCode::Function(16).encode(Modifiers::NONE, mode).unwrap(),
+            "\x1b[1;2S".to_string()
+        );
+        assert_eq!(
+            KeyCode::Function(17).encode(Modifiers::NONE, mode).unwrap(),
+            "\x1b[15;2~".to_string()
+        );
+        assert_eq!(
+            KeyCode::Function(24).encode(Modifiers::NONE, mode).unwrap(),
+            "\x1b[24;2~".to_string()
+        );
+        assert_eq!(
+            KeyCode::Function(13).encode(Modifiers::CTRL, mode).unwrap(),
+            "\x1b[1;6P".to_string()
+        );
+    }
 }
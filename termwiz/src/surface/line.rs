@@ -1,5 +1,7 @@
-use crate::cell::{Cell, CellAttributes};
+use crate::cell::{Blink, Cell, CellAttributes, Intensity, Underline};
 use crate::cellcluster::CellCluster;
+use crate::color::{ColorAttribute, ColorSpec};
+use crate::escape::csi::{Sgr, CSI};
 use crate::hyperlink::Rule;
 use crate::surface::{Change, SequenceNo, SEQ_ZERO};
 use bitflags::bitflags;
@@ -370,6 +372,37 @@ impl Line {
         s
     }
 
+    /// Like `as_str`, except that the attributes of the cells are
+    /// expressed as SGR escape sequences, and trailing blank cells
+    /// are omitted.  The attributes are reset at the end of the line.
+    pub fn as_escaped_str(&self) -> String {
+        let default = CellAttributes::default();
+        let is_blank =
+            |cell: &Cell| cell.str() == " " && cell.attrs().background() == ColorAttribute::Default;
+        let len = self
+            .cells
+            .iter()
+            .rposition(|cell| !is_blank(cell))
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+
+        let mut s = String::new();
+        let mut current = default.clone();
+        for (_, cell) in self.visible_cells().take_while(|(idx, _)| *idx < len) {
+            let mut attrs = cell.attrs().clone_sgr_only();
+            attrs.set_wrapped(false);
+            if attrs != current {
+                push_sgr(&attrs, &mut s);
+                current = attrs;
+            }
+            s.push_str(cell.str());
+        }
+        if current != default {
+            s.push_str(&CSI::Sgr(Sgr::Reset).to_string());
+        }
+        s
+    }
+
     pub fn split_off(&mut self, idx: usize) -> Self {
         let cells = self.cells.split_off(idx);
         Self {
@@ -642,6 +675,14 @@ impl Line {
         CellCluster::make_cluster(self.cells.len(), self.visible_cells())
     }
 
+    /// Returns an estimate of the number of bytes of memory that
+    /// are used to hold the line
+    pub fn estimated_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.cells.capacity() * std::mem::size_of::<Cell>()
+            + self.cells.iter().map(Cell::heap_size).sum::<usize>()
+    }
+
     pub fn cells(&self) -> &[Cell] {
         &self.cells
     }
@@ -758,6 +799,57 @@ impl Line {
     }
 }
 
+/// Appends the SGR sequences that switch from the default attributes
+/// to `attrs`
+fn push_sgr(attrs: &CellAttributes, s: &mut String) {
+    fn spec(color: ColorAttribute) -> ColorSpec {
+        match color {
+            ColorAttribute::TrueColorWithPaletteFallback(rgb, _)
+            | ColorAttribute::TrueColorWithDefaultFallback(rgb) => ColorSpec::TrueColor(rgb),
+            ColorAttribute::PaletteIndex(idx) => ColorSpec::PaletteIndex(idx),
+            ColorAttribute::Default => ColorSpec::Default,
+        }
+    }
+
+    let mut sgr = vec![Sgr::Reset];
+    if attrs.intensity() != Intensity::Normal {
+        sgr.push(Sgr::Intensity(attrs.intensity()));
+    }
+    if attrs.underline() != Underline::None {
+        sgr.push(Sgr::Underline(attrs.underline()));
+    }
+    if attrs.underline_color() != ColorAttribute::Default {
+        sgr.push(Sgr::UnderlineColor(spec(attrs.underline_color())));
+    }
+    if attrs.blink() != Blink::None {
+        sgr.push(Sgr::Blink(attrs.blink()));
+    }
+    if attrs.italic() {
+        sgr.push(Sgr::Italic(true));
+    }
+    if attrs.reverse() {
+        sgr.push(Sgr::Inverse(true));
+    }
+    if attrs.invisible() {
+        sgr.push(Sgr::Invisible(true));
+    }
+    if attrs.strikethrough() {
+        sgr.push(Sgr::StrikeThrough(true));
+    }
+    if attrs.overline() {
+        sgr.push(Sgr::Overline(true));
+    }
+    if attrs.foreground() != ColorAttribute::Default {
+        sgr.push(Sgr::Foreground(spec(attrs.foreground())));
+    }
+    if attrs.background() != ColorAttribute::Default {
+        sgr.push(Sgr::Background(spec(attrs.background())));
+    }
+    for sgr in sgr {
+        s.push_str(&CSI::Sgr(sgr).to_string());
+    }
+}
+
 impl<'a> From<&'a str> for Line {
     fn from(s: &str) -> Line {
         Line::from_text(s, &CellAttributes::default())
@@ -852,4 +944,23 @@ mod test {
         let r = line.compute_double_click_range(200, |_| true);
         assert_eq!(r, DoubleClickRange::Range(200..200));
     }
+
+    #[test]
+    fn escaped_str() {
+        let mut line: Line = "hello world   ".into();
+        let bold = CellAttributes::default()
+            .set_intensity(Intensity::Bold)
+            .set_foreground(crate::color::AnsiColor::Maroon)
+            .clone();
+        for idx in 6..11 {
+            line.cells_mut()[idx].attrs_mut().clone_from(&bold);
+        }
+        assert_eq!(
+            line.as_escaped_str(),
+            "hello \u{1b}[0m\u{1b}[1m\u{1b}[31mworld\u{1b}[0m"
+        );
+
+        let line: Line = "plain   ".into();
+        assert_eq!(line.as_escaped_str(), "plain");
+    }
 }
@@ -5,13 +5,18 @@ use anyhow::{anyhow, bail, Context};
 use async_ossl::AsyncSslStream;
 use async_trait::async_trait;
 use codec::*;
-use config::{configuration, SshBackend, SshDomain, TlsDomainClient, UnixDomain, UnixTarget};
+use config::{
+    configuration, AutoReconnect, PredictiveEcho, SshBackend, SshDomain, TlsDomainClient,
+    UnixDomain, UnixTarget,
+};
 use filedescriptor::FileDescriptor;
 use futures::FutureExt;
 use mux::connui::ConnectionUI;
 use mux::domain::{alloc_domain_id, DomainId};
 use mux::pane::PaneId;
 use mux::ssh::ssh_connect_with_ui;
+use mux::sshcli::SshCliRelayTarget;
+use mux::sshforward::SshForwards;
 use mux::Mux;
 use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
 use openssl::x509::X509;
@@ -26,7 +31,7 @@ use std::net::TcpStream;
 use std::path::Path;
 use std::path::PathBuf;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 enum ReaderMessage {
@@ -35,6 +40,9 @@ enum ReaderMessage {
         promise: Sender<anyhow::Result<Pdu>>,
     },
     Readable,
+    /// Requests have been waiting on the server for longer
+    /// than the timeout of the domain
+    TimedOut,
 }
 
 #[derive(Clone)]
@@ -43,6 +51,8 @@ pub struct Client {
     local_domain_id: DomainId,
     pub is_reconnectable: bool,
     pub is_local: bool,
+    pub predictive_echo: PredictiveEcho,
+    pub local_echo_threshold_ms: u64,
 }
 
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
@@ -176,6 +186,31 @@ enum NotReconnectableError {
     ClientWasDestroyed,
 }
 
+/// Returns a value in the range 0.0..1.0 for spreading out reconnect
+/// attempts.  It only needs to differ between clients, so the clock
+/// is a good enough source.
+fn jitter() -> f64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    f64::from(nanos % 1_000_000) / 1_000_000.0
+}
+
+/// Returns true if the domain has panes that are still alive.
+/// Must not be called on the main thread.
+fn domain_has_live_panes(local_domain_id: DomainId) -> bool {
+    promise::spawn::block_on(promise::spawn::spawn_into_main_thread(async move {
+        match Mux::get() {
+            Some(mux) => mux
+                .iter_panes()
+                .iter()
+                .any(|pane| pane.domain_id() == local_domain_id && !pane.is_dead()),
+            None => false,
+        }
+    }))
+}
+
 fn client_thread(
     reconnectable: &mut Reconnectable,
     local_domain_id: DomainId,
@@ -193,6 +228,8 @@ async fn client_thread_async(
 
     struct Promises {
         map: HashMap<u64, Sender<anyhow::Result<Pdu>>>,
+        /// When the map last went from empty to non-empty
+        oldest: Instant,
     }
 
     impl Promises {
@@ -211,55 +248,123 @@ async fn client_thread_async(
     }
     let mut promises = Promises {
         map: HashMap::new(),
+        oldest: Instant::now(),
     };
 
     let mut stream = reconnectable.take_stream().unwrap();
+    let timeout = reconnectable.timeout();
+    let mut last_read = Instant::now();
+
+    // Introduce ourselves, asking for compression if it is wanted.
+    // The PDUs are uncompressed until the server agrees to it, which
+    // servers that don't know about ClientHello never do.
+    // Delta line updates are always asked for: they arrive as a PDU
+    // of their own, which the panes can apply whenever it is sent.
+    let mut compression = Compression::default();
+    let mut hello_serial = Some(next_serial);
+    next_serial += 1;
+    Pdu::ClientHello(ClientHello {
+        username: config::username_from_env().unwrap_or_default(),
+        hostname: hostname::get()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        pid: std::process::id(),
+        compression: reconnectable.compression(),
+        delta_lines: true,
+    })
+    .encode_async_with(&mut stream, hello_serial.unwrap(), &mut compression)
+    .await
+    .context("encoding ClientHello")?;
+    stream.flush().await.context("flushing PDU to server")?;
 
     loop {
         let rx_msg = rx.recv();
         let wait_for_read = stream
             .wait_for_readable()
             .map(|_| Ok(ReaderMessage::Readable));
+        // A connection that has silently gone away, rather than
+        // being closed, shows up as requests that never get a reply
+        let timeout_deadline = match timeout {
+            Some(timeout) if !promises.map.is_empty() => {
+                Some(last_read.max(promises.oldest) + timeout)
+            }
+            _ => None,
+        };
+        let timed_out = async move {
+            match timeout_deadline {
+                Some(deadline) => {
+                    smol::Timer::at(deadline).await;
+                    Ok(ReaderMessage::TimedOut)
+                }
+                None => futures::future::pending().await,
+            }
+        };
 
-        match smol::future::or(rx_msg, wait_for_read).await {
+        match smol::future::or(smol::future::or(rx_msg, wait_for_read), timed_out).await {
             Ok(ReaderMessage::SendPdu { pdu, promise }) => {
                 let serial = next_serial;
                 next_serial += 1;
+                if promises.map.is_empty() {
+                    promises.oldest = Instant::now();
+                }
                 promises.map.insert(serial, promise);
 
-                pdu.encode_async(&mut stream, serial)
+                pdu.encode_async_with(&mut stream, serial, &mut compression)
                     .await
                     .context("encoding a PDU to send to the server")?;
                 stream.flush().await.context("flushing PDU to server")?;
             }
-            Ok(ReaderMessage::Readable) => match Pdu::decode_async(&mut stream).await {
-                Ok(decoded) => {
-                    log::trace!("decoded serial {}", decoded.serial);
-                    if decoded.serial == 0 {
-                        process_unilateral(local_domain_id, decoded)
-                            .context("processing unilateral PDU from server")
-                            .map_err(|e| {
-                                log::error!("process_unilateral: {:?}", e);
-                                e
-                            })?;
-                    } else if let Some(promise) = promises.map.remove(&decoded.serial) {
-                        if promise.try_send(Ok(decoded.pdu)).is_err() {
-                            return Err(NotReconnectableError::ClientWasDestroyed.into());
+            Ok(ReaderMessage::Readable) => {
+                match Pdu::decode_async_with(&mut stream, &mut compression).await {
+                    Ok(decoded) => {
+                        log::trace!("decoded serial {}", decoded.serial);
+                        last_read = Instant::now();
+                        if hello_serial == Some(decoded.serial) {
+                            hello_serial.take();
+                            match decoded.pdu {
+                                Pdu::ClientHelloResponse(ClientHelloResponse {
+                                    compression: true,
+                                    ..
+                                }) => {
+                                    compression.enable();
+                                }
+                                Pdu::ClientHelloResponse(_) => {}
+                                pdu => log::debug!("server doesn't know ClientHello: {:?}", pdu),
+                            }
+                        } else if decoded.serial == 0 {
+                            process_unilateral(local_domain_id, decoded)
+                                .context("processing unilateral PDU from server")
+                                .map_err(|e| {
+                                    log::error!("process_unilateral: {:?}", e);
+                                    e
+                                })?;
+                        } else if let Some(promise) = promises.map.remove(&decoded.serial) {
+                            if promise.try_send(Ok(decoded.pdu)).is_err() {
+                                return Err(NotReconnectableError::ClientWasDestroyed.into());
+                            }
+                        } else {
+                            let reason =
+                                format!("got serial {:?} without a corresponding promise", decoded);
+                            promises.fail_all(&reason);
+                            anyhow::bail!("{}", reason);
                         }
-                    } else {
-                        let reason =
-                            format!("got serial {:?} without a corresponding promise", decoded);
+                    }
+                    Err(err) => {
+                        let reason = format!("Error while decoding response pdu: {:#}", err);
+                        log::error!("{}", reason);
                         promises.fail_all(&reason);
-                        anyhow::bail!("{}", reason);
+                        return Err(err).context("Error while decoding response pdu");
                     }
                 }
-                Err(err) => {
-                    let reason = format!("Error while decoding response pdu: {:#}", err);
-                    log::error!("{}", reason);
-                    promises.fail_all(&reason);
-                    return Err(err).context("Error while decoding response pdu");
-                }
-            },
+            }
+            Ok(ReaderMessage::TimedOut) => {
+                let reason = format!(
+                    "no response from the server for {:?}",
+                    timeout.unwrap_or_default()
+                );
+                promises.fail_all(&reason);
+                anyhow::bail!("{}", reason);
+            }
             Err(_) => {
                 return Err(NotReconnectableError::ClientWasDestroyed.into());
             }
@@ -369,6 +474,9 @@ struct Reconnectable {
     config: ClientDomainConfig,
     stream: Option<Box<dyn AsyncReadAndWrite>>,
     tls_creds: Option<GetTlsCredsResponse>,
+    /// The port forwards of an ssh domain, which live
+    /// for as long as the connection
+    ssh_forwards: Option<SshForwards>,
 }
 
 struct SshStream {
@@ -417,6 +525,7 @@ impl Reconnectable {
             config,
             stream,
             tls_creds: None,
+            ssh_forwards: None,
         }
     }
 
@@ -438,23 +547,62 @@ impl Reconnectable {
         self.stream.take()
     }
 
+    /// Whether to ask the server to compress the PDUs
+    fn compression(&self) -> bool {
+        match &self.config {
+            ClientDomainConfig::Unix(unix) => unix.compression,
+            ClientDomainConfig::Tls(tls) => tls.compression,
+            ClientDomainConfig::Ssh(ssh) => ssh.compression,
+        }
+    }
+
+    fn predictive_echo(&self) -> PredictiveEcho {
+        match &self.config {
+            ClientDomainConfig::Unix(unix) => unix.predictive_echo,
+            ClientDomainConfig::Tls(tls) => tls.predictive_echo,
+            ClientDomainConfig::Ssh(ssh) => ssh.predictive_echo,
+        }
+    }
+
+    fn local_echo_threshold_ms(&self) -> u64 {
+        match &self.config {
+            ClientDomainConfig::Unix(unix) => unix.local_echo_threshold_ms,
+            ClientDomainConfig::Tls(tls) => tls.local_echo_threshold_ms,
+            ClientDomainConfig::Ssh(ssh) => ssh.local_echo_threshold_ms,
+        }
+    }
+
     fn is_local(&mut self) -> bool {
         matches!(&self.config, ClientDomainConfig::Unix(_))
     }
 
-    fn reconnectable(&mut self) -> bool {
+    fn auto_reconnect(&self) -> AutoReconnect {
         match &self.config {
             // It doesn't make sense to reconnect to a unix socket; we only
             // get disconnected it it dies, so respawning it would not preserve
             // the set of tabs and we'd have confusing and inconsistent state
-            ClientDomainConfig::Unix(_) => false,
-            ClientDomainConfig::Tls(_) => true,
-            // It *does* make sense to reconnect with an ssh session, but we
-            // need to grow some smarts about whether the disconnect was because
-            // we sent CTRL-D to close the last session, or whether it was a network
-            // level disconnect, because we will otherwise throw up authentication
-            // dialogs that would be annoying
-            ClientDomainConfig::Ssh(_) => false,
+            ClientDomainConfig::Unix(_) => AutoReconnect::disabled(),
+            ClientDomainConfig::Tls(tls) => tls.auto_reconnect.clone().unwrap_or_default(),
+            // Reconnecting an ssh session may throw up authentication
+            // dialogs that would be annoying, so the user has to opt in
+            ClientDomainConfig::Ssh(ssh) => ssh
+                .auto_reconnect
+                .clone()
+                .unwrap_or_else(AutoReconnect::disabled),
+        }
+    }
+
+    fn reconnectable(&mut self) -> bool {
+        self.auto_reconnect().enabled
+    }
+
+    /// How long to wait for a reply from the server before deciding
+    /// that the connection has been lost
+    fn timeout(&self) -> Option<Duration> {
+        match &self.config {
+            ClientDomainConfig::Unix(_) => None,
+            ClientDomainConfig::Tls(tls) => Some(tls.read_timeout),
+            ClientDomainConfig::Ssh(ssh) => Some(ssh.timeout),
         }
     }
 
@@ -519,7 +667,8 @@ impl Reconnectable {
             .to_string(),
         );
         for (k, v) in &ssh_dom.ssh_option {
-            ssh_config.insert(k.to_string(), v.to_string());
+            // ssh_config keywords are case insensitive
+            ssh_config.insert(k.to_lowercase(), v.to_string());
         }
 
         if let Some(username) = &ssh_dom.username {
@@ -531,8 +680,23 @@ impl Reconnectable {
         if ssh_dom.no_agent_auth {
             ssh_config.insert("identitiesonly".to_string(), "yes".to_string());
         }
+        if ssh_dom.forward_agent {
+            ssh_config.insert("forwardagent".to_string(), "yes".to_string());
+        }
+        if let Some(interval) = ssh_dom.server_alive_interval {
+            ssh_config.insert("serveraliveinterval".to_string(), interval.to_string());
+        }
+        if let Some(count) = ssh_dom.server_alive_count_max {
+            ssh_config.insert("serveralivecountmax".to_string(), count.to_string());
+        }
+
+        // Release the ports of the previous connection before
+        // setting up the forwards again
+        self.ssh_forwards.take();
 
         let sess = ssh_connect_with_ui(ssh_config, ui)?;
+        self.ssh_forwards
+            .replace(SshForwards::establish(&ssh_dom, &sess, ui));
         let proxy_bin = Self::wezterm_bin_path(&ssh_dom.remote_wezterm_path);
 
         let cmd = if initial {
@@ -651,6 +815,15 @@ impl Reconnectable {
     ) -> anyhow::Result<()> {
         openssl::init();
 
+        // Use the latest settings of the domain, so that a reconnect
+        // picks up a client certificate that has been moved or rotated
+        let tls_client = configuration()
+            .tls_clients
+            .iter()
+            .find(|dom| dom.name == tls_client.name)
+            .cloned()
+            .unwrap_or(tls_client);
+
         let remote_address = &tls_client.remote_address;
 
         let remote_host_name = remote_address.split(':').next().ok_or_else(|| {
@@ -688,6 +861,9 @@ impl Reconnectable {
                         "Failed to reuse creds: {:?}\nWill retry bootstrap via SSH\n",
                         err
                     ));
+                    // The creds may have expired or the server may have
+                    // restarted with a new CA, so obtain fresh ones
+                    self.tls_creds.take();
                 }
             }
         }
@@ -878,60 +1054,106 @@ impl Client {
     fn new(local_domain_id: DomainId, mut reconnectable: Reconnectable) -> Self {
         let is_reconnectable = reconnectable.reconnectable();
         let is_local = reconnectable.is_local();
+        let predictive_echo = reconnectable.predictive_echo();
+        let local_echo_threshold_ms = reconnectable.local_echo_threshold_ms();
         let (sender, mut receiver) = unbounded();
 
         thread::spawn(move || {
-            const BASE_INTERVAL: Duration = Duration::from_secs(1);
-            const MAX_INTERVAL: Duration = Duration::from_secs(10);
-
-            let mut backoff = BASE_INTERVAL;
-            loop {
+            'client: loop {
                 if let Err(e) = client_thread(&mut reconnectable, local_domain_id, &mut receiver) {
-                    if !reconnectable.reconnectable() {
+                    let settings = reconnectable.auto_reconnect();
+                    if !settings.enabled {
                         log::debug!("client thread ended: {}", e);
                         break;
                     }
 
                     if let Some(ioerr) = e.root_cause().downcast_ref::<std::io::Error>() {
                         if let std::io::ErrorKind::UnexpectedEof = ioerr.kind() {
-                            // Don't reconnect for a simple EOF
-                            log::error!("server closed connection ({})", e);
-                            break;
+                            // The server closes the connection when its last
+                            // pane is closed, which is not worth reconnecting for
+                            if !domain_has_live_panes(local_domain_id) {
+                                log::error!("server closed connection ({})", e);
+                                break;
+                            }
                         }
                     }
 
                     if let Some(err) = e.root_cause().downcast_ref::<NotReconnectableError>() {
                         log::error!("{}; won't try to reconnect", err);
+                        if *err == NotReconnectableError::ClientWasDestroyed {
+                            // The domain has already let go of this client,
+                            // and may since have attached with another one
+                            return;
+                        }
                         break;
                     }
 
                     let mut ui = ConnectionUI::new();
                     ui.title("wezterm: Reconnecting...");
 
+                    let mut attempt = 0;
                     loop {
+                        attempt += 1;
+                        if settings.exhausted(attempt) {
+                            log::error!(
+                                "{}: giving up after {} attempts to reconnect",
+                                reconnectable.config.name(),
+                                settings.max_attempts
+                            );
+                            ui.output_str(&format!(
+                                "Giving up after {} attempts to reconnect\n",
+                                settings.max_attempts
+                            ));
+                            break 'client;
+                        }
+
+                        let delay = settings.delay_for_attempt(attempt, jitter());
+                        let deadline = Instant::now() + delay;
+                        promise::spawn::spawn_into_main_thread(async move {
+                            ClientDomain::set_reconnecting(
+                                local_domain_id,
+                                Some((attempt, deadline)),
+                            );
+                        })
+                        .detach();
+                        log::warn!(
+                            "{}: client disconnected {}; reconnect attempt {} in {:?}",
+                            reconnectable.config.name(),
+                            e,
+                            attempt,
+                            delay
+                        );
                         ui.sleep_with_reason(
-                            &format!("client disconnected {}; will reconnect", e),
-                            backoff,
+                            &format!("client disconnected {}; reconnect attempt {}", e, attempt),
+                            delay,
                         )
                         .ok();
                         let initial = false;
                         let no_auto_start = true; // Don't auto-start on a reconnect
                         match reconnectable.connect(initial, &mut ui, no_auto_start) {
                             Ok(_) => {
-                                backoff = BASE_INTERVAL;
-                                log::error!("Reconnected!");
+                                log::warn!(
+                                    "{}: reconnected after {} attempts",
+                                    reconnectable.config.name(),
+                                    attempt
+                                );
                                 promise::spawn::spawn_into_main_thread(async move {
-                                    ClientDomain::reattach(local_domain_id, ui).await.ok();
+                                    ClientDomain::set_reconnecting(local_domain_id, None);
+                                    ClientDomain::reattach(local_domain_id, attempt, ui)
+                                        .await
+                                        .ok();
                                 })
                                 .detach();
                                 break;
                             }
                             Err(err) => {
-                                backoff = (backoff + backoff).min(MAX_INTERVAL);
-                                ui.output_str(&format!(
-                                    "problem reconnecting: {}; will reconnect in {:?}\n",
-                                    err, backoff
-                                ));
+                                log::warn!(
+                                    "{}: reconnect attempt {} failed: {:#}",
+                                    reconnectable.config.name(),
+                                    attempt,
+                                    err
+                                );
+                                ui.output_str(&format!("problem reconnecting: {}\n", err));
                             }
                         }
                     }
@@ -967,6 +1189,8 @@ impl Client {
             local_domain_id,
             is_reconnectable,
             is_local,
+            predictive_echo,
+            local_echo_threshold_ms,
         }
     }
 
@@ -1016,6 +1240,14 @@ impl Client {
     ) -> anyhow::Result<Self> {
         let config = configuration();
 
+        // In the panes of an ssh domain, the mux is reached
+        // through the relay of that domain instead
+        if std::env::var_os("WEZTERM_UNIX_SOCKET").is_none() {
+            if let Some(relay) = SshCliRelayTarget::from_env() {
+                return Self::new_ssh_cli_relay(alloc_domain_id(), &relay, ui);
+            }
+        }
+
         let unix_dom = match std::env::var_os("WEZTERM_UNIX_SOCKET") {
             Some(path) => config::UnixDomain {
                 socket_path: Some(path.into()),
@@ -1033,7 +1265,46 @@ impl Client {
                 .clone(),
         };
 
-        Self::new_unix_domain(alloc_domain_id(), &unix_dom, initial, ui, no_auto_start)
+        Self::new_unix_domain(alloc_domain_id(), &unix_dom, initial, ui, no_auto_start).map_err(
+            |err| {
+                if std::env::var_os("WEZTERM_PANE").is_some()
+                    && std::env::var_os("WEZTERM_UNIX_SOCKET").is_none()
+                {
+                    // Most likely a pane of an ssh domain whose relay
+                    // couldn't be set up, or whose variables were not
+                    // accepted by the ssh server
+                    err.context(format!(
+                        "WEZTERM_PANE is set but neither WEZTERM_UNIX_SOCKET nor {} are. \
+                         If this is a pane of `wezterm ssh`, the ssh server must allow \
+                         TCP forwarding and accept the WEZTERM_* environment variables \
+                         (AcceptEnv WEZTERM_*) for wezterm cli to reach the mux",
+                        mux::sshcli::ADDRESS_ENV
+                    ))
+                } else {
+                    err
+                }
+            },
+        )
+    }
+
+    /// Connects to the mux of the wezterm whose ssh domain the current
+    /// pane belongs to, through the relay of that domain
+    pub fn new_ssh_cli_relay(
+        local_domain_id: DomainId,
+        relay: &SshCliRelayTarget,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<Self> {
+        ui.output_str(&format!("Connect to {} via ssh\n", relay.address()));
+        let stream = relay.connect()?;
+        let stream: Box<dyn AsyncReadAndWrite> = Box::new(Async::new(stream)?);
+        // The relay ends at the unix socket of the mux, so this
+        // behaves like a unix domain, which is never reconnected
+        let unix_dom = UnixDomain {
+            no_serve_automatically: true,
+            ..Default::default()
+        };
+        let reconnectable = Reconnectable::new(ClientDomainConfig::Unix(unix_dom), Some(stream));
+        Ok(Self::new(local_domain_id, reconnectable))
     }
 
     pub fn new_unix_domain(
@@ -1091,6 +1362,24 @@ impl Client {
     rpc!(mouse_event, SendMouseEvent, UnitResponse);
     rpc!(resize, Resize, UnitResponse);
     rpc!(set_zoomed, SetPaneZoomed, UnitResponse);
+    rpc!(rearrange_panes, RearrangePanes, UnitResponse);
+    rpc!(
+        move_pane_to_new_tab,
+        MovePaneToNewTab,
+        MovePaneToNewTabResponse
+    );
+    rpc!(move_pane, MovePane, UnitResponse);
+    rpc!(move_tab_to_window, MoveTabToWindow, MoveTabToWindowResponse);
+    rpc!(get_pane_text, GetPaneText, GetPaneTextResponse);
+    rpc!(activate_pane, ActivatePane, ActivatePaneResponse);
+    rpc!(
+        activate_pane_direction,
+        ActivatePaneDirection,
+        ActivatePaneResponse
+    );
+    rpc!(activate_tab, ActivateTab, ActivatePaneResponse);
+    rpc!(save_session, SaveSession, SaveSessionResponse);
+    rpc!(restore_session, RestoreSession, UnitResponse);
     rpc!(
         get_tab_render_changes,
         GetPaneRenderChanges,
@@ -1105,4 +1394,43 @@ impl Client {
         SearchScrollbackResponse
     );
     rpc!(kill_pane, KillPane, UnitResponse);
+    rpc!(list_clients, ListClients = (), ListClientsResponse);
+    rpc!(
+        list_ssh_forwards,
+        ListSshForwards = (),
+        ListSshForwardsResponse
+    );
+    rpc!(
+        list_tls_certificates,
+        ListTlsCertificates = (),
+        ListTlsCertificatesResponse
+    );
+
+    /// Like the `rpc!` methods, but recognizes the error produced by
+    /// servers that predate this request, as the user will otherwise
+    /// be confronted with a cryptic message about an invalid PDU.
+    pub async fn get_log_entries(
+        &self,
+        pdu: GetLogEntries,
+    ) -> anyhow::Result<GetLogEntriesResponse> {
+        let start = std::time::Instant::now();
+        let result = self.send_pdu(Pdu::GetLogEntries(pdu)).await;
+        let elapsed = start.elapsed();
+        metrics::histogram!("rpc", elapsed, "method" => "get_log_entries");
+        match result {
+            Ok(Pdu::GetLogEntriesResponse(res)) => Ok(res),
+            Ok(Pdu::ErrorResponse(ErrorResponse { reason }))
+                if reason.starts_with("invalid PDU") =>
+            {
+                bail!(
+                    "the server is too old to support retrieving its log entries; \
+                     please install the same version of wezterm ({}) on both the \
+                     client and server",
+                    config::wezterm_version()
+                )
+            }
+            Ok(_) => bail!("unexpected response {:?}", result),
+            Err(err) => Err(err),
+        }
+    }
 }
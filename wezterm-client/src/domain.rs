@@ -2,21 +2,23 @@ use crate::client::Client;
 use crate::pane::ClientPane;
 use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use codec::{ListPanesResponse, Spawn, SplitPane};
-use config::keyassignment::SpawnTabDomain;
+use codec::{ListPanesResponse, MovePane, MovePaneToNewTab, MoveTabToWindow, Spawn, SplitPane};
+use config::keyassignment::{PaneDirection, SpawnTabDomain};
 use config::{SshDomain, TlsDomainClient, UnixDomain};
 use mux::connui::ConnectionUI;
 use mux::domain::{alloc_domain_id, Domain, DomainId, DomainState};
 use mux::pane::{Pane, PaneId};
 use mux::tab::{SplitDirection, Tab, TabId};
 use mux::window::WindowId;
-use mux::Mux;
+use mux::{Mux, MuxNotification};
 use portable_pty::{CommandBuilder, PtySize};
 use promise::spawn::spawn_into_new_thread;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 pub struct ClientInner {
     pub client: Client,
@@ -25,9 +27,17 @@ pub struct ClientInner {
     remote_to_local_window: Mutex<HashMap<WindowId, WindowId>>,
     remote_to_local_tab: Mutex<HashMap<TabId, TabId>>,
     remote_to_local_pane: Mutex<HashMap<PaneId, PaneId>>,
+    /// Set once the domain has let go of this client, so that the
+    /// panes that are removed from the local mux as a consequence
+    /// are left running on the server
+    detached: AtomicBool,
 }
 
 impl ClientInner {
+    pub fn is_detached(&self) -> bool {
+        self.detached.load(Ordering::Relaxed)
+    }
+
     fn remote_to_local_window(&self, remote_window_id: WindowId) -> Option<WindowId> {
         let map = self.remote_to_local_window.lock().unwrap();
         map.get(&remote_window_id).cloned()
@@ -107,6 +117,16 @@ impl ClientInner {
         None
     }
 
+    fn local_to_remote_tab_id(&self, local_tab_id: TabId) -> Option<TabId> {
+        let map = self.remote_to_local_tab.lock().unwrap();
+        for (remote, local) in map.iter() {
+            if *local == local_tab_id {
+                return Some(*remote);
+            }
+        }
+        None
+    }
+
     pub fn is_local(&self) -> bool {
         self.client.is_local
     }
@@ -165,6 +185,7 @@ impl ClientInner {
             remote_to_local_window: Mutex::new(HashMap::new()),
             remote_to_local_tab: Mutex::new(HashMap::new()),
             remote_to_local_pane: Mutex::new(HashMap::new()),
+            detached: AtomicBool::new(false),
         }
     }
 }
@@ -174,6 +195,9 @@ pub struct ClientDomain {
     label: String,
     inner: RefCell<Option<Arc<ClientInner>>>,
     local_domain_id: DomainId,
+    /// The current reconnect attempt, counting from 1, and the
+    /// time at which it will be made
+    reconnecting: RefCell<Option<(usize, Instant)>>,
 }
 
 impl ClientDomain {
@@ -185,6 +209,7 @@ impl ClientDomain {
             label,
             inner: RefCell::new(None),
             local_domain_id,
+            reconnecting: RefCell::new(None),
         }
     }
 
@@ -194,16 +219,64 @@ impl ClientDomain {
 
     pub fn perform_detach(&self) {
         log::error!("detached domain {}", self.local_domain_id);
-        self.inner.borrow_mut().take();
+        if let Some(inner) = self.inner.borrow_mut().take() {
+            inner.detached.store(true, Ordering::Relaxed);
+        }
+        self.reconnecting.borrow_mut().take();
         let mux = Mux::get().unwrap();
         mux.domain_was_detached(self.local_domain_id);
     }
 
+    /// Records which attempt the client is making to reconnect to the
+    /// server and when it will make it, or that it is no longer trying
+    /// to reconnect
+    pub fn set_reconnecting(domain_id: DomainId, reconnecting: Option<(usize, Instant)>) {
+        let mux = Mux::get().unwrap();
+        let domain = match mux.get_domain(domain_id) {
+            Some(domain) => domain,
+            None => return,
+        };
+        let domain = match domain.downcast_ref::<Self>() {
+            Some(domain) => domain,
+            None => return,
+        };
+        if domain.inner.borrow().is_none() {
+            return;
+        }
+        let changed = domain.reconnecting.borrow().map(|(attempt, _)| attempt)
+            != reconnecting.map(|(attempt, _)| attempt);
+        *domain.reconnecting.borrow_mut() = reconnecting;
+        if changed {
+            mux.notify(MuxNotification::DomainStateChanged(domain_id));
+        }
+    }
+
+    /// Returns the reconnect attempt that the client is currently
+    /// making, counting from 1, or None if it is connected
+    pub fn reconnect_attempt(&self) -> Option<usize> {
+        self.reconnecting.borrow().map(|(attempt, _)| attempt)
+    }
+
     pub fn remote_to_local_pane_id(&self, remote_pane_id: TabId) -> Option<TabId> {
         let inner = self.inner()?;
         inner.remote_to_local_pane_id(remote_pane_id)
     }
 
+    /// Removes the pane from its local tab, without killing it, so that
+    /// the next resync can place it wherever the server has moved it.
+    /// The tab is left for the mux to remove if it is now empty.
+    fn remove_pane_from_local_tab(&self, pane_id: PaneId) -> anyhow::Result<()> {
+        let mux = Mux::get().unwrap();
+        let (_domain_id, _window_id, tab_id) = mux
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane_id {} is invalid", pane_id))?;
+        let tab = mux
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab_id {} is invalid", tab_id))?;
+        tab.remove_pane(pane_id);
+        Ok(())
+    }
+
     pub fn get_client_inner_for_domain(domain_id: DomainId) -> anyhow::Result<Arc<ClientInner>> {
         let mux = Mux::get().unwrap();
         let domain = mux
@@ -224,13 +297,37 @@ impl ClientDomain {
     /// more tabs at the time that a disconnect was detected, and
     /// it's also possible that another client connected and adjusted
     /// the set of tabs since we were connected, so we need to re-sync.
-    pub async fn reattach(domain_id: DomainId, ui: ConnectionUI) -> anyhow::Result<()> {
+    /// `attempts` is the number of attempts that it took to reconnect.
+    pub async fn reattach(
+        domain_id: DomainId,
+        attempts: usize,
+        ui: ConnectionUI,
+    ) -> anyhow::Result<()> {
         let inner = Self::get_client_inner_for_domain(domain_id)?;
 
         let panes = inner.client.list_panes().await?;
         Self::process_pane_list(inner, panes)?;
 
+        let mux = Mux::get().unwrap();
+        for pane in mux.iter_panes() {
+            if pane.domain_id() != domain_id {
+                continue;
+            }
+            if let Some(client_pane) = pane.downcast_ref::<ClientPane>() {
+                client_pane
+                    .renderable
+                    .borrow()
+                    .inner
+                    .borrow_mut()
+                    .reconnected();
+            }
+        }
+
         ui.close();
+        mux.notify(MuxNotification::DomainReconnected {
+            domain_id,
+            attempts,
+        });
         Ok(())
     }
 
@@ -278,7 +375,13 @@ impl ClientDomain {
                 tab.sync_with_pane_tree(root_size, tabroot, |entry| {
                     if let Some(pane_id) = inner.remote_to_local_pane_id(entry.pane_id) {
                         match mux.get_pane(pane_id) {
-                            Some(pane) => pane,
+                            Some(pane) => {
+                                // The pane may have been moved to another tab
+                                if let Some(client_pane) = pane.downcast_ref::<ClientPane>() {
+                                    client_pane.remote_tab_id.set(entry.tab_id);
+                                }
+                                pane
+                            }
                             None => {
                                 // We likely decided that we hit EOF on the tab and
                                 // removed it from the mux.  Let's add it back, but
@@ -346,6 +449,7 @@ impl ClientDomain {
 
         let inner = Arc::new(ClientInner::new(domain_id, client));
         *domain.inner.borrow_mut() = Some(Arc::clone(&inner));
+        mux.notify(MuxNotification::DomainStateChanged(domain_id));
 
         Self::process_pane_list(inner, panes)?;
 
@@ -353,6 +457,17 @@ impl ClientDomain {
     }
 }
 
+fn remote_pane_id(pane_id: PaneId) -> anyhow::Result<PaneId> {
+    let mux = Mux::get().unwrap();
+    let pane = mux
+        .get_pane(pane_id)
+        .ok_or_else(|| anyhow!("pane_id {} is invalid", pane_id))?;
+    let pane = pane
+        .downcast_ref::<ClientPane>()
+        .ok_or_else(|| anyhow!("pane_id {} is not a ClientPane", pane_id))?;
+    Ok(pane.remote_pane_id())
+}
+
 #[async_trait(?Send)]
 impl Domain for ClientDomain {
     fn domain_id(&self) -> DomainId {
@@ -469,6 +584,111 @@ impl Domain for ClientDomain {
         Ok(pane)
     }
 
+    async fn move_pane_to_new_tab(
+        &self,
+        pane_id: PaneId,
+        window_id: Option<WindowId>,
+    ) -> anyhow::Result<Option<(Rc<Tab>, WindowId)>> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        let mux = Mux::get().unwrap();
+        let remote_pane_id = remote_pane_id(pane_id)?;
+
+        let result = inner
+            .client
+            .move_pane_to_new_tab(MovePaneToNewTab {
+                pane_id: remote_pane_id,
+                window_id: window_id.and_then(|w| inner.local_to_remote_window(w)),
+            })
+            .await?;
+
+        self.remove_pane_from_local_tab(pane_id)?;
+        if let Some(window_id) = window_id {
+            if inner.local_to_remote_window(window_id).is_none() {
+                inner.record_remote_to_local_window_mapping(result.window_id, window_id);
+            }
+        }
+        self.resync().await?;
+
+        let tab = inner
+            .remote_to_local_tab_id(result.tab_id)
+            .and_then(|tab_id| mux.get_tab(tab_id))
+            .ok_or_else(|| anyhow!("remote tab {} was not synced", result.tab_id))?;
+        let window_id = inner
+            .remote_to_local_window(result.window_id)
+            .ok_or_else(|| anyhow!("remote window {} was not synced", result.window_id))?;
+        Ok(Some((tab, window_id)))
+    }
+
+    async fn move_pane_to_tab(
+        &self,
+        pane_id: PaneId,
+        tab_id: TabId,
+        direction: PaneDirection,
+    ) -> anyhow::Result<bool> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        let remote_tab_id = inner
+            .local_to_remote_tab_id(tab_id)
+            .ok_or_else(|| anyhow!("tab {} has no remote counterpart", tab_id))?;
+        let remote_pane_id = remote_pane_id(pane_id)?;
+
+        inner
+            .client
+            .move_pane(MovePane {
+                pane_id: remote_pane_id,
+                tab_id: remote_tab_id,
+                direction,
+            })
+            .await?;
+
+        self.remove_pane_from_local_tab(pane_id)?;
+        self.resync().await?;
+
+        Ok(true)
+    }
+
+    async fn move_tab_to_window(
+        &self,
+        tab_id: TabId,
+        window_id: Option<WindowId>,
+    ) -> anyhow::Result<Option<WindowId>> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        let mux = Mux::get().unwrap();
+        let remote_tab_id = inner
+            .local_to_remote_tab_id(tab_id)
+            .ok_or_else(|| anyhow!("tab {} has no remote counterpart", tab_id))?;
+
+        let result = inner
+            .client
+            .move_tab_to_window(MoveTabToWindow {
+                tab_id: remote_tab_id,
+                window_id: window_id.and_then(|w| inner.local_to_remote_window(w)),
+            })
+            .await?;
+
+        // The server may have placed the tab into a window that is
+        // already known to us, which is not necessarily the one that
+        // was requested if that has no remote counterpart
+        let window_id = inner.remote_to_local_window(result.window_id).or(window_id);
+        let tab = mux
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+        let old_window_id = mux
+            .window_containing_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} is not in any window", tab_id))?;
+        let window_id = mux.reparent_tab(&tab, old_window_id, window_id)?;
+        if inner.remote_to_local_window(result.window_id).is_none() {
+            inner.record_remote_to_local_window_mapping(result.window_id, window_id);
+        }
+
+        Ok(Some(window_id))
+    }
+
     async fn attach(&self) -> anyhow::Result<()> {
         let domain_id = self.local_domain_id;
         let config = self.config.clone();
@@ -519,11 +739,26 @@ impl Domain for ClientDomain {
         ui.output_str("Attached!\n");
         drop(activity);
         ui.close();
+
+        // Recreate any tabs of a restored session that were waiting
+        // for this domain to be attached
+        promise::spawn::spawn(async move {
+            if let Err(err) = mux::session::restore_pending_session(domain_id).await {
+                log::error!("session restore: {:#}", err);
+            }
+        })
+        .detach();
         Ok(())
     }
 
+    /// Removes the panes of this domain from the local mux, leaving
+    /// them running on the server so that they can be attached to again
     fn detach(&self) -> anyhow::Result<()> {
-        bail!("detach not implemented");
+        if self.inner.borrow().is_none() {
+            bail!("domain {} is not attached", self.config.name());
+        }
+        self.perform_detach();
+        Ok(())
     }
 
     fn state(&self) -> DomainState {
@@ -533,4 +768,8 @@ impl Domain for ClientDomain {
             DomainState::Detached
         }
     }
+
+    fn reconnect_deadline(&self) -> Option<Instant> {
+        self.reconnecting.borrow().map(|(_, deadline)| deadline)
+    }
 }
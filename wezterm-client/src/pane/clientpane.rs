@@ -1,4 +1,4 @@
-use crate::domain::ClientInner;
+use crate::domain::{ClientDomain, ClientInner};
 use crate::pane::mousestate::MouseState;
 use crate::pane::renderable::{RenderableInner, RenderableState};
 use anyhow::bail;
@@ -9,13 +9,13 @@ use filedescriptor::Pipe;
 use mux::domain::DomainId;
 use mux::pane::{alloc_pane_id, Pane, PaneId, Pattern, SearchResult};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
-use mux::tab::TabId;
+use mux::tab::{PaneArrangement, TabId};
 use mux::{Mux, MuxNotification};
-use portable_pty::PtySize;
+use portable_pty::{ExitStatus, PtySize};
 use rangeset::RangeSet;
 use ratelim::RateLimiter;
-use std::cell::RefCell;
 use std::cell::RefMut;
+use std::cell::{Cell, RefCell};
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -29,7 +29,8 @@ pub struct ClientPane {
     client: Arc<ClientInner>,
     local_pane_id: PaneId,
     pub remote_pane_id: PaneId,
-    pub remote_tab_id: TabId,
+    /// The remote tab changes when the pane is moved to another tab
+    pub remote_tab_id: Cell<TabId>,
     pub renderable: RefCell<RenderableState>,
     palette: RefCell<ColorPalette>,
     writer: RefCell<PaneWriter>,
@@ -87,7 +88,7 @@ impl ClientPane {
             mouse,
             remote_pane_id,
             local_pane_id,
-            remote_tab_id,
+            remote_tab_id: Cell::new(remote_tab_id),
             renderable: RefCell::new(render),
             writer: RefCell::new(writer),
             palette: RefCell::new(palette),
@@ -107,6 +108,14 @@ impl ClientPane {
                     .borrow_mut()
                     .apply_changes_to_surface(delta);
             }
+            Pdu::GetPaneRenderDeltaResponse(delta) => {
+                *self.mouse_grabbed.borrow_mut() = delta.changes.mouse_grabbed;
+                self.renderable
+                    .borrow()
+                    .inner
+                    .borrow_mut()
+                    .apply_delta_to_surface(delta);
+            }
             Pdu::SetClipboard(SetClipboard {
                 clipboard,
                 selection,
@@ -155,6 +164,37 @@ impl ClientPane {
     pub fn remote_pane_id(&self) -> TabId {
         self.remote_pane_id
     }
+
+    /// Returns the text of the pane, as `Pane::get_text` does, but
+    /// asks the server for it rather than using the lines that we
+    /// have fetched so far, which may be placeholders
+    pub async fn get_remote_text(
+        &self,
+        start_line: Option<isize>,
+        end_line: Option<isize>,
+        escapes: bool,
+    ) -> anyhow::Result<String> {
+        let response = self
+            .client
+            .client
+            .get_pane_text(GetPaneText {
+                pane_id: self.remote_pane_id,
+                start_line,
+                end_line,
+                escapes,
+            })
+            .await?;
+        Ok(response.text)
+    }
+
+    /// Returns the attempt that the domain is making to reconnect
+    /// to the server, if it has lost its connection
+    fn reconnect_attempt(&self) -> Option<usize> {
+        let mux = Mux::get()?;
+        let domain = mux.get_domain(self.client.local_domain_id)?;
+        let domain = domain.downcast_ref::<ClientDomain>()?;
+        domain.reconnect_attempt()
+    }
 }
 
 #[async_trait(?Send)]
@@ -193,7 +233,10 @@ impl Pane for ClientPane {
     fn get_title(&self) -> String {
         let renderable = self.renderable.borrow();
         let inner = renderable.inner.borrow();
-        inner.title.clone()
+        match self.reconnect_attempt() {
+            Some(attempt) => format!("reconnecting (attempt {})… {}", attempt, inner.title),
+            None => inner.title.clone(),
+        }
     }
 
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
@@ -238,7 +281,7 @@ impl Pane for ClientPane {
         let mut inner = render.inner.borrow_mut();
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
-        let remote_tab_id = self.remote_tab_id;
+        let remote_tab_id = self.remote_tab_id.get();
         // Invalidate any cached rows on a resize
         inner.make_all_stale();
         promise::spawn::spawn(async move {
@@ -255,6 +298,23 @@ impl Pane for ClientPane {
         inner.update_last_send();
     }
 
+    fn set_arrangement(&self, arrangement: PaneArrangement) {
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        let remote_tab_id = self.remote_tab_id.get();
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .rearrange_panes(RearrangePanes {
+                    containing_tab_id: remote_tab_id,
+                    pane_id: remote_pane_id,
+                    arrangement,
+                })
+                .await
+        })
+        .detach();
+    }
+
     fn resize(&self, size: PtySize) -> anyhow::Result<()> {
         let render = self.renderable.borrow();
         let mut inner = render.inner.borrow_mut();
@@ -271,7 +331,7 @@ impl Pane for ClientPane {
 
             let client = Arc::clone(&self.client);
             let remote_pane_id = self.remote_pane_id;
-            let remote_tab_id = self.remote_tab_id;
+            let remote_tab_id = self.remote_tab_id.get();
             promise::spawn::spawn(async move {
                 client
                     .client
@@ -337,6 +397,10 @@ impl Pane for ClientPane {
     }
 
     fn kill(&self) {
+        if self.client.is_detached() {
+            // The pane is only going away locally
+            return;
+        }
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
         promise::spawn::spawn(async move {
@@ -366,6 +430,10 @@ impl Pane for ClientPane {
         self.renderable.borrow().inner.borrow().dead
     }
 
+    fn get_exit_status(&self) -> Option<ExitStatus> {
+        self.renderable.borrow().inner.borrow().exit_status.clone()
+    }
+
     fn palette(&self) -> ColorPalette {
         let tardy = self.renderable.borrow().inner.borrow().is_tardy();
 
@@ -385,8 +453,7 @@ impl Pane for ClientPane {
     }
 
     fn is_alt_screen_active(&self) -> bool {
-        // FIXME: retrieve this from the remote
-        false
+        self.renderable.borrow().inner.borrow().alt_screen
     }
 
     fn get_current_working_dir(&self) -> Option<Url> {
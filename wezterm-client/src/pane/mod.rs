@@ -2,4 +2,5 @@ pub use clientpane::ClientPane;
 
 mod clientpane;
 mod mousestate;
+mod predict;
 mod renderable;
@@ -0,0 +1,287 @@
+//! Predictive local echo, along the lines of mosh.  When the round trip
+//! to the server is slow, printable characters and simple cursor movements
+//! are shown as soon as they are typed, as predictions that are laid over
+//! the lines that the server sent.  Each prediction is erased again once
+//! the server shows it, or once the server has had time to show it and
+//! showed something else instead.
+use codec::InputSerial;
+use mux::renderable::StableCursorPosition;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use termwiz::cell::{Cell, CellAttributes, Underline};
+use termwiz::surface::SEQ_ZERO;
+use wezterm_term::{KeyCode, Line, StableRowIndex};
+
+/// The longest that a prediction is shown for, in case the
+/// round trip time is too far off for it to be judged properly
+const PREDICTION_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug)]
+struct Prediction {
+    /// The input that the prediction was made for
+    serial: InputSerial,
+    made: Instant,
+    row: StableRowIndex,
+    col: usize,
+    /// What the cell at `col` is predicted to become, or None
+    /// if only the cursor is predicted to move
+    cell: Option<Cell>,
+    /// Where the cursor is predicted to be afterwards
+    cursor_x: usize,
+}
+
+impl Prediction {
+    /// Whether `line`, which the server sent for the row of the
+    /// prediction, and the cursor position show what was predicted
+    fn is_shown(&self, line: Option<&Line>, cursor: Option<&StableCursorPosition>) -> bool {
+        match &self.cell {
+            Some(cell) => line
+                .and_then(|line| line.cells().get(self.col))
+                .map(|shown| shown.str() == cell.str())
+                .unwrap_or(false),
+            None => cursor
+                .map(|cursor| cursor.y == self.row && cursor.x == self.cursor_x)
+                .unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Predictions {
+    predictions: Vec<Prediction>,
+    /// The most recent input that the server has responded to
+    confirmed: Option<InputSerial>,
+}
+
+impl Predictions {
+    pub fn is_empty(&self) -> bool {
+        self.predictions.is_empty()
+    }
+
+    /// The rows that there are predictions for
+    pub fn rows(&self) -> Vec<StableRowIndex> {
+        let mut rows: Vec<StableRowIndex> = self.predictions.iter().map(|p| p.row).collect();
+        rows.sort_unstable();
+        rows.dedup();
+        rows
+    }
+
+    fn attributes(underline: bool) -> CellAttributes {
+        let mut attrs = CellAttributes::default();
+        if underline {
+            attrs.set_underline(Underline::Double);
+        }
+        attrs
+    }
+
+    /// Predicts the effect of typing `key` with the cursor at `cursor`
+    /// on `line`, moving the cursor to where it is predicted to go.
+    /// Returns false for keys whose effect can't safely be predicted.
+    pub fn predict_key(
+        &mut self,
+        serial: InputSerial,
+        key: KeyCode,
+        cursor: &mut StableCursorPosition,
+        line: &Line,
+        underline: bool,
+    ) -> bool {
+        if mux::pane::looks_like_password_prompt(&line.as_str()) {
+            // Don't reveal what is typed in response to the prompt
+            return false;
+        }
+
+        let x = cursor.x;
+        let (col, cell, cursor_x) = match key {
+            KeyCode::Char(c) if !c.is_control() => {
+                let cell = Cell::new(c, Self::attributes(underline));
+                let width = cell.width();
+                (x, Some(cell), x + width)
+            }
+            KeyCode::Backspace if x > 0 => (
+                x - 1,
+                Some(Cell::new(' ', Self::attributes(underline))),
+                x - 1,
+            ),
+            KeyCode::LeftArrow if x > 0 => (x - 1, None, x - 1),
+            // A shell won't move the cursor past the end of the input
+            KeyCode::RightArrow
+                if line
+                    .cells()
+                    .get(x + 1..)
+                    .map(|rest| rest.iter().any(|cell| cell.str() != " "))
+                    .unwrap_or(false) =>
+            {
+                (x, None, x + 1)
+            }
+            _ => return false,
+        };
+
+        self.predictions.push(Prediction {
+            serial,
+            made: Instant::now(),
+            row: cursor.y,
+            col,
+            cell,
+            cursor_x,
+        });
+        cursor.x = cursor_x;
+        true
+    }
+
+    /// Predicts the effect of pasting `text` with the cursor at `cursor`
+    /// on `line`, wrapping it to `cols` columns, and moves the cursor to
+    /// where it is predicted to go.
+    pub fn predict_paste(
+        &mut self,
+        serial: InputSerial,
+        text: &str,
+        cursor: &mut StableCursorPosition,
+        line: &Line,
+        cols: usize,
+        underline: bool,
+    ) -> bool {
+        if mux::pane::looks_like_password_prompt(&line.as_str())
+            || text.chars().any(|c| c.is_control() && c != '\n')
+        {
+            return false;
+        }
+
+        let attrs = Self::attributes(underline);
+        let made = Instant::now();
+        let text = textwrap::fill(text, cols);
+        let mut row = cursor.y;
+        let mut col = cursor.x;
+        for (idx, text) in text.split('\n').enumerate() {
+            if idx > 0 {
+                row += 1;
+                col = 0;
+            }
+            let line = Line::from_text(text, &attrs);
+            let mut cells = line.cells().iter();
+            while let Some(cell) = cells.next() {
+                let width = cell.width();
+                // Skip the padding that follows wide cells
+                for _ in 1..width {
+                    cells.next();
+                }
+                self.predictions.push(Prediction {
+                    serial,
+                    made,
+                    row,
+                    col,
+                    cell: Some(cell.clone()),
+                    cursor_x: col + width,
+                });
+                col += width;
+            }
+        }
+        cursor.y = row;
+        cursor.x = col;
+        true
+    }
+
+    /// Lays the predictions for `row` over `line`
+    pub fn apply(&self, row: StableRowIndex, line: &mut Line) {
+        for prediction in self.predictions.iter().filter(|p| p.row == row) {
+            if let Some(cell) = &prediction.cell {
+                line.set_cell(prediction.col, cell.clone(), SEQ_ZERO);
+            }
+        }
+    }
+
+    /// Returns where the cursor is predicted to be, given that the
+    /// server has it at `cursor`
+    pub fn cursor(&self, cursor: StableCursorPosition) -> StableCursorPosition {
+        match self.predictions.last() {
+            Some(prediction) => StableCursorPosition {
+                x: prediction.cursor_x,
+                y: prediction.row,
+                ..cursor
+            },
+            None => cursor,
+        }
+    }
+
+    /// Called with what the server sent: `input_serial` is the input that
+    /// it responded to, if any, `lines` are its versions of the rows that
+    /// there are predictions for, and `cursor` is its cursor position, if
+    /// it sent one.  The predictions that it shows are dropped, along with
+    /// those that have expired.
+    pub fn reconcile(
+        &mut self,
+        input_serial: Option<InputSerial>,
+        lines: &HashMap<StableRowIndex, Line>,
+        cursor: Option<&StableCursorPosition>,
+        rtt: Duration,
+    ) {
+        if let Some(serial) = input_serial {
+            self.confirmed = self.confirmed.max(Some(serial));
+        }
+        self.predictions
+            .retain(|p| !p.is_shown(lines.get(&p.row), cursor));
+        self.expire(rtt);
+    }
+
+    /// Drops the predictions that the server has had time to show but
+    /// didn't, allowing a round trip after it responded to the input,
+    /// returning true if any were dropped
+    pub fn expire(&mut self, rtt: Duration) -> bool {
+        let confirmed = self.confirmed;
+        let before = self.predictions.len();
+        self.predictions.retain(|p| {
+            let elapsed = p.made.elapsed();
+            let answered = confirmed.map(|c| c >= p.serial).unwrap_or(false);
+            elapsed < PREDICTION_TIMEOUT && !(answered && elapsed >= rtt * 2)
+        });
+        self.predictions.len() != before
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn predictions() {
+        let mut predictions = Predictions::default();
+        let mut cursor = StableCursorPosition::default();
+        let mut line = Line::from_text("$ ", &CellAttributes::default());
+        line.resize(10, SEQ_ZERO);
+        cursor.x = 2;
+        let serial = InputSerial::now();
+
+        assert!(predictions.predict_key(serial, KeyCode::Char('l'), &mut cursor, &line, true));
+        assert!(predictions.predict_key(serial, KeyCode::Char('s'), &mut cursor, &line, true));
+        assert!(!predictions.predict_key(serial, KeyCode::Enter, &mut cursor, &line, true));
+        assert!(!predictions.predict_key(serial, KeyCode::RightArrow, &mut cursor, &line, true));
+        assert_eq!(cursor.x, 4);
+        assert_eq!(predictions.cursor(StableCursorPosition::default()).x, 4);
+
+        let mut shown = line.clone();
+        predictions.apply(0, &mut shown);
+        assert_eq!(shown.as_str().trim_end(), "$ ls");
+        assert_eq!(
+            shown.cells()[2].attrs().underline(),
+            Underline::Double,
+            "predictions are underlined"
+        );
+
+        // The server echoed the first character, but not yet the second
+        let mut echoed = line.clone();
+        echoed.set_cell(2, Cell::new('l', CellAttributes::default()), SEQ_ZERO);
+        let mut lines = HashMap::new();
+        lines.insert(0, echoed);
+        predictions.reconcile(None, &lines, None, Duration::from_secs(1));
+        assert_eq!(predictions.predictions.len(), 1);
+        assert_eq!(predictions.predictions[0].col, 3);
+
+        // The server answered the input, and a round trip later still
+        // doesn't show the second character
+        predictions.reconcile(Some(serial), &lines, None, Duration::from_millis(0));
+        assert!(predictions.is_empty());
+
+        let prompt = Line::from_text("Password: ", &CellAttributes::default());
+        cursor.x = 10;
+        assert!(!predictions.predict_key(serial, KeyCode::Char('x'), &mut cursor, &prompt, true));
+    }
+}
@@ -1,21 +1,24 @@
 use crate::domain::ClientInner;
 use crate::pane::clientpane::ClientPane;
+use crate::pane::predict::Predictions;
 use anyhow::anyhow;
 use codec::*;
-use config::{configuration, ConfigHandle};
+use config::{configuration, ConfigHandle, PredictiveEcho};
 use lru::LruCache;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use mux::Mux;
+use portable_pty::ExitStatus;
 use promise::BrokenPromise;
 use rangeset::*;
 use ratelim::RateLimiter;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ops::Range;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use termwiz::cell::{Cell, CellAttributes, Underline};
+use termwiz::cell::CellAttributes;
 use termwiz::color::AnsiColor;
 use termwiz::surface::{SequenceNo, SEQ_ZERO};
 use url::Url;
@@ -67,9 +70,20 @@ pub struct RenderableInner {
     pub dimensions: RenderableDimensions,
 
     lines: LruCache<StableRowIndex, LineEntry>,
+    /// The generations of the lines of the viewport, as they were last
+    /// sent by a server that we negotiated `delta_lines` with
+    generations: HashMap<StableRowIndex, u64>,
     pub title: String,
     pub working_dir: Option<Url>,
     pub seqno: SequenceNo,
+    pub alt_screen: bool,
+    pub exit_status: Option<ExitStatus>,
+
+    /// The echo that we predicted for what was typed
+    predictions: Predictions,
+    /// The lines that the server sent for the rows that predictions
+    /// are laid over
+    unpredicted: HashMap<StableRowIndex, Line>,
 
     fetch_limiter: RateLimiter,
 
@@ -107,8 +121,13 @@ impl RenderableInner {
             cursor_position: StableCursorPosition::default(),
             dimensions,
             lines: LruCache::new(configuration().scrollback_lines),
+            generations: HashMap::new(),
             title: title.to_string(),
             working_dir: None,
+            alt_screen: false,
+            exit_status: None,
+            predictions: Predictions::default(),
+            unpredicted: HashMap::new(),
             fetch_limiter,
             last_send_time: now,
             last_recv_time: now,
@@ -132,73 +151,109 @@ impl RenderableInner {
         }
     }
 
-    /// Predictive echo can be noisy when the link is working well,
-    /// so we only employ it when it looks like the latency is high.
-    /// We pick 100ms as the threshold for this.
+    /// Whether to predict the echo of what is typed.  In `Auto` mode
+    /// predictions can be noisy when the link is working well, so we only
+    /// employ them when it looks like the latency is high.  Applications
+    /// using the alternate screen redraw it in ways that we can't hope to
+    /// predict, and tend to use letters as commands, so predictions are
+    /// never made for them.
     fn should_predict(&self) -> bool {
-        !self.client.is_local() && self.last_input_rtt >= 100
+        if self.alt_screen {
+            return false;
+        }
+        match self.client.client.predictive_echo {
+            PredictiveEcho::Off => false,
+            // A local mux is only slow when it is busy, which echo
+            // predictions wouldn't help with
+            PredictiveEcho::Auto => {
+                !self.client.is_local()
+                    && self.last_input_rtt >= self.client.client.local_echo_threshold_ms
+            }
+            PredictiveEcho::Always => true,
+        }
     }
 
-    /// Compute a "prediction" and apply it to the line data that we
-    /// have available, marking it as dirty so that it gets rendered.
-    /// The prediction is basically just local echo.
-    /// Open questions:
-    /// how do we tell if the intent is to suppress local echo during eg:
-    ///  * password prompt?  One option is to look back and see if the line
-    ///                      looks like a password prompt.
-    ///  * normal mode in vim: letter presses are typically movement or
-    ///                        other editor commands
-    /// There are bound to be a number of other edge cases that we should
-    /// handle.
-    fn apply_prediction(&mut self, c: KeyCode, line: &mut Line) {
-        let text = line.as_str();
-        if text.contains("sword") {
-            // This line might be a password prompt.  Don't force
-            // on local echo here, as we don't want to reveal content
-            // from their password
-            return;
+    /// Predictions are underlined once the latency is high enough that
+    /// they would otherwise be mistaken for what the server sent
+    fn underline_predictions(&self) -> bool {
+        self.last_input_rtt >= self.client.client.local_echo_threshold_ms
+    }
+
+    /// Returns the line that is shown for `row`, if we have one
+    fn shown_line(&self, row: StableRowIndex) -> Option<Line> {
+        match self.lines.peek(&row) {
+            Some(LineEntry::Stale(line))
+            | Some(LineEntry::Line(line))
+            | Some(LineEntry::Dirty(line))
+            | Some(LineEntry::DirtyAndFetching(line, _)) => Some(line.clone()),
+            Some(LineEntry::Fetching(_)) | None => None,
         }
+    }
 
-        match c {
-            KeyCode::Enter => {
-                self.cursor_position.x = 0;
-                self.cursor_position.y += 1;
-            }
-            KeyCode::UpArrow => {
-                self.cursor_position.y = self.cursor_position.y.saturating_sub(1);
-            }
-            KeyCode::DownArrow => {
-                self.cursor_position.y += 1;
+    /// Replaces the line that is shown for `row`, marking it as dirty
+    /// so that it gets rendered, without losing track of a fetch
+    fn replace_shown_line(&mut self, row: StableRowIndex, line: Line) {
+        let entry = match self.lines.pop(&row) {
+            Some(LineEntry::DirtyAndFetching(_, then)) | Some(LineEntry::Fetching(then)) => {
+                LineEntry::DirtyAndFetching(line, then)
             }
-            KeyCode::RightArrow => {
-                self.cursor_position.x += 1;
-            }
-            KeyCode::LeftArrow => {
-                self.cursor_position.x = self.cursor_position.x.saturating_sub(1);
-            }
-            KeyCode::Delete => {
-                line.erase_cell(self.cursor_position.x, SEQ_ZERO);
-            }
-            KeyCode::Backspace => {
-                if self.cursor_position.x > 0 {
-                    line.erase_cell(self.cursor_position.x - 1, SEQ_ZERO);
-                    self.cursor_position.x -= 1;
-                }
-            }
-            KeyCode::Char(c) => {
-                let cell = Cell::new(
-                    c,
-                    CellAttributes::default()
-                        .set_underline(Underline::Double)
-                        .clone(),
-                );
+            _ => LineEntry::Dirty(line),
+        };
+        self.lines.put(row, entry);
+    }
 
-                let cell = line.set_cell(self.cursor_position.x, cell, SEQ_ZERO);
-                // Adjust the cursor to reflect the width of this new cell
-                self.cursor_position.x += cell.width();
+    /// Lays the predictions over the lines that the server sent,
+    /// and puts back the lines that no longer have any predictions
+    fn show_predictions(&mut self) {
+        let rows = self.predictions.rows();
+
+        let unpredicted: Vec<StableRowIndex> = self
+            .unpredicted
+            .keys()
+            .filter(|row| !rows.contains(row))
+            .cloned()
+            .collect();
+        for row in unpredicted {
+            if let Some(line) = self.unpredicted.remove(&row) {
+                self.replace_shown_line(row, line);
             }
-            _ => {}
         }
+
+        for row in rows {
+            let mut line = match self.unpredicted.get(&row) {
+                Some(line) => line.clone(),
+                None => match self.shown_line(row) {
+                    Some(line) => {
+                        self.unpredicted.insert(row, line.clone());
+                        line
+                    }
+                    None => continue,
+                },
+            };
+            self.predictions.apply(row, &mut line);
+            self.replace_shown_line(row, line);
+        }
+    }
+
+    /// Drops the predictions that the server shows in what it just sent,
+    /// and those that it has had time to show but didn't.  `cursor` is the
+    /// cursor position that it sent, if any.
+    fn reconcile_predictions(
+        &mut self,
+        input_serial: Option<InputSerial>,
+        cursor: Option<&StableCursorPosition>,
+    ) {
+        if self.predictions.is_empty() && self.unpredicted.is_empty() {
+            return;
+        }
+        self.predictions.reconcile(
+            input_serial,
+            &self.unpredicted,
+            cursor,
+            Duration::from_millis(self.last_input_rtt),
+        );
+        self.show_predictions();
+        self.cursor_position = self.predictions.cursor(self.cursor_position);
     }
 
     /// Based on a keypress, apply a "prediction" of what the terminal
@@ -209,59 +264,23 @@ impl RenderableInner {
         if !self.should_predict() {
             return;
         }
-
-        let c = match key {
-            KeyCode::LeftArrow
-            | KeyCode::RightArrow
-            | KeyCode::UpArrow
-            | KeyCode::DownArrow
-            | KeyCode::Delete
-            | KeyCode::Backspace
-            | KeyCode::Enter
-            | KeyCode::Char(_) => key,
-            _ => return,
-        };
         if mods != KeyModifiers::NONE && mods != KeyModifiers::SHIFT {
             return;
         }
 
-        let row = self.cursor_position.y;
-        match self.lines.pop(&row) {
-            Some(LineEntry::Stale(mut line))
-            | Some(LineEntry::Line(mut line))
-            | Some(LineEntry::Dirty(mut line)) => {
-                self.apply_prediction(c, &mut line);
-                self.lines.put(row, LineEntry::Dirty(line));
-            }
-            Some(LineEntry::DirtyAndFetching(mut line, instant)) => {
-                self.apply_prediction(c, &mut line);
-                self.lines
-                    .put(row, LineEntry::DirtyAndFetching(line, instant));
-            }
-            Some(entry) => {
-                self.lines.put(row, entry);
-            }
-            None => {}
-        }
-    }
-
-    fn apply_paste_prediction(&mut self, row: usize, text: &str, line: &mut Line) {
-        let attrs = CellAttributes::default()
-            .set_underline(Underline::Double)
-            .clone();
-
-        let text_line = Line::from_text(text, &attrs);
-
-        if row == 0 {
-            for cell in text_line.cells() {
-                line.set_cell(self.cursor_position.x, cell.clone(), SEQ_ZERO);
-                self.cursor_position.x += cell.width();
-            }
-        } else {
-            // The pasted line replaces the data for the existing line
-            line.resize_and_clear(0, SEQ_ZERO, CellAttributes::default());
-            line.append_line(text_line, SEQ_ZERO);
-            self.cursor_position.x = line.cells().len();
+        let line = match self.shown_line(self.cursor_position.y) {
+            Some(line) => line,
+            None => return,
+        };
+        let underline = self.underline_predictions();
+        if self.predictions.predict_key(
+            self.input_serial,
+            key,
+            &mut self.cursor_position,
+            &line,
+            underline,
+        ) {
+            self.show_predictions();
         }
     }
 
@@ -270,39 +289,124 @@ impl RenderableInner {
             return;
         }
 
-        let text = textwrap::fill(text, self.dimensions.cols);
-        let lines: Vec<&str> = text.split("\n").collect();
+        let line = match self.shown_line(self.cursor_position.y) {
+            Some(line) => line,
+            None => return,
+        };
+        let underline = self.underline_predictions();
+        if self.predictions.predict_paste(
+            self.input_serial,
+            text,
+            &mut self.cursor_position,
+            &line,
+            self.dimensions.cols,
+            underline,
+        ) {
+            self.show_predictions();
+        }
+    }
+
+    /// Called once the client has reconnected to the server.
+    /// The lines that were received before the connection was lost
+    /// are refetched, so that they replace what the pane shows rather
+    /// than being added to it, and the pane is polled right away.
+    pub fn reconnected(&mut self) {
+        self.dead = false;
+        self.predictions = Predictions::default();
+        self.show_predictions();
+        self.make_all_stale();
+        self.generations.clear();
+        self.update_last_send();
+    }
+
+    pub fn update_last_send(&mut self) {
+        self.last_send_time = Instant::now();
+        self.poll_interval = BASE_POLL_INTERVAL;
+    }
+
+    pub fn apply_changes_to_surface(&mut self, delta: GetPaneRenderChangesResponse) {
+        self.apply_changes(delta, &RangeSet::new());
+    }
+
+    /// Applies the changes from a server that we negotiated `delta_lines`
+    /// with.  Each delta is applied to our copy of its line if that is the
+    /// generation that it was computed from; the lines of the viewport
+    /// that we don't have the current version of are fetched.
+    pub fn apply_delta_to_surface(&mut self, delta: GetPaneRenderDeltaResponse) {
+        let GetPaneRenderDeltaResponse {
+            mut changes,
+            generations,
+            deltas,
+        } = delta;
+        let config = configuration();
+        let dims = changes.dimensions;
+        let viewport = dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex;
 
-        for (idx, paste_line) in lines.iter().enumerate() {
-            let row = self.cursor_position.y + idx as StableRowIndex;
+        for (stable_row, generation) in generations {
+            self.generations.insert(stable_row, generation);
+        }
 
-            match self.lines.pop(&row) {
-                Some(LineEntry::Stale(mut line))
-                | Some(LineEntry::Line(mut line))
-                | Some(LineEntry::Dirty(mut line)) => {
-                    self.apply_paste_prediction(idx, paste_line, &mut line);
-                    self.lines.put(row, LineEntry::Dirty(line));
+        for delta in deltas.deltas() {
+            let stable_row = delta.row;
+            let line = if self.generations.get(&stable_row) != Some(&delta.base_generation) {
+                None
+            } else if let Some(line) = self.unpredicted.get(&stable_row) {
+                // The delta applies to what the server sent,
+                // not to the predictions laid over it
+                Some(line.clone())
+            } else {
+                match self.lines.pop(&stable_row) {
+                    Some(LineEntry::Line(line)) | Some(LineEntry::Dirty(line)) => Some(line),
+                    Some(entry) => {
+                        self.lines.put(stable_row, entry);
+                        None
+                    }
+                    None => None,
                 }
-                Some(LineEntry::DirtyAndFetching(mut line, instant)) => {
-                    self.apply_paste_prediction(idx, paste_line, &mut line);
-                    self.lines
-                        .put(row, LineEntry::DirtyAndFetching(line, instant));
+            };
+            match line {
+                Some(mut line) => {
+                    delta.apply(&mut line, changes.seqno);
+                    self.generations.insert(stable_row, delta.generation);
+                    self.put_line(stable_row, line, &config, None);
+                }
+                None => {
+                    log::trace!(
+                        "row {} is not generation {}, so fetch it",
+                        stable_row,
+                        delta.base_generation
+                    );
+                    self.generations.remove(&stable_row);
                 }
-                Some(entry) => {
-                    self.lines.put(row, entry);
+            }
+        }
+        self.generations
+            .retain(|stable_row, _| viewport.contains(stable_row));
+
+        // The rest of the viewport is unchanged since it was last sent,
+        // so it only needs fetching where we don't have what was sent
+        let mut current = RangeSet::new();
+        for stable_row in viewport {
+            if self.generations.contains_key(&stable_row) {
+                current.add(stable_row);
+            } else {
+                match self.lines.peek(&stable_row) {
+                    Some(LineEntry::Fetching(_)) | Some(LineEntry::DirtyAndFetching(..)) => {}
+                    _ => changes.dirty_lines.push(stable_row..stable_row + 1),
                 }
-                None => {}
             }
         }
-        self.cursor_position.y += lines.len().saturating_sub(1) as StableRowIndex;
-    }
 
-    pub fn update_last_send(&mut self) {
-        self.last_send_time = Instant::now();
-        self.poll_interval = BASE_POLL_INTERVAL;
+        self.apply_changes(changes, &current);
     }
 
-    pub fn apply_changes_to_surface(&mut self, delta: GetPaneRenderChangesResponse) {
+    /// Applies `delta`; the lines in `current` are known to be up to date,
+    /// so are not fetched even if the cursor moved to or from them
+    fn apply_changes(
+        &mut self,
+        delta: GetPaneRenderChangesResponse,
+        current: &RangeSet<StableRowIndex>,
+    ) {
         let now = Instant::now();
         self.poll_interval = BASE_POLL_INTERVAL;
         self.last_recv_time = now;
@@ -317,6 +421,7 @@ impl RenderableInner {
             // we'll address that below
             dirty.add(delta.cursor_position.y);
         }
+        dirty.remove_set(current);
 
         // Keep track of the approximate round trip time by recording how
         // long it took for this response to come back
@@ -344,12 +449,15 @@ impl RenderableInner {
         self.title = delta.title;
         self.working_dir = delta.working_dir.map(Into::into);
         self.seqno = delta.seqno;
+        self.alt_screen = delta.is_alt_screen_active;
+        self.exit_status = delta.exit_status;
 
         let config = configuration();
         for (stable_row, line) in delta.bonus_lines.lines() {
             self.put_line(stable_row, line, &config, None);
             dirty.remove(stable_row);
         }
+        self.reconcile_predictions(delta.input_serial, Some(&delta.cursor_position));
 
         Mux::get()
             .unwrap()
@@ -446,6 +554,9 @@ impl RenderableInner {
                     if fetch_start == then =>
                 {
                     log::trace!("row {} fetch done -> Dirty", stable_row,);
+                    // The server no longer knows this line by the
+                    // generation that it was sent as, if any
+                    self.generations.remove(&stable_row);
                     LineEntry::Dirty(line)
                 }
                 Some(e) => {
@@ -461,6 +572,10 @@ impl RenderableInner {
                 }
                 None => return,
             }
+        } else if self.unpredicted.contains_key(&stable_row) {
+            // The predictions are laid over it again once
+            // everything that the server sent has been applied
+            LineEntry::Dirty(line)
         } else {
             if let Some(LineEntry::Line(prior)) = self.lines.pop(&stable_row) {
                 if prior == line {
@@ -472,6 +587,11 @@ impl RenderableInner {
                 LineEntry::Dirty(line)
             }
         };
+        if let Some(unpredicted) = self.unpredicted.get_mut(&stable_row) {
+            if let LineEntry::Dirty(line) = &entry {
+                *unpredicted = line.clone();
+            }
+        }
         self.lines.put(stable_row, entry);
     }
 
@@ -527,6 +647,7 @@ impl RenderableInner {
                     for (stable_row, line) in lines.into_iter() {
                         inner.put_line(stable_row, line, &config, Some(now));
                     }
+                    inner.reconcile_predictions(None, None);
                 }
                 Err(err) => {
                     log::error!("get_lines failed: {}", err);
@@ -700,6 +821,11 @@ impl RenderableState {
             }
         }
 
+        let rtt = Duration::from_millis(inner.last_input_rtt);
+        if inner.predictions.expire(rtt) {
+            inner.show_predictions();
+        }
+
         let mut result = RangeSet::new();
         for r in lines {
             match inner.lines.get(&r) {
@@ -3,7 +3,7 @@
 use crate::locator::{FontDataHandle, FontDataSource};
 use crate::parser::ParsedFont;
 use anyhow::{anyhow, Context};
-use config::{configuration, FreeTypeLoadTarget};
+use config::{configuration, Config, FreeTypeLoadTarget};
 pub use freetype::*;
 use memmap2::{Mmap, MmapOptions};
 use rangeset::RangeSet;
@@ -52,8 +52,13 @@ fn render_mode_to_load_target(render_mode: FT_Render_Mode) -> u32 {
 }
 
 pub fn compute_load_flags_from_config() -> (i32, FT_Render_Mode) {
-    let config = configuration();
+    compute_load_flags(&configuration())
+}
 
+/// Computes the load flags and render mode specified by `config`,
+/// which may be a window-specific configuration that has overrides
+/// applied on top of the global configuration.
+pub fn compute_load_flags(config: &Config) -> (i32, FT_Render_Mode) {
     let load_flags = config.freetype_load_flags.bits() | FT_LOAD_COLOR;
 
     fn target_to_render(t: FreeTypeLoadTarget) -> FT_Render_Mode {
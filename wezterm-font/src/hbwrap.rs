@@ -36,6 +36,32 @@ pub fn feature_from_string(s: &str) -> Result<hb_feature_t, Error> {
     }
 }
 
+/// Returns the ISO 15924 tag of the script to which the codepoint belongs
+pub fn script_tag_for_codepoint(c: char) -> hb_tag_t {
+    unsafe { hb_unicode_script(hb_unicode_funcs_get_default(), c as hb_codepoint_t) as hb_tag_t }
+}
+
+/// Converts a script name such as `"Deva"` into an ISO 15924 tag
+/// that can be compared with `script_tag_for_codepoint`.
+/// In the same way as `hb_script_from_string`, only the first four
+/// characters are considered and their case is normalized, which means
+/// that some longer names, such as `"Devanagari"`, are also accepted.
+/// We don't use `hb_script_from_string` itself because it may return
+/// values that are not valid members of the `hb_script_t` enum.
+pub fn script_tag_from_string(s: &str) -> hb_tag_t {
+    let mut tag: hb_tag_t = 0;
+    let chars = s.bytes().chain(std::iter::repeat(b' ')).take(4);
+    for (idx, c) in chars.enumerate() {
+        let c = if idx == 0 {
+            c.to_ascii_uppercase()
+        } else {
+            c.to_ascii_lowercase()
+        };
+        tag = (tag << 8) | c as hb_tag_t;
+    }
+    tag
+}
+
 pub struct Font {
     font: *mut hb_font_t,
 }
@@ -4,9 +4,7 @@ use crate::parser::ParsedFont;
 use crate::rasterizer::{new_rasterizer, FontRasterizer};
 use crate::shaper::{new_shaper, FontShaper};
 use anyhow::{Context, Error};
-use config::{
-    configuration, ConfigHandle, FontRasterizerSelection, FontStretch, FontWeight, TextStyle,
-};
+use config::{configuration, ConfigHandle, FontStretch, FontWeight, TextStyle};
 use rangeset::RangeSet;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
@@ -48,6 +46,8 @@ pub struct LoadedFont {
     dpi: u32,
     font_config: Weak<FontConfigInner>,
     pending_fallback: Arc<Mutex<Vec<ParsedFont>>>,
+    /// Overrides the configured harfbuzz_features
+    harfbuzz_features: Option<Vec<String>>,
 }
 
 impl LoadedFont {
@@ -73,8 +73,11 @@ impl LoadedFont {
         }
         if loaded {
             if let Some(font_config) = self.font_config.upgrade() {
-                *self.shaper.borrow_mut() =
-                    new_shaper(&*font_config.config.borrow(), &self.handles.borrow())?;
+                *self.shaper.borrow_mut() = new_shaper(
+                    &*font_config.config.borrow(),
+                    &self.handles.borrow(),
+                    self.harfbuzz_features.as_deref(),
+                )?;
             }
         }
         Ok(loaded)
@@ -202,13 +205,11 @@ impl LoadedFont {
         if let Some(raster) = rasterizers.get(&fallback) {
             raster.rasterize_glyph(glyph_pos, self.font_size, self.dpi)
         } else {
-            let raster_selection = self
+            let config = self
                 .font_config
                 .upgrade()
-                .map_or(FontRasterizerSelection::default(), |c| {
-                    c.config.borrow().font_rasterizer
-                });
-            let raster = new_rasterizer(raster_selection, &(self.handles.borrow())[fallback])?;
+                .map_or_else(configuration, |c| c.config.borrow().clone());
+            let raster = new_rasterizer(&config, &(self.handles.borrow())[fallback])?;
             let result = raster.rasterize_glyph(glyph_pos, self.font_size, self.dpi);
             rasterizers.insert(fallback, raster);
             result
@@ -352,6 +353,7 @@ impl FallbackResolveInfo {
                     ),
                     url: Some(url.to_string()),
                     timeout: Some(Duration::from_secs(15)),
+                    on_click: None,
                 }
                 .show();
             } else {
@@ -496,7 +498,7 @@ impl FontConfigInner {
                 .resolve_multiple(attrs, &mut handles, &mut loaded, pixel_size);
         }
 
-        let shaper = new_shaper(&*config, &handles)?;
+        let shaper = new_shaper(&*config, &handles, None)?;
 
         let metrics = shaper.metrics(font_size, dpi).with_context(|| {
             format!(
@@ -514,6 +516,7 @@ impl FontConfigInner {
             dpi,
             font_config: Rc::downgrade(myself),
             pending_fallback: Arc::new(Mutex::new(vec![])),
+            harfbuzz_features: None,
         });
 
         title_font.replace(Rc::clone(&loaded));
@@ -599,7 +602,10 @@ impl FontConfigInner {
             }
         }
 
-        Ok((new_shaper(&*config, &handles)?, handles))
+        Ok((
+            new_shaper(&*config, &handles, style.harfbuzz_features.as_deref())?,
+            handles,
+        ))
     }
 
     /// Given a text style, load (with caching) the font that best
@@ -679,6 +685,7 @@ impl FontConfigInner {
             dpi,
             font_config: Rc::downgrade(myself),
             pending_fallback: Arc::new(Mutex::new(vec![])),
+            harfbuzz_features: style.harfbuzz_features.clone(),
         });
 
         fonts.insert(style.clone(), Rc::clone(&loaded));
@@ -175,6 +175,10 @@ fn build_fallback_list_impl() -> anyhow::Result<Vec<ParsedFont>> {
         italic: false,
         is_fallback: true,
         is_synthetic: true,
+        synthesize_bold: None,
+        synthesize_italic: None,
+        ranges: vec![],
+        scripts: vec![],
     };
     if let Ok(descriptors) = descriptor_from_attr(&symbols) {
         for descriptor in descriptors.iter() {
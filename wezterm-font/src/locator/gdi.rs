@@ -282,6 +282,10 @@ impl FontLocator for GdiFontLocator {
                         family: font.family_name(),
                         is_fallback: true,
                         is_synthetic: true,
+                        synthesize_bold: None,
+                        synthesize_italic: None,
+                        ranges: vec![],
+                        scripts: vec![],
                     };
 
                     if !resolved.contains(&attr) {
@@ -1,6 +1,6 @@
 use crate::locator::{FontDataHandle, FontDataSource, FontOrigin};
 use crate::shaper::GlyphInfo;
-use config::FontAttributes;
+use config::{CodepointRange, FontAttributes};
 pub use config::{FontStretch, FontWeight};
 use rangeset::RangeSet;
 use std::cmp::Ordering;
@@ -26,6 +26,10 @@ pub struct ParsedFont {
     pub synthesize_dim: bool,
     pub assume_emoji_presentation: bool,
     pub pixel_sizes: Vec<u16>,
+    /// If non-empty, the font is only used for these codepoints
+    pub ranges: Vec<CodepointRange>,
+    /// If non-empty, the font is only used for these scripts
+    pub scripts: Vec<String>,
 }
 
 impl std::fmt::Debug for ParsedFont {
@@ -42,6 +46,8 @@ impl std::fmt::Debug for ParsedFont {
             .field("synthesize_dim", &self.synthesize_dim)
             .field("assume_emoji_presentation", &self.assume_emoji_presentation)
             .field("pixel_sizes", &self.pixel_sizes)
+            .field("ranges", &self.ranges)
+            .field("scripts", &self.scripts)
             .finish()
     }
 }
@@ -61,6 +67,8 @@ impl Clone for ParsedFont {
             cap_height: self.cap_height.clone(),
             coverage: Mutex::new(self.coverage.lock().unwrap().clone()),
             pixel_sizes: self.pixel_sizes.clone(),
+            ranges: self.ranges.clone(),
+            scripts: self.scripts.clone(),
         }
     }
 }
@@ -147,6 +155,17 @@ impl ParsedFont {
         )
     }
 
+    /// Returns a description of the codepoints that this font is
+    /// restricted to, if any
+    pub fn restriction_description(&self) -> Option<String> {
+        if self.ranges.is_empty() && self.scripts.is_empty() {
+            return None;
+        }
+        let mut items: Vec<String> = self.ranges.iter().map(|r| r.to_string()).collect();
+        items.extend(self.scripts.iter().map(|s| format!("script {}", s)));
+        Some(items.join(", "))
+    }
+
     pub fn lua_fallback(handles: &[Self]) -> String {
         let mut code = "wezterm.font_with_fallback({\n".to_string();
 
@@ -166,6 +185,9 @@ impl ParsedFont {
             if !p.pixel_sizes.is_empty() {
                 code.push_str(&format!("  -- Pixel sizes: {:?}\n", p.pixel_sizes));
             }
+            if let Some(restriction) = p.restriction_description() {
+                code.push_str(&format!("  -- Only used for {}\n", restriction));
+            }
 
             if p.weight == FontWeight::REGULAR && p.stretch == FontStretch::Normal && !p.italic {
                 code.push_str(&format!("  \"{}\",\n", p.names.family));
@@ -222,6 +244,8 @@ impl ParsedFont {
             coverage: Mutex::new(RangeSet::new()),
             cap_height,
             pixel_sizes,
+            ranges: vec![],
+            scripts: vec![],
         })
     }
 
@@ -452,12 +476,26 @@ impl ParsedFont {
     }
 
     /// Update self to reflect whether the rasterizer might need to synthesize
-    /// italic for this font.
+    /// italic or bold for this font.
+    /// The `synthesize_bold` and `synthesize_italic` fields of `attr` can
+    /// be used to force or suppress synthesis.
+    /// Any codepoint restriction from `attr` is also carried over.
     pub fn synthesize(mut self, attr: &FontAttributes) -> Self {
-        self.synthesize_italic = !self.italic && attr.italic;
-        self.synthesize_bold = attr.weight >= FontWeight::BOLD
-            && attr.weight > self.weight
-            && self.weight <= FontWeight::REGULAR;
+        self.ranges = attr.ranges.clone();
+        self.scripts = attr.scripts.clone();
+        self.synthesize_italic =
+            attr.synthesize_italic.unwrap_or(true) && !self.italic && attr.italic;
+        self.synthesize_bold = match attr.synthesize_bold {
+            Some(false) => false,
+            // The font may have eg: a medium weight that the default
+            // heuristic won't embolden, but which is still too light
+            Some(true) => attr.weight >= FontWeight::BOLD && attr.weight > self.weight,
+            None => {
+                attr.weight >= FontWeight::BOLD
+                    && attr.weight > self.weight
+                    && self.weight <= FontWeight::REGULAR
+            }
+        };
         self.synthesize_dim = attr.weight < FontWeight::REGULAR
             && attr.weight < self.weight
             && self.weight >= FontWeight::REGULAR;
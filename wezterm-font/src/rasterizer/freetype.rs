@@ -5,6 +5,7 @@ use crate::{ftwrap, RasterizedGlyph};
 use ::freetype::{FT_GlyphSlotRec_, FT_Glyph_Format, FT_Matrix};
 use anyhow::bail;
 use color_types::linear_u8_to_srgb8;
+use config::ConfigHandle;
 use std::cell::RefCell;
 use std::mem;
 use std::slice;
@@ -15,6 +16,8 @@ pub struct FreeTypeRasterizer {
     _lib: ftwrap::Library,
     synthesize_bold: bool,
     synthesize_italic: bool,
+    load_flags: i32,
+    render_mode: ftwrap::FT_Render_Mode,
 }
 
 impl FontRasterizer for FreeTypeRasterizer {
@@ -26,11 +29,13 @@ impl FontRasterizer for FreeTypeRasterizer {
     ) -> anyhow::Result<RasterizedGlyph> {
         self.face.borrow_mut().set_font_size(size, dpi)?;
 
-        let (load_flags, render_mode) = ftwrap::compute_load_flags_from_config();
-
         let mut face = self.face.borrow_mut();
-        let ft_glyph =
-            face.load_and_render_glyph(glyph_pos, load_flags, render_mode, self.synthesize_bold)?;
+        let ft_glyph = face.load_and_render_glyph(
+            glyph_pos,
+            self.load_flags,
+            self.render_mode,
+            self.synthesize_bold,
+        )?;
 
         let mode: ftwrap::FT_Pixel_Mode =
             unsafe { mem::transmute(u32::from(ft_glyph.bitmap.pixel_mode)) };
@@ -283,7 +288,7 @@ impl FreeTypeRasterizer {
         }
     }
 
-    pub fn from_locator(parsed: &ParsedFont) -> anyhow::Result<Self> {
+    pub fn from_locator(parsed: &ParsedFont, config: &ConfigHandle) -> anyhow::Result<Self> {
         log::trace!("Rasterizier wants {:?}", parsed);
         let lib = ftwrap::Library::new()?;
         let mut face = lib.face_from_locator(&parsed.handle)?;
@@ -300,12 +305,16 @@ impl FreeTypeRasterizer {
             }));
         }
 
+        let (load_flags, render_mode) = ftwrap::compute_load_flags(config);
+
         Ok(Self {
             _lib: lib,
             face: RefCell::new(face),
             has_color,
             synthesize_bold: parsed.synthesize_bold,
             synthesize_italic: parsed.synthesize_italic,
+            load_flags,
+            render_mode,
         })
     }
 }
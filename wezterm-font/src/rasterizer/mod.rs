@@ -1,6 +1,6 @@
 use crate::parser::ParsedFont;
 use crate::units::*;
-use config::FontRasterizerSelection;
+use config::{ConfigHandle, FontRasterizerSelection};
 
 pub mod freetype;
 
@@ -86,13 +86,15 @@ pub trait FontRasterizer {
     ) -> anyhow::Result<RasterizedGlyph>;
 }
 
+/// Creates a rasterizer for `handle`, using the rasterizer and
+/// freetype settings from `config`
 pub fn new_rasterizer(
-    rasterizer: FontRasterizerSelection,
+    config: &ConfigHandle,
     handle: &ParsedFont,
 ) -> anyhow::Result<Box<dyn FontRasterizer>> {
-    match rasterizer {
+    match config.font_rasterizer {
         FontRasterizerSelection::FreeType => Ok(Box::new(
-            freetype::FreeTypeRasterizer::from_locator(handle)?,
+            freetype::FreeTypeRasterizer::from_locator(handle, config)?,
         )),
     }
 }
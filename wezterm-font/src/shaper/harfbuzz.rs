@@ -4,7 +4,7 @@ use crate::parser::ParsedFont;
 use crate::shaper::{FallbackIdx, FontMetrics, FontShaper, GlyphInfo};
 use crate::units::*;
 use anyhow::{anyhow, Context};
-use config::ConfigHandle;
+use config::CodepointRange;
 use log::error;
 use ordered_float::NotNan;
 use std::cell::{RefCell, RefMut};
@@ -54,13 +54,59 @@ struct MetricsKey {
     dpi: u32,
 }
 
+/// Limits a font to the codepoints described by the `ranges`
+/// and `scripts` that were configured for it
+struct Restriction {
+    ranges: Vec<CodepointRange>,
+    scripts: Vec<harfbuzz::hb_tag_t>,
+}
+
+impl Restriction {
+    fn new(handle: &ParsedFont) -> Option<Self> {
+        if handle.ranges.is_empty() && handle.scripts.is_empty() {
+            return None;
+        }
+        Some(Self {
+            ranges: handle.ranges.clone(),
+            scripts: handle
+                .scripts
+                .iter()
+                .map(|s| harfbuzz::script_tag_from_string(s))
+                .collect(),
+        })
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.ranges.iter().any(|r| r.contains(c))
+            || (!self.scripts.is_empty()
+                && self
+                    .scripts
+                    .contains(&harfbuzz::script_tag_for_codepoint(c)))
+    }
+
+    /// Returns true if the font may be used to shape all of `s`.
+    /// Only the first codepoint of each grapheme is considered, so
+    /// that combining marks don't prevent eg: a Devanagari font from
+    /// being used for a Devanagari cluster.
+    fn covers(&self, s: &str) -> bool {
+        s.graphemes(true)
+            .all(|g| g.chars().next().map(|c| self.contains(c)).unwrap_or(true))
+    }
+}
+
 pub struct HarfbuzzShaper {
     handles: Vec<ParsedFont>,
     fonts: Vec<RefCell<Option<FontPair>>>,
+    restrictions: Vec<Option<Restriction>>,
+    /// The order in which the fonts are considered during fallback.
+    /// Restricted fonts come first so that the codepoints that they
+    /// are restricted to are pinned to them.
+    order: Vec<FallbackIdx>,
     lib: ftwrap::Library,
     metrics: RefCell<HashMap<MetricsKey, FontMetrics>>,
     features: Vec<harfbuzz::hb_feature_t>,
     lang: harfbuzz::hb_language_t,
+    load_flags: i32,
 }
 
 #[derive(Error, Debug)]
@@ -97,7 +143,11 @@ fn is_question_string(s: &str) -> bool {
 }
 
 impl HarfbuzzShaper {
-    pub fn new(config: &ConfigHandle, handles: &[ParsedFont]) -> anyhow::Result<Self> {
+    pub fn new(
+        handles: &[ParsedFont],
+        harfbuzz_features: &[String],
+        load_flags: i32,
+    ) -> anyhow::Result<Self> {
         let lib = ftwrap::Library::new()?;
         let handles = handles.to_vec();
         let mut fonts = vec![];
@@ -105,10 +155,15 @@ impl HarfbuzzShaper {
             fonts.push(RefCell::new(None));
         }
 
+        let restrictions: Vec<Option<Restriction>> = handles.iter().map(Restriction::new).collect();
+        let mut order: Vec<FallbackIdx> = (0..handles.len())
+            .filter(|&idx| restrictions[idx].is_some())
+            .collect();
+        order.extend((0..handles.len()).filter(|&idx| restrictions[idx].is_none()));
+
         let lang = harfbuzz::language_from_string("en")?;
 
-        let features: Vec<harfbuzz::hb_feature_t> = config
-            .harfbuzz_features
+        let features: Vec<harfbuzz::hb_feature_t> = harfbuzz_features
             .iter()
             .filter_map(|s| harfbuzz::feature_from_string(s).ok())
             .collect();
@@ -116,10 +171,13 @@ impl HarfbuzzShaper {
         Ok(Self {
             fonts,
             handles,
+            restrictions,
+            order,
             lib,
             metrics: RefCell::new(HashMap::new()),
             features,
             lang,
+            load_flags,
         })
     }
 
@@ -136,8 +194,7 @@ impl HarfbuzzShaper {
                     log::trace!("shaper wants {} {:?}", font_idx, handle);
                     let face = self.lib.face_from_locator(&handle.handle)?;
                     let mut font = harfbuzz::Font::new(face.face);
-                    let (load_flags, _) = ftwrap::compute_load_flags_from_config();
-                    font.set_load_flags(load_flags);
+                    font.set_load_flags(self.load_flags);
                     *opt_pair = Some(FontPair {
                         face,
                         font,
@@ -157,9 +214,11 @@ impl HarfbuzzShaper {
         }
     }
 
+    /// Shapes `s` using the first suitable font at or after position
+    /// `fallback_pos` in the fallback order
     fn do_shape(
         &self,
-        mut font_idx: FallbackIdx,
+        mut fallback_pos: usize,
         s: &str,
         font_size: f64,
         dpi: u32,
@@ -178,20 +237,33 @@ impl HarfbuzzShaper {
 
         let cell_width;
         let shaped_any;
-        let initial_font_idx = font_idx;
+        let font_idx;
+        let initial_fallback_pos = fallback_pos;
 
         loop {
-            match self.load_fallback(font_idx).context("load_fallback")? {
+            let idx = self
+                .order
+                .get(fallback_pos)
+                .copied()
+                .unwrap_or(fallback_pos);
+            if let Some(Some(restriction)) = self.restrictions.get(idx) {
+                if !restriction.covers(s) {
+                    fallback_pos += 1;
+                    continue;
+                }
+            }
+            match self.load_fallback(idx).context("load_fallback")? {
                 Some(mut pair) => {
                     // Ignore presentation if we've reached the last resort font
-                    if font_idx + 1 < self.fonts.len() {
+                    if fallback_pos + 1 < self.fonts.len() {
                         if let Some(p) = presentation {
                             if pair.presentation != p {
-                                font_idx += 1;
+                                fallback_pos += 1;
                                 continue;
                             }
                         }
                     }
+                    font_idx = idx;
                     let size = pair.face.set_font_size(font_size, dpi)?;
                     // Tell harfbuzz to recompute important font metrics!
                     pair.font.font_changed();
@@ -221,7 +293,7 @@ impl HarfbuzzShaper {
             }
         }
 
-        if font_idx > 0 && font_idx + 1 == self.fonts.len() {
+        if fallback_pos > 0 && fallback_pos + 1 == self.fonts.len() {
             // We are the last resort font, so each codepoint is considered
             // to be worthy of a fallback lookup
             for c in s.chars() {
@@ -241,7 +313,7 @@ impl HarfbuzzShaper {
                 // but might potentially discover the text presentation for
                 // that glyph in a fallback font and swap it out a little
                 // later after a flash of showing the emoji one.
-                return self.do_shape(initial_font_idx, s, font_size, dpi, no_glyphs, None);
+                return self.do_shape(initial_fallback_pos, s, font_size, dpi, no_glyphs, None);
             }
         }
 
@@ -323,7 +395,7 @@ impl HarfbuzzShaper {
                 */
 
                 let mut shape = match self.do_shape(
-                    font_idx + 1,
+                    fallback_pos + 1,
                     substr,
                     font_size,
                     dpi,
@@ -410,6 +482,60 @@ impl HarfbuzzShaper {
 
         Ok(cluster)
     }
+
+    /// Splits the text into runs according to which of the restricted
+    /// fonts, if any, claims each grapheme, and shapes each run in turn
+    fn shape_restricted_runs(
+        &self,
+        text: &str,
+        font_size: f64,
+        dpi: u32,
+        no_glyphs: &mut Vec<char>,
+        presentation: Option<Presentation>,
+    ) -> anyhow::Result<Vec<GlyphInfo>> {
+        let claim = |g: &str| -> Option<FallbackIdx> {
+            let c = g.chars().next()?;
+            self.order.iter().copied().find(|&idx| {
+                self.restrictions[idx]
+                    .as_ref()
+                    .map(|r| r.contains(c))
+                    .unwrap_or(false)
+            })
+        };
+
+        let mut runs = vec![];
+        let mut run_start = 0;
+        let mut run_claim = None;
+        for (idx, g) in text.grapheme_indices(true) {
+            let this_claim = claim(g);
+            if idx > 0 && this_claim != run_claim {
+                runs.push(run_start..idx);
+                run_start = idx;
+            }
+            run_claim = this_claim;
+        }
+        if run_start < text.len() {
+            runs.push(run_start..text.len());
+        }
+
+        let mut glyphs = vec![];
+        for run in runs {
+            let mut shaped = self.do_shape(
+                0,
+                &text[run.clone()],
+                font_size,
+                dpi,
+                no_glyphs,
+                presentation,
+            )?;
+            for info in &mut shaped {
+                info.cluster += run.start as u32;
+            }
+            glyphs.append(&mut shaped);
+        }
+
+        Ok(glyphs)
+    }
 }
 
 impl FontShaper for HarfbuzzShaper {
@@ -423,7 +549,11 @@ impl FontShaper for HarfbuzzShaper {
     ) -> anyhow::Result<Vec<GlyphInfo>> {
         log::trace!("shape byte_len={} `{}`", text.len(), text.escape_debug());
         let start = std::time::Instant::now();
-        let result = self.do_shape(0, text, size, dpi, no_glyphs, presentation);
+        let result = if self.restrictions.iter().any(Option::is_some) {
+            self.shape_restricted_runs(text, size, dpi, no_glyphs, presentation)
+        } else {
+            self.do_shape(0, text, size, dpi, no_glyphs, presentation)
+        };
         metrics::histogram!("shape.harfbuzz", start.elapsed());
         /*
         if let Ok(glyphs) = &result {
@@ -561,6 +691,10 @@ mod test {
                     weight: Default::default(),
                     is_fallback: false,
                     is_synthetic: false,
+                    synthesize_bold: None,
+                    synthesize_italic: None,
+                    ranges: vec![],
+                    scripts: vec![],
                     italic: false,
                 },
                 14,
@@ -570,7 +704,8 @@ mod test {
 
         let config = config::configuration();
 
-        let shaper = HarfbuzzShaper::new(&config, &[handle]).unwrap();
+        let (load_flags, _) = ftwrap::compute_load_flags(&config);
+        let shaper = HarfbuzzShaper::new(&[handle], &config.harfbuzz_features, load_flags).unwrap();
         {
             let mut no_glyphs = vec![];
             let info = shaper.shape("abc", 10., 72, &mut no_glyphs, None).unwrap();
@@ -83,14 +83,19 @@ pub trait FontShaper {
 
 pub use config::FontShaperSelection;
 
+/// Creates a shaper for the fonts in `handles`.
+/// `harfbuzz_features` overrides the features from the config.
 pub fn new_shaper(
     config: &config::ConfigHandle,
     handles: &[ParsedFont],
+    harfbuzz_features: Option<&[String]>,
 ) -> anyhow::Result<Box<dyn FontShaper>> {
     match config.font_shaper {
-        FontShaperSelection::Harfbuzz => {
-            Ok(Box::new(harfbuzz::HarfbuzzShaper::new(config, handles)?))
-        }
+        FontShaperSelection::Harfbuzz => Ok(Box::new(harfbuzz::HarfbuzzShaper::new(
+            handles,
+            harfbuzz_features.unwrap_or(&config.harfbuzz_features),
+            crate::ftwrap::compute_load_flags(config).0,
+        )?)),
         FontShaperSelection::Allsorts => {
             anyhow::bail!("The incomplete Allsorts shaper has been removed");
         }
@@ -1,4 +1,4 @@
-use config::SshParameters;
+use config::{SerialFlowControl, SerialParity, SshParameters};
 use std::ffi::OsString;
 use structopt::StructOpt;
 
@@ -95,9 +95,33 @@ pub struct SerialCommand {
     #[structopt(long = "baud")]
     pub baud: Option<usize>,
 
+    /// Set the flow control.  The default is software (XON/XOFF).
+    #[structopt(long = "flow-control", possible_values = &["none", "hardware", "software"])]
+    pub flow_control: Option<SerialFlowControl>,
+
+    /// Set the parity.  The default is none.
+    #[structopt(long = "parity", possible_values = &["none", "odd", "even"])]
+    pub parity: Option<SerialParity>,
+
+    /// Set the number of stop bits.  The default is 1.
+    #[structopt(long = "stop-bits", possible_values = &["1", "2"])]
+    pub stop_bits: Option<u8>,
+
+    /// Set the number of data bits.  The default is 8.
+    #[structopt(long = "data-bits", possible_values = &["5", "6", "7", "8"])]
+    pub data_bits: Option<u8>,
+
+    /// Rather than closing the window when the device is disconnected,
+    /// wait for it to come back and resume
+    #[structopt(long = "reconnect")]
+    pub reconnect: bool,
+
     /// Specifies the serial device name.
     /// On Windows systems this can be a name like `COM0`.
-    /// On posix systems this will be something like `/dev/ttyUSB0`
+    /// On posix systems this will be something like `/dev/ttyUSB0`.
+    /// This can also be the name of one of the `serial_ports`
+    /// in the configuration, whose settings are then used, unless
+    /// they are overridden by the options above.
     #[structopt(parse(from_os_str))]
     pub port: OsString,
 }
@@ -135,3 +159,15 @@ pub struct LsFontsCommand {
     #[structopt(long = "text", conflicts_with = "list-system")]
     pub text: Option<String>,
 }
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct ShowKeysCommand {
+    /// Emit the key assignments as a lua `keys` table that can be
+    /// pasted into your configuration file
+    #[structopt(long = "lua", conflicts_with = "json")]
+    pub lua: bool,
+
+    /// Emit the key assignments as JSON
+    #[structopt(long = "json")]
+    pub json: bool,
+}
@@ -0,0 +1,113 @@
+//! Helpers for enforcing a minimum contrast ratio between text
+//! and its background, using the relative luminance and contrast
+//! ratio definitions from WCAG 2.
+//! <https://www.w3.org/TR/WCAG20/#relativeluminancedef>
+use wezterm_term::color::RgbColor;
+
+/// Returns the relative luminance of the color, in the range 0.0-1.0
+pub fn relative_luminance(color: RgbColor) -> f32 {
+    let (r, g, b, _) = color.to_linear_tuple_rgba();
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn ratio_of_luminance(a: f32, b: f32) -> f32 {
+    let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// Returns the contrast ratio between the two colors, in the
+/// range 1.0 (no contrast) to 21.0 (black on white)
+pub fn contrast_ratio(a: RgbColor, b: RgbColor) -> f32 {
+    ratio_of_luminance(relative_luminance(a), relative_luminance(b))
+}
+
+fn mix(a: RgbColor, b: RgbColor, amount: f32) -> RgbColor {
+    let (ar, ag, ab, _) = a.to_tuple_rgba();
+    let (br, bg, bb, _) = b.to_tuple_rgba();
+    RgbColor::new_f32(
+        ar + (br - ar) * amount,
+        ag + (bg - ag) * amount,
+        ab + (bb - ab) * amount,
+    )
+}
+
+/// Returns a version of `fg` that has at least `min_ratio` contrast
+/// against `bg`.  The foreground is moved towards white or black,
+/// whichever direction can satisfy the ratio (preferring the one that
+/// keeps it on the same side of the background that it started on),
+/// and is changed as little as possible so that it retains its hue.
+/// If neither direction can reach the ratio, the more contrasting
+/// extreme is returned.
+pub fn ensure_contrast(fg: RgbColor, bg: RgbColor, min_ratio: f32) -> RgbColor {
+    let bg_lum = relative_luminance(bg);
+    let fg_lum = relative_luminance(fg);
+    if ratio_of_luminance(fg_lum, bg_lum) >= min_ratio {
+        return fg;
+    }
+
+    let white = RgbColor::new_8bpc(0xff, 0xff, 0xff);
+    let black = RgbColor::new_8bpc(0, 0, 0);
+    let white_ratio = ratio_of_luminance(1.0, bg_lum);
+    let black_ratio = ratio_of_luminance(0.0, bg_lum);
+
+    let target = if fg_lum >= bg_lum {
+        if white_ratio >= min_ratio || white_ratio >= black_ratio {
+            white
+        } else {
+            black
+        }
+    } else if black_ratio >= min_ratio || black_ratio >= white_ratio {
+        black
+    } else {
+        white
+    };
+
+    if contrast_ratio(target, bg) <= min_ratio {
+        return target;
+    }
+
+    // Binary search for the smallest adjustment that satisfies
+    // the ratio; the ratio increases monotonically as we approach
+    // the target.
+    let mut low = 0.0f32;
+    let mut high = 1.0f32;
+    for _ in 0..12 {
+        let mid = (low + high) / 2.;
+        if contrast_ratio(mix(fg, target, mid), bg) >= min_ratio {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    mix(fg, target, high)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn contrast() {
+        let white = RgbColor::new_8bpc(0xff, 0xff, 0xff);
+        let black = RgbColor::new_8bpc(0, 0, 0);
+        assert!((contrast_ratio(white, black) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio(black, black) - 1.0).abs() < 0.01);
+
+        let dark_grey = RgbColor::new_8bpc(0x22, 0x22, 0x22);
+        assert!(contrast_ratio(dark_grey, black) < 3.0);
+        let adjusted = ensure_contrast(dark_grey, black, 3.0);
+        assert!(contrast_ratio(adjusted, black) >= 3.0);
+        // It was only nudged, rather than being forced to white
+        assert!(contrast_ratio(adjusted, black) < 3.5);
+        assert!(relative_luminance(adjusted) > relative_luminance(dark_grey));
+
+        // Already sufficient contrast is left alone
+        assert_eq!(ensure_contrast(white, black, 3.0), white);
+
+        // Dark text on a light background gets darker
+        let light_grey = RgbColor::new_8bpc(0xcc, 0xcc, 0xcc);
+        let adjusted = ensure_contrast(light_grey, white, 3.0);
+        assert!(relative_luminance(adjusted) < relative_luminance(light_grey));
+        assert!(contrast_ratio(adjusted, white) >= 3.0);
+    }
+}
@@ -35,6 +35,61 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    /// Cells of a 2 by 4 grid, numbered left to right, top to bottom,
+    /// as in the names of the octant characters
+    pub struct Octant: u8{
+        const ONE = 1<<0;
+        const TWO = 1<<1;
+        const THREE = 1<<2;
+        const FOUR = 1<<3;
+        const FIVE = 1<<4;
+        const SIX = 1<<5;
+        const SEVEN = 1<<6;
+        const EIGHT = 1<<7;
+    }
+}
+
+/// Returns true if the octant pattern has a character of its own
+/// outside of the octant block (eg: a half block or a quadrant),
+/// and was therefore omitted from the octant block.
+const fn octant_is_unified(pattern: u8) -> bool {
+    matches!(
+        pattern,
+        // Empty and full
+        0x00 | 0xff
+        // Upper, lower, left and right halves
+        | 0x0f | 0xf0 | 0x55 | 0xaa
+        // Quadrants
+        | 0x05 | 0x0a | 0x50 | 0xa0 | 0xa5 | 0x5a | 0x5f | 0xaf | 0xf5 | 0xfa
+        // Upper and lower one and three quarters
+        | 0x03 | 0xc0 | 0x3f | 0xfc
+        // The corner octants
+        | 0x01 | 0x02 | 0x40 | 0x80
+        // Middle left and middle right one quarter
+        | 0x14 | 0x28
+    )
+}
+
+const fn octant_patterns() -> [u8; 230] {
+    let mut patterns = [0u8; 230];
+    let mut idx = 0;
+    let mut pattern = 0u8;
+    while pattern < 0xff {
+        if !octant_is_unified(pattern) {
+            patterns[idx] = pattern;
+            idx += 1;
+        }
+        pattern += 1;
+    }
+    patterns
+}
+
+/// The octant patterns of U+1CD00 - U+1CDE5, in codepoint order.
+/// The block is laid out in ascending order of the pattern,
+/// skipping the patterns that are unified with other characters.
+const OCTANTS: [u8; 230] = octant_patterns();
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum BlockAlpha {
     /// 100%
@@ -143,6 +198,8 @@ pub enum BlockKey {
     Quadrants(Quadrant),
     /// A combination of sextants <https://unicode.org/charts/PDF/U1FB00.pdf>
     Sextants(Sextant),
+    /// A combination of octants <https://www.unicode.org/charts/PDF/U1CC00.pdf>
+    Octants(Octant),
     /// A braille dot pattern
     Braille(u8),
 
@@ -227,6 +284,55 @@ impl PolyStyle {
     }
 }
 
+/// The wavy right hand edge of the powerline flame glyphs,
+/// from the top left to the bottom left of the cell
+const FLAME_EDGE: &[PolyCommand] = &[
+    PolyCommand::MoveTo(BlockCoord::Zero, BlockCoord::Zero),
+    PolyCommand::QuadTo {
+        control: (BlockCoord::One, BlockCoord::Frac(1, 4)),
+        to: (BlockCoord::Frac(1, 2), BlockCoord::Frac(1, 2)),
+    },
+    PolyCommand::QuadTo {
+        control: (BlockCoord::One, BlockCoord::Frac(3, 4)),
+        to: (BlockCoord::Zero, BlockCoord::One),
+    },
+];
+const FLAME: &[PolyCommand] = &[
+    PolyCommand::MoveTo(BlockCoord::Zero, BlockCoord::Zero),
+    PolyCommand::QuadTo {
+        control: (BlockCoord::One, BlockCoord::Frac(1, 4)),
+        to: (BlockCoord::Frac(1, 2), BlockCoord::Frac(1, 2)),
+    },
+    PolyCommand::QuadTo {
+        control: (BlockCoord::One, BlockCoord::Frac(3, 4)),
+        to: (BlockCoord::Zero, BlockCoord::One),
+    },
+    PolyCommand::Close,
+];
+const FLAME_EDGE_MIRRORED: &[PolyCommand] = &[
+    PolyCommand::MoveTo(BlockCoord::One, BlockCoord::Zero),
+    PolyCommand::QuadTo {
+        control: (BlockCoord::Zero, BlockCoord::Frac(1, 4)),
+        to: (BlockCoord::Frac(1, 2), BlockCoord::Frac(1, 2)),
+    },
+    PolyCommand::QuadTo {
+        control: (BlockCoord::Zero, BlockCoord::Frac(3, 4)),
+        to: (BlockCoord::One, BlockCoord::One),
+    },
+];
+const FLAME_MIRRORED: &[PolyCommand] = &[
+    PolyCommand::MoveTo(BlockCoord::One, BlockCoord::Zero),
+    PolyCommand::QuadTo {
+        control: (BlockCoord::Zero, BlockCoord::Frac(1, 4)),
+        to: (BlockCoord::Frac(1, 2), BlockCoord::Frac(1, 2)),
+    },
+    PolyCommand::QuadTo {
+        control: (BlockCoord::Zero, BlockCoord::Frac(3, 4)),
+        to: (BlockCoord::One, BlockCoord::One),
+    },
+    PolyCommand::Close,
+];
+
 impl BlockKey {
     pub fn filter_out_synthetic(glyphs: &mut Vec<char>) {
         let config = config::configuration();
@@ -3627,6 +3733,60 @@ impl BlockKey {
                 intensity: BlockAlpha::Full,
                 style: PolyStyle::Outline,
             }]),
+            // [] Powerline flame thick
+            0xe0c0 => Self::Poly(&[Poly {
+                path: FLAME,
+                intensity: BlockAlpha::Full,
+                style: PolyStyle::Fill,
+            }]),
+            // [] Powerline flame thin
+            0xe0c1 => Self::Poly(&[Poly {
+                path: FLAME_EDGE,
+                intensity: BlockAlpha::Full,
+                style: PolyStyle::Outline,
+            }]),
+            // [] Powerline flame thick mirrored
+            0xe0c2 => Self::Poly(&[Poly {
+                path: FLAME_MIRRORED,
+                intensity: BlockAlpha::Full,
+                style: PolyStyle::Fill,
+            }]),
+            // [] Powerline flame thin mirrored
+            0xe0c3 => Self::Poly(&[Poly {
+                path: FLAME_EDGE_MIRRORED,
+                intensity: BlockAlpha::Full,
+                style: PolyStyle::Outline,
+            }]),
+            // [] Powerline inverted filled right arrow
+            0xe0d6 => Self::Poly(&[Poly {
+                path: &[
+                    PolyCommand::MoveTo(BlockCoord::Zero, BlockCoord::Zero),
+                    PolyCommand::LineTo(BlockCoord::One, BlockCoord::Zero),
+                    PolyCommand::LineTo(BlockCoord::One, BlockCoord::One),
+                    PolyCommand::LineTo(BlockCoord::Zero, BlockCoord::One),
+                    PolyCommand::LineTo(BlockCoord::One, BlockCoord::Frac(1, 2)),
+                    PolyCommand::Close,
+                ],
+                intensity: BlockAlpha::Full,
+                style: PolyStyle::Fill,
+            }]),
+            // [] Powerline inverted filled left arrow
+            0xe0d7 => Self::Poly(&[Poly {
+                path: &[
+                    PolyCommand::MoveTo(BlockCoord::One, BlockCoord::Zero),
+                    PolyCommand::LineTo(BlockCoord::Zero, BlockCoord::Zero),
+                    PolyCommand::LineTo(BlockCoord::Zero, BlockCoord::One),
+                    PolyCommand::LineTo(BlockCoord::One, BlockCoord::One),
+                    PolyCommand::LineTo(BlockCoord::Zero, BlockCoord::Frac(1, 2)),
+                    PolyCommand::Close,
+                ],
+                intensity: BlockAlpha::Full,
+                style: PolyStyle::Fill,
+            }]),
+            // Octants: 𜴀 𜴁 𜴂 ... 𜷤 𜷥
+            n @ 0x1cd00..=0x1cde5 => {
+                Self::Octants(Octant::from_bits_truncate(OCTANTS[(n - 0x1cd00) as usize]))
+            }
             _ => return None,
         })
     }
@@ -3759,6 +3919,8 @@ impl<T: Texture2d> GlyphCache<T> {
                 descender_plus_two: 0,
                 underline_height: *underline_height,
                 strike_row: 0,
+                curly_height: 0,
+                curly_period: 0.,
                 cell_size: cell_size.clone(),
             },
             _ => render_metrics.clone(),
@@ -3858,6 +4020,36 @@ impl<T: Texture2d> GlyphCache<T> {
                     );
                 }
             }
+            BlockKey::Octants(o) => {
+                let y_quarter = metrics.cell_size.height as f32 / 4.;
+                let x_half = metrics.cell_size.width as f32 / 2.;
+                let width = metrics.cell_size.width as usize;
+                let height = metrics.cell_size.height as usize;
+
+                // Use the same boundaries as the other block elements,
+                // so that adjacent cells line up without seams
+                let x_edges = [0, scale(x_half), width];
+                let y_edges = [
+                    0,
+                    scale(y_quarter),
+                    scale(y_quarter * 2.),
+                    scale(y_quarter * 3.),
+                    height,
+                ];
+
+                for row in 0..4 {
+                    for col in 0..2 {
+                        let octant = Octant::from_bits_truncate(1 << (row * 2 + col));
+                        if o.contains(octant) {
+                            fill_rect(
+                                &mut buffer,
+                                x_edges[col]..x_edges[col + 1],
+                                y_edges[row]..y_edges[row + 1],
+                            );
+                        }
+                    }
+                }
+            }
             BlockKey::Braille(dots_pattern) => {
                 // `dots_pattern` is a byte whose bits corresponds to dots
                 // on a 2 by 4 dots-grid.
@@ -3868,10 +4060,14 @@ impl<T: Texture2d> GlyphCache<T> {
                 // 7 8  <- This last line is filled last (for the remaining 192 symbols)
                 //
                 // NOTE: for simplicity & performance reasons, a dot is a square not a circle.
+                //
+                // The dots are snapped to whole pixels so that they are all
+                // the same size and line up exactly with the dots in the
+                // neighboring cells.
 
                 let dot_area_width = metrics.cell_size.width as f32 / 2.;
                 let dot_area_height = metrics.cell_size.height as f32 / 4.;
-                let square_length = dot_area_width / 2.;
+                let square_length = (dot_area_width / 2.).round().max(1.);
                 let topleft_offset_x = dot_area_width / 2. - square_length / 2.;
                 let topleft_offset_y = dot_area_height / 2. - square_length / 2.;
 
@@ -3903,8 +4099,8 @@ impl<T: Texture2d> GlyphCache<T> {
                         // Bit for this dot position is not set
                         continue;
                     }
-                    let topleft_x = (*dot_pos_x) * dot_area_width + topleft_offset_x;
-                    let topleft_y = (*dot_pos_y) * dot_area_height + topleft_offset_y;
+                    let topleft_x = ((*dot_pos_x) * dot_area_width + topleft_offset_x).round();
+                    let topleft_y = ((*dot_pos_y) * dot_area_height + topleft_offset_y).round();
 
                     let path = PathBuilder::from_rect(
                         tiny_skia::Rect::from_xywh(
@@ -3965,3 +4161,29 @@ fn fill_rect(buffer: &mut Image, x: Range<usize>, y: Range<usize>) {
 fn scale(f: f32) -> usize {
     f.ceil().max(1.) as usize
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn octants() {
+        // [𜴀] BLOCK OCTANT-3
+        assert_eq!(
+            BlockKey::from_char('\u{1cd00}'),
+            Some(BlockKey::Octants(Octant::THREE))
+        );
+        // [𜴂] BLOCK OCTANT-123
+        assert_eq!(
+            BlockKey::from_char('\u{1cd02}'),
+            Some(BlockKey::Octants(Octant::ONE | Octant::TWO | Octant::THREE))
+        );
+        // [𜷥] BLOCK OCTANT-2345678
+        assert_eq!(
+            BlockKey::from_char('\u{1cde5}'),
+            Some(BlockKey::Octants(Octant::all() - Octant::ONE))
+        );
+        // Every slot in the table was populated
+        assert!(OCTANTS.iter().all(|&pattern| pattern != 0));
+    }
+}
@@ -43,6 +43,10 @@ impl GuiFrontEnd {
                     MuxNotification::WindowInvalidated(_) => {}
                     MuxNotification::PaneOutput(_) => {}
                     MuxNotification::PaneAdded(_) => {}
+                    MuxNotification::PaneMoved { .. } => {}
+                    MuxNotification::PaneMonitor { .. } => {}
+                    MuxNotification::DomainStateChanged(_) => {}
+                    MuxNotification::DomainReconnected { .. } => {}
                     MuxNotification::Alert {
                         pane_id: _,
                         alert:
@@ -72,6 +76,10 @@ impl GuiFrontEnd {
                     | MuxNotification::Alert {
                         pane_id: _,
                         alert: Alert::TitleMaybeChanged,
+                    }
+                    | MuxNotification::Alert {
+                        pane_id: _,
+                        alert: Alert::SetUserVar { .. },
                     } => {}
                     MuxNotification::Empty => {
                         if mux::activity::Activity::count() == 0 {
@@ -19,9 +19,9 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 use std::rc::Rc;
 use std::sync::{Arc, MutexGuard};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use termwiz::color::RgbColor;
-use termwiz::image::{ImageData, ImageDataType};
+use termwiz::image::{AnimationControl, AnimationState, ImageData, ImageDataType};
 use termwiz::surface::CursorShape;
 use wezterm_font::units::*;
 use wezterm_font::{FontConfiguration, GlyphInfo, LoadedFont};
@@ -205,10 +205,44 @@ impl<'a> BitmapImage for DecodedImageHandle<'a> {
     }
 }
 
+/// Returns the frame that follows `current`, skipping over gapless
+/// (0-duration) frames, or None if playback should remain on the
+/// current frame, either because the loop count has been exhausted
+/// or because the animation is still loading.
+fn next_frame(
+    current: usize,
+    durations: &[Duration],
+    control: &AnimationControl,
+    loops_played: &mut u32,
+) -> Option<usize> {
+    let mut frame = current;
+    for _ in 0..durations.len() {
+        frame += 1;
+        if frame >= durations.len() {
+            if control.state == AnimationState::Loading {
+                return None;
+            }
+            if control.loops != 0 && *loops_played + 1 >= control.loops {
+                return None;
+            }
+            *loops_played += 1;
+            frame = 0;
+        }
+        if !durations[frame].is_zero() {
+            return Some(frame);
+        }
+    }
+    None
+}
+
 #[derive(Debug)]
 pub struct DecodedImage {
     frame_start: Instant,
     current_frame: usize,
+    /// How many times the animation has wrapped around to the start
+    loops_played: u32,
+    /// The AnimationControl::generation that we last acted upon
+    control_generation: usize,
     image: Arc<ImageData>,
 }
 
@@ -219,26 +253,27 @@ impl DecodedImage {
         Self {
             frame_start: Instant::now(),
             current_frame: 0,
+            loops_played: 0,
+            control_generation: 0,
             image: Arc::new(image),
         }
     }
 
     fn load(image_data: &Arc<ImageData>) -> Self {
+        let control_generation = image_data.animation().generation;
         match &*image_data.data() {
             ImageDataType::EncodedFile(_) => {
                 log::warn!("Unexpected ImageDataType::EncodedFile; either file is unreadable or we missed a .decode call somewhere");
                 Self::placeholder()
             }
             ImageDataType::AnimRgba8 { durations, .. } => {
-                let current_frame = if durations.len() > 1 && durations[0].as_millis() == 0 {
-                    // Skip possible 0-duration root frame
-                    1
-                } else {
-                    0
-                };
+                // Skip possible 0-duration root frame(s)
+                let current_frame = durations.iter().position(|d| !d.is_zero()).unwrap_or(0);
                 Self {
                     frame_start: Instant::now(),
                     current_frame,
+                    loops_played: 0,
+                    control_generation,
                     image: Arc::clone(image_data),
                 }
             }
@@ -246,6 +281,8 @@ impl DecodedImage {
             _ => Self {
                 frame_start: Instant::now(),
                 current_frame: 0,
+                loops_played: 0,
+                control_generation,
                 image: Arc::clone(image_data),
             },
         }
@@ -589,6 +626,8 @@ impl<T: Texture2d> GlyphCache<T> {
         decoded: &mut DecodedImage,
         padding: Option<usize>,
     ) -> anyhow::Result<(Sprite<T>, Option<Instant>)> {
+        // Take a copy so that we don't hold both locks at once
+        let control = decoded.image.animation().clone();
         let mut handle = DecodedImageHandle {
             h: decoded.image.data(),
             current_frame: decoded.current_frame,
@@ -609,26 +648,43 @@ impl<T: Texture2d> GlyphCache<T> {
                 durations,
                 ..
             } => {
+                let now = Instant::now();
+                if control.generation != decoded.control_generation {
+                    decoded.control_generation = control.generation;
+                    decoded.loops_played = 0;
+                    if let Some(frame) = control.current_frame {
+                        decoded.current_frame = frame;
+                        decoded.frame_start = now;
+                    }
+                }
+                // Frames may have been deleted since we last looked
+                if decoded.current_frame >= frames.len() {
+                    decoded.current_frame = frames.len() - 1;
+                }
+
                 let mut next = None;
-                if frames.len() > 1 {
-                    let now = Instant::now();
+                if frames.len() > 1 && control.state != AnimationState::Stopped {
                     let mut next_due = decoded.frame_start + durations[decoded.current_frame];
                     if now >= next_due {
-                        // Advance to next frame
-                        decoded.current_frame += 1;
-                        if decoded.current_frame >= frames.len() {
-                            decoded.current_frame = 0;
-                            // Skip potential 0-duration root frame
-                            if durations[0].as_millis() == 0 && frames.len() > 1 {
-                                decoded.current_frame += 1;
-                            }
+                        if let Some(frame) = next_frame(
+                            decoded.current_frame,
+                            durations,
+                            &control,
+                            &mut decoded.loops_played,
+                        ) {
+                            decoded.current_frame = frame;
+                            decoded.frame_start = now;
+                            next_due = now + durations[frame];
+                            next.replace(next_due);
+                        } else if control.state == AnimationState::Loading {
+                            // Hold the last frame until more frames arrive
+                            // or the application changes the state
+                            next.replace(now + Duration::from_millis(40));
                         }
-                        decoded.frame_start = now;
-                        next_due = decoded.frame_start + durations[decoded.current_frame];
-                        handle.current_frame = decoded.current_frame;
+                    } else {
+                        next.replace(next_due);
                     }
-
-                    next.replace(next_due);
+                    handle.current_frame = decoded.current_frame;
                 }
 
                 let hash = hashes[decoded.current_frame];
@@ -641,10 +697,7 @@ impl<T: Texture2d> GlyphCache<T> {
 
                 frame_cache.insert(hash, sprite.clone());
 
-                return Ok((
-                    sprite,
-                    Some(decoded.frame_start + durations[decoded.current_frame]),
-                ));
+                return Ok((sprite, next));
             }
             ImageDataType::EncodedFile(_) => unreachable!(),
         }
@@ -781,14 +834,16 @@ impl<T: Texture2d> GlyphCache<T> {
 
         let draw_curly = |buffer: &mut Image| {
             let max_y = metrics.cell_size.height as usize - 1;
-            let x_factor = (2. * std::f32::consts::PI) / metrics.cell_size.width as f32;
-
-            // Have the wave go from the descender to the bottom of the cell
-            let wave_height =
-                metrics.cell_size.height - (cell_rect.origin.y + metrics.descender_row);
-
-            let half_height = (wave_height as f32 / 4.).max(1.);
-            let y = (cell_rect.origin.y + metrics.descender_row) as usize - half_height as usize;
+            let x_factor = (2. * std::f32::consts::PI) / metrics.curly_period;
+
+            // Have the wave hang down from the underline position, so that
+            // it stays clear of the descenders, moving it up only as far
+            // as is needed for it to fit in the cell
+            let half_height = (metrics.curly_height as f32 / 2.).max(1.);
+            let wave_extent = 2 * half_height as isize + metrics.underline_height;
+            let y = (cell_rect.origin.y + metrics.descender_row)
+                .min(metrics.cell_size.height - wave_extent)
+                .max(0) as usize;
 
             fn add(x: usize, y: usize, val: u8, max_y: usize, buffer: &mut Image) {
                 let y = y.min(max_y);
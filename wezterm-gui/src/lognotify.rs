@@ -0,0 +1,116 @@
+//! Raises a toast notification when an error is logged, if enabled
+//! via the `notify_on_error_log` configuration option, so that problems
+//! don't go unnoticed until the debug overlay happens to be opened.
+//! Clicking the notification opens the debug overlay at the entry.
+use crate::termwindow::{last_focused_window, TermWindowNotif};
+use ::window::WindowOps;
+use config::ConfigSubscription;
+use env_bootstrap::ringlog::{self, Entry, ObserverId};
+use log::Level;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wezterm_toast_notification::{ClickHandler, ToastNotification};
+
+/// A flood of errors produces at most one notification per interval
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Failing to show a notification is logged as an error by this
+/// target, which would otherwise lead to a notification about
+/// failing to show a notification
+const ALWAYS_EXCLUDED: &[&str] = &["wezterm_toast_notification"];
+
+lazy_static::lazy_static! {
+    static ref LAST_NOTIFIED: Mutex<Option<Instant>> = Mutex::new(None);
+    /// The observer is only registered while notifications are enabled,
+    /// as the ring otherwise copies every entry for it
+    static ref OBSERVER: Mutex<Option<ObserverId>> = Mutex::new(None);
+    static ref CONFIG_SUBSCRIPTION: Mutex<Option<ConfigSubscription>> = Mutex::new(None);
+}
+
+fn is_excluded(target: &str, excluded: &[String]) -> bool {
+    ALWAYS_EXCLUDED
+        .iter()
+        .any(|prefix| target.starts_with(prefix))
+        || excluded.iter().any(|prefix| target.starts_with(prefix))
+}
+
+fn on_entry(entry: &Entry) {
+    if entry.level != Level::Error {
+        return;
+    }
+    let config = config::configuration();
+    if !config.notify_on_error_log
+        || is_excluded(&entry.target, &config.notify_on_error_log_exclude_targets)
+    {
+        return;
+    }
+
+    {
+        let mut last = LAST_NOTIFIED.lock().unwrap();
+        let now = Instant::now();
+        if let Some(last) = *last {
+            if now.duration_since(last) < NOTIFY_INTERVAL {
+                return;
+            }
+        }
+        last.replace(now);
+    }
+
+    let message = format!(
+        "{}\nClick to view it in the debug overlay",
+        entry.msg.lines().next().unwrap_or("")
+    );
+    let seq = entry.seq;
+    promise::spawn::spawn_into_main_thread(async move {
+        ToastNotification {
+            title: "Wezterm Error".to_string(),
+            message,
+            url: None,
+            timeout: Some(NOTIFY_INTERVAL),
+            on_click: Some(ClickHandler::new(move || show_entry(seq))),
+        }
+        .show();
+    })
+    .detach();
+}
+
+/// Opens the debug overlay in the most recently focused window,
+/// browsing the log with the cursor on the entry with sequence
+/// number `seq`.  This may be called from any thread.
+fn show_entry(seq: u64) {
+    promise::spawn::spawn_into_main_thread(async move {
+        if let Some(window) = last_focused_window() {
+            window.show();
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                term_window.show_debug_overlay_at(Some(seq));
+            })));
+        }
+    })
+    .detach();
+}
+
+/// Registers or unregisters the observer to match the configuration
+fn update_observer() {
+    let enabled = config::configuration().notify_on_error_log;
+    let mut observer = OBSERVER.lock().unwrap();
+    if enabled && observer.is_none() {
+        observer.replace(ringlog::register_observer(on_entry));
+    } else if !enabled {
+        if let Some(id) = observer.take() {
+            ringlog::unregister_observer(id);
+        }
+    }
+}
+
+pub fn register() {
+    update_observer();
+    let subscription = config::subscribe_to_config_reload(|| {
+        // The configuration can't be read from within this callback
+        promise::spawn::spawn_into_main_thread(async {
+            update_observer();
+        })
+        .detach();
+        true
+    });
+    CONFIG_SUBSCRIPTION.lock().unwrap().replace(subscription);
+}
@@ -4,7 +4,7 @@
 use crate::frontend::front_end;
 use ::window::*;
 use anyhow::{anyhow, Context};
-use config::{ConfigHandle, SshBackend};
+use config::{ConfigHandle, SerialDomain, SerialFlowControl, SerialParity, SshBackend};
 use mux::activity::Activity;
 use mux::domain::{Domain, LocalDomain};
 use mux::Mux;
@@ -22,9 +22,11 @@ use wezterm_ssh::*;
 use wezterm_toast_notification::*;
 
 mod cache;
+mod contrast;
 mod customglyph;
 mod frontend;
 mod glyphcache;
+mod lognotify;
 mod markdown;
 mod overlay;
 mod quad;
@@ -33,6 +35,7 @@ mod scripting;
 mod scrollbar;
 mod selection;
 mod shapecache;
+mod sshprompt;
 mod stats;
 mod tabbar;
 mod termwindow;
@@ -98,6 +101,9 @@ enum SubCommand {
 
     #[structopt(name = "ls-fonts", about = "Display information about fonts")]
     LsFonts(LsFontsCommand),
+
+    #[structopt(name = "show-keys", about = "Show key assignments")]
+    ShowKeys(ShowKeysCommand),
 }
 
 async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
@@ -189,11 +195,75 @@ fn run_ssh(opts: SshCommand) -> anyhow::Result<()> {
     gui.run_forever()
 }
 
+/// Returns the pty system for a serial port with the settings of `dom`
+fn serial_tty(dom: &SerialDomain) -> anyhow::Result<portable_pty::serial::SerialTty> {
+    let port = match (&dom.port, &dom.usb_serial_number) {
+        (Some(port), _) => port.clone(),
+        (None, Some(_)) => String::new(),
+        (None, None) => anyhow::bail!("serial port {} has no port", dom.name),
+    };
+    let mut serial = portable_pty::serial::SerialTty::new(&port);
+    if let Some(baud) = dom.baud {
+        serial.set_baud_rate(serial::BaudRate::from_speed(baud));
+    }
+    serial.set_flow_control(match dom.flow_control {
+        SerialFlowControl::None => serial::FlowControl::FlowNone,
+        SerialFlowControl::Hardware => serial::FlowControl::FlowHardware,
+        SerialFlowControl::Software => serial::FlowControl::FlowSoftware,
+    });
+    serial.set_parity(match dom.parity {
+        SerialParity::None => serial::Parity::ParityNone,
+        SerialParity::Odd => serial::Parity::ParityOdd,
+        SerialParity::Even => serial::Parity::ParityEven,
+    });
+    match dom.stop_bits {
+        None | Some(1) => serial.set_stop_bits(serial::StopBits::Stop1),
+        Some(2) => serial.set_stop_bits(serial::StopBits::Stop2),
+        Some(n) => anyhow::bail!("serial port {}: {} is not a valid stop_bits", dom.name, n),
+    }
+    serial.set_char_size(match dom.data_bits {
+        Some(5) => serial::CharSize::Bits5,
+        Some(6) => serial::CharSize::Bits6,
+        Some(7) => serial::CharSize::Bits7,
+        None | Some(8) => serial::CharSize::Bits8,
+        Some(n) => anyhow::bail!("serial port {}: {} is not a valid data_bits", dom.name, n),
+    });
+    serial.set_reconnect(dom.reconnect);
+    serial.set_usb_serial_number(dom.usb_serial_number.clone());
+    Ok(serial)
+}
+
 fn run_serial(config: config::ConfigHandle, opts: &SerialCommand) -> anyhow::Result<()> {
-    let mut serial = portable_pty::serial::SerialTty::new(&opts.port);
+    let port = opts.port.to_string_lossy();
+    let mut dom = config
+        .serial_ports
+        .iter()
+        .find(|dom| dom.name == port)
+        .cloned()
+        .unwrap_or_else(|| SerialDomain {
+            name: port.to_string(),
+            port: Some(port.to_string()),
+            ..SerialDomain::default()
+        });
     if let Some(baud) = opts.baud {
-        serial.set_baud_rate(serial::BaudRate::from_speed(baud));
+        dom.baud.replace(baud);
+    }
+    if let Some(flow_control) = opts.flow_control {
+        dom.flow_control = flow_control;
+    }
+    if let Some(parity) = opts.parity {
+        dom.parity = parity;
+    }
+    if let Some(stop_bits) = opts.stop_bits {
+        dom.stop_bits.replace(stop_bits);
+    }
+    if let Some(data_bits) = opts.data_bits {
+        dom.data_bits.replace(data_bits);
     }
+    if opts.reconnect {
+        dom.reconnect = true;
+    }
+    let serial = serial_tty(&dom)?;
 
     let pty_system = Box::new(serial);
     let domain: Arc<dyn Domain> = Arc::new(LocalDomain::with_pty_system("local", pty_system));
@@ -296,6 +366,21 @@ async fn spawn_tab_in_default_domain_if_mux_is_empty(
     }
 
     let config = config::configuration();
+    if cmd.is_none() && config.restore_session_on_startup {
+        let path = mux::session::default_session_file();
+        if path.exists() {
+            match mux::session::read_session_file(&path) {
+                Ok(session) => {
+                    mux::session::restore_session(session).await?;
+                    if !mux.is_empty() {
+                        return Ok(());
+                    }
+                }
+                Err(err) => log::error!("restore_session_on_startup: {:#}", err),
+            }
+        }
+    }
+
     let window_id = mux.new_empty_window();
     let _tab = domain
         .spawn(config.initial_size(), cmd, None, *window_id)
@@ -324,6 +409,22 @@ async fn update_mux_domains(config: &ConfigHandle, do_auto_connect: bool) -> any
         }
     }
 
+    for serial_dom in &config.serial_ports {
+        if mux.get_domain_by_name(&serial_dom.name).is_some() {
+            continue;
+        }
+        let pty_system = match serial_tty(serial_dom) {
+            Ok(serial) => Box::new(serial),
+            Err(err) => {
+                log::error!("{:#}", err);
+                continue;
+            }
+        };
+        let domain: Arc<dyn Domain> =
+            Arc::new(LocalDomain::with_pty_system(&serial_dom.name, pty_system));
+        mux.add_domain(&domain);
+    }
+
     Ok(())
 }
 
@@ -464,6 +565,130 @@ fn maybe_show_configuration_error_window() {
     }
 }
 
+pub fn run_show_keys(config: config::ConfigHandle, cmd: &ShowKeysCommand) -> anyhow::Result<()> {
+    use config::keyassignment::InputMap;
+    use config::{key_binding_to_lua, keycode_to_config_name, modifiers_to_config_string, Key};
+    use tabout::{tabulate_output, Alignment, Column};
+
+    if let Err(err) = config::configuration_result() {
+        log::error!("{}", err);
+        return Ok(());
+    }
+
+    let user_keys = config.key_bindings();
+    let user_keys_up = config.key_up_bindings();
+    let map = InputMap::new(&config);
+
+    // (key name, binding, source)
+    let mut entries = vec![];
+    for (key_up, key, mods, action) in map
+        .key_assignments()
+        .map(|(key, mods, action)| (false, key, mods, action))
+        .chain(
+            map.key_up_assignments()
+                .map(|(key, mods, action)| (true, key, mods, action)),
+        )
+    {
+        let name = match keycode_to_config_name(key) {
+            Some(name) => name,
+            None => {
+                log::warn!("{:?} cannot be expressed in the configuration", key);
+                continue;
+            }
+        };
+        let user = if key_up { &user_keys_up } else { &user_keys };
+        let source = if user.contains_key(&(key.clone(), mods)) {
+            "user"
+        } else {
+            "default"
+        };
+        let description = if key_up {
+            map.key_up_description(key, mods)
+        } else {
+            map.key_description(key, mods)
+        };
+        entries.push((
+            name,
+            Key {
+                key: key.clone(),
+                mods,
+                action: action.clone(),
+                key_up,
+                description: description.map(str::to_string),
+            },
+            source,
+        ));
+    }
+    entries.sort_by_key(|(name, binding, _)| {
+        (
+            modifiers_to_config_string(binding.mods),
+            name.clone(),
+            binding.key_up,
+        )
+    });
+
+    if cmd.json {
+        let mut items = vec![];
+        for (_, binding, source) in &entries {
+            let mut item = serde_json::to_value(binding)?;
+            item["source"] = serde_json::Value::String(source.to_string());
+            items.push(item);
+        }
+        println!("{}", serde_json::to_string_pretty(&items)?);
+    } else if cmd.lua {
+        println!("local wezterm = require 'wezterm';");
+        println!("return {{");
+        println!("  keys = {{");
+        for (_, binding, _) in &entries {
+            println!("    {},", key_binding_to_lua(binding)?);
+        }
+        println!("  }},");
+        println!("}}");
+    } else {
+        let cols = vec![
+            Column {
+                name: "MODS".to_string(),
+                alignment: Alignment::Left,
+            },
+            Column {
+                name: "KEY".to_string(),
+                alignment: Alignment::Left,
+            },
+            Column {
+                name: "ACTION".to_string(),
+                alignment: Alignment::Left,
+            },
+            Column {
+                name: "SOURCE".to_string(),
+                alignment: Alignment::Left,
+            },
+            Column {
+                name: "DESCRIPTION".to_string(),
+                alignment: Alignment::Left,
+            },
+        ];
+        let data: Vec<Vec<String>> = entries
+            .iter()
+            .map(|(name, binding, source)| {
+                vec![
+                    modifiers_to_config_string(binding.mods),
+                    name.clone(),
+                    if binding.key_up {
+                        format!("{:?} (key up)", binding.action)
+                    } else {
+                        format!("{:?}", binding.action)
+                    },
+                    source.to_string(),
+                    binding.description.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+    }
+
+    Ok(())
+}
+
 pub fn run_ls_fonts(config: config::ConfigHandle, cmd: &LsFontsCommand) -> anyhow::Result<()> {
     use wezterm_font::parser::ParsedFont;
 
@@ -517,6 +742,9 @@ pub fn run_ls_fonts(config: config::ConfigHandle, cmd: &LsFontsCommand) -> anyho
                     "",
                     parsed.handle.diagnostic_string()
                 );
+                if let Some(restriction) = parsed.restriction_description() {
+                    println!("{:29}selected by the rule for {}", "", restriction);
+                }
             }
         }
         return Ok(());
@@ -638,6 +866,7 @@ fn run() -> anyhow::Result<()> {
     };
 
     env_bootstrap::bootstrap();
+    config::lua::add_context_setup_func(scripting::domain::register);
 
     stats::Stats::init()?;
     let _saver = umask::UmaskSaver::new();
@@ -648,6 +877,9 @@ fn run() -> anyhow::Result<()> {
         opts.skip_config,
     );
     let config = config::configuration();
+    env_bootstrap::ringlog::apply_config(&config);
+    lognotify::register();
+    sshprompt::register();
 
     let sub = match opts.cmd.as_ref().cloned() {
         Some(sub) => sub,
@@ -675,5 +907,67 @@ fn run() -> anyhow::Result<()> {
         SubCommand::Serial(serial) => run_serial(config, &serial),
         SubCommand::Connect(connect) => run_mux_client(config, &connect),
         SubCommand::LsFonts(cmd) => run_ls_fonts(config, &cmd),
+        SubCommand::ShowKeys(cmd) => run_show_keys(config, &cmd),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn serial_error(dom: &SerialDomain) -> Option<String> {
+        serial_tty(dom).err().map(|err| err.to_string())
+    }
+
+    #[test]
+    fn serial_tty_validation() {
+        let dom = |stop_bits, data_bits| SerialDomain {
+            name: "test".to_string(),
+            port: Some("/dev/ttyUSB0".to_string()),
+            stop_bits,
+            data_bits,
+            ..SerialDomain::default()
+        };
+
+        assert_eq!(serial_error(&dom(None, None)), None);
+        for stop_bits in 1..=2 {
+            assert_eq!(serial_error(&dom(Some(stop_bits), None)), None);
+        }
+        for data_bits in 5..=8 {
+            assert_eq!(serial_error(&dom(None, Some(data_bits))), None);
+        }
+
+        assert_eq!(
+            serial_error(&dom(Some(0), None)).as_deref(),
+            Some("serial port test: 0 is not a valid stop_bits")
+        );
+        assert_eq!(
+            serial_error(&dom(Some(3), None)).as_deref(),
+            Some("serial port test: 3 is not a valid stop_bits")
+        );
+        assert_eq!(
+            serial_error(&dom(None, Some(4))).as_deref(),
+            Some("serial port test: 4 is not a valid data_bits")
+        );
+        assert_eq!(
+            serial_error(&dom(None, Some(9))).as_deref(),
+            Some("serial port test: 9 is not a valid data_bits")
+        );
+
+        // A port is only optional when the device is found by its
+        // USB serial number
+        let no_port = SerialDomain {
+            port: None,
+            ..dom(None, None)
+        };
+        assert_eq!(
+            serial_error(&no_port).as_deref(),
+            Some("serial port test has no port")
+        );
+        let by_serial_number = SerialDomain {
+            usb_serial_number: Some("A1B2C3".to_string()),
+            ..no_port
+        };
+        assert_eq!(serial_error(&by_serial_number), None);
     }
 }
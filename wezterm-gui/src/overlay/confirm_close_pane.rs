@@ -1,15 +1,104 @@
+use crate::scripting::pane::PaneObject;
 use crate::TermWindow;
-use mux::pane::PaneId;
+use mux::pane::{Pane, PaneId};
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
 use mux::Mux;
+use std::path::Path;
+use std::rc::Rc;
 use termwiz::cell::AttributeChange;
 use termwiz::color::ColorAttribute;
 use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
 use termwiz::surface::{Change, CursorVisibility, Position};
 use termwiz::terminal::Terminal;
 
+/// Returns the file name of the executable of the process
+fn process_name(executable: &str) -> String {
+    Path::new(executable)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| executable.to_string())
+}
+
+/// Calls the `mux-is-process-stateful` event for the foreground process
+/// of the pane.  Returns None if there is no handler, the handler
+/// returned nil, or the foreground process isn't known.
+fn is_process_stateful(pane: &Rc<dyn Pane>) -> Option<bool> {
+    let executable = pane.get_foreground_process_name()?;
+    let name = process_name(&executable);
+    let argv = pane.get_foreground_process_argv().unwrap_or_default();
+    let cwd = pane
+        .get_current_working_dir()
+        .map(|url| url.path().to_string());
+
+    match config::run_immediate_with_lua_config(|lua| {
+        let lua = match lua {
+            Some(lua) if config::lua::has_event_handlers(&lua, "mux-is-process-stateful") => lua,
+            _ => return Ok(None),
+        };
+        let info = lua.create_table()?;
+        info.set("name", name)?;
+        info.set("executable", executable)?;
+        info.set("argv", argv)?;
+        info.set("cwd", cwd)?;
+        match config::lua::emit_sync_callback(
+            &*lua,
+            (
+                "mux-is-process-stateful".to_string(),
+                (info, PaneObject::new(pane)),
+            ),
+        )? {
+            mlua::Value::Nil => Ok(None),
+            mlua::Value::Boolean(stateful) => Ok(Some(stateful)),
+            other => anyhow::bail!(
+                "expected true, false or nil, but got a {}",
+                other.type_name()
+            ),
+        }
+    }) {
+        Ok(stateful) => stateful,
+        Err(err) => {
+            log::warn!("mux-is-process-stateful: {:#}", err);
+            None
+        }
+    }
+}
+
+/// Returns the names of the processes that mean that closing `panes`
+/// should be confirmed, or an empty list if they can be closed without
+/// prompting.
+/// The `mux-is-process-stateful` event decides for the foreground process
+/// of each pane, falling back to `skip_close_confirmation_for_processes_named`.
+pub fn processes_preventing_close(panes: &[Rc<dyn Pane>]) -> Vec<String> {
+    let mut names = vec![];
+    for pane in panes {
+        let pane_names = match is_process_stateful(pane) {
+            Some(true) => vec![pane
+                .get_foreground_process_name()
+                .map(|exe| process_name(&exe))
+                .unwrap_or_else(|| pane.get_title())],
+            Some(false) => vec![],
+            None => pane.get_processes_preventing_close(),
+        };
+        for name in pane_names {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Appends the names of the running processes to the prompt
+fn confirmation_message(prompt: &str, processes: &[String]) -> String {
+    if processes.is_empty() {
+        prompt.to_string()
+    } else {
+        format!("{}  Still running: {}", prompt, processes.join(", "))
+    }
+}
+
 fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Result<bool> {
     term.set_raw_mode()?;
 
@@ -169,11 +258,15 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
 
 pub fn confirm_close_pane(
     pane_id: PaneId,
+    processes: Vec<String>,
     mut term: TermWizTerminal,
     mux_window_id: WindowId,
     window: ::window::Window,
 ) -> anyhow::Result<()> {
-    if run_confirmation_app("🛑 Really kill this pane?", &mut term)? {
+    if run_confirmation_app(
+        &confirmation_message("🛑 Really kill this pane?", &processes),
+        &mut term,
+    )? {
         promise::spawn::spawn_into_main_thread(async move {
             let mux = Mux::get().unwrap();
             let tab = match mux.get_active_tab_for_window(mux_window_id) {
@@ -191,12 +284,16 @@ pub fn confirm_close_pane(
 
 pub fn confirm_close_tab(
     tab_id: TabId,
+    processes: Vec<String>,
     mut term: TermWizTerminal,
     _mux_window_id: WindowId,
     window: ::window::Window,
 ) -> anyhow::Result<()> {
     if run_confirmation_app(
-        "🛑 Really kill this tab and all contained panes?",
+        &confirmation_message(
+            "🛑 Really kill this tab and all contained panes?",
+            &processes,
+        ),
         &mut term,
     )? {
         promise::spawn::spawn_into_main_thread(async move {
@@ -211,13 +308,17 @@ pub fn confirm_close_tab(
 }
 
 pub fn confirm_close_window(
+    processes: Vec<String>,
     mut term: TermWizTerminal,
     mux_window_id: WindowId,
     window: ::window::Window,
     tab_id: TabId,
 ) -> anyhow::Result<()> {
     if run_confirmation_app(
-        "🛑 Really kill this window and all contained tabs and panes?",
+        &confirmation_message(
+            "🛑 Really kill this window and all contained tabs and panes?",
+            &processes,
+        ),
         &mut term,
     )? {
         promise::spawn::spawn_into_main_thread(async move {
@@ -1,21 +1,24 @@
 use crate::selection::{SelectionCoordinate, SelectionRange};
 use crate::termwindow::{TermWindow, TermWindowNotif};
-use config::keyassignment::ScrollbackEraseMode;
+use config::keyassignment::{ClipboardCopyDestination, ScrollbackEraseMode};
 use mux::domain::DomainId;
 use mux::pane::{Pane, PaneId};
 use mux::renderable::*;
+use mux::Mux;
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use std::cell::{RefCell, RefMut};
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
-use termwiz::surface::{CursorVisibility, SequenceNo};
+use termwiz::cell::{Cell, CellAttributes};
+use termwiz::surface::{CursorVisibility, SequenceNo, SEQ_ZERO};
 use unicode_segmentation::*;
 use url::Url;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    unicode_column_width, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex,
+    unicode_column_width, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, SemanticType,
+    SemanticZone, StableRowIndex,
 };
 use window::WindowOps;
 
@@ -31,6 +34,15 @@ struct CopyRenderable {
     viewport: Option<StableRowIndex>,
     /// We use this to cancel ourselves later
     window: ::window::Window,
+    /// True if `"` was pressed and the next key names a register
+    awaiting_register: bool,
+    /// The register that the next yank will copy to
+    register: Option<String>,
+    /// A message shown at the bottom of the viewport until the
+    /// next key press
+    status: Option<String>,
+    /// The row that needs to be redrawn because the status changed
+    status_dirty: Option<StableRowIndex>,
 }
 
 struct Dimensions {
@@ -52,6 +64,10 @@ impl CopyOverlay {
             delegate: Rc::clone(pane),
             start: None,
             viewport: term_window.get_viewport(pane.pane_id()),
+            awaiting_register: false,
+            register: None,
+            status: None,
+            status_dirty: None,
         };
         Rc::new(CopyOverlay {
             delegate: Rc::clone(pane),
@@ -63,6 +79,22 @@ impl CopyOverlay {
         let mut r = self.render.borrow_mut();
         r.viewport = viewport;
     }
+
+    pub fn move_backward_semantic_zone(&self, zone_type: SemanticType) {
+        self.render
+            .borrow_mut()
+            .move_to_semantic_zone(zone_type, false);
+    }
+
+    pub fn move_forward_semantic_zone(&self, zone_type: SemanticType) {
+        self.render
+            .borrow_mut()
+            .move_to_semantic_zone(zone_type, true);
+    }
+
+    pub fn select_semantic_zone(&self, zone_type: SemanticType) {
+        self.render.borrow_mut().select_semantic_zone(zone_type);
+    }
 }
 
 impl CopyRenderable {
@@ -363,6 +395,122 @@ impl CopyRenderable {
         self.select_to_cursor_pos();
     }
 
+    fn status_row(&self) -> StableRowIndex {
+        let dims = self.dimensions();
+        (dims.top + dims.dims.viewport_rows as StableRowIndex).saturating_sub(1)
+    }
+
+    fn set_status(&mut self, status: String) {
+        self.status.replace(status);
+        self.status_dirty.replace(self.status_row());
+        self.window.invalidate();
+    }
+
+    fn clear_status(&mut self) {
+        if self.status.take().is_some() {
+            self.status_dirty.replace(self.status_row());
+            self.window.invalidate();
+        }
+    }
+
+    /// Returns the zones of the specified type, or None if the
+    /// pane has no semantic zones at all, which is usually because
+    /// the shell integration has not been set up
+    fn semantic_zones_of_type(&mut self, zone_type: SemanticType) -> Option<Vec<SemanticZone>> {
+        let zones = self
+            .delegate
+            .get_semantic_zones()
+            .unwrap_or_else(|_| vec![]);
+        if zones.is_empty() {
+            self.set_status("There are no semantic zones in this pane".to_string());
+            return None;
+        }
+        Some(
+            zones
+                .into_iter()
+                .filter(|zone| zone.semantic_type == zone_type)
+                .collect(),
+        )
+    }
+
+    fn move_to_semantic_zone(&mut self, zone_type: SemanticType, forward: bool) {
+        let zones = match self.semantic_zones_of_type(zone_type) {
+            Some(zones) => zones,
+            None => return,
+        };
+        let cursor = (self.cursor.y, self.cursor.x);
+        let zone = if forward {
+            zones
+                .iter()
+                .find(|zone| (zone.start_y, zone.start_x) > cursor)
+        } else {
+            zones
+                .iter()
+                .rev()
+                .find(|zone| (zone.start_y, zone.start_x) < cursor)
+        };
+        match zone {
+            Some(zone) => {
+                self.cursor.y = zone.start_y;
+                self.cursor.x = zone.start_x;
+                self.select_to_cursor_pos();
+            }
+            None => self.set_status(format!(
+                "There is no {} {:?} zone",
+                if forward { "next" } else { "previous" },
+                zone_type
+            )),
+        }
+    }
+
+    /// Selects the zone that contains the cursor, or failing that,
+    /// the closest one before it
+    fn select_semantic_zone(&mut self, zone_type: SemanticType) {
+        let zones = match self.semantic_zones_of_type(zone_type) {
+            Some(zones) => zones,
+            None => return,
+        };
+        let cursor = (self.cursor.y, self.cursor.x);
+        let zone = zones
+            .iter()
+            .rev()
+            .find(|zone| (zone.start_y, zone.start_x) <= cursor)
+            .or_else(|| zones.first());
+        match zone {
+            Some(zone) => {
+                self.start.replace(SelectionCoordinate {
+                    x: zone.start_x,
+                    y: zone.start_y,
+                });
+                self.cursor.y = zone.end_y;
+                self.cursor.x = zone.end_x;
+                self.select_to_cursor_pos();
+            }
+            None => self.set_status(format!("There is no {:?} zone", zone_type)),
+        }
+    }
+
+    /// Copies the selection to the register named by `"`, or to the
+    /// clipboard if no register was named, and then leaves copy mode
+    fn yank(&mut self) {
+        let dest = match self.register.take() {
+            Some(name) => ClipboardCopyDestination::Register(name),
+            None => ClipboardCopyDestination::default(),
+        };
+        let pane_id = self.delegate.pane_id();
+        self.window
+            .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                let mux = Mux::get().unwrap();
+                if let Some(pane) = mux.get_pane(pane_id) {
+                    let text = term_window.selection_text(&pane);
+                    if !text.is_empty() {
+                        term_window.copy_to_clipboard(dest, text);
+                    }
+                }
+            })));
+        self.close();
+    }
+
     fn toggle_selection_by_cell(&mut self) {
         if self.start.take().is_none() {
             let coord = SelectionCoordinate {
@@ -401,6 +549,20 @@ impl Pane for CopyOverlay {
     }
 
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        self.render.borrow_mut().clear_status();
+
+        if self.render.borrow().awaiting_register {
+            let mut r = self.render.borrow_mut();
+            r.awaiting_register = false;
+            if let (KeyCode::Char(c), KeyModifiers::NONE)
+            | (KeyCode::Char(c), KeyModifiers::SHIFT) = (key, mods)
+            {
+                r.register.replace(c.to_string());
+                r.set_status(format!("Yank to register \"{}", c));
+            }
+            return Ok(());
+        }
+
         match (key, mods) {
             (KeyCode::Char('c'), KeyModifiers::CTRL)
             | (KeyCode::Char('g'), KeyModifiers::CTRL)
@@ -470,6 +632,30 @@ impl Pane for CopyOverlay {
             (KeyCode::Char('L'), KeyModifiers::NONE) => {
                 self.render.borrow_mut().move_to_viewport_bottom();
             }
+            (KeyCode::Char('"'), KeyModifiers::SHIFT) | // FIXME: normalize the shift away!
+            (KeyCode::Char('"'), KeyModifiers::NONE) => {
+                self.render.borrow_mut().awaiting_register = true;
+            }
+            (KeyCode::Char('y'), KeyModifiers::NONE) => {
+                self.render.borrow_mut().yank();
+            }
+            (KeyCode::Char('['), KeyModifiers::NONE) => {
+                self.move_backward_semantic_zone(SemanticType::Prompt);
+            }
+            (KeyCode::Char(']'), KeyModifiers::NONE) => {
+                self.move_forward_semantic_zone(SemanticType::Prompt);
+            }
+            (KeyCode::Char('{'), KeyModifiers::SHIFT) | // FIXME: normalize the shift away!
+            (KeyCode::Char('{'), KeyModifiers::NONE) => {
+                self.move_backward_semantic_zone(SemanticType::Output);
+            }
+            (KeyCode::Char('}'), KeyModifiers::SHIFT) | // FIXME: normalize the shift away!
+            (KeyCode::Char('}'), KeyModifiers::NONE) => {
+                self.move_forward_semantic_zone(SemanticType::Output);
+            }
+            (KeyCode::Char('z'), KeyModifiers::NONE) => {
+                self.select_semantic_zone(SemanticType::Output);
+            }
             (KeyCode::PageUp, KeyModifiers::NONE) | (KeyCode::Char('b'), KeyModifiers::CTRL) => self.render.borrow_mut().page_up(),
             (KeyCode::PageDown, KeyModifiers::NONE) | (KeyCode::Char('f'), KeyModifiers::CTRL) => self.render.borrow_mut().page_down(),
             _ => {}
@@ -531,11 +717,35 @@ impl Pane for CopyOverlay {
         lines: Range<StableRowIndex>,
         seqno: SequenceNo,
     ) -> RangeSet<StableRowIndex> {
-        self.delegate.get_changed_since(lines, seqno)
+        let mut dirty = self.delegate.get_changed_since(lines.clone(), seqno);
+        if let Some(row) = self.render.borrow().status_dirty {
+            dirty.add(row);
+        }
+        dirty.intersection_with_range(lines)
     }
 
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
-        self.delegate.get_lines(lines)
+        let (top, mut lines) = self.delegate.get_lines(lines);
+        let mut render = self.render.borrow_mut();
+        let status_row = render.status_row();
+        if let Some(row) = render.status_dirty {
+            if row >= top && row < top + lines.len() as StableRowIndex {
+                render.status_dirty.take();
+            }
+        }
+
+        if let Some(status) = render.status.as_ref() {
+            let cols = self.delegate.get_dimensions().cols;
+            for (idx, line) in lines.iter_mut().enumerate() {
+                if idx as StableRowIndex + top == status_row {
+                    let rev = CellAttributes::default().set_reverse(true).clone();
+                    line.fill_range(0..cols, &Cell::new(' ', rev.clone()), SEQ_ZERO);
+                    line.overlay_text_with_attribute(0, status, rev, SEQ_ZERO);
+                }
+            }
+        }
+
+        (top, lines)
     }
 
     fn get_dimensions(&self) -> RenderableDimensions {
@@ -1,19 +1,151 @@
+use crate::overlay::logview::LogView;
 use crate::scripting::guiwin::GuiWin;
-use chrono::prelude::*;
+use crate::termwindow::TermWindowNotif;
 use log::Level;
 use luahelper::ValueWrapper;
 use mlua::Value;
+use mux::domain::DomainId;
 use mux::termwiztermtab::TermWizTerminal;
-use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
-use termwiz::color::AnsiColor;
-use termwiz::input::{InputEvent, KeyCode, KeyEvent};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::PathBuf;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
 use termwiz::lineedit::*;
 use termwiz::surface::Change;
 use termwiz::terminal::Terminal;
+use window::{Clipboard, WindowOps};
+
+/// While the overlay is open, retain at least this many
+/// entries per level so that bursts don't scroll out of view
+const OVERLAY_RING_CAPACITY: usize = 256;
+
+/// Remembers the lines entered into the repl across sessions.
+/// The lines entered during this session are merged into the
+/// history file when this is dropped.
+struct HistoryFile {
+    path: PathBuf,
+    limit: usize,
+    added: Vec<String>,
+}
+
+impl HistoryFile {
+    fn new(limit: usize) -> Self {
+        Self {
+            path: config::CACHE_DIR.join("debug-overlay-history"),
+            limit,
+            added: vec![],
+        }
+    }
+
+    /// Returns the saved lines, oldest first.
+    /// A file that is missing or can't be read is treated as empty.
+    fn load(&self) -> Vec<String> {
+        if self.limit == 0 {
+            return vec![];
+        }
+        match std::fs::read(&self.path) {
+            Ok(data) => merge_history(parse_history(&data), &[], self.limit),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!(
+                        "ignoring debug overlay history {}: {:#}",
+                        self.path.display(),
+                        err
+                    );
+                }
+                vec![]
+            }
+        }
+    }
+
+    fn add(&mut self, line: &str) {
+        self.added.push(line.to_string());
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        if self.limit == 0 || self.added.is_empty() {
+            return Ok(());
+        }
+        // Re-read the file so that we don't lose lines saved by
+        // another overlay that was closed while this one was open
+        let lines = merge_history(self.load(), &self.added, self.limit);
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temp = self.path.with_extension("tmp");
+        let mut file = std::fs::File::create(&temp)?;
+        for line in &lines {
+            writeln!(file, "{}", line)?;
+        }
+        drop(file);
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Drop for HistoryFile {
+    fn drop(&mut self) {
+        if let Err(err) = self.save() {
+            log::warn!(
+                "unable to save debug overlay history to {}: {:#}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Parses the content of the history file, skipping any lines
+/// that were mangled by a partial write or other corruption
+fn parse_history(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter(|line| {
+            !line.trim().is_empty()
+                && !line
+                    .chars()
+                    .any(|c| c == std::char::REPLACEMENT_CHARACTER || c.is_control())
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Appends `added` to `existing`, keeping only the most recent
+/// occurrence of each line and at most `limit` lines
+fn merge_history(existing: Vec<String>, added: &[String], limit: usize) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut lines: Vec<String> = existing
+        .into_iter()
+        .chain(added.iter().cloned())
+        .rev()
+        .filter(|line| seen.insert(line.clone()))
+        .take(limit)
+        .collect();
+    lines.reverse();
+    lines
+}
+
+/// Describes the number of quads used to render the most recent
+/// frame of `window`, with and without merging runs of cells
+fn describe_render_stats(window: &::window::Window) -> String {
+    let (tx, rx) = smol::channel::bounded(1);
+    window.notify(TermWindowNotif::GetRenderStats(tx));
+    match promise::spawn::block_on(rx.recv()) {
+        Ok(stats) => format!(
+            "the last frame used {} quads, {} without merging runs of cells",
+            stats.quads, stats.unmerged_quads
+        ),
+        Err(err) => format!("unable to get the render stats: {:#}", err),
+    }
+}
 
 struct LuaReplHost {
     history: BasicHistory,
     lua: mlua::Lua,
+    /// Set when the user asked to browse the log, so that we can
+    /// distinguish that from canceling the repl
+    browse_log: bool,
 }
 
 impl LineEditorHost for LuaReplHost {
@@ -26,7 +158,31 @@ impl LineEditorHost for LuaReplHost {
         event: &InputEvent,
         editor: &mut LineEditor<'_>,
     ) -> Option<Action> {
+        if let InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('L'),
+            modifiers: Modifiers::CTRL,
+        }) = event
+        {
+            // Wipe the captured log as well as the screen.  The log
+            // view notices this and discards its own copy of the entries
+            env_bootstrap::ringlog::clear_entries();
+            return Some(Action::Repaint);
+        }
+
         let (line, _cursor) = editor.get_line_and_cursor();
+        if line.is_empty()
+            && matches!(
+                event,
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char('/'),
+                    ..
+                })
+            )
+        {
+            self.browse_log = true;
+            return Some(Action::Cancel);
+        }
+
         if line.is_empty()
             && matches!(
                 event,
@@ -65,72 +221,69 @@ impl LineEditorHost for LuaReplHost {
     }
 }
 
-pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow::Result<()> {
+/// Runs the debug overlay.  When `focus` is set, the log is browsed
+/// with the cursor on the entry with that sequence number before
+/// the repl starts.
+pub fn show_debug_overlay(
+    mut term: TermWizTerminal,
+    gui_win: GuiWin,
+    domain_id: Option<DomainId>,
+    focus: Option<u64>,
+) -> anyhow::Result<()> {
     term.no_grab_mouse_in_raw_mode();
 
+    for level in &[
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ] {
+        if env_bootstrap::ringlog::ring_capacity(*level) < OVERLAY_RING_CAPACITY {
+            env_bootstrap::ringlog::set_ring_capacity(*level, OVERLAY_RING_CAPACITY);
+        }
+    }
+
     let lua = config::Config::load()?
         .lua
         .ok_or_else(|| anyhow::anyhow!("failed to setup lua context"))?;
     lua.load("wezterm = require 'wezterm'").exec()?;
+    let window = gui_win.window.clone();
     lua.globals().set("window", gui_win)?;
+    let copy = {
+        let window = window.clone();
+        move |text: String| {
+            let window = window.clone();
+            promise::spawn::spawn_into_main_thread(async move {
+                window.set_clipboard(Clipboard::Clipboard, text);
+            })
+            .detach();
+        }
+    };
 
-    let mut latest_log_entry = None;
+    let mut log_view = LogView::new(domain_id);
+    let mut history_file = HistoryFile::new(config::configuration().debug_key_history_limit);
+    let mut history = BasicHistory::default();
+    for line in history_file.load() {
+        history.add(&line);
+    }
     let mut host = LuaReplHost {
-        history: BasicHistory::default(),
+        history,
         lua,
+        browse_log: false,
     };
 
     term.render(&[Change::Title("Debug".to_string())])?;
+    if let Some(path) = env_bootstrap::ringlog::log_file_path() {
+        term.render(&[Change::Text(format!("Logging to {}\r\n", path.display()))])?;
+    }
 
-    fn print_new_log_entries(
-        term: &mut TermWizTerminal,
-        latest: &mut Option<DateTime<Local>>,
-    ) -> termwiz::Result<()> {
-        let entries = env_bootstrap::ringlog::get_entries();
-        let mut changes = vec![];
-        for entry in entries {
-            if let Some(latest) = latest {
-                if entry.then <= *latest {
-                    // already seen this one
-                    continue;
-                }
-            }
-            latest.replace(entry.then);
-
-            changes.push(Change::AllAttributes(CellAttributes::default()));
-            changes.push(Change::Text(entry.then.format("%H:%M:%S%.3f ").to_string()));
-
-            changes.push(
-                AttributeChange::Foreground(match entry.level {
-                    Level::Error => AnsiColor::Maroon.into(),
-                    Level::Warn => AnsiColor::Red.into(),
-                    Level::Info => AnsiColor::Green.into(),
-                    Level::Debug => AnsiColor::Blue.into(),
-                    Level::Trace => AnsiColor::Fuschia.into(),
-                })
-                .into(),
-            );
-            changes.push(Change::Text(
-                match entry.level {
-                    Level::Error => "ERROR",
-                    Level::Warn => "WARNING",
-                    Level::Info => "INFO",
-                    Level::Debug => "DEBUG",
-                    Level::Trace => "TRACE",
-                }
-                .to_string(),
-            ));
-            changes.push(Change::AllAttributes(CellAttributes::default()));
-            changes.push(AttributeChange::Intensity(Intensity::Bold).into());
-            changes.push(Change::Text(format!(" {}", entry.target)));
-            changes.push(Change::AllAttributes(CellAttributes::default()));
-            changes.push(Change::Text(format!(" > {}\r\n", entry.msg)));
-        }
-        term.render(&changes)
+    if focus.is_some() {
+        log_view.browse(&mut term, false, focus, &copy)?;
     }
 
     loop {
-        print_new_log_entries(&mut term, &mut latest_log_entry)?;
+        log_view.print_new_entries(&mut term)?;
         let mut editor = LineEditor::new(&mut term);
         editor.set_prompt("> ");
         if let Some(line) = editor.read_line(&mut host)? {
@@ -138,6 +291,17 @@ pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow:
                 continue;
             }
             host.history().add(&line);
+            history_file.add(&line);
+
+            if let Some(command) = line.strip_prefix(':') {
+                let text = if command.trim() == "quads" {
+                    describe_render_stats(&window)
+                } else {
+                    log_view.command(command)
+                };
+                term.render(&[Change::Text(format!("{}\r\n", text))])?;
+                continue;
+            }
 
             let expr = format!("return {}", line);
             let chunk = host.lua.load(&expr);
@@ -157,8 +321,40 @@ pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow:
                     term.render(&[Change::Text(format!("{}\r\n", text.replace("\n", "\r\n")))])?;
                 }
             }
+        } else if host.browse_log {
+            host.browse_log = false;
+            log_view.browse(&mut term, true, None, &copy)?;
         } else {
             return Ok(());
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_dedups_and_limits() {
+        assert_eq!(
+            merge_history(lines(&["a", "b", "c"]), &lines(&["b", "d", "d"]), 10),
+            lines(&["a", "c", "b", "d"])
+        );
+        assert_eq!(
+            merge_history(lines(&["a", "b", "c"]), &lines(&["d"]), 2),
+            lines(&["c", "d"])
+        );
+    }
+
+    #[test]
+    fn parse_skips_corrupt_lines() {
+        assert_eq!(
+            parse_history(b"wezterm.mux.all_windows()\n\xff\xfe junk\n\n\x00\x01\nwindow:title()"),
+            lines(&["wezterm.mux.all_windows()", "window:title()"])
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! The domain status overlay lists the domains along with whether they
+//! are attached, how many panes they have and, for those that have lost
+//! their connection, when they will next try to reconnect.
+//! Domains can be attached, detached and restarted from the list.
+use anyhow::anyhow;
+use mux::domain::{DomainId, DomainState};
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::time::{Duration, Instant};
+use termwiz::cell::AttributeChange;
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+/// How often the list is refreshed while idle, so that the
+/// reconnect countdowns stay current
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What the overlay knows about a domain
+#[derive(Debug, Clone)]
+struct DomainInfo {
+    domain_id: DomainId,
+    name: String,
+    label: String,
+    state: DomainState,
+    num_panes: usize,
+    reconnect_deadline: Option<Instant>,
+}
+
+/// Collects the state of the domains.
+/// The mux can only be inspected on the main thread.
+fn snapshot() -> Vec<DomainInfo> {
+    promise::spawn::block_on(promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        let panes = mux.iter_panes();
+        let mut domains: Vec<DomainInfo> = mux
+            .iter_domains()
+            .iter()
+            .filter(|domain| domain.spawnable())
+            .map(|domain| DomainInfo {
+                domain_id: domain.domain_id(),
+                name: domain.domain_name().to_string(),
+                label: domain.domain_label().to_string(),
+                state: domain.state(),
+                num_panes: panes
+                    .iter()
+                    .filter(|pane| pane.domain_id() == domain.domain_id())
+                    .count(),
+                reconnect_deadline: domain.reconnect_deadline(),
+            })
+            .collect();
+        domains.sort_by_key(|domain| domain.domain_id);
+        domains
+    }))
+}
+
+fn describe_state(info: &DomainInfo, now: Instant) -> String {
+    match (info.state, info.reconnect_deadline) {
+        (DomainState::Attached, Some(deadline)) => {
+            let remaining = deadline.saturating_duration_since(now);
+            // Round up, so that the countdown doesn't show 0s
+            // for most of the final second
+            let secs = remaining.as_secs() + if remaining.subsec_nanos() > 0 { 1 } else { 0 };
+            format!("Reconnecting in {}s", secs)
+        }
+        (DomainState::Attached, None) => "Attached".to_string(),
+        (DomainState::Detached, _) => "Detached".to_string(),
+    }
+}
+
+/// Attaches the domain, which also opens windows for the windows that
+/// it has on the server.  Does nothing if the domain is already attached.
+/// Must be called on the main thread.
+pub fn attach_domain(domain_id: DomainId) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let domain = mux
+        .get_domain(domain_id)
+        .ok_or_else(|| anyhow!("invalid domain id {}", domain_id))?;
+    if domain.state() == DomainState::Attached {
+        return Ok(());
+    }
+    promise::spawn::spawn(async move {
+        if let Err(err) = domain.attach().await {
+            log::error!(
+                "while attaching domain `{}`: {:#}",
+                domain.domain_name(),
+                err
+            );
+        }
+    })
+    .detach();
+    Ok(())
+}
+
+/// Detaches the domain, closing its panes locally while leaving them
+/// running on the server.  Must be called on the main thread.
+pub fn detach_domain(domain_id: DomainId) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let domain = mux
+        .get_domain(domain_id)
+        .ok_or_else(|| anyhow!("invalid domain id {}", domain_id))?;
+    domain.detach()
+}
+
+fn restart_domain(domain_id: DomainId) -> anyhow::Result<()> {
+    detach_domain(domain_id)?;
+    attach_domain(domain_id)
+}
+
+/// Performs `action` on the main thread, returning text that
+/// describes the outcome
+fn perform(info: &DomainInfo, action: fn(DomainId) -> anyhow::Result<()>, verb: &str) -> String {
+    let domain_id = info.domain_id;
+    match promise::spawn::block_on(promise::spawn::spawn_into_main_thread(async move {
+        action(domain_id)
+    })) {
+        Ok(()) => format!("{} `{}`", verb, info.name),
+        Err(err) => format!("`{}`: {:#}", info.name, err),
+    }
+}
+
+fn render(
+    term: &mut TermWizTerminal,
+    domains: &[DomainInfo],
+    selected: usize,
+    status_message: Option<&str>,
+) -> termwiz::Result<()> {
+    let now = Instant::now();
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        Change::Text(
+            "Enter/a: attach and restore windows  d: detach  r: restart  Escape: close\r\n\r\n"
+                .to_string(),
+        ),
+    ];
+
+    for (idx, info) in domains.iter().enumerate() {
+        if idx == selected {
+            changes.push(AttributeChange::Reverse(true).into());
+        }
+        let label = if info.label.is_empty() || info.label == info.name {
+            String::new()
+        } else {
+            format!("  {}", info.label)
+        };
+        changes.push(Change::Text(format!(
+            " {:<16} {:<20} {} panes{}\r\n",
+            info.name,
+            describe_state(info, now),
+            info.num_panes,
+            label
+        )));
+        if idx == selected {
+            changes.push(AttributeChange::Reverse(false).into());
+        }
+    }
+
+    if let Some(message) = status_message {
+        changes.push(Change::Text(format!("\r\n{}\r\n", message)));
+    }
+
+    term.render(&changes)?;
+    term.flush()
+}
+
+pub fn domain_status(mut term: TermWizTerminal) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title("Domains".to_string())])?;
+
+    let mut selected = 0;
+    let mut status_message: Option<String> = None;
+
+    loop {
+        let domains = snapshot();
+        selected = selected.min(domains.len().saturating_sub(1));
+        render(&mut term, &domains, selected, status_message.as_deref())?;
+
+        let event = match term.poll_input(Some(REFRESH_INTERVAL))? {
+            Some(InputEvent::Key(event)) => event,
+            _ => continue,
+        };
+        status_message.take();
+        let info = domains.get(selected);
+
+        match event {
+            KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }
+            | KeyEvent {
+                key: KeyCode::Char('q'),
+                ..
+            } => break,
+            KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }
+            | KeyEvent {
+                key: KeyCode::Char('k'),
+                ..
+            } => {
+                selected = selected.saturating_sub(1);
+            }
+            KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }
+            | KeyEvent {
+                key: KeyCode::Char('j'),
+                ..
+            } => {
+                selected += 1;
+            }
+            KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }
+            | KeyEvent {
+                key: KeyCode::Char('a'),
+                ..
+            } => {
+                if let Some(info) = info {
+                    status_message.replace(if info.state == DomainState::Attached {
+                        format!("`{}` is already attached", info.name)
+                    } else {
+                        perform(info, attach_domain, "attaching")
+                    });
+                }
+            }
+            KeyEvent {
+                key: KeyCode::Char('d'),
+                ..
+            } => {
+                if let Some(info) = info {
+                    status_message.replace(perform(info, detach_domain, "detached"));
+                }
+            }
+            KeyEvent {
+                key: KeyCode::Char('r'),
+                ..
+            } => {
+                if let Some(info) = info {
+                    status_message.replace(perform(info, restart_domain, "restarting"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn state() {
+        let now = Instant::now();
+        let mut info = DomainInfo {
+            domain_id: 1,
+            name: "unix".to_string(),
+            label: String::new(),
+            state: DomainState::Detached,
+            num_panes: 0,
+            reconnect_deadline: None,
+        };
+        assert_eq!(describe_state(&info, now), "Detached");
+
+        info.state = DomainState::Attached;
+        assert_eq!(describe_state(&info, now), "Attached");
+
+        info.reconnect_deadline
+            .replace(now + Duration::from_millis(3500));
+        assert_eq!(describe_state(&info, now), "Reconnecting in 4s");
+        assert_eq!(
+            describe_state(&info, now + Duration::from_secs(5)),
+            "Reconnecting in 0s"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! The key bindings overlay is a searchable cheat sheet that lists
+//! the active key and mouse bindings, using the description given
+//! to each binding in the configuration, or an automatically
+//! generated one otherwise.
+use super::palette::{describe_assignment, describe_keys, describe_mouse, fuzzy_match};
+use config::keyassignment::{InputMap, KeyAssignment};
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{grapheme_column_width, AttributeChange, CellAttributes, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The number of rows used by the heading and the filter text
+const HEADER_ROWS: usize = 2;
+
+#[derive(Clone, Debug)]
+pub struct KeyBindingEntry {
+    /// The kind of binding, which is used to group the entries
+    pub group: &'static str,
+    /// A description of the key chord or mouse event
+    pub keys: String,
+    pub brief: String,
+}
+
+fn is_listed(action: &KeyAssignment) -> bool {
+    !matches!(
+        action,
+        KeyAssignment::Nop | KeyAssignment::DisableDefaultAssignment
+    )
+}
+
+/// Builds the list of entries, grouped by the kind of binding
+/// and sorted by description within each group
+pub fn build_key_binding_entries(input_map: &InputMap) -> Vec<KeyBindingEntry> {
+    fn brief(description: Option<&str>, action: &KeyAssignment) -> String {
+        description
+            .map(str::to_string)
+            .unwrap_or_else(|| describe_assignment(action))
+    }
+
+    let mut groups = vec![];
+
+    groups.push(
+        input_map
+            .key_assignments()
+            .filter(|(_, _, action)| is_listed(action))
+            .map(|(key, mods, action)| KeyBindingEntry {
+                group: "Keys",
+                keys: describe_keys(key, mods),
+                brief: brief(input_map.key_description(key, mods), action),
+            })
+            .collect::<Vec<_>>(),
+    );
+    groups.push(
+        input_map
+            .key_up_assignments()
+            .filter(|(_, _, action)| is_listed(action))
+            .map(|(key, mods, action)| KeyBindingEntry {
+                group: "Key releases",
+                keys: describe_keys(key, mods),
+                brief: brief(input_map.key_up_description(key, mods), action),
+            })
+            .collect(),
+    );
+    groups.push(
+        input_map
+            .mouse_assignments()
+            .filter(|(_, _, action)| is_listed(action))
+            .map(|(event, mods, action)| KeyBindingEntry {
+                group: "Mouse",
+                keys: describe_mouse(event, mods),
+                brief: brief(input_map.mouse_description(event, mods), action),
+            })
+            .collect(),
+    );
+
+    let mut entries = vec![];
+    for mut group in groups {
+        group.sort_by(|a, b| a.brief.cmp(&b.brief).then_with(|| a.keys.cmp(&b.keys)));
+        entries.append(&mut group);
+    }
+    entries
+}
+
+/// Returns the lines to display for the entries that match `filter`;
+/// a heading is emitted before the first entry of each group.
+/// Matching is performed against the description and the keys.
+fn filter_lines<'a>(entries: &'a [KeyBindingEntry], filter: &str) -> Vec<Line<'a>> {
+    let mut lines = vec![];
+    let mut group = None;
+    for entry in entries {
+        if fuzzy_match(filter, &entry.brief).is_none() && fuzzy_match(filter, &entry.keys).is_none()
+        {
+            continue;
+        }
+        if group != Some(entry.group) {
+            group = Some(entry.group);
+            lines.push(Line::Heading(entry.group));
+        }
+        lines.push(Line::Entry(entry));
+    }
+    lines
+}
+
+enum Line<'a> {
+    Heading(&'static str),
+    Entry(&'a KeyBindingEntry),
+}
+
+fn render(
+    lines: &[Line],
+    filter: &str,
+    top_row: usize,
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<()> {
+    let size = term.get_screen_size()?;
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        Change::Text(
+            "Type to filter the bindings.  Use the arrow keys to scroll.  \
+             Press Escape to close\r\n"
+                .to_string(),
+        ),
+        Change::AllAttributes(CellAttributes::default()),
+        Change::Text(format!("> {}\r\n", filter)),
+    ];
+
+    let visible_rows = size.rows.saturating_sub(HEADER_ROWS);
+    for line in lines.iter().skip(top_row).take(visible_rows) {
+        match line {
+            Line::Heading(group) => {
+                changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+                changes.push(Change::Text(format!("{}\r\n", group)));
+                changes.push(AttributeChange::Intensity(Intensity::Normal).into());
+            }
+            Line::Entry(entry) => {
+                // Leave room for the key chord and a space either side
+                let label_width = size.cols.saturating_sub(entry.keys.len() + 3);
+                let mut brief = String::new();
+                let mut width = 0;
+                for g in entry.brief.graphemes(true) {
+                    let g_width = grapheme_column_width(g, None);
+                    if width + g_width > label_width {
+                        break;
+                    }
+                    width += g_width;
+                    brief.push_str(g);
+                }
+                let padding = size.cols.saturating_sub(width + entry.keys.len() + 3);
+                changes.push(Change::Text(format!(
+                    "  {}{}{}\r\n",
+                    brief,
+                    " ".repeat(padding),
+                    entry.keys
+                )));
+            }
+        }
+    }
+
+    term.render(&changes)?;
+    term.flush()?;
+    Ok(())
+}
+
+pub fn list_key_bindings(
+    mut term: TermWizTerminal,
+    entries: Vec<KeyBindingEntry>,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title("Key Bindings".to_string())])?;
+
+    let mut filter = String::new();
+    let mut lines = filter_lines(&entries, &filter);
+    let mut top_row = 0;
+
+    render(&lines, &filter, top_row, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let visible_rows = term
+            .get_screen_size()?
+            .rows
+            .saturating_sub(HEADER_ROWS)
+            .max(1);
+        let mut filter_changed = false;
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                top_row = top_row.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                top_row += 1;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            }) => {
+                top_row = top_row.saturating_sub(visible_rows);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            }) => {
+                top_row += visible_rows;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                filter.pop();
+                filter_changed = true;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                filter.push(c);
+                filter_changed = true;
+            }
+            InputEvent::Mouse(MouseEvent { mouse_buttons, .. }) => {
+                if mouse_buttons.contains(MouseButtons::VERT_WHEEL) {
+                    if mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+                        top_row = top_row.saturating_sub(1);
+                    } else {
+                        top_row += 1;
+                    }
+                } else if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as closing the list
+                    break;
+                }
+            }
+            _ => {}
+        }
+
+        if filter_changed {
+            lines = filter_lines(&entries, &filter);
+            top_row = 0;
+        }
+        top_row = top_row.min(lines.len().saturating_sub(visible_rows));
+
+        render(&lines, &filter, top_row, &mut term)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+//! The key event inspector overlay.
+//! The window resolves each key event that arrives while the
+//! inspector is active, without acting on it, and sends the
+//! description here to be displayed.
+use mux::termwiztermtab::TermWizTerminal;
+use std::sync::mpsc::Receiver;
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::surface::Change;
+use termwiz::terminal::Terminal;
+
+pub fn show_key_debug_overlay(
+    mut term: TermWizTerminal,
+    events: Receiver<Vec<String>>,
+) -> anyhow::Result<()> {
+    term.no_grab_mouse_in_raw_mode();
+    term.render(&[
+        Change::Title("Key Events".to_string()),
+        Change::Text(
+            "Key events are described here instead of being acted upon.\r\n\
+             Press Escape twice, or trigger ShowKeyDebugOverlay again, to close.\r\n\r\n"
+                .to_string(),
+        ),
+    ])?;
+
+    // The window closes the inspector by dropping the sender
+    while let Ok(lines) = events.recv() {
+        let mut changes = vec![];
+        for (idx, line) in lines.into_iter().enumerate() {
+            if idx == 0 {
+                changes.push(Change::Attribute(AttributeChange::Intensity(
+                    Intensity::Bold,
+                )));
+                changes.push(Change::Text(format!("{}\r\n", line)));
+                changes.push(Change::Attribute(AttributeChange::Intensity(
+                    Intensity::Normal,
+                )));
+            } else {
+                changes.push(Change::Text(format!("  {}\r\n", line)));
+            }
+        }
+        term.render(&changes)?;
+    }
+    Ok(())
+}
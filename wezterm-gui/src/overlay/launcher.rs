@@ -5,12 +5,16 @@
 //! be rendered as a popup/context menu if the system supports it; at the
 //! time of writing our window layer doesn't provide an API for context
 //! menus.
+//! Entries may be grouped into submenus, which are shown as separate
+//! pages that can be drilled into, and the filter mode searches the
+//! entries of every page at once.
+use crate::overlay::palette::fuzzy_match;
 use crate::termwindow::clipboard::ClipboardHelper;
 use crate::termwindow::spawn::SpawnWhere;
 use crate::termwindow::TermWindow;
 use anyhow::anyhow;
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
-use config::{configuration, TermConfig};
+use config::{configuration, LaunchMenuItem, TermConfig};
 use mux::domain::{DomainId, DomainState};
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
@@ -18,11 +22,15 @@ use mux::window::WindowId;
 use mux::Mux;
 use portable_pty::PtySize;
 use std::sync::Arc;
-use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::cell::{grapheme_column_width, AttributeChange, CellAttributes, Intensity, Underline};
 use termwiz::color::ColorAttribute;
-use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
 use termwiz::surface::{Change, Position};
 use termwiz::terminal::Terminal;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The number of rows used by the heading and the breadcrumb
+const HEADER_ROWS: usize = 2;
 
 #[derive(Clone)]
 enum Entry {
@@ -35,6 +43,10 @@ enum Entry {
         label: String,
         domain: DomainId,
     },
+    Submenu {
+        label: String,
+        children: Vec<Entry>,
+    },
 }
 
 impl Entry {
@@ -42,8 +54,121 @@ impl Entry {
         match self {
             Entry::Spawn { label, .. } => label,
             Entry::Attach { label, .. } => label,
+            Entry::Submenu { label, .. } => label,
+        }
+    }
+
+    fn from_menu_item(item: &LaunchMenuItem) -> Self {
+        match item {
+            LaunchMenuItem::Submenu { label, children } => Entry::Submenu {
+                label: label.to_string(),
+                children: children.iter().map(Entry::from_menu_item).collect(),
+            },
+            LaunchMenuItem::Spawn(command) => Entry::Spawn {
+                label: match command.label.as_ref() {
+                    Some(label) => label.to_string(),
+                    None => match command.args.as_ref() {
+                        Some(args) => args.join(" "),
+                        None => "(default shell)".to_string(),
+                    },
+                },
+                command: command.clone(),
+                spawn_where: SpawnWhere::NewTab,
+            },
+        }
+    }
+}
+
+/// A row of the launcher.  `path` holds the index of the entry, and of
+/// each of the submenus that contain it, starting from the top level.
+struct Row {
+    label: String,
+    path: Vec<usize>,
+    /// The indices of the graphemes in `label` that matched the filter
+    positions: Vec<usize>,
+}
+
+fn entry_at<'a>(entries: &'a [Entry], path: &[usize]) -> Option<&'a Entry> {
+    let (last, parents) = path.split_last()?;
+    let mut entries = entries;
+    for idx in parents {
+        match entries.get(*idx)? {
+            Entry::Submenu { children, .. } => entries = children,
+            _ => return None,
+        }
+    }
+    entries.get(*last)
+}
+
+/// Returns the labels of the submenus that lead to `page`
+fn breadcrumb(entries: &[Entry], page: &[usize]) -> Vec<String> {
+    (1..=page.len())
+        .filter_map(|len| entry_at(entries, &page[..len]))
+        .map(|entry| entry.label().to_string())
+        .collect()
+}
+
+/// Returns the rows for the entries of the submenu at `page`
+fn page_rows(entries: &[Entry], page: &[usize]) -> Vec<Row> {
+    let children = match entry_at(entries, page) {
+        Some(Entry::Submenu { children, .. }) => children,
+        _ => entries,
+    };
+    children
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            let mut path = page.to_vec();
+            path.push(idx);
+            let label = match entry {
+                Entry::Submenu { label, .. } => format!("{} >", label),
+                _ => entry.label().to_string(),
+            };
+            Row {
+                label,
+                path,
+                positions: vec![],
+            }
+        })
+        .collect()
+}
+
+/// Returns rows for the launchable entries at every level that match
+/// `filter`, best first.  Each row is labelled with the labels of the
+/// submenus that contain it, so that the match can consider them too.
+fn filter_rows(entries: &[Entry], filter: &str) -> Vec<Row> {
+    fn collect(entries: &[Entry], prefix: &str, parent: &[usize], rows: &mut Vec<Row>) {
+        for (idx, entry) in entries.iter().enumerate() {
+            let mut path = parent.to_vec();
+            path.push(idx);
+            let label = format!("{}{}", prefix, entry.label());
+            match entry {
+                Entry::Submenu { children, .. } => {
+                    collect(children, &format!("{} > ", label), &path, rows);
+                }
+                _ => rows.push(Row {
+                    label,
+                    path,
+                    positions: vec![],
+                }),
+            }
         }
     }
+
+    let mut rows = vec![];
+    collect(entries, "", &[], &mut rows);
+
+    let mut matches: Vec<(i64, Row)> = rows
+        .into_iter()
+        .filter_map(|mut row| {
+            let (score, positions) = fuzzy_match(filter, &row.label)?;
+            row.positions = positions;
+            Some((score, row))
+        })
+        .collect();
+    // The sort is stable, so equal scores remain in menu order
+    matches.sort_by(|a, b| b.0.cmp(&a.0));
+    matches.into_iter().map(|(_, row)| row).collect()
 }
 
 #[cfg(windows)]
@@ -105,6 +230,7 @@ pub fn launcher(
     mut term: TermWizTerminal,
     mux_window_id: WindowId,
     domains: Vec<(DomainId, String, DomainState, String)>,
+    extra_entries: Vec<LaunchMenuItem>,
     clipboard: ClipboardHelper,
     size: PtySize,
     term_config: Arc<TermConfig>,
@@ -117,19 +243,10 @@ pub fn launcher(
     let config = configuration();
 
     // Pull in the user defined entries from the launch_menu
-    // section of the configuration.
-    for item in &config.launch_menu {
-        entries.push(Entry::Spawn {
-            label: match item.label.as_ref() {
-                Some(label) => label.to_string(),
-                None => match item.args.as_ref() {
-                    Some(args) => args.join(" "),
-                    None => "(default shell)".to_string(),
-                },
-            },
-            command: item.clone(),
-            spawn_where: SpawnWhere::NewTab,
-        });
+    // section of the configuration, followed by those that
+    // were returned by the populate-launch-menu event.
+    for item in config.launch_menu.iter().chain(extra_entries.iter()) {
+        entries.push(Entry::from_menu_item(item));
     }
 
     #[cfg(windows)]
@@ -166,10 +283,13 @@ pub fn launcher(
     }
 
     fn render(
+        rows: &[Row],
+        heading: &str,
         active_idx: usize,
-        entries: &[Entry],
+        top_row: usize,
         term: &mut TermWizTerminal,
-    ) -> termwiz::Result<()> {
+    ) -> anyhow::Result<()> {
+        let size = term.get_screen_size()?;
         let mut changes = vec![
             Change::ClearScreen(ColorAttribute::Default),
             Change::CursorPosition {
@@ -178,38 +298,59 @@ pub fn launcher(
             },
             Change::Text(
                 "Select an item and press Enter to launch it.  \
-                Press Escape to cancel\r\n"
+                Press / to filter, Escape to cancel\r\n"
                     .to_string(),
             ),
             Change::AllAttributes(CellAttributes::default()),
+            Change::Text(format!("{}\r\n", heading)),
         ];
 
-        for (idx, entry) in entries.iter().enumerate() {
+        let visible_rows = size.rows.saturating_sub(HEADER_ROWS);
+        for (idx, row) in rows.iter().enumerate().skip(top_row).take(visible_rows) {
+            // Leave room for a space either side of the label
+            let label_width = size.cols.saturating_sub(2);
+
             if idx == active_idx {
                 changes.push(AttributeChange::Reverse(true).into());
             }
-
-            changes.push(Change::Text(format!(" {} \r\n", entry.label())));
-
+            changes.push(Change::Text(" ".to_string()));
+            let mut width = 0;
+            for (g_idx, g) in row.label.graphemes(true).enumerate() {
+                let g_width = grapheme_column_width(g, None);
+                if width + g_width > label_width {
+                    break;
+                }
+                width += g_width;
+                let matched = row.positions.contains(&g_idx);
+                if matched {
+                    changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+                    changes.push(AttributeChange::Underline(Underline::Single).into());
+                }
+                changes.push(Change::Text(g.to_string()));
+                if matched {
+                    changes.push(AttributeChange::Intensity(Intensity::Normal).into());
+                    changes.push(AttributeChange::Underline(Underline::None).into());
+                }
+            }
+            changes.push(Change::Text(" ".to_string()));
             if idx == active_idx {
                 changes.push(AttributeChange::Reverse(false).into());
             }
+            changes.push(Change::Text("\r\n".to_string()));
         }
-        term.render(&changes)
+        term.render(&changes)?;
+        term.flush()?;
+        Ok(())
     }
 
-    term.render(&[Change::Title("Launcher".to_string())])?;
-    render(active_idx, &entries, &mut term)?;
-
     fn launch(
-        active_idx: usize,
-        entries: &[Entry],
+        entry: &Entry,
         size: PtySize,
         mux_window_id: WindowId,
         clipboard: ClipboardHelper,
         term_config: Arc<TermConfig>,
     ) {
-        match entries[active_idx].clone() {
+        match entry.clone() {
             Entry::Spawn {
                 command,
                 spawn_where,
@@ -236,77 +377,163 @@ pub fn launcher(
                 })
                 .detach();
             }
+            Entry::Submenu { .. } => {}
         }
     }
 
+    // The submenu that is being shown; empty for the top level
+    let mut page: Vec<usize> = vec![];
+    // Set while the filter mode is active
+    let mut filter: Option<String> = None;
+    let mut top_row = 0;
+
+    let heading = |page: &[usize], filter: &Option<String>| match filter {
+        Some(filter) => format!("/ {}", filter),
+        None => {
+            let mut crumbs = vec!["Launcher".to_string()];
+            crumbs.extend(breadcrumb(&entries, page));
+            crumbs.join(" > ")
+        }
+    };
+
+    let mut rows = page_rows(&entries, &page);
+    term.render(&[Change::Title("Launcher".to_string())])?;
+    render(
+        &rows,
+        &heading(&page, &filter),
+        active_idx,
+        top_row,
+        &mut term,
+    )?;
+
     while let Ok(Some(event)) = term.poll_input(None) {
+        // Set to open the active row; `launch_allowed` is cleared
+        // when only a submenu should be opened
+        let mut activate = false;
+        let mut launch_allowed = true;
+
         match event {
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Char('k'),
-                ..
-            })
-            | InputEvent::Key(KeyEvent {
-                key: KeyCode::UpArrow,
-                ..
-            }) => {
-                active_idx = active_idx.saturating_sub(1);
-            }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Char('j'),
-                ..
-            })
-            | InputEvent::Key(KeyEvent {
-                key: KeyCode::DownArrow,
-                ..
-            }) => {
-                active_idx = (active_idx + 1).min(entries.len() - 1);
-            }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Escape,
-                ..
-            }) => {
-                break;
+            InputEvent::Key(KeyEvent { key, modifiers }) => {
+                if let Some(filter_text) = filter.as_mut() {
+                    match key {
+                        KeyCode::UpArrow => {
+                            active_idx = active_idx.saturating_sub(1);
+                        }
+                        KeyCode::DownArrow => {
+                            active_idx = (active_idx + 1).min(rows.len().saturating_sub(1));
+                        }
+                        KeyCode::Escape => {
+                            filter = None;
+                            active_idx = 0;
+                        }
+                        KeyCode::Backspace => {
+                            filter_text.pop();
+                            active_idx = 0;
+                        }
+                        KeyCode::Enter => {
+                            activate = true;
+                        }
+                        KeyCode::Char(c)
+                            if modifiers == Modifiers::NONE || modifiers == Modifiers::SHIFT =>
+                        {
+                            filter_text.push(c);
+                            active_idx = 0;
+                        }
+                        _ => {}
+                    }
+                } else {
+                    match key {
+                        KeyCode::Char('k') | KeyCode::UpArrow => {
+                            active_idx = active_idx.saturating_sub(1);
+                        }
+                        KeyCode::Char('j') | KeyCode::DownArrow => {
+                            active_idx = (active_idx + 1).min(rows.len().saturating_sub(1));
+                        }
+                        KeyCode::Escape => {
+                            break;
+                        }
+                        KeyCode::Char('h') | KeyCode::LeftArrow | KeyCode::Backspace => {
+                            // Return to the parent, with the submenu
+                            // that we came from selected
+                            if let Some(idx) = page.pop() {
+                                active_idx = idx;
+                            }
+                        }
+                        KeyCode::Char('l') | KeyCode::RightArrow => {
+                            activate = true;
+                            launch_allowed = false;
+                        }
+                        KeyCode::Char('/') => {
+                            filter = Some(String::new());
+                            active_idx = 0;
+                        }
+                        KeyCode::Enter => {
+                            activate = true;
+                        }
+                        _ => {}
+                    }
+                }
             }
             InputEvent::Mouse(MouseEvent {
                 y, mouse_buttons, ..
             }) => {
-                if y > 0 && y as usize <= entries.len() {
-                    active_idx = y as usize - 1;
-
+                let y = y as usize;
+                if y >= HEADER_ROWS && top_row + y - HEADER_ROWS < rows.len() {
+                    active_idx = top_row + y - HEADER_ROWS;
                     if mouse_buttons == MouseButtons::LEFT {
-                        launch(
-                            active_idx,
-                            &entries,
-                            size,
-                            mux_window_id,
-                            clipboard,
-                            term_config,
-                        );
-                        break;
+                        activate = true;
                     }
                 }
-                if mouse_buttons != MouseButtons::NONE {
+                if mouse_buttons != MouseButtons::NONE && !activate {
                     // Treat any other mouse button as cancel
                     break;
                 }
             }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Enter,
-                ..
-            }) => {
-                launch(
-                    active_idx,
-                    &entries,
-                    size,
-                    mux_window_id,
-                    clipboard,
-                    term_config,
-                );
-                break;
-            }
             _ => {}
         }
-        render(active_idx, &entries, &mut term)?;
+
+        if activate {
+            if let Some(row) = rows.get(active_idx) {
+                match entry_at(&entries, &row.path) {
+                    Some(Entry::Submenu { .. }) => {
+                        page = row.path.clone();
+                        filter = None;
+                        active_idx = 0;
+                    }
+                    Some(entry) if launch_allowed => {
+                        launch(entry, size, mux_window_id, clipboard, term_config);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        rows = match filter.as_ref() {
+            Some(filter) => filter_rows(&entries, filter),
+            None => page_rows(&entries, &page),
+        };
+        active_idx = active_idx.min(rows.len().saturating_sub(1));
+
+        // Keep the active row visible
+        let visible_rows = term
+            .get_screen_size()?
+            .rows
+            .saturating_sub(HEADER_ROWS)
+            .max(1);
+        if active_idx < top_row {
+            top_row = active_idx;
+        } else if active_idx >= top_row + visible_rows {
+            top_row = active_idx + 1 - visible_rows;
+        }
+
+        render(
+            &rows,
+            &heading(&page, &filter),
+            active_idx,
+            top_row,
+            &mut term,
+        )?;
     }
 
     Ok(())
@@ -322,3 +549,53 @@ fn do_domain_attach(domain: DomainId) {
     })
     .detach();
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spawn(label: &str) -> Entry {
+        Entry::Spawn {
+            label: label.to_string(),
+            command: SpawnCommand::default(),
+            spawn_where: SpawnWhere::NewTab,
+        }
+    }
+
+    #[test]
+    fn nested_entries() {
+        let entries = vec![
+            spawn("top"),
+            Entry::Submenu {
+                label: "Prod".to_string(),
+                children: vec![
+                    spawn("web01"),
+                    Entry::Submenu {
+                        label: "DB".to_string(),
+                        children: vec![spawn("db01")],
+                    },
+                ],
+            },
+        ];
+
+        assert_eq!(entry_at(&entries, &[1, 1, 0]).unwrap().label(), "db01");
+        assert!(entry_at(&entries, &[0, 0]).is_none());
+        assert_eq!(breadcrumb(&entries, &[1, 1]), vec!["Prod", "DB"]);
+
+        let labels =
+            |rows: Vec<Row>| -> Vec<String> { rows.into_iter().map(|r| r.label).collect() };
+        assert_eq!(labels(page_rows(&entries, &[])), vec!["top", "Prod >"]);
+        assert_eq!(labels(page_rows(&entries, &[1])), vec!["web01", "DB >"]);
+
+        // The filter searches every level, and matches the labels
+        // of the submenus as well as those of the entries
+        assert_eq!(
+            labels(filter_rows(&entries, "")),
+            vec!["top", "Prod > web01", "Prod > DB > db01"]
+        );
+        let rows = filter_rows(&entries, "prod db");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].label, "Prod > DB > db01");
+        assert_eq!(rows[0].path, vec![1, 1, 0]);
+    }
+}
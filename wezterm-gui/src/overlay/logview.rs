@@ -0,0 +1,873 @@
+//! The log portion of the debug overlay.
+//! Entries are printed above the repl prompt as they arrive, and
+//! the log can also be browsed full screen, where it can be
+//! searched and filtered by level.
+use env_bootstrap::ringlog::Entry;
+use log::Level;
+use mux::domain::DomainId;
+use mux::termwiztermtab::TermWizTerminal;
+use std::collections::HashMap;
+use std::time::Duration;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes, Intensity};
+use termwiz::color::{AnsiColor, ColorAttribute};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use wezterm_client::client::Client;
+use wezterm_client::domain::ClientDomain;
+
+/// The overlay keeps its own copy of the entries so that filters can
+/// be changed without losing entries that have since been evicted
+/// from the ring; this bounds the size of that copy.
+const MAX_ENTRIES: usize = 10_000;
+
+const DISPLAY_TIMESTAMP_FORMAT: &str = "%H:%M:%S%.3f";
+
+/// Copied entries include the date, as they are likely to end up
+/// in a bug report
+const COPY_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f";
+
+/// How often the browser checks for new entries while idle
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Tracks what has been printed to the overlay from the log ring
+#[derive(Default)]
+pub struct LogView {
+    /// The entries received so far, oldest first
+    entries: Vec<Entry>,
+    /// The sequence number of the most recently seen entry
+    latest: Option<u64>,
+    /// The ring's clear generation when `entries` was last updated
+    clear_generation: u64,
+    /// The number of dropped entries we last reported for each level
+    dropped: HashMap<Level, u64>,
+    /// When set, only entries logged by this thread are shown
+    thread: Option<String>,
+    /// When set, only entries whose message or target contains
+    /// this text (ignoring case) are shown
+    search: String,
+    /// When set, only entries at least as severe as this are shown
+    min_level: Option<Level>,
+    /// The domain of the pane that was active when the overlay was opened
+    domain_id: Option<DomainId>,
+    /// When set, entries from the mux server of `domain_id` are also shown
+    server: Option<ServerLog>,
+    /// The sequence number of the entry under the cursor in the browser
+    cursor: Option<u64>,
+    /// The sequence number of the other end of the selection
+    anchor: Option<u64>,
+    /// Describes the outcome of the most recent action in the browser
+    status_message: Option<String>,
+    /// When true, the browser scrolls to show new entries as they arrive
+    following: bool,
+    /// The sequence number of the first visible entry while paused
+    top: Option<u64>,
+    /// The sequence number of the most recent entry at the time that
+    /// following was paused
+    paused_latest: u64,
+}
+
+struct ServerLog {
+    client: Client,
+    latest: u64,
+}
+
+/// Returns the client for the mux server associated with the domain.
+/// The domain can only be resolved on the main thread.
+fn server_client(domain_id: DomainId) -> anyhow::Result<Client> {
+    promise::spawn::block_on(promise::spawn::spawn_into_main_thread(async move {
+        let inner = ClientDomain::get_client_inner_for_domain(domain_id)
+            .map_err(|_| anyhow::anyhow!("the current pane is not in a multiplexer domain"))?;
+        Ok(inner.client.clone())
+    }))
+}
+
+impl LogView {
+    pub fn new(domain_id: Option<DomainId>) -> Self {
+        Self {
+            domain_id,
+            clear_generation: env_bootstrap::ringlog::clear_generation(),
+            ..Self::default()
+        }
+    }
+
+    /// Handles a `:command` line from the repl, returning text
+    /// that describes the outcome
+    pub fn command(&mut self, command: &str) -> String {
+        let mut words = command.splitn(2, ' ');
+        match (words.next(), words.next().map(str::trim)) {
+            (Some("thread"), Some(name)) if !name.is_empty() => {
+                self.thread.replace(name.to_string());
+                format!("showing only log entries from thread {}", name)
+            }
+            (Some("thread"), _) => {
+                self.thread.take();
+                "showing log entries from all threads".to_string()
+            }
+            (Some("loglevel"), Some(spec)) if !spec.is_empty() => {
+                env_bootstrap::ringlog::set_stderr_filters(spec);
+                format!("stderr and the log file now use the log filters {}", spec)
+            }
+            (Some("server"), _) => {
+                if self.server.take().is_some() {
+                    return "no longer showing server log entries".to_string();
+                }
+                let client = match self
+                    .domain_id
+                    .ok_or_else(|| anyhow::anyhow!("no active pane"))
+                    .and_then(server_client)
+                {
+                    Ok(client) => client,
+                    Err(err) => return format!("{:#}", err),
+                };
+                self.server.replace(ServerLog { client, latest: 0 });
+                "showing server log entries alongside local entries".to_string()
+            }
+            _ => format!(
+                "unknown command :{}. Available commands:\r\n\
+                 :thread NAME       show only log entries from thread NAME\r\n\
+                 :thread            show log entries from all threads\r\n\
+                 :loglevel FILTERS  set the stderr log filters, as in WEZTERM_LOG\r\n\
+                 :quads             show the number of quads used by the last frame\r\n\
+                 :server            toggle showing the mux server log",
+                command
+            ),
+        }
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        if let Some(thread) = &self.thread {
+            if entry.thread_name.as_deref() != Some(thread.as_str()) {
+                return false;
+            }
+        }
+        if let Some(min_level) = self.min_level {
+            if entry.level > min_level {
+                return false;
+            }
+        }
+        if !self.search.is_empty() {
+            let search = self.search.to_lowercase();
+            if !entry.msg.to_lowercase().contains(&search)
+                && !entry.target.to_lowercase().contains(&search)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Moves newly logged entries into our copy, returning the index
+    /// of the first new entry.  Notices about entries that were dropped
+    /// or evicted from the ring are appended to `changes`.
+    fn update(&mut self, changes: &mut Vec<Change>) -> usize {
+        // When the log has been cleared, such as via CTRL-L in the repl,
+        // discard our copy too so that only fresh entries are shown.
+        // This is checked before fetching so that a clear racing with
+        // the fetch is noticed on the next update.
+        let generation = env_bootstrap::ringlog::clear_generation();
+        if generation != self.clear_generation {
+            self.clear_generation = generation;
+            self.entries.clear();
+            self.latest.take();
+            self.dropped.clear();
+            self.paused_latest = 0;
+            self.resume();
+        }
+
+        let (entries, _cursor) =
+            env_bootstrap::ringlog::get_entries_since(self.latest.unwrap_or(0));
+
+        let mut stats: Vec<_> = env_bootstrap::ringlog::get_stats().into_iter().collect();
+        stats.sort_by_key(|(level, _)| *level);
+        for (level, stats) in stats {
+            let shown = self.dropped.entry(level).or_insert(0);
+            if stats.dropped != *shown {
+                *shown = stats.dropped;
+                if stats.dropped > 0 {
+                    changes.push(Change::AllAttributes(CellAttributes::default()));
+                    changes.push(AttributeChange::Intensity(Intensity::Half).into());
+                    changes.push(Change::Text(format!(
+                        "{} {} entries dropped (capacity {})\r\n",
+                        stats.dropped,
+                        level.as_str().to_lowercase(),
+                        stats.capacity
+                    )));
+                }
+            }
+        }
+
+        if self.entries.len() + entries.len() > MAX_ENTRIES {
+            let excess = (self.entries.len() + entries.len()).saturating_sub(MAX_ENTRIES);
+            self.entries.drain(0..excess.min(self.entries.len()));
+        }
+        let mut first_new = self.entries.len();
+
+        // Eviction is detected through the dropped counts above, rather
+        // than through gaps in the sequence numbers: a collapsed repeat
+        // takes on the sequence number of its latest occurrence, which
+        // leaves a gap even though nothing was evicted
+        for entry in entries {
+            self.latest.replace(entry.seq);
+            if entry.repeats > 1 {
+                // The ring collapsed a repeat into an entry that we
+                // already have; replace our copy with the updated one
+                if let Some(idx) = self.entries[..first_new].iter().rposition(|e| {
+                    e.level == entry.level && e.target == entry.target && e.msg == entry.msg
+                }) {
+                    self.entries.remove(idx);
+                    first_new -= 1;
+                }
+            }
+            self.entries.push(entry);
+        }
+
+        first_new
+    }
+
+    pub fn print_new_entries(&mut self, term: &mut TermWizTerminal) -> termwiz::Result<()> {
+        let mut changes = vec![];
+        let first_new = self.update(&mut changes);
+
+        for entry in &self.entries[first_new..] {
+            if self.matches(entry) {
+                render_entry(entry, false, &mut changes);
+            }
+        }
+
+        if let Some(mut server) = self.server.take() {
+            match promise::spawn::block_on(server.client.get_log_entries(codec::GetLogEntries {
+                since_seq: server.latest,
+            })) {
+                Ok(logs) => {
+                    server.latest = logs.latest_seq;
+                    for entry in logs.entries {
+                        if self.matches(&entry) {
+                            changes.push(Change::AllAttributes(CellAttributes::default()));
+                            changes.push(AttributeChange::Intensity(Intensity::Half).into());
+                            changes.push(Change::Text("server ".to_string()));
+                            render_entry(&entry, false, &mut changes);
+                        }
+                    }
+                    self.server.replace(server);
+                }
+                Err(err) => {
+                    changes.push(Change::AllAttributes(CellAttributes::default()));
+                    changes.push(Change::Text(format!(
+                        "unable to fetch server log entries: {:#}\r\n",
+                        err
+                    )));
+                }
+            }
+        }
+        term.render(&changes)
+    }
+
+    /// Shows the log full screen, allowing it to be searched, filtered
+    /// and copied.  `copy` is called with the text of the entries that
+    /// the user chose to copy.  New entries are shown as they arrive
+    /// until the user scrolls, which pauses following the log.
+    /// When `focus` is set, the browser starts out paused with the
+    /// cursor on the entry with that sequence number.
+    /// Returns when Escape is pressed, having cleared the filter and
+    /// re-printed the most recent entries so that the repl can continue
+    /// below them.
+    pub fn browse(
+        &mut self,
+        term: &mut TermWizTerminal,
+        mut editing_search: bool,
+        focus: Option<u64>,
+        copy: &dyn Fn(String),
+    ) -> anyhow::Result<()> {
+        term.set_raw_mode()?;
+        self.resume();
+        if let Some(seq) = focus {
+            self.update(&mut vec![]);
+            if self.entries.iter().any(|e| e.seq == seq) {
+                self.pause();
+                self.cursor.replace(seq);
+            } else {
+                self.status_message
+                    .replace("that entry is no longer in the log".to_string());
+            }
+        }
+
+        let mut redraw = true;
+        loop {
+            let prior_latest = self.latest;
+            self.update(&mut vec![]);
+            let have_new = self.latest != prior_latest;
+            if redraw || (have_new && self.following) {
+                self.render_browser(term, editing_search)?;
+            } else if have_new {
+                // Only the new entry count in the status line changes
+                // while paused, so avoid re-rendering everything
+                self.render_status(term, editing_search)?;
+            }
+
+            redraw = true;
+            let event = match term.poll_input(Some(REFRESH_INTERVAL))? {
+                Some(InputEvent::Key(event)) => event,
+                Some(_) => continue,
+                None => {
+                    redraw = false;
+                    continue;
+                }
+            };
+            self.status_message.take();
+            let page = term.get_screen_size()?.rows.saturating_sub(2).max(1) as isize;
+
+            match event {
+                KeyEvent {
+                    key: KeyCode::Escape,
+                    ..
+                } => {
+                    if self.cursor.is_some() || !self.following {
+                        self.resume();
+                    } else {
+                        break;
+                    }
+                }
+                KeyEvent {
+                    key: KeyCode::Enter,
+                    ..
+                } if editing_search => {
+                    editing_search = false;
+                }
+                KeyEvent {
+                    key: KeyCode::Backspace,
+                    ..
+                } if editing_search => {
+                    self.search.pop();
+                }
+                KeyEvent {
+                    key: KeyCode::Char(c),
+                    modifiers: Modifiers::NONE,
+                }
+                | KeyEvent {
+                    key: KeyCode::Char(c),
+                    modifiers: Modifiers::SHIFT,
+                } if editing_search => {
+                    self.search.push(c);
+                }
+                KeyEvent {
+                    key: KeyCode::Char('/'),
+                    ..
+                } => {
+                    editing_search = true;
+                }
+                KeyEvent {
+                    key: KeyCode::UpArrow,
+                    modifiers,
+                }
+                | KeyEvent {
+                    key: KeyCode::Char('k'),
+                    modifiers,
+                } => {
+                    self.pause();
+                    self.move_cursor(-1, modifiers == Modifiers::SHIFT);
+                }
+                KeyEvent {
+                    key: KeyCode::DownArrow,
+                    modifiers,
+                }
+                | KeyEvent {
+                    key: KeyCode::Char('j'),
+                    modifiers,
+                } => {
+                    self.pause();
+                    self.move_cursor(1, modifiers == Modifiers::SHIFT);
+                }
+                KeyEvent {
+                    key: KeyCode::PageUp,
+                    modifiers,
+                } => {
+                    self.pause();
+                    self.move_cursor(-page, modifiers == Modifiers::SHIFT);
+                }
+                KeyEvent {
+                    key: KeyCode::PageDown,
+                    modifiers,
+                } => {
+                    self.pause();
+                    self.move_cursor(page, modifiers == Modifiers::SHIFT);
+                }
+                KeyEvent {
+                    key: KeyCode::Home,
+                    modifiers,
+                }
+                | KeyEvent {
+                    key: KeyCode::Char('g'),
+                    modifiers,
+                } => {
+                    self.pause();
+                    self.move_cursor(isize::MIN, modifiers == Modifiers::SHIFT);
+                }
+                KeyEvent {
+                    key: KeyCode::End,
+                    modifiers,
+                }
+                | KeyEvent {
+                    key: KeyCode::Char('G'),
+                    modifiers,
+                } => {
+                    self.pause();
+                    self.move_cursor(isize::MAX, modifiers == Modifiers::SHIFT);
+                }
+                KeyEvent {
+                    key: KeyCode::Char('F'),
+                    ..
+                } => self.resume(),
+                KeyEvent {
+                    key: KeyCode::Char('v'),
+                    modifiers: Modifiers::NONE,
+                } => {
+                    if self.anchor.take().is_none() {
+                        self.move_cursor(0, false);
+                        self.anchor = self.cursor;
+                    }
+                }
+                KeyEvent {
+                    key: KeyCode::Char('y'),
+                    modifiers: Modifiers::NONE,
+                }
+                | KeyEvent {
+                    key: KeyCode::Char('C'),
+                    modifiers: Modifiers::CTRL,
+                } => {
+                    let message = Self::copy_entries(&self.selection(), copy);
+                    self.status_message.replace(message);
+                    self.anchor.take();
+                }
+                KeyEvent {
+                    key: KeyCode::Char('Y'),
+                    ..
+                } => {
+                    let message = Self::copy_entries(&self.matching(), copy);
+                    self.status_message.replace(message);
+                }
+                KeyEvent {
+                    key: KeyCode::Char(c),
+                    modifiers: Modifiers::NONE,
+                } => {
+                    let level = match c {
+                        'e' => Level::Error,
+                        'w' => Level::Warn,
+                        'i' => Level::Info,
+                        'd' => Level::Debug,
+                        't' => Level::Trace,
+                        _ => continue,
+                    };
+                    // Selecting the current level again removes the filter
+                    if self.min_level == Some(level) {
+                        self.min_level.take();
+                    } else {
+                        self.min_level.replace(level);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.search.clear();
+        self.min_level.take();
+        self.resume();
+        self.render_tail(term)?;
+        Ok(())
+    }
+
+    /// Stops scrolling to show new entries
+    fn pause(&mut self) {
+        if self.following {
+            self.following = false;
+            self.paused_latest = self.latest.unwrap_or(0);
+        }
+    }
+
+    /// Discards the cursor and selection and resumes
+    /// scrolling to show new entries
+    fn resume(&mut self) {
+        self.following = true;
+        self.top.take();
+        self.cursor.take();
+        self.anchor.take();
+    }
+
+    fn matching(&self) -> Vec<&Entry> {
+        self.entries.iter().filter(|e| self.matches(e)).collect()
+    }
+
+    /// Returns the index within `matching` of the entry with
+    /// the specified sequence number
+    fn position(matching: &[&Entry], seq: Option<u64>) -> Option<usize> {
+        let seq = seq?;
+        matching.iter().position(|e| e.seq == seq)
+    }
+
+    /// Moves the cursor by `delta` entries, placing it on the most
+    /// recent entry if it isn't already visible.  When `extend` is
+    /// true the selection is extended to the new cursor position.
+    fn move_cursor(&mut self, delta: isize, extend: bool) {
+        let matching = self.matching();
+        if matching.is_empty() {
+            return;
+        }
+        let idx = match Self::position(&matching, self.cursor) {
+            Some(idx) => {
+                if delta < 0 {
+                    idx.saturating_sub(delta.unsigned_abs())
+                } else {
+                    idx.saturating_add(delta as usize).min(matching.len() - 1)
+                }
+            }
+            None => matching.len() - 1,
+        };
+        let seq = matching[idx].seq;
+        if extend && self.anchor.is_none() {
+            self.anchor = self.cursor.or(Some(seq));
+        }
+        self.cursor.replace(seq);
+    }
+
+    /// Returns the entries between the anchor and the cursor, or
+    /// just the entry under the cursor if there is no anchor
+    fn selection(&self) -> Vec<&Entry> {
+        let matching = self.matching();
+        let cursor = match Self::position(&matching, self.cursor) {
+            Some(idx) => idx,
+            None => return vec![],
+        };
+        let anchor = Self::position(&matching, self.anchor).unwrap_or(cursor);
+        let (start, end) = if anchor <= cursor {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        };
+        matching[start..=end].to_vec()
+    }
+
+    /// Passes the plain text of the entries to `copy`, returning
+    /// a message that describes the outcome
+    fn copy_entries(entries: &[&Entry], copy: &dyn Fn(String)) -> String {
+        if entries.is_empty() {
+            return "move the cursor to an entry to select it".to_string();
+        }
+        let mut text = String::new();
+        for entry in entries {
+            text.push_str(&entry_text(entry, COPY_TIMESTAMP_FORMAT));
+            text.push('\n');
+        }
+        copy(text);
+        format!(
+            "copied {} {} to the clipboard",
+            entries.len(),
+            if entries.len() == 1 {
+                "entry"
+            } else {
+                "entries"
+            }
+        )
+    }
+
+    /// Clears the screen and prints as many of the most recent
+    /// matching entries as will fit, leaving room for the prompt
+    fn render_tail(&self, term: &mut TermWizTerminal) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let matching = self.matching();
+        let first = first_visible(&matching, size.cols, size.rows.saturating_sub(1));
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+        ];
+        for entry in &matching[first..] {
+            render_entry(entry, false, &mut changes);
+        }
+        term.render(&changes)
+    }
+
+    fn status_line(&self, matching: &[&Entry], editing_search: bool) -> String {
+        let mut status = String::new();
+        if !self.following {
+            let new_entries = matching
+                .iter()
+                .filter(|e| e.seq > self.paused_latest)
+                .count();
+            status.push_str(&format!(
+                "PAUSED \u{2014} {} new {} (F to follow) | ",
+                new_entries,
+                if new_entries == 1 { "entry" } else { "entries" }
+            ));
+        }
+        status.push_str(&format!(
+            "showing {} of {} entries",
+            matching.len(),
+            self.entries.len()
+        ));
+        if let Some(level) = self.min_level {
+            status.push_str(&format!(" | level: {}+", level.as_str().to_lowercase()));
+        }
+        if editing_search || !self.search.is_empty() {
+            status.push_str(&format!(" | /{}", self.search));
+        }
+        if let Some(message) = &self.status_message {
+            status.push_str(&format!(" | {}", message));
+        } else if !editing_search {
+            status.push_str(
+                " | / search, e/w/i/d/t level, j/k move, F follow, \
+                 v select, y copy, Y copy all, Esc return to repl",
+            );
+        }
+        status
+    }
+
+    fn push_status(changes: &mut Vec<Change>, status: &str, row: usize, cols: usize) {
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(row),
+        });
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+        changes.push(Change::ClearToEndOfLine(ColorAttribute::Default));
+        changes.push(AttributeChange::Reverse(true).into());
+        changes.push(Change::Text(truncate(status, cols)));
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+    }
+
+    fn render_status(
+        &self,
+        term: &mut TermWizTerminal,
+        editing_search: bool,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let status = self.status_line(&self.matching(), editing_search);
+        let mut changes = vec![];
+        Self::push_status(
+            &mut changes,
+            &status,
+            size.rows.saturating_sub(1),
+            size.cols,
+        );
+        term.render(&changes)?;
+        term.flush()
+    }
+
+    fn render_browser(
+        &mut self,
+        term: &mut TermWizTerminal,
+        editing_search: bool,
+    ) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let rows = size.rows.saturating_sub(1);
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+        ];
+
+        let top = {
+            let matching = self.matching();
+            let tail = first_visible(&matching, size.cols, rows);
+            let cursor = Self::position(&matching, self.cursor);
+
+            let first = if self.following && cursor.is_none() {
+                tail
+            } else {
+                // Stay where we were when paused, but never leave
+                // blank space below the most recent entry
+                let mut first = self
+                    .top
+                    .and_then(|top| matching.iter().position(|e| e.seq >= top))
+                    .unwrap_or(tail)
+                    .min(tail);
+                // and keep the cursor in view
+                if let Some(cursor) = cursor {
+                    if cursor < first {
+                        first = cursor;
+                    } else {
+                        first = first.max(first_visible(&matching[..=cursor], size.cols, rows));
+                    }
+                }
+                first
+            };
+
+            let selected = match cursor {
+                Some(cursor) => {
+                    let anchor = Self::position(&matching, self.anchor).unwrap_or(cursor);
+                    anchor.min(cursor)..anchor.max(cursor) + 1
+                }
+                None => 0..0,
+            };
+
+            let mut used = 0;
+            for (idx, entry) in matching.iter().enumerate().skip(first) {
+                let height = entry_height(entry, size.cols);
+                if used + height > rows {
+                    break;
+                }
+                used += height;
+                render_entry(entry, selected.contains(&idx), &mut changes);
+            }
+
+            let status = self.status_line(&matching, editing_search);
+            Self::push_status(&mut changes, &status, rows, size.cols);
+
+            matching.get(first).map(|e| e.seq)
+        };
+        if !self.following {
+            self.top = top;
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    }
+}
+
+/// Returns the index of the first of `entries` that should be shown
+/// such that the last entry is visible within `rows` rows
+fn first_visible(entries: &[&Entry], cols: usize, rows: usize) -> usize {
+    let mut used = 0;
+    let mut first = entries.len();
+    while first > 0 {
+        let height = entry_height(entries[first - 1], cols);
+        if used + height > rows {
+            break;
+        }
+        used += height;
+        first -= 1;
+    }
+    first
+}
+
+/// Returns the number of rows that the entry occupies when rendered.
+/// Messages such as panics can span several lines, each of which
+/// may also wrap.
+fn entry_height(entry: &Entry, cols: usize) -> usize {
+    entry_lines(&entry_text(entry, DISPLAY_TIMESTAMP_FORMAT))
+        .map(|line| {
+            let width = unicode_column_width(line, None);
+            if cols == 0 || width == 0 {
+                1
+            } else {
+                (width + cols - 1) / cols
+            }
+        })
+        .sum()
+}
+
+/// Splits text from an entry into the lines that it is rendered as
+fn entry_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split('\n').map(|line| line.trim_end_matches('\r'))
+}
+
+fn truncate(text: &str, cols: usize) -> String {
+    let mut result = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let mut buf = [0u8; 4];
+        let c_width = unicode_column_width(c.encode_utf8(&mut buf), None);
+        if width + c_width > cols {
+            break;
+        }
+        width += c_width;
+        result.push(c);
+    }
+    result
+}
+
+#[derive(Clone, Copy)]
+enum Style {
+    Normal,
+    Dim,
+    Bold,
+    Level(Level),
+}
+
+/// Breaks the entry into styled pieces of text, so that it can be
+/// rendered consistently with and without attributes
+fn entry_segments(entry: &Entry, timestamp_format: &str) -> Vec<(Style, String)> {
+    let mut segments = vec![
+        (
+            Style::Normal,
+            format!("{} ", entry.format_timestamp(timestamp_format)),
+        ),
+        (
+            Style::Level(entry.level),
+            match entry.level {
+                Level::Error => "ERROR",
+                Level::Warn => "WARNING",
+                Level::Info => "INFO",
+                Level::Debug => "DEBUG",
+                Level::Trace => "TRACE",
+            }
+            .to_string(),
+        ),
+    ];
+    if let Some(thread) = &entry.thread_name {
+        segments.push((Style::Dim, format!(" [{}]", thread)));
+    }
+    segments.push((Style::Bold, format!(" {}", entry.target)));
+    if let Some(file) = &entry.file {
+        segments.push((
+            Style::Dim,
+            match entry.line {
+                Some(line) => format!(" {}:{}", file, line),
+                None => format!(" {}", file),
+            },
+        ));
+    } else if let Some(module_path) = &entry.module_path {
+        segments.push((Style::Dim, format!(" {}", module_path)));
+    }
+    if entry.repeats > 1 {
+        segments.push((
+            Style::Normal,
+            format!(" > {} (x{})", entry.msg, entry.repeats),
+        ));
+    } else {
+        segments.push((Style::Normal, format!(" > {}", entry.msg)));
+    }
+    segments
+}
+
+/// Returns the entry as it is rendered, without any attributes
+fn entry_text(entry: &Entry, timestamp_format: &str) -> String {
+    entry_segments(entry, timestamp_format)
+        .into_iter()
+        .map(|(_, text)| text)
+        .collect()
+}
+
+fn render_entry(entry: &Entry, selected: bool, changes: &mut Vec<Change>) {
+    for (style, text) in entry_segments(entry, DISPLAY_TIMESTAMP_FORMAT) {
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+        if selected {
+            changes.push(AttributeChange::Reverse(true).into());
+        }
+        match style {
+            Style::Normal => {}
+            Style::Dim => changes.push(AttributeChange::Intensity(Intensity::Half).into()),
+            Style::Bold => changes.push(AttributeChange::Intensity(Intensity::Bold).into()),
+            Style::Level(level) => changes.push(
+                AttributeChange::Foreground(match level {
+                    Level::Error => AnsiColor::Maroon.into(),
+                    Level::Warn => AnsiColor::Red.into(),
+                    Level::Info => AnsiColor::Green.into(),
+                    Level::Debug => AnsiColor::Blue.into(),
+                    Level::Trace => AnsiColor::Fuschia.into(),
+                })
+                .into(),
+            ),
+        }
+        // The terminal doesn't translate a bare newline into a carriage
+        // return, so multi-line messages would otherwise staircase
+        changes.push(Change::Text(
+            entry_lines(&text).collect::<Vec<_>>().join("\r\n"),
+        ));
+    }
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+    changes.push(Change::Text("\r\n".to_string()));
+}
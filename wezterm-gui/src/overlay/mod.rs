@@ -9,7 +9,12 @@ use std::rc::Rc;
 mod confirm_close_pane;
 mod copy;
 mod debug;
+mod domainstatus;
+mod keybindings;
+mod keydebug;
 mod launcher;
+mod logview;
+mod palette;
 mod quickselect;
 mod search;
 mod tabnavigator;
@@ -18,9 +23,14 @@ pub use confirm_close_pane::confirm_close_pane;
 pub use confirm_close_pane::confirm_close_tab;
 pub use confirm_close_pane::confirm_close_window;
 pub use confirm_close_pane::confirm_quit_program;
+pub use confirm_close_pane::processes_preventing_close;
 pub use copy::CopyOverlay;
 pub use debug::show_debug_overlay;
+pub use domainstatus::{attach_domain, detach_domain, domain_status};
+pub use keybindings::{build_key_binding_entries, list_key_bindings};
+pub use keydebug::show_key_debug_overlay;
 pub use launcher::launcher;
+pub use palette::{build_entries, command_palette, PaletteEntry};
 pub use quickselect::QuickSelectOverlay;
 pub use search::SearchOverlay;
 pub use tabnavigator::tab_navigator;
@@ -0,0 +1,705 @@
+//! The command palette is an overlay that lists the key assignments
+//! from the configuration, a set of commonly used built-in actions and
+//! any commands added by the `augment-command-palette` event.
+//! Typing filters the list using fuzzy subsequence matching, and the
+//! chosen entry is returned to the caller so that it can be performed
+//! once the overlay has been dismissed.
+use config::keyassignment::{
+    BroadcastScope, InputMap, KeyAssignment, MouseEventTrigger, PaneLayout, RotationDirection,
+    ScrollbackEraseMode, SpawnCommand, SpawnTabDomain,
+};
+use mux::termwiztermtab::TermWizTerminal;
+use std::io::Write;
+use std::path::PathBuf;
+use termwiz::cell::{grapheme_column_width, AttributeChange, CellAttributes, Intensity, Underline};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The number of recently used commands to remember
+const RECENT_LIMIT: usize = 100;
+
+/// The number of rows used by the heading and the filter text
+const HEADER_ROWS: usize = 2;
+
+#[derive(Clone, Debug)]
+pub struct PaletteEntry {
+    pub brief: String,
+    pub action: KeyAssignment,
+    /// A description of the key chord that is bound to the action
+    pub keys: Option<String>,
+}
+
+/// Returns a human readable description of a key assignment
+pub fn describe_assignment(action: &KeyAssignment) -> String {
+    use KeyAssignment::*;
+    match action {
+        SpawnTab(SpawnTabDomain::DefaultDomain) => "New tab in the default domain".to_string(),
+        SpawnTab(SpawnTabDomain::CurrentPaneDomain) => "New tab".to_string(),
+        SpawnTab(SpawnTabDomain::DomainName(name)) => format!("New tab in domain `{}`", name),
+        SpawnWindow => "New window".to_string(),
+        ToggleFullScreen => "Toggle full screen mode".to_string(),
+        Copy | CopyTo(_) => "Copy the selection".to_string(),
+        Paste | PasteFrom(_) | PastePrimarySelection => "Paste".to_string(),
+        ActivateTabRelative(n) if *n < 0 => "Activate the tab to the left".to_string(),
+        ActivateTabRelative(_) => "Activate the tab to the right".to_string(),
+        IncreaseFontSize => "Increase the font size".to_string(),
+        DecreaseFontSize => "Decrease the font size".to_string(),
+        ResetFontSize => "Reset the font size".to_string(),
+        ResetFontAndWindowSize => "Reset the font and window size".to_string(),
+        ActivateTab(n) if *n < 0 => format!("Activate tab {} from the right", -n),
+        ActivateTab(n) => format!("Activate tab {}", n + 1),
+        ActivateLastTab => "Activate the previously active tab".to_string(),
+        SendString(s) => format!("Send the string {:?}", s),
+        Hide => "Hide the window".to_string(),
+        Show => "Show the window".to_string(),
+        CloseCurrentTab { .. } => "Close the current tab".to_string(),
+        ReloadConfiguration => "Reload the configuration".to_string(),
+        MoveTabRelative(n) if *n < 0 => "Move the tab to the left".to_string(),
+        MoveTabRelative(_) => "Move the tab to the right".to_string(),
+        MoveTab(n) => format!("Move the tab to position {}", n + 1),
+        MoveTabToNewWindow => "Move the tab to a new window".to_string(),
+        ScrollByPage(n) if *n < 0 => "Scroll up by a page".to_string(),
+        ScrollByPage(_) => "Scroll down by a page".to_string(),
+        ScrollByLine(n) if *n < 0 => "Scroll up by a line".to_string(),
+        ScrollByLine(_) => "Scroll down by a line".to_string(),
+        ScrollToPrompt(n) if *n < 0 => "Scroll to the previous prompt".to_string(),
+        ScrollToPrompt(_) => "Scroll to the next prompt".to_string(),
+        ScrollToTop => "Scroll to the top".to_string(),
+        ScrollToBottom => "Scroll to the bottom".to_string(),
+        ShowTabNavigator => "Show the tab navigator".to_string(),
+        ShowDebugOverlay => "Show the debug overlay".to_string(),
+        ShowKeyDebugOverlay => "Inspect key events".to_string(),
+        ListKeyBindings => "List key bindings".to_string(),
+        HideApplication => "Hide the application".to_string(),
+        QuitApplication => "Quit wezterm".to_string(),
+        SpawnCommandInNewTab(cmd) => format!("New tab running {}", describe_command(cmd)),
+        SpawnCommandInNewWindow(cmd) => {
+            format!("New window running {}", describe_command(cmd))
+        }
+        SplitHorizontal(_) => "Split the pane horizontally".to_string(),
+        SplitVertical(_) => "Split the pane vertically".to_string(),
+        ShowLauncher => "Show the launcher".to_string(),
+        ShowDomainStatus => "Show the status of the domains".to_string(),
+        AttachDomain(name) => format!("Attach the `{}` domain", name),
+        DetachDomain(SpawnTabDomain::DomainName(name)) => format!("Detach the `{}` domain", name),
+        DetachDomain(SpawnTabDomain::CurrentPaneDomain) => {
+            "Detach the domain of the current pane".to_string()
+        }
+        DetachDomain(SpawnTabDomain::DefaultDomain) => "Detach the default domain".to_string(),
+        ClearScrollback(ScrollbackEraseMode::ScrollbackOnly) => "Clear the scrollback".to_string(),
+        ClearScrollback(ScrollbackEraseMode::ScrollbackAndViewport) => {
+            "Clear the scrollback and the viewport".to_string()
+        }
+        Search(_) => "Search the scrollback".to_string(),
+        ActivateCopyMode => "Activate copy mode".to_string(),
+        AdjustPaneSize(direction, n) => format!("Resize the pane {:?} by {}", direction, n),
+        ActivatePaneDirection(direction) => format!("Activate the pane {:?}", direction),
+        ActivateLastPane => "Activate the previously active pane".to_string(),
+        TogglePaneZoomState => "Toggle the zoom state of the pane".to_string(),
+        BalancePanes => "Balance the sizes of the panes".to_string(),
+        ApplyPaneLayout(layout) => format!(
+            "Arrange the panes in the {} layout",
+            describe_layout(*layout)
+        ),
+        RotatePanes(RotationDirection::Clockwise) => "Rotate the panes clockwise".to_string(),
+        RotatePanes(RotationDirection::CounterClockwise) => {
+            "Rotate the panes counter-clockwise".to_string()
+        }
+        SwapActivePaneWithIndex(pane_index) => {
+            format!("Swap the active pane with pane {}", pane_index)
+        }
+        ToggleInputBroadcast {
+            scope: BroadcastScope::Tab,
+        } => "Toggle broadcasting input to the panes of the tab".to_string(),
+        ToggleInputBroadcast {
+            scope: BroadcastScope::Window,
+        } => "Toggle broadcasting input to the panes of the window".to_string(),
+        ToggleInputBroadcast {
+            scope: BroadcastScope::Selected,
+        } => "Toggle broadcasting input to the pane".to_string(),
+        MovePaneToNewTab => "Move the pane to a new tab".to_string(),
+        MovePaneToNewWindow => "Move the pane to a new window".to_string(),
+        MovePane { tab_id, direction } => {
+            format!(
+                "Move the pane {:?} of the active pane of tab {}",
+                direction, tab_id
+            )
+        }
+        CloseCurrentPane { .. } => "Close the current pane".to_string(),
+        EmitEvent(name) => format!("Emit the `{}` event", name),
+        QuickSelect => "Quick select".to_string(),
+        StartKeyRecording(register) => format!("Record keys into register `{}`", register),
+        StopKeyRecording => "Stop recording keys".to_string(),
+        ReplayKeyRecording(register) => format!("Replay keys from register `{}`", register),
+        RepeatLastAssignment => "Repeat the last action".to_string(),
+        MoveBackwardSemanticZone(zone_type) => {
+            format!("Move to the previous {:?} zone in copy mode", zone_type)
+        }
+        MoveForwardSemanticZone(zone_type) => {
+            format!("Move to the next {:?} zone in copy mode", zone_type)
+        }
+        SelectSemanticZone(zone_type) => format!("Select the {:?} zone in copy mode", zone_type),
+        Multiple(actions) => actions
+            .iter()
+            .map(describe_assignment)
+            .collect::<Vec<_>>()
+            .join(", then "),
+        _ => format!("{:?}", action),
+    }
+}
+
+/// Returns the name that is used for the layout in the configuration
+fn describe_layout(layout: PaneLayout) -> &'static str {
+    match layout {
+        PaneLayout::MainVertical => "main-vertical",
+        PaneLayout::Tiled => "tiled",
+        PaneLayout::EvenHorizontal => "even-horizontal",
+    }
+}
+
+fn describe_command(cmd: &SpawnCommand) -> String {
+    match (&cmd.label, &cmd.args) {
+        (Some(label), _) => label.to_string(),
+        (None, Some(args)) => args.join(" "),
+        (None, None) => "the default program".to_string(),
+    }
+}
+
+fn modifier_names(mods: ::window::Modifiers) -> Vec<String> {
+    use ::window::Modifiers as WM;
+    let mut parts = vec![];
+    for (flag, name) in &[
+        (WM::LEADER, "LEADER"),
+        (WM::SUPER, "SUPER"),
+        (WM::CTRL, "CTRL"),
+        (WM::ALT, "ALT"),
+        (WM::SHIFT, "SHIFT"),
+    ] {
+        if mods.contains(*flag) {
+            parts.push(name.to_string());
+        }
+    }
+    parts
+}
+
+/// Returns a description of a key chord, such as `CTRL+SHIFT+P`
+pub fn describe_keys(key: &::window::KeyCode, mods: ::window::Modifiers) -> String {
+    let mut parts = modifier_names(mods);
+    let key = match key {
+        ::window::KeyCode::Char(c) => {
+            if c.is_ascii_uppercase() && !mods.contains(::window::Modifiers::SHIFT) {
+                parts.push("SHIFT".to_string());
+            }
+            match c {
+                ' ' => "Space".to_string(),
+                '\r' | '\n' => "Enter".to_string(),
+                '\t' => "Tab".to_string(),
+                c => c.to_string(),
+            }
+        }
+        ::window::KeyCode::Function(n) => format!("F{}", n),
+        key => format!("{:?}", key),
+    };
+    parts.push(key);
+    parts.join("+")
+}
+
+/// Returns a description of a mouse event, such as `CTRL+Double Left Down`
+pub fn describe_mouse(event: &MouseEventTrigger, mods: ::window::Modifiers) -> String {
+    let (streak, button, what) = match event {
+        MouseEventTrigger::Down { streak, button } => (streak, button, "Down"),
+        MouseEventTrigger::Up { streak, button } => (streak, button, "Up"),
+        MouseEventTrigger::Drag { streak, button } => (streak, button, "Drag"),
+    };
+    let streak = match streak {
+        1 => String::new(),
+        2 => "Double ".to_string(),
+        3 => "Triple ".to_string(),
+        n => format!("{}x ", n),
+    };
+    let mut parts = modifier_names(mods);
+    parts.push(format!("{}{:?} {}", streak, button, what));
+    parts.join("+")
+}
+
+/// Builds the list of entries; the user-defined commands come first,
+/// followed by the key assignments and then any built-in actions that
+/// are not bound to a key.  Each action is listed only once.
+pub fn build_entries(input_map: &InputMap, commands: Vec<PaletteEntry>) -> Vec<PaletteEntry> {
+    let mut entries = commands;
+
+    let mut bound: Vec<(String, &KeyAssignment, Option<&str>)> = input_map
+        .key_assignments()
+        .filter(|(_, _, action)| match action {
+            KeyAssignment::Nop
+            | KeyAssignment::DisableDefaultAssignment
+            | KeyAssignment::ActivateCommandPalette => false,
+            _ => true,
+        })
+        .map(|(key, mods, action)| {
+            (
+                describe_keys(key, mods),
+                action,
+                input_map.key_description(key, mods),
+            )
+        })
+        .collect();
+    // Prefer the shortest chord when an action is bound more than once
+    bound.sort_by(|a, b| a.0.len().cmp(&b.0.len()).then_with(|| a.0.cmp(&b.0)));
+
+    let mut assigned: Vec<PaletteEntry> = vec![];
+    for (keys, action, description) in bound {
+        if entries
+            .iter()
+            .chain(assigned.iter())
+            .any(|e| e.action == *action)
+        {
+            continue;
+        }
+        assigned.push(PaletteEntry {
+            brief: description
+                .map(str::to_string)
+                .unwrap_or_else(|| describe_assignment(action)),
+            action: action.clone(),
+            keys: Some(keys),
+        });
+    }
+    assigned.sort_by(|a, b| a.brief.cmp(&b.brief));
+    entries.append(&mut assigned);
+
+    use KeyAssignment::*;
+    for action in vec![
+        SpawnTab(SpawnTabDomain::CurrentPaneDomain),
+        SpawnWindow,
+        SplitHorizontal(SpawnCommand {
+            domain: SpawnTabDomain::CurrentPaneDomain,
+            ..SpawnCommand::default()
+        }),
+        SplitVertical(SpawnCommand {
+            domain: SpawnTabDomain::CurrentPaneDomain,
+            ..SpawnCommand::default()
+        }),
+        CloseCurrentPane { confirm: true },
+        CloseCurrentTab { confirm: true },
+        TogglePaneZoomState,
+        BalancePanes,
+        ApplyPaneLayout(PaneLayout::MainVertical),
+        ApplyPaneLayout(PaneLayout::Tiled),
+        ApplyPaneLayout(PaneLayout::EvenHorizontal),
+        RotatePanes(RotationDirection::Clockwise),
+        RotatePanes(RotationDirection::CounterClockwise),
+        ToggleInputBroadcast {
+            scope: BroadcastScope::Tab,
+        },
+        ToggleInputBroadcast {
+            scope: BroadcastScope::Window,
+        },
+        ToggleInputBroadcast {
+            scope: BroadcastScope::Selected,
+        },
+        MovePaneToNewTab,
+        MovePaneToNewWindow,
+        MoveTabToNewWindow,
+        ToggleFullScreen,
+        ShowLauncher,
+        ShowDomainStatus,
+        ShowTabNavigator,
+        ShowDebugOverlay,
+        ShowKeyDebugOverlay,
+        ListKeyBindings,
+        ActivateCopyMode,
+        QuickSelect,
+        ClearScrollback(ScrollbackEraseMode::ScrollbackOnly),
+        ScrollToTop,
+        ScrollToBottom,
+        IncreaseFontSize,
+        DecreaseFontSize,
+        ResetFontSize,
+        ReloadConfiguration,
+        QuitApplication,
+    ] {
+        if entries.iter().any(|e| e.action == action) {
+            continue;
+        }
+        entries.push(PaletteEntry {
+            brief: describe_assignment(&action),
+            action,
+            keys: None,
+        });
+    }
+
+    entries
+}
+
+/// Matches `pattern` as a case insensitive subsequence of `text`.
+/// Returns a score, where higher is better, and the indices of the
+/// graphemes in `text` that matched, or None if it doesn't match.
+/// Consecutive matches and matches at the start of words score
+/// more highly, and unmatched gaps reduce the score.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<String> = pattern
+        .graphemes(true)
+        .filter(|g| !g.trim().is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut score = 0;
+    let mut positions = vec![];
+    let mut pattern_iter = pattern.iter().peekable();
+    let mut prev: Option<&str> = None;
+    let mut last_match: Option<usize> = None;
+
+    for (idx, g) in text.graphemes(true).enumerate() {
+        let wanted = match pattern_iter.peek() {
+            Some(wanted) => wanted,
+            None => break,
+        };
+        if g.to_lowercase() == **wanted {
+            score += 16;
+            if last_match.map(|last| last + 1 == idx).unwrap_or(false) {
+                score += 8;
+            }
+            let at_word_start = prev
+                .map(|p| !p.chars().all(char::is_alphanumeric))
+                .unwrap_or(true);
+            if at_word_start {
+                score += 8;
+            }
+            if let Some(last) = last_match {
+                score -= (idx - last - 1) as i64;
+            }
+            positions.push(idx);
+            last_match = Some(idx);
+            pattern_iter.next();
+        }
+        prev = Some(g);
+    }
+
+    if pattern_iter.peek().is_some() {
+        None
+    } else {
+        Some((score, positions))
+    }
+}
+
+/// Remembers the briefs of the most recently used entries across
+/// sessions, most recent first.
+struct RecentCommands {
+    path: PathBuf,
+    recent: Vec<String>,
+}
+
+impl RecentCommands {
+    fn load() -> Self {
+        let path = config::CACHE_DIR.join("command-palette-recent");
+        let recent = match std::fs::read(&path) {
+            Ok(data) => String::from_utf8_lossy(&data)
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!(
+                        "ignoring command palette history {}: {:#}",
+                        path.display(),
+                        err
+                    );
+                }
+                vec![]
+            }
+        };
+        Self { path, recent }
+    }
+
+    /// Returns the rank of `brief`, where lower is more recently used
+    fn rank(&self, brief: &str) -> usize {
+        self.recent
+            .iter()
+            .position(|r| r == brief)
+            .unwrap_or(usize::max_value())
+    }
+
+    fn add(&mut self, brief: &str) {
+        // Newlines would corrupt the file, and are unlikely to be
+        // worth remembering
+        if brief.contains('\n') {
+            return;
+        }
+        self.recent.retain(|r| r != brief);
+        self.recent.insert(0, brief.to_string());
+        self.recent.truncate(RECENT_LIMIT);
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temp = self.path.with_extension("tmp");
+        let mut file = std::fs::File::create(&temp)?;
+        for line in &self.recent {
+            writeln!(file, "{}", line)?;
+        }
+        drop(file);
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+struct Match {
+    entry_idx: usize,
+    positions: Vec<usize>,
+}
+
+/// Returns the entries that match `filter`, best first
+fn filter_entries(entries: &[PaletteEntry], filter: &str, recent: &RecentCommands) -> Vec<Match> {
+    let mut matches: Vec<(i64, usize, Match)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(entry_idx, entry)| {
+            let (score, positions) = fuzzy_match(filter, &entry.brief)?;
+            Some((
+                score,
+                recent.rank(&entry.brief),
+                Match {
+                    entry_idx,
+                    positions,
+                },
+            ))
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.cmp(&b.1))
+            .then_with(|| a.2.entry_idx.cmp(&b.2.entry_idx))
+    });
+    matches.into_iter().map(|(_, _, m)| m).collect()
+}
+
+fn render(
+    entries: &[PaletteEntry],
+    matches: &[Match],
+    filter: &str,
+    active_idx: usize,
+    top_row: usize,
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<()> {
+    let size = term.get_screen_size()?;
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        Change::Text(
+            "Type to filter, then press Enter to run the selected command.  \
+             Press Escape to cancel\r\n"
+                .to_string(),
+        ),
+        Change::AllAttributes(CellAttributes::default()),
+        Change::Text(format!("> {}\r\n", filter)),
+    ];
+
+    let visible_rows = size.rows.saturating_sub(HEADER_ROWS);
+    for (idx, m) in matches.iter().enumerate().skip(top_row).take(visible_rows) {
+        let entry = &entries[m.entry_idx];
+        let keys = entry.keys.as_deref().unwrap_or("");
+        // Leave room for the key chord and a space either side
+        let label_width = size.cols.saturating_sub(keys.len() + 3);
+
+        if idx == active_idx {
+            changes.push(AttributeChange::Reverse(true).into());
+        }
+        changes.push(Change::Text(" ".to_string()));
+        let mut width = 0;
+        for (g_idx, g) in entry.brief.graphemes(true).enumerate() {
+            let g_width = grapheme_column_width(g, None);
+            if width + g_width > label_width {
+                break;
+            }
+            width += g_width;
+            let matched = m.positions.contains(&g_idx);
+            if matched {
+                changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+                changes.push(AttributeChange::Underline(Underline::Single).into());
+            }
+            changes.push(Change::Text(g.to_string()));
+            if matched {
+                changes.push(AttributeChange::Intensity(Intensity::Normal).into());
+                changes.push(AttributeChange::Underline(Underline::None).into());
+            }
+        }
+        let padding = size.cols.saturating_sub(width + keys.len() + 2);
+        changes.push(Change::Text(format!("{}{} ", " ".repeat(padding), keys)));
+        if idx == active_idx {
+            changes.push(AttributeChange::Reverse(false).into());
+        }
+        changes.push(Change::Text("\r\n".to_string()));
+    }
+
+    term.render(&changes)?;
+    term.flush()?;
+    Ok(())
+}
+
+/// Runs the palette, returning the action that was chosen, or None if
+/// the palette was cancelled.
+pub fn command_palette(
+    mut term: TermWizTerminal,
+    entries: Vec<PaletteEntry>,
+) -> anyhow::Result<Option<KeyAssignment>> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title("Command Palette".to_string())])?;
+
+    let mut recent = RecentCommands::load();
+    let mut filter = String::new();
+    let mut matches = filter_entries(&entries, &filter, &recent);
+    let mut active_idx = 0;
+    let mut top_row = 0;
+    let mut selected = None;
+
+    render(&entries, &matches, &filter, active_idx, top_row, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let mut filter_changed = false;
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('p'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                active_idx = (active_idx + 1).min(matches.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                filter.pop();
+                filter_changed = true;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(m) = matches.get(active_idx) {
+                    selected = Some(m.entry_idx);
+                }
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                filter.push(c);
+                filter_changed = true;
+            }
+            InputEvent::Mouse(MouseEvent {
+                y, mouse_buttons, ..
+            }) => {
+                let y = y as usize;
+                if y >= HEADER_ROWS && top_row + y - HEADER_ROWS < matches.len() {
+                    active_idx = top_row + y - HEADER_ROWS;
+                    if mouse_buttons == MouseButtons::LEFT {
+                        selected = Some(matches[active_idx].entry_idx);
+                        break;
+                    }
+                }
+                if mouse_buttons != MouseButtons::NONE && mouse_buttons != MouseButtons::LEFT {
+                    // Treat any other mouse button as cancel
+                    break;
+                }
+            }
+            _ => {}
+        }
+
+        if filter_changed {
+            matches = filter_entries(&entries, &filter, &recent);
+            active_idx = 0;
+            top_row = 0;
+        }
+
+        // Keep the active entry visible
+        let visible_rows = term
+            .get_screen_size()?
+            .rows
+            .saturating_sub(HEADER_ROWS)
+            .max(1);
+        if active_idx < top_row {
+            top_row = active_idx;
+        } else if active_idx >= top_row + visible_rows {
+            top_row = active_idx + 1 - visible_rows;
+        }
+
+        render(&entries, &matches, &filter, active_idx, top_row, &mut term)?;
+    }
+
+    match selected {
+        Some(entry_idx) => {
+            let entry = &entries[entry_idx];
+            recent.add(&entry.brief);
+            if let Err(err) = recent.save() {
+                log::warn!("failed to save command palette history: {:#}", err);
+            }
+            Ok(Some(entry.action.clone()))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_match("", "New tab"), Some((0, vec![])));
+        assert_eq!(fuzzy_match("xyz", "New tab"), None);
+        // Case insensitive, and spaces in the pattern are ignored
+        assert_eq!(fuzzy_match("n T", "New tab").unwrap().1, vec![0, 4]);
+
+        // Matches at the start of words and runs of consecutive
+        // matches are preferred over scattered matches
+        let (words, _) = fuzzy_match("nt", "New tab").unwrap();
+        let (scattered, _) = fuzzy_match("nt", "Send the string").unwrap();
+        assert!(words > scattered, "{} > {}", words, scattered);
+        let (run, _) = fuzzy_match("tab", "New tab").unwrap();
+        let (gaps, _) = fuzzy_match("tab", "Toggle full screen and bar").unwrap();
+        assert!(run > gaps, "{} > {}", run, gaps);
+    }
+
+    #[test]
+    fn keys() {
+        use ::window::{KeyCode as WK, Modifiers as WM};
+        assert_eq!(describe_keys(&WK::Char('P'), WM::CTRL), "CTRL+SHIFT+P");
+        assert_eq!(
+            describe_keys(&WK::Char(' '), WM::CTRL | WM::SHIFT),
+            "CTRL+SHIFT+Space"
+        );
+        assert_eq!(describe_keys(&WK::Char('t'), WM::SUPER), "SUPER+t");
+    }
+}
@@ -1,14 +1,16 @@
+use crate::scripting::guiwin::GuiWin;
+use crate::scripting::pane::PaneObject;
 use crate::selection::{SelectionCoordinate, SelectionRange};
 use crate::termwindow::{TermWindow, TermWindowNotif};
 use config::keyassignment::{ClipboardCopyDestination, ScrollbackEraseMode};
-use config::ConfigHandle;
+use config::{ConfigHandle, QuickSelectAction, QuickSelectPattern};
 use mux::domain::DomainId;
 use mux::pane::{Pane, PaneId, Pattern, SearchResult};
 use mux::renderable::*;
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use std::cell::{RefCell, RefMut};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -100,6 +102,96 @@ fn compute_labels_for_alphabet(alphabet: &str, num_matches: usize) -> Vec<String
         .collect()
 }
 
+/// Computes labels in the same way as compute_labels_for_alphabet,
+/// but skips any that would be ambiguous with the labels in `taken`,
+/// which are those already assigned to the matches of other patterns.
+/// Fewer than `num_matches` labels are returned if the alphabet
+/// cannot produce enough unambiguous labels.
+fn compute_labels_avoiding(alphabet: &str, num_matches: usize, taken: &[String]) -> Vec<String> {
+    // A label is ambiguous if it is a prefix of a taken label,
+    // or if a taken label is a prefix of it
+    let taken_labels: HashSet<&str> = taken.iter().map(String::as_str).collect();
+    let taken_prefixes: HashSet<&str> = taken
+        .iter()
+        .flat_map(|t| t.char_indices().map(move |(i, c)| &t[..i + c.len_utf8()]))
+        .collect();
+    let ambiguous = |label: &str| {
+        taken_prefixes.contains(label)
+            || label
+                .char_indices()
+                .any(|(i, c)| taken_labels.contains(&label[..i + c.len_utf8()]))
+    };
+
+    let alphabet = alphabet
+        .chars()
+        .map(|c| c.to_lowercase().to_string())
+        .collect::<Vec<String>>();
+    let mut primary: Vec<String> = alphabet
+        .iter()
+        .filter(|label| !ambiguous(label))
+        .cloned()
+        .collect();
+    let mut secondary = vec![];
+    let mut prefixes = alphabet.clone();
+
+    while primary.len() + secondary.len() < num_matches {
+        // As in compute_labels_for_alphabet, take a prefix from the
+        // end of the alphabet; it can no longer be used as a label
+        // in its own right
+        let prefix = match prefixes.pop() {
+            Some(p) => p,
+            None => break,
+        };
+        primary.retain(|label| *label != prefix);
+
+        let needed = num_matches - primary.len() - secondary.len();
+        let prefixed: Vec<String> = alphabet
+            .iter()
+            .map(|s| format!("{}{}", prefix, s))
+            .filter(|label| !ambiguous(label))
+            .take(needed)
+            .collect();
+        secondary.splice(0..0, prefixed);
+    }
+
+    let len = secondary.len();
+    primary
+        .into_iter()
+        .take(num_matches.saturating_sub(len))
+        .chain(secondary)
+        .collect()
+}
+
+/// Removes matches that overlap a match from a higher priority
+/// group; groups with a lower index have a higher priority.
+/// The matches from a single search never overlap each other,
+/// so the matches that are kept are always disjoint.
+fn remove_overlapping_matches(mut matches: Vec<QuickSelectMatch>) -> Vec<QuickSelectMatch> {
+    // Stable, so that the order within a group is preserved
+    matches.sort_by_key(|m| m.group);
+
+    let mut kept: Vec<QuickSelectMatch> = vec![];
+    // start -> end of each kept match
+    let mut claimed: BTreeMap<(StableRowIndex, usize), (StableRowIndex, usize)> = BTreeMap::new();
+    for m in matches {
+        let start = (m.result.start_y, m.result.start_x);
+        let end = (m.result.end_y, m.result.end_x);
+        // Since the claimed ranges are disjoint, only the one that
+        // starts closest before our end can overlap us
+        let overlaps = match claimed.range(..end).next_back() {
+            Some((_, prior_end)) => *prior_end > start,
+            None => false,
+        };
+        if !overlaps {
+            claimed.insert(start, end);
+            kept.push(m);
+        }
+    }
+
+    kept.sort_by(|a, b| a.result.cmp(&b.result));
+    kept
+}
+
 #[cfg(test)]
 mod alphabet_test {
     use super::*;
@@ -134,6 +226,57 @@ mod alphabet_test {
             vec!["aa", "ab", "ba", "bb"]
         );
     }
+
+    #[test]
+    fn avoiding_taken_labels() {
+        let taken = vec!["a".to_string(), "db".to_string()];
+        // "a" is taken, and "d" would be a prefix of "db"
+        assert_eq!(
+            compute_labels_avoiding("abcd", 3, &taken),
+            vec!["b", "c", "da"]
+        );
+        assert_eq!(compute_labels_avoiding("xy", 2, &taken), vec!["x", "y"]);
+    }
+
+    #[test]
+    fn avoiding_nothing_matches_alphabet() {
+        for n in 0..20 {
+            assert_eq!(
+                compute_labels_avoiding("abcd", n, &[]),
+                compute_labels_for_alphabet("abcd", n),
+                "num_matches={}",
+                n
+            );
+        }
+    }
+
+    fn result(start_x: usize, end_x: usize, group: usize) -> QuickSelectMatch {
+        QuickSelectMatch {
+            result: SearchResult {
+                start_y: 0,
+                start_x,
+                end_y: 0,
+                end_x,
+                match_id: 0,
+            },
+            group,
+        }
+    }
+
+    #[test]
+    fn overlapping_matches() {
+        let kept = remove_overlapping_matches(vec![
+            result(0, 10, 1),
+            result(12, 20, 1),
+            result(4, 8, 0),
+            result(20, 24, 0),
+        ]);
+        let ranges: Vec<(usize, usize, usize)> = kept
+            .iter()
+            .map(|m| (m.result.start_x, m.result.end_x, m.group))
+            .collect();
+        assert_eq!(ranges, vec![(4, 8, 0), (12, 20, 1), (20, 24, 0)]);
+    }
 }
 
 pub struct QuickSelectOverlay {
@@ -147,12 +290,27 @@ struct MatchResult {
     label: String,
 }
 
+/// A set of patterns that are searched for together, and whose
+/// matches share an alphabet and an action
+struct SearchGroup {
+    pattern: String,
+    alphabet: String,
+    action: QuickSelectAction,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct QuickSelectMatch {
+    result: SearchResult,
+    /// Index into the search groups
+    group: usize,
+}
+
 struct QuickSelectRenderable {
     delegate: Rc<dyn Pane>,
-    /// The text that the user entered
-    pattern: Pattern,
+    /// The patterns to search for, in priority order
+    groups: Vec<SearchGroup>,
     /// The most recently queried set of matches
-    results: Vec<SearchResult>,
+    results: Vec<QuickSelectMatch>,
     by_line: HashMap<StableRowIndex, Vec<MatchResult>>,
     by_label: HashMap<String, usize>,
     selection: String,
@@ -178,6 +336,9 @@ impl QuickSelectOverlay {
 
         let config = term_window.config.clone();
 
+        // The default patterns and the plain patterns from the config
+        // are combined into a single regex, while each rule is searched
+        // for separately so that we can tell which rule a match is for.
         let mut pattern = "(".to_string();
         if !config.disable_default_quick_select_patterns {
             for p in &PATTERNS {
@@ -187,20 +348,54 @@ impl QuickSelectOverlay {
                 pattern.push_str(p);
             }
         }
+        let mut rules = vec![];
         for p in &config.quick_select_patterns {
-            if pattern.len() > 1 {
-                pattern.push('|');
+            match p {
+                QuickSelectPattern::Regex(p) => {
+                    if pattern.len() > 1 {
+                        pattern.push('|');
+                    }
+                    pattern.push_str(p);
+                }
+                QuickSelectPattern::Rule(rule) => rules.push(rule),
             }
-            pattern.push_str(p);
         }
         pattern.push(')');
 
-        let pattern = Pattern::Regex(pattern);
+        // A stable sort, so that rules of equal priority retain their
+        // order and are preferred over the plain patterns
+        rules.sort_by(|a, b| b.priority.cmp(&a.priority));
+        let mut groups: Vec<SearchGroup> = rules
+            .iter()
+            .map(|rule| SearchGroup {
+                pattern: rule.regex.clone(),
+                alphabet: rule
+                    .alphabet
+                    .clone()
+                    .unwrap_or_else(|| config.quick_select_alphabet.clone()),
+                action: rule.action.clone(),
+            })
+            .collect();
+        if pattern.len() > 2 {
+            // Plain patterns have the default priority of 0
+            let pos = rules
+                .iter()
+                .position(|rule| rule.priority < 0)
+                .unwrap_or(rules.len());
+            groups.insert(
+                pos,
+                SearchGroup {
+                    pattern,
+                    alphabet: config.quick_select_alphabet.clone(),
+                    action: QuickSelectAction::Copy,
+                },
+            );
+        }
 
         let window = term_window.window.clone().unwrap();
         let mut renderer = QuickSelectRenderable {
             delegate: Rc::clone(pane),
-            pattern,
+            groups,
             selection: "".to_string(),
             results: vec![],
             by_line: HashMap::new(),
@@ -293,7 +488,7 @@ impl Pane for QuickSelectOverlay {
                     if let Some(pos) = r
                         .results
                         .iter()
-                        .position(|res| res.start_y > prior && res.start_y < top)
+                        .position(|m| m.result.start_y > prior && m.result.start_y < top)
                     {
                         r.activate_match_number(pos);
                     } else {
@@ -309,7 +504,7 @@ impl Pane for QuickSelectOverlay {
                 if let Some(cur) = r.result_pos {
                     let top = r.viewport.unwrap_or(dims.physical_top);
                     let bottom = top + dims.viewport_rows as isize;
-                    if let Some(pos) = r.results.iter().position(|res| res.start_y >= bottom) {
+                    if let Some(pos) = r.results.iter().position(|m| m.result.start_y >= bottom) {
                         r.activate_match_number(pos);
                     } else {
                         let len = r.results.len().saturating_sub(1);
@@ -334,9 +529,9 @@ impl Pane for QuickSelectOverlay {
                 let mut r = self.renderer.borrow_mut();
                 r.selection.push(c);
                 let lowered = r.selection.to_lowercase();
-                let paste = lowered != r.selection;
+                let uppercase = lowered != r.selection;
                 if let Some(result_index) = r.by_label.get(&lowered).cloned() {
-                    r.select_and_copy_match_number(result_index, paste);
+                    r.perform_match_action(result_index, uppercase);
                     r.close();
                 }
             }
@@ -519,37 +714,49 @@ impl QuickSelectRenderable {
     }
 
     fn recompute_results(&mut self) {
-        /// Produce the sorted seq of unique match_ids from the results
-        fn compute_uniq_results(results: &[SearchResult]) -> Vec<usize> {
-            let mut ids: Vec<usize> = results.iter().map(|sr| sr.match_id).collect();
+        // Count the unique match_ids of each group; the match_ids
+        // are only unique within the search for a group
+        let mut uniq_results: Vec<Vec<usize>> = vec![vec![]; self.groups.len()];
+        for m in &self.results {
+            uniq_results[m.group].push(m.result.match_id);
+        }
+
+        // Label each unique result, giving the higher priority groups
+        // the first choice of labels
+        let mut taken: Vec<String> = vec![];
+        let mut labels_by_group = vec![];
+        for (group, ids) in self.groups.iter().zip(uniq_results.iter_mut()) {
             ids.sort();
             ids.dedup();
-            ids
+            let labels = compute_labels_avoiding(&group.alphabet, ids.len(), &taken);
+            taken.extend(labels.iter().cloned());
+            labels_by_group.push(labels);
         }
-
-        let uniq_results = compute_uniq_results(&self.results);
-
-        // Label each unique result
-        let labels =
-            compute_labels_for_alphabet(&self.config.quick_select_alphabet, uniq_results.len());
         self.by_label.clear();
 
-        // Keep track of match_id -> label
-        let mut assigned_labels: HashMap<usize, usize> = HashMap::new();
+        // Keep track of (group, match_id) -> label
+        let mut assigned_labels: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut num_assigned = vec![0; self.groups.len()];
 
         // Work through the results in reverse order, so that we assign eg: `a` to the
         // bottom-right-most result first and so on
-        for (result_index, res) in self.results.iter().enumerate().rev() {
+        for (result_index, m) in self.results.iter().enumerate().rev() {
+            let res = &m.result;
             // Figure out which label to use based on the match_id
-            let label_index = match assigned_labels.get(&res.match_id).copied() {
+            let label_index = match assigned_labels.get(&(m.group, res.match_id)).copied() {
                 Some(idx) => idx,
                 None => {
-                    let idx = assigned_labels.len();
-                    assigned_labels.insert(res.match_id, idx);
+                    let idx = num_assigned[m.group];
+                    num_assigned[m.group] += 1;
+                    assigned_labels.insert((m.group, res.match_id), idx);
                     idx
                 }
             };
-            let label = &labels[label_index];
+            let label = match labels_by_group[m.group].get(label_index) {
+                Some(label) => label,
+                // The alphabet cannot label any more matches
+                None => continue,
+            };
 
             self.by_label.insert(label.clone(), result_index);
             for idx in res.start_y..=res.end_y {
@@ -595,13 +802,18 @@ impl QuickSelectRenderable {
         let bar_pos = self.compute_search_row();
         self.dirty_results.add(bar_pos);
 
-        if !self.pattern.is_empty() {
+        if !self.groups.is_empty() {
             let pane: Rc<dyn Pane> = self.delegate.clone();
             let window = self.window.clone();
-            let pattern = self.pattern.clone();
+            let patterns: Vec<String> = self.groups.iter().map(|g| g.pattern.clone()).collect();
             promise::spawn::spawn(async move {
-                let mut results = pane.search(pattern).await?;
-                results.sort();
+                let mut matches = vec![];
+                for (group, pattern) in patterns.into_iter().enumerate() {
+                    for result in pane.search(Pattern::Regex(pattern)).await? {
+                        matches.push(QuickSelectMatch { result, group });
+                    }
+                }
+                let results = remove_overlapping_matches(matches);
 
                 let pane_id = pane.pane_id();
                 let mut results = Some(results);
@@ -642,8 +854,11 @@ impl QuickSelectRenderable {
             })));
     }
 
-    fn select_and_copy_match_number(&mut self, n: usize, paste: bool) {
-        let result = self.results[n].clone();
+    /// Selects the match and performs the action of its pattern.
+    /// `uppercase` is true if the label was typed in uppercase.
+    fn perform_match_action(&mut self, n: usize, uppercase: bool) {
+        let QuickSelectMatch { result, group } = self.results[n];
+        let action = self.groups[group].action.clone();
 
         let pane_id = self.delegate.pane_id();
         self.window
@@ -669,14 +884,26 @@ impl QuickSelectRenderable {
                 let mux = mux::Mux::get().unwrap();
                 if let Some(pane) = mux.get_pane(pane_id) {
                     let text = term_window.selection_text(&pane);
-                    if !text.is_empty() {
-                        if paste {
+                    if text.is_empty() {
+                        return;
+                    }
+                    match action {
+                        QuickSelectAction::Copy => {
+                            if uppercase {
+                                let _ = pane.send_paste(&text);
+                            }
+                            term_window.copy_to_clipboard(
+                                ClipboardCopyDestination::ClipboardAndPrimarySelection,
+                                text,
+                            );
+                        }
+                        QuickSelectAction::Paste => {
                             let _ = pane.send_paste(&text);
                         }
-                        term_window.copy_to_clipboard(
-                            ClipboardCopyDestination::ClipboardAndPrimarySelection,
-                            text,
-                        );
+                        QuickSelectAction::Open => term_window.open_uri(&pane, text),
+                        QuickSelectAction::EmitEvent(name) => {
+                            emit_select_event(term_window, name, &pane, text)
+                        }
                     }
                 }
             })));
@@ -684,7 +911,35 @@ impl QuickSelectRenderable {
 
     fn activate_match_number(&mut self, n: usize) {
         self.result_pos.replace(n);
-        let result = self.results[n].clone();
+        let result = self.results[n].result;
         self.set_viewport(Some(result.start_y));
     }
 }
+
+/// Emits the event produced by `wezterm.action_callback`, passing
+/// the selected text as an additional parameter
+fn emit_select_event(term_window: &TermWindow, name: String, pane: &Rc<dyn Pane>, text: String) {
+    let window = GuiWin::new(term_window);
+    let pane = PaneObject::new(pane);
+
+    async fn emit(
+        lua: Option<Rc<mlua::Lua>>,
+        name: String,
+        window: GuiWin,
+        pane: PaneObject,
+        text: String,
+    ) -> anyhow::Result<()> {
+        if let Some(lua) = lua {
+            let args = lua.pack_multi((window, pane, text))?;
+            if let Err(err) = config::lua::emit_event(&lua, (name.clone(), args)).await {
+                log::error!("while processing {} event: {:#}", name, err);
+            }
+        }
+        Ok(())
+    }
+
+    promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+        emit(lua, name, window, pane, text)
+    }))
+    .detach();
+}
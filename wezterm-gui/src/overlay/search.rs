@@ -492,6 +492,27 @@ impl SearchRenderable {
     }
 
     fn activate_match_number(&mut self, n: usize) {
+        // The lines that hold the oldest matches may have been evicted
+        // from the scrollback since the search was performed; the results
+        // are sorted, so move on to the oldest match that remains
+        let scrollback_top = self.delegate.get_dimensions().scrollback_top;
+        let n = if self.results[n].start_y < scrollback_top {
+            match self
+                .results
+                .iter()
+                .position(|res| res.start_y >= scrollback_top)
+            {
+                Some(n) => n,
+                None => {
+                    self.result_pos.take();
+                    self.set_viewport(None);
+                    self.clear_selection();
+                    return;
+                }
+            }
+        } else {
+            n
+        };
         self.result_pos.replace(n);
         let result = self.results[n].clone();
 
@@ -0,0 +1,94 @@
+//! DomainObject represents a Mux Domain instance in lua code
+use super::luaerr;
+use anyhow::anyhow;
+use config::lua::get_or_create_module;
+use mlua::{Lua, UserData, UserDataMethods};
+use mux::domain::{Domain, DomainId, DomainState};
+use mux::Mux;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct DomainObject {
+    pub domain: DomainId,
+}
+
+impl DomainObject {
+    pub fn new(domain: &Arc<dyn Domain>) -> Self {
+        Self {
+            domain: domain.domain_id(),
+        }
+    }
+
+    pub fn domain(&self) -> mlua::Result<Arc<dyn Domain>> {
+        let mux = get_mux()?;
+        mux.get_domain(self.domain)
+            .ok_or_else(|| anyhow!("domain id {} is not valid", self.domain))
+            .map_err(luaerr)
+    }
+}
+
+fn get_mux() -> mlua::Result<Rc<Mux>> {
+    Mux::get()
+        .ok_or_else(|| anyhow!("must be called on main thread"))
+        .map_err(luaerr)
+}
+
+fn state_name(state: DomainState) -> &'static str {
+    match state {
+        DomainState::Attached => "Attached",
+        DomainState::Detached => "Detached",
+    }
+}
+
+impl UserData for DomainObject {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("domain_id", |_, this, _: ()| Ok(this.domain()?.domain_id()));
+        methods.add_method("name", |_, this, _: ()| {
+            Ok(this.domain()?.domain_name().to_string())
+        });
+        methods.add_method("label", |_, this, _: ()| {
+            Ok(this.domain()?.domain_label().to_string())
+        });
+        methods.add_method("state", |_, this, _: ()| {
+            Ok(state_name(this.domain()?.state()))
+        });
+        methods.add_method("is_spawnable", |_, this, _: ()| {
+            Ok(this.domain()?.spawnable())
+        });
+        methods.add_method("has_any_panes", |_, this, _: ()| {
+            let domain_id = this.domain()?.domain_id();
+            let mux = get_mux()?;
+            Ok(mux
+                .iter_panes()
+                .iter()
+                .any(|pane| pane.domain_id() == domain_id))
+        });
+    }
+}
+
+/// Sets up `wezterm.mux`, which provides access to the mux from
+/// the event handlers in the config
+pub fn register(lua: &Lua) -> anyhow::Result<()> {
+    let wezterm_mod = get_or_create_module(lua, "wezterm")?;
+    let mux_mod = lua.create_table()?;
+    mux_mod.set(
+        "all_domains",
+        lua.create_function(|_, _: ()| {
+            let mux = get_mux()?;
+            let mut domains: Vec<DomainObject> =
+                mux.iter_domains().iter().map(DomainObject::new).collect();
+            domains.sort_by_key(|domain| domain.domain);
+            Ok(domains)
+        })?,
+    )?;
+    mux_mod.set(
+        "get_domain",
+        lua.create_function(|_, name: String| {
+            let mux = get_mux()?;
+            Ok(mux.get_domain_by_name(&name).map(|d| DomainObject::new(&d)))
+        })?,
+    )?;
+    wezterm_mod.set("mux", mux_mod)?;
+    Ok(())
+}
@@ -6,11 +6,58 @@ use crate::TermWindow;
 use config::keyassignment::KeyAssignment;
 use luahelper::*;
 use mlua::{UserData, UserDataMethods};
+use mux::tab::TabId;
 use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
 use serde::*;
 use wezterm_toast_notification::ToastNotification;
 use window::{Connection, ConnectionOps, WindowOps, WindowState};
 
+/// Describes the OpenGL implementation used to render a window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuInfo {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+    /// Whether this appears to be a software rasterizer
+    /// such as llvmpipe rather than a hardware accelerated driver
+    pub is_software: bool,
+}
+impl_lua_conversion!(GpuInfo);
+
+/// Counts the quads that were generated to render the most
+/// recent frame of a window
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RenderStats {
+    /// The number of quads that were sent to the GPU
+    pub quads: usize,
+    /// The number of quads that would have been needed if adjacent
+    /// background cells were not merged together
+    pub unmerged_quads: usize,
+}
+impl_lua_conversion!(RenderStats);
+
+impl GpuInfo {
+    pub fn new(vendor: &str, renderer: &str, version: &str) -> Self {
+        const SOFTWARE_RENDERERS: &[&str] = &[
+            "llvmpipe",
+            "softpipe",
+            "swrast",
+            "software rasterizer",
+            "swiftshader",
+            "gdi generic",
+            "microsoft basic render driver",
+        ];
+        let lower = renderer.to_lowercase();
+        Self {
+            vendor: vendor.to_string(),
+            renderer: renderer.to_string(),
+            version: version.to_string(),
+            is_software: SOFTWARE_RENDERERS.iter().any(|name| lower.contains(name)),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct GuiWin {
     mux_window_id: MuxWindowId,
@@ -38,7 +85,8 @@ impl UserData for GuiWin {
                     title,
                     message,
                     url,
-                    timeout: timeout.map(std::time::Duration::from_millis)
+                    timeout: timeout.map(std::time::Duration::from_millis),
+                    on_click: None,
                 });
                 Ok(())
             },
@@ -64,6 +112,7 @@ impl UserData for GuiWin {
                 pixel_width: usize,
                 pixel_height: usize,
                 dpi: usize,
+                scale_factor: f64,
                 is_full_screen: bool,
             }
             impl_lua_conversion!(Dims);
@@ -72,11 +121,79 @@ impl UserData for GuiWin {
                 pixel_width: dims.pixel_width,
                 pixel_height: dims.pixel_height,
                 dpi: dims.dpi,
+                scale_factor: dims.dpi as f64 / ::window::default_dpi(),
                 is_full_screen: window_state.contains(WindowState::FULL_SCREEN),
                 // FIXME: expose other states here
             };
             Ok(dims)
         });
+        methods.add_async_method("get_gpu_info", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window.notify(TermWindowNotif::GetGpuInfo(tx));
+            let info = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+            Ok(info)
+        });
+        methods.add_async_method("get_screen_name", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window.notify(TermWindowNotif::GetScreenName(tx));
+            let name = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+            Ok(name)
+        });
+        methods.add_async_method("leader_is_active", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window.notify(TermWindowNotif::GetLeaderIsActive(tx));
+            let active = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+            Ok(active)
+        });
+        methods.add_async_method("active_key_recording", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window
+                .notify(TermWindowNotif::GetActiveKeyRecording(tx));
+            let register = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+            Ok(register)
+        });
+        methods.add_async_method(
+            "action_history",
+            |_, this, count: Option<usize>| async move {
+                let (tx, rx) = smol::channel::bounded(1);
+                this.window.notify(TermWindowNotif::GetActionHistory {
+                    count: count.unwrap_or(usize::MAX),
+                    tx,
+                });
+                let history = rx
+                    .recv()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{:#}", e))
+                    .map_err(luaerr)?;
+                Ok(history)
+            },
+        );
+        methods.add_async_method("get_render_stats", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window.notify(TermWindowNotif::GetRenderStats(tx));
+            let stats = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+            Ok(stats)
+        });
         methods.add_async_method(
             "get_selection_text_for_pane",
             |_, this, pane: PaneObject| async move {
@@ -104,6 +221,27 @@ impl UserData for GuiWin {
                 Ok(())
             },
         );
+        methods.add_method(
+            "move_tab_to_window",
+            |_, this, (tab_id, window, tab_idx): (Option<TabId>, Option<GuiWin>, Option<usize>)| {
+                let window_id = window.map(|w| w.mux_window_id);
+                this.window
+                    .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                        let tab_id = match tab_id {
+                            Some(tab_id) => tab_id,
+                            None => {
+                                let mux = Mux::get().unwrap();
+                                match mux.get_active_tab_for_window(term_window.mux_window_id) {
+                                    Some(tab) => tab.tab_id(),
+                                    None => return,
+                                }
+                            }
+                        };
+                        term_window.move_tab_to_window(tab_id, window_id, tab_idx);
+                    })));
+                Ok(())
+            },
+        );
         methods.add_async_method("effective_config", |_, this, _: ()| async move {
             let (tx, rx) = smol::channel::bounded(1);
             this.window.notify(TermWindowNotif::GetEffectiveConfig(tx));
@@ -132,5 +270,19 @@ impl UserData for GuiWin {
                 .notify(TermWindowNotif::SetConfigOverrides(value.0));
             Ok(())
         });
+        methods.add_method("set_color_scheme", |_, this, scheme: String| {
+            if config::configuration()
+                .color_scheme_by_name(&scheme)
+                .is_none()
+            {
+                log::error!(
+                    "window:set_color_scheme: color scheme \"{}\" was not found",
+                    scheme
+                );
+                return Ok(false);
+            }
+            this.window.notify(TermWindowNotif::SetColorScheme(scheme));
+            Ok(true)
+        });
     }
 }
@@ -1,5 +1,7 @@
+pub mod domain;
 pub mod guiwin;
 pub mod pane;
+pub mod tab;
 
 fn luaerr(err: anyhow::Error) -> mlua::Error {
     mlua::Error::external(err)
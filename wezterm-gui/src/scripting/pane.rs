@@ -1,10 +1,67 @@
 //! PaneObject represents a Mux Pane instance in lua code
 use super::luaerr;
+use super::tab::TabObject;
 use anyhow::anyhow;
+use config::ConfigHandle;
 use mlua::{UserData, UserDataMethods};
 use mux::pane::{Pane, PaneId};
-use mux::Mux;
+use mux::{Mux, MuxNotification};
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::Mutex;
+use wezterm_client::pane::ClientPane;
+
+/// Setting this user var (eg: via `OSC 1337 ; SetUserVar`) to a number
+/// between 0.0 and 1.0 overrides the background opacity of the pane
+const BACKGROUND_OPACITY_USER_VAR: &str = "WEZTERM_BACKGROUND_OPACITY";
+
+lazy_static::lazy_static! {
+    static ref BACKGROUND_OPACITY: Mutex<HashMap<PaneId, f32>> = Mutex::new(HashMap::new());
+    static ref HARFBUZZ_FEATURES: Mutex<HashMap<PaneId, Vec<String>>> = Mutex::new(HashMap::new());
+}
+
+/// Returns the background opacity that should be used for the pane
+/// in place of `window_background_opacity`, if any.
+/// An override set via `pane:set_background_opacity` takes precedence
+/// over one set via the user var.
+pub fn background_opacity_override(pane: &Rc<dyn Pane>) -> Option<f32> {
+    if let Some(opacity) = BACKGROUND_OPACITY.lock().unwrap().get(&pane.pane_id()) {
+        return Some(*opacity);
+    }
+    pane.copy_user_vars()
+        .get(BACKGROUND_OPACITY_USER_VAR)
+        .and_then(|value| value.trim().parse::<f32>().ok())
+        .filter(|opacity| (0.0..=1.0).contains(opacity))
+}
+
+/// Returns the harfbuzz features that should be used for the pane
+/// in place of `harfbuzz_features`, if any.
+/// An override set via `pane:set_harfbuzz_features` takes precedence
+/// over the first of the `harfbuzz_features_rules` that matches the
+/// foreground process.
+pub fn harfbuzz_features_override(
+    pane: &Rc<dyn Pane>,
+    config: &ConfigHandle,
+) -> Option<Vec<String>> {
+    if let Some(features) = HARFBUZZ_FEATURES.lock().unwrap().get(&pane.pane_id()) {
+        return Some(features.clone());
+    }
+    if config.harfbuzz_features_rules.is_empty() {
+        return None;
+    }
+    let process = pane.get_foreground_process_name()?;
+    config
+        .harfbuzz_features_rules
+        .iter()
+        .find(|rule| rule.matches(&process))
+        .map(|rule| rule.harfbuzz_features.clone())
+}
+
+/// Called when a pane is closed, to discard its overrides
+pub fn forget_pane(pane_id: PaneId) {
+    BACKGROUND_OPACITY.lock().unwrap().remove(&pane_id);
+    HARFBUZZ_FEATURES.lock().unwrap().remove(&pane_id);
+}
 
 #[derive(Clone)]
 pub struct PaneObject {
@@ -38,6 +95,31 @@ impl UserData for PaneObject {
                 .get_current_working_dir()
                 .map(|u| u.to_string()))
         });
+        methods.add_method("get_foreground_process_name", |_, this, _: ()| {
+            Ok(this.pane()?.get_foreground_process_name())
+        });
+        methods.add_method(
+            "set_harfbuzz_features",
+            |_, this, features: Option<Vec<String>>| {
+                let pane = this.pane()?;
+                match features {
+                    Some(features) => {
+                        HARFBUZZ_FEATURES
+                            .lock()
+                            .unwrap()
+                            .insert(this.pane, features);
+                    }
+                    None => {
+                        HARFBUZZ_FEATURES.lock().unwrap().remove(&this.pane);
+                    }
+                }
+                let mux = Mux::get().unwrap();
+                if let Some((_domain, window_id, _tab)) = mux.resolve_pane_id(pane.pane_id()) {
+                    mux.notify(MuxNotification::WindowInvalidated(window_id));
+                }
+                Ok(())
+            },
+        );
         methods.add_method("paste", |_, this, text: String| {
             this.pane()?.send_paste(&text).map_err(luaerr)?;
             Ok(())
@@ -51,6 +133,126 @@ impl UserData for PaneObject {
         methods.add_method("get_user_vars", |_, this, _: ()| {
             Ok(this.pane()?.copy_user_vars())
         });
+        methods.add_async_method(
+            "get_text",
+            |_, this, (start, end, escapes): (Option<isize>, Option<isize>, Option<bool>)| async move {
+                let pane = this.pane()?;
+                let escapes = escapes.unwrap_or(false);
+                // A client pane only has the lines that it has fetched
+                // so far, so ask the server for the text instead
+                match pane.downcast_ref::<ClientPane>() {
+                    Some(client_pane) => client_pane
+                        .get_remote_text(start, end, escapes)
+                        .await
+                        .map_err(luaerr),
+                    None => Ok(pane.get_text(start, end, escapes)),
+                }
+            },
+        );
+        methods.add_method("get_exit_status", |lua, this, _: ()| {
+            let status = match this.pane()?.get_exit_status() {
+                Some(status) => status,
+                None => return Ok(None),
+            };
+            let table = lua.create_table()?;
+            table.set("success", status.success())?;
+            table.set("code", status.exit_code())?;
+            table.set("signal", status.signal())?;
+            Ok(Some(table))
+        });
+        methods.add_method("get_scrollback_stats", |lua, this, _: ()| {
+            let stats = match this.pane()?.get_scrollback_stats() {
+                Some(stats) => stats,
+                None => return Ok(None),
+            };
+            let table = lua.create_table()?;
+            table.set("lines", stats.lines)?;
+            table.set("estimated_bytes", stats.estimated_bytes)?;
+            table.set("evicted_lines", stats.evicted_lines)?;
+            table.set("evicted_bytes", stats.evicted_bytes)?;
+            Ok(Some(table))
+        });
+        methods.add_method("has_unseen_output", |_, this, _: ()| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            Ok(mux.has_unseen_output(this.pane()?.pane_id()))
+        });
+        methods.add_method("is_broadcast_target", |_, this, _: ()| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            Ok(mux.is_input_broadcast_target(this.pane()?.pane_id()))
+        });
+        methods.add_method("tab", |_, this, _: ()| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            Ok(mux
+                .resolve_pane_id(this.pane()?.pane_id())
+                .and_then(|(_, _, tab_id)| mux.get_tab(tab_id))
+                .map(|tab| TabObject::new(&tab)))
+        });
+        methods.add_method("is_zoomed", |_, this, _: ()| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            let pane_id = this.pane()?.pane_id();
+            Ok(mux
+                .resolve_pane_id(pane_id)
+                .and_then(|(_, _, tab_id)| mux.get_tab(tab_id))
+                .and_then(|tab| tab.get_zoomed_pane())
+                .map(|pane| pane.pane_id() == pane_id)
+                .unwrap_or(false))
+        });
+        methods.add_method("watch_for_silence", |_, this, seconds: f64| {
+            if !seconds.is_finite() || seconds <= 0.0 {
+                return Err(luaerr(anyhow!(
+                    "watch_for_silence: {} must be a positive number of seconds",
+                    seconds
+                )));
+            }
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            mux.watch_for_silence(
+                this.pane()?.pane_id(),
+                std::time::Duration::from_secs_f64(seconds),
+            );
+            Ok(())
+        });
+        methods.add_method("watch_for_activity", |_, this, _: ()| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            mux.watch_for_activity(this.pane()?.pane_id());
+            Ok(())
+        });
+        methods.add_method("set_background_opacity", |_, this, opacity: Option<f32>| {
+            let pane = this.pane()?;
+            match opacity {
+                Some(opacity) if !(0.0..=1.0).contains(&opacity) => {
+                    return Err(luaerr(anyhow!(
+                        "background opacity {} must be between 0.0 and 1.0",
+                        opacity
+                    )));
+                }
+                Some(opacity) => {
+                    BACKGROUND_OPACITY
+                        .lock()
+                        .unwrap()
+                        .insert(this.pane, opacity);
+                }
+                None => {
+                    BACKGROUND_OPACITY.lock().unwrap().remove(&this.pane);
+                }
+            }
+            let mux = Mux::get().unwrap();
+            if let Some((_domain, window_id, _tab)) = mux.resolve_pane_id(pane.pane_id()) {
+                mux.notify(MuxNotification::WindowInvalidated(window_id));
+            }
+            Ok(())
+        });
 
         // When called with no arguments, returns the lines from the
         // viewport as plain text (no escape sequences).
@@ -0,0 +1,60 @@
+//! TabObject represents a Mux Tab instance in lua code
+use super::luaerr;
+use super::pane::PaneObject;
+use anyhow::anyhow;
+use mlua::{UserData, UserDataMethods};
+use mux::tab::{Tab, TabId};
+use mux::Mux;
+use std::rc::Rc;
+
+#[derive(Clone)]
+pub struct TabObject {
+    pub tab: TabId,
+}
+
+impl TabObject {
+    pub fn new(tab: &Rc<Tab>) -> Self {
+        Self { tab: tab.tab_id() }
+    }
+
+    pub fn tab(&self) -> mlua::Result<Rc<Tab>> {
+        let mux = Mux::get()
+            .ok_or_else(|| anyhow!("must be called on main thread"))
+            .map_err(luaerr)?;
+        mux.get_tab(self.tab)
+            .ok_or_else(|| anyhow!("tab id {} is not valid", self.tab))
+            .map_err(luaerr)
+    }
+}
+
+impl UserData for TabObject {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("tab_id", |_, this, _: ()| Ok(this.tab()?.tab_id()));
+        methods.add_method("focus_history", |_, this, _: ()| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow!("must be called on main thread"))
+                .map_err(luaerr)?;
+            Ok(this
+                .tab()?
+                .focus_history()
+                .into_iter()
+                .filter_map(|pane_id| mux.get_pane(pane_id))
+                .map(|pane| PaneObject::new(&pane))
+                .collect::<Vec<_>>())
+        });
+        methods.add_method("get_user_vars", |_, this, _: ()| {
+            Ok(this.tab()?.copy_user_vars())
+        });
+        methods.add_method(
+            "set_user_var",
+            |_, this, (name, value): (String, String)| {
+                let tab_id = this.tab()?.tab_id();
+                let mux = Mux::get()
+                    .ok_or_else(|| anyhow!("must be called on main thread"))
+                    .map_err(luaerr)?;
+                mux.set_tab_user_var(tab_id, name, value);
+                Ok(())
+            },
+        );
+    }
+}
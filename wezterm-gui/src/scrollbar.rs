@@ -7,12 +7,57 @@ pub struct ScrollHit {
     pub top: usize,
     /// Height of the thumb, in pixels.
     pub height: usize,
-    /// Number of rows that correspond to the thumb in rows.
-    /// This is normally == viewport height, but in the case
-    /// where there are a sufficient number of rows of scrollback
-    /// that the pixel height of the thumb would be too small,
-    /// we will scale things in order to remain useful.
-    pub rows: f32,
+}
+
+/// Describes the space that the thumb moves through
+struct Track {
+    /// Offset from the top of the window in pixels
+    top: f32,
+    /// Height of the track in pixels
+    height: f32,
+    /// Height of the thumb in pixels
+    thumb_size: f32,
+    /// The number of rows that the viewport can be scrolled
+    /// up from the bottom of the scrollback
+    scrollable_rows: f32,
+}
+
+impl Track {
+    fn compute(
+        pane: &dyn Pane,
+        dims: &Dimensions,
+        tab_bar_height: f32,
+        tab_bar_at_bottom: bool,
+        min_thumb_size: f32,
+    ) -> Self {
+        let height = dims.pixel_height as f32 - tab_bar_height;
+        let render_dims = pane.get_dimensions();
+
+        let viewport_rows = render_dims.viewport_rows as f32;
+        let scroll_size = (render_dims.scrollback_rows as f32).max(viewport_rows);
+
+        // The thumb represents the viewport, but we don't allow it
+        // to become so small that it is impossible to grab
+        let thumb_size = ((viewport_rows / scroll_size) * height)
+            .max(min_thumb_size)
+            .min(height);
+
+        Self {
+            top: if tab_bar_at_bottom {
+                0.
+            } else {
+                tab_bar_height
+            },
+            height,
+            thumb_size,
+            scrollable_rows: scroll_size - viewport_rows,
+        }
+    }
+
+    /// The distance, in pixels, that the thumb can move
+    fn travel(&self) -> f32 {
+        self.height - self.thumb_size
+    }
 }
 
 impl ScrollHit {
@@ -24,60 +69,63 @@ impl ScrollHit {
         dims: &Dimensions,
         tab_bar_height: f32,
         tab_bar_at_bottom: bool,
+        min_thumb_size: f32,
     ) -> Self {
-        let max_thumb_height = dims.pixel_height as f32 - tab_bar_height;
+        let track = Track::compute(
+            pane,
+            dims,
+            tab_bar_height,
+            tab_bar_at_bottom,
+            min_thumb_size,
+        );
         let render_dims = pane.get_dimensions();
 
-        let scroll_top = render_dims
-            .physical_top
-            .saturating_sub(viewport.unwrap_or(render_dims.physical_top));
-
-        let scroll_size = render_dims.scrollback_rows as f32;
-
-        let thumb_size = (render_dims.viewport_rows as f32 / scroll_size) * max_thumb_height;
-
-        const MIN_HEIGHT: f32 = 10.;
-        let (thumb_size, rows) = if thumb_size < MIN_HEIGHT {
-            (
-                MIN_HEIGHT,
-                render_dims.viewport_rows as f32 * MIN_HEIGHT / thumb_size,
-            )
+        let rows_from_top = viewport
+            .unwrap_or(render_dims.physical_top)
+            .saturating_sub(render_dims.scrollback_top) as f32;
+        let position = if track.scrollable_rows > 0. {
+            (rows_from_top / track.scrollable_rows).max(0.).min(1.)
         } else {
-            (thumb_size, render_dims.viewport_rows as f32)
+            1.
         };
 
-        let thumb_top = if tab_bar_at_bottom {
-            0.
-        } else {
-            tab_bar_height
-        } + (1.
-            - (scroll_top as f32 + render_dims.viewport_rows as f32) / scroll_size)
-            * max_thumb_height;
-
-        let thumb_size = thumb_size.ceil() as usize;
-        let thumb_top = thumb_top.ceil() as usize;
+        let thumb_top = track.top + position * track.travel();
 
         Self {
-            top: thumb_top,
-            height: thumb_size,
-            rows,
+            top: thumb_top.round() as usize,
+            height: track.thumb_size.ceil() as usize,
         }
     }
 
     /// Given a new thumb top coordinate (produced by dragging the thumb),
     /// compute the equivalent viewport offset.
+    /// The thumb top is fractional so that the position maps smoothly
+    /// onto the full scrollback range, even when a pixel of movement
+    /// is worth less than a row.
     pub fn thumb_top_to_scroll_top(
-        thumb_top: usize,
+        thumb_top: f32,
         pane: &dyn Pane,
-        viewport: Option<StableRowIndex>,
         dims: &Dimensions,
         tab_bar_height: f32,
         tab_bar_at_bottom: bool,
+        min_thumb_size: f32,
     ) -> StableRowIndex {
         let render_dims = pane.get_dimensions();
-        let thumb = Self::thumb(pane, viewport, dims, tab_bar_height, tab_bar_at_bottom);
+        let track = Track::compute(
+            pane,
+            dims,
+            tab_bar_height,
+            tab_bar_at_bottom,
+            min_thumb_size,
+        );
 
-        let rows_from_top = ((thumb_top as f32) / thumb.height as f32) * thumb.rows;
+        let travel = track.travel();
+        let position = if travel > 0. {
+            ((thumb_top - track.top) / travel).max(0.).min(1.)
+        } else {
+            1.
+        };
+        let rows_from_top = (position * track.scrollable_rows).round();
 
         render_dims
             .scrollback_top
@@ -327,6 +327,7 @@ mod test {
         config.font = TextStyle {
             font: vec![FontAttributes::new("Fira Code")],
             foreground: None,
+            harfbuzz_features: None,
         };
         config.font_rules.clear();
         config.compute_extra_defaults(None);
@@ -0,0 +1,306 @@
+//! Presents the host verification and authentication prompts of ssh
+//! sessions in a modal window of their own, rather than in the terminal
+//! of the pane that is connecting, so that they also work for connections
+//! that are made before any pane is visible.
+use anyhow::bail;
+use mux::sshprompt::{set_ssh_prompt_handler, SshPromptHandler};
+use mux::termwiztermtab::{self, TermWizTerminal};
+use portable_pty::PtySize;
+use promise::Promise;
+use std::sync::Arc;
+use termwiz::cell::{unicode_column_width, AttributeChange};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+use wezterm_ssh::{AuthenticationEvent, HostVerificationDecision, HostVerificationEvent};
+
+/// The column at which the text of the modal starts
+const MARGIN: usize = 2;
+
+struct ModalPrompts;
+
+impl SshPromptHandler for ModalPrompts {
+    fn verify_host(
+        &self,
+        verify: &HostVerificationEvent,
+    ) -> anyhow::Result<HostVerificationDecision> {
+        let message = verify.message.clone();
+        run_modal(format!("Verify SSH host {}", verify.host), move |term| {
+            host_verification_app(term, &message)
+        })
+    }
+
+    fn authenticate(&self, auth: &AuthenticationEvent) -> anyhow::Result<Vec<String>> {
+        if auth.prompts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut header = vec![];
+        if let Some(label) = &auth.label {
+            header.push(format!("SSH authentication for {}", label));
+        } else if auth.username.is_empty() {
+            header.push(format!("SSH authentication for {}", auth.host));
+        } else {
+            header.push(format!(
+                "SSH authentication for {}@{}",
+                auth.username, auth.host
+            ));
+        }
+        if let Some(fingerprint) = &auth.fingerprint {
+            header.push(format!("Host key fingerprint: {}", fingerprint));
+        }
+        if !auth.instructions.is_empty() {
+            header.push(String::new());
+            header.extend(auth.instructions.lines().map(|line| line.to_string()));
+        }
+        let prompts: Vec<(String, bool)> = auth
+            .prompts
+            .iter()
+            .map(|prompt| (prompt.prompt.clone(), prompt.echo))
+            .collect();
+
+        let title = format!("SSH authentication for {}", auth.host);
+        run_modal(title, move |term| {
+            let mut answers = vec![];
+            for (idx, (prompt, echo)) in prompts.iter().enumerate() {
+                let mut lines = header.clone();
+                if prompts.len() > 1 {
+                    lines.push(String::new());
+                    lines.push(format!("Prompt {} of {}", idx + 1, prompts.len()));
+                }
+                answers.push(read_answer(term, &lines, prompt, *echo)?);
+            }
+            Ok(answers)
+        })
+    }
+}
+
+pub fn register() {
+    set_ssh_prompt_handler(Arc::new(ModalPrompts));
+}
+
+/// Runs `func` in a new window, blocking the calling thread, which
+/// must not be the main thread, until it completes
+fn run_modal<T, F>(title: String, func: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: Send + 'static + FnOnce(&mut TermWizTerminal) -> anyhow::Result<T>,
+{
+    let mut promise = Promise::new();
+    let future = promise.get_future().unwrap();
+
+    promise::spawn::spawn_into_main_thread(async move {
+        let result = termwiztermtab::run(PtySize::default(), move |mut term| {
+            term.set_raw_mode()?;
+            term.render(&[Change::Title(title)])?;
+            func(&mut term)
+        })
+        .await;
+        promise.result(result);
+    })
+    .detach();
+
+    promise::spawn::block_on(future)
+}
+
+/// Renders `lines` verbatim, starting at the top of the window
+fn render_lines(changes: &mut Vec<Change>, lines: &[String]) {
+    for (y, line) in lines.iter().enumerate() {
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(MARGIN),
+            y: Position::Absolute(1 + y),
+        });
+        changes.push(Change::Text(line.to_string()));
+    }
+}
+
+fn host_verification_app(
+    term: &mut TermWizTerminal,
+    message: &str,
+) -> anyhow::Result<HostVerificationDecision> {
+    let lines: Vec<String> = message.lines().map(|line| line.to_string()).collect();
+    let button_row = 1 + lines.len() + 1;
+
+    let buttons = [
+        (" [A]ccept once ", HostVerificationDecision::Accept),
+        (" [R]emember ", HostVerificationDecision::Remember),
+        (" Re[j]ect ", HostVerificationDecision::Reject),
+    ];
+    let mut button_x = vec![];
+    let mut x = MARGIN;
+    for (label, _) in &buttons {
+        button_x.push(x);
+        x += unicode_column_width(label, None) + 4;
+    }
+    let button_at = |x: usize, y: usize| {
+        if y != button_row {
+            return None;
+        }
+        buttons
+            .iter()
+            .zip(button_x.iter())
+            .position(|((label, _), &start)| {
+                x >= start && x < start + unicode_column_width(label, None)
+            })
+    };
+
+    // Rejecting is the default choice, so that an errant Enter
+    // doesn't trust the host
+    let mut active = buttons.len() - 1;
+
+    loop {
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+        ];
+        render_lines(&mut changes, &lines);
+        for (idx, ((label, _), &x)) in buttons.iter().zip(button_x.iter()).enumerate() {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(x),
+                y: Position::Absolute(button_row),
+            });
+            if idx == active {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(Change::Text(label.to_string()));
+            if idx == active {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(MARGIN),
+            y: Position::Absolute(button_row + 2),
+        });
+        changes.push(Change::Text(
+            "Tab and the arrow keys move between the choices; Enter confirms".to_string(),
+        ));
+        term.render(&changes)?;
+
+        match term.poll_input(None)? {
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('a' | 'A'),
+                ..
+            })) => return Ok(HostVerificationDecision::Accept),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('r' | 'R'),
+                ..
+            })) => return Ok(HostVerificationDecision::Remember),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('j' | 'J' | 'n' | 'N'),
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            })) => return Ok(HostVerificationDecision::Reject),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            })) => return Ok(buttons[active].1),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Tab | KeyCode::RightArrow,
+                ..
+            })) => active = (active + 1) % buttons.len(),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::LeftArrow,
+                ..
+            })) => active = (active + buttons.len() - 1) % buttons.len(),
+            Some(InputEvent::Mouse(MouseEvent {
+                x,
+                y,
+                mouse_buttons,
+                ..
+            })) => {
+                if let Some(idx) = button_at(x as usize, y as usize) {
+                    active = idx;
+                    if mouse_buttons == MouseButtons::LEFT {
+                        return Ok(buttons[idx].1);
+                    }
+                }
+            }
+            Some(_) => {}
+            None => return Ok(HostVerificationDecision::Reject),
+        }
+    }
+}
+
+/// Shows `header` and `prompt` verbatim and reads the answer to the
+/// prompt, which is not displayed unless `echo` is set
+fn read_answer(
+    term: &mut TermWizTerminal,
+    header: &[String],
+    prompt: &str,
+    echo: bool,
+) -> anyhow::Result<String> {
+    let mut lines = header.to_vec();
+    lines.push(String::new());
+    lines.extend(prompt.split('\n').map(|line| line.to_string()));
+    let prompt_row = lines.len();
+    let prompt_width = unicode_column_width(lines.last().unwrap(), None);
+
+    let mut answer = String::new();
+    loop {
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Visible),
+        ];
+        render_lines(&mut changes, &lines);
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(MARGIN),
+            y: Position::Absolute(prompt_row + 2),
+        });
+        changes.push(Change::Text(if echo {
+            "Enter submits; Escape cancels".to_string()
+        } else {
+            "The input is not shown.  Enter submits; Escape cancels".to_string()
+        }));
+        let shown = if echo {
+            unicode_column_width(&answer, None)
+        } else {
+            0
+        };
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(MARGIN + prompt_width),
+            y: Position::Absolute(prompt_row),
+        });
+        if echo {
+            changes.push(Change::Text(answer.clone()));
+        }
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(MARGIN + prompt_width + shown),
+            y: Position::Absolute(prompt_row),
+        });
+        term.render(&changes)?;
+
+        match term.poll_input(None)? {
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            })) => return Ok(answer),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }))
+            | None => bail!("Authentication was cancelled"),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            })) => {
+                answer.pop();
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('u'),
+                modifiers: Modifiers::CTRL,
+            })) => answer.clear(),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers,
+            })) if !modifiers.contains(Modifiers::CTRL) => answer.push(c),
+            Some(InputEvent::Paste(text)) => {
+                answer.push_str(text.trim_end_matches(|c| c == '\r' || c == '\n'))
+            }
+            Some(_) => {}
+        }
+    }
+}
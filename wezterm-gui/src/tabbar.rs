@@ -6,6 +6,7 @@ use termwiz::cell::unicode_column_width;
 use termwiz::cell::{Cell, CellAttributes};
 use termwiz::color::ColorSpec;
 use termwiz::escape::csi::Sgr;
+use termwiz::escape::osc::Progress;
 use termwiz::escape::parser::Parser;
 use termwiz::escape::{Action, ControlCode, CSI};
 use termwiz::surface::SEQ_ZERO;
@@ -28,6 +29,11 @@ pub enum TabBarItem {
 pub struct TabEntry {
     pub item: TabBarItem,
     pub title: Line,
+    /// The progress to show underneath the tab.  This is only
+    /// reported when the title was produced by the default formatting,
+    /// as a custom `format-tab-title` handler is responsible for
+    /// its own presentation.
+    pub progress: Progress,
     x: usize,
     width: usize,
 }
@@ -36,6 +42,7 @@ pub struct TabEntry {
 struct TitleText {
     items: Vec<FormatItem>,
     len: usize,
+    progress: Progress,
 }
 
 fn call_format_tab_title(
@@ -76,6 +83,7 @@ fn call_format_tab_title(
                     Ok(Some(TitleText {
                         items,
                         len: line.cells().len(),
+                        progress: Progress::None,
                     }))
                 }
                 _ => {
@@ -83,6 +91,7 @@ fn call_format_tab_title(
                     Ok(Some(TitleText {
                         len: unicode_column_width(&s, None),
                         items: vec![FormatItem::Text(s)],
+                        progress: Progress::None,
                     }))
                 }
             }
@@ -128,6 +137,19 @@ fn compute_tab_title(
                         classic_spacing,
                     );
                 }
+                if config.use_fancy_tab_bar && tab.has_unseen_output {
+                    // Indicate that something happened in one of the
+                    // panes in this tab since it was last looked at
+                    title = format!("\u{25cf} {}", title);
+                }
+                if config.use_fancy_tab_bar && tab.active_pane_zoomed {
+                    // The other panes in this tab are hidden
+                    title = format!("\u{2922} {}", title);
+                }
+                if tab.is_broadcasting {
+                    // Make it obvious that typing goes to several panes
+                    title = format!("\u{21c9} {}", title);
+                }
                 // We have a preferred soft minimum on tab width to make it
                 // easier to click on tab titles, but we'll still go below
                 // this if there are too many tabs to fit the window at
@@ -145,6 +167,7 @@ fn compute_tab_title(
             TitleText {
                 len: unicode_column_width(&title, None),
                 items: vec![FormatItem::Text(title)],
+                progress: tab.progress,
             }
         }
     }
@@ -299,6 +322,7 @@ impl TabBarState {
             items.push(TabEntry {
                 item: TabBarItem::Tab { tab_idx, active },
                 title,
+                progress: tab_title.progress,
                 x: tab_start_idx,
                 width,
             });
@@ -321,6 +345,7 @@ impl TabBarState {
             items.push(TabEntry {
                 item: TabBarItem::NewTabButton,
                 title: new_tab_button.clone(),
+                progress: Progress::None,
                 x: button_start,
                 width,
             });
@@ -339,6 +364,7 @@ impl TabBarState {
         items.push(TabEntry {
             item: TabBarItem::None,
             title: status_line.clone(),
+            progress: Progress::None,
             x,
             width: status_space_available,
         });
@@ -466,7 +492,8 @@ fn parse_status_text(text: &str, default_cell: CellAttributes) -> Line {
             | Action::Esc(_)
             | Action::KittyImage(_)
             | Action::XtGetTcap(_)
-            | Action::Sixel(_) => {
+            | Action::Sixel(_)
+            | Action::SixelProgress(_) => {
                 flush_print(&mut print_buffer, &mut cells, &pen);
             }
         }
@@ -0,0 +1,165 @@
+//! Remembers the assignments that were performed in response to key
+//! presses or selections from the command palette, so that the most
+//! recent one can be repeated by `RepeatLastAssignment`, and so that
+//! lua can inspect them via `window:action_history`.
+use config::keyassignment::KeyAssignment;
+use mux::pane::Pane;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// The number of assignments to remember
+const ACTION_HISTORY_LIMIT: usize = 100;
+
+#[derive(Default)]
+pub struct ActionHistory {
+    /// Most recent last
+    entries: VecDeque<KeyAssignment>,
+}
+
+impl ActionHistory {
+    fn push(&mut self, assignment: &KeyAssignment) {
+        if self.entries.len() == ACTION_HISTORY_LIMIT {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(assignment.clone());
+    }
+
+    /// Returns up to `count` of the most recent assignments,
+    /// most recent first
+    pub fn recent(&self, count: usize) -> Vec<KeyAssignment> {
+        self.entries.iter().rev().take(count).cloned().collect()
+    }
+}
+
+/// Returns the name of the assignment, which is the name that is
+/// used to specify it in the configuration, eg: `AdjustPaneSize`
+fn assignment_name(assignment: &KeyAssignment) -> Option<String> {
+    match serde_json::to_value(assignment).ok()? {
+        serde_json::Value::String(name) => Some(name),
+        serde_json::Value::Object(map) => map.keys().next().cloned(),
+        _ => None,
+    }
+}
+
+/// Returns true if repeating the assignment makes sense.
+/// `allowed_overlays` lists the names of assignments that open an
+/// overlay but that should be repeatable anyway.
+fn is_repeatable(assignment: &KeyAssignment, allowed_overlays: &[String]) -> bool {
+    use KeyAssignment::*;
+    match assignment {
+        // Repeating these would either repeat the repeat, do nothing,
+        // or amount to typing input, which is better done by the keys
+        // that produce it
+        RepeatLastAssignment
+        | Nop
+        | DisableDefaultAssignment
+        | SendString(_)
+        | SendKey(_)
+        | ReplayKeyRecording(_) => false,
+        // These depend on the position of the mouse
+        SelectTextAtMouseCursor(_)
+        | ExtendSelectionToMouseCursor(_)
+        | OpenLinkAtMouseCursor
+        | CompleteSelection(_)
+        | CompleteSelectionOrOpenLinkAtMouseCursor(_)
+        | StartWindowDrag => false,
+        // These open an overlay
+        ShowTabNavigator
+        | ShowDebugOverlay
+        | ShowKeyDebugOverlay
+        | ListKeyBindings
+        | ShowLauncher
+        | ShowDomainStatus
+        | Search(_)
+        | ActivateCopyMode
+        | QuickSelect
+        | ActivateCommandPalette => match assignment_name(assignment) {
+            Some(name) => allowed_overlays.contains(&name),
+            None => false,
+        },
+        Multiple(actions) => actions
+            .iter()
+            .all(|action| is_repeatable(action, allowed_overlays)),
+        _ => true,
+    }
+}
+
+impl super::TermWindow {
+    /// Performs an assignment that the user requested by pressing
+    /// a key or via the command palette, and records it in the history
+    pub fn perform_user_assignment(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        assignment: &KeyAssignment,
+    ) -> anyhow::Result<()> {
+        if *assignment != KeyAssignment::RepeatLastAssignment {
+            self.action_history.push(assignment);
+        }
+        self.perform_key_assignment(pane, assignment)
+    }
+
+    /// Performs the most recently recorded assignment that can be repeated
+    pub fn repeat_last_assignment(&mut self, pane: &Rc<dyn Pane>) -> anyhow::Result<()> {
+        let allowed_overlays = &self.config.repeatable_overlay_assignments;
+        let assignment = self
+            .action_history
+            .entries
+            .iter()
+            .rev()
+            .find(|assignment| is_repeatable(assignment, allowed_overlays))
+            .cloned();
+        match assignment {
+            Some(assignment) => self.perform_user_assignment(pane, &assignment),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns up to `count` of the most recently performed assignments,
+    /// most recent first
+    pub fn action_history(&self, count: usize) -> Vec<KeyAssignment> {
+        self.action_history.recent(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config::keyassignment::{PaneDirection, Pattern};
+
+    #[test]
+    fn repeatable() {
+        let none: Vec<String> = vec![];
+        assert!(is_repeatable(
+            &KeyAssignment::AdjustPaneSize(PaneDirection::Left, 5),
+            &none
+        ));
+        assert!(!is_repeatable(&KeyAssignment::RepeatLastAssignment, &none));
+        assert!(!is_repeatable(
+            &KeyAssignment::SendString("ls\n".to_string()),
+            &none
+        ));
+
+        let search = KeyAssignment::Search(Pattern::CaseSensitiveString("".into()));
+        assert!(!is_repeatable(&search, &none));
+        assert!(is_repeatable(&search, &["Search".to_string()]));
+        assert!(!is_repeatable(
+            &KeyAssignment::ActivateCommandPalette,
+            &["Search".to_string()]
+        ));
+
+        assert!(is_repeatable(
+            &KeyAssignment::Multiple(vec![
+                KeyAssignment::ActivatePaneDirection(PaneDirection::Up),
+                KeyAssignment::TogglePaneZoomState,
+            ]),
+            &none
+        ));
+        assert!(!is_repeatable(
+            &KeyAssignment::Multiple(vec![
+                KeyAssignment::ActivatePaneDirection(PaneDirection::Up),
+                KeyAssignment::SendString("x".to_string()),
+            ]),
+            &none
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+//! Input broadcast sends the keys and pastes that are directed at one
+//! pane to the other panes that are part of the same broadcast, as
+//! recorded by the mux for the tab or window.  Panes whose cursor is
+//! at what looks like a password prompt don't receive broadcast input,
+//! so that a password typed into one pane isn't sent to the others.
+use config::keyassignment::BroadcastScope;
+use mux::pane::Pane;
+use mux::{Mux, MuxNotification};
+use std::io::Write;
+use std::rc::Rc;
+use termwiz::input::{KeyCode, Modifiers};
+
+impl super::TermWindow {
+    pub fn toggle_input_broadcast(&mut self, scope: BroadcastScope) {
+        let mux = Mux::get().unwrap();
+        match scope {
+            BroadcastScope::Window => {
+                if let Some(mut window) = mux.get_window_mut(self.mux_window_id) {
+                    window.toggle_input_broadcast();
+                }
+                return;
+            }
+            BroadcastScope::Tab => {
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    tab.toggle_input_broadcast();
+                }
+            }
+            BroadcastScope::Selected => {
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return,
+                };
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    tab.toggle_input_broadcast_for_pane(pane.pane_id());
+                }
+            }
+        }
+        mux.notify(MuxNotification::WindowInvalidated(self.mux_window_id));
+    }
+
+    /// Returns the panes, other than `pane`, that should receive the
+    /// input that is sent to `pane`
+    fn broadcast_targets(&self, pane: &Rc<dyn Pane>) -> Vec<Rc<dyn Pane>> {
+        // Overlays are not part of any tab, so their input is never
+        // broadcast, but check anyway in case that ever changes
+        if self.pane_state(pane.pane_id()).overlay.is_some() {
+            return vec![];
+        }
+        let mux = Mux::get().unwrap();
+        mux.input_broadcast_targets(pane.pane_id())
+            .into_iter()
+            .filter(|target| {
+                if target.is_at_password_prompt() {
+                    log::debug!(
+                        "not broadcasting to pane {}, which is at a password prompt",
+                        target.pane_id()
+                    );
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect()
+    }
+
+    pub fn broadcast_key(&self, pane: &Rc<dyn Pane>, key: KeyCode, mods: Modifiers) {
+        for target in self.broadcast_targets(pane) {
+            if let Err(err) = target.key_down(key, mods) {
+                log::error!("broadcasting key to pane {}: {:#}", target.pane_id(), err);
+            }
+        }
+    }
+
+    /// Broadcasts text that is sent as-is, such as the result of composition
+    pub fn broadcast_text(&self, pane: &Rc<dyn Pane>, text: &str) {
+        for target in self.broadcast_targets(pane) {
+            if let Err(err) = target.writer().write_all(text.as_bytes()) {
+                log::error!("broadcasting text to pane {}: {:#}", target.pane_id(), err);
+            }
+        }
+    }
+
+    pub fn broadcast_paste(&self, pane: &Rc<dyn Pane>, text: &str) {
+        for target in self.broadcast_targets(pane) {
+            if let Err(err) = target.trickle_paste(text.to_string()) {
+                log::error!("broadcasting paste to pane {}: {:#}", target.pane_id(), err);
+            }
+        }
+    }
+}
@@ -52,6 +52,10 @@ impl TermWindow {
 
     pub fn copy_to_clipboard(&self, clipboard: ClipboardCopyDestination, text: String) {
         let clipboard = match clipboard {
+            ClipboardCopyDestination::Register(name) => {
+                self.registers.borrow_mut().insert(name, text);
+                return;
+            }
             ClipboardCopyDestination::Clipboard => [Some(Clipboard::Clipboard), None],
             ClipboardCopyDestination::PrimarySelection => [Some(Clipboard::PrimarySelection), None],
             ClipboardCopyDestination::ClipboardAndPrimarySelection => [
@@ -72,6 +76,15 @@ impl TermWindow {
         let clipboard = match clipboard {
             ClipboardPasteSource::Clipboard => Clipboard::Clipboard,
             ClipboardPasteSource::PrimarySelection => Clipboard::PrimarySelection,
+            ClipboardPasteSource::Register(name) => {
+                let text = self.registers.borrow().get(&name).cloned();
+                if let Some(text) = text {
+                    self.broadcast_paste(pane, &text);
+                    pane.trickle_paste(text).ok();
+                    self.maybe_scroll_to_bottom_for_input(&pane);
+                }
+                return;
+            }
         };
         let future = window.get_clipboard(clipboard);
         promise::spawn::spawn(async move {
@@ -81,6 +94,7 @@ impl TermWindow {
                         let mux = Mux::get().unwrap();
                         mux.get_pane(pane_id)
                     }) {
+                        myself.broadcast_paste(&pane, &clip);
                         pane.trickle_paste(clip).ok();
                     }
                 })));
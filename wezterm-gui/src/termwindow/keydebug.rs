@@ -0,0 +1,278 @@
+//! The key event inspector.
+//! While the inspector overlay is active, key events are resolved in
+//! the same way as `key_event_impl` resolves them, but rather than
+//! performing the assignment or sending the key to the pane, a
+//! description of what would have happened is shown in the overlay
+//! and logged at debug level under the `key_events` target.
+use super::keyevent::{window_mods_to_termwiz_mods, Key};
+use ::window::{KeyCode, KeyEvent, Modifiers};
+use config::keyassignment::KeyAssignment;
+use mux::pane::PaneId;
+use std::sync::mpsc::{channel, Sender};
+use termwiz::input::KeyCodeEncodeModes;
+use wezterm_term::TerminalConfiguration;
+
+/// The state of an active key event inspector
+pub struct KeyDebugState {
+    pane_id: PaneId,
+    events: Sender<Vec<String>>,
+    /// The number of consecutive presses of Escape
+    escapes: usize,
+}
+
+fn describe_chord(key: &KeyCode, mods: Modifiers) -> String {
+    let key = config::keycode_to_config_name(key).unwrap_or_else(|| format!("{:?}", key));
+    format!("{} {}", config::modifiers_to_config_string(mods), key)
+}
+
+impl super::TermWindow {
+    pub fn show_key_debug_overlay(&mut self) {
+        if let Some(state) = self.key_debug.as_ref() {
+            let is_open = mux::Mux::get()
+                .map(|mux| mux.get_pane(state.pane_id).is_some())
+                .unwrap_or(false);
+            self.close_key_debug_overlay();
+            if is_open {
+                return;
+            }
+            // Otherwise the inspector went away without us noticing;
+            // open a fresh one
+        }
+
+        let mux = mux::Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let (tx, rx) = channel();
+        let (overlay, future) = crate::overlay::start_overlay(self, &tab, move |_tab_id, term| {
+            crate::overlay::show_key_debug_overlay(term, rx)
+        });
+        self.key_debug.replace(KeyDebugState {
+            pane_id: overlay.pane_id(),
+            events: tx,
+            escapes: 0,
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Dropping the sender causes the overlay to finish,
+    /// which in turn removes it from the tab
+    fn close_key_debug_overlay(&mut self) {
+        self.key_debug.take();
+    }
+
+    /// Called when the overlay `pane_id` has been removed by any
+    /// means, so that the inspector state doesn't outlive its overlay
+    pub fn key_debug_overlay_removed(&mut self, pane_id: PaneId) {
+        if self.key_debug.as_ref().map(|state| state.pane_id) == Some(pane_id) {
+            self.close_key_debug_overlay();
+        }
+    }
+
+    /// If the key event inspector is the active overlay, describes
+    /// the event instead of processing it, and returns true.
+    pub fn key_debug_event(&mut self, window_key: &KeyEvent) -> bool {
+        let pane_id = match self.key_debug.as_ref() {
+            Some(state) => state.pane_id,
+            None => return false,
+        };
+        if self.get_active_pane_or_overlay().map(|pane| pane.pane_id()) != Some(pane_id) {
+            return false;
+        }
+
+        let (lines, assignment) = if window_key.key_is_down {
+            self.describe_key_down(window_key)
+        } else {
+            self.describe_key_up(window_key)
+        };
+
+        for line in &lines {
+            log::debug!(target: "key_events", "{}", line);
+        }
+
+        let mut close = matches!(assignment, Some(KeyAssignment::ShowKeyDebugOverlay));
+        if let Some(state) = self.key_debug.as_mut() {
+            if window_key.key_is_down && !window_key.key.is_modifier() {
+                if window_key.key == KeyCode::Char('\u{1b}')
+                    && window_key.modifiers == Modifiers::NONE
+                {
+                    state.escapes += 1;
+                    close |= state.escapes >= 2;
+                } else {
+                    state.escapes = 0;
+                }
+            }
+            if state.events.send(lines).is_err() {
+                close = true;
+            }
+        }
+        if close {
+            self.close_key_debug_overlay();
+        }
+        true
+    }
+
+    fn describe_key_up(&self, window_key: &KeyEvent) -> (Vec<String>, Option<KeyAssignment>) {
+        let mut lines = vec![format!("key up: {:?}", window_key)];
+        let leader_mod = if self.leader_is_active() {
+            Modifiers::LEADER
+        } else {
+            Modifiers::NONE
+        };
+        let assignment = self.lookup_key_up(window_key, leader_mod);
+        match &assignment {
+            Some(assignment) => lines.push(format!("key_up binding -> {:?}", assignment)),
+            None => lines.push("no key_up binding".to_string()),
+        }
+        (lines, assignment)
+    }
+
+    /// Follows the same sequence of lookups as key_event_impl.
+    /// The leader key is activated and canceled as usual, so that
+    /// bindings that use it can be inspected.
+    fn describe_key_down(&mut self, window_key: &KeyEvent) -> (Vec<String>, Option<KeyAssignment>) {
+        let leader_active = self.leader_is_active();
+        let leader_mod = if leader_active {
+            Modifiers::LEADER
+        } else {
+            Modifiers::NONE
+        };
+        let mut lines = vec![format!(
+            "key down: {:?}{}",
+            window_key,
+            if leader_active {
+                " (leader active)"
+            } else {
+                ""
+            }
+        )];
+
+        let key_up_bound = match self.lookup_key_up(window_key, leader_mod) {
+            Some(assignment) => {
+                lines.push(format!("key_up binding on release -> {:?}", assignment));
+                true
+            }
+            None => false,
+        };
+
+        let mut candidates = vec![];
+        if let Some(raw_code) = window_key.raw_code {
+            candidates.push((
+                "raw code",
+                KeyCode::RawCode(raw_code),
+                window_key.raw_modifiers,
+            ));
+        }
+        if let Some(key) = &window_key.raw_key {
+            candidates.push(("raw key", key.clone(), window_key.raw_modifiers));
+        }
+        candidates.push(("key", window_key.key.clone(), window_key.modifiers));
+
+        for (stage, key, mods) in candidates {
+            if !leader_active {
+                if let Some(duration) = self.input_map.is_leader(&key, mods) {
+                    lines.push(format!(
+                        "{} {} is the leader key; active for {:?}",
+                        stage,
+                        describe_chord(&key, mods),
+                        duration
+                    ));
+                    self.activate_leader(duration);
+                    return (lines, None);
+                }
+            }
+
+            if let Some(assignment) = self.input_map.lookup_key(&key, mods | leader_mod) {
+                lines.push(format!(
+                    "{} {} -> {:?}",
+                    stage,
+                    describe_chord(&key, mods | leader_mod),
+                    assignment
+                ));
+                if leader_active {
+                    self.cancel_leader();
+                }
+                return (lines, Some(assignment));
+            }
+            lines.push(format!(
+                "{} {} is not bound",
+                stage,
+                describe_chord(&key, mods | leader_mod)
+            ));
+
+            if stage == "raw key"
+                && !leader_active
+                && !key_up_bound
+                && self.bypass_compose(window_key.raw_modifiers)
+            {
+                if let Key::Code(term_key) = self.win_key_code_to_termwiz_key_code(&key) {
+                    lines.push("composition bypassed for this alt modifier".to_string());
+                    lines.push(self.describe_sent_key(term_key, window_mods_to_termwiz_mods(mods)));
+                    return (lines, None);
+                }
+            }
+        }
+
+        if key_up_bound {
+            lines.push("not sent: the key is bound to an action on release".to_string());
+            if leader_active {
+                self.cancel_leader();
+            }
+            return (lines, None);
+        }
+
+        if leader_active {
+            if !window_key.key.is_modifier() {
+                lines.push(
+                    "swallowed: only bindings are recognized while the leader is active"
+                        .to_string(),
+                );
+                self.cancel_leader();
+            }
+            return (lines, None);
+        }
+
+        match self.win_key_code_to_termwiz_key_code(&window_key.key) {
+            Key::Code(term_key) => lines.push(
+                self.describe_sent_key(term_key, window_mods_to_termwiz_mods(window_key.modifiers)),
+            ),
+            Key::Composed(text) => lines.push(format!("would write {:?} to the pane", text)),
+            Key::None => {
+                lines.push("not sent: this key has no terminal representation".to_string())
+            }
+        }
+        (lines, None)
+    }
+
+    /// The encoding also depends on modes, such as application cursor
+    /// keys, that are private to the terminal of the pane; this assumes
+    /// the default modes.
+    fn describe_sent_key(
+        &self,
+        key: termwiz::input::KeyCode,
+        mods: termwiz::input::Modifiers,
+    ) -> String {
+        let enable_csi_u_key_encoding = self
+            .get_active_pane_no_overlay()
+            .and_then(|pane| pane.get_config())
+            .map(|config| config.enable_csi_u_key_encoding())
+            .unwrap_or(self.config.enable_csi_u_key_encoding);
+        match key.encode(
+            mods,
+            KeyCodeEncodeModes {
+                enable_csi_u_key_encoding,
+                application_cursor_keys: false,
+                newline_mode: false,
+            },
+        ) {
+            Ok(text) => format!(
+                "would send {:?} {:?} to the pane, encoded as {:?}",
+                key, mods, text
+            ),
+            Err(err) => format!("would send {:?} {:?} to the pane: {:#}", key, mods, err),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use ::window::{KeyCode, KeyEvent, Modifiers, WindowOps};
+use config::keyassignment::KeyAssignment;
 
 pub fn window_mods_to_termwiz_mods(modifiers: ::window::Modifiers) -> termwiz::input::Modifiers {
     let mut result = termwiz::input::Modifiers::NONE;
@@ -33,10 +34,119 @@ pub enum Key {
     None,
 }
 
+/// A key that was pressed while it had a `key_up` binding;
+/// the assignment is performed when that key is released.
+pub struct PendingKeyUp {
+    key: KeyCode,
+    raw_code: Option<u32>,
+    assignment: KeyAssignment,
+    /// Set when another key was pressed after this one.  That stops
+    /// the auto-repeat of this key, so a subsequent press of this key
+    /// means that we missed its release.
+    interrupted: bool,
+}
+
+impl PendingKeyUp {
+    /// Returns true if `event` is for the same physical key.
+    /// The modifiers are not considered, as they may have been
+    /// released before the key itself.
+    fn matches(&self, event: &KeyEvent) -> bool {
+        match (self.raw_code, event.raw_code) {
+            (Some(a), Some(b)) => a == b,
+            _ => match (&self.key, &event.key) {
+                (KeyCode::Char(a), KeyCode::Char(b)) => a.to_lowercase().eq(b.to_lowercase()),
+                (a, b) => a == b,
+            },
+        }
+    }
+}
+
 impl super::TermWindow {
+    /// Performs the assignment bound to the release of a key,
+    /// if that key was pressed while the binding was active.
+    fn key_up_event_impl(&mut self, window_key: &KeyEvent, context: &dyn WindowOps) -> bool {
+        let idx = match self
+            .pending_key_up
+            .iter()
+            .position(|p| p.matches(window_key))
+        {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let pending = self.pending_key_up.remove(idx);
+        self.perform_key_up(pending, context);
+        true
+    }
+
+    fn perform_key_up(&mut self, pending: PendingKeyUp, context: &dyn WindowOps) {
+        if let Some(pane) = self.get_active_pane_or_overlay() {
+            self.perform_user_assignment(&pane, &pending.assignment)
+                .ok();
+            context.invalidate();
+        }
+    }
+
+    /// Returns the assignment to perform when this key is released,
+    /// considering the same variations of the key as key_event_impl
+    pub fn lookup_key_up(
+        &self,
+        window_key: &KeyEvent,
+        leader_mod: Modifiers,
+    ) -> Option<KeyAssignment> {
+        if let Some(raw_code) = window_key.raw_code {
+            if let Some(assignment) = self.input_map.lookup_key_up(
+                &KeyCode::RawCode(raw_code),
+                window_key.raw_modifiers | leader_mod,
+            ) {
+                return Some(assignment);
+            }
+        }
+        if let Some(key) = &window_key.raw_key {
+            if let Some(assignment) = self
+                .input_map
+                .lookup_key_up(key, window_key.raw_modifiers | leader_mod)
+            {
+                return Some(assignment);
+            }
+        }
+        self.input_map
+            .lookup_key_up(&window_key.key, window_key.modifiers | leader_mod)
+    }
+
+    /// Returns true if the composed form of a key pressed with the
+    /// alt modifiers in `raw_modifiers` should be ignored in favor of
+    /// the raw key, as configured by the send_composed_key_when_XXX
+    /// options.
+    pub fn bypass_compose(&self, raw_modifiers: Modifiers) -> bool {
+        let config = &self.config;
+
+        // This is a bit ugly.
+        // Not all of our platforms report LEFT|RIGHT ALT; most report just ALT.
+        // For those that do distinguish between them we want to respect the left vs.
+        // right settings for the compose behavior.
+        // Otherwise, if the event didn't include left vs. right then we want to
+        // respect the generic compose behavior.
+        // Left ALT and they disabled compose
+        (raw_modifiers.contains(Modifiers::LEFT_ALT)
+            && !config.send_composed_key_when_left_alt_is_pressed)
+            // Right ALT and they disabled compose
+            || (raw_modifiers.contains(Modifiers::RIGHT_ALT)
+                && !config.send_composed_key_when_right_alt_is_pressed)
+            // Generic ALT and they disabled generic compose
+            || (!raw_modifiers.contains(Modifiers::RIGHT_ALT)
+                && !raw_modifiers.contains(Modifiers::LEFT_ALT)
+                && raw_modifiers.contains(Modifiers::ALT)
+                && !config.send_composed_key_when_alt_is_pressed)
+    }
+
     pub fn key_event_impl(&mut self, window_key: KeyEvent, context: &dyn WindowOps) -> bool {
+        if self.key_debug_event(&window_key) {
+            context.invalidate();
+            return true;
+        }
+
         if !window_key.key_is_down {
-            return false;
+            return self.key_up_event_impl(&window_key, context);
         }
 
         if self.config.debug_key_events {
@@ -60,12 +170,52 @@ impl super::TermWindow {
             }
             Some(_) => {
                 // Expired; clear out the old expiration time
-                self.leader_is_down.take();
+                self.cancel_leader();
                 (false, Modifiers::NONE)
             }
             _ => (false, Modifiers::NONE),
         };
 
+        // While a key with a release binding is held, swallow its
+        // auto-repeated presses so that its press binding, if any,
+        // is performed just once.
+        if let Some(idx) = self
+            .pending_key_up
+            .iter()
+            .position(|p| p.matches(&window_key))
+        {
+            if !self.pending_key_up[idx].interrupted {
+                return true;
+            }
+            // This is a fresh press, so the release was lost; perform
+            // the release action now rather than leaving the key
+            // swallowed
+            let pending = self.pending_key_up.remove(idx);
+            self.perform_key_up(pending, context);
+        }
+        if !window_key.key.is_modifier() {
+            for pending in &mut self.pending_key_up {
+                pending.interrupted = true;
+            }
+        }
+
+        // If the key is bound to an action on release then remember
+        // it so that we can perform that action when it is released.
+        // Press bindings for the key are still performed, but the key
+        // is not otherwise sent to the pane.
+        let key_up_bound = match self.lookup_key_up(&window_key, leader_mod) {
+            Some(assignment) => {
+                self.pending_key_up.push(PendingKeyUp {
+                    key: window_key.key.clone(),
+                    raw_code: window_key.raw_code,
+                    assignment,
+                    interrupted: false,
+                });
+                true
+            }
+            None => false,
+        };
+
         let modifiers = window_mods_to_termwiz_mods(window_key.modifiers);
         let raw_modifiers = window_mods_to_termwiz_mods(window_key.raw_modifiers);
 
@@ -85,8 +235,7 @@ impl super::TermWindow {
                     .is_leader(&raw_code_key, window_key.raw_modifiers)
                 {
                     // Yes; record its expiration
-                    self.leader_is_down
-                        .replace(std::time::Instant::now() + duration);
+                    self.activate_leader(duration);
                     return true;
                 }
             }
@@ -95,13 +244,13 @@ impl super::TermWindow {
                 .input_map
                 .lookup_key(&raw_code_key, window_key.raw_modifiers | leader_mod)
             {
-                self.perform_key_assignment(&pane, &assignment).ok();
+                self.perform_user_assignment(&pane, &assignment).ok();
                 context.invalidate();
 
                 if leader_active {
                     // A successful leader key-lookup cancels the leader
                     // virtual modifier state
-                    self.leader_is_down.take();
+                    self.cancel_leader();
                 }
                 return true;
             }
@@ -114,8 +263,7 @@ impl super::TermWindow {
                 // Check to see if this key-press is the leader activating
                 if let Some(duration) = self.input_map.is_leader(key, window_key.raw_modifiers) {
                     // Yes; record its expiration
-                    self.leader_is_down
-                        .replace(std::time::Instant::now() + duration);
+                    self.activate_leader(duration);
                     return true;
                 }
             }
@@ -124,43 +272,26 @@ impl super::TermWindow {
                 .input_map
                 .lookup_key(key, window_key.raw_modifiers | leader_mod)
             {
-                self.perform_key_assignment(&pane, &assignment).ok();
+                self.perform_user_assignment(&pane, &assignment).ok();
                 context.invalidate();
 
                 if leader_active {
                     // A successful leader key-lookup cancels the leader
                     // virtual modifier state
-                    self.leader_is_down.take();
+                    self.cancel_leader();
                 }
                 return true;
             }
 
             // While the leader modifier is active, only registered
             // keybindings are recognized.
-            if !leader_active {
-                let config = &self.config;
-
-                // This is a bit ugly.
-                // Not all of our platforms report LEFT|RIGHT ALT; most report just ALT.
-                // For those that do distinguish between them we want to respect the left vs.
-                // right settings for the compose behavior.
-                // Otherwise, if the event didn't include left vs. right then we want to
-                // respect the generic compose behavior.
-                let bypass_compose =
-                    // Left ALT and they disabled compose
-                    (window_key.raw_modifiers.contains(Modifiers::LEFT_ALT)
-                    && !config.send_composed_key_when_left_alt_is_pressed)
-                    // Right ALT and they disabled compose
-                    || (window_key.raw_modifiers.contains(Modifiers::RIGHT_ALT)
-                        && !config.send_composed_key_when_right_alt_is_pressed)
-                    // Generic ALT and they disabled generic compose
-                    || (!window_key.raw_modifiers.contains(Modifiers::RIGHT_ALT)
-                        && !window_key.raw_modifiers.contains(Modifiers::LEFT_ALT)
-                        && window_key.raw_modifiers.contains(Modifiers::ALT)
-                        && !config.send_composed_key_when_alt_is_pressed);
+            if !leader_active && !key_up_bound {
+                let bypass_compose = self.bypass_compose(window_key.raw_modifiers);
 
                 if let Key::Code(term_key) = self.win_key_code_to_termwiz_key_code(&key) {
                     if bypass_compose && pane.key_down(term_key, raw_modifiers).is_ok() {
+                        self.record_key(term_key, raw_modifiers);
+                        self.broadcast_key(&pane, term_key, raw_modifiers);
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
                         }
@@ -179,8 +310,7 @@ impl super::TermWindow {
                 .is_leader(&window_key.key, window_key.modifiers)
             {
                 // Yes; record its expiration
-                self.leader_is_down
-                    .replace(std::time::Instant::now() + duration);
+                self.activate_leader(duration);
                 return true;
             }
         }
@@ -189,12 +319,17 @@ impl super::TermWindow {
             .input_map
             .lookup_key(&window_key.key, window_key.modifiers | leader_mod)
         {
-            self.perform_key_assignment(&pane, &assignment).ok();
+            self.perform_user_assignment(&pane, &assignment).ok();
             context.invalidate();
             if leader_active {
                 // A successful leader key-lookup cancels the leader
                 // virtual modifier state
-                self.leader_is_down.take();
+                self.cancel_leader();
+            }
+            true
+        } else if key_up_bound {
+            if leader_active {
+                self.cancel_leader();
             }
             true
         } else if leader_active {
@@ -202,7 +337,7 @@ impl super::TermWindow {
                 // Leader was pressed and this non-modifier keypress isn't
                 // a registered key binding; swallow this event and cancel
                 // the leader modifier
-                self.leader_is_down.take();
+                self.cancel_leader();
             }
             true
         } else {
@@ -210,6 +345,8 @@ impl super::TermWindow {
             match key {
                 Key::Code(key) => {
                     if pane.key_down(key, modifiers).is_ok() {
+                        self.record_key(key, modifiers);
+                        self.broadcast_key(&pane, key, modifiers);
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
                         }
@@ -225,9 +362,11 @@ impl super::TermWindow {
                         // Leader was pressed and this non-modifier keypress isn't
                         // a registered key binding; swallow this event and cancel
                         // the leader modifier.
-                        self.leader_is_down.take();
+                        self.cancel_leader();
                     } else {
                         pane.writer().write_all(s.as_bytes()).ok();
+                        self.record_text(&s);
+                        self.broadcast_text(&pane, &s);
                         self.maybe_scroll_to_bottom_for_input(&pane);
                         context.invalidate();
                     }
@@ -0,0 +1,142 @@
+//! Key recordings capture the keys that were sent to a pane so that
+//! they can be replayed later, into the same or a different pane.
+//! Keys are recorded prior to being encoded, so that replaying them
+//! produces the appropriate sequences for the modes (such as
+//! application cursor keys) that are active in the pane at the time
+//! of replay.  The registers are shared by all windows.
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use smol::Timer;
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::Duration;
+use termwiz::input::{KeyCode, Modifiers};
+
+lazy_static::lazy_static! {
+    static ref REGISTERS: Mutex<HashMap<String, Vec<RecordedKey>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone)]
+pub enum RecordedKey {
+    /// A key press, to be encoded by the pane
+    Key { key: KeyCode, mods: Modifiers },
+    /// Text that is sent as-is, such as the result of composition
+    Text(String),
+}
+
+impl RecordedKey {
+    fn send_to(&self, pane: &Rc<dyn Pane>) -> anyhow::Result<()> {
+        match self {
+            Self::Key { key, mods } => pane.key_down(*key, *mods),
+            Self::Text(text) => {
+                pane.writer().write_all(text.as_bytes())?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An in-progress recording
+pub struct KeyRecording {
+    pub register: String,
+    keys: Vec<RecordedKey>,
+}
+
+impl super::TermWindow {
+    /// Returns the name of the register that is being recorded
+    /// into by this window, if any
+    pub fn active_key_recording(&self) -> Option<String> {
+        self.key_recording.as_ref().map(|r| r.register.clone())
+    }
+
+    pub fn start_key_recording(&mut self, register: &str) {
+        self.key_recording.replace(KeyRecording {
+            register: register.to_string(),
+            keys: vec![],
+        });
+        self.key_recording_changed();
+    }
+
+    /// Stops recording and saves the keys into the register,
+    /// replacing its prior contents
+    pub fn stop_key_recording(&mut self) {
+        if let Some(recording) = self.key_recording.take() {
+            REGISTERS
+                .lock()
+                .unwrap()
+                .insert(recording.register, recording.keys);
+            self.key_recording_changed();
+        }
+    }
+
+    fn key_recording_changed(&mut self) {
+        self.emit_status_event();
+        self.update_title_impl();
+    }
+
+    /// Called whenever a key is sent to a pane as a result
+    /// of being pressed, or of a SendKey assignment
+    pub fn record_key(&mut self, key: KeyCode, mods: Modifiers) {
+        if let Some(recording) = self.key_recording.as_mut() {
+            if !key.is_modifier() {
+                recording.keys.push(RecordedKey::Key { key, mods });
+            }
+        }
+    }
+
+    /// Called whenever text is sent to a pane as a result of
+    /// composition, or of a SendString assignment
+    pub fn record_text(&mut self, text: &str) {
+        if let Some(recording) = self.key_recording.as_mut() {
+            recording.keys.push(RecordedKey::Text(text.to_string()));
+        }
+    }
+
+    /// Sends the keys from the register to the pane, pausing for
+    /// `key_replay_delay` between each one if it is non-zero
+    pub fn replay_key_recording(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        register: &str,
+    ) -> anyhow::Result<()> {
+        let keys = REGISTERS
+            .lock()
+            .unwrap()
+            .get(register)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("there is no key recording named {}", register))?;
+
+        let delay = Duration::from_millis(self.config.key_replay_delay);
+        if delay.as_millis() == 0 {
+            for key in &keys {
+                key.send_to(pane)?;
+            }
+            self.maybe_scroll_to_bottom_for_input(pane);
+            return Ok(());
+        }
+
+        let pane_id = pane.pane_id();
+        promise::spawn::spawn(async move {
+            for key in keys {
+                Timer::after(delay).await;
+                if let Err(err) = send_to_pane_id(pane_id, &key) {
+                    log::error!("replaying key recording: {:#}", err);
+                    break;
+                }
+            }
+        })
+        .detach();
+
+        Ok(())
+    }
+}
+
+fn send_to_pane_id(pane_id: PaneId, key: &RecordedKey) -> anyhow::Result<()> {
+    let mux = Mux::get().expect("replay to happen on the main thread");
+    let pane = mux
+        .get_pane(pane_id)
+        .ok_or_else(|| anyhow::anyhow!("pane {} no longer exists", pane_id))?;
+    key.send_to(&pane)
+}
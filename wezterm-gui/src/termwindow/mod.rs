@@ -4,11 +4,13 @@ use super::utilsprites::RenderMetrics;
 use crate::cache::LruCache;
 use crate::glium::texture::SrgbTexture2d;
 use crate::overlay::{
-    confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program, launcher,
-    start_overlay, start_overlay_pane, tab_navigator, CopyOverlay, QuickSelectOverlay,
-    SearchOverlay,
+    attach_domain, build_entries, command_palette, confirm_close_pane, confirm_close_tab,
+    confirm_close_window, confirm_quit_program, detach_domain, domain_status, launcher,
+    processes_preventing_close, start_overlay, start_overlay_pane, tab_navigator, CopyOverlay,
+    PaletteEntry, QuickSelectOverlay, SearchOverlay,
 };
-use crate::scripting::guiwin::GuiWin;
+use crate::scripting::domain::DomainObject;
+use crate::scripting::guiwin::{GpuInfo, GuiWin, RenderStats};
 use crate::scripting::pane::PaneObject;
 use crate::scrollbar::*;
 use crate::selection::Selection;
@@ -20,14 +22,16 @@ use anyhow::Context;
 use anyhow::{anyhow, ensure};
 use config::keyassignment::{
     ClipboardCopyDestination, ClipboardPasteSource, InputMap, KeyAssignment, SpawnCommand,
+    SpawnTabDomain,
 };
 use config::{
-    configuration, AudibleBell, ConfigHandle, DimensionContext, GradientOrientation, TermConfig,
-    WindowCloseConfirmation,
+    configuration, AudibleBell, ConfigHandle, DimensionContext, GradientOrientation,
+    LaunchMenuItem, TabBarPosition, TermConfig, WindowCloseConfirmation,
 };
 use luahelper::impl_lua_conversion;
 use mlua::FromLua;
 use mux::domain::{DomainId, DomainState};
+use mux::monitor::MonitorKind;
 use mux::pane::{Pane, PaneId};
 use mux::renderable::RenderableDimensions;
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection, Tab, TabId};
@@ -44,24 +48,31 @@ use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use termwiz::escape::osc::Progress;
 use termwiz::hyperlink::Hyperlink;
 use termwiz::image::{ImageData, ImageDataType};
 use termwiz::surface::SequenceNo;
 use wezterm_font::FontConfiguration;
-use wezterm_term::color::ColorPalette;
+use wezterm_term::color::{ColorPalette, RgbColor};
 use wezterm_term::input::LastMouseClick;
 use wezterm_term::{Alert, SemanticZone, StableRowIndex, TerminalConfiguration};
+use wezterm_toast_notification::ToastNotification;
 
+mod actionhistory;
+mod broadcast;
 pub mod clipboard;
+mod keydebug;
 mod keyevent;
+mod keyrecord;
 mod mouseevent;
 mod prevcursor;
 mod render;
 pub mod resize;
 mod selection;
 pub mod spawn;
+mod tabdrag;
 use clipboard::ClipboardHelper;
-use prevcursor::PrevCursorPos;
+use prevcursor::{CursorAnimation, PrevCursorPos};
 use spawn::SpawnWhere;
 
 const ATLAS_SIZE: usize = 128;
@@ -76,6 +87,19 @@ pub fn set_window_class(cls: &str) {
     *WINDOW_CLASS.lock().unwrap() = cls.to_owned();
 }
 
+thread_local! {
+    /// The window that most recently had the focus, which is where
+    /// actions that don't come from a window, such as clicking on a
+    /// notification, take effect
+    static LAST_FOCUSED_WINDOW: RefCell<Option<(MuxWindowId, Window)>> = RefCell::new(None);
+}
+
+/// Returns the window that most recently had the focus, if it
+/// is still open
+pub fn last_focused_window() -> Option<Window> {
+    LAST_FOCUSED_WINDOW.with(|last| last.borrow().as_ref().map(|(_, window)| window.clone()))
+}
+
 /// Type used together with Window::notify to do something in the
 /// context of the window-specific event loop
 pub enum TermWindowNotif {
@@ -86,6 +110,15 @@ pub enum TermWindowNotif {
     },
     SetRightStatus(String),
     GetDimensions(Sender<(Dimensions, WindowState)>),
+    GetGpuInfo(Sender<Option<GpuInfo>>),
+    GetScreenName(Sender<Option<String>>),
+    GetRenderStats(Sender<RenderStats>),
+    GetLeaderIsActive(Sender<bool>),
+    GetActiveKeyRecording(Sender<Option<String>>),
+    GetActionHistory {
+        count: usize,
+        tx: Sender<Vec<KeyAssignment>>,
+    },
     GetSelectionForPane {
         pane_id: PaneId,
         tx: Sender<String>,
@@ -97,6 +130,7 @@ pub enum TermWindowNotif {
     },
     GetConfigOverrides(Sender<serde_json::Value>),
     SetConfigOverrides(serde_json::Value),
+    SetColorScheme(String),
     CancelOverlayForPane(PaneId),
     CancelOverlayForTab {
         tab_id: TabId,
@@ -153,6 +187,15 @@ pub struct PaneState {
     pub overlay: Option<Rc<dyn Pane>>,
 
     bell_start: Option<Instant>,
+
+    /// How dimmed the pane was when it was last painted;
+    /// 0.0 is the active appearance, 1.0 is fully dimmed
+    dim_amount: Option<f32>,
+    /// When a fade between the active and inactive appearance started,
+    /// the amount it started from and the amount it is heading towards
+    dim_transition: Option<(Instant, f32, f32)>,
+
+    cursor_animation: CursorAnimation,
 }
 
 /// Data used when synchronously formatting pane and window titles
@@ -162,6 +205,17 @@ pub struct TabInformation {
     pub tab_index: usize,
     pub is_active: bool,
     pub active_pane: Option<PaneInformation>,
+    /// The progress reported by the active pane
+    pub progress: Progress,
+    /// true if any pane in the tab has unseen output
+    pub has_unseen_output: bool,
+    /// true if the input typed into the active pane is broadcast
+    /// to other panes
+    pub is_broadcasting: bool,
+    /// true if the active pane is zoomed
+    pub active_pane_zoomed: bool,
+    /// The user vars of the tab, as opposed to those of its panes
+    pub user_vars: HashMap<String, String>,
 }
 impl_lua_conversion!(TabInformation);
 
@@ -180,6 +234,8 @@ pub struct PaneInformation {
     pub pixel_height: usize,
     pub title: String,
     pub user_vars: HashMap<String, String>,
+    pub progress: Progress,
+    pub has_unseen_output: bool,
 }
 impl_lua_conversion!(PaneInformation);
 
@@ -212,6 +268,10 @@ pub struct TermWindow {
     pub config_overrides: serde_json::Value,
     /// When we most recently received keyboard focus
     focused: Option<Instant>,
+    /// The name of the screen that the window was most recently on
+    screen_name: Option<String>,
+    /// The text that the input method is composing, if any
+    ime_composition: ImeCompositionStatus,
     fonts: Rc<FontConfiguration>,
     /// Window dimensions and dpi
     pub dimensions: Dimensions,
@@ -224,6 +284,14 @@ pub struct TermWindow {
     input_map: InputMap,
     /// If is_some, the LEADER modifier is active until the specified instant.
     leader_is_down: Option<std::time::Instant>,
+    /// Keys that are held down and have an action bound to their release
+    pending_key_up: Vec<keyevent::PendingKeyUp>,
+    key_recording: Option<keyrecord::KeyRecording>,
+    action_history: actionhistory::ActionHistory,
+    /// Set while the key event inspector is open
+    key_debug: Option<keydebug::KeyDebugState>,
+    /// Text that was copied to a named register, by name
+    registers: RefCell<HashMap<String, String>>,
     show_tab_bar: bool,
     show_scroll_bar: bool,
     tab_bar: TabBarState,
@@ -246,6 +314,8 @@ pub struct TermWindow {
     window_background: Option<Arc<ImageData>>,
 
     current_mouse_buttons: Vec<MousePress>,
+    /// Horizontal scrolling that hasn't yet amounted to a whole event
+    horz_wheel_accumulator: i32,
 
     /// Keeps track of double and triple clicks
     last_mouse_click: Option<LastMouseClick>,
@@ -255,6 +325,10 @@ pub struct TermWindow {
 
     shape_cache:
         RefCell<LruCache<ShapeCacheKey, anyhow::Result<Rc<Vec<ShapedInfo<SrgbTexture2d>>>>>>,
+    /// Maps (fg, bg) to the fg adjusted to satisfy minimum_contrast_ratio
+    contrast_cache: RefCell<LruCache<(RgbColor, RgbColor), RgbColor>>,
+    /// Maps a harfbuzz_features override to the config derived from it
+    harfbuzz_features_configs: RefCell<HashMap<Vec<String>, ConfigHandle>>,
 
     next_blink_paint: RefCell<Instant>,
     last_status_call: Instant,
@@ -265,9 +339,14 @@ pub struct TermWindow {
 
     ui_items: Vec<UIItem>,
     dragging: Option<(UIItem, MouseEvent)>,
+    tab_drag: tabdrag::TabDragState,
 
     event_states: HashMap<String, EventState>,
     has_animation: RefCell<Option<Instant>>,
+    /// The number of quads that were avoided by merging
+    /// adjacent backgrounds during the current paint pass
+    merged_quads: RefCell<usize>,
+    render_stats: RenderStats,
     /// We use this to attempt to do something reasonable
     /// if we run out of texture space
     allow_images: bool,
@@ -298,17 +377,23 @@ impl TermWindow {
 
                 let mux_window_id = self.mux_window_id;
 
-                let can_close = mux
-                    .get_window(mux_window_id)
-                    .map_or(false, |w| w.can_close_without_prompting());
-                if can_close {
+                let panes: Vec<Rc<dyn Pane>> = match mux.get_window(mux_window_id) {
+                    Some(w) => w
+                        .iter()
+                        .flat_map(|tab| tab.iter_panes_ignoring_zoom())
+                        .map(|pos| pos.pane)
+                        .collect(),
+                    None => vec![],
+                };
+                let processes = processes_preventing_close(&panes);
+                if processes.is_empty() {
                     mux.kill_window(self.mux_window_id);
                     window.close();
                     return;
                 }
                 let window = self.window.clone().unwrap();
                 let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
-                    confirm_close_window(term, mux_window_id, window, tab_id)
+                    confirm_close_window(processes, term, mux_window_id, window, tab_id)
                 });
                 self.assign_overlay(tab.tab_id(), overlay);
                 promise::spawn::spawn(future).detach();
@@ -322,8 +407,15 @@ impl TermWindow {
     fn focus_changed(&mut self, focused: bool, window: &Window) {
         log::trace!("Setting focus to {:?}", focused);
         self.focused = if focused { Some(Instant::now()) } else { None };
+        if focused {
+            let mux_window_id = self.mux_window_id;
+            let window = window.clone();
+            LAST_FOCUSED_WINDOW.with(|last| last.borrow_mut().replace((mux_window_id, window)));
+        }
 
         if self.focused.is_none() {
+            // We won't see the release of any keys that are held down
+            self.pending_key_up.clear();
             self.last_mouse_click = None;
             self.current_mouse_buttons.clear();
             self.is_click_to_focus = false;
@@ -350,7 +442,8 @@ impl TermWindow {
         match RenderState::new(ctx, &self.fonts, &self.render_metrics, ATLAS_SIZE) {
             Ok(gl) => {
                 log::info!(
-                    "OpenGL initialized! {} {} is_context_loss_possible={} wezterm version: {}",
+                    "OpenGL initialized! {} {} {} is_context_loss_possible={} wezterm version: {}",
+                    gl.context.get_opengl_vendor_string(),
                     gl.context.get_opengl_renderer_string(),
                     gl.context.get_opengl_version_string(),
                     gl.context.is_context_loss_possible(),
@@ -546,6 +639,11 @@ impl TermWindow {
         } else {
             0
         };
+        let tab_bar_width = if show_tab_bar {
+            Self::tab_bar_pixel_width_impl(&config, &fontconfig)? as usize
+        } else {
+            0
+        };
 
         let terminal_size = PtySize {
             rows: physical_rows as u16,
@@ -587,7 +685,8 @@ impl TermWindow {
         let padding_bottom = config.window_padding.bottom.evaluate_as_pixels(v_context) as u16;
 
         let dimensions = Dimensions {
-            pixel_width: (terminal_size.pixel_width + padding_left + padding_right) as usize,
+            pixel_width: (terminal_size.pixel_width + padding_left + padding_right) as usize
+                + tab_bar_width,
             pixel_height: ((terminal_size.rows * render_metrics.cell_size.height as u16)
                 + padding_top
                 + padding_bottom) as usize
@@ -615,6 +714,8 @@ impl TermWindow {
             config_overrides: serde_json::Value::default(),
             palette: None,
             focused: None,
+            screen_name: None,
+            ime_composition: ImeCompositionStatus::None,
             mux_window_id,
             fonts: Rc::clone(&fontconfig),
             render_metrics,
@@ -624,6 +725,11 @@ impl TermWindow {
             render_state,
             input_map: InputMap::new(&config),
             leader_is_down: None,
+            pending_key_up: vec![],
+            key_debug: None,
+            key_recording: None,
+            action_history: actionhistory::ActionHistory::default(),
+            registers: RefCell::new(HashMap::new()),
             show_tab_bar,
             show_scroll_bar: config.enable_scroll_bar,
             tab_bar: TabBarState::default(),
@@ -637,6 +743,7 @@ impl TermWindow {
             tab_state: RefCell::new(HashMap::new()),
             pane_state: RefCell::new(HashMap::new()),
             current_mouse_buttons: vec![],
+            horz_wheel_accumulator: 0,
             last_mouse_click: None,
             current_highlight: None,
             shape_cache: RefCell::new(LruCache::new(
@@ -644,17 +751,26 @@ impl TermWindow {
                 "shape_cache.miss.rate",
                 65536,
             )),
+            harfbuzz_features_configs: RefCell::new(HashMap::new()),
+            contrast_cache: RefCell::new(LruCache::new(
+                "contrast_cache.hit.rate",
+                "contrast_cache.miss.rate",
+                1024,
+            )),
             next_blink_paint: RefCell::new(Instant::now()),
             last_status_call: Instant::now(),
             last_text_blink_paint: RefCell::new(Instant::now()),
             last_text_blink_paint_rapid: RefCell::new(Instant::now()),
             event_states: HashMap::new(),
             has_animation: RefCell::new(None),
+            merged_quads: RefCell::new(0),
+            render_stats: RenderStats::default(),
             scheduled_animation: RefCell::new(None),
             allow_images: true,
             semantic_zones: HashMap::new(),
             ui_items: vec![],
             dragging: None,
+            tab_drag: tabdrag::TabDragState::default(),
             last_ui_item: None,
             is_click_to_focus: false,
         };
@@ -705,6 +821,7 @@ impl TermWindow {
             }
 
             myself.created(&window, Rc::clone(&gl))?;
+            myself.apply_tear_off(&window);
             myself.subscribe_to_pane_updates();
             myself.emit_window_event("window-config-reloaded", None);
             myself.emit_status_event();
@@ -720,7 +837,17 @@ impl TermWindow {
         window: &Window,
     ) -> anyhow::Result<bool> {
         match event {
-            WindowEvent::Destroyed => Ok(false),
+            WindowEvent::Destroyed => {
+                self.forget_drop_target();
+                let mux_window_id = self.mux_window_id;
+                LAST_FOCUSED_WINDOW.with(|last| {
+                    let mut last = last.borrow_mut();
+                    if matches!(&*last, Some((id, _)) if *id == mux_window_id) {
+                        last.take();
+                    }
+                });
+                Ok(false)
+            }
             WindowEvent::CloseRequested => {
                 self.close_requested(window);
                 Ok(true)
@@ -730,10 +857,25 @@ impl TermWindow {
                 self.config_was_reloaded();
                 Ok(true)
             }
+            WindowEvent::ScreenChanged(name) => {
+                log::debug!("Window is now on screen {}", name);
+                self.screen_name.replace(name);
+                self.emit_window_event("window-screen-changed", None);
+                Ok(true)
+            }
             WindowEvent::FocusChanged(focused) => {
                 self.focus_changed(focused, window);
                 Ok(true)
             }
+            WindowEvent::ImeComposition(status) => {
+                self.ime_composition_changed(status, window);
+                Ok(true)
+            }
+            WindowEvent::KeyboardLayoutChanged(layout) => {
+                log::debug!("Keyboard layout is now {}", layout);
+                self.emit_window_event_with_text("keyboard-layout-changed", Some(layout));
+                Ok(true)
+            }
             WindowEvent::MouseEvent(event) => {
                 self.mouse_event_impl(event, window);
                 Ok(true)
@@ -818,6 +960,43 @@ impl TermWindow {
                     .map_err(chan_err)
                     .context("send GetDimensions response")?;
             }
+            TermWindowNotif::GetGpuInfo(tx) => {
+                let info = self.render_state.as_ref().map(|gl| {
+                    GpuInfo::new(
+                        gl.context.get_opengl_vendor_string(),
+                        gl.context.get_opengl_renderer_string(),
+                        gl.context.get_opengl_version_string(),
+                    )
+                });
+                tx.try_send(info)
+                    .map_err(chan_err)
+                    .context("send GetGpuInfo response")?;
+            }
+            TermWindowNotif::GetScreenName(tx) => {
+                tx.try_send(self.screen_name.clone())
+                    .map_err(chan_err)
+                    .context("send GetScreenName response")?;
+            }
+            TermWindowNotif::GetRenderStats(tx) => {
+                tx.try_send(self.render_stats)
+                    .map_err(chan_err)
+                    .context("send GetRenderStats response")?;
+            }
+            TermWindowNotif::GetLeaderIsActive(tx) => {
+                tx.try_send(self.leader_is_active())
+                    .map_err(chan_err)
+                    .context("send GetLeaderIsActive response")?;
+            }
+            TermWindowNotif::GetActiveKeyRecording(tx) => {
+                tx.try_send(self.active_key_recording())
+                    .map_err(chan_err)
+                    .context("send GetActiveKeyRecording response")?;
+            }
+            TermWindowNotif::GetActionHistory { count, tx } => {
+                tx.try_send(self.action_history(count))
+                    .map_err(chan_err)
+                    .context("send GetActionHistory response")?;
+            }
             TermWindowNotif::GetEffectiveConfig(tx) => {
                 tx.try_send(self.config.clone())
                     .map_err(chan_err)
@@ -837,6 +1016,18 @@ impl TermWindow {
                     self.config_was_reloaded();
                 }
             }
+            TermWindowNotif::SetColorScheme(scheme) => {
+                let mut overrides = match &self.config_overrides {
+                    serde_json::Value::Object(map) => map.clone(),
+                    _ => serde_json::Map::new(),
+                };
+                overrides.insert("color_scheme".to_string(), scheme.into());
+                let overrides = serde_json::Value::Object(overrides);
+                if overrides != self.config_overrides {
+                    self.config_overrides = overrides;
+                    self.config_was_reloaded();
+                }
+            }
             TermWindowNotif::CancelOverlayForPane(pane_id) => {
                 self.cancel_overlay_for_pane(pane_id);
             }
@@ -860,6 +1051,17 @@ impl TermWindow {
                     alert: Alert::Bell,
                     pane_id,
                 } => {
+                    // Every window is notified about every bell; only
+                    // respond to those from our own panes.  Overlays are
+                    // not associated with a mux window, so we can only
+                    // rule out panes that are known to belong elsewhere.
+                    let mux = Mux::get().unwrap();
+                    if let Some((_domain, window_id, _tab_id)) = mux.resolve_pane_id(pane_id) {
+                        if window_id != self.mux_window_id {
+                            return Ok(());
+                        }
+                    }
+
                     match self.config.audible_bell {
                         AudibleBell::SystemBeep => {
                             Connection::get().expect("on main thread").beep();
@@ -872,11 +1074,20 @@ impl TermWindow {
 
                     let mut per_pane = self.pane_state(pane_id);
                     per_pane.bell_start.replace(Instant::now());
+
+                    // The flash can't be seen if the window is hidden
+                    // behind others, so ask for attention as well
+                    if self.focused.is_none() {
+                        window.request_attention();
+                    }
                     window.invalidate();
                 }
                 MuxNotification::PaneOutput(pane_id) => {
                     self.mux_pane_output_event(pane_id);
                 }
+                MuxNotification::PaneAdded(pane_id) => {
+                    self.apply_config_overrides_to_pane(pane_id);
+                }
                 MuxNotification::WindowInvalidated(_) => {
                     window.invalidate();
                 }
@@ -885,6 +1096,38 @@ impl TermWindow {
                         window.close();
                     }
                 }
+                MuxNotification::PaneMoved {
+                    pane_id,
+                    old_tab_id,
+                    new_tab_id,
+                    ..
+                } => {
+                    self.emit_pane_moved_event(pane_id, old_tab_id, new_tab_id);
+                    window.invalidate();
+                }
+                MuxNotification::PaneMonitor {
+                    pane_id,
+                    kind,
+                    elapsed,
+                } => match kind {
+                    MonitorKind::Silence => {
+                        self.emit_pane_monitor_event("pane-silence", pane_id, elapsed)
+                    }
+                    MonitorKind::Activity => {
+                        self.emit_pane_monitor_event("pane-activity", pane_id, elapsed)
+                    }
+                    MonitorKind::Idle => self.show_idle_notification(pane_id, elapsed),
+                },
+                MuxNotification::DomainStateChanged(domain_id) => {
+                    self.emit_domain_state_changed_event(domain_id);
+                    window.invalidate();
+                }
+                MuxNotification::DomainReconnected {
+                    domain_id,
+                    attempts,
+                } => {
+                    self.emit_domain_reconnected_event(domain_id, attempts);
+                }
                 _ => {}
             },
             TermWindowNotif::EmitStatusUpdate => {
@@ -941,6 +1184,22 @@ impl TermWindow {
         tab.contains_pane(pane_id)
     }
 
+    /// Newly spawned panes are configured from the global configuration;
+    /// if this window has overrides, apply them to the pane so that it
+    /// matches the rest of the window
+    fn apply_config_overrides_to_pane(&mut self, pane_id: PaneId) {
+        if self.config_overrides.is_null() {
+            return;
+        }
+        let mux = Mux::get().unwrap();
+        if let Some(pane) = mux.get_pane(pane_id) {
+            pane.set_config(Arc::new(TermConfig::with_config(self.config.clone())));
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+    }
+
     fn mux_pane_output_event(&mut self, pane_id: PaneId) {
         metrics::histogram!("mux.pane_output_event.rate", 1.);
         if self.is_pane_visible(pane_id) {
@@ -948,6 +1207,34 @@ impl TermWindow {
                 win.invalidate();
             }
         }
+        self.note_unseen_output(pane_id);
+    }
+
+    /// If the pane belongs to this window but is not its active pane,
+    /// flag it as having unseen output so that the tab bar can show
+    /// that there is activity.  The flag is cleared by update_title_impl
+    /// when the pane becomes active.
+    fn note_unseen_output(&mut self, pane_id: PaneId) {
+        let mux = Mux::get().unwrap();
+        if mux.has_unseen_output(pane_id) {
+            return;
+        }
+        let window = match mux.get_window(self.mux_window_id) {
+            Some(window) => window,
+            None => return,
+        };
+        let is_active = window
+            .get_active()
+            .and_then(|tab| tab.get_active_pane())
+            .map(|pane| pane.pane_id() == pane_id)
+            .unwrap_or(false);
+        if is_active || !window.iter().any(|tab| tab.contains_pane(pane_id)) {
+            return;
+        }
+        drop(window);
+        if mux.set_unseen_output(pane_id, true) {
+            self.update_title_impl();
+        }
     }
 
     fn mux_pane_output_event_callback(
@@ -983,8 +1270,11 @@ impl TermWindow {
             MuxNotification::PaneAdded(pane_id) => {
                 // If some other client spawns a pane inside this window, this
                 // gives us an opportunity to attach it to the clipboard.
+                // It also needs to pick up any config overrides for
+                // this window, which is done by the window event handler.
                 let mux = Mux::get().expect("mux is calling us");
                 if let Some(mux_window) = mux.get_window(mux_window_id) {
+                    let mut found = false;
                     for tab in mux_window.iter() {
                         for pos in tab.iter_panes() {
                             if pos.pane.pane_id() == pane_id {
@@ -993,11 +1283,14 @@ impl TermWindow {
                                         window: window.clone(),
                                     });
                                 pos.pane.set_clipboard(&clipboard);
+                                found = true;
                                 break;
                             }
                         }
                     }
-                    return true;
+                    if !found {
+                        return true;
+                    }
                 } else {
                     // Something inconsistent: cancel subscription
                     return false;
@@ -1009,6 +1302,26 @@ impl TermWindow {
                     return true;
                 }
             }
+            MuxNotification::PaneRemoved(pane_id) => {
+                crate::scripting::pane::forget_pane(pane_id);
+                return true;
+            }
+            MuxNotification::PaneMoved { old_window_id, .. } => {
+                // The window that gave up the pane reports the move
+                if old_window_id != mux_window_id {
+                    return true;
+                }
+            }
+            MuxNotification::PaneMonitor { pane_id, .. } => {
+                // Only the window that holds the pane reports on it
+                let mux = Mux::get().expect("mux is calling us");
+                match mux.resolve_pane_id(pane_id) {
+                    Some((_domain, window_id, _tab_id)) if window_id == mux_window_id => {}
+                    _ => return true,
+                }
+            }
+            MuxNotification::DomainStateChanged(_) => {}
+            MuxNotification::DomainReconnected { .. } => {}
             _ => return true,
         }
 
@@ -1031,6 +1344,46 @@ impl TermWindow {
         self.emit_window_event("update-right-status", None);
     }
 
+    /// Returns true if the leader key has been pressed and
+    /// has not yet expired
+    pub fn leader_is_active(&self) -> bool {
+        matches!(self.leader_is_down, Some(expiry) if expiry > Instant::now())
+    }
+
+    /// Activates the leader key for the specified duration
+    fn activate_leader(&mut self, duration: Duration) {
+        let expiry = Instant::now() + duration;
+        self.leader_is_down.replace(expiry);
+        self.leader_changed();
+
+        // Arrange to notice when it expires, so that the status
+        // can be updated without waiting for the next keypress
+        if let Some(window) = self.window.clone() {
+            promise::spawn::spawn(async move {
+                Timer::at(expiry).await;
+                window.notify(TermWindowNotif::Apply(Box::new(|tw| {
+                    if tw.leader_is_down.is_some() && !tw.leader_is_active() {
+                        tw.cancel_leader();
+                    }
+                })));
+            })
+            .detach();
+        }
+    }
+
+    /// Deactivates the leader key, if it was active
+    fn cancel_leader(&mut self) {
+        if self.leader_is_down.take().is_some() {
+            self.leader_changed();
+        }
+    }
+
+    fn leader_changed(&mut self) {
+        self.emit_window_event("leader-changed", None);
+        self.emit_status_event();
+        self.update_title_impl();
+    }
+
     fn schedule_window_event(&mut self, name: &str, pane_id: Option<PaneId>) {
         let window = GuiWin::new(self);
         let pane = match pane_id {
@@ -1186,11 +1539,26 @@ impl TermWindow {
     fn palette(&mut self) -> &ColorPalette {
         if self.palette.is_none() {
             self.palette
-                .replace(config::TermConfig::new().color_palette());
+                .replace(TermConfig::with_config(self.config.clone()).color_palette());
         }
         self.palette.as_ref().unwrap()
     }
 
+    /// Returns the config that should be used to render the text
+    /// in the pane, taking into account any harfbuzz_features
+    /// override that applies to it
+    pub fn config_for_pane_text(&self, pane: &Rc<dyn Pane>) -> ConfigHandle {
+        match crate::scripting::pane::harfbuzz_features_override(pane, &self.config) {
+            Some(features) => self
+                .harfbuzz_features_configs
+                .borrow_mut()
+                .entry(features)
+                .or_insert_with_key(|features| self.config.with_harfbuzz_features(features))
+                .clone(),
+            None => self.config.clone(),
+        }
+    }
+
     pub fn config_was_reloaded(&mut self) {
         log::debug!(
             "config was reloaded, overrides: {:?}",
@@ -1209,6 +1577,7 @@ impl TermWindow {
         };
         self.config = config.clone();
         self.palette.take();
+        self.harfbuzz_features_configs.borrow_mut().clear();
 
         self.window_background =
             reload_background_image(&config, &self.window_background, &self.dimensions);
@@ -1223,11 +1592,14 @@ impl TermWindow {
         } else {
             self.show_tab_bar = config.enable_tab_bar;
         }
+        drop(window);
 
         self.show_scroll_bar = config.enable_scroll_bar;
         self.shape_cache.borrow_mut().clear();
+        self.contrast_cache.borrow_mut().clear();
         self.input_map = InputMap::new(&config);
         self.leader_is_down = None;
+        self.pending_key_up.clear();
         let dimensions = self.dimensions;
 
         if let Err(err) = self.fonts.config_changed(&config) {
@@ -1243,6 +1615,8 @@ impl TermWindow {
                 }
             }
         };
+        // The tab bar colors may have changed
+        self.update_title_impl();
 
         if let Some(window) = self.window.as_ref().map(|w| w.clone()) {
             self.apply_scale_change(&dimensions, self.fonts.get_font_scale(), &window);
@@ -1284,6 +1658,35 @@ impl TermWindow {
         self.update_title_impl();
     }
 
+    /// Returns the status to show in the tab bar.
+    /// If there is no update-right-status handler to show something
+    /// else, the fancy tab bar shows a badge while the leader is active
+    /// or while keys are being recorded.
+    fn effective_right_status(&self) -> String {
+        if self.right_status.is_empty()
+            && self.config.use_fancy_tab_bar
+            && (self.leader_is_active() || self.key_recording.is_some())
+        {
+            let has_handler = config::run_immediate_with_lua_config(|lua| {
+                Ok(lua
+                    .map(|lua| config::lua::has_event_handlers(&lua, "update-right-status"))
+                    .unwrap_or(false))
+            })
+            .unwrap_or(false);
+            if !has_handler {
+                let mut status = String::new();
+                if let Some(register) = self.active_key_recording() {
+                    status.push_str(&format!("\x1b[7m REC {} \x1b[0m ", register));
+                }
+                if self.leader_is_active() {
+                    status.push_str("\x1b[7m LEADER \x1b[0m ");
+                }
+                return status;
+            }
+        }
+        self.right_status.clone()
+    }
+
     /// Called by window:set_right_status after the status has
     /// been updated; let's update the bar
     pub fn update_title_post_status(&mut self) {
@@ -1296,6 +1699,9 @@ impl TermWindow {
             Some(window) => window,
             _ => return,
         };
+        if let Some(pane) = window.get_active().and_then(|tab| tab.get_active_pane()) {
+            mux.set_unseen_output(pane.pane_id(), false);
+        }
         let tabs = self.get_tab_information();
         let panes = self.get_pane_information();
         let active_tab = tabs.iter().find(|t| t.is_active).cloned();
@@ -1313,7 +1719,7 @@ impl TermWindow {
             .bottom
             .evaluate_as_pixels(v_context) as u16;
 
-        let tab_bar_y = if self.config.tab_bar_at_bottom {
+        let tab_bar_y = if self.config.resolved_tab_bar_position() == TabBarPosition::Bottom {
             let avail_height = self
                 .dimensions
                 .pixel_height
@@ -1335,9 +1741,9 @@ impl TermWindow {
             },
             &tabs,
             &panes,
-            self.config.colors.as_ref().and_then(|c| c.tab_bar.as_ref()),
+            Some(&self.config.tab_bar_colors()),
             &self.config,
-            &self.right_status,
+            &self.effective_right_status(),
         );
         if new_tab_bar != self.tab_bar {
             self.tab_bar = new_tab_bar;
@@ -1444,22 +1850,38 @@ impl TermWindow {
         }
     }
 
-    fn update_text_cursor(&mut self, pane: &Rc<dyn Pane>) {
-        let cursor = pane.get_cursor_position();
+    /// Tells the input method where the cursor is, so that its
+    /// candidate window appears alongside it.  This is called as
+    /// part of painting, so that it tracks changes to the position
+    /// of the pane as well as to the cursor within it.
+    fn update_text_cursor(&mut self, pos: &PositionedPane) {
+        let cursor = pos.pane.get_cursor_position();
+        let dims = pos.pane.get_dimensions();
+        let top = self
+            .get_viewport(pos.pane.pane_id())
+            .unwrap_or(dims.physical_top);
+
+        let (padding_left, padding_top) = self.padding_left_top();
+        let padding_left = padding_left + self.tab_bar_width_at(TabBarPosition::Left);
+        let tab_bar_height = if self.show_tab_bar
+            && self.config.resolved_tab_bar_position() != TabBarPosition::Bottom
+        {
+            self.tab_bar_pixel_height().unwrap_or(0.)
+        } else {
+            0.
+        };
+
+        let cell_width = self.render_metrics.cell_size.width;
+        let cell_height = self.render_metrics.cell_size.height;
+        let r = Rect::new(
+            Point::new(
+                ((cursor.x.max(0) + pos.left) as isize * cell_width).add(padding_left as isize),
+                (((cursor.y - top).max(0) + pos.top as isize) * cell_height)
+                    .add((padding_top + tab_bar_height) as isize),
+            ),
+            self.render_metrics.cell_size,
+        );
         if let Some(win) = self.window.as_ref() {
-            let top = pane.get_dimensions().physical_top + if self.show_tab_bar { -1 } else { 0 };
-
-            let (padding_left, padding_top) = self.padding_left_top();
-
-            let r = Rect::new(
-                Point::new(
-                    (cursor.x.max(0) as isize * self.render_metrics.cell_size.width)
-                        .add(padding_left as isize),
-                    ((cursor.y - top).max(0) as isize * self.render_metrics.cell_size.height)
-                        .add(padding_top as isize),
-                ),
-                self.render_metrics.cell_size,
-            );
             win.set_text_cursor_position(r);
         }
     }
@@ -1552,6 +1974,12 @@ impl TermWindow {
     }
 
     fn show_debug_overlay(&mut self) {
+        self.show_debug_overlay_at(None)
+    }
+
+    /// Opens the debug overlay, browsing the log with the cursor on
+    /// the entry with sequence number `seq`, if given
+    pub fn show_debug_overlay_at(&mut self, seq: Option<u64>) {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
             Some(tab) => tab,
@@ -1559,9 +1987,26 @@ impl TermWindow {
         };
 
         let gui_win = GuiWin::new(self);
+        let domain_id = tab.get_active_pane().map(|pane| pane.domain_id());
 
         let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
-            crate::overlay::show_debug_overlay(term, gui_win)
+            crate::overlay::show_debug_overlay(term, gui_win, domain_id, seq)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_key_bindings(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let entries = crate::overlay::build_key_binding_entries(&self.input_map);
+
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            crate::overlay::list_key_bindings(term, entries)
         });
         self.assign_overlay(tab.tab_id(), overlay);
         promise::spawn::spawn(future).detach();
@@ -1603,6 +2048,36 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    fn show_domain_status(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| domain_status(term));
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn detach_spawn_tab_domain(&self, domain: &SpawnTabDomain) -> anyhow::Result<()> {
+        let mux = Mux::get().unwrap();
+        let domain = match domain {
+            SpawnTabDomain::DefaultDomain => mux.default_domain(),
+            SpawnTabDomain::CurrentPaneDomain => {
+                let pane = self
+                    .get_active_pane_no_overlay()
+                    .ok_or_else(|| anyhow!("window has no active pane"))?;
+                mux.get_domain(pane.domain_id())
+                    .ok_or_else(|| anyhow!("current pane has unresolvable domain id"))?
+            }
+            SpawnTabDomain::DomainName(name) => mux
+                .get_domain_by_name(name)
+                .ok_or_else(|| anyhow!("invalid domain {}", name))?,
+        };
+        detach_domain(domain.domain_id())
+    }
+
     fn show_launcher(&mut self) {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1649,6 +2124,10 @@ impl TermWindow {
             .get_active_pane()
             .expect("tab has no panes!")
             .domain_id();
+        let extra_entries = match self.get_active_pane_no_overlay() {
+            Some(pane) => self.populate_launch_menu(&pane),
+            None => vec![],
+        };
         let size = self.terminal_size;
         let term_config = Arc::new(TermConfig::with_config(self.config.clone()));
 
@@ -1659,6 +2138,7 @@ impl TermWindow {
                 term,
                 mux_window_id,
                 domains,
+                extra_entries,
                 clipboard,
                 size,
                 term_config,
@@ -1668,6 +2148,110 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    /// Calls the `populate-launch-menu` event, which can return
+    /// additional entries for the launcher
+    fn populate_launch_menu(&mut self, pane: &Rc<dyn Pane>) -> Vec<LaunchMenuItem> {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        match config::run_immediate_with_lua_config(|lua| {
+            let mut entries = vec![];
+            if let Some(lua) = lua {
+                let v = config::lua::emit_sync_callback(
+                    &*lua,
+                    ("populate-launch-menu".to_string(), (window, pane)),
+                )?;
+                if let mlua::Value::Table(_) = v {
+                    entries = Vec::<LaunchMenuItem>::from_lua(v, &*lua)?;
+                }
+            }
+            Ok(entries)
+        }) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::warn!("populate-launch-menu: {}", err);
+                vec![]
+            }
+        }
+    }
+
+    /// Calls the `augment-command-palette` event, which can return
+    /// additional entries for the command palette
+    fn augment_command_palette(&mut self, pane: &Rc<dyn Pane>) -> Vec<PaletteEntry> {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+        match config::run_immediate_with_lua_config(|lua| {
+            let mut entries = vec![];
+            if let Some(lua) = lua {
+                let v = config::lua::emit_sync_callback(
+                    &*lua,
+                    ("augment-command-palette".to_string(), (window, pane)),
+                )?;
+                if let mlua::Value::Table(tbl) = v {
+                    for item in tbl.sequence_values::<mlua::Table>() {
+                        let item = item?;
+                        entries.push(PaletteEntry {
+                            brief: item.get("brief")?,
+                            action: KeyAssignment::from_lua(
+                                item.get::<_, mlua::Value>("action")?,
+                                &*lua,
+                            )?,
+                            keys: None,
+                        });
+                    }
+                }
+            }
+            Ok(entries)
+        }) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::warn!("augment-command-palette: {}", err);
+                vec![]
+            }
+        }
+    }
+
+    fn show_command_palette(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        // The chosen action is performed against the pane that was
+        // active when the palette was opened
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+
+        let commands = self.augment_command_palette(&pane);
+        let entries = build_entries(&self.input_map, commands);
+
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            command_palette(term, entries)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+
+        let window = self.window.clone().unwrap();
+        promise::spawn::spawn(async move {
+            // The overlay has been scheduled for removal by the time
+            // that the future resolves, so the action won't be
+            // confused by its presence
+            if let Some(assignment) = future.await? {
+                window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    let mux = Mux::get().unwrap();
+                    if let Some(pane) = mux.get_pane(pane_id) {
+                        if let Err(err) = term_window.perform_user_assignment(&pane, &assignment) {
+                            log::error!("{:#}", err);
+                        }
+                    }
+                })));
+            }
+            anyhow::Result::<()>::Ok(())
+        })
+        .detach();
+    }
+
     /// Returns the Prompt semantic zones
     fn get_semantic_zones(&mut self, pane: &Rc<dyn Pane>) -> &[SemanticZone] {
         let mut cache = self
@@ -1816,7 +2400,7 @@ impl TermWindow {
             }
             CopyTo(dest) => {
                 let text = self.selection_text(pane);
-                self.copy_to_clipboard(*dest, text);
+                self.copy_to_clipboard(dest.clone(), text);
             }
             Paste => {
                 self.paste_from_clipboard(pane, ClipboardPasteSource::Clipboard);
@@ -1825,7 +2409,7 @@ impl TermWindow {
                 self.paste_from_clipboard(pane, ClipboardPasteSource::PrimarySelection);
             }
             PasteFrom(source) => {
-                self.paste_from_clipboard(pane, *source);
+                self.paste_from_clipboard(pane, source.clone());
             }
             ActivateTabRelative(n) => {
                 self.activate_tab_relative(*n)?;
@@ -1854,12 +2438,16 @@ impl TermWindow {
             ActivateTab(n) => {
                 self.activate_tab(*n)?;
             }
-            SendString(s) => pane.writer().write_all(s.as_bytes())?,
+            SendString(s) => {
+                pane.writer().write_all(s.as_bytes())?;
+                self.record_text(s);
+            }
             SendKey(key) => {
                 use keyevent::{window_mods_to_termwiz_mods, Key};
                 let mods = window_mods_to_termwiz_mods(key.mods);
                 if let Key::Code(key) = self.win_key_code_to_termwiz_key_code(&key.key) {
                     pane.key_down(key, mods)?;
+                    self.record_key(key, mods);
                 }
             }
             Hide => {
@@ -1878,6 +2466,12 @@ impl TermWindow {
             ReloadConfiguration => config::reload(),
             MoveTab(n) => self.move_tab(*n)?,
             MoveTabRelative(n) => self.move_tab_relative(*n)?,
+            MoveTabToNewWindow => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    self.move_tab_to_window(tab.tab_id(), None, None);
+                }
+            }
             ScrollByPage(n) => self.scroll_by_page(*n)?,
             ScrollByLine(n) => self.scroll_by_line(*n)?,
             ScrollToPrompt(n) => self.scroll_to_prompt(*n)?,
@@ -1885,7 +2479,38 @@ impl TermWindow {
             ScrollToBottom => self.scroll_to_bottom(pane),
             ShowTabNavigator => self.show_tab_navigator(),
             ShowDebugOverlay => self.show_debug_overlay(),
+            ShowKeyDebugOverlay => self.show_key_debug_overlay(),
+            ListKeyBindings => self.show_key_bindings(),
             ShowLauncher => self.show_launcher(),
+            ShowDomainStatus => self.show_domain_status(),
+            AttachDomain(name) => {
+                let mux = Mux::get().unwrap();
+                let domain = mux
+                    .get_domain_by_name(name)
+                    .ok_or_else(|| anyhow!("invalid domain {}", name))?;
+                attach_domain(domain.domain_id())?;
+            }
+            DetachDomain(domain) => self.detach_spawn_tab_domain(domain)?,
+            ActivateCommandPalette => self.show_command_palette(),
+            StartKeyRecording(register) => self.start_key_recording(register),
+            StopKeyRecording => self.stop_key_recording(),
+            ReplayKeyRecording(register) => self.replay_key_recording(pane, register)?,
+            RepeatLastAssignment => self.repeat_last_assignment(pane)?,
+            MoveBackwardSemanticZone(zone_type) => {
+                if let Some(copy) = pane.downcast_ref::<CopyOverlay>() {
+                    copy.move_backward_semantic_zone(*zone_type);
+                }
+            }
+            MoveForwardSemanticZone(zone_type) => {
+                if let Some(copy) = pane.downcast_ref::<CopyOverlay>() {
+                    copy.move_forward_semantic_zone(*zone_type);
+                }
+            }
+            SelectSemanticZone(zone_type) => {
+                if let Some(copy) = pane.downcast_ref::<CopyOverlay>() {
+                    copy.select_semantic_zone(*zone_type);
+                }
+            }
             HideApplication => {
                 let con = Connection::get().expect("call on gui thread");
                 con.hide_application();
@@ -1930,7 +2555,7 @@ impl TermWindow {
             CompleteSelectionOrOpenLinkAtMouseCursor(dest) => {
                 let text = self.selection_text(pane);
                 if !text.is_empty() {
-                    self.copy_to_clipboard(*dest, text);
+                    self.copy_to_clipboard(dest.clone(), text);
                     let window = self.window.as_ref().unwrap();
                     window.invalidate();
                 } else {
@@ -1940,7 +2565,7 @@ impl TermWindow {
             CompleteSelection(dest) => {
                 let text = self.selection_text(pane);
                 if !text.is_empty() {
-                    self.copy_to_clipboard(*dest, text);
+                    self.copy_to_clipboard(dest.clone(), text);
                     let window = self.window.as_ref().unwrap();
                     window.invalidate();
                 }
@@ -1994,6 +2619,19 @@ impl TermWindow {
                     tab.activate_pane_direction(*direction);
                 }
             }
+            ActivateLastPane => {
+                let mux = Mux::get().unwrap();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(()),
+                };
+
+                let tab_id = tab.tab_id();
+
+                if self.tab_state(tab_id).overlay.is_none() {
+                    tab.activate_last_pane();
+                }
+            }
             TogglePaneZoomState => {
                 let mux = Mux::get().unwrap();
                 let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -2002,10 +2640,110 @@ impl TermWindow {
                 };
                 tab.toggle_zoom();
             }
+            BalancePanes => {
+                let mux = Mux::get().unwrap();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(()),
+                };
+
+                if self.tab_state(tab.tab_id()).overlay.is_none() {
+                    tab.balance_panes();
+                }
+            }
+            ApplyPaneLayout(layout) => {
+                let mux = Mux::get().unwrap();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(()),
+                };
+
+                if self.tab_state(tab.tab_id()).overlay.is_none() {
+                    tab.apply_pane_layout(*layout);
+                }
+            }
+            RotatePanes(direction) => {
+                let mux = Mux::get().unwrap();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(()),
+                };
+
+                if self.tab_state(tab.tab_id()).overlay.is_none() {
+                    tab.rotate_panes(*direction);
+                }
+            }
+            SwapActivePaneWithIndex(pane_index) => {
+                let mux = Mux::get().unwrap();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(()),
+                };
+
+                if self.tab_state(tab.tab_id()).overlay.is_none() {
+                    tab.swap_active_with_index(*pane_index);
+                }
+            }
+            ToggleInputBroadcast { scope } => self.toggle_input_broadcast(*scope),
+            MovePaneToNewTab => self.move_pane_to_new_tab(Some(self.mux_window_id)),
+            MovePaneToNewWindow => self.move_pane_to_new_tab(None),
+            MovePane { tab_id, direction } => {
+                let pane_id = match self.get_active_pane_no_overlay() {
+                    Some(pane) => pane.pane_id(),
+                    None => return Ok(()),
+                };
+                let tab_id = *tab_id;
+                let direction = *direction;
+                promise::spawn::spawn(async move {
+                    let mux = Mux::get().unwrap();
+                    if let Err(err) = mux.move_pane_to_tab(pane_id, tab_id, direction).await {
+                        log::error!(
+                            "Failed to move pane {} to tab {}: {:#}",
+                            pane_id,
+                            tab_id,
+                            err
+                        );
+                    }
+                })
+                .detach();
+            }
         };
         Ok(())
     }
 
+    /// Moves the active pane into a new tab in the specified window,
+    /// or into a new window if `window_id` is None
+    fn move_pane_to_new_tab(&self, window_id: Option<MuxWindowId>) {
+        let pane_id = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane.pane_id(),
+            None => return,
+        };
+        promise::spawn::spawn(async move {
+            let mux = Mux::get().unwrap();
+            if let Err(err) = mux.move_pane_to_new_tab(pane_id, window_id).await {
+                log::error!("Failed to move pane {} to a new tab: {:#}", pane_id, err);
+            }
+        })
+        .detach();
+    }
+
+    /// Moves the tab into the specified window, placing it at `tab_idx`,
+    /// or into a new window if `window_id` is None
+    pub fn move_tab_to_window(
+        &self,
+        tab_id: TabId,
+        window_id: Option<MuxWindowId>,
+        tab_idx: Option<usize>,
+    ) {
+        promise::spawn::spawn(async move {
+            let mux = Mux::get().unwrap();
+            if let Err(err) = mux.move_tab_to_window(tab_id, window_id, tab_idx).await {
+                log::error!("Failed to move tab {} to another window: {:#}", tab_id, err);
+            }
+        })
+        .detach();
+    }
+
     fn do_open_link_at_mouse_cursor(&self, pane: &Rc<dyn Pane>) {
         // They clicked on a link, so let's open it!
         // We need to ensure that we spawn the `open` call outside of the context
@@ -2015,41 +2753,252 @@ impl TermWindow {
         // perform below; here we allow the user to define an `open-uri` event
         // handler that can bypass the normal `open::that` functionality.
         if let Some(link) = self.current_highlight.as_ref().cloned() {
-            let window = GuiWin::new(self);
-            let pane = PaneObject::new(pane);
-
-            async fn open_uri(
-                lua: Option<Rc<mlua::Lua>>,
-                window: GuiWin,
-                pane: PaneObject,
-                link: String,
-            ) -> anyhow::Result<()> {
-                let default_click = match lua {
-                    Some(lua) => {
-                        let args = lua.pack_multi((window, pane, link.clone()))?;
-                        config::lua::emit_event(&lua, ("open-uri".to_string(), args))
-                            .await
-                            .map_err(|e| {
-                                log::error!("while processing open-uri event: {:#}", e);
-                                e
-                            })?
-                    }
-                    None => true,
-                };
-                if default_click {
-                    log::info!("clicking {}", link);
-                    if let Err(err) = open::that(&link) {
-                        log::error!("failed to open {}: {:?}", link, err);
-                    }
+            self.open_uri(pane, link.uri().to_string());
+        }
+    }
+
+    fn ime_composition_changed(&mut self, status: ImeCompositionStatus, window: &Window) {
+        if self.ime_composition == status {
+            return;
+        }
+        self.ime_composition = status;
+        window.invalidate();
+
+        let text = match &self.ime_composition {
+            ImeCompositionStatus::None => None,
+            ImeCompositionStatus::Composing(text) => Some(text.clone()),
+        };
+        self.emit_window_event_with_text("ime-composition", text);
+    }
+
+    /// Emits the named event with the window, the active pane and
+    /// `text` as its parameters
+    fn emit_window_event_with_text(&self, name: &str, text: Option<String>) {
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(&pane);
+        let name = name.to_string();
+
+        async fn emit(
+            lua: Option<Rc<mlua::Lua>>,
+            name: String,
+            window: GuiWin,
+            pane: PaneObject,
+            text: Option<String>,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, text))?;
+                if let Err(err) = config::lua::emit_event(&lua, (name.clone(), args)).await {
+                    log::error!("while processing {} event: {:#}", name, err);
                 }
-                Ok(())
             }
+            Ok(())
+        }
 
-            promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
-                open_uri(lua, window, pane, link.uri().to_string())
-            }))
-            .detach();
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            emit(lua, name, window, pane, text)
+        }))
+        .detach();
+    }
+
+    /// Emits the `pane-moved` event with the window, the pane that
+    /// was moved and the ids of the tabs that it was moved between
+    fn emit_pane_moved_event(&self, pane_id: PaneId, old_tab_id: TabId, new_tab_id: TabId) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(&pane);
+
+        async fn emit(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            old_tab_id: TabId,
+            new_tab_id: TabId,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, old_tab_id, new_tab_id))?;
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("pane-moved".to_string(), args)).await
+                {
+                    log::error!("while processing pane-moved event: {:#}", err);
+                }
+            }
+            Ok(())
         }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            emit(lua, window, pane, old_tab_id, new_tab_id)
+        }))
+        .detach();
+    }
+
+    /// Emits `domain-state-changed` with the window and the domain
+    fn emit_domain_state_changed_event(&self, domain_id: DomainId) {
+        let mux = Mux::get().unwrap();
+        let domain = match mux.get_domain(domain_id) {
+            Some(domain) => domain,
+            None => return,
+        };
+        let window = GuiWin::new(self);
+        let domain = DomainObject::new(&domain);
+
+        async fn emit(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            domain: DomainObject,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, domain))?;
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("domain-state-changed".to_string(), args)).await
+                {
+                    log::error!("while processing domain-state-changed event: {:#}", err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            emit(lua, window, domain)
+        }))
+        .detach();
+    }
+
+    fn emit_domain_reconnected_event(&self, domain_id: DomainId, attempts: usize) {
+        let mux = Mux::get().unwrap();
+        let domain = match mux.get_domain(domain_id) {
+            Some(domain) => domain,
+            None => return,
+        };
+        let window = GuiWin::new(self);
+        let domain = DomainObject::new(&domain);
+
+        async fn emit(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            domain: DomainObject,
+            attempts: usize,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, domain, attempts))?;
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("domain-reconnected".to_string(), args)).await
+                {
+                    log::error!("while processing domain-reconnected event: {:#}", err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            emit(lua, window, domain, attempts)
+        }))
+        .detach();
+    }
+
+    /// Emits `pane-silence` or `pane-activity` with the window, the
+    /// pane and the number of seconds since the monitor was armed
+    fn emit_pane_monitor_event(&self, name: &'static str, pane_id: PaneId, elapsed: Duration) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(&pane);
+        let elapsed = elapsed.as_secs_f64();
+
+        async fn emit(
+            lua: Option<Rc<mlua::Lua>>,
+            name: &'static str,
+            window: GuiWin,
+            pane: PaneObject,
+            elapsed: f64,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, elapsed))?;
+                if let Err(err) = config::lua::emit_event(&lua, (name.to_string(), args)).await {
+                    log::error!("while processing {} event: {:#}", name, err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            emit(lua, name, window, pane, elapsed)
+        }))
+        .detach();
+    }
+
+    /// Raises the toast for `notify_when_idle_seconds`, unless the
+    /// user is already looking at the pane
+    fn show_idle_notification(&self, pane_id: PaneId, elapsed: Duration) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let is_active = self
+            .get_active_pane_no_overlay()
+            .map(|active| active.pane_id() == pane_id)
+            .unwrap_or(false);
+        if self.focused.is_some() && is_active {
+            return;
+        }
+        ToastNotification {
+            title: pane.get_title(),
+            message: format!("No output for {} seconds", elapsed.as_secs()),
+            url: None,
+            timeout: None,
+            on_click: None,
+        }
+        .show();
+    }
+
+    /// Opens the uri, unless an `open-uri` event handler chooses
+    /// to handle it instead
+    pub fn open_uri(&self, pane: &Rc<dyn Pane>, uri: String) {
+        let window = GuiWin::new(self);
+        let pane = PaneObject::new(pane);
+
+        async fn open_uri(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            link: String,
+        ) -> anyhow::Result<()> {
+            let default_click = match lua {
+                Some(lua) => {
+                    let args = lua.pack_multi((window, pane, link.clone()))?;
+                    config::lua::emit_event(&lua, ("open-uri".to_string(), args))
+                        .await
+                        .map_err(|e| {
+                            log::error!("while processing open-uri event: {:#}", e);
+                            e
+                        })?
+                }
+                None => true,
+            };
+            if default_click {
+                log::info!("clicking {}", link);
+                if let Err(err) = open::that(&link) {
+                    log::error!("failed to open {}: {:?}", link, err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            open_uri(lua, window, pane, uri)
+        }))
+        .detach();
     }
     fn close_current_pane(&mut self, confirm: bool) {
         let mux_window_id = self.mux_window_id;
@@ -2064,10 +3013,15 @@ impl TermWindow {
         };
 
         let pane_id = pane.pane_id();
-        if confirm && !pane.can_close_without_prompting() {
+        let processes = if confirm {
+            processes_preventing_close(&[Rc::clone(&pane)])
+        } else {
+            vec![]
+        };
+        if !processes.is_empty() {
             let window = self.window.clone().unwrap();
             let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
-                confirm_close_pane(pane_id, term, mux_window_id, window)
+                confirm_close_pane(pane_id, processes, term, mux_window_id, window)
             });
             self.assign_overlay_for_pane(pane_id, overlay);
             promise::spawn::spawn(future).detach();
@@ -2084,10 +3038,20 @@ impl TermWindow {
         };
         let tab_id = tab.tab_id();
         let mux_window_id = self.mux_window_id;
-        if confirm && !tab.can_close_without_prompting() {
+        let processes = if confirm {
+            let panes: Vec<Rc<dyn Pane>> = tab
+                .iter_panes_ignoring_zoom()
+                .into_iter()
+                .map(|pos| pos.pane)
+                .collect();
+            processes_preventing_close(&panes)
+        } else {
+            vec![]
+        };
+        if !processes.is_empty() {
             let window = self.window.clone().unwrap();
             let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
-                confirm_close_tab(tab_id, term, mux_window_id, window)
+                confirm_close_tab(tab_id, processes, term, mux_window_id, window)
             });
             self.assign_overlay(tab_id, overlay);
             promise::spawn::spawn(future).detach();
@@ -2241,6 +3205,8 @@ impl TermWindow {
             pixel_height: pos.pixel_height,
             title: pos.pane.get_title(),
             user_vars: pos.pane.copy_user_vars(),
+            progress: pos.pane.get_progress(),
+            has_unseen_output: Mux::get().unwrap().has_unseen_output(pos.pane.pane_id()),
         }
     }
 
@@ -2257,15 +3223,29 @@ impl TermWindow {
             .enumerate()
             .map(|(idx, tab)| {
                 let panes = self.get_pos_panes_for_tab(tab);
+                let active_pane = panes
+                    .iter()
+                    .find(|p| p.is_active)
+                    .map(|p| self.pos_pane_to_pane_info(p));
 
                 TabInformation {
                     tab_index: idx,
                     tab_id: tab.tab_id(),
                     is_active: tab_index == idx,
-                    active_pane: panes
+                    progress: active_pane
+                        .as_ref()
+                        .map(|p| p.progress)
+                        .unwrap_or(Progress::None),
+                    has_unseen_output: panes
                         .iter()
-                        .find(|p| p.is_active)
-                        .map(|p| self.pos_pane_to_pane_info(p)),
+                        .any(|p| mux.has_unseen_output(p.pane.pane_id())),
+                    is_broadcasting: active_pane
+                        .as_ref()
+                        .map(|p| mux.is_input_broadcast_target(p.pane_id))
+                        .unwrap_or(false),
+                    active_pane_zoomed: active_pane.as_ref().map(|p| p.is_zoomed).unwrap_or(false),
+                    user_vars: tab.copy_user_vars(),
+                    active_pane,
                 }
             })
             .collect()
@@ -2318,14 +3298,16 @@ impl TermWindow {
 
     /// if pane_id.is_none(), removes any overlay for the specified tab.
     /// Otherwise: if the overlay is the specified pane for that tab, remove it.
-    fn cancel_overlay_for_tab(&self, tab_id: TabId, pane_id: Option<PaneId>) {
+    fn cancel_overlay_for_tab(&mut self, tab_id: TabId, pane_id: Option<PaneId>) {
         if pane_id.is_some() {
             let current = self.tab_state(tab_id).overlay.as_ref().map(|o| o.pane_id());
             if current != pane_id {
                 return;
             }
         }
-        if let Some(pane) = self.tab_state(tab_id).overlay.take() {
+        let overlay = self.tab_state(tab_id).overlay.take();
+        if let Some(pane) = overlay {
+            self.key_debug_overlay_removed(pane.pane_id());
             Mux::get().unwrap().remove_pane(pane.pane_id());
         }
         if let Some(window) = self.window.as_ref() {
@@ -2337,8 +3319,10 @@ impl TermWindow {
         window.notify(TermWindowNotif::CancelOverlayForTab { tab_id, pane_id });
     }
 
-    fn cancel_overlay_for_pane(&self, pane_id: PaneId) {
-        if let Some(pane) = self.pane_state(pane_id).overlay.take() {
+    fn cancel_overlay_for_pane(&mut self, pane_id: PaneId) {
+        let overlay = self.pane_state(pane_id).overlay.take();
+        if let Some(pane) = overlay {
+            self.key_debug_overlay_removed(pane.pane_id());
             // Ungh, when I built the CopyOverlay, its pane doesn't get
             // added to the mux and instead it reports the overlaid
             // pane id.  Take care to avoid killing ourselves off
@@ -2357,7 +3341,9 @@ impl TermWindow {
     }
 
     pub fn assign_overlay_for_pane(&mut self, pane_id: PaneId, overlay: Rc<dyn Pane>) {
-        if let Some(prior) = self.pane_state(pane_id).overlay.replace(overlay) {
+        let prior = self.pane_state(pane_id).overlay.replace(overlay);
+        if let Some(prior) = prior {
+            self.key_debug_overlay_removed(prior.pane_id());
             if pane_id != prior.pane_id() {
                 Mux::get().unwrap().remove_pane(prior.pane_id());
             }
@@ -2367,8 +3353,10 @@ impl TermWindow {
 
     pub fn assign_overlay(&mut self, tab_id: TabId, overlay: Rc<dyn Pane>) {
         let pane_id = overlay.pane_id();
-        if let Some(prior) = self.tab_state(tab_id).overlay.replace(overlay) {
+        let prior = self.tab_state(tab_id).overlay.replace(overlay);
+        if let Some(prior) = prior {
             if pane_id != prior.pane_id() {
+                self.key_debug_overlay_removed(prior.pane_id());
                 Mux::get().unwrap().remove_pane(prior.pane_id());
             }
         }
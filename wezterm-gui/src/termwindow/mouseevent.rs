@@ -5,10 +5,10 @@ use ::window::{
     MouseButtons as WMB, MouseCursor, MouseEvent, MouseEventKind as WMEK, MousePress, WindowOps,
 };
 use config::keyassignment::{MouseEventTrigger, SpawnTabDomain};
+use config::TabBarPosition;
 use mux::pane::Pane;
 use mux::tab::SplitDirection;
 use mux::Mux;
-use std::convert::TryInto;
 use std::ops::Sub;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -56,14 +56,18 @@ impl super::TermWindow {
         };
 
         self.current_mouse_event.replace(event.clone());
+        self.update_drop_target();
 
-        let first_line_offset = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
+        let first_line_offset = if self.show_tab_bar
+            && self.config.resolved_tab_bar_position() != TabBarPosition::Bottom
+        {
             self.tab_bar_pixel_height().unwrap_or(0.) as isize
         } else {
             0
         };
 
         let (padding_left, padding_top) = self.padding_left_top();
+        let padding_left = padding_left + self.tab_bar_width_at(TabBarPosition::Left);
 
         let y = (event
             .coords
@@ -93,9 +97,14 @@ impl super::TermWindow {
                     // Completed a window drag
                     return;
                 }
-                if press == &MousePress::Left && self.dragging.take().is_some() {
-                    // Completed a drag
-                    return;
+                if press == &MousePress::Left {
+                    if let Some((item, start_event)) = self.dragging.take() {
+                        if let UIItemType::TabBar(TabBarItem::Tab { .. }) = item.item_type {
+                            self.drop_tab(start_event, &event, context);
+                        }
+                        // Completed a drag
+                        return;
+                    }
                 }
             }
 
@@ -222,20 +231,24 @@ impl super::TermWindow {
         };
 
         let dims = pane.get_dimensions();
-        let current_viewport = self.get_viewport(pane.pane_id());
 
+        // The thumb moves by the same distance as the pointer has
+        // moved since the drag started.  We always compute relative
+        // to the start of the drag, rather than the prior event,
+        // so that slow drags of less than a row per event still
+        // accumulate into movement.
         let from_top = start_event.coords.y.saturating_sub(item.y as isize);
-        let effective_thumb_top = event.coords.y.saturating_sub(from_top).max(0) as usize;
+        let effective_thumb_top = event.coords.y.saturating_sub(from_top) as f32;
 
         // Convert thumb top into a row index by reversing the math
         // in ScrollHit::thumb
         let row = ScrollHit::thumb_top_to_scroll_top(
             effective_thumb_top,
             &*pane,
-            current_viewport,
             &self.dimensions,
             self.tab_bar_pixel_height().unwrap_or(0.),
-            self.config.tab_bar_at_bottom,
+            self.config.resolved_tab_bar_position() == TabBarPosition::Bottom,
+            self.min_scroll_bar_height(),
         );
         self.set_viewport(pane.pane_id(), Some(row), dims);
         context.invalidate();
@@ -258,6 +271,9 @@ impl super::TermWindow {
             UIItemType::ScrollThumb => {
                 self.drag_scroll_thumb(item, start_event, event, context);
             }
+            UIItemType::TabBar(TabBarItem::Tab { .. }) => {
+                self.drag_tab(item, start_event, event, context);
+            }
             _ => {
                 log::error!("drag not implemented for {:?}", item);
             }
@@ -274,8 +290,13 @@ impl super::TermWindow {
     ) {
         self.last_ui_item.replace(item.clone());
         match item.item_type {
-            UIItemType::TabBar(item) => {
-                self.mouse_event_tab_bar(item, event, context);
+            UIItemType::TabBar(tab_bar_item) => {
+                if let (TabBarItem::Tab { .. }, WMEK::Press(MousePress::Left)) =
+                    (&tab_bar_item, &event.kind)
+                {
+                    self.start_tab_drag(item, event.clone());
+                }
+                self.mouse_event_tab_bar(tab_bar_item, event, context);
             }
             UIItemType::AboveScrollThumb => {
                 self.mouse_event_above_scroll_thumb(item, pane, event, context);
@@ -349,7 +370,7 @@ impl super::TermWindow {
                 Some(
                     current_viewport
                         .unwrap_or(dims.physical_top)
-                        .saturating_sub(self.terminal_size.rows.try_into().unwrap()),
+                        .saturating_sub(dims.viewport_rows as StableRowIndex),
                 ),
                 dims,
             );
@@ -374,7 +395,7 @@ impl super::TermWindow {
                 Some(
                     current_viewport
                         .unwrap_or(dims.physical_top)
-                        .saturating_add(self.terminal_size.rows.try_into().unwrap()),
+                        .saturating_add(dims.viewport_rows as StableRowIndex),
                 ),
                 dims,
             );
@@ -415,6 +436,22 @@ impl super::TermWindow {
         }
     }
 
+    /// Adds `amount` to the horizontal wheel accumulator and returns the
+    /// number of whole events that it now represents; positive values
+    /// are to the left and negative values to the right.
+    fn accumulate_horz_wheel(&mut self, amount: i16) -> i32 {
+        let per_event = self.config.horizontal_wheel_ticks_per_event.max(1) as i32;
+        let amount = amount as i32;
+        if amount.signum() != self.horz_wheel_accumulator.signum() {
+            // Changing direction discards any partial movement
+            self.horz_wheel_accumulator = 0;
+        }
+        self.horz_wheel_accumulator += amount;
+        let events = self.horz_wheel_accumulator / per_event;
+        self.horz_wheel_accumulator %= per_event;
+        events
+    }
+
     pub fn mouse_event_terminal(
         &mut self,
         mut pane: Rc<dyn Pane>,
@@ -431,6 +468,16 @@ impl super::TermWindow {
             return;
         }
 
+        // Horizontal scrolling is accumulated so that high resolution
+        // touchpads don't generate an event for every tiny movement
+        let horz_wheel_events = match event.kind {
+            WMEK::HorzWheel(amount) => match self.accumulate_horz_wheel(amount) {
+                0 => return,
+                n => n,
+            },
+            _ => 0,
+        };
+
         let mut is_click_to_focus = false;
 
         for pos in self.get_panes_to_render() {
@@ -592,7 +639,15 @@ impl super::TermWindow {
                     None
                 }
             }
-            WMEK::VertWheel(_) | WMEK::HorzWheel(_) => None,
+            WMEK::HorzWheel(_) => Some(MouseEventTrigger::Down {
+                streak: 1,
+                button: if horz_wheel_events > 0 {
+                    TMB::WheelLeft(1)
+                } else {
+                    TMB::WheelRight(1)
+                },
+            }),
+            WMEK::VertWheel(_) => None,
         };
 
         if !pane.is_mouse_grabbed()
@@ -615,7 +670,9 @@ impl super::TermWindow {
                     .input_map
                     .lookup_mouse(event_trigger_type.clone(), modifiers)
                 {
-                    self.perform_key_assignment(&pane, &action).ok();
+                    for _ in 0..horz_wheel_events.abs().max(1) {
+                        self.perform_key_assignment(&pane, &action).ok();
+                    }
                     return;
                 }
             }
@@ -647,7 +704,13 @@ impl super::TermWindow {
                         TMB::WheelDown((-amount) as usize)
                     }
                 }
-                WMEK::HorzWheel(_) => TMB::None,
+                WMEK::HorzWheel(_) => {
+                    if horz_wheel_events > 0 {
+                        TMB::WheelLeft(horz_wheel_events as usize)
+                    } else {
+                        TMB::WheelRight((-horz_wheel_events) as usize)
+                    }
+                }
             },
             x,
             y,
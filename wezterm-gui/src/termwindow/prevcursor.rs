@@ -1,5 +1,7 @@
+use config::EasingFunction;
 use mux::renderable::StableCursorPosition;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use termwiz::surface::SequenceNo;
 
 #[derive(Clone)]
 pub struct PrevCursorPos {
@@ -33,3 +35,77 @@ impl PrevCursorPos {
         self.when
     }
 }
+
+/// Tracks where the cursor is drawn while it glides between cells.
+/// Positions are expressed in cells relative to the top left of
+/// the viewport of the pane.
+#[derive(Clone, Default)]
+pub struct CursorAnimation {
+    from: (f32, f32),
+    to: Option<(f32, f32)>,
+    start: Option<Instant>,
+    /// The state of the pane when the cursor was last seen;
+    /// used to detect jumps that shouldn't be animated
+    pub alt_screen: bool,
+    pub seqno: SequenceNo,
+}
+
+impl CursorAnimation {
+    fn position(&self, now: Instant, duration: Duration, easing: &EasingFunction) -> (f32, f32) {
+        let to = match self.to {
+            Some(to) => to,
+            None => return self.from,
+        };
+        let elapsed = match self.start {
+            Some(start) => now.duration_since(start),
+            None => return to,
+        };
+        if elapsed >= duration {
+            return to;
+        }
+        let progress = easing.evaluate_at_position(elapsed.as_secs_f32() / duration.as_secs_f32());
+        (
+            self.from.0 + (to.0 - self.from.0) * progress,
+            self.from.1 + (to.1 - self.from.1) * progress,
+        )
+    }
+
+    /// Returns true if `to` differs from the position that the
+    /// cursor was last heading towards
+    pub fn is_moving_to(&self, to: (f32, f32)) -> bool {
+        self.to != Some(to)
+    }
+
+    /// Takes note of the cursor position for the current frame.
+    /// If the cursor is still travelling, returns the position at which
+    /// it should be drawn.  A cursor that `jumped` moves there directly.
+    pub fn update(
+        &mut self,
+        to: (f32, f32),
+        jumped: bool,
+        duration: Duration,
+        easing: &EasingFunction,
+    ) -> Option<(f32, f32)> {
+        let now = Instant::now();
+        if self.is_moving_to(to) {
+            // Start from wherever it is currently drawn, so that
+            // a cursor that changes direction mid-flight doesn't
+            // appear to teleport
+            self.from = if jumped || self.to.is_none() {
+                to
+            } else {
+                self.position(now, duration, easing)
+            };
+            self.to = Some(to);
+            self.start = Some(now);
+        }
+
+        let current = self.position(now, duration, easing);
+        if current == to {
+            self.start = None;
+            None
+        } else {
+            Some(current)
+        }
+    }
+}
@@ -2,7 +2,9 @@ use crate::customglyph::BlockKey;
 use crate::customglyph::*;
 use crate::glium::texture::SrgbTexture2d;
 use crate::glyphcache::{CachedGlyph, GlyphCache};
+use crate::overlay::{CopyOverlay, QuickSelectOverlay, SearchOverlay};
 use crate::quad::Quad;
+use crate::scripting::guiwin::RenderStats;
 use crate::shapecache::*;
 use crate::tabbar::{TabBarItem, TabEntry};
 use crate::termwindow::{
@@ -17,26 +19,30 @@ use ::window::glium::uniforms::{
     MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction,
 };
 use ::window::glium::{uniform, BlendingFunction, LinearBlendingFactor, Surface};
-use ::window::{Point, Rect, Size, WindowOps};
+use ::window::{ImeCompositionStatus, Point, Rect, Size, WindowOps};
 use anyhow::anyhow;
 use config::{
-    ConfigHandle, DimensionContext, HsbTransform, TabBarColors, TextStyle, VisualBellTarget,
+    ConfigHandle, DimensionContext, HsbTransform, TabBarColors, TabBarPosition, TextStyle,
+    VisualBellTarget,
 };
 use euclid::num::Zero;
 use mux::pane::Pane;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection};
+use mux::Mux;
+use portable_pty::ExitStatus;
 use smol::Timer;
 use std::ops::Range;
 use std::rc::Rc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-use termwiz::cell::Blink;
+use termwiz::cell::{Blink, Underline};
 use termwiz::cellcluster::CellCluster;
-use termwiz::surface::{CursorShape, CursorVisibility};
+use termwiz::escape::osc::Progress;
+use termwiz::surface::{CursorShape, CursorVisibility, SEQ_ZERO};
 use wezterm_font::units::{IntPixelLength, PixelLength};
 use wezterm_font::{ClearShapeCache, FontMetrics, GlyphInfo, LoadedFont};
 use wezterm_term::color::{ColorAttribute, ColorPalette, RgbColor};
-use wezterm_term::{CellAttributes, Line, StableRowIndex};
+use wezterm_term::{unicode_column_width, CellAttributes, Line, StableRowIndex};
 use window::bitmaps::atlas::SpriteSlice;
 use window::bitmaps::Texture2d;
 use window::color::LinearRgba;
@@ -64,6 +70,8 @@ pub struct RenderScreenLineOpenGLParams<'a> {
     pub cursor_border_color: LinearRgba,
     pub foreground: LinearRgba,
     pub is_active: bool,
+    /// Used to dim the contents of inactive panes
+    pub hsv: PaneHsv,
 
     pub selection_fg: LinearRgba,
     pub selection_bg: LinearRgba,
@@ -72,6 +80,11 @@ pub struct RenderScreenLineOpenGLParams<'a> {
 
     pub window_is_transparent: bool,
     pub default_bg: LinearRgba,
+    /// The opacity of non-default text background colors
+    pub text_background_opacity: f32,
+    /// Adjust the foreground to have at least this contrast ratio
+    /// against the background; 1.0 disables the adjustment
+    pub minimum_contrast_ratio: f32,
 
     /// Override font resolution; useful together with
     /// the resolved title font
@@ -86,6 +99,15 @@ pub struct RenderScreenLineOpenGLParams<'a> {
     pub render_metrics: RenderMetrics,
 }
 
+/// The color transforms applied to the different elements
+/// of a pane; None leaves the colors unchanged.
+#[derive(Clone, Copy, Default)]
+pub struct PaneHsv {
+    pub text: Option<HsbTransform>,
+    pub background: Option<HsbTransform>,
+    pub image: Option<HsbTransform>,
+}
+
 pub struct ComputeCellFgBgParams<'a> {
     pub stable_line_idx: Option<StableRowIndex>,
     pub cell_idx: usize,
@@ -112,6 +134,30 @@ pub struct ComputeCellFgBgResult {
     pub cursor_shape: Option<CursorShape>,
 }
 
+/// A background rectangle that has not yet been emitted.
+/// Horizontally adjacent rectangles of the same color are
+/// accumulated here so that a run of cells can be drawn with
+/// a single quad, rather than one quad per cell.
+struct BackgroundRun {
+    rect: Rect,
+    color: LinearRgba,
+    hsv: Option<HsbTransform>,
+}
+
+/// A line decoration (underline, strikethrough) over a run of
+/// whitespace cells that has not yet been emitted.
+/// Only straight lines are merged; their sprite is the same in
+/// every column, so it can be stretched across the whole run.
+struct DecorationRun {
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+    texture: TextureRect,
+    color: LinearRgba,
+    hsv: Option<HsbTransform>,
+}
+
 /// Basic cache of computed data from prior cluster to avoid doing the same
 /// work for space separated clusters with the same style
 #[derive(Clone, Debug)]
@@ -216,6 +262,23 @@ impl super::TermWindow {
         }
         log::debug!("paint_impl before call_draw elapsed={:?}", start.elapsed());
 
+        if let Some(gl_state) = self.render_state.as_ref() {
+            let quads = gl_state
+                .vb
+                .iter()
+                .map(|vb| *vb.next_quad.borrow())
+                .sum::<usize>();
+            self.render_stats = RenderStats {
+                quads,
+                unmerged_quads: quads + *self.merged_quads.borrow(),
+            };
+            metrics::histogram!("gui.paint.quads.size", quads as f64);
+            metrics::histogram!(
+                "gui.paint.quads.unmerged.size",
+                self.render_stats.unmerged_quads as f64
+            );
+        }
+
         self.call_draw(frame).ok();
         log::debug!("paint_impl elapsed={:?}", start.elapsed());
         metrics::histogram!("gui.paint.opengl", start.elapsed());
@@ -246,6 +309,11 @@ impl super::TermWindow {
 
     fn update_next_frame_time(&self, next_due: Option<Instant>) {
         if let Some(next_due) = next_due {
+            // Don't schedule frames faster than we are prepared to paint,
+            // as some animated images have very short frame durations
+            let earliest =
+                Instant::now() + Duration::from_millis(1000 / self.config.max_fps as u64);
+            let next_due = next_due.max(earliest);
             let mut has_anim = self.has_animation.borrow_mut();
             match *has_anim {
                 None => {
@@ -312,6 +380,81 @@ impl super::TermWindow {
         None
     }
 
+    /// Computes the color transforms for the pane.  Inactive panes are
+    /// dimmed according to the inactive_pane_hsb family of options, fading
+    /// over inactive_pane_dim_time_ms when the focus changes.
+    /// The copy mode and quick select overlays are always shown undimmed
+    /// so that they remain readable.
+    fn pane_hsv(&self, pos: &PositionedPane, config: &ConfigHandle) -> PaneHsv {
+        let undimmed = pos.is_active
+            || pos.pane.downcast_ref::<CopyOverlay>().is_some()
+            || pos.pane.downcast_ref::<QuickSelectOverlay>().is_some();
+        let target = if undimmed { 0. } else { 1. };
+        let duration = Duration::from_millis(config.inactive_pane_dim_time_ms).as_secs_f32();
+
+        let mut per_pane = self.pane_state(pos.pane.pane_id());
+        let fading = match per_pane.dim_transition {
+            Some((start, from, to)) if to == target => {
+                let completion = if duration > 0. {
+                    start.elapsed().as_secs_f32() / duration
+                } else {
+                    1.
+                };
+                if completion < 1. {
+                    Some(from + (to - from) * completion)
+                } else {
+                    None
+                }
+            }
+            // Either we're not fading, or the focus changed again part
+            // way through a fade, in which case we head back from the
+            // current amount
+            _ => {
+                let current = per_pane.dim_amount.unwrap_or(target);
+                if current != target && duration > 0. {
+                    per_pane
+                        .dim_transition
+                        .replace((Instant::now(), current, target));
+                    Some(current)
+                } else {
+                    None
+                }
+            }
+        };
+        if fading.is_none() {
+            per_pane.dim_transition.take();
+        }
+        let amount = fading.unwrap_or(target);
+        per_pane.dim_amount.replace(amount);
+        drop(per_pane);
+
+        if fading.is_some() {
+            self.update_next_frame_time(Some(
+                Instant::now() + Duration::from_millis(1000 / config.max_fps as u64),
+            ));
+        }
+
+        if amount == 0. {
+            return PaneHsv::default();
+        }
+        let text = config.inactive_pane_hsb;
+        PaneHsv {
+            text: Some(text.interpolate(amount)),
+            background: Some(
+                config
+                    .inactive_pane_background_hsb
+                    .unwrap_or(text)
+                    .interpolate(amount),
+            ),
+            image: Some(
+                config
+                    .inactive_pane_image_hsb
+                    .unwrap_or(text)
+                    .interpolate(amount),
+            ),
+        }
+    }
+
     fn filled_rectangle<'a>(
         &self,
         layer: &'a mut MappedQuads,
@@ -336,6 +479,93 @@ impl super::TermWindow {
         Ok(quad)
     }
 
+    /// Adds a background rectangle to `run`.  If it continues the pending
+    /// rectangle then the two are merged, otherwise the pending rectangle
+    /// is emitted and a new run is started.
+    /// The caller must call `flush_background_run` before allocating any
+    /// other quads in `layer`, so that the pending rectangle doesn't end
+    /// up being drawn on top of them.
+    fn merge_background_rect(
+        &self,
+        layer: &mut MappedQuads,
+        run: &mut Option<BackgroundRun>,
+        rect: Rect,
+        color: LinearRgba,
+        hsv: Option<HsbTransform>,
+    ) -> anyhow::Result<()> {
+        if let Some(pending) = run.as_mut() {
+            if pending.color == color
+                && pending.hsv == hsv
+                && pending.rect.max_x() == rect.min_x()
+                && pending.rect.min_y() == rect.min_y()
+                && pending.rect.height() == rect.height()
+            {
+                pending.rect.size.width += rect.width();
+                *self.merged_quads.borrow_mut() += 1;
+                return Ok(());
+            }
+        }
+        self.flush_background_run(layer, run)?;
+        run.replace(BackgroundRun { rect, color, hsv });
+        Ok(())
+    }
+
+    fn flush_background_run(
+        &self,
+        layer: &mut MappedQuads,
+        run: &mut Option<BackgroundRun>,
+    ) -> anyhow::Result<()> {
+        if let Some(pending) = run.take() {
+            let mut quad = self.filled_rectangle(layer, pending.rect, pending.color)?;
+            quad.set_hsv(pending.hsv);
+        }
+        Ok(())
+    }
+
+    /// Adds a line decoration to `run`, merging it with the pending
+    /// decoration if it continues it, otherwise emitting the pending
+    /// decoration and starting a new run.
+    fn merge_decoration(
+        &self,
+        layer: &mut MappedQuads,
+        run: &mut Option<DecorationRun>,
+        decoration: DecorationRun,
+    ) -> anyhow::Result<()> {
+        if let Some(pending) = run.as_mut() {
+            if pending.texture == decoration.texture
+                && pending.color == decoration.color
+                && pending.hsv == decoration.hsv
+                && pending.right == decoration.left
+                && pending.top == decoration.top
+                && pending.bottom == decoration.bottom
+            {
+                pending.right = decoration.right;
+                *self.merged_quads.borrow_mut() += 1;
+                return Ok(());
+            }
+        }
+        self.flush_decoration_run(layer, run)?;
+        run.replace(decoration);
+        Ok(())
+    }
+
+    fn flush_decoration_run(
+        &self,
+        layer: &mut MappedQuads,
+        run: &mut Option<DecorationRun>,
+    ) -> anyhow::Result<()> {
+        if let Some(pending) = run.take() {
+            let mut quad = layer.allocate()?;
+            quad.set_position(pending.left, pending.top, pending.right, pending.bottom);
+            quad.set_texture_adjust(0., 0., 0., 0.);
+            quad.set_hsv(pending.hsv);
+            quad.set_has_color(false);
+            quad.set_texture(pending.texture);
+            quad.set_fg_color(pending.color);
+        }
+        Ok(())
+    }
+
     fn poly_quad<'a>(
         &self,
         layer: &'a mut MappedQuads,
@@ -444,6 +674,38 @@ impl super::TermWindow {
         Ok(())
     }
 
+    /// Draws a thin line along the bottom of `rect` to show the
+    /// progress reported by the active pane in a tab
+    fn paint_tab_progress(
+        &self,
+        layer: &mut MappedQuads,
+        rect: Rect,
+        metrics: &FontMetrics,
+        progress: Progress,
+        color: RgbColor,
+        palette: &ColorPalette,
+    ) -> anyhow::Result<()> {
+        let (fraction, color) = match progress {
+            Progress::None => return Ok(()),
+            Progress::Percentage(pct) => (pct as f32 / 100., color),
+            // There's no meaningful position to show, so span the
+            // whole tab to indicate that something is happening
+            Progress::Indeterminate => (1.0, color),
+            Progress::Error => (1.0, palette.colors.0[1]),
+        };
+        let width = (rect.width() as f32 * fraction).round() as isize;
+        if width <= 0 {
+            return Ok(());
+        }
+        let height = (metrics.underline_thickness.get() as isize).max(1) * 2;
+        self.filled_rectangle(
+            layer,
+            euclid::rect(rect.min_x(), rect.max_y() - height, width, height),
+            rgbcolor_to_window_color(color),
+        )?;
+        Ok(())
+    }
+
     fn paint_one_tab(
         &self,
         pos_x: f32,
@@ -504,6 +766,7 @@ impl super::TermWindow {
             foreground: rgbcolor_to_window_color(palette.foreground),
             pane: None,
             is_active: true,
+            hsv: PaneHsv::default(),
             selection_fg: LinearRgba::default(),
             selection_bg: LinearRgba::default(),
             cursor_fg: LinearRgba::default(),
@@ -512,6 +775,8 @@ impl super::TermWindow {
             filled_box,
             window_is_transparent,
             default_bg,
+            text_background_opacity: self.config.text_background_opacity,
+            minimum_contrast_ratio: self.config.minimum_contrast_ratio,
             font: Some(Rc::clone(font)),
             style: Some(&self.config.window_frame.font),
             use_pixel_positioning: true,
@@ -585,6 +850,15 @@ impl super::TermWindow {
                     layers,
                 )?;
 
+                self.paint_tab_progress(
+                    &mut layers[2],
+                    text_bounding_rect,
+                    metrics,
+                    item.progress,
+                    c.fg_color,
+                    palette,
+                )?;
+
                 if !active {
                     // Draw a partial dividing line on the edge of the tab.
                     // This gives a bit more definition on runs of inactive
@@ -766,7 +1040,10 @@ impl super::TermWindow {
         fontconfig: &wezterm_font::FontConfiguration,
         render_metrics: &RenderMetrics,
     ) -> anyhow::Result<f32> {
-        if config.use_fancy_tab_bar {
+        if config.resolved_tab_bar_position().is_vertical() {
+            // A vertical tab bar takes away width rather than height
+            Ok(0.)
+        } else if config.use_fancy_tab_bar {
             let font = fontconfig.title_font()?;
             Ok(font.metrics().cell_height.get() as f32 * 2.)
         } else {
@@ -778,14 +1055,99 @@ impl super::TermWindow {
         Self::tab_bar_pixel_height_impl(&self.config, &self.fonts, &self.render_metrics)
     }
 
+    /// Returns the width of the tab bar when it is displayed down the
+    /// side of the window, or 0 when it is horizontal.
+    /// `tab_max_width` is the number of cells available for the titles.
+    pub fn tab_bar_pixel_width_impl(
+        config: &ConfigHandle,
+        fontconfig: &wezterm_font::FontConfiguration,
+    ) -> anyhow::Result<f32> {
+        if config.resolved_tab_bar_position().is_vertical() {
+            let font = fontconfig.title_font()?;
+            // Allow for half a cell of padding either side of the titles
+            Ok(font.metrics().cell_width.get() as f32 * (config.tab_max_width as f32 + 1.))
+        } else {
+            Ok(0.)
+        }
+    }
+
+    pub fn tab_bar_pixel_width(&self) -> anyhow::Result<f32> {
+        Self::tab_bar_pixel_width_impl(&self.config, &self.fonts)
+    }
+
+    /// If `cursor_animation` is enabled and the cursor of the active pane
+    /// is still travelling to its current cell, returns the position,
+    /// in cells relative to the top left of the viewport, at which it
+    /// should be drawn.  This is purely cosmetic; everything else uses
+    /// the real cursor position.
+    fn animated_cursor_position(
+        &self,
+        pos: &PositionedPane,
+        cursor: &StableCursorPosition,
+        stable_top: StableRowIndex,
+        dims: &RenderableDimensions,
+    ) -> Option<(f32, f32)> {
+        let config = &self.config;
+        if config.cursor_animation.duration_ms == 0
+            || !pos.is_active
+            || self.focused.is_none()
+            || cursor.visibility != CursorVisibility::Visible
+        {
+            return None;
+        }
+        let row = cursor.y - stable_top;
+        if row < 0 || row >= dims.viewport_rows as StableRowIndex {
+            return None;
+        }
+        let to = (cursor.x as f32, row as f32);
+
+        let mut state = self.pane_state(pos.pane.pane_id());
+        let animation = &mut state.cursor_animation;
+
+        let alt_screen = pos.pane.is_alt_screen_active();
+        let seqno = pos.pane.get_current_seqno();
+        // Don't animate a cursor that was moved as a side effect of the
+        // screen being cleared or switched; both of those show up as
+        // every row in the viewport changing at once
+        let jumped = animation.is_moving_to(to)
+            && (alt_screen != animation.alt_screen
+                || pos
+                    .pane
+                    .get_changed_since(
+                        stable_top..stable_top + dims.viewport_rows as StableRowIndex,
+                        animation.seqno,
+                    )
+                    .len()
+                    >= dims.viewport_rows as StableRowIndex);
+        animation.alt_screen = alt_screen;
+        animation.seqno = seqno;
+
+        let position = animation.update(
+            to,
+            jumped,
+            Duration::from_millis(config.cursor_animation.duration_ms),
+            &config.cursor_animation.easing,
+        );
+        if position.is_some() {
+            self.update_next_frame_time(Some(
+                Instant::now() + Duration::from_millis(1000 / config.max_fps as u64),
+            ));
+        }
+        position
+    }
+
+    /// Returns the width of the tab bar if it is being shown
+    /// at the `position` side of the window, otherwise 0
+    pub fn tab_bar_width_at(&self, position: TabBarPosition) -> f32 {
+        if self.show_tab_bar && self.config.resolved_tab_bar_position() == position {
+            self.tab_bar_pixel_width().unwrap_or(0.)
+        } else {
+            0.
+        }
+    }
+
     fn paint_fancy_tab_bar(&self, palette: &ColorPalette) -> anyhow::Result<Vec<UIItem>> {
-        let colors = self
-            .config
-            .colors
-            .as_ref()
-            .and_then(|c| c.tab_bar.as_ref())
-            .cloned()
-            .unwrap_or_else(TabBarColors::default);
+        let colors = self.config.tab_bar_colors();
         let font = self.fonts.title_font()?;
         let metrics = font.metrics();
 
@@ -878,8 +1240,259 @@ impl super::TermWindow {
         Ok(ui_items)
     }
 
+    /// Renders the fancy tab bar as a strip down the side of the window,
+    /// with a full width row for each tab
+    fn paint_vertical_tab_bar(&self, palette: &ColorPalette) -> anyhow::Result<Vec<UIItem>> {
+        let colors = self.config.tab_bar_colors();
+        let font = self.fonts.title_font()?;
+        let metrics = font.metrics();
+        let cell_width = metrics.cell_width.get() as isize;
+        let cell_height = metrics.cell_height.get() as isize;
+
+        let bar_width = self.tab_bar_pixel_width()? as isize;
+        let window_width = self.dimensions.pixel_width as isize;
+        let window_height = self.dimensions.pixel_height as isize;
+        let on_right = self.config.resolved_tab_bar_position() == TabBarPosition::Right;
+        let bar_x = if on_right {
+            (window_width - bar_width).max(0)
+        } else {
+            0
+        };
+
+        // The dividing line that is logically part of the active tab
+        // runs along the edge that is adjacent to the panes, and the
+        // rows extend up to it
+        let divider_width = (cell_width / 4).max(1);
+        let divider_x = if on_right {
+            bar_x
+        } else {
+            bar_x + bar_width - divider_width
+        };
+        let row_x = if on_right {
+            bar_x + divider_width
+        } else {
+            bar_x + cell_width / 4
+        };
+        let row_width = bar_width - divider_width - cell_width / 4;
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        let vb = [&gl_state.vb[0], &gl_state.vb[1], &gl_state.vb[2]];
+        let mut vb_mut0 = vb[0].current_vb_mut();
+        let mut vb_mut1 = vb[1].current_vb_mut();
+        let mut vb_mut2 = vb[2].current_vb_mut();
+        let mut layers = [
+            vb[0].map(&mut vb_mut0),
+            vb[1].map(&mut vb_mut1),
+            vb[2].map(&mut vb_mut2),
+        ];
+
+        // Overall tab bar background
+        self.filled_rectangle(
+            &mut layers[0],
+            euclid::rect(bar_x, 0, bar_width, window_height),
+            rgbcolor_to_window_color(if self.focused.is_some() {
+                self.config.window_frame.active_titlebar_bg
+            } else {
+                self.config.window_frame.inactive_titlebar_bg
+            }),
+        )?;
+
+        // The background can be used to drag the window; it is
+        // registered first so that the tabs take precedence over it
+        let mut ui_items = vec![UIItem {
+            x: bar_x as usize,
+            width: bar_width as usize,
+            y: 0,
+            height: window_height as usize,
+            item_type: UIItemType::TabBar(TabBarItem::None),
+        }];
+
+        let items = self.tab_bar.items();
+        let mut y = cell_height / 2;
+        for item in items.iter() {
+            let row_height = match item.item {
+                // The status is rendered at the bottom, below
+                TabBarItem::None => continue,
+                TabBarItem::Tab { .. } => cell_height * 3 / 2,
+                TabBarItem::NewTabButton => cell_height,
+            };
+            ui_items.push(self.paint_vertical_tab_entry(
+                euclid::rect(row_x, y, row_width, row_height),
+                palette,
+                item,
+                &colors,
+                &font,
+                &metrics,
+                &mut layers,
+            )?);
+            y += row_height + cell_height / 4;
+        }
+
+        if let Some(status_item) = items
+            .iter()
+            .find(|item| matches!(item.item, TabBarItem::None))
+        {
+            let row_height = cell_height * 3 / 2;
+            let status_y = window_height - row_height - cell_height / 2;
+            // Don't draw over the tabs if there are too many to leave room
+            if status_y >= y {
+                self.paint_vertical_tab_entry(
+                    euclid::rect(row_x, status_y, row_width, row_height),
+                    palette,
+                    status_item,
+                    &colors,
+                    &font,
+                    &metrics,
+                    &mut layers,
+                )?;
+            }
+        }
+
+        self.filled_rectangle(
+            &mut layers[1],
+            euclid::rect(divider_x, 0, divider_width, window_height),
+            rgbcolor_to_window_color(colors.active_tab.bg_color),
+        )?;
+
+        Ok(ui_items)
+    }
+
+    /// Renders a single row of the vertical tab bar within `rect`
+    fn paint_vertical_tab_entry(
+        &self,
+        rect: Rect,
+        palette: &ColorPalette,
+        item: &TabEntry,
+        colors: &TabBarColors,
+        font: &Rc<LoadedFont>,
+        metrics: &FontMetrics,
+        layers: &mut [MappedQuads; 3],
+    ) -> anyhow::Result<UIItem> {
+        let gl_state = self.render_state.as_ref().unwrap();
+
+        let white_space = gl_state.util_sprites.white_space.texture_coords();
+        let filled_box = gl_state.util_sprites.filled_box.texture_coords();
+        let window_is_transparent =
+            self.window_background.is_some() || self.config.window_background_opacity != 1.0;
+        let default_bg = rgbcolor_alpha_to_window_color(
+            palette.resolve_bg(ColorAttribute::Default),
+            if window_is_transparent {
+                0.
+            } else {
+                self.config.text_background_opacity
+            },
+        );
+
+        // Vertically center a single line of text within the row
+        let text_bounding_rect = rect.inflate(
+            metrics.cell_width.get() as isize / -2,
+            (metrics.cell_height.get() as isize - rect.height()) / 2,
+        );
+
+        let hover = match &self.current_mouse_event {
+            Some(event) => rect.contains(event.coords),
+            None => false,
+        };
+
+        let (fg_color, bg_color) = match item.item {
+            TabBarItem::Tab { active, .. } => {
+                let c = if active {
+                    &colors.active_tab
+                } else if hover {
+                    &colors.inactive_tab_hover
+                } else {
+                    &colors.inactive_tab
+                };
+                (c.fg_color, Some(c.bg_color))
+            }
+            TabBarItem::NewTabButton => {
+                let c = if hover {
+                    &colors.new_tab_hover
+                } else {
+                    &colors.new_tab
+                };
+                (c.fg_color, Some(c.bg_color))
+            }
+            TabBarItem::None => (colors.inactive_tab.fg_color, None),
+        };
+
+        if let Some(bg_color) = bg_color {
+            self.filled_rectangle(&mut layers[1], rect, rgbcolor_to_window_color(bg_color))?;
+        }
+
+        let params = RenderScreenLineOpenGLParams {
+            top_pixel_y: text_bounding_rect.min_y() as f32,
+            left_pixel_x: text_bounding_rect.min_x() as f32,
+            pixel_width: text_bounding_rect.width() as f32,
+            stable_line_idx: None,
+            line: &item.title,
+            selection: 0..0,
+            cursor: &Default::default(),
+            palette: &palette,
+            dims: &RenderableDimensions {
+                cols: self.terminal_size.cols as _,
+                physical_top: 0,
+                scrollback_rows: 0,
+                scrollback_top: 0,
+                viewport_rows: 1,
+            },
+            config: &self.config,
+            cursor_border_color: LinearRgba::default(),
+            foreground: rgbcolor_to_window_color(fg_color),
+            pane: None,
+            is_active: true,
+            hsv: PaneHsv::default(),
+            selection_fg: LinearRgba::default(),
+            selection_bg: LinearRgba::default(),
+            cursor_fg: LinearRgba::default(),
+            cursor_bg: LinearRgba::default(),
+            white_space,
+            filled_box,
+            window_is_transparent,
+            default_bg,
+            text_background_opacity: self.config.text_background_opacity,
+            minimum_contrast_ratio: self.config.minimum_contrast_ratio,
+            font: Some(Rc::clone(font)),
+            style: Some(&self.config.window_frame.font),
+            use_pixel_positioning: true,
+            pre_shaped: None,
+            render_metrics: RenderMetrics::with_font_metrics(metrics),
+        };
+        let cell_clusters = item.title.cluster();
+        let shaped = self.cluster_and_shape(&cell_clusters, &params)?;
+        self.render_screen_line_opengl(
+            RenderScreenLineOpenGLParams {
+                pre_shaped: Some(&shaped),
+                ..params
+            },
+            layers,
+        )?;
+
+        self.paint_tab_progress(
+            &mut layers[2],
+            rect.inflate(metrics.cell_width.get() as isize / -2, 0),
+            metrics,
+            item.progress,
+            fg_color,
+            palette,
+        )?;
+
+        Ok(UIItem {
+            x: rect.min_x() as usize,
+            width: rect.width() as usize,
+            y: rect.min_y() as usize,
+            height: rect.height() as usize,
+            item_type: UIItemType::TabBar(item.item),
+        })
+    }
+
     fn paint_tab_bar(&mut self) -> anyhow::Result<()> {
         let palette = self.palette().clone();
+        if self.config.resolved_tab_bar_position().is_vertical() {
+            self.ui_items
+                .append(&mut self.paint_vertical_tab_bar(&palette)?);
+            return Ok(());
+        }
         if self.config.use_fancy_tab_bar {
             self.ui_items
                 .append(&mut self.paint_fancy_tab_bar(&palette)?);
@@ -887,7 +1500,7 @@ impl super::TermWindow {
         }
 
         let tab_bar_height = self.tab_bar_pixel_height()?;
-        let tab_bar_y = if self.config.tab_bar_at_bottom {
+        let tab_bar_y = if self.config.resolved_tab_bar_position() == TabBarPosition::Bottom {
             ((self.dimensions.pixel_height as f32) - tab_bar_height).max(0.)
         } else {
             0.
@@ -946,6 +1559,7 @@ impl super::TermWindow {
                 foreground: rgbcolor_to_window_color(palette.foreground),
                 pane: None,
                 is_active: true,
+                hsv: PaneHsv::default(),
                 selection_fg: LinearRgba::default(),
                 selection_bg: LinearRgba::default(),
                 cursor_fg: LinearRgba::default(),
@@ -954,6 +1568,8 @@ impl super::TermWindow {
                 filled_box,
                 window_is_transparent,
                 default_bg,
+                text_background_opacity: self.config.text_background_opacity,
+                minimum_contrast_ratio: self.config.minimum_contrast_ratio,
                 style: None,
                 font: None,
                 use_pixel_positioning: false,
@@ -966,10 +1582,148 @@ impl super::TermWindow {
         Ok(())
     }
 
+    /// Returns the color to use for the visual bell
+    fn visual_bell_color(&mut self) -> LinearRgba {
+        let foreground = self.palette().foreground;
+        let (r, g, b, _) = self
+            .config
+            .visual_bell
+            .color
+            .or(self.config.resolved_palette.visual_bell)
+            .unwrap_or(foreground)
+            .to_linear_tuple_rgba();
+        LinearRgba::with_components(r, g, b, 1.0)
+    }
+
+    /// When `visual_bell.target = "Window"`, fades the bell color in
+    /// over the whole window if any of the visible panes is ringing.
+    /// This is drawn above the backgrounds of the panes but beneath
+    /// their text; the tab bar is painted over it.
+    fn paint_window_bell(&mut self, panes: &[PositionedPane]) -> anyhow::Result<()> {
+        let config = self.config.clone();
+        let intensity = panes
+            .iter()
+            .filter_map(|pos| {
+                self.get_intensity_if_bell_target_ringing(
+                    &pos.pane,
+                    &config,
+                    VisualBellTarget::Window,
+                )
+            })
+            .fold(None, |max: Option<f32>, i| {
+                Some(max.map_or(i, |m| m.max(i)))
+            });
+        let intensity = match intensity {
+            Some(intensity) => intensity,
+            None => return Ok(()),
+        };
+
+        let (r, g, b, _) = self.visual_bell_color().tuple();
+        let gl_state = self.render_state.as_ref().unwrap();
+        let vb = &gl_state.vb[0];
+        let mut vb_mut = vb.current_vb_mut();
+        let mut layer = vb.map(&mut vb_mut);
+        self.filled_rectangle(
+            &mut layer,
+            Rect::new(
+                Point::new(0, 0),
+                Size::new(
+                    self.dimensions.pixel_width as isize,
+                    self.dimensions.pixel_height as isize,
+                ),
+            ),
+            LinearRgba::with_components(r, g, b, intensity),
+        )?;
+        Ok(())
+    }
+
+    /// When `visual_bell.target = "TabBarOnly"`, tints the tabs that
+    /// contain a pane that is ringing.  This works from the tab
+    /// positions recorded in the ui items so that it applies to every
+    /// style of tab bar.  It is drawn in the top layer at half of the
+    /// bell intensity so that the tab title remains legible.
+    fn paint_tab_bar_bell(&mut self) -> anyhow::Result<()> {
+        let config = self.config.clone();
+        let tabs = {
+            let mux = Mux::get().unwrap();
+            let window = match mux.get_window(self.mux_window_id) {
+                Some(window) => window,
+                None => return Ok(()),
+            };
+            window
+                .iter()
+                .map(|tab| tab.iter_panes_ignoring_zoom())
+                .collect::<Vec<_>>()
+        };
+
+        let mut ringing = vec![];
+        for (tab_idx, panes) in tabs.iter().enumerate() {
+            let intensity = panes
+                .iter()
+                .filter_map(|pos| {
+                    self.get_intensity_if_bell_target_ringing(
+                        &pos.pane,
+                        &config,
+                        VisualBellTarget::TabBarOnly,
+                    )
+                })
+                .fold(None, |max: Option<f32>, i| {
+                    Some(max.map_or(i, |m| m.max(i)))
+                });
+            if let Some(intensity) = intensity {
+                ringing.push((tab_idx, intensity));
+            }
+        }
+        if ringing.is_empty() {
+            return Ok(());
+        }
+
+        let (r, g, b, _) = self.visual_bell_color().tuple();
+        let gl_state = self.render_state.as_ref().unwrap();
+        let vb = &gl_state.vb[2];
+        let mut vb_mut = vb.current_vb_mut();
+        let mut layer = vb.map(&mut vb_mut);
+        for item in &self.ui_items {
+            let item_tab_idx = match &item.item_type {
+                UIItemType::TabBar(TabBarItem::Tab { tab_idx, .. }) => *tab_idx,
+                _ => continue,
+            };
+            if let Some((_, intensity)) = ringing.iter().find(|(idx, _)| *idx == item_tab_idx) {
+                self.filled_rectangle(
+                    &mut layer,
+                    Rect::new(
+                        Point::new(item.x as isize, item.y as isize),
+                        Size::new(item.width as isize, item.height as isize),
+                    ),
+                    LinearRgba::with_components(r, g, b, intensity / 2.),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Paints a translucent copy of the tab that is being dragged
+    /// away from the tab bar
+    fn paint_tab_drag_ghost(&mut self) -> anyhow::Result<()> {
+        let ghost = match self.tab_drag.ghost {
+            Some(ghost) => ghost,
+            None => return Ok(()),
+        };
+        let color =
+            rgbcolor_alpha_to_window_color(self.config.tab_bar_colors().active_tab.bg_color, 0.5);
+        let gl_state = self.render_state.as_ref().unwrap();
+        let vb = &gl_state.vb[2];
+        let mut vb_mut = vb.current_vb_mut();
+        let mut layer = vb.map(&mut vb_mut);
+        self.filled_rectangle(&mut layer, ghost, color)?;
+        Ok(())
+    }
+
     pub fn paint_pane_opengl(
         &mut self,
         pos: &PositionedPane,
         num_panes: usize,
+        has_opacity_overrides: bool,
     ) -> anyhow::Result<()> {
         self.check_for_dirty_lines_and_invalidate_selection(&pos.pane);
         /*
@@ -994,12 +1748,15 @@ impl super::TermWindow {
 
         let (padding_left, padding_top) = self.padding_left_top();
 
-        let tab_bar_height = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
+        let tab_bar_height = if self.show_tab_bar
+            && self.config.resolved_tab_bar_position() != TabBarPosition::Bottom
+        {
             self.tab_bar_pixel_height()?
         } else {
             0.
         };
         let top_pixel_y = tab_bar_height + padding_top;
+        let tab_bar_left = self.tab_bar_width_at(TabBarPosition::Left);
 
         let cursor = pos.pane.get_cursor_position();
         if pos.is_active {
@@ -1007,7 +1764,7 @@ impl super::TermWindow {
         }
 
         let current_viewport = self.get_viewport(pos.pane.pane_id());
-        let (stable_top, lines);
+        let (stable_top, mut lines);
         let dims = pos.pane.get_dimensions();
 
         {
@@ -1029,6 +1786,55 @@ impl super::TermWindow {
             lines = vp_lines;
         }
 
+        // Show the text being composed by the input method at the cursor,
+        // using the pane's font, with the cursor following it as though
+        // it had already been typed
+        let cursor = match &self.ime_composition {
+            ImeCompositionStatus::Composing(text) if pos.is_active => {
+                let row = (cursor.y - stable_top) as usize;
+                if let Some(line) = lines.get_mut(row) {
+                    let attrs = self.config.ime_preedit_colors.as_cell_attributes();
+                    line.overlay_text_with_attribute(cursor.x, text, attrs, SEQ_ZERO);
+                    line.resize(dims.cols, SEQ_ZERO);
+                }
+                StableCursorPosition {
+                    x: (cursor.x + unicode_column_width(text, None))
+                        .min(dims.cols.saturating_sub(1)),
+                    ..cursor
+                }
+            }
+            _ => cursor,
+        };
+
+        // A pane that is being held open after its process exited
+        // shows the status below the last output.  It is only drawn
+        // here, so it isn't part of the text that is copied or searched.
+        // If that row is scrolled out of view, or the output has already
+        // filled it, there is nowhere to put the banner without hiding
+        // some of the output, so it is left out.
+        if let Some(status) = pos.pane.get_exit_status() {
+            let row = if cursor.x == 0 {
+                cursor.y
+            } else {
+                cursor.y + 1
+            };
+            let row = row - stable_top;
+            if row >= 0 {
+                if let Some(line) = lines.get_mut(row as usize) {
+                    if line.is_whitespace() {
+                        let text = exit_banner_text(&status, dims.cols);
+                        line.overlay_text_with_attribute(
+                            0,
+                            &text,
+                            exit_banner_attrs(&status),
+                            SEQ_ZERO,
+                        );
+                        line.resize(dims.cols, SEQ_ZERO);
+                    }
+                }
+            }
+        }
+
         let gl_state = self.render_state.as_ref().unwrap();
         let vb = [&gl_state.vb[0], &gl_state.vb[1], &gl_state.vb[2]];
 
@@ -1049,15 +1855,35 @@ impl super::TermWindow {
         let white_space = gl_state.util_sprites.white_space.texture_coords();
         let filled_box = gl_state.util_sprites.filled_box.texture_coords();
 
-        let window_is_transparent =
-            self.window_background.is_some() || config.window_background_opacity != 1.0;
+        let hsv = self.pane_hsv(pos, config);
+
+        let pane_opacity = crate::scripting::pane::background_opacity_override(&pos.pane);
+        let background_opacity = pane_opacity.unwrap_or(config.window_background_opacity);
+        // Don't let text backgrounds become more transparent than the
+        // pane itself, so that text remains readable in an opaque pane
+        let text_background_opacity = match pane_opacity {
+            Some(opacity) => config.text_background_opacity.max(opacity),
+            None => config.text_background_opacity,
+        };
+
+        // The search and quick select overlays pick their own highlight
+        // colors; leave those alone
+        let minimum_contrast_ratio = if pos.pane.downcast_ref::<SearchOverlay>().is_some()
+            || pos.pane.downcast_ref::<QuickSelectOverlay>().is_some()
+        {
+            1.0
+        } else {
+            config.minimum_contrast_ratio
+        };
+
+        let window_is_transparent = self.window_background.is_some() || background_opacity != 1.0;
 
         let default_bg = rgbcolor_alpha_to_window_color(
             palette.resolve_bg(ColorAttribute::Default),
             if window_is_transparent {
                 0.
             } else {
-                config.text_background_opacity
+                text_background_opacity
             },
         );
 
@@ -1087,18 +1913,54 @@ impl super::TermWindow {
                     quad.set_hsv(config.window_background_image_hsb);
                     quad.set_fg_color(color);
                 }
+                _ if num_panes > 1 && has_opacity_overrides => {
+                    // The per-pane backgrounds below cover everything
+                    // except the tab bar, and must not be blended over
+                    // the window background for their opacity to be
+                    // respected, so only fill in the tab bar area
+                    if self.show_tab_bar {
+                        let tab_bar_height = self.tab_bar_pixel_height()? as isize;
+                        let tab_bar_width = self.tab_bar_pixel_width()? as isize;
+                        let width = self.dimensions.pixel_width as isize;
+                        let height = self.dimensions.pixel_height as isize;
+                        let rect: Rect = match self.config.resolved_tab_bar_position() {
+                            TabBarPosition::Top => euclid::rect(0, 0, width, tab_bar_height),
+                            TabBarPosition::Bottom => euclid::rect(
+                                0,
+                                (height - tab_bar_height).max(0),
+                                width,
+                                tab_bar_height,
+                            ),
+                            TabBarPosition::Left => euclid::rect(0, 0, tab_bar_width, height),
+                            TabBarPosition::Right => euclid::rect(
+                                (width - tab_bar_width).max(0),
+                                0,
+                                tab_bar_width,
+                                height,
+                            ),
+                        };
+                        self.filled_rectangle(
+                            &mut layers[0],
+                            rect,
+                            rgbcolor_alpha_to_window_color(
+                                global_bg_color,
+                                config.window_background_opacity,
+                            ),
+                        )?;
+                    }
+                }
                 _ => {
                     // Regular window background color
-                    let background = rgbcolor_alpha_to_window_color(
-                        if num_panes == 1 {
-                            // If we're the only pane, use the pane's palette
-                            // to draw the padding background
-                            palette.background
-                        } else {
-                            global_bg_color
-                        },
-                        config.window_background_opacity,
-                    );
+                    let background = if num_panes == 1 {
+                        // If we're the only pane, use the pane's palette
+                        // to draw the padding background
+                        rgbcolor_alpha_to_window_color(palette.background, background_opacity)
+                    } else {
+                        rgbcolor_alpha_to_window_color(
+                            global_bg_color,
+                            config.window_background_opacity,
+                        )
+                    };
                     self.filled_rectangle(
                         &mut layers[0],
                         Rect::new(
@@ -1121,10 +1983,13 @@ impl super::TermWindow {
 
             // We want to fill out to the edges of the splits
             let (x, width_delta) = if pos.left == 0 {
-                (0, padding_left as isize + (cell_width / 2.0) as isize)
+                (
+                    tab_bar_left as isize,
+                    padding_left as isize + (cell_width / 2.0) as isize,
+                )
             } else {
                 (
-                    padding_left as isize - (cell_width / 2.0) as isize
+                    (tab_bar_left + padding_left) as isize - (cell_width / 2.0) as isize
                         + (pos.left as f32 * cell_width) as isize,
                     cell_width as isize,
                 )
@@ -1181,14 +2046,19 @@ impl super::TermWindow {
                 ),
                 rgbcolor_alpha_to_window_color(
                     palette.background,
-                    config.window_background_opacity,
+                    match pane_opacity {
+                        Some(opacity) => opacity,
+                        // Without any overrides this is blended over the
+                        // window background; match the resulting opacity
+                        // when that background isn't drawn
+                        None if has_opacity_overrides => {
+                            1. - (1. - config.window_background_opacity).powi(2)
+                        }
+                        None => config.window_background_opacity,
+                    },
                 ),
             )?;
-            quad.set_hsv(if pos.is_active {
-                None
-            } else {
-                Some(config.inactive_pane_hsb)
-            });
+            quad.set_hsv(hsv.background);
         }
 
         {
@@ -1201,8 +2071,9 @@ impl super::TermWindow {
             ) {
                 // target background color
                 let (r, g, b, _) = config
-                    .resolved_palette
                     .visual_bell
+                    .color
+                    .or(config.resolved_palette.visual_bell)
                     .unwrap_or(palette.foreground)
                     .to_linear_tuple_rgba();
 
@@ -1213,11 +2084,9 @@ impl super::TermWindow {
                 } else {
                     // otherwise We'll interpolate between the background color
                     // and the the target color
-                    let (r1, g1, b1, a) = rgbcolor_alpha_to_window_color(
-                        palette.background,
-                        config.window_background_opacity,
-                    )
-                    .tuple();
+                    let (r1, g1, b1, a) =
+                        rgbcolor_alpha_to_window_color(palette.background, background_opacity)
+                            .tuple();
                     LinearRgba::with_components(
                         r1 + (r - r1) * intensity,
                         g1 + (g - g1) * intensity,
@@ -1234,7 +2103,7 @@ impl super::TermWindow {
                     &mut layers[0],
                     Rect::new(
                         Point::new(
-                            ((pos.left as f32 * cell_width) + padding_left) as isize,
+                            ((pos.left as f32 * cell_width) + padding_left + tab_bar_left) as isize,
                             (top_pixel_y + (pos.top as f32 * cell_height) + padding_top) as isize,
                         ),
                         Size::new(
@@ -1245,11 +2114,7 @@ impl super::TermWindow {
                     background,
                 )?;
 
-                quad.set_hsv(if pos.is_active {
-                    None
-                } else {
-                    Some(config.inactive_pane_hsb)
-                });
+                quad.set_hsv(hsv.background);
             }
         }
 
@@ -1264,33 +2129,52 @@ impl super::TermWindow {
                 current_viewport,
                 &self.dimensions,
                 tab_bar_height,
-                config.tab_bar_at_bottom,
+                config.resolved_tab_bar_position() == TabBarPosition::Bottom,
+                self.min_scroll_bar_height(),
             );
             let thumb_top = info.top as f32;
             let thumb_size = info.height as f32;
-            let color = rgbcolor_to_window_color(palette.scrollbar_thumb);
+            let is_hovered = match (&self.dragging, &self.last_ui_item) {
+                (Some((item, _)), _) | (None, Some(item)) => {
+                    item.item_type == UIItemType::ScrollThumb
+                }
+                (None, None) => false,
+            };
+            let color = rgbcolor_to_window_color(if is_hovered {
+                config
+                    .resolved_palette
+                    .scrollbar_thumb_hover
+                    .unwrap_or(palette.scrollbar_thumb)
+            } else {
+                palette.scrollbar_thumb
+            });
 
             // Adjust the scrollbar thumb position
             let config = &self.config;
             let padding = self.effective_right_padding(&config) as f32;
+            // The scrollbar sits to the left of a tab bar on the right
+            let right_edge = self
+                .dimensions
+                .pixel_width
+                .saturating_sub(self.tab_bar_width_at(TabBarPosition::Right) as usize);
 
             // Register the scroll bar location
             self.ui_items.push(UIItem {
-                x: self.dimensions.pixel_width - padding as usize,
+                x: right_edge - padding as usize,
                 width: padding as usize,
                 y: tab_bar_height as usize,
                 height: thumb_top as usize,
                 item_type: UIItemType::AboveScrollThumb,
             });
             self.ui_items.push(UIItem {
-                x: self.dimensions.pixel_width - padding as usize,
+                x: right_edge - padding as usize,
                 width: padding as usize,
                 y: thumb_top as usize,
                 height: thumb_size as usize,
                 item_type: UIItemType::ScrollThumb,
             });
             self.ui_items.push(UIItem {
-                x: self.dimensions.pixel_width - padding as usize,
+                x: right_edge - padding as usize,
                 width: padding as usize,
                 y: (thumb_top + thumb_size) as usize,
                 height: self
@@ -1303,10 +2187,7 @@ impl super::TermWindow {
             self.filled_rectangle(
                 &mut layers[2],
                 Rect::new(
-                    Point::new(
-                        self.dimensions.pixel_width as isize - padding as isize,
-                        thumb_top as isize,
-                    ),
+                    Point::new(right_edge as isize - padding as isize, thumb_top as isize),
                     Size::new(padding as isize, thumb_size as isize),
                 ),
                 color,
@@ -1315,11 +2196,24 @@ impl super::TermWindow {
 
         let selrange = self.selection(pos.pane.pane_id()).range.clone();
 
+        // While the cursor is gliding between cells, the lines are
+        // rendered without it, and it is drawn separately afterwards
+        let animated_cursor = self.animated_cursor_position(pos, &cursor, stable_top, &dims);
+        let line_cursor = if animated_cursor.is_some() {
+            StableCursorPosition {
+                visibility: CursorVisibility::Hidden,
+                ..cursor
+            }
+        } else {
+            cursor
+        };
+
         let start = Instant::now();
         let selection_fg = rgbcolor_to_window_color(palette.selection_fg);
         let selection_bg = rgbcolor_to_window_color(palette.selection_bg);
         let cursor_fg = rgbcolor_to_window_color(palette.cursor_fg);
         let cursor_bg = rgbcolor_to_window_color(palette.cursor_bg);
+        let line_config = self.config_for_pane_text(&pos.pane);
         for (line_idx, line) in lines.iter().enumerate() {
             let stable_row = stable_top + line_idx as StableRowIndex;
 
@@ -1331,19 +2225,21 @@ impl super::TermWindow {
                 RenderScreenLineOpenGLParams {
                     top_pixel_y: top_pixel_y
                         + (line_idx + pos.top) as f32 * self.render_metrics.cell_size.height as f32,
-                    left_pixel_x: padding_left
+                    left_pixel_x: tab_bar_left
+                        + padding_left
                         + (pos.left as f32 * self.render_metrics.cell_size.width as f32),
                     pixel_width: dims.cols as f32 * self.render_metrics.cell_size.width as f32,
                     stable_line_idx: Some(stable_row),
                     line: &line,
                     selection: selrange,
-                    cursor: &cursor,
+                    cursor: &line_cursor,
                     palette: &palette,
                     dims: &dims,
-                    config: &config,
+                    config: &line_config,
                     cursor_border_color,
                     foreground,
                     is_active: pos.is_active,
+                    hsv,
                     pane: Some(&pos.pane),
                     selection_fg,
                     selection_bg,
@@ -1353,6 +2249,8 @@ impl super::TermWindow {
                     filled_box,
                     window_is_transparent,
                     default_bg,
+                    text_background_opacity,
+                    minimum_contrast_ratio,
                     font: None,
                     style: None,
                     use_pixel_positioning: false,
@@ -1362,6 +2260,49 @@ impl super::TermWindow {
                 &mut layers,
             )?;
         }
+
+        if let Some((x, y)) = animated_cursor {
+            let cell_width = self.render_metrics.cell_size.width as f32;
+            let cell_height = self.render_metrics.cell_size.height as f32;
+            let left = tab_bar_left + padding_left + (pos.left as f32 + x) * cell_width;
+            let top = top_pixel_y + (pos.top as f32 + y) * cell_height;
+
+            match config.default_cursor_style.effective_shape(cursor.shape) {
+                CursorShape::BlinkingBlock | CursorShape::SteadyBlock => {
+                    // Behind the text, so that the glyphs remain legible
+                    // as the block passes over them
+                    let mut quad = self.filled_rectangle(
+                        &mut layers[0],
+                        euclid::rect(
+                            left.round() as isize,
+                            top.round() as isize,
+                            cell_width as isize,
+                            cell_height as isize,
+                        ),
+                        cursor_bg,
+                    )?;
+                    quad.set_hsv(hsv.text);
+                }
+                shape => {
+                    let left = left - self.dimensions.pixel_width as f32 / 2.;
+                    let top = top - self.dimensions.pixel_height as f32 / 2.;
+                    let mut quad = layers[2].allocate()?;
+                    quad.set_position(left, top, left + cell_width, top + cell_height);
+                    quad.set_texture_adjust(0., 0., 0., 0.);
+                    quad.set_has_color(false);
+                    quad.set_hsv(hsv.text);
+                    quad.set_texture(
+                        gl_state
+                            .glyph_cache
+                            .borrow_mut()
+                            .cursor_sprite(Some(shape), &self.render_metrics)?
+                            .texture_coords(),
+                    );
+                    quad.set_fg_color(cursor_border_color);
+                }
+            }
+        }
+
         /*
         if let Some(zone) = zone {
             // TODO: render a thingy to jump to prior prompt
@@ -1500,23 +2441,78 @@ impl super::TermWindow {
         &mut self,
         split: &PositionedSplit,
         pane: &Rc<dyn Pane>,
+        active: Option<&PositionedPane>,
+        broadcast: &[&PositionedPane],
     ) -> anyhow::Result<()> {
         let gl_state = self.render_state.as_ref().unwrap();
         let vb = &gl_state.vb[2];
         let mut vb_mut = vb.current_vb_mut();
         let mut quads = vb.map(&mut vb_mut);
         let palette = pane.palette();
-        let foreground = rgbcolor_to_window_color(palette.split);
+        let pane_split = &self.config.pane_split;
         let cell_width = self.render_metrics.cell_size.width as f32;
         let cell_height = self.render_metrics.cell_size.height as f32;
 
-        let first_row_offset = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
+        let is_hovered = match (&self.dragging, &self.last_ui_item) {
+            (Some((item, _)), _) | (None, Some(item)) => match &item.item_type {
+                UIItemType::Split(s) => s.index == split.index,
+                _ => false,
+            },
+            (None, None) => false,
+        };
+        let is_adjacent_to_active = active
+            .map(|pos| split_borders_pane(split, pos))
+            .unwrap_or(false);
+        let is_adjacent_to_broadcast = broadcast.iter().any(|pos| split_borders_pane(split, pos));
+        let color = pane_split.color.unwrap_or(palette.split);
+        let color = if is_hovered {
+            pane_split
+                .hover_color
+                .or(pane_split.active_color)
+                .unwrap_or(color)
+        } else if is_adjacent_to_broadcast {
+            pane_split
+                .broadcast_color
+                .unwrap_or_else(|| palette.colors.0[1])
+        } else if is_adjacent_to_active {
+            pane_split.active_color.unwrap_or(color)
+        } else {
+            color
+        };
+        let foreground = rgbcolor_to_window_color(color);
+
+        // The divider runs through the middle of the cell that separates
+        // the panes, so it cannot be thicker than that cell
+        let cell_across = if split.direction == SplitDirection::Horizontal {
+            cell_width
+        } else {
+            cell_height
+        };
+        let thickness = if pane_split.thickness.is_zero() {
+            self.render_metrics.underline_height as f32
+        } else {
+            pane_split.thickness.evaluate_as_pixels(DimensionContext {
+                dpi: self.dimensions.dpi as f32,
+                pixel_max: cell_across,
+                pixel_cell: cell_across,
+            })
+        }
+        .max(1.)
+        .min(cell_across);
+        // The area that can be dragged to resize grows with the thickness,
+        // so that a thick divider is easy to grab
+        let hit_size = cell_across + thickness;
+
+        let first_row_offset = if self.show_tab_bar
+            && self.config.resolved_tab_bar_position() != TabBarPosition::Bottom
+        {
             self.tab_bar_pixel_height()?
         } else {
             0.
         };
 
         let (padding_left, padding_top) = self.padding_left_top();
+        let padding_left = padding_left + self.tab_bar_width_at(TabBarPosition::Left);
 
         let pos_y = split.top as f32 * cell_height + first_row_offset + padding_top;
         let pos_x = split.left as f32 * cell_width + padding_left;
@@ -1526,19 +2522,19 @@ impl super::TermWindow {
                 &mut quads,
                 Rect::new(
                     Point::new(
-                        pos_x as isize + (cell_width / 2.0) as isize,
+                        (pos_x + (cell_width - thickness) / 2.0) as isize,
                         pos_y as isize - (cell_height / 2.0) as isize,
                     ),
                     Size::new(
-                        self.render_metrics.underline_height,
+                        thickness as isize,
                         (1 + split.size as isize) * cell_height as isize,
                     ),
                 ),
                 foreground,
             )?;
             self.ui_items.push(UIItem {
-                x: padding_left as usize + (split.left * cell_width as usize),
-                width: cell_width as usize,
+                x: (pos_x + (cell_width - hit_size) / 2.0).max(0.) as usize,
+                width: hit_size as usize,
                 y: padding_top as usize
                     + first_row_offset as usize
                     + split.top * cell_height as usize,
@@ -1551,11 +2547,11 @@ impl super::TermWindow {
                 Rect::new(
                     Point::new(
                         pos_x as isize - (cell_width / 2.0) as isize,
-                        pos_y as isize + (cell_height / 2.0) as isize,
+                        (pos_y + (cell_height - thickness) / 2.0) as isize,
                     ),
                     Size::new(
                         (1 + split.size as isize) * cell_width as isize,
-                        self.render_metrics.underline_height,
+                        thickness as isize,
                     ),
                 ),
                 foreground,
@@ -1563,10 +2559,8 @@ impl super::TermWindow {
             self.ui_items.push(UIItem {
                 x: padding_left as usize + (split.left * cell_width as usize),
                 width: split.size * cell_width as usize,
-                y: padding_top as usize
-                    + first_row_offset as usize
-                    + split.top * cell_height as usize,
-                height: cell_height as usize,
+                y: (pos_y + (cell_height - hit_size) / 2.0).max(0.) as usize,
+                height: hit_size as usize,
                 item_type: UIItemType::Split(split.clone()),
             });
         }
@@ -1580,6 +2574,7 @@ impl super::TermWindow {
             for vb in &gl_state.vb {
                 vb.clear_quad_allocation();
             }
+            *self.merged_quads.borrow_mut() = 0;
         }
 
         // Clear out UI item positions; we'll rebuild these as we render
@@ -1587,24 +2582,38 @@ impl super::TermWindow {
 
         let panes = self.get_panes_to_render();
         let num_panes = panes.len();
+        let has_opacity_overrides = panes
+            .iter()
+            .any(|pos| crate::scripting::pane::background_opacity_override(&pos.pane).is_some());
 
-        for pos in panes {
+        for pos in &panes {
             if pos.is_active {
-                self.update_text_cursor(&pos.pane);
+                self.update_text_cursor(pos);
             }
-            self.paint_pane_opengl(&pos, num_panes)?;
+            self.paint_pane_opengl(pos, num_panes, has_opacity_overrides)?;
         }
 
         if let Some(pane) = self.get_active_pane_or_overlay() {
+            let active = panes.iter().find(|pos| pos.is_active);
+            let mux = Mux::get().unwrap();
+            let broadcast: Vec<&PositionedPane> = panes
+                .iter()
+                .filter(|pos| mux.is_input_broadcast_target(pos.pane.pane_id()))
+                .collect();
             let splits = self.get_splits();
             for split in &splits {
-                self.paint_split_opengl(split, &pane)?;
+                self.paint_split_opengl(split, &pane, active, &broadcast)?;
             }
         }
 
+        self.paint_window_bell(&panes)?;
+
         if self.show_tab_bar {
             self.paint_tab_bar()?;
+            self.paint_tab_bar_bell()?;
+            self.paint_tab_drag_ghost()?;
         }
+        self.tab_bar_was_painted();
 
         Ok(())
     }
@@ -1656,6 +2665,12 @@ impl super::TermWindow {
                         bg_default = false;
                     }
 
+                    // Text that was deliberately made invisible by setting
+                    // fg == bg is left alone
+                    if params.minimum_contrast_ratio > 1.0 && fg != bg {
+                        fg = self.ensure_contrast(fg, bg, params.minimum_contrast_ratio);
+                    }
+
                     // Check for blink, and if this is the "not-visible"
                     // part of blinking then set fg = bg.  This is a cheap
                     // means of getting it done without impacting other
@@ -1708,7 +2723,7 @@ impl super::TermWindow {
                     if params.window_is_transparent && bg_is_default {
                         0.0
                     } else {
-                        params.config.text_background_opacity
+                        params.text_background_opacity
                     },
                 );
 
@@ -1764,11 +2779,7 @@ impl super::TermWindow {
 
         let num_cols = params.dims.cols;
 
-        let hsv = if params.is_active {
-            None
-        } else {
-            Some(params.config.inactive_pane_hsb)
-        };
+        let hsv = params.hsv.text;
 
         let cell_width = params.render_metrics.cell_size.width as f32;
         let cell_height = params.render_metrics.cell_size.height as f32;
@@ -1807,6 +2818,9 @@ impl super::TermWindow {
         // Need to consider:
         // * background when it is not the default color
         // * Reverse video attribute
+        // Adjacent clusters that differ only in their foreground
+        // attributes are merged into a single background quad.
+        let mut bg_run = None;
         for item in shaped {
             let cluster = &item.cluster;
             let attrs = &cluster.attrs;
@@ -1830,7 +2844,7 @@ impl super::TermWindow {
                 }
 
                 (
-                    rgbcolor_alpha_to_window_color(bg, self.config.text_background_opacity),
+                    rgbcolor_alpha_to_window_color(bg, params.text_background_opacity),
                     bg_default,
                 )
             };
@@ -1856,11 +2870,17 @@ impl super::TermWindow {
                     ),
                 );
                 if let Some(rect) = rect.intersection(&bounding_rect) {
-                    let mut quad = self.filled_rectangle(&mut layers[0], rect, bg_color)?;
-                    quad.set_hsv(hsv);
+                    self.merge_background_rect(
+                        &mut layers[0],
+                        &mut bg_run,
+                        rect,
+                        bg_color,
+                        params.hsv.background,
+                    )?;
                 }
             }
         }
+        self.flush_background_run(&mut layers[0], &mut bg_run)?;
 
         // Render the selection background color
         if !params.selection.is_empty() {
@@ -1885,6 +2905,12 @@ impl super::TermWindow {
         }
 
         let mut overlay_images = vec![];
+        // Runs of cells whose background is overridden, for example
+        // by the selection, are merged until something else needs
+        // to be drawn in layers[0]
+        let mut bg_run = None;
+        // Likewise for underlines and strikethrough over whitespace
+        let mut decoration_run = None;
 
         for item in shaped {
             let style_params = &item.style;
@@ -2012,8 +3038,9 @@ impl super::TermWindow {
                     if bg_color != style_params.bg_color {
                         // Override the background color
                         if !params.use_pixel_positioning || glyph_idx == 0 {
-                            let mut quad = self.filled_rectangle(
+                            self.merge_background_rect(
                                 &mut layers[0],
+                                &mut bg_run,
                                 Rect::new(
                                     Point::new(
                                         (params.left_pixel_x
@@ -2036,8 +3063,8 @@ impl super::TermWindow {
                                     ),
                                 ),
                                 bg_color,
+                                hsv,
                             )?;
-                            quad.set_hsv(hsv);
                         }
                     }
 
@@ -2075,13 +3102,14 @@ impl super::TermWindow {
 
                     for img in &images {
                         if img.z_index() < 0 {
+                            self.flush_background_run(&mut layers[0], &mut bg_run)?;
                             self.populate_image_quad(
                                 &img,
                                 gl_state,
                                 &mut layers[0],
                                 cell_idx,
                                 &params,
-                                hsv,
+                                params.hsv.image,
                                 glyph_color,
                             )?;
                         }
@@ -2090,6 +3118,33 @@ impl super::TermWindow {
                     // Underlines
                     if style_params.underline_tex_rect != params.white_space {
                         if !params.use_pixel_positioning || glyph_idx == 0 {
+                            self.flush_background_run(&mut layers[0], &mut bg_run)?;
+                        }
+                        let straight_line = !matches!(
+                            cluster.attrs.underline(),
+                            Underline::Curly | Underline::Dotted | Underline::Dashed
+                        );
+                        let is_whitespace = params
+                            .line
+                            .cells()
+                            .get(cell_idx)
+                            .map(|cell| cell.str().chars().all(char::is_whitespace))
+                            .unwrap_or(false);
+                        if !params.use_pixel_positioning && straight_line && is_whitespace {
+                            self.merge_decoration(
+                                &mut layers[0],
+                                &mut decoration_run,
+                                DecorationRun {
+                                    left: pos_x,
+                                    top: pos_y,
+                                    right: pos_x + cell_width,
+                                    bottom: pos_y + cell_height,
+                                    texture: style_params.underline_tex_rect,
+                                    color: style_params.underline_color,
+                                    hsv,
+                                },
+                            )?;
+                        } else if !params.use_pixel_positioning || glyph_idx == 0 {
                             let mut quad = layers[0].allocate()?;
                             quad.set_position(
                                 pos_x,
@@ -2117,6 +3172,7 @@ impl super::TermWindow {
                         if let Some(cell) = params.line.cells().get(cell_idx) {
                             if let Some(block) = BlockKey::from_cell(cell) {
                                 if glyph_color != bg_color {
+                                    self.flush_background_run(&mut layers[0], &mut bg_run)?;
                                     self.populate_block_quad(
                                         block,
                                         gl_state,
@@ -2229,6 +3285,8 @@ impl super::TermWindow {
                 cluster_x_pos += glyph.x_advance.get() as f32;
             }
         }
+        self.flush_background_run(&mut layers[0], &mut bg_run)?;
+        self.flush_decoration_run(&mut layers[0], &mut decoration_run)?;
 
         for (cell_idx, img, glyph_color) in overlay_images {
             self.populate_image_quad(
@@ -2237,7 +3295,7 @@ impl super::TermWindow {
                 &mut layers[2],
                 cell_idx,
                 &params,
-                hsv,
+                params.hsv.image,
                 glyph_color,
             )?;
         }
@@ -2263,7 +3321,7 @@ impl super::TermWindow {
                     ),
                     params.foreground,
                 )?;
-                quad.set_hsv(hsv);
+                quad.set_hsv(params.hsv.background);
             }
 
             if params.stable_line_idx == Some(params.cursor.y)
@@ -2496,8 +3554,9 @@ impl super::TermWindow {
                 let (r1, g1, b1, a) = bg_color.tuple();
                 let (r, g, b, _) = params
                     .config
-                    .resolved_palette
                     .visual_bell
+                    .color
+                    .or(params.config.resolved_palette.visual_bell)
                     .map(|c| c.to_linear_tuple_rgba())
                     .unwrap_or_else(|| fg_color.tuple());
 
@@ -2761,6 +3820,55 @@ impl super::TermWindow {
         }
         Ok(())
     }
+
+    fn ensure_contrast(&self, fg: RgbColor, bg: RgbColor, min_ratio: f32) -> RgbColor {
+        let mut cache = self.contrast_cache.borrow_mut();
+        if let Some(adjusted) = cache.get(&(fg, bg)) {
+            return *adjusted;
+        }
+        let adjusted = crate::contrast::ensure_contrast(fg, bg, min_ratio);
+        cache.put((fg, bg), adjusted);
+        adjusted
+    }
+}
+
+/// Returns true if the split forms part of the border of the pane
+fn split_borders_pane(split: &PositionedSplit, pos: &PositionedPane) -> bool {
+    let overlaps = |start: usize, len: usize, other_start: usize, other_len: usize| {
+        start < other_start + other_len && other_start < start + len
+    };
+    match split.direction {
+        SplitDirection::Horizontal => {
+            (pos.left + pos.width == split.left || pos.left == split.left + 1)
+                && overlaps(pos.top, pos.height, split.top, split.size)
+        }
+        SplitDirection::Vertical => {
+            (pos.top + pos.height == split.top || pos.top == split.top + 1)
+                && overlaps(pos.left, pos.width, split.left, split.size)
+        }
+    }
+}
+
+/// Returns the text of the banner for a pane that is held open after
+/// its process exited, padded so that it fills a line of `cols` cells
+fn exit_banner_text(status: &ExitStatus, cols: usize) -> String {
+    let text = format!("Process {} \u{2014} press Enter to close", status);
+    let width = unicode_column_width(&text, None);
+    format!("{}{}", text, " ".repeat(cols.saturating_sub(width)))
+}
+
+/// The banner uses the pane's palette: red for a failure, or green
+/// when exit_behavior="Hold" is holding a successful process
+fn exit_banner_attrs(status: &ExitStatus) -> CellAttributes {
+    let mut attrs = CellAttributes::default();
+    attrs
+        .set_foreground(ColorAttribute::PaletteIndex(0))
+        .set_background(ColorAttribute::PaletteIndex(if status.success() {
+            2
+        } else {
+            1
+        }));
+    attrs
 }
 
 fn rgbcolor_to_window_color(color: RgbColor) -> LinearRgba {
@@ -127,10 +127,13 @@ impl super::TermWindow {
 
         let config = &self.config;
 
-        let tab_bar_height = if self.show_tab_bar {
-            self.tab_bar_pixel_height().unwrap_or(0.)
+        let (tab_bar_height, tab_bar_width) = if self.show_tab_bar {
+            (
+                self.tab_bar_pixel_height().unwrap_or(0.),
+                self.tab_bar_pixel_width().unwrap_or(0.),
+            )
         } else {
-            0.
+            (0., 0.)
         };
 
         let (size, dims) = if let Some(cell_dims) = scale_changed_cells {
@@ -166,7 +169,8 @@ impl super::TermWindow {
                 + tab_bar_height as u16;
 
             let pixel_width = (cols * self.render_metrics.cell_size.width as u16)
-                + (padding_left + padding_right);
+                + (padding_left + padding_right)
+                + tab_bar_width as u16;
 
             let dims = Dimensions {
                 pixel_width: pixel_width as usize,
@@ -195,7 +199,8 @@ impl super::TermWindow {
 
             let avail_width = dimensions
                 .pixel_width
-                .saturating_sub((padding_left + padding_right) as usize);
+                .saturating_sub((padding_left + padding_right) as usize)
+                .saturating_sub(tab_bar_width as usize);
             let avail_height = dimensions
                 .pixel_height
                 .saturating_sub((padding_top + padding_bottom) as usize)
@@ -379,10 +384,13 @@ impl super::TermWindow {
         };
 
         let show_tab_bar = config.enable_tab_bar && !config.hide_tab_bar_if_only_one_tab;
-        let tab_bar_height = if show_tab_bar {
-            self.tab_bar_pixel_height()? as usize
+        let (tab_bar_height, tab_bar_width) = if show_tab_bar {
+            (
+                self.tab_bar_pixel_height()? as usize,
+                self.tab_bar_pixel_width()? as usize,
+            )
         } else {
-            0
+            (0, 0)
         };
 
         let h_context = DimensionContext {
@@ -402,7 +410,8 @@ impl super::TermWindow {
         let dimensions = Dimensions {
             pixel_width: ((terminal_size.cols * render_metrics.cell_size.width as u16)
                 + padding_left
-                + effective_right_padding(&config, h_context)) as usize,
+                + effective_right_padding(&config, h_context)) as usize
+                + tab_bar_width,
             pixel_height: ((terminal_size.rows * render_metrics.cell_size.height as u16)
                 + padding_top
                 + padding_bottom) as usize
@@ -432,6 +441,16 @@ impl super::TermWindow {
             },
         )
     }
+
+    pub fn min_scroll_bar_height(&self) -> f32 {
+        self.config
+            .min_scroll_bar_height
+            .evaluate_as_pixels(DimensionContext {
+                pixel_cell: self.render_metrics.cell_size.height as f32,
+                dpi: self.dimensions.dpi as f32,
+                pixel_max: self.dimensions.pixel_height as f32,
+            })
+    }
 }
 
 /// Computes the effective padding for the RHS.
@@ -1,13 +1,17 @@
+use crate::scripting::pane::PaneObject;
 use crate::termwindow::{ClipboardHelper, MuxWindowId};
 use anyhow::{anyhow, bail};
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
-use config::TermConfig;
+use config::{configuration, CwdInheritance, TermConfig};
 use mux::activity::Activity;
 use mux::domain::DomainState;
+use mux::pane::Pane;
 use mux::tab::SplitDirection;
 use mux::Mux;
 use percent_encoding::percent_decode_str;
 use portable_pty::{CommandBuilder, PtySize};
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::Arc;
 
 #[derive(Copy, Debug, Clone, Eq, PartialEq)]
@@ -84,51 +88,50 @@ impl super::TermWindow {
             src_window_id
         };
 
-        let (domain, cwd) = match spawn.domain {
-            SpawnTabDomain::DefaultDomain => {
-                let cwd = mux
-                    .get_active_tab_for_window(src_window_id)
-                    .and_then(|tab| tab.get_active_pane())
-                    .and_then(|pane| pane.get_current_working_dir());
-                (mux.default_domain().clone(), cwd)
-            }
+        let source_pane = mux
+            .get_active_tab_for_window(src_window_id)
+            .and_then(|tab| tab.get_active_pane());
+
+        let domain = match spawn.domain {
+            SpawnTabDomain::DefaultDomain => mux.default_domain().clone(),
             SpawnTabDomain::CurrentPaneDomain => {
                 if spawn_where == SpawnWhere::NewWindow {
                     // CurrentPaneDomain is the default value for the spawn domain.
                     // It doesn't make sense to use it when spawning a new window,
                     // so we treat it as DefaultDomain instead.
-                    let cwd = mux
-                        .get_active_tab_for_window(src_window_id)
-                        .and_then(|tab| tab.get_active_pane())
-                        .and_then(|pane| pane.get_current_working_dir());
-                    (mux.default_domain().clone(), cwd)
+                    mux.default_domain().clone()
                 } else {
-                    let tab = match mux.get_active_tab_for_window(src_window_id) {
-                        Some(tab) => tab,
+                    let pane = match source_pane.as_ref() {
+                        Some(pane) => pane,
                         None => bail!("window has no tabs?"),
                     };
-                    let pane = tab
-                        .get_active_pane()
-                        .ok_or_else(|| anyhow!("current tab has no pane!?"))?;
-                    (
-                        mux.get_domain(pane.domain_id())
-                            .ok_or_else(|| anyhow!("current tab has unresolvable domain id!?"))?,
-                        pane.get_current_working_dir(),
-                    )
+                    mux.get_domain(pane.domain_id())
+                        .ok_or_else(|| anyhow!("current tab has unresolvable domain id!?"))?
                 }
             }
-            SpawnTabDomain::DomainName(name) => (
-                mux.get_domain_by_name(&name).ok_or_else(|| {
+            SpawnTabDomain::DomainName(ref name) => {
+                mux.get_domain_by_name(name).ok_or_else(|| {
                     anyhow!("spawn_tab called with unresolvable domain name {}", name)
-                })?,
-                None,
-            ),
+                })?
+            }
         };
 
         if domain.state() == DomainState::Detached {
             bail!("Cannot spawn a tab into a Detached domain");
         }
 
+        // The working directory of the active pane is only meaningful
+        // to a new pane in the same domain
+        let inherited_cwd = match source_pane.as_ref() {
+            Some(pane) if pane.domain_id() == domain.domain_id() => {
+                match configuration().cwd_inheritance_for_domain(domain.domain_name()) {
+                    CwdInheritance::CurrentPaneDir => pane.get_current_working_dir(),
+                    CwdInheritance::DomainDefault => None,
+                }
+            }
+            _ => None,
+        };
+
         let cwd = if let Some(cwd) = spawn.cwd.as_ref() {
             Some(cwd.to_str().map(|s| s.to_owned()).ok_or_else(|| {
                 anyhow!(
@@ -137,7 +140,7 @@ impl super::TermWindow {
                 )
             })?)
         } else {
-            match cwd {
+            match inherited_cwd {
                 Some(url) if url.scheme() == "file" => {
                     if let Ok(path) = percent_decode_str(url.path()).decode_utf8() {
                         let path = path.into_owned();
@@ -157,6 +160,7 @@ impl super::TermWindow {
                 Some(_) | None => None,
             }
         };
+        let cwd = Self::resolve_spawn_cwd(&spawn, cwd, source_pane.as_ref());
 
         let cmd_builder = if let Some(args) = spawn.args {
             let mut builder = CommandBuilder::from_argv(args.iter().map(Into::into).collect());
@@ -172,21 +176,27 @@ impl super::TermWindow {
         };
 
         let clipboard: Arc<dyn wezterm_term::Clipboard> = Arc::new(clipboard);
+        // A pane that is held open after its process exited is
+        // replaced by the pane that is split from it
+        let mut replaced = None;
 
         match spawn_where {
             SpawnWhere::SplitPane(direction) => {
                 let mux = Mux::get().unwrap();
                 if let Some(tab) = mux.get_active_tab_for_window(target_window_id) {
-                    let pane = tab
+                    let active = tab
                         .get_active_pane()
                         .ok_or_else(|| anyhow!("tab to have a pane"))?;
 
                     log::trace!("doing split_pane");
                     let pane = domain
-                        .split_pane(cmd_builder, cwd, tab.tab_id(), pane.pane_id(), direction)
+                        .split_pane(cmd_builder, cwd, tab.tab_id(), active.pane_id(), direction)
                         .await?;
                     pane.set_config(term_config);
                     pane.set_clipboard(&clipboard);
+                    if active.get_exit_status().is_some() {
+                        replaced.replace(active);
+                    }
                 } else {
                     bail!("there is no active tab while splitting pane!?");
                 }
@@ -215,9 +225,56 @@ impl super::TermWindow {
 
         drop(activity);
 
+        if let Some(pane) = replaced {
+            pane.kill();
+            mux.prune_dead_windows();
+        }
+
         Ok(())
     }
 
+    /// Calls the `resolve-spawn-cwd` event, which can replace the
+    /// directory that the new pane is about to start in.
+    /// The handler is passed the spawn command, with its `cwd` set to
+    /// that directory, and the active pane.  It returns the directory to
+    /// use instead, `false` to use the default directory of the domain,
+    /// or nothing to keep the proposed directory.
+    fn resolve_spawn_cwd(
+        spawn: &SpawnCommand,
+        cwd: Option<String>,
+        pane: Option<&Rc<dyn Pane>>,
+    ) -> Option<String> {
+        let proposed = cwd.clone();
+        let result = config::run_immediate_with_lua_config(|lua| {
+            let lua = match lua {
+                Some(lua) if config::lua::has_event_handlers(&lua, "resolve-spawn-cwd") => lua,
+                _ => return Ok(cwd),
+            };
+            let mut spawn = spawn.clone();
+            spawn.cwd = cwd.as_ref().map(PathBuf::from);
+            let pane = pane.map(PaneObject::new);
+            match config::lua::emit_sync_callback(
+                &*lua,
+                ("resolve-spawn-cwd".to_string(), (spawn, pane)),
+            )? {
+                mlua::Value::Nil => Ok(cwd),
+                mlua::Value::Boolean(false) => Ok(None),
+                mlua::Value::String(dir) => Ok(Some(dir.to_str()?.to_string())),
+                other => bail!(
+                    "expected a string, false or nil, but got {}",
+                    other.type_name()
+                ),
+            }
+        });
+        match result {
+            Ok(cwd) => cwd,
+            Err(err) => {
+                log::error!("resolve-spawn-cwd: {:#}", err);
+                proposed
+            }
+        }
+    }
+
     pub fn spawn_tab(&mut self, domain: &SpawnTabDomain) {
         self.spawn_command(
             &SpawnCommand {
@@ -0,0 +1,344 @@
+//! Dragging a tab along the tab bar moves it past the tabs that it
+//! is dragged over.  Once the pointer leaves the tab bar a ghost of the
+//! tab follows it; dropping the tab onto another window moves the tab
+//! into that window, and dropping it away from all of the windows tears
+//! it off into a new window at the drop position.
+//! Each window records where it and its tabs are on the screen, so that
+//! the window that owns the drag can tell which window the tab was
+//! dropped onto.  The window position is inferred from the most recent
+//! mouse event that the window received.
+use crate::tabbar::TabBarItem;
+use crate::termwindow::{UIItem, UIItemType};
+use ::window::{MouseEvent, Point, Rect, ScreenPoint, Size, Window, WindowOps};
+use mux::tab::TabId;
+use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TabRect {
+    x: isize,
+    y: isize,
+    width: isize,
+    height: isize,
+}
+
+/// Where a window and its tabs are on the screen
+struct DropTarget {
+    /// The position of the top left corner of the window
+    origin: ScreenPoint,
+    width: isize,
+    height: isize,
+    /// The tabs, relative to the window, in tab order
+    tabs: Vec<TabRect>,
+}
+
+/// Where a torn off tab should appear, along with the config overrides
+/// that it inherits from the window that it was torn off from
+struct TearOff {
+    position: ScreenPoint,
+    config_overrides: serde_json::Value,
+}
+
+thread_local! {
+    static DROP_TARGETS: RefCell<HashMap<MuxWindowId, DropTarget>> =
+        RefCell::new(HashMap::new());
+    static TEAR_OFFS: RefCell<HashMap<TabId, TearOff>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Default)]
+pub struct TabDragState {
+    /// Where to paint the ghost of the dragged tab, which is only
+    /// shown while the pointer is away from the tab bar
+    pub ghost: Option<Rect>,
+    /// Set when the dragged tab is moved along the tab bar, so that
+    /// it isn't moved again until the tab bar has been repainted
+    moved_since_paint: bool,
+}
+
+/// Returns the index at which a tab that is dropped at `x`, `y` should
+/// be placed among `tabs`, or None if the point is clear of the tabs,
+/// in which case the tab should be placed after them
+fn drop_index(tabs: &[TabRect], x: isize, y: isize) -> Option<usize> {
+    let left = tabs.iter().map(|t| t.x).min()?;
+    let top = tabs.iter().map(|t| t.y).min()?;
+    let right = tabs.iter().map(|t| t.x + t.width).max()?;
+    let bottom = tabs.iter().map(|t| t.y + t.height).max()?;
+    if x < left || x >= right || y < top || y >= bottom {
+        return None;
+    }
+    // The tab goes after those rows that end above the point, and
+    // after the tabs in its row whose middle is to its left
+    Some(
+        tabs.iter()
+            .filter(|t| {
+                if t.y + t.height <= y {
+                    true
+                } else if t.y > y {
+                    false
+                } else {
+                    t.x + t.width / 2 < x
+                }
+            })
+            .count(),
+    )
+}
+
+fn dragged_tab_idx(item: &UIItem) -> Option<usize> {
+    match item.item_type {
+        UIItemType::TabBar(TabBarItem::Tab { tab_idx, .. }) => Some(tab_idx),
+        _ => None,
+    }
+}
+
+impl super::TermWindow {
+    /// Records where this window and its tabs are on the screen
+    pub fn update_drop_target(&self) {
+        let event = match self.current_mouse_event.as_ref() {
+            Some(event) => event,
+            None => return,
+        };
+        let origin = ScreenPoint::new(
+            event.screen_coords.x - event.coords.x,
+            event.screen_coords.y - event.coords.y,
+        );
+        let mut tabs: Vec<(usize, TabRect)> = self
+            .ui_items
+            .iter()
+            .filter_map(|item| {
+                dragged_tab_idx(item).map(|tab_idx| {
+                    (
+                        tab_idx,
+                        TabRect {
+                            x: item.x as isize,
+                            y: item.y as isize,
+                            width: item.width as isize,
+                            height: item.height as isize,
+                        },
+                    )
+                })
+            })
+            .collect();
+        tabs.sort_by_key(|(tab_idx, _)| *tab_idx);
+        let target = DropTarget {
+            origin,
+            width: self.dimensions.pixel_width as isize,
+            height: self.dimensions.pixel_height as isize,
+            tabs: tabs.into_iter().map(|(_, rect)| rect).collect(),
+        };
+        DROP_TARGETS.with(|targets| {
+            targets.borrow_mut().insert(self.mux_window_id, target);
+        });
+    }
+
+    pub fn forget_drop_target(&self) {
+        DROP_TARGETS.with(|targets| {
+            targets.borrow_mut().remove(&self.mux_window_id);
+        });
+    }
+
+    /// Called when the tab bar has been painted
+    pub fn tab_bar_was_painted(&mut self) {
+        self.tab_drag.moved_since_paint = false;
+        self.update_drop_target();
+    }
+
+    /// Called for a newly created window.  If its tab was torn off from
+    /// another window, it takes on the config overrides of that window
+    /// and moves to the position at which the tab was dropped
+    pub fn apply_tear_off(&mut self, window: &Window) {
+        let mux = Mux::get().unwrap();
+        let tab_id = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab.tab_id(),
+            None => return,
+        };
+        let tear_off = match TEAR_OFFS.with(|tear_offs| tear_offs.borrow_mut().remove(&tab_id)) {
+            Some(tear_off) => tear_off,
+            None => return,
+        };
+        if !tear_off.config_overrides.is_null() {
+            self.config_overrides = tear_off.config_overrides;
+            self.config_was_reloaded();
+        }
+        window.set_window_position(tear_off.position);
+    }
+
+    pub fn start_tab_drag(&mut self, item: UIItem, event: MouseEvent) {
+        self.tab_drag = TabDragState::default();
+        self.dragging.replace((item, event));
+    }
+
+    pub fn drag_tab(
+        &mut self,
+        mut item: UIItem,
+        start_event: MouseEvent,
+        event: MouseEvent,
+        context: &dyn WindowOps,
+    ) {
+        let tab_idx = match dragged_tab_idx(&item) {
+            Some(tab_idx) => tab_idx,
+            None => return,
+        };
+        let x = event.coords.x;
+        let y = event.coords.y;
+        let over_tab_bar = self
+            .ui_items
+            .iter()
+            .any(|i| matches!(i.item_type, UIItemType::TabBar(_)) && i.hit_test(x, y));
+
+        if over_tab_bar {
+            self.tab_drag.ghost = None;
+            let vertical = self.config.resolved_tab_bar_position().is_vertical();
+            let target = self.ui_items.iter().find_map(|i| {
+                let idx = dragged_tab_idx(i)?;
+                if idx == tab_idx || !i.hit_test(x, y) {
+                    return None;
+                }
+                // Wait for the pointer to pass the middle of the other
+                // tab, so that tabs of different sizes don't keep
+                // swapping places with each other
+                let (pos, middle) = if vertical {
+                    (y, (i.y + i.height / 2) as isize)
+                } else {
+                    (x, (i.x + i.width / 2) as isize)
+                };
+                let past_middle = if idx > tab_idx {
+                    pos >= middle
+                } else {
+                    pos <= middle
+                };
+                if past_middle {
+                    Some(idx)
+                } else {
+                    None
+                }
+            });
+            if let Some(idx) = target {
+                if !self.tab_drag.moved_since_paint && self.move_tab(idx).is_ok() {
+                    item.item_type = UIItemType::TabBar(TabBarItem::Tab {
+                        tab_idx: idx,
+                        active: true,
+                    });
+                    self.tab_drag.moved_since_paint = true;
+                }
+            }
+        } else {
+            // The ghost keeps the pointer at the same place within
+            // the tab as when the drag started
+            let dx = start_event.coords.x - item.x as isize;
+            let dy = start_event.coords.y - item.y as isize;
+            self.tab_drag.ghost.replace(Rect::new(
+                Point::new(x - dx, y - dy),
+                Size::new(item.width as isize, item.height as isize),
+            ));
+        }
+
+        context.invalidate();
+        self.dragging.replace((item, start_event));
+    }
+
+    pub fn drop_tab(
+        &mut self,
+        start_event: MouseEvent,
+        event: &MouseEvent,
+        context: &dyn WindowOps,
+    ) {
+        if self.tab_drag.ghost.take().is_none() {
+            // Dropped onto the tab bar; it has already been moved
+            return;
+        }
+        context.invalidate();
+
+        let mux = Mux::get().unwrap();
+        let (tab_id, alone_in_window) = match mux.get_window(self.mux_window_id) {
+            Some(window) => match window.get_active() {
+                Some(tab) => (tab.tab_id(), window.len() == 1),
+                None => return,
+            },
+            None => return,
+        };
+
+        let screen = event.screen_coords;
+        let target = DROP_TARGETS.with(|targets| {
+            targets
+                .borrow()
+                .iter()
+                .filter(|(window_id, _)| **window_id != self.mux_window_id)
+                .find_map(|(window_id, target)| {
+                    let x = screen.x - target.origin.x;
+                    let y = screen.y - target.origin.y;
+                    if x >= 0 && y >= 0 && x < target.width && y < target.height {
+                        Some((*window_id, drop_index(&target.tabs, x, y)))
+                    } else {
+                        None
+                    }
+                })
+        });
+
+        match target {
+            Some((window_id, tab_idx)) => {
+                self.move_tab_to_window(tab_id, Some(window_id), tab_idx);
+            }
+            None => {
+                let x = event.coords.x;
+                let y = event.coords.y;
+                let inside = x >= 0
+                    && y >= 0
+                    && x < self.dimensions.pixel_width as isize
+                    && y < self.dimensions.pixel_height as isize;
+                if inside || alone_in_window {
+                    // Dropped onto its own window, or there is nothing
+                    // to tear it away from
+                    return;
+                }
+                TEAR_OFFS.with(|tear_offs| {
+                    tear_offs.borrow_mut().insert(
+                        tab_id,
+                        TearOff {
+                            position: ScreenPoint::new(
+                                screen.x - start_event.coords.x,
+                                screen.y - start_event.coords.y,
+                            ),
+                            config_overrides: self.config_overrides.clone(),
+                        },
+                    );
+                });
+                self.move_tab_to_window(tab_id, None, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drop_position() {
+        let tab = |x, y| TabRect {
+            x,
+            y,
+            width: 100,
+            height: 20,
+        };
+
+        // A tab bar along the top of the window
+        let row = vec![tab(0, 0), tab(100, 0), tab(200, 0)];
+        assert_eq!(drop_index(&row, 10, 10), Some(0));
+        assert_eq!(drop_index(&row, 60, 10), Some(1));
+        assert_eq!(drop_index(&row, 140, 10), Some(1));
+        assert_eq!(drop_index(&row, 299, 10), Some(3));
+        // Beyond the tabs, or below the tab bar
+        assert_eq!(drop_index(&row, 350, 10), None);
+        assert_eq!(drop_index(&row, 60, 30), None);
+
+        // A tab bar down the side of the window
+        let column = vec![tab(0, 0), tab(0, 20), tab(0, 40)];
+        assert_eq!(drop_index(&column, 10, 5), Some(0));
+        assert_eq!(drop_index(&column, 10, 25), Some(1));
+        assert_eq!(drop_index(&column, 60, 45), Some(3));
+
+        assert_eq!(drop_index(&[], 0, 0), None);
+    }
+}
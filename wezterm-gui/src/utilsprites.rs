@@ -4,6 +4,7 @@ use ::window::bitmaps::{BitmapImage, Image, Texture2d};
 use ::window::color::SrgbaPixel;
 use ::window::{Point, Rect, Size};
 use anyhow::Context;
+use config::{ConfigHandle, DimensionContext};
 use std::rc::Rc;
 use wezterm_font::units::*;
 use wezterm_font::{FontConfiguration, FontMetrics};
@@ -15,9 +16,104 @@ pub struct RenderMetrics {
     pub descender_plus_two: IntPixelLength,
     pub underline_height: IntPixelLength,
     pub strike_row: IntPixelLength,
+    /// The distance from the peak to the trough of a curly underline
+    pub curly_height: IntPixelLength,
+    /// The horizontal length of one cycle of a curly underline.
+    /// This always divides evenly into the cell width.
+    pub curly_period: f32,
     pub cell_size: Size,
 }
 
+/// The rows at which the various line decorations are drawn
+struct LineRows {
+    descender_row: IntPixelLength,
+    descender_plus_two: IntPixelLength,
+    underline_height: IntPixelLength,
+    strike_row: IntPixelLength,
+    curly_height: IntPixelLength,
+    curly_period: f32,
+}
+
+impl LineRows {
+    /// Computes the line positions for a cell of the specified size
+    /// whose baseline is `baseline` pixels from the top of the cell.
+    /// The values from the font metrics are used unless they are
+    /// overridden by `config`.  Everything is rounded to whole device
+    /// pixels so that the lines are crisp and don't shimmer as the
+    /// display scrolls.
+    fn new(
+        metrics: &FontMetrics,
+        config: Option<&ConfigHandle>,
+        dpi: f32,
+        cell_width: usize,
+        cell_height: usize,
+        baseline: f64,
+    ) -> Self {
+        let v_context = DimensionContext {
+            dpi,
+            pixel_max: cell_height as f32,
+            pixel_cell: cell_height as f32,
+        };
+        let h_context = DimensionContext {
+            dpi,
+            pixel_max: cell_width as f32,
+            pixel_cell: cell_width as f32,
+        };
+        let cell_height = cell_height as isize;
+
+        let underline_height = match config.and_then(|c| c.underline_thickness) {
+            Some(thickness) => thickness.evaluate_as_pixels(v_context) as isize,
+            None => metrics.underline_thickness.get().round() as isize,
+        }
+        .max(1)
+        .min(cell_height);
+
+        let font_descender_row = (baseline - metrics.underline_position.get()) as isize;
+        let descender_row = match config.and_then(|c| c.underline_position) {
+            Some(position) => {
+                (baseline.round() as f32 + position.evaluate_as_pixels(v_context)) as isize
+            }
+            None => font_descender_row,
+        }
+        .max(0)
+        .min(cell_height - underline_height);
+
+        // The second line of a double underline is placed so that there
+        // is always a gap of at least 1 pixel between the two lines;
+        // if there isn't room below the first line, the first line is
+        // moved up instead (see draw_double in glyphcache.rs)
+        let gap = underline_height.max(1);
+        let descender_plus_two =
+            (descender_row + underline_height + gap).min(cell_height - underline_height);
+
+        let curly_height = match config.and_then(|c| c.curly_underline.height) {
+            Some(height) => height.evaluate_as_pixels(v_context) as isize,
+            None => (cell_height - descender_row) / 2,
+        }
+        .max(1);
+        // The wave is drawn separately in each cell, so the period is
+        // adjusted to fit a whole number of cycles into a cell in order
+        // for the wave to join up with its neighbors
+        let curly_period = match config.and_then(|c| c.curly_underline.period) {
+            Some(period) => {
+                let period = period.evaluate_as_pixels(h_context).max(1.);
+                let cycles = (cell_width as f32 / period).round().max(1.);
+                cell_width as f32 / cycles
+            }
+            None => cell_width as f32,
+        };
+
+        Self {
+            descender_row,
+            descender_plus_two,
+            underline_height,
+            strike_row: font_descender_row / 2,
+            curly_height,
+            curly_period,
+        }
+    }
+}
+
 impl RenderMetrics {
     pub fn with_font_metrics(metrics: &FontMetrics) -> Self {
         let (cell_height, cell_width) = (
@@ -25,21 +121,24 @@ impl RenderMetrics {
             metrics.cell_width.get().ceil() as usize,
         );
 
-        let underline_height = metrics.underline_thickness.get().round().max(1.) as isize;
-
-        let descender_row =
-            (cell_height as f64 + (metrics.descender - metrics.underline_position).get()) as isize;
-        let descender_plus_two =
-            (2 * underline_height + descender_row).min(cell_height as isize - underline_height);
-        let strike_row = descender_row / 2;
+        let rows = LineRows::new(
+            metrics,
+            None,
+            0.,
+            cell_width,
+            cell_height,
+            cell_height as f64 + metrics.descender.get(),
+        );
 
         Self {
             descender: metrics.descender,
-            descender_row,
-            descender_plus_two,
-            strike_row,
+            descender_row: rows.descender_row,
+            descender_plus_two: rows.descender_plus_two,
+            strike_row: rows.strike_row,
+            curly_height: rows.curly_height,
+            curly_period: rows.curly_period,
             cell_size: Size::new(cell_width as isize, cell_height as isize),
-            underline_height,
+            underline_height: rows.underline_height,
         }
     }
 
@@ -48,7 +147,8 @@ impl RenderMetrics {
             .default_font_metrics()
             .context("failed to get font metrics!?")?;
 
-        let line_height = fonts.config().line_height;
+        let config = fonts.config();
+        let line_height = config.line_height;
 
         let (cell_height, cell_width) = (
             (metrics.cell_height.get() * line_height).ceil() as usize,
@@ -59,22 +159,24 @@ impl RenderMetrics {
         // such that we are horizontally centered.
         let line_height_y_adjust = (cell_height as f64 - metrics.cell_height.get().ceil()) / 2.;
 
-        let underline_height = metrics.underline_thickness.get().round().max(1.) as isize;
-
-        let descender_row = (cell_height as f64
-            + (metrics.descender - metrics.underline_position).get()
-            - line_height_y_adjust) as isize;
-        let descender_plus_two =
-            (2 * underline_height + descender_row).min(cell_height as isize - underline_height);
-        let strike_row = descender_row / 2;
+        let rows = LineRows::new(
+            &metrics,
+            Some(&config),
+            fonts.get_dpi() as f32,
+            cell_width,
+            cell_height,
+            cell_height as f64 + metrics.descender.get() - line_height_y_adjust,
+        );
 
         Ok(Self {
             descender: metrics.descender - PixelLength::new(line_height_y_adjust),
-            descender_row,
-            descender_plus_two,
-            strike_row,
+            descender_row: rows.descender_row,
+            descender_plus_two: rows.descender_plus_two,
+            strike_row: rows.strike_row,
+            curly_height: rows.curly_height,
+            curly_period: rows.curly_period,
             cell_size: Size::new(cell_width as isize, cell_height as isize),
-            underline_height,
+            underline_height: rows.underline_height,
         })
     }
 }
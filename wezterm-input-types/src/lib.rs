@@ -157,7 +157,9 @@ pub enum MouseEventKind {
     Move,
     Press(MousePress),
     Release(MousePress),
+    /// Positive values scroll up, negative values scroll down
     VertWheel(i16),
+    /// Positive values scroll left, negative values scroll right
     HorzWheel(i16),
 }
 
@@ -0,0 +1,109 @@
+//! The connections of the clients of the mux, which are
+//! reported by `wezterm cli list-clients`.
+pub use codec::ClientInfo;
+use codec::{ClientHello, Compression, CompressionStats};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+struct Connection {
+    username: String,
+    hostname: String,
+    pid: u32,
+    connected: Instant,
+    last_input: Instant,
+    compression: bool,
+    stats: CompressionStats,
+}
+
+lazy_static::lazy_static! {
+    static ref CLIENTS: Mutex<BTreeMap<usize, Connection>> = Mutex::new(BTreeMap::new());
+}
+static CONNECTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the connections that are currently open
+pub fn clients() -> Vec<ClientInfo> {
+    let now = Instant::now();
+    CLIENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(&connection_id, conn)| ClientInfo {
+            connection_id,
+            username: conn.username.clone(),
+            hostname: conn.hostname.clone(),
+            pid: conn.pid,
+            connected_secs: (now - conn.connected).as_secs(),
+            idle_secs: (now - conn.last_input).as_secs(),
+            compression: conn.compression,
+            stats: conn.stats,
+            compression_ratio: conn.stats.ratio(),
+        })
+        .collect()
+}
+
+/// The entry of a connection in the registry, which is
+/// removed when this is dropped
+pub(crate) struct Registration {
+    id: usize,
+}
+
+impl Registration {
+    pub fn new() -> Self {
+        let id = CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        CLIENTS.lock().unwrap().insert(
+            id,
+            Connection {
+                username: String::new(),
+                hostname: String::new(),
+                pid: 0,
+                connected: now,
+                last_input: now,
+                compression: false,
+                stats: CompressionStats::default(),
+            },
+        );
+        Self { id }
+    }
+
+    fn with_connection<F: FnOnce(&mut Connection)>(&self, func: F) {
+        if let Some(conn) = CLIENTS.lock().unwrap().get_mut(&self.id) {
+            func(conn);
+        }
+    }
+
+    /// Records who the client says it is
+    pub fn hello(&self, hello: &ClientHello) {
+        self.with_connection(|conn| {
+            conn.username = hello.username.clone();
+            conn.hostname = hello.hostname.clone();
+            conn.pid = hello.pid;
+        });
+    }
+
+    /// Records that a PDU was received, along with the
+    /// current statistics of the connection
+    pub fn received(&self, compression: &Compression) {
+        self.with_connection(|conn| {
+            conn.last_input = Instant::now();
+            conn.compression = compression.is_enabled();
+            conn.stats = compression.stats();
+        });
+    }
+
+    /// Records the current statistics of the connection
+    pub fn sent(&self, compression: &Compression) {
+        self.with_connection(|conn| {
+            conn.compression = compression.is_enabled();
+            conn.stats = compression.stats();
+        });
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        CLIENTS.lock().unwrap().remove(&self.id);
+    }
+}
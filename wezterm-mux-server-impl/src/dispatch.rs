@@ -1,8 +1,9 @@
+use crate::clients::Registration;
 use crate::sessionhandler::{PduSender, SessionHandler};
 use crate::UnixStream;
 use anyhow::Context;
 use async_ossl::AsyncSslStream;
-use codec::{DecodedPdu, Pdu};
+use codec::{ClientHelloResponse, Compression, DecodedPdu, Pdu};
 use futures::FutureExt;
 use mux::{Mux, MuxNotification};
 use smol::prelude::*;
@@ -55,6 +56,9 @@ where
         }
     });
     let mut handler = SessionHandler::new(pdu_sender);
+    let registration = Registration::new();
+    // Uncompressed until the client asks for it in its ClientHello
+    let mut compression = Compression::default();
 
     {
         let mux = Mux::get().expect("to be running on gui thread");
@@ -68,15 +72,41 @@ where
 
         match smol::future::or(rx_msg, wait_for_read).await {
             Ok(Item::Readable) => {
-                let decoded = Pdu::decode_async(&mut stream).await?;
-                handler.process_one(decoded);
+                let decoded = Pdu::decode_async_with(&mut stream, &mut compression).await?;
+                registration.received(&compression);
+                match decoded.pdu {
+                    // This is handled here rather than by the session
+                    // handler, as it changes how the connection is encoded
+                    Pdu::ClientHello(hello) => {
+                        registration.hello(&hello);
+                        handler.set_delta_lines(hello.delta_lines);
+                        Pdu::ClientHelloResponse(ClientHelloResponse {
+                            compression: hello.compression,
+                            delta_lines: hello.delta_lines,
+                        })
+                        .encode_async_with(&mut stream, decoded.serial, &mut compression)
+                        .await?;
+                        stream.flush().await.context("flushing PDU to client")?;
+                        if hello.compression {
+                            compression.enable();
+                        } else {
+                            compression.disable();
+                        }
+                        registration.sent(&compression);
+                    }
+                    pdu => handler.process_one(DecodedPdu {
+                        serial: decoded.serial,
+                        pdu,
+                    }),
+                }
             }
             Ok(Item::WritePdu(decoded)) => {
                 decoded
                     .pdu
-                    .encode_async(&mut stream, decoded.serial)
+                    .encode_async_with(&mut stream, decoded.serial, &mut compression)
                     .await?;
                 stream.flush().await.context("flushing PDU to client")?;
+                registration.sent(&compression);
             }
             Ok(Item::Notif(MuxNotification::PaneOutput(pane_id))) => {
                 handler.schedule_pane_push(pane_id);
@@ -84,9 +114,10 @@ where
             Ok(Item::Notif(MuxNotification::PaneAdded(_pane_id))) => {}
             Ok(Item::Notif(MuxNotification::PaneRemoved(pane_id))) => {
                 Pdu::PaneRemoved(codec::PaneRemoved { pane_id })
-                    .encode_async(&mut stream, 0)
+                    .encode_async_with(&mut stream, 0, &mut compression)
                     .await?;
                 stream.flush().await.context("flushing PDU to client")?;
+                registration.sent(&compression);
             }
             Ok(Item::Notif(MuxNotification::Alert { pane_id, alert })) => {
                 {
@@ -99,6 +130,10 @@ where
             Ok(Item::Notif(MuxNotification::WindowRemoved(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowCreated(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowInvalidated(_window_id))) => {}
+            Ok(Item::Notif(MuxNotification::PaneMoved { .. })) => {}
+            Ok(Item::Notif(MuxNotification::PaneMonitor { .. })) => {}
+            Ok(Item::Notif(MuxNotification::DomainStateChanged(_domain_id))) => {}
+            Ok(Item::Notif(MuxNotification::DomainReconnected { .. })) => {}
             Ok(Item::Notif(MuxNotification::Empty)) => {}
             Err(err) => {
                 log::error!("process_async Err {}", err);
@@ -3,10 +3,13 @@ use std::os::unix::net::{UnixListener, UnixStream};
 #[cfg(windows)]
 use uds_windows::{UnixListener, UnixStream};
 
+pub mod clients;
 pub mod dispatch;
 pub mod local;
 pub mod pki;
+pub mod sentlines;
 pub mod sessionhandler;
+pub mod tlsstatus;
 
 lazy_static::lazy_static! {
     pub static ref PKI: pki::Pki = pki::Pki::init().expect("failed to initialize PKI");
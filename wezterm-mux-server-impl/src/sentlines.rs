@@ -0,0 +1,244 @@
+//! Tracks the lines of a pane that were sent to a client that negotiated
+//! `delta_lines`, so that when they change only the cells that changed
+//! need to be sent, as a `LineDelta` from the version that the client
+//! has.  The PDUs arrive in the order that they were sent, so a line is
+//! known to the client as soon as it has been sent; each time it is sent,
+//! in full or as a delta, it is given a new generation, which lets the
+//! client notice when it doesn't have the version that a delta applies
+//! to and fetch the whole line instead.
+use codec::LineDelta;
+use std::collections::HashMap;
+use std::ops::Range;
+use wezterm_term::{Line, StableRowIndex};
+
+struct SentLine {
+    generation: u64,
+    line: Line,
+}
+
+/// The lines that need to be sent to bring the client up to date
+#[derive(Default, Debug)]
+pub struct LineUpdates {
+    /// The lines that have to be sent in full
+    pub lines: Vec<(StableRowIndex, Line)>,
+    /// The generations of `lines`
+    pub generations: Vec<(StableRowIndex, u64)>,
+    pub deltas: Vec<LineDelta>,
+}
+
+#[derive(Default)]
+pub struct SentLines {
+    last_generation: u64,
+    lines: HashMap<StableRowIndex, SentLine>,
+}
+
+impl std::fmt::Debug for SentLines {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(fmt, "SentLines {{ lines: {} }}", self.lines.len())
+    }
+}
+
+impl SentLines {
+    /// Works out how to bring the client up to date with `lines`, which
+    /// are the current lines of the viewport, and records them as sent.
+    /// Lines outside of the viewport are forgotten, so that they are sent
+    /// in full if they come back into it.
+    pub fn compute_updates(
+        &mut self,
+        viewport: Range<StableRowIndex>,
+        lines: Vec<(StableRowIndex, Line)>,
+    ) -> LineUpdates {
+        self.lines.retain(|row, _| viewport.contains(row));
+
+        let mut updates = LineUpdates::default();
+        for (row, line) in lines {
+            let generation = self.last_generation + 1;
+            let delta = match self.lines.get(&row) {
+                Some(sent) => {
+                    match LineDelta::compute(row, sent.generation, generation, &sent.line, &line) {
+                        Some(delta)
+                            if delta.ranges.is_empty() && delta.len == sent.line.cells().len() =>
+                        {
+                            // The client already has it
+                            continue;
+                        }
+                        delta => delta,
+                    }
+                }
+                None => None,
+            };
+            match delta {
+                Some(delta) => updates.deltas.push(delta),
+                None => {
+                    updates.lines.push((row, line.clone()));
+                    updates.generations.push((row, generation));
+                }
+            }
+            self.last_generation = generation;
+            self.lines.insert(row, SentLine { generation, line });
+        }
+        updates
+    }
+
+    /// Forgets the lines in `rows`, which the client fetched by some other
+    /// means and so no longer knows by the generation that they were sent
+    /// as; they are sent in full the next time that changes are pushed
+    pub fn forget(&mut self, rows: &Range<StableRowIndex>) {
+        self.lines.retain(|row, _| !rows.contains(row));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use codec::{Compression, GetPaneRenderChangesResponse, GetPaneRenderDeltaResponse, Pdu};
+    use mux::renderable::{
+        terminal_get_cursor_position, terminal_get_dimensions, terminal_get_lines,
+    };
+    use std::convert::TryInto;
+    use std::sync::Arc;
+    use wezterm_term::color::ColorPalette;
+    use wezterm_term::{Terminal, TerminalConfiguration, TerminalSize};
+
+    #[derive(Debug)]
+    struct TestTermConfig;
+    impl TerminalConfiguration for TestTermConfig {
+        fn color_palette(&self) -> ColorPalette {
+            ColorPalette::default()
+        }
+    }
+
+    /// A recording, in ttyrec format, of vim 9.0 in an 80x24
+    /// xterm-256color terminal as a file is opened, the cursor is moved
+    /// down ten lines, a statement is typed into a new line and vim is
+    /// quit.  Each frame is what was read from the pty in one go.
+    fn vim_session() -> Vec<Vec<u8>> {
+        let mut data: &[u8] = include_bytes!("../testdata/vim.ttyrec");
+        let mut frames = vec![];
+        while !data.is_empty() {
+            // Each frame starts with the seconds, microseconds and
+            // length of its data, as little endian u32s
+            let len = u32::from_le_bytes(data[8..12].try_into().unwrap()) as usize;
+            frames.push(data[12..12 + len].to_vec());
+            data = &data[12 + len..];
+        }
+        frames
+    }
+
+    fn encoded_len(pdu: &Pdu, compression: &mut Compression) -> usize {
+        let mut encoded = vec![];
+        smol::block_on(pdu.encode_async_with(&mut encoded, 0, compression)).unwrap();
+        encoded.len()
+    }
+
+    /// Feeds the vim session both through the full lines that are sent to
+    /// clients that don't negotiate `delta_lines`, and through the deltas,
+    /// checking that applying the deltas reproduces the screen, and that they
+    /// take substantially fewer bytes
+    #[test]
+    fn vim_bandwidth() {
+        let size = TerminalSize {
+            physical_rows: 24,
+            physical_cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        let mut term = Terminal::new(
+            size,
+            Arc::new(TestTermConfig),
+            "WezTerm",
+            "test",
+            Box::new(Vec::new()),
+        );
+
+        let mut sent = SentLines::default();
+        let mut client: HashMap<StableRowIndex, (u64, Line)> = HashMap::new();
+        let mut full_bytes = 0;
+        let mut delta_bytes = 0;
+        let mut full_compressed = Compression::default();
+        full_compressed.enable();
+        let mut delta_compressed = Compression::default();
+        delta_compressed.enable();
+        let mut full_compressed_bytes = 0;
+        let mut delta_compressed_bytes = 0;
+
+        for frame in vim_session() {
+            term.advance_bytes(&frame);
+            let dims = terminal_get_dimensions(&mut term);
+            let cursor_position = terminal_get_cursor_position(&mut term);
+            let viewport =
+                dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex;
+            let (first_row, lines) = terminal_get_lines(&mut term, viewport.clone());
+            let lines: Vec<(StableRowIndex, Line)> = lines
+                .into_iter()
+                .enumerate()
+                .map(|(idx, line)| (first_row + idx as StableRowIndex, line))
+                .collect();
+
+            let changes = |bonus_lines: Vec<(StableRowIndex, Line)>| GetPaneRenderChangesResponse {
+                pane_id: 0,
+                mouse_grabbed: false,
+                cursor_position,
+                dimensions: dims,
+                dirty_lines: vec![],
+                title: "vim".to_string(),
+                working_dir: None,
+                bonus_lines: bonus_lines.into(),
+                input_serial: None,
+                seqno: 0,
+                is_alt_screen_active: true,
+                exit_status: None,
+            };
+
+            let mut full_lines = lines.clone();
+            let cursor_line = lines[(cursor_position.y - first_row) as usize].clone();
+            full_lines.push(cursor_line);
+            let full = Pdu::GetPaneRenderChangesResponse(changes(full_lines));
+            full_bytes += encoded_len(&full, &mut Compression::default());
+            full_compressed_bytes += encoded_len(&full, &mut full_compressed);
+
+            let updates = sent.compute_updates(viewport, lines.clone());
+            let delta = Pdu::GetPaneRenderDeltaResponse(GetPaneRenderDeltaResponse {
+                changes: changes(updates.lines),
+                generations: updates.generations,
+                deltas: updates.deltas.into(),
+            });
+            let mut encoded = vec![];
+            delta.encode(&mut encoded, 0).unwrap();
+            delta_bytes += encoded_len(&delta, &mut Compression::default());
+            delta_compressed_bytes += encoded_len(&delta, &mut delta_compressed);
+
+            // Apply what the client would receive to its copy of the screen
+            let delta = match Pdu::decode(encoded.as_slice()).unwrap().pdu {
+                Pdu::GetPaneRenderDeltaResponse(delta) => delta,
+                pdu => panic!("unexpected {:?}", pdu),
+            };
+            let generations: HashMap<_, _> = delta.generations.into_iter().collect();
+            for (row, line) in delta.changes.bonus_lines.lines() {
+                client.insert(row, (generations[&row], line));
+            }
+            for delta in delta.deltas.deltas() {
+                let (generation, line) = client.get_mut(&delta.row).unwrap();
+                assert_eq!(*generation, delta.base_generation);
+                delta.apply(line, 0);
+                *generation = delta.generation;
+            }
+            for (row, line) in &lines {
+                assert_eq!(client[row].1.cells(), line.cells(), "row {}", row);
+            }
+        }
+
+        assert!(
+            delta_bytes * 10 < full_bytes,
+            "deltas took {} bytes, full lines {}",
+            delta_bytes,
+            full_bytes
+        );
+        assert!(
+            delta_compressed_bytes * 2 < full_compressed_bytes,
+            "compressed deltas took {} bytes, full lines {}",
+            delta_compressed_bytes,
+            full_compressed_bytes
+        );
+    }
+}
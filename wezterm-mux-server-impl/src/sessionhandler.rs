@@ -1,3 +1,4 @@
+use crate::sentlines::{LineUpdates, SentLines};
 use crate::PKI;
 use anyhow::anyhow;
 use codec::*;
@@ -7,7 +8,7 @@ use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use mux::Mux;
 use percent_encoding::percent_decode_str;
-use portable_pty::PtySize;
+use portable_pty::{ExitStatus, PtySize};
 use promise::spawn::spawn_into_main_thread;
 use std::collections::HashMap;
 use std::rc::Rc;
@@ -43,9 +44,15 @@ pub(crate) struct PerPane {
     working_dir: Option<Url>,
     dimensions: RenderableDimensions,
     mouse_grabbed: bool,
+    is_alt_screen_active: bool,
+    exit_status: Option<ExitStatus>,
     sent_initial_palette: bool,
     seqno: SequenceNo,
     pub(crate) notifications: Vec<Alert>,
+    /// Whether the client negotiated `delta_lines`, in which case
+    /// `sent_lines` tracks what it was sent
+    delta_lines: bool,
+    sent_lines: SentLines,
 }
 
 impl PerPane {
@@ -53,13 +60,23 @@ impl PerPane {
         &mut self,
         pane: &Rc<dyn Pane>,
         force_with_input_serial: Option<InputSerial>,
-    ) -> Option<GetPaneRenderChangesResponse> {
+    ) -> Option<Pdu> {
         let mut changed = false;
         let mouse_grabbed = pane.is_mouse_grabbed();
         if mouse_grabbed != self.mouse_grabbed {
             changed = true;
         }
 
+        let is_alt_screen_active = pane.is_alt_screen_active();
+        if is_alt_screen_active != self.is_alt_screen_active {
+            changed = true;
+        }
+
+        let exit_status = pane.get_exit_status();
+        if exit_status != self.exit_status {
+            changed = true;
+        }
+
         let dims = pane.get_dimensions();
         if dims != self.dimensions {
             changed = true;
@@ -96,7 +113,7 @@ impl PerPane {
         let viewport_range =
             dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex;
 
-        let (first_line, lines) = pane.get_lines(viewport_range);
+        let (first_line, lines) = pane.get_lines(viewport_range.clone());
         let mut bonus_lines = lines
             .into_iter()
             .enumerate()
@@ -107,20 +124,32 @@ impl PerPane {
             })
             .collect::<Vec<_>>();
 
-        // Always send the cursor's row, as that tends to the busiest and we don't
-        // have a sequencing concept for our idea of the remote state.
-        let (cursor_line, lines) = pane.get_lines(cursor_position.y..cursor_position.y + 1);
-        bonus_lines.push((cursor_line, lines[0].clone()));
+        let mut line_updates = None;
+        if self.delta_lines {
+            // Only the lines that the client doesn't already have are
+            // sent, and those that it has an older version of are sent
+            // as deltas; the cursor's row is in the viewport
+            let mut updates = self.sent_lines.compute_updates(viewport_range, bonus_lines);
+            bonus_lines = std::mem::take(&mut updates.lines);
+            line_updates = Some(updates);
+        } else {
+            // Always send the cursor's row, as that tends to the busiest and we don't
+            // have a sequencing concept for our idea of the remote state.
+            let (cursor_line, lines) = pane.get_lines(cursor_position.y..cursor_position.y + 1);
+            bonus_lines.push((cursor_line, lines[0].clone()));
+        }
 
         self.cursor_position = cursor_position;
         self.title = title.clone();
         self.working_dir = working_dir.clone();
         self.dimensions = dims;
         self.mouse_grabbed = mouse_grabbed;
+        self.is_alt_screen_active = is_alt_screen_active;
+        self.exit_status = exit_status.clone();
         self.seqno = pane.get_current_seqno();
 
         let bonus_lines = bonus_lines.into();
-        Some(GetPaneRenderChangesResponse {
+        let changes = GetPaneRenderChangesResponse {
             pane_id: pane.pane_id(),
             mouse_grabbed,
             dirty_lines: all_dirty_lines.iter().cloned().collect(),
@@ -131,6 +160,20 @@ impl PerPane {
             working_dir: working_dir.map(Into::into),
             input_serial: force_with_input_serial,
             seqno: self.seqno,
+            is_alt_screen_active,
+            exit_status,
+        };
+        Some(match line_updates {
+            Some(LineUpdates {
+                generations,
+                deltas,
+                ..
+            }) => Pdu::GetPaneRenderDeltaResponse(GetPaneRenderDeltaResponse {
+                changes,
+                generations,
+                deltas: deltas.into(),
+            }),
+            None => Pdu::GetPaneRenderChangesResponse(changes),
         })
     }
 }
@@ -141,11 +184,8 @@ fn maybe_push_pane_changes(
     per_pane: Arc<Mutex<PerPane>>,
 ) -> anyhow::Result<()> {
     let mut per_pane = per_pane.lock().unwrap();
-    if let Some(resp) = per_pane.compute_changes(pane, None) {
-        sender.send(DecodedPdu {
-            pdu: Pdu::GetPaneRenderChangesResponse(resp),
-            serial: 0,
-        })?;
+    if let Some(pdu) = per_pane.compute_changes(pane, None) {
+        sender.send(DecodedPdu { pdu, serial: 0 })?;
     }
     if !per_pane.sent_initial_palette {
         per_pane.notifications.push(Alert::PaletteChanged);
@@ -179,6 +219,7 @@ fn maybe_push_pane_changes(
 pub struct SessionHandler {
     to_write_tx: PduSender,
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
+    delta_lines: bool,
 }
 
 impl SessionHandler {
@@ -197,15 +238,28 @@ impl SessionHandler {
         Self {
             to_write_tx,
             per_pane: HashMap::new(),
+            delta_lines: false,
         }
     }
 
     pub(crate) fn per_pane(&mut self, pane_id: PaneId) -> Arc<Mutex<PerPane>> {
-        Arc::clone(
-            self.per_pane
-                .entry(pane_id)
-                .or_insert_with(|| Arc::new(Mutex::new(PerPane::default()))),
-        )
+        let delta_lines = self.delta_lines;
+        Arc::clone(self.per_pane.entry(pane_id).or_insert_with(|| {
+            Arc::new(Mutex::new(PerPane {
+                delta_lines,
+                ..PerPane::default()
+            }))
+        }))
+    }
+
+    /// Sets whether the client negotiated `delta_lines` in its `ClientHello`
+    pub fn set_delta_lines(&mut self, delta_lines: bool) {
+        self.delta_lines = delta_lines;
+        for per_pane in self.per_pane.values() {
+            let mut per_pane = per_pane.lock().unwrap();
+            per_pane.delta_lines = delta_lines;
+            per_pane.sent_lines = SentLines::default();
+        }
     }
 
     pub fn schedule_pane_push(&mut self, pane_id: PaneId) {
@@ -377,6 +431,180 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::RearrangePanes(RearrangePanes {
+                containing_tab_id,
+                pane_id,
+                arrangement,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let tab = mux
+                                .get_tab(containing_tab_id)
+                                .ok_or_else(|| anyhow!("no such tab {}", containing_tab_id))?;
+                            tab.set_active_pane(&pane);
+                            tab.rearrange(arrangement);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::MovePaneToNewTab(MovePaneToNewTab { pane_id, window_id }) => {
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let mux = Mux::get().unwrap();
+                        let result = mux.move_pane_to_new_tab(pane_id, window_id).await.map(
+                            |(tab, window_id)| {
+                                Pdu::MovePaneToNewTabResponse(MovePaneToNewTabResponse {
+                                    tab_id: tab.tab_id(),
+                                    window_id,
+                                })
+                            },
+                        );
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
+            Pdu::MovePane(MovePane {
+                pane_id,
+                tab_id,
+                direction,
+            }) => {
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let mux = Mux::get().unwrap();
+                        let result = mux
+                            .move_pane_to_tab(pane_id, tab_id, direction)
+                            .await
+                            .map(|_| Pdu::UnitResponse(UnitResponse {}));
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
+            Pdu::MoveTabToWindow(MoveTabToWindow { tab_id, window_id }) => {
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let mux = Mux::get().unwrap();
+                        let result = mux.move_tab_to_window(tab_id, window_id, None).await.map(
+                            |window_id| {
+                                Pdu::MoveTabToWindowResponse(MoveTabToWindowResponse { window_id })
+                            },
+                        );
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
+            Pdu::GetPaneText(GetPaneText {
+                pane_id,
+                start_line,
+                end_line,
+                escapes,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let text = pane.get_text(start_line, end_line, escapes);
+                            Ok(Pdu::GetPaneTextResponse(GetPaneTextResponse { text }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::ActivatePane(ActivatePane { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.activate_pane(pane_id)?;
+                            Ok(Pdu::ActivatePaneResponse(ActivatePaneResponse { pane_id }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::ActivatePaneDirection(ActivatePaneDirection { pane_id, direction }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane_id = mux.activate_pane_direction(pane_id, direction)?;
+                            Ok(Pdu::ActivatePaneResponse(ActivatePaneResponse { pane_id }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::ActivateTab(ActivateTab {
+                pane_id,
+                tab_id,
+                relative,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane_id = mux.activate_tab(pane_id, tab_id, relative)?;
+                            Ok(Pdu::ActivatePaneResponse(ActivatePaneResponse { pane_id }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::SaveSession(SaveSession { include_commands }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let session = mux::session::save_session(&mux, include_commands);
+                            Ok(Pdu::SaveSessionResponse(SaveSessionResponse { session }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::RestoreSession(RestoreSession { session }) => {
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let result = mux::session::restore_session(session)
+                            .await
+                            .map(|_| Pdu::UnitResponse(UnitResponse {}));
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
             Pdu::Resize(Resize {
                 containing_tab_id,
                 pane_id,
@@ -422,12 +650,8 @@ impl SessionHandler {
                             // cursor position so that the predictive echo doesn't
                             // leave the cursor in the wrong place
                             let mut per_pane = per_pane.lock().unwrap();
-                            if let Some(resp) = per_pane.compute_changes(&pane, Some(input_serial))
-                            {
-                                sender.send(DecodedPdu {
-                                    pdu: Pdu::GetPaneRenderChangesResponse(resp),
-                                    serial: 0,
-                                })?;
+                            if let Some(pdu) = per_pane.compute_changes(&pane, Some(input_serial)) {
+                                sender.send(DecodedPdu { pdu, serial: 0 })?;
                             }
                             Ok(Pdu::UnitResponse(UnitResponse {}))
                         },
@@ -506,6 +730,7 @@ impl SessionHandler {
             }
 
             Pdu::GetLines(GetLines { pane_id, lines }) => {
+                let per_pane = self.per_pane(pane_id);
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
@@ -515,7 +740,9 @@ impl SessionHandler {
                                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
                             let mut lines_and_indices = vec![];
 
+                            let mut per_pane = per_pane.lock().unwrap();
                             for range in lines {
+                                per_pane.sent_lines.forget(&range);
                                 let (first_row, lines) = pane.get_lines(range);
                                 for (idx, line) in lines.into_iter().enumerate() {
                                     let stable_row = first_row + idx as StableRowIndex;
@@ -554,6 +781,43 @@ impl SessionHandler {
                 );
             }
 
+            Pdu::GetLogEntries(GetLogEntries { since_seq }) => {
+                let (entries, latest_seq) = env_bootstrap::ringlog::get_entries_since(since_seq);
+                send_response(Ok(Pdu::GetLogEntriesResponse(GetLogEntriesResponse {
+                    entries,
+                    latest_seq,
+                })))
+            }
+
+            Pdu::ListSshForwards(ListSshForwards {}) => {
+                send_response(Ok(Pdu::ListSshForwardsResponse(ListSshForwardsResponse {
+                    forwards: mux::sshforward::ssh_forward_status(),
+                })))
+            }
+
+            Pdu::ListTlsCertificates(ListTlsCertificates {}) => send_response(Ok(
+                Pdu::ListTlsCertificatesResponse(ListTlsCertificatesResponse {
+                    certificates: crate::tlsstatus::tls_certificate_status(),
+                }),
+            )),
+
+            Pdu::ListClients(ListClients {}) => {
+                send_response(Ok(Pdu::ListClientsResponse(ListClientsResponse {
+                    clients: crate::clients::clients(),
+                })))
+            }
+
+            // This is normally handled by the dispatcher, which owns the
+            // encoding of the connection; without that, compression
+            // cannot be turned on
+            Pdu::ClientHello(hello) => {
+                self.set_delta_lines(hello.delta_lines);
+                send_response(Ok(Pdu::ClientHelloResponse(ClientHelloResponse {
+                    compression: false,
+                    delta_lines: hello.delta_lines,
+                })))
+            }
+
             Pdu::Invalid { .. } => send_response(Err(anyhow!("invalid PDU {:?}", decoded.pdu))),
             Pdu::Pong { .. }
             | Pdu::ListPanesResponse { .. }
@@ -562,12 +826,23 @@ impl SessionHandler {
             | Pdu::SetPalette { .. }
             | Pdu::SpawnResponse { .. }
             | Pdu::GetPaneRenderChangesResponse { .. }
+            | Pdu::GetPaneRenderDeltaResponse { .. }
             | Pdu::UnitResponse { .. }
             | Pdu::LivenessResponse { .. }
             | Pdu::SearchScrollbackResponse { .. }
             | Pdu::GetLinesResponse { .. }
             | Pdu::GetCodecVersionResponse { .. }
             | Pdu::GetTlsCredsResponse { .. }
+            | Pdu::GetLogEntriesResponse { .. }
+            | Pdu::ListSshForwardsResponse { .. }
+            | Pdu::ListTlsCertificatesResponse { .. }
+            | Pdu::ClientHelloResponse { .. }
+            | Pdu::ListClientsResponse { .. }
+            | Pdu::MovePaneToNewTabResponse { .. }
+            | Pdu::MoveTabToWindowResponse { .. }
+            | Pdu::GetPaneTextResponse { .. }
+            | Pdu::ActivatePaneResponse { .. }
+            | Pdu::SaveSessionResponse { .. }
             | Pdu::PaneRemoved { .. }
             | Pdu::ErrorResponse { .. } => {
                 send_response(Err(anyhow!("expected a request, got {:?}", decoded.pdu)))
@@ -0,0 +1,22 @@
+//! The status of the certificates presented by the TLS listeners,
+//! which is reported by `wezterm cli list-tls-certificates`.
+pub use codec::TlsCertificateStatus;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    static ref STATUS: Mutex<BTreeMap<String, TlsCertificateStatus>> = Mutex::new(BTreeMap::new());
+}
+
+/// Records the status of the listener on `status.bind_address`,
+/// replacing any that was recorded before
+pub fn set_tls_certificate_status(status: TlsCertificateStatus) {
+    STATUS
+        .lock()
+        .unwrap()
+        .insert(status.bind_address.clone(), status);
+}
+
+pub fn tls_certificate_status() -> Vec<TlsCertificateStatus> {
+    STATUS.lock().unwrap().values().cloned().collect()
+}
@@ -79,6 +79,7 @@ fn run() -> anyhow::Result<()> {
     );
 
     let config = config::configuration();
+    env_bootstrap::ringlog::apply_config(&config);
     #[cfg(unix)]
     {
         if opts.daemonize {
@@ -148,6 +149,14 @@ fn run() -> anyhow::Result<()> {
     for name in &config::configuration().mux_env_remove {
         std::env::remove_var(name);
     }
+    // Panes reach the agent that is forwarded to ssh domain connections
+    // through a link that `wezterm cli proxy` keeps up to date
+    #[cfg(unix)]
+    {
+        if std::env::var_os("SSH_AUTH_SOCK").is_none() {
+            std::env::set_var("SSH_AUTH_SOCK", config::forwarded_agent_path());
+        }
+    }
 
     let need_builder = !opts.prog.is_empty() || opts.cwd.is_some();
 
@@ -1,25 +1,148 @@
 use anyhow::{anyhow, Context, Error};
 use async_ossl::AsyncSslStream;
-use config::TlsDomainServer;
+use config::{configuration, TlsDomainServer};
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslStream, SslVerifyMode};
-use openssl::x509::X509;
+use openssl::x509::{X509Ref, X509};
 use promise::spawn::spawn_into_main_thread;
 use std::net::TcpListener;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wezterm_mux_server_impl::tlsstatus::{set_tls_certificate_status, TlsCertificateStatus};
 use wezterm_mux_server_impl::PKI;
 
+/// How often to check whether the certificate is about to expire,
+/// in addition to when it is loaded
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
 struct OpenSSLNetListener {
-    acceptor: Arc<SslAcceptor>,
+    state: Arc<ListenerState>,
     listener: TcpListener,
 }
 
-impl OpenSSLNetListener {
-    pub fn new(listener: TcpListener, acceptor: SslAcceptor) -> Self {
-        Self {
-            listener,
-            acceptor: Arc::new(acceptor),
+/// The acceptor of a TLS listener, which is rebuilt when the files of
+/// its certificate change, so that new connections are made with the
+/// latest certificate while the existing sessions carry on
+struct ListenerState {
+    bind_address: String,
+    acceptor: Mutex<Arc<SslAcceptor>>,
+    /// The certificate that `acceptor` presents
+    cert: Mutex<X509>,
+    reload_error: Mutex<Option<String>>,
+}
+
+impl ListenerState {
+    fn acceptor(&self) -> Arc<SslAcceptor> {
+        Arc::clone(&self.acceptor.lock().unwrap())
+    }
+
+    /// Rebuilds the acceptor from the current configuration.
+    /// If that fails, the previous acceptor remains in use.
+    fn reload(&self) {
+        let config = configuration();
+        let tls_server = match config
+            .tls_servers
+            .iter()
+            .find(|tls_server| tls_server.bind_address == self.bind_address)
+        {
+            Some(tls_server) => tls_server,
+            // Listeners are not added or removed without a restart
+            None => return,
+        };
+
+        match build_acceptor(tls_server) {
+            Ok((acceptor, cert)) => {
+                let fingerprint = |cert: &X509Ref| {
+                    cert.digest(MessageDigest::sha256())
+                        .map(|digest| digest.to_vec())
+                        .ok()
+                };
+                if fingerprint(&cert) != fingerprint(&self.cert.lock().unwrap()) {
+                    log::info!(
+                        "TLS listener on {} loaded a new certificate; \
+                         it applies to new connections",
+                        self.bind_address
+                    );
+                }
+                *self.acceptor.lock().unwrap() = Arc::new(acceptor);
+                *self.cert.lock().unwrap() = cert;
+                self.reload_error.lock().unwrap().take();
+            }
+            Err(err) => {
+                let err = format!("{:#}", err);
+                log::error!(
+                    "TLS listener on {} failed to reload its certificate, \
+                     and is still using the previous one: {}",
+                    self.bind_address,
+                    err
+                );
+                self.reload_error.lock().unwrap().replace(err);
+            }
         }
+        self.update_status(tls_server);
+    }
+
+    /// Records the status of the certificate, logging a warning
+    /// if it expires soon
+    fn update_status(&self, tls_server: &TlsDomainServer) {
+        let cert = self.cert.lock().unwrap();
+        let days_remaining = match days_until_expiry(&cert) {
+            Ok(days) => days,
+            Err(err) => {
+                log::error!(
+                    "checking the expiry of the certificate of TLS listener on {}: {:#}",
+                    self.bind_address,
+                    err
+                );
+                return;
+            }
+        };
+        let expiring = days_remaining < tls_server.certificate_expiry_warning_days as i64;
+        if days_remaining < 0 {
+            log::warn!(
+                "The certificate of TLS listener on {} expired on {}",
+                self.bind_address,
+                cert.not_after()
+            );
+        } else if expiring {
+            log::warn!(
+                "The certificate of TLS listener on {} expires in {} days, on {}",
+                self.bind_address,
+                days_remaining,
+                cert.not_after()
+            );
+        }
+
+        let subject = cert
+            .subject_name()
+            .entries_by_nid(openssl::nid::Nid::COMMONNAME)
+            .next()
+            .and_then(|cn| cn.data().as_utf8().ok().map(|cn| cn.to_string()))
+            .unwrap_or_default();
+
+        set_tls_certificate_status(TlsCertificateStatus {
+            bind_address: self.bind_address.clone(),
+            cert_path: cert_file(tls_server).display().to_string(),
+            subject,
+            not_after: cert.not_after().to_string(),
+            days_remaining,
+            expiring,
+            reload_error: self.reload_error.lock().unwrap().clone(),
+        });
+    }
+}
+
+fn days_until_expiry(cert: &X509Ref) -> anyhow::Result<i64> {
+    let now = Asn1Time::days_from_now(0)?;
+    let diff = now.diff(cert.not_after())?;
+    Ok(diff.days as i64)
+}
+
+impl OpenSSLNetListener {
+    fn new(listener: TcpListener, state: Arc<ListenerState>) -> Self {
+        Self { listener, state }
     }
 
     /// Authenticates the peer.
@@ -74,7 +197,7 @@ impl OpenSSLNetListener {
             match stream {
                 Ok(stream) => {
                     stream.set_nodelay(true).ok();
-                    let acceptor = self.acceptor.clone();
+                    let acceptor = self.state.acceptor();
 
                     match acceptor.accept(stream) {
                         Ok(stream) => {
@@ -109,15 +232,19 @@ impl OpenSSLNetListener {
     }
 }
 
-pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
-    openssl::init();
+fn cert_file(tls_server: &TlsDomainServer) -> PathBuf {
+    tls_server
+        .pem_cert
+        .clone()
+        .unwrap_or_else(|| PKI.server_pem())
+}
 
+/// Builds the acceptor for `tls_server`, returning it along
+/// with the certificate that it presents
+fn build_acceptor(tls_server: &TlsDomainServer) -> anyhow::Result<(SslAcceptor, X509)> {
     let mut acceptor = SslAcceptor::mozilla_modern(SslMethod::tls())?;
 
-    let cert_file = tls_server
-        .pem_cert
-        .clone()
-        .unwrap_or_else(|| PKI.server_pem());
+    let cert_file = cert_file(tls_server);
     acceptor
         .set_certificate_file(&cert_file, SslFiletype::PEM)
         .context(format!(
@@ -169,6 +296,55 @@ pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
     acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
 
     let acceptor = acceptor.build();
+    let cert = acceptor
+        .context()
+        .certificate()
+        .ok_or_else(|| anyhow!("TLS listener has no certificate"))?
+        .to_owned();
+
+    Ok((acceptor, cert))
+}
+
+pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
+    openssl::init();
+
+    let (acceptor, cert) = build_acceptor(tls_server)?;
+    let state = Arc::new(ListenerState {
+        bind_address: tls_server.bind_address.clone(),
+        acceptor: Mutex::new(Arc::new(acceptor)),
+        cert: Mutex::new(cert),
+        reload_error: Mutex::new(None),
+    });
+    state.update_status(tls_server);
+
+    // The certificate files are watched along with the configuration,
+    // so a change to them reloads the configuration
+    let subscription = config::subscribe_to_config_reload({
+        let state = Arc::clone(&state);
+        move || {
+            let state = Arc::clone(&state);
+            spawn_into_main_thread(async move {
+                state.reload();
+            })
+            .detach();
+            true
+        }
+    });
+
+    {
+        let state = Arc::clone(&state);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(EXPIRY_CHECK_INTERVAL);
+            let config = configuration();
+            if let Some(tls_server) = config
+                .tls_servers
+                .iter()
+                .find(|tls_server| tls_server.bind_address == state.bind_address)
+            {
+                state.update_status(tls_server);
+            }
+        });
+    }
 
     log::error!("listening with TLS on {:?}", tls_server.bind_address);
 
@@ -179,9 +355,10 @@ pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
                 tls_server.bind_address,
             )
         })?,
-        acceptor,
+        state,
     );
     std::thread::spawn(move || {
+        let _subscription = subscription;
         net_listener.run();
     });
     Ok(())
@@ -0,0 +1,67 @@
+//! Agent forwarding.  When `ForwardAgent yes` is in effect for the host
+//! and there is a local agent given by `IdentityAgent`, which defaults
+//! to `SSH_AUTH_SOCK`, or to the OpenSSH agent named pipe on Windows,
+//! each session channel asks the remote host to forward its agent.
+//! Only the ssh2 backend is able to make that request.
+use crate::channelwrap::ChannelWrap;
+
+/// The named pipe of the OpenSSH agent that ships with Windows, which
+/// Pageant can also be configured to provide
+#[cfg(windows)]
+const DEFAULT_WINDOWS_AGENT: &str = r"\\.\pipe\openssh-ssh-agent";
+
+impl crate::sessioninner::SessionInner {
+    pub(crate) fn wants_agent_forwarding(&self) -> bool {
+        self.config.get("forwardagent").map(|s| s.as_str()) == Some("yes")
+    }
+
+    /// Returns the path to the local agent if agent forwarding is
+    /// enabled for the host and there is a local agent to forward,
+    /// logging why not if it is enabled but there is no agent
+    pub(crate) fn resolve_forwarded_agent(&self, hostname: &str) -> Option<String> {
+        if !self.wants_agent_forwarding() {
+            return None;
+        }
+
+        let agent = match self.config.get("identityagent").map(|s| s.as_str()) {
+            Some("none") => None,
+            Some(path) => Some(path.to_string()),
+            #[cfg(windows)]
+            None => Some(DEFAULT_WINDOWS_AGENT.to_string()),
+            #[cfg(unix)]
+            None => None,
+        };
+
+        match agent {
+            Some(path) if std::path::Path::new(&path).exists() => Some(path),
+            Some(path) => {
+                log::error!(
+                    "ForwardAgent is enabled for {}, but the local agent {} \
+                     does not exist; not forwarding the agent",
+                    hostname,
+                    path
+                );
+                None
+            }
+            None => {
+                log::error!(
+                    "ForwardAgent is enabled for {}, but no local agent is \
+                     available (SSH_AUTH_SOCK is not set); not forwarding the agent",
+                    hostname
+                );
+                None
+            }
+        }
+    }
+
+    /// Asks the remote host to forward the agent to the processes
+    /// that are run via `channel`
+    pub(crate) fn request_agent_forwarding(&self, channel: &mut ChannelWrap) {
+        if self.forwarded_agent.is_none() {
+            return;
+        }
+        if let Err(err) = channel.request_auth_agent_forwarding() {
+            log::error!("Failed to request agent forwarding: {:#}", err);
+        }
+    }
+}
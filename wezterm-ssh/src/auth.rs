@@ -1,3 +1,4 @@
+use crate::error::SessionError;
 use crate::session::SessionEvent;
 use anyhow::Context;
 use libssh_rs as libssh;
@@ -13,7 +14,14 @@ pub struct AuthenticationPrompt {
 
 #[derive(Debug)]
 pub struct AuthenticationEvent {
+    /// The host that is being authenticated with
+    pub host: String,
+    /// The fingerprint of the host key, if it was verified
+    pub fingerprint: Option<String>,
     pub username: String,
+    /// Describes who is being authenticated when it isn't the user
+    /// of the session itself, such as for a jump host
+    pub label: Option<String>,
     pub instructions: String,
     pub prompts: Vec<AuthenticationPrompt>,
     pub(crate) reply: Sender<Vec<String>>,
@@ -30,23 +38,56 @@ impl AuthenticationEvent {
 }
 
 impl crate::sessioninner::SessionInner {
-    fn agent_auth(&mut self, sess: &ssh2::Session, user: &str) -> anyhow::Result<bool> {
-        if let Some(only) = self.config.get("identitiesonly") {
-            if only == "yes" {
-                log::trace!("Skipping agent auth because identitiesonly=yes");
-                return Ok(false);
-            }
-        }
+    /// Returns the public keys of the `IdentityFile`s, as the blobs in
+    /// their `.pub` files, so that the keys of the agent can be matched
+    /// against them
+    fn identity_file_public_keys(&self) -> Vec<Vec<u8>> {
+        let files = match self.config.get("identityfile") {
+            Some(files) => files,
+            None => return vec![],
+        };
+        files
+            .split_whitespace()
+            .filter_map(|file| std::fs::read_to_string(format!("{}.pub", file)).ok())
+            .filter_map(|pubkey| {
+                // The format is `type base64-blob comment`
+                let blob = pubkey.split_whitespace().nth(1)?;
+                base64::decode(blob).ok()
+            })
+            .collect()
+    }
 
+    fn agent_auth(&mut self, sess: &ssh2::Session, user: &str) -> anyhow::Result<bool> {
         let mut agent = sess.agent()?;
         if agent.connect().is_err() {
             // If the agent is around, we can proceed with other methods
             return Ok(false);
         }
 
+        // With IdentitiesOnly, only the keys of the agent that correspond
+        // to an IdentityFile are offered, so that an agent holding many
+        // keys doesn't exhaust the MaxAuthTries of the server
+        let allowed = if self.identities_only() {
+            Some(self.identity_file_public_keys())
+        } else {
+            None
+        };
+
         agent.list_identities()?;
         let identities = agent.identities()?;
         for identity in identities {
+            if let Some(allowed) = &allowed {
+                if !allowed
+                    .iter()
+                    .any(|blob| blob.as_slice() == identity.blob())
+                {
+                    log::trace!(
+                        "Skipping agent key {} because identitiesonly=yes",
+                        identity.comment()
+                    );
+                    continue;
+                }
+            }
             if agent.userauth(user, &identity).is_ok() {
                 return Ok(true);
             }
@@ -82,13 +123,22 @@ impl crate::sessioninner::SessionInner {
                         log::info!("pubkey_file immediately ok for {}", file.display());
                         return Ok(true);
                     }
+                    Err(_) if self.batch_mode() => {
+                        log::debug!(
+                            "Not asking for the passphrase of {} because batchmode=yes",
+                            file.display()
+                        );
+                    }
                     Err(_) => {
                         // Most likely cause of error is that we need a passphrase
                         // to decrypt the key, so let's prompt the user for one.
                         let (reply, answers) = bounded(1);
                         self.tx_event
                             .try_send(SessionEvent::Authenticate(AuthenticationEvent {
+                                host: host.to_string(),
+                                fingerprint: self.host_fingerprint.clone(),
                                 username: "".to_string(),
+                                label: None,
                                 instructions: "".to_string(),
                                 prompts: vec![AuthenticationPrompt {
                                     prompt: format!(
@@ -129,12 +179,26 @@ impl crate::sessioninner::SessionInner {
 
     pub fn authenticate_libssh(&mut self, sess: &libssh::Session) -> anyhow::Result<()> {
         let tx = self.tx_event.clone();
+        let host = self.config.get("hostname").cloned().unwrap_or_default();
+        let fingerprint = self.host_fingerprint.clone();
 
         // Set the callback for pubkey auth
+        let callback_host = host.clone();
+        let callback_fingerprint = fingerprint.clone();
+        let batch_mode = self.batch_mode();
         sess.set_auth_callback(move |prompt, echo, _verify, identity| {
+            if batch_mode {
+                // An empty passphrase fails to decrypt the key,
+                // which moves on to the next one
+                log::debug!("Not prompting for {:?} because batchmode=yes", identity);
+                return Ok(String::new());
+            }
             let (reply, answers) = bounded(1);
             tx.try_send(SessionEvent::Authenticate(AuthenticationEvent {
+                host: callback_host.clone(),
+                fingerprint: callback_fingerprint.clone(),
                 username: "".to_string(),
+                label: None,
                 instructions: "".to_string(),
                 prompts: vec![AuthenticationPrompt {
                     prompt: match identity {
@@ -173,7 +237,7 @@ impl crate::sessioninner::SessionInner {
                 }
             }
 
-            if auth_methods.contains(AuthMethods::INTERACTIVE) {
+            if auth_methods.contains(AuthMethods::INTERACTIVE) && !batch_mode {
                 loop {
                     match sess.userauth_keyboard_interactive(None, None)? {
                         AuthStatus::Success => return Ok(()),
@@ -183,7 +247,10 @@ impl crate::sessioninner::SessionInner {
                             let (reply, answers) = bounded(1);
                             self.tx_event
                                 .try_send(SessionEvent::Authenticate(AuthenticationEvent {
+                                    host: host.clone(),
+                                    fingerprint: fingerprint.clone(),
                                     username: sess.get_user_name()?,
+                                    label: None,
                                     instructions: info.instruction,
                                     prompts: info
                                         .prompts
@@ -215,11 +282,14 @@ impl crate::sessioninner::SessionInner {
                 }
             }
 
-            if auth_methods.contains(AuthMethods::PASSWORD) {
+            if auth_methods.contains(AuthMethods::PASSWORD) && !batch_mode {
                 let (reply, answers) = bounded(1);
                 self.tx_event
                     .try_send(SessionEvent::Authenticate(AuthenticationEvent {
+                        host: host.clone(),
+                        fingerprint: fingerprint.clone(),
                         username: "".to_string(),
+                        label: None,
                         instructions: "".to_string(),
                         prompts: vec![AuthenticationPrompt {
                             prompt: "Password: ".to_string(),
@@ -241,11 +311,18 @@ impl crate::sessioninner::SessionInner {
                 }
             }
 
-            anyhow::bail!(
-                "unhandled auth case; methods={:?}, status={:?}",
+            log::debug!(
+                "authentication failed; methods={:?}, status={:?}",
                 auth_methods,
                 status_by_method
             );
+            return Err(SessionError::AuthenticationFailed {
+                user: sess.get_user_name().unwrap_or_default(),
+                host,
+                methods: format!("{:?}", auth_methods),
+                batch_mode,
+            }
+            .into());
         }
     }
 
@@ -255,6 +332,7 @@ impl crate::sessioninner::SessionInner {
         user: &str,
         host: &str,
     ) -> anyhow::Result<()> {
+        let batch_mode = self.batch_mode();
         loop {
             if sess.authenticated() {
                 return Ok(());
@@ -276,11 +354,28 @@ impl crate::sessioninner::SessionInner {
                 }
             }
 
+            // The remaining methods prompt, so without them, there is
+            // nothing else to try
+            if batch_mode {
+                let mut methods: Vec<&str> = methods.into_iter().collect();
+                methods.sort_unstable();
+                return Err(SessionError::AuthenticationFailed {
+                    user: user.to_string(),
+                    host: host.to_string(),
+                    methods: methods.join(","),
+                    batch_mode,
+                }
+                .into());
+            }
+
             if !sess.authenticated() && methods.contains("password") {
                 let (reply, answers) = bounded(1);
                 self.tx_event
                     .try_send(SessionEvent::Authenticate(AuthenticationEvent {
+                        host: host.to_string(),
+                        fingerprint: self.host_fingerprint.clone(),
                         username: user.to_string(),
+                        label: None,
                         instructions: "".to_string(),
                         prompts: vec![AuthenticationPrompt {
                             prompt: format!("Password for {}@{}: ", user, host),
@@ -305,6 +400,8 @@ impl crate::sessioninner::SessionInner {
             if !sess.authenticated() && methods.contains("keyboard-interactive") {
                 struct Helper<'a> {
                     tx_event: &'a Sender<SessionEvent>,
+                    host: &'a str,
+                    fingerprint: &'a Option<String>,
                 }
 
                 impl<'a> ssh2::KeyboardInteractivePrompt for Helper<'a> {
@@ -317,7 +414,10 @@ impl crate::sessioninner::SessionInner {
                         let (reply, answers) = bounded(1);
                         if let Err(err) = self.tx_event.try_send(SessionEvent::Authenticate(
                             AuthenticationEvent {
+                                host: self.host.to_string(),
+                                fingerprint: self.fingerprint.clone(),
                                 username: username.to_string(),
+                                label: None,
                                 instructions: instructions.to_string(),
                                 prompts: prompts
                                     .iter()
@@ -348,6 +448,8 @@ impl crate::sessioninner::SessionInner {
 
                 let mut helper = Helper {
                     tx_event: &self.tx_event,
+                    host,
+                    fingerprint: &self.host_fingerprint,
                 };
 
                 if let Err(err) = sess.userauth_keyboard_interactive(user, &mut helper) {
@@ -127,6 +127,15 @@ impl ChannelWrap {
         }
     }
 
+    pub fn request_auth_agent_forwarding(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Ssh2(chan) => Ok(chan.request_auth_agent_forwarding()?),
+            Self::LibSsh(_) => {
+                anyhow::bail!("agent forwarding is not supported by the libssh backend")
+            }
+        }
+    }
+
     pub fn send_signal(&mut self, signame: &str) -> anyhow::Result<()> {
         match self {
             Self::Ssh2(_) => Ok(()),
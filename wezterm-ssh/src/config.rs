@@ -1,10 +1,14 @@
 //! Parse an ssh_config(5) formatted config file
 use regex::{Captures, Regex};
 use std::collections::BTreeMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub type ConfigMap = BTreeMap<String, String>;
 
+/// Limits how deeply `Include` directives can nest, which guards
+/// against files that include themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+
 /// A Pattern in a `Host` list
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct Pattern {
@@ -50,27 +54,34 @@ impl Pattern {
     /// Returns true if hostname matches the
     /// condition specified by a list of patterns
     fn match_group(hostname: &str, patterns: &[Self]) -> bool {
+        let mut matched = false;
         for pat in patterns {
             if pat.match_text(hostname) {
-                // We got a definitive name match.
-                // If it was an exlusion then we've been told
-                // that this doesn't really match, otherwise
-                // we got one that we were looking for
-                return !pat.negated;
+                if pat.negated {
+                    // An exclusion overrides any other match,
+                    // wherever it appears in the list
+                    return false;
+                }
+                matched = true;
             }
         }
-        false
+        matched
     }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 enum Criteria {
+    /// The host name as it was given; used for `Host` stanzas
     Host(Vec<Pattern>),
     Exec(String),
+    /// The target host name, after any `HostName` substitution;
+    /// used for `Match host`
+    HostName(Vec<Pattern>),
     OriginalHost(Vec<Pattern>),
     User(Vec<Pattern>),
     LocalUser(Vec<Pattern>),
     All,
+    Not(Box<Criteria>),
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -80,6 +91,85 @@ enum Context {
     Final,
 }
 
+/// The values that the criteria of a `Match` are tested against
+struct MatchState<'a> {
+    /// The host name as it was given
+    original_host: &'a str,
+    /// The host name after any `HostName` substitution that has
+    /// been resolved so far
+    hostname: String,
+    /// The remote user that has been resolved so far
+    user: &'a str,
+    local_user: &'a str,
+}
+
+impl<'a> MatchState<'a> {
+    /// Expands the tokens that are valid in a `Match exec` command
+    fn expand_tokens(&self, command: &str) -> String {
+        let mut result = String::new();
+        let mut chars = command.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('h') => result.push_str(&self.hostname),
+                Some('n') => result.push_str(self.original_host),
+                Some('r') => result.push_str(self.user),
+                Some('u') => result.push_str(self.local_user),
+                Some('%') => result.push('%'),
+                Some(other) => {
+                    result.push('%');
+                    result.push(other);
+                }
+                None => result.push('%'),
+            }
+        }
+        result
+    }
+}
+
+/// Runs the command of a `Match exec` criterion, which matches
+/// if the command exits successfully
+fn exec_matches(command: &str, state: &MatchState) -> bool {
+    let command = state.expand_tokens(command);
+    let mut cmd;
+    if cfg!(windows) {
+        let comspec = std::env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_string());
+        cmd = std::process::Command::new(comspec);
+        cmd.args(&["/c", &command]);
+    } else {
+        cmd = std::process::Command::new("sh");
+        cmd.args(&["-c", &command]);
+    }
+    cmd.stdin(std::process::Stdio::null());
+    cmd.stdout(std::process::Stdio::null());
+    match cmd.status() {
+        Ok(status) => status.success(),
+        Err(err) => {
+            log::error!("error running `Match exec {}`: {:#}", command, err);
+            false
+        }
+    }
+}
+
+impl Criteria {
+    fn is_match(&self, state: &MatchState) -> bool {
+        match self {
+            Criteria::Host(patterns) | Criteria::OriginalHost(patterns) => {
+                Pattern::match_group(state.original_host, patterns)
+            }
+            Criteria::HostName(patterns) => Pattern::match_group(&state.hostname, patterns),
+            Criteria::Exec(command) => exec_matches(command, state),
+            Criteria::User(patterns) => Pattern::match_group(state.user, patterns),
+            Criteria::LocalUser(patterns) => Pattern::match_group(state.local_user, patterns),
+            Criteria::All => true,
+            Criteria::Not(criteria) => !criteria.is_match(state),
+        }
+    }
+}
+
 /// Represents `Host pattern,list` stanza in the config,
 /// and the options that it logically contains
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -90,41 +180,14 @@ struct MatchGroup {
 }
 
 impl MatchGroup {
-    fn is_match(&self, hostname: &str, user: &str, local_user: &str, context: Context) -> bool {
+    fn is_match(&self, state: &MatchState, context: Context) -> bool {
         if self.context != context {
             return false;
         }
-        for c in &self.criteria {
-            match c {
-                Criteria::Host(patterns) => {
-                    if !Pattern::match_group(hostname, patterns) {
-                        return false;
-                    }
-                }
-                Criteria::Exec(_) => {
-                    log::warn!("Match Exec is not implemented");
-                }
-                Criteria::OriginalHost(patterns) => {
-                    if !Pattern::match_group(hostname, patterns) {
-                        return false;
-                    }
-                }
-                Criteria::User(patterns) => {
-                    if !Pattern::match_group(user, patterns) {
-                        return false;
-                    }
-                }
-                Criteria::LocalUser(patterns) => {
-                    if !Pattern::match_group(local_user, patterns) {
-                        return false;
-                    }
-                }
-                Criteria::All => {
-                    // Always matches
-                }
-            }
-        }
-        true
+        // Criteria are evaluated in order and stop at the first that
+        // doesn't match, so that an `exec` is only run when the
+        // criteria before it have matched
+        self.criteria.iter().all(|c| c.is_match(state))
     }
 }
 
@@ -139,39 +202,101 @@ struct ParsedConfigFile {
     groups: Vec<MatchGroup>,
 }
 
+/// Splits the arguments of a `Match` line, which may be quoted
+fn split_match_args(v: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quoted = false;
+    for c in v.chars() {
+        if c == '"' {
+            quoted = !quoted;
+            in_arg = true;
+        } else if c.is_whitespace() && !quoted {
+            if in_arg {
+                args.push(std::mem::take(&mut current));
+                in_arg = false;
+            }
+        } else {
+            current.push(c);
+            in_arg = true;
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
 impl ParsedConfigFile {
     fn parse(s: &str, cwd: Option<&Path>) -> Self {
         let mut options = ConfigMap::new();
         let mut groups = vec![];
 
-        Self::parse_impl(s, cwd, &mut options, &mut groups);
+        Self::parse_impl(s, cwd, &mut options, &mut groups, None, 0);
 
         Self { options, groups }
     }
 
+    /// Parses the files that match `pattern` as a continuation of the
+    /// file that includes them.  Relative patterns are resolved against
+    /// `cwd`, which is the directory of the top level config file,
+    /// usually `~/.ssh`.  If the `Include` appears inside the group
+    /// `current`, the contents of the included files are conditional
+    /// on that group matching.
     fn do_include(
         pattern: &str,
         cwd: Option<&Path>,
         options: &mut ConfigMap,
         groups: &mut Vec<MatchGroup>,
+        current: Option<usize>,
+        depth: usize,
     ) {
+        if depth >= MAX_INCLUDE_DEPTH {
+            log::error!(
+                "error expanding `Include {}`: includes are nested more than {} levels deep",
+                pattern,
+                MAX_INCLUDE_DEPTH
+            );
+            return;
+        }
+
+        let pattern = match (pattern.strip_prefix("~/"), dirs_next::home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+            _ => pattern.to_string(),
+        };
+
         match filenamegen::Glob::new(&pattern) {
             Ok(g) => {
                 match cwd
                     .as_ref()
                     .map(|p| p.to_path_buf())
-                    .or_else(|| std::env::current_dir().ok())
+                    .or_else(|| dirs_next::home_dir().map(|home| home.join(".ssh")))
                 {
                     Some(cwd) => {
-                        for path in g.walk(&cwd) {
-                            let path = if path.is_absolute() {
-                                path
-                            } else {
-                                cwd.join(path)
-                            };
+                        let mut paths: Vec<PathBuf> = g
+                            .walk(&cwd)
+                            .map(|path| {
+                                if path.is_absolute() {
+                                    path
+                                } else {
+                                    cwd.join(path)
+                                }
+                            })
+                            .collect();
+                        // As with ssh, files are included in lexical order
+                        paths.sort();
+                        for path in paths {
                             match std::fs::read_to_string(&path) {
                                 Ok(data) => {
-                                    Self::parse_impl(&data, Some(&cwd), options, groups);
+                                    Self::parse_impl(
+                                        &data,
+                                        Some(&cwd),
+                                        options,
+                                        groups,
+                                        current,
+                                        depth + 1,
+                                    );
                                 }
                                 Err(err) => {
                                     log::error!(
@@ -198,12 +323,23 @@ impl ParsedConfigFile {
         }
     }
 
+    /// Parses `s` into `options` and `groups`.
+    /// `current` is the index of the group whose options are being
+    /// parsed, which is None at the top level of a config file.
     fn parse_impl(
         s: &str,
         cwd: Option<&Path>,
         options: &mut ConfigMap,
         groups: &mut Vec<MatchGroup>,
+        mut current: Option<usize>,
+        depth: usize,
     ) {
+        // The criteria that any groups in this file are nested within
+        let (enclosing_criteria, enclosing_context) = match current {
+            Some(idx) => (groups[idx].criteria.clone(), groups[idx].context),
+            None => (vec![], Context::FirstPass),
+        };
+
         for line in s.lines() {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
@@ -250,63 +386,95 @@ impl ParsedConfigFile {
                 }
 
                 if k == "include" {
-                    Self::do_include(v, cwd, options, groups);
+                    let num_groups = groups.len();
+                    for pattern in v.split_ascii_whitespace() {
+                        Self::do_include(pattern, cwd, options, groups, current, depth);
+                    }
+                    if groups.len() > num_groups {
+                        // The options that follow belong to the group that
+                        // contained the `Include`, but must come after those
+                        // of the included files so that they don't take
+                        // precedence over them
+                        let criteria = match current {
+                            Some(idx) => groups[idx].criteria.clone(),
+                            None => vec![Criteria::All],
+                        };
+                        let context = match current {
+                            Some(idx) => groups[idx].context,
+                            None => Context::FirstPass,
+                        };
+                        groups.push(MatchGroup {
+                            criteria,
+                            options: ConfigMap::new(),
+                            context,
+                        });
+                        current = Some(groups.len() - 1);
+                    }
                     continue;
                 }
 
                 if k == "host" {
                     let patterns = parse_whitespace_pattern_list(v);
+                    let mut criteria = enclosing_criteria.clone();
+                    criteria.push(Criteria::Host(patterns));
                     groups.push(MatchGroup {
-                        criteria: vec![Criteria::Host(patterns)],
+                        criteria,
                         options: ConfigMap::new(),
-                        context: Context::FirstPass,
+                        context: enclosing_context,
                     });
+                    current = Some(groups.len() - 1);
                     continue;
                 }
 
                 if k == "match" {
-                    let mut criteria = vec![];
-                    let mut context = Context::FirstPass;
+                    let mut criteria = enclosing_criteria.clone();
+                    let mut context = enclosing_context;
 
-                    let mut tokens = v.split_ascii_whitespace();
+                    let args = split_match_args(v);
+                    let mut tokens = args.iter().map(|s| s.as_str());
 
                     while let Some(cname) = tokens.next() {
-                        match cname.to_lowercase().as_str() {
-                            "all" => {
-                                criteria.push(Criteria::All);
-                            }
+                        let (negated, cname) = match cname.strip_prefix('!') {
+                            Some(cname) => (true, cname),
+                            None => (false, cname),
+                        };
+                        let criterion = match cname.to_lowercase().as_str() {
+                            "all" => Criteria::All,
                             "canonical" => {
                                 context = Context::Canonical;
+                                continue;
                             }
                             "final" => {
                                 context = Context::Final;
+                                continue;
                             }
-                            "exec" => {
-                                criteria.push(Criteria::Exec(
-                                    tokens.next().unwrap_or("false").to_string(),
-                                ));
-                            }
+                            "exec" => Criteria::Exec(tokens.next().unwrap_or("false").to_string()),
                             "host" => {
-                                criteria.push(Criteria::Host(parse_pattern_list(
-                                    tokens.next().unwrap_or(""),
-                                )));
-                            }
-                            "originalhost" => {
-                                criteria.push(Criteria::OriginalHost(parse_pattern_list(
-                                    tokens.next().unwrap_or(""),
-                                )));
+                                Criteria::HostName(parse_pattern_list(tokens.next().unwrap_or("")))
                             }
+                            "originalhost" => Criteria::OriginalHost(parse_pattern_list(
+                                tokens.next().unwrap_or(""),
+                            )),
                             "user" => {
-                                criteria.push(Criteria::User(parse_pattern_list(
-                                    tokens.next().unwrap_or(""),
-                                )));
+                                Criteria::User(parse_pattern_list(tokens.next().unwrap_or("")))
                             }
                             "localuser" => {
-                                criteria.push(Criteria::LocalUser(parse_pattern_list(
-                                    tokens.next().unwrap_or(""),
-                                )));
+                                Criteria::LocalUser(parse_pattern_list(tokens.next().unwrap_or("")))
+                            }
+                            _ => {
+                                log::error!(
+                                    "unsupported criteria `{}` in `Match {}`; \
+                                     the Match will be ignored",
+                                    cname,
+                                    v
+                                );
+                                Criteria::Not(Box::new(Criteria::All))
                             }
-                            _ => break,
+                        };
+                        if negated {
+                            criteria.push(Criteria::Not(Box::new(criterion)));
+                        } else {
+                            criteria.push(criterion);
                         }
                     }
 
@@ -315,6 +483,7 @@ impl ParsedConfigFile {
                         options: ConfigMap::new(),
                         context,
                     });
+                    current = Some(groups.len() - 1);
                     continue;
                 }
 
@@ -333,8 +502,8 @@ impl ParsedConfigFile {
                         .or_insert_with(|| v.to_string());
                 }
 
-                if let Some(group) = groups.last_mut() {
-                    add_option(&mut group.options, k, v);
+                if let Some(idx) = current {
+                    add_option(&mut groups[idx].options, k, v);
                 } else {
                     add_option(options, k, v);
                 }
@@ -348,7 +517,6 @@ impl ParsedConfigFile {
     fn apply_matches(
         &self,
         hostname: &str,
-        user: &str,
         local_user: &str,
         context: Context,
         target: &mut ConfigMap,
@@ -362,7 +530,21 @@ impl ParsedConfigFile {
             if group.context != Context::FirstPass {
                 needs_reparse = true;
             }
-            if group.is_match(hostname, user, local_user, context) {
+            let is_match = {
+                // `Match host` and `Match user` test the values that
+                // have been resolved by the preceding lines
+                let state = MatchState {
+                    original_host: hostname,
+                    hostname: target
+                        .get("hostname")
+                        .map(|h| h.replace("%h", hostname))
+                        .unwrap_or_else(|| hostname.to_string()),
+                    user: target.get("user").map(|s| s.as_str()).unwrap_or(local_user),
+                    local_user,
+                };
+                group.is_match(&state, context)
+            };
+            if is_match {
                 for (k, v) in &group.options {
                     target.entry(k.to_string()).or_insert_with(|| v.to_string());
                 }
@@ -373,6 +555,45 @@ impl ParsedConfigFile {
     }
 }
 
+/// Limits how deeply the `ProxyJump` options of jump hosts can refer
+/// to further jump hosts, which guards against loops in the config
+const MAX_JUMP_DEPTH: usize = 8;
+
+/// Parses an entry of a `ProxyJump` list, which has the form
+/// `[user@]host[:port]` or `ssh://[user@]host[:port]`
+fn parse_jump_host(spec: &str) -> anyhow::Result<(Option<String>, String, Option<u16>)> {
+    let host_spec = spec.strip_prefix("ssh://").unwrap_or(spec);
+    let (user, host_port) = match host_spec.rfind('@') {
+        Some(idx) => (Some(host_spec[..idx].to_string()), &host_spec[idx + 1..]),
+        None => (None, host_spec),
+    };
+    let (host, port) = if let Some(rest) = host_port.strip_prefix('[') {
+        // An IPv6 address, which needs brackets when there is a port
+        let end = rest
+            .find(']')
+            .ok_or_else(|| anyhow::anyhow!("invalid ProxyJump host {}", spec))?;
+        (&rest[..end], rest[end + 1..].strip_prefix(':'))
+    } else {
+        match host_port.find(':') {
+            Some(idx) if !host_port[idx + 1..].contains(':') => {
+                (&host_port[..idx], Some(&host_port[idx + 1..]))
+            }
+            _ => (host_port, None),
+        }
+    };
+    if host.is_empty() {
+        anyhow::bail!("invalid ProxyJump host {}", spec);
+    }
+    let port = match port {
+        Some(port) => Some(
+            port.parse::<u16>()
+                .map_err(|_| anyhow::anyhow!("invalid port in ProxyJump host {}", spec))?,
+        ),
+        None => None,
+    };
+    Ok((user, host.to_string(), port))
+}
+
 /// A context for resolving configuration values.
 /// Holds a combination of environment and token expansion state,
 /// as well as the set of configs that should be consulted.
@@ -472,13 +693,7 @@ impl Config {
         let mut needs_reparse = false;
 
         for config in &self.config_files {
-            if config.apply_matches(
-                host,
-                target_user,
-                &local_user,
-                Context::FirstPass,
-                &mut result,
-            ) {
+            if config.apply_matches(host, &local_user, Context::FirstPass, &mut result) {
                 needs_reparse = true;
             }
         }
@@ -542,6 +757,53 @@ impl Config {
         result
     }
 
+    /// Resolves the `ProxyJump` option of `config`, which is the
+    /// configuration of some host, into the configuration of each of
+    /// the jump hosts, in the order in which they need to be connected.
+    /// As with `ssh`, given `ProxyJump a,b` the host is reached via `b`,
+    /// which is itself reached via `a`; `a` may have a `ProxyJump` option
+    /// of its own, which is resolved in the same way.
+    pub fn jump_hosts(&self, config: &ConfigMap) -> anyhow::Result<Vec<ConfigMap>> {
+        self.jump_hosts_impl(config, 0)
+    }
+
+    fn jump_hosts_impl(&self, config: &ConfigMap, depth: usize) -> anyhow::Result<Vec<ConfigMap>> {
+        let proxy_jump = match config.get("proxyjump") {
+            Some(p) if !p.is_empty() && p != "none" => p,
+            _ => return Ok(vec![]),
+        };
+        if depth >= MAX_JUMP_DEPTH {
+            anyhow::bail!(
+                "ProxyJump {} is nested more than {} levels deep",
+                proxy_jump,
+                MAX_JUMP_DEPTH
+            );
+        }
+
+        let mut hops = vec![];
+        for (idx, spec) in proxy_jump.split(',').enumerate() {
+            let (user, host, port) = parse_jump_host(spec.trim())?;
+            let mut hop = self.for_host(&host);
+            if let Some(user) = user {
+                hop.insert("user".to_string(), user);
+            }
+            if let Some(port) = port {
+                hop.insert("port".to_string(), port.to_string());
+            }
+            if idx == 0 {
+                hops.append(&mut self.jump_hosts_impl(&hop, depth + 1)?);
+            }
+            // The jump hosts are connected in order, each via the one
+            // before it, so only the first can use a ProxyCommand
+            hop.remove("proxyjump");
+            if !hops.is_empty() {
+                hop.remove("proxycommand");
+            }
+            hops.push(hop);
+        }
+        Ok(hops)
+    }
+
     /// Return true if a given option name is subject to environment variable
     /// expansion.
     fn should_expand_environment(&self, key: &str) -> bool {
@@ -644,6 +906,72 @@ mod test {
     use super::*;
     use k9::snapshot;
 
+    #[test]
+    fn parse_proxy_jump() {
+        assert_eq!(
+            parse_jump_host("bastion").unwrap(),
+            (None, "bastion".to_string(), None)
+        );
+        assert_eq!(
+            parse_jump_host("me@bastion:2222").unwrap(),
+            (Some("me".to_string()), "bastion".to_string(), Some(2222))
+        );
+        assert_eq!(
+            parse_jump_host("ssh://me@[::1]:2222").unwrap(),
+            (Some("me".to_string()), "::1".to_string(), Some(2222))
+        );
+        assert_eq!(
+            parse_jump_host("::1").unwrap(),
+            (None, "::1".to_string(), None)
+        );
+        assert!(parse_jump_host("bastion:ssh").is_err());
+        assert!(parse_jump_host("me@").is_err());
+
+        let mut config = Config::new();
+        let mut fake_env = ConfigMap::new();
+        fake_env.insert("HOME".to_string(), "/home/me".to_string());
+        fake_env.insert("USER".to_string(), "me".to_string());
+        config.assign_environment(fake_env);
+        config.add_config_string(
+            r#"
+        Host prod
+            ProxyJump inner,ops@edge:2222
+        Host inner
+            HostName 10.0.0.2
+            ProxyJump outer
+        Host edge
+            ProxyJump ignored
+        Host loop
+            ProxyJump loop
+            "#,
+        );
+
+        let summary = |hops: Vec<ConfigMap>| -> Vec<String> {
+            hops.iter()
+                .map(|hop| {
+                    assert!(hop.get("proxyjump").is_none());
+                    format!("{}@{}:{}", hop["user"], hop["hostname"], hop["port"])
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            summary(config.jump_hosts(&config.for_host("prod")).unwrap()),
+            vec!["me@outer:22", "me@10.0.0.2:22", "ops@edge:2222"]
+        );
+        // The ProxyJump of the last jump host is only ignored when it
+        // is reached via another jump host
+        assert_eq!(
+            summary(config.jump_hosts(&config.for_host("edge")).unwrap()),
+            vec!["me@ignored:22"]
+        );
+        assert!(config
+            .jump_hosts(&config.for_host("outer"))
+            .unwrap()
+            .is_empty());
+        assert!(config.jump_hosts(&config.for_host("loop")).is_err());
+    }
+
     #[test]
     fn parse_user() {
         let mut config = Config::new();
@@ -829,7 +1157,7 @@ Config {
             groups: [
                 MatchGroup {
                     criteria: [
-                        Host(
+                        HostName(
                             [
                                 Pattern {
                                     negated: false,
@@ -850,7 +1178,7 @@ Config {
                 },
                 MatchGroup {
                     criteria: [
-                        Host(
+                        HostName(
                             [
                                 Pattern {
                                     negated: true,
@@ -879,7 +1207,7 @@ Config {
                 },
                 MatchGroup {
                     criteria: [
-                        Host(
+                        HostName(
                             [
                                 Pattern {
                                     negated: true,
@@ -1197,4 +1525,153 @@ Config {
 "#
         );
     }
+
+    #[test]
+    fn parse_corpus() {
+        let mut config = Config::new();
+
+        let mut fake_env = ConfigMap::new();
+        fake_env.insert("HOME".to_string(), "/home/tester".to_string());
+        fake_env.insert("USER".to_string(), "tester".to_string());
+        config.assign_environment(fake_env);
+
+        config
+            .add_config_file(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ssh_config/config"));
+
+        snapshot!(
+            config.for_host("web1"),
+            r#"
+{
+    "hostname": "web1.internal.example.com",
+    "identityfile": "/home/tester/.ssh/deploy_key",
+    "port": "22",
+    "proxyjump": "bastion",
+    "serveraliveinterval": "30",
+    "user": "deploy",
+    "userknownhostsfile": "/home/tester/.ssh/known_hosts /home/tester/.ssh/known_hosts2",
+}
+"#
+        );
+
+        snapshot!(
+            config.for_host("db"),
+            r#"
+{
+    "hostname": "db.internal.example.com",
+    "identityfile": "/home/tester/.ssh/db_key",
+    "port": "22",
+    "proxyjump": "bastion",
+    "serveraliveinterval": "30",
+    "user": "dba",
+    "userknownhostsfile": "/home/tester/.ssh/known_hosts /home/tester/.ssh/known_hosts2",
+}
+"#
+        );
+
+        snapshot!(
+            config.for_host("pi"),
+            r#"
+{
+    "forwardagent": "yes",
+    "hostname": "192.168.1.10",
+    "identityfile": "/home/tester/.ssh/id_dsa /home/tester/.ssh/id_ecdsa /home/tester/.ssh/id_ed25519 /home/tester/.ssh/id_rsa",
+    "port": "2222",
+    "serveraliveinterval": "30",
+    "user": "pi",
+    "userknownhostsfile": "/home/tester/.ssh/known_hosts /home/tester/.ssh/known_hosts2",
+}
+"#
+        );
+
+        snapshot!(
+            config.for_host("bastion"),
+            r#"
+{
+    "hostname": "bastion.example.com",
+    "identityfile": "/home/tester/.ssh/id_dsa /home/tester/.ssh/id_ecdsa /home/tester/.ssh/id_ed25519 /home/tester/.ssh/id_rsa",
+    "port": "22",
+    "serveraliveinterval": "30",
+    "user": "jump",
+    "userknownhostsfile": "/home/tester/.ssh/known_hosts /home/tester/.ssh/known_hosts2",
+}
+"#
+        );
+
+        snapshot!(
+            config.for_host("other"),
+            r#"
+{
+    "forwardagent": "yes",
+    "hostname": "other",
+    "identityfile": "/home/tester/.ssh/id_dsa /home/tester/.ssh/id_ecdsa /home/tester/.ssh/id_ed25519 /home/tester/.ssh/id_rsa",
+    "port": "22",
+    "serveraliveinterval": "30",
+    "user": "fallback",
+    "userknownhostsfile": "/home/tester/.ssh/known_hosts /home/tester/.ssh/known_hosts2",
+}
+"#
+        );
+    }
+
+    #[test]
+    fn match_negation() {
+        let mut config = Config::new();
+
+        let mut fake_env = ConfigMap::new();
+        fake_env.insert("HOME".to_string(), "/home/me".to_string());
+        fake_env.insert("USER".to_string(), "me".to_string());
+        config.assign_environment(fake_env);
+
+        config.add_config_string(
+            r#"
+        Host * !secret
+            Port 2200
+
+        Match !localuser me
+            User nobody
+
+        Match all
+            Port 2222
+            "#,
+        );
+
+        let opts = config.for_host("secret");
+        assert_eq!(opts.get("port").map(|s| s.as_str()), Some("2222"));
+        assert_eq!(opts.get("user").map(|s| s.as_str()), Some("me"));
+
+        let opts = config.for_host("public");
+        assert_eq!(opts.get("port").map(|s| s.as_str()), Some("2200"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn match_exec() {
+        let mut config = Config::new();
+
+        let mut fake_env = ConfigMap::new();
+        fake_env.insert("HOME".to_string(), "/home/me".to_string());
+        fake_env.insert("USER".to_string(), "me".to_string());
+        config.assign_environment(fake_env);
+
+        config.add_config_string(
+            r#"
+        Match exec "test %n = office"
+            User worker
+
+        Match exec false
+            Port 1
+
+        Match host office exec true
+            Port 2022
+            "#,
+        );
+
+        let opts = config.for_host("office");
+        assert_eq!(opts.get("user").map(|s| s.as_str()), Some("worker"));
+        assert_eq!(opts.get("port").map(|s| s.as_str()), Some("2022"));
+
+        let opts = config.for_host("home");
+        assert_eq!(opts.get("user").map(|s| s.as_str()), Some("me"));
+        assert_eq!(opts.get("port").map(|s| s.as_str()), Some("22"));
+    }
 }
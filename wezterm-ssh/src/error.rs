@@ -0,0 +1,72 @@
+use std::time::Duration;
+use thiserror::Error;
+
+/// Represents the reason that a session could not be established.
+/// The failures that the caller may want to handle differently, such as
+/// by not retrying, have their own variants; anything else is `Other`.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum SessionError {
+    /// The connection was not established within `ConnectTimeout`
+    #[error("connecting to {address} timed out after {} seconds", .timeout.as_secs())]
+    ConnectTimeout { address: String, timeout: Duration },
+
+    /// The host is not known and `BatchMode` rules out asking
+    /// whether to trust it
+    #[error("host key verification failed: {host} is not known and BatchMode is enabled")]
+    HostKeyVerificationFailed { host: String },
+
+    /// No authentication method succeeded.  When `BatchMode` is enabled,
+    /// the methods that need to prompt are not attempted.
+    #[error(
+        "permission denied for {user}@{host} (methods: {methods}){}",
+        batch_mode_note(*.batch_mode)
+    )]
+    AuthenticationFailed {
+        user: String,
+        host: String,
+        methods: String,
+        batch_mode: bool,
+    },
+
+    #[error("{0}")]
+    Other(String),
+}
+
+fn batch_mode_note(batch_mode: bool) -> &'static str {
+    if batch_mode {
+        "; the methods that prompt are disabled by BatchMode"
+    } else {
+        ""
+    }
+}
+
+impl SessionError {
+    /// Returns the `SessionError` in the chain of `err`, if there is
+    /// one, and otherwise wraps the whole chain up as `Other`
+    pub(crate) fn from_anyhow(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<SessionError>())
+            .cloned()
+            .unwrap_or_else(|| SessionError::Other(format!("{:#}", err)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_anyhow() {
+        let host_key = SessionError::HostKeyVerificationFailed {
+            host: "foo:22".to_string(),
+        };
+        let err = anyhow::Error::new(host_key.clone()).context("bastion");
+        assert_eq!(SessionError::from_anyhow(&err), host_key);
+
+        let err = anyhow::anyhow!("boom").context("connecting");
+        assert_eq!(
+            SessionError::from_anyhow(&err),
+            SessionError::Other("connecting: boom".to_string())
+        );
+    }
+}
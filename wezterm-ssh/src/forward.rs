@@ -0,0 +1,111 @@
+//! Port forwarding.  For a local forward, the user of the session
+//! accepts the connections and passes each of them to `forward_socket`,
+//! which connects it to the target through a direct-tcpip channel.
+//! For a remote forward, the remote host listens for the connections,
+//! and the channels that it opens for them are connected to the target
+//! from here.
+use crate::session::{CancelForward, ForwardSocket, ListenForward};
+use crate::sessionwrap::SessionWrap;
+use anyhow::Context;
+use filedescriptor::FileDescriptor;
+use std::net::TcpStream;
+
+fn tcp_stream_into_fd(stream: TcpStream) -> FileDescriptor {
+    #[cfg(unix)]
+    {
+        FileDescriptor::new(stream)
+    }
+    #[cfg(windows)]
+    unsafe {
+        use std::os::windows::io::{FromRawSocket, IntoRawSocket};
+        FileDescriptor::from_raw_socket(stream.into_raw_socket())
+    }
+}
+
+/// Where to connect the connections accepted by a remote forward
+#[derive(Debug)]
+pub(crate) struct RemoteForward {
+    pub bind_host: String,
+    pub target_host: String,
+    pub target_port: u16,
+}
+
+impl crate::sessioninner::SessionInner {
+    pub(crate) fn forward_socket(
+        &mut self,
+        sess: &mut SessionWrap,
+        msg: ForwardSocket,
+    ) -> anyhow::Result<()> {
+        let channel = sess
+            .open_direct_tcpip(&msg.host, msg.port)
+            .with_context(|| format!("opening channel to {}:{}", msg.host, msg.port))?;
+        self.add_socket_channel(channel, tcp_stream_into_fd(msg.socket))
+    }
+
+    pub(crate) fn listen_forward(
+        &mut self,
+        sess: &mut SessionWrap,
+        msg: ListenForward,
+    ) -> anyhow::Result<u16> {
+        let port = sess
+            .listen_forward(&msg.bind_host, msg.bind_port)
+            .with_context(|| {
+                format!(
+                    "asking the remote host to listen on {}:{}",
+                    msg.bind_host, msg.bind_port
+                )
+            })?;
+        self.remote_forwards.insert(
+            port,
+            RemoteForward {
+                bind_host: msg.bind_host,
+                target_host: msg.target_host,
+                target_port: msg.target_port,
+            },
+        );
+        Ok(port)
+    }
+
+    pub(crate) fn cancel_forward(&mut self, sess: &mut SessionWrap, msg: CancelForward) {
+        log::trace!("cancel forward {}:{}", msg.bind_host, msg.bind_port);
+        self.remote_forwards.remove(&msg.bind_port);
+        sess.cancel_forward(msg.bind_port);
+    }
+
+    /// Connects any channels that the remote host has opened for the
+    /// connections accepted by our remote forwards to their targets
+    pub(crate) fn accept_remote_forwards(&mut self, sess: &mut SessionWrap) {
+        while let Some((port, mut channel)) = sess.accept_forward() {
+            let forward = match self.remote_forwards.get(&port) {
+                Some(forward) => forward,
+                None => {
+                    log::error!(
+                        "remote host forwarded a connection for port {}, \
+                         which is not being forwarded",
+                        port
+                    );
+                    channel.close();
+                    continue;
+                }
+            };
+
+            let target = (forward.target_host.as_str(), forward.target_port);
+            let sock = match TcpStream::connect(target).with_context(|| {
+                format!(
+                    "connecting {}:{} to {}:{}",
+                    forward.bind_host, port, forward.target_host, forward.target_port
+                )
+            }) {
+                Ok(stream) => tcp_stream_into_fd(stream),
+                Err(err) => {
+                    log::error!("Failed to forward connection: {:#}", err);
+                    channel.close();
+                    continue;
+                }
+            };
+            if let Err(err) = self.add_socket_channel(channel, sock) {
+                log::error!("Failed to forward connection: {:#}", err);
+            }
+        }
+    }
+}
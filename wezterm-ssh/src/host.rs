@@ -1,3 +1,4 @@
+use crate::error::SessionError;
 use crate::session::SessionEvent;
 use anyhow::{anyhow, Context};
 use libssh_rs as libssh;
@@ -6,22 +7,85 @@ use ssh2::CheckResult;
 use std::io::Write;
 use std::path::Path;
 
+/// How the user responded to a `HostVerificationEvent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostVerificationDecision {
+    /// Don't trust the host, which abandons the connection
+    Reject,
+    /// Trust the host for this connection only
+    Accept,
+    /// Trust the host and add its key to the known_hosts file
+    Remember,
+}
+
+impl From<bool> for HostVerificationDecision {
+    fn from(trust_host: bool) -> Self {
+        if trust_host {
+            Self::Remember
+        } else {
+            Self::Reject
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct HostVerificationEvent {
     pub message: String,
-    pub(crate) reply: Sender<bool>,
+    /// The host and port that is being connected to
+    pub host: String,
+    /// The fingerprint of the host key presented by the server
+    pub fingerprint: String,
+    pub(crate) reply: Sender<HostVerificationDecision>,
 }
 
 impl HostVerificationEvent {
+    /// Answers the prompt; trusting the host adds it to known_hosts
     pub async fn answer(self, trust_host: bool) -> anyhow::Result<()> {
-        Ok(self.reply.send(trust_host).await?)
+        self.decide(trust_host.into()).await
     }
     pub fn try_answer(self, trust_host: bool) -> anyhow::Result<()> {
-        Ok(self.reply.try_send(trust_host)?)
+        self.try_decide(trust_host.into())
+    }
+    pub async fn decide(self, decision: HostVerificationDecision) -> anyhow::Result<()> {
+        Ok(self.reply.send(decision).await?)
+    }
+    pub fn try_decide(self, decision: HostVerificationDecision) -> anyhow::Result<()> {
+        Ok(self.reply.try_send(decision)?)
     }
 }
 
 impl crate::sessioninner::SessionInner {
+    /// Asks the user whether to trust a host that is not yet known,
+    /// returning an error if they decline
+    fn confirm_host(
+        &mut self,
+        message: String,
+        host: String,
+        fingerprint: String,
+    ) -> anyhow::Result<HostVerificationDecision> {
+        if self.batch_mode() {
+            return Err(SessionError::HostKeyVerificationFailed { host }.into());
+        }
+
+        let (reply, confirm) = bounded(1);
+        self.tx_event
+            .try_send(SessionEvent::HostVerify(HostVerificationEvent {
+                message,
+                host,
+                fingerprint,
+                reply,
+            }))
+            .context("sending HostVerify request to user")?;
+
+        let decision = smol::block_on(confirm.recv())
+            .context("waiting for host verification confirmation from user")?;
+
+        if decision == HostVerificationDecision::Reject {
+            anyhow::bail!("user declined to trust host");
+        }
+        Ok(decision)
+    }
+
     pub fn host_verification_libssh(
         &mut self,
         sess: &libssh::Session,
@@ -31,31 +95,26 @@ impl crate::sessioninner::SessionInner {
         let key = sess
             .get_server_public_key()?
             .get_public_key_hash_hexa(libssh::PublicKeyHashType::Sha256)?;
+        self.host_fingerprint.replace(key.clone());
 
         match sess.is_known_server()? {
             libssh::KnownHosts::Ok => Ok(()),
             libssh::KnownHosts::NotFound | libssh::KnownHosts::Unknown => {
-                let (reply, confirm) = bounded(1);
-                self.tx_event
-                    .try_send(SessionEvent::HostVerify(HostVerificationEvent {
-                        message: format!(
-                            "SSH host {}:{} is not yet trusted.\n\
+                let decision = self.confirm_host(
+                    format!(
+                        "SSH host {}:{} is not yet trusted.\n\
                                     Fingerprint: {}.\n\
                                     Trust and continue connecting?",
-                            hostname, port, key
-                        ),
-                        reply,
-                    }))
-                    .context("sending HostVerify request to user")?;
-
-                let trusted = smol::block_on(confirm.recv())
-                    .context("waiting for host verification confirmation from user")?;
+                        hostname, port, key
+                    ),
+                    format!("{}:{}", hostname, port),
+                    key,
+                )?;
 
-                if !trusted {
-                    anyhow::bail!("user declined to trust host");
+                if decision == HostVerificationDecision::Remember {
+                    sess.update_known_hosts_file()?;
                 }
-
-                Ok(sess.update_known_hosts_file()?)
+                Ok(())
             }
             libssh::KnownHosts::Changed => {
                 anyhow::bail!(
@@ -133,28 +192,24 @@ impl crate::sessioninner::SessionInner {
                     })
                 })
                 .ok_or_else(|| anyhow!("failed to get host fingerprint"))?;
+            self.host_fingerprint.replace(fingerprint.clone());
 
             match known_hosts.check_port(&remote_host_name, port, key) {
                 CheckResult::Match => {}
                 CheckResult::NotFound => {
-                    let (reply, confirm) = bounded(1);
-                    self.tx_event
-                        .try_send(SessionEvent::HostVerify(HostVerificationEvent {
-                            message: format!(
-                                "SSH host {} is not yet trusted.\n\
+                    let decision = self.confirm_host(
+                        format!(
+                            "SSH host {} is not yet trusted.\n\
                                 {:?} Fingerprint: {}.\n\
                                 Trust and continue connecting?",
-                                remote_address, key_type, fingerprint
-                            ),
-                            reply,
-                        }))
-                        .context("sending HostVerify request to user")?;
-
-                    let trusted = smol::block_on(confirm.recv())
-                        .context("waiting for host verification confirmation from user")?;
+                            remote_address, key_type, fingerprint
+                        ),
+                        remote_address.to_string(),
+                        fingerprint.clone(),
+                    )?;
 
-                    if !trusted {
-                        anyhow::bail!("user declined to trust host");
+                    if decision == HostVerificationDecision::Accept {
+                        return Ok(());
                     }
 
                     let host_and_port = if port != 22 {
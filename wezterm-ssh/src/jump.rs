@@ -0,0 +1,116 @@
+//! ProxyJump support.  The jump hosts are connected in order, each one
+//! tunnelled through a direct-tcpip channel of the one before it, and
+//! the session to the host itself is tunnelled through the last of them.
+//! The events of the jump host sessions, such as their authentication
+//! prompts, are passed on to the user of the session to the host.
+use crate::config::{Config, ConfigMap};
+use crate::session::{Session, SessionEvent};
+use anyhow::{anyhow, Context};
+use smol::channel::Receiver;
+
+fn hostname_and_port(config: &ConfigMap) -> anyhow::Result<(String, u16)> {
+    let hostname = config
+        .get("hostname")
+        .ok_or_else(|| anyhow!("hostname not present in config"))?
+        .to_string();
+    let port = config
+        .get("port")
+        .ok_or_else(|| anyhow!("port is always set in config loader"))?
+        .parse::<u16>()?;
+    Ok((hostname, port))
+}
+
+/// Describes a jump host in prompts and errors
+fn jump_host_label(config: &ConfigMap) -> String {
+    let hostname = config.get("hostname").map(|s| s.as_str()).unwrap_or("?");
+    match config.get("user") {
+        Some(user) => format!("{}@{} (jump host)", user, hostname),
+        None => format!("{} (jump host)", hostname),
+    }
+}
+
+impl crate::sessioninner::SessionInner {
+    /// If the host has a ProxyJump option, connects to the jump hosts
+    /// and places a socket that is tunnelled through them to
+    /// `hostname:port` in `self.transport`.
+    /// The returned jump host sessions must be kept alive for as long
+    /// as the socket is in use.
+    pub(crate) fn connect_jump_hosts(
+        &mut self,
+        hostname: &str,
+        port: u16,
+    ) -> anyhow::Result<Vec<Session>> {
+        let mut ssh_config = Config::new();
+        ssh_config.add_default_config_files();
+        let mut hops = ssh_config.jump_hosts(&self.config)?;
+        if hops.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // The jump hosts use the same backend and options as this session
+        for hop in &mut hops {
+            for (key, value) in &self.config {
+                if key.starts_with("wezterm_") {
+                    hop.entry(key.to_string())
+                        .or_insert_with(|| value.to_string());
+                }
+            }
+        }
+
+        let mut targets = hops
+            .iter()
+            .skip(1)
+            .map(hostname_and_port)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        targets.push((hostname.to_string(), port));
+
+        let mut sessions = vec![];
+        for (hop, (target_host, target_port)) in hops.into_iter().zip(targets) {
+            let label = jump_host_label(&hop);
+            let (session, events) = Session::connect_with_transport(hop, self.transport.take())
+                .with_context(|| format!("connecting to {}", label))?;
+            self.forward_jump_host_events(&label, &events)?;
+            let tunnel = smol::block_on(session.direct_tcpip(&target_host, target_port))
+                .with_context(|| {
+                    format!(
+                        "connecting to {}:{} via {}",
+                        target_host, target_port, label
+                    )
+                })?;
+            self.transport.replace(tunnel);
+            sessions.push(session);
+        }
+
+        Ok(sessions)
+    }
+
+    /// Passes the events of a jump host session on to the user of this
+    /// session until the jump host session is authenticated.
+    /// Prompts and banners are labelled with the jump host so that they
+    /// can be told apart from those of the host itself.
+    fn forward_jump_host_events(
+        &mut self,
+        label: &str,
+        events: &Receiver<SessionEvent>,
+    ) -> anyhow::Result<()> {
+        while let Ok(event) = smol::block_on(events.recv()) {
+            let event = match event {
+                SessionEvent::Authenticated => return Ok(()),
+                SessionEvent::Error(err) => {
+                    return Err(anyhow::Error::new(err).context(label.to_string()))
+                }
+                SessionEvent::Banner(Some(banner)) => {
+                    SessionEvent::Banner(Some(format!("{}: {}", label, banner)))
+                }
+                SessionEvent::Authenticate(mut auth) => {
+                    auth.label = Some(label.to_string());
+                    SessionEvent::Authenticate(auth)
+                }
+                event => event,
+            };
+            smol::block_on(self.tx_event.send(event))
+                .context("forwarding jump host event to user")?;
+        }
+        anyhow::bail!("{} disconnected before authenticating", label)
+    }
+}
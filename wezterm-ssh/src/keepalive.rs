@@ -0,0 +1,117 @@
+//! Keepalives, following the `ServerAliveInterval` and `ServerAliveCountMax`
+//! options of OpenSSH.  When nothing has been received from the remote host
+//! for an interval, a keepalive request that the host has to reply to is
+//! sent.  Once more than the count of keepalives have gone unanswered, the
+//! session is considered to be dead and is ended, rather than waiting for
+//! the connection to time out, which can take a very long time when a NAT
+//! gateway has silently dropped it.
+use crate::config::ConfigMap;
+use crate::sessionwrap::SessionWrap;
+use anyhow::Context;
+use std::time::{Duration, Instant};
+
+/// The value of `ServerAliveCountMax` when it isn't set
+const DEFAULT_COUNT_MAX: usize = 3;
+
+#[derive(Debug)]
+pub(crate) struct KeepAlive {
+    host: String,
+    interval: Duration,
+    count_max: usize,
+    /// When the next keepalive is due
+    due: Instant,
+    /// The number of keepalives sent since anything was last received
+    unanswered: usize,
+}
+
+impl KeepAlive {
+    /// Returns the keepalive settings of the host, or None if
+    /// `ServerAliveInterval` is not set, or is 0
+    pub fn from_config(config: &ConfigMap) -> anyhow::Result<Option<Self>> {
+        let interval = match config.get("serveraliveinterval") {
+            Some(interval) => interval
+                .parse::<u64>()
+                .with_context(|| format!("parsing ServerAliveInterval {}", interval))?,
+            None => return Ok(None),
+        };
+        if interval == 0 {
+            return Ok(None);
+        }
+        let count_max = match config.get("serveralivecountmax") {
+            Some(count) => count
+                .parse::<usize>()
+                .with_context(|| format!("parsing ServerAliveCountMax {}", count))?,
+            None => DEFAULT_COUNT_MAX,
+        };
+        let interval = Duration::from_secs(interval);
+
+        Ok(Some(Self {
+            host: config.get("hostname").cloned().unwrap_or_default(),
+            interval,
+            count_max,
+            due: Instant::now() + interval,
+            unanswered: 0,
+        }))
+    }
+
+    /// Whether a keepalive has been sent and not yet answered, in which
+    /// case the loop of the session needs to watch for the reply
+    pub fn awaiting_reply(&self) -> bool {
+        self.unanswered > 0
+    }
+
+    /// Records that something was received from the remote host
+    pub fn received(&mut self) {
+        self.unanswered = 0;
+        self.due = Instant::now() + self.interval;
+    }
+
+    /// How long the loop of the session can wait before calling `tick`
+    pub fn time_until_due(&self) -> Duration {
+        self.due.saturating_duration_since(Instant::now())
+    }
+
+    /// Sends a keepalive if one is due, returning an error once more
+    /// than `ServerAliveCountMax` keepalives have gone unanswered
+    pub fn tick(&mut self, sess: &mut SessionWrap) -> anyhow::Result<()> {
+        let now = Instant::now();
+        if now < self.due {
+            return Ok(());
+        }
+        if self.unanswered >= self.count_max {
+            anyhow::bail!("Timeout, server {} not responding", self.host);
+        }
+        sess.send_keepalive().context("sending keepalive")?;
+        self.unanswered += 1;
+        self.due = now + self.interval;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings() {
+        let mut config = ConfigMap::new();
+        config.insert("hostname".to_string(), "foo".to_string());
+        assert!(KeepAlive::from_config(&config).unwrap().is_none());
+
+        config.insert("serveraliveinterval".to_string(), "0".to_string());
+        assert!(KeepAlive::from_config(&config).unwrap().is_none());
+
+        config.insert("serveraliveinterval".to_string(), "15".to_string());
+        let keepalive = KeepAlive::from_config(&config).unwrap().unwrap();
+        assert_eq!(keepalive.interval, Duration::from_secs(15));
+        assert_eq!(keepalive.count_max, DEFAULT_COUNT_MAX);
+        assert!(!keepalive.awaiting_reply());
+
+        config.insert("serveralivecountmax".to_string(), "1".to_string());
+        let keepalive = KeepAlive::from_config(&config).unwrap().unwrap();
+        assert_eq!(keepalive.count_max, 1);
+
+        config.insert("serveraliveinterval".to_string(), "soon".to_string());
+        assert!(KeepAlive::from_config(&config).is_err());
+    }
+}
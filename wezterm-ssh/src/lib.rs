@@ -1,9 +1,14 @@
+mod agent;
 mod auth;
 mod channelwrap;
 mod config;
 mod dirwrap;
+mod error;
 mod filewrap;
+mod forward;
 mod host;
+mod jump;
+mod keepalive;
 mod pty;
 mod session;
 mod sessioninner;
@@ -13,6 +18,7 @@ mod sftpwrap;
 
 pub use auth::*;
 pub use config::*;
+pub use error::*;
 pub use host::*;
 pub use pty::*;
 pub use session::*;
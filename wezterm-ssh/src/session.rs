@@ -1,5 +1,6 @@
 use crate::auth::*;
 use crate::config::ConfigMap;
+use crate::error::SessionError;
 use crate::host::*;
 use crate::pty::*;
 use crate::sessioninner::*;
@@ -9,6 +10,7 @@ use portable_pty::PtySize;
 use smol::channel::{bounded, Receiver, Sender};
 use std::collections::HashMap;
 use std::io::Write;
+use std::net::TcpStream;
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
@@ -16,7 +18,7 @@ pub enum SessionEvent {
     Banner(Option<String>),
     HostVerify(HostVerificationEvent),
     Authenticate(AuthenticationEvent),
-    Error(String),
+    Error(SessionError),
     Authenticated,
 }
 
@@ -52,6 +54,10 @@ pub(crate) enum SessionRequest {
     Exec(Exec, Sender<anyhow::Result<ExecResult>>),
     Sftp(SftpRequest),
     SignalChannel(SignalChannel),
+    DirectTcpIp(DirectTcpIp, Sender<anyhow::Result<FileDescriptor>>),
+    ForwardSocket(ForwardSocket, Sender<anyhow::Result<()>>),
+    ListenForward(ListenForward, Sender<anyhow::Result<u16>>),
+    CancelForward(CancelForward),
 }
 
 #[derive(Debug)]
@@ -66,6 +72,33 @@ pub(crate) struct Exec {
     pub env: Option<HashMap<String, String>>,
 }
 
+#[derive(Debug)]
+pub(crate) struct DirectTcpIp {
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug)]
+pub(crate) struct ForwardSocket {
+    pub host: String,
+    pub port: u16,
+    pub socket: TcpStream,
+}
+
+#[derive(Debug)]
+pub(crate) struct ListenForward {
+    pub bind_host: String,
+    pub bind_port: u16,
+    pub target_host: String,
+    pub target_port: u16,
+}
+
+#[derive(Debug)]
+pub(crate) struct CancelForward {
+    pub bind_host: String,
+    pub bind_port: u16,
+}
+
 #[derive(Clone)]
 pub struct Session {
     tx: SessionSender,
@@ -79,6 +112,16 @@ impl Drop for Session {
 
 impl Session {
     pub fn connect(config: ConfigMap) -> anyhow::Result<(Self, Receiver<SessionEvent>)> {
+        Self::connect_with_transport(config, None)
+    }
+
+    /// Like `connect`, but if `transport` is provided the ssh protocol
+    /// is spoken over it rather than over a new connection to the host.
+    /// This is used to tunnel through jump hosts.
+    pub(crate) fn connect_with_transport(
+        config: ConfigMap,
+        transport: Option<FileDescriptor>,
+    ) -> anyhow::Result<(Self, Receiver<SessionEvent>)> {
         let (tx_event, rx_event) = bounded(8);
         let (tx_req, rx_req) = bounded(8);
         let (mut sender_write, mut sender_read) = socketpair()?;
@@ -100,6 +143,10 @@ impl Session {
             next_channel_id: 1,
             next_file_id: 1,
             sender_read,
+            transport,
+            forwarded_agent: None,
+            remote_forwards: HashMap::new(),
+            host_fingerprint: None,
         };
         std::thread::spawn(move || inner.run());
         Ok((Self { tx: session_sender }, rx_event))
@@ -150,6 +197,81 @@ impl Session {
         Ok(exec)
     }
 
+    /// Asks the remote host to connect to `host:port`, returning a
+    /// socket that is connected to it through the ssh session
+    pub async fn direct_tcpip(&self, host: &str, port: u16) -> anyhow::Result<FileDescriptor> {
+        let (reply, rx) = bounded(1);
+        self.tx
+            .send(SessionRequest::DirectTcpIp(
+                DirectTcpIp {
+                    host: host.to_string(),
+                    port,
+                },
+                reply,
+            ))
+            .await?;
+        rx.recv().await?
+    }
+
+    /// Like `direct_tcpip`, except that the data of the connection to
+    /// `host:port` is carried by `socket`, such as a connection that
+    /// was accepted by a local port forward
+    pub async fn forward_socket(
+        &self,
+        host: &str,
+        port: u16,
+        socket: TcpStream,
+    ) -> anyhow::Result<()> {
+        let (reply, rx) = bounded(1);
+        self.tx
+            .send(SessionRequest::ForwardSocket(
+                ForwardSocket {
+                    host: host.to_string(),
+                    port,
+                    socket,
+                },
+                reply,
+            ))
+            .await?;
+        rx.recv().await?
+    }
+
+    /// Asks the remote host to listen on `bind_host:bind_port`, and
+    /// connects the connections that it accepts to `target_host:target_port`.
+    /// If `bind_port` is 0 then the remote host picks the port.
+    /// Returns the port that the remote host is listening on.
+    pub async fn listen_forward(
+        &self,
+        bind_host: &str,
+        bind_port: u16,
+        target_host: &str,
+        target_port: u16,
+    ) -> anyhow::Result<u16> {
+        let (reply, rx) = bounded(1);
+        self.tx
+            .send(SessionRequest::ListenForward(
+                ListenForward {
+                    bind_host: bind_host.to_string(),
+                    bind_port,
+                    target_host: target_host.to_string(),
+                    target_port,
+                },
+                reply,
+            ))
+            .await?;
+        rx.recv().await?
+    }
+
+    /// Stops forwarding the connections that the remote host accepts
+    /// on a port that was set up by `listen_forward`
+    pub fn cancel_forward(&self, bind_host: &str, bind_port: u16) -> anyhow::Result<()> {
+        self.tx
+            .try_send(SessionRequest::CancelForward(CancelForward {
+                bind_host: bind_host.to_string(),
+                bind_port,
+            }))
+    }
+
     /// Creates a new reference to the sftp channel for filesystem operations
     ///
     /// ### Note
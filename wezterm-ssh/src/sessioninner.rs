@@ -1,9 +1,12 @@
 use crate::channelwrap::ChannelWrap;
 use crate::config::ConfigMap;
 use crate::dirwrap::DirWrap;
+use crate::error::SessionError;
 use crate::filewrap::FileWrap;
+use crate::forward::RemoteForward;
+use crate::keepalive::KeepAlive;
 use crate::pty::*;
-use crate::session::{Exec, ExecResult, SessionEvent, SessionRequest, SignalChannel};
+use crate::session::{DirectTcpIp, Exec, ExecResult, SessionEvent, SessionRequest, SignalChannel};
 use crate::sessionwrap::SessionWrap;
 use crate::sftp::dir::{Dir, DirId, DirRequest};
 use crate::sftp::file::{File, FileId, FileRequest};
@@ -12,16 +15,18 @@ use crate::sftpwrap::SftpWrap;
 use anyhow::{anyhow, Context};
 use camino::Utf8PathBuf;
 use filedescriptor::{
-    poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN, POLLOUT,
+    poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, IntoRawSocketDescriptor,
+    POLLIN, POLLOUT,
 };
+use libssh2_sys::LIBSSH2_ERROR_TIMEOUT;
 use libssh_rs as libssh;
 use portable_pty::ExitStatus;
 use smol::channel::{bounded, Receiver, Sender, TryRecvError};
 use std::collections::{HashMap, VecDeque};
 use std::ffi::CStr;
 use std::io::{Read, Write};
-use std::net::TcpStream;
-use std::time::Duration;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub(crate) struct DescriptorState {
@@ -48,6 +53,18 @@ pub(crate) struct SessionInner {
     pub next_channel_id: ChannelId,
     pub next_file_id: FileId,
     pub sender_read: FileDescriptor,
+    /// A connected socket to speak the ssh protocol over, rather than
+    /// connecting to the host; set for jump hosts and for sessions
+    /// that are tunnelled through them
+    pub transport: Option<FileDescriptor>,
+    /// The local agent that is forwarded to the remote host, if
+    /// agent forwarding is enabled
+    pub forwarded_agent: Option<String>,
+    /// The targets of the remote port forwards, keyed by the port
+    /// that the remote host is listening on
+    pub remote_forwards: HashMap<u16, RemoteForward>,
+    /// The fingerprint of the host key, once it has been verified
+    pub host_fingerprint: Option<String>,
 }
 
 impl Drop for SessionInner {
@@ -60,7 +77,7 @@ impl SessionInner {
     pub fn run(&mut self) {
         if let Err(err) = self.run_impl() {
             self.tx_event
-                .try_send(SessionEvent::Error(format!("{:#}", err)))
+                .try_send(SessionEvent::Error(SessionError::from_anyhow(&err)))
                 .ok();
         }
     }
@@ -81,6 +98,41 @@ impl SessionInner {
         }
     }
 
+    /// Whether `BatchMode` is enabled, in which case the user is never
+    /// prompted for passwords, passphrases or to trust a host
+    pub fn batch_mode(&self) -> bool {
+        self.config
+            .get("batchmode")
+            .map(|value| value == "yes")
+            .unwrap_or(false)
+    }
+
+    /// Whether `IdentitiesOnly` is enabled, in which case only the keys
+    /// of the `IdentityFile`s are offered, even if the agent has others
+    pub fn identities_only(&self) -> bool {
+        self.config
+            .get("identitiesonly")
+            .map(|value| value == "yes")
+            .unwrap_or(false)
+    }
+
+    /// Returns `ConnectTimeout`, or None if it isn't set, or is 0
+    fn connect_timeout(&self) -> anyhow::Result<Option<Duration>> {
+        match self.config.get("connecttimeout") {
+            Some(timeout) if timeout != "none" => {
+                let timeout = timeout
+                    .parse::<u64>()
+                    .with_context(|| format!("parsing ConnectTimeout {}", timeout))?;
+                if timeout == 0 {
+                    Ok(None)
+                } else {
+                    Ok(Some(Duration::from_secs(timeout)))
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn run_impl_libssh(&mut self) -> anyhow::Result<()> {
         let hostname = self
             .config
@@ -154,7 +206,26 @@ impl SessionInner {
         sess.set_option(libssh::SshOption::User(Some(user)))?;
         sess.set_option(libssh::SshOption::Port(port))?;
         sess.options_parse_config(None)?; // FIXME: overridden config path?
-        if let Some(agent) = self.config.get("identityagent") {
+        let _jump_sessions = self.connect_jump_hosts(&hostname, port)?;
+        let tunnelled = match self.transport.take() {
+            Some(transport) => {
+                sess.set_option(libssh::SshOption::Socket(
+                    transport.into_socket_descriptor() as _,
+                ))?;
+                true
+            }
+            None => false,
+        };
+        if self.identities_only() {
+            // libssh offers every key of the agent, and can't be told to
+            // offer only some of them, so don't use the agent at all.
+            // The IdentityFiles are loaded directly, instead, which means
+            // that keys that only the agent has can't be used; the ssh2
+            // backend filters the keys of the agent instead.
+            sess.set_option(libssh::SshOption::IdentityAgent(Some(
+                "/dev/null".to_string(),
+            )))?;
+        } else if let Some(agent) = self.config.get("identityagent") {
             sess.set_option(libssh::SshOption::IdentityAgent(Some(agent.clone())))?;
         }
         if let Some(files) = self.config.get("identityfile") {
@@ -168,14 +239,37 @@ impl SessionInner {
                 break;
             }
         }
-        if let Some(cmd) = self.config.get("proxycommand") {
+        if let Some(cmd) = self.config.get("proxycommand").filter(|_| !tunnelled) {
             sess.set_option(libssh::SshOption::ProxyCommand(Some(cmd.to_string())))?;
         }
         if let Some(types) = self.config.get("pubkeyacceptedtypes") {
             sess.set_option(libssh::SshOption::PublicKeyAcceptedTypes(types.to_string()))?;
         }
+        if self.wants_agent_forwarding() {
+            log::error!(
+                "ForwardAgent is enabled for {}, but agent forwarding is not \
+                 supported by the libssh backend; set wezterm_ssh_backend or \
+                 ssh_backend to ssh2 to use it",
+                hostname
+            );
+        }
+        let connect_timeout = self.connect_timeout()?;
+        if let Some(timeout) = connect_timeout {
+            sess.set_option(libssh::SshOption::Timeout(timeout))?;
+        }
 
-        sess.connect()?;
+        let started = Instant::now();
+        if let Err(err) = sess.connect() {
+            // libssh doesn't report a timeout as such
+            if let Some(timeout) = connect_timeout.filter(|t| started.elapsed() >= *t) {
+                return Err(SessionError::ConnectTimeout {
+                    address: format!("{}:{}", hostname, port),
+                    timeout,
+                }
+                .into());
+            }
+            return Err(err.into());
+        }
 
         let banner = sess.get_server_banner()?;
         self.tx_event
@@ -225,14 +319,17 @@ impl SessionInner {
             ))))
             .context("notifying user of banner")?;
 
-        let tcp: TcpStream = if let Some(proxy_command) =
-            self.config.get("proxycommand").and_then(|c| {
-                if !c.is_empty() && c != "none" {
-                    Some(c)
-                } else {
-                    None
-                }
-            }) {
+        let connect_timeout = self.connect_timeout()?;
+        let _jump_sessions = self.connect_jump_hosts(&hostname, port)?;
+        let tcp: TcpStream = if let Some(transport) = self.transport.take() {
+            socket_into_tcp_stream(transport)
+        } else if let Some(proxy_command) = self.config.get("proxycommand").and_then(|c| {
+            if !c.is_empty() && c != "none" {
+                Some(c)
+            } else {
+                None
+            }
+        }) {
             let mut cmd;
             if cfg!(windows) {
                 let comspec = std::env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_string());
@@ -252,25 +349,21 @@ impl SessionInner {
                 .spawn()
                 .with_context(|| format!("spawning ProxyCommand {}", proxy_command))?;
 
-            #[cfg(unix)]
-            unsafe {
-                use std::os::unix::io::{FromRawFd, IntoRawFd};
-                TcpStream::from_raw_fd(a.into_raw_fd())
-            }
-            #[cfg(windows)]
-            unsafe {
-                use std::os::windows::io::{FromRawSocket, IntoRawSocket};
-                TcpStream::from_raw_socket(a.into_raw_socket())
-            }
+            socket_into_tcp_stream(a)
         } else {
-            let socket = TcpStream::connect((hostname.as_str(), port))
-                .with_context(|| format!("connecting to {}", remote_address))?;
+            let socket = match connect_timeout {
+                Some(timeout) => connect_with_timeout(&hostname, port, timeout)?,
+                None => TcpStream::connect((hostname.as_str(), port))
+                    .with_context(|| format!("connecting to {}", remote_address))?,
+            };
             socket
                 .set_nodelay(true)
                 .context("setting TCP NODELAY on ssh connection")?;
             socket
         };
 
+        self.forwarded_agent = self.resolve_forwarded_agent(&hostname);
+
         let mut sess = ssh2::Session::new()?;
         if self
             .config
@@ -283,8 +376,23 @@ impl SessionInner {
         }
         sess.set_blocking(true);
         sess.set_tcp_stream(tcp);
-        sess.handshake()
-            .with_context(|| format!("ssh handshake with {}", remote_address))?;
+        // ConnectTimeout also covers the exchange of the banners
+        if let Some(timeout) = connect_timeout {
+            sess.set_timeout(timeout.as_millis() as u32);
+        }
+        if let Err(err) = sess.handshake() {
+            return match connect_timeout {
+                Some(timeout) if err.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT) => {
+                    Err(SessionError::ConnectTimeout {
+                        address: remote_address,
+                        timeout,
+                    }
+                    .into())
+                }
+                _ => Err(err).with_context(|| format!("ssh handshake with {}", remote_address)),
+            };
+        }
+        sess.set_timeout(0);
 
         self.tx_event
             .try_send(SessionEvent::Banner(sess.banner().map(|s| s.to_string())))
@@ -308,11 +416,27 @@ impl SessionInner {
 
     fn request_loop(&mut self, sess: &mut SessionWrap) -> anyhow::Result<()> {
         let mut sleep_delay = Duration::from_millis(100);
+        let mut keepalive = KeepAlive::from_config(&self.config)?;
 
         loop {
             self.tick_io()?;
             self.drain_request_pipe();
             self.dispatch_pending_requests(sess)?;
+            self.accept_remote_forwards(sess);
+
+            // Sent last so that the reply can't be consumed before
+            // the poll below sees it arrive
+            let mut session_events = sess.get_poll_flags();
+            if let Some(keepalive) = keepalive.as_mut() {
+                if let Err(err) = keepalive.tick(sess) {
+                    log::error!("{:#}", err);
+                    return Err(err);
+                }
+                if keepalive.awaiting_reply() {
+                    session_events |= POLLIN;
+                }
+                sleep_delay = sleep_delay.min(keepalive.time_until_due());
+            }
 
             let mut poll_array = vec![
                 pollfd {
@@ -322,7 +446,7 @@ impl SessionInner {
                 },
                 pollfd {
                     fd: sess.as_socket_descriptor(),
-                    events: sess.get_poll_flags(),
+                    events: session_events,
                     revents: 0,
                 },
             ];
@@ -350,6 +474,12 @@ impl SessionInner {
             poll(&mut poll_array, Some(sleep_delay)).context("poll")?;
             sleep_delay += sleep_delay;
 
+            if poll_array[1].revents & POLLIN != 0 {
+                if let Some(keepalive) = keepalive.as_mut() {
+                    keepalive.received();
+                }
+            }
+
             for (idx, poll) in poll_array.iter().enumerate() {
                 if poll.revents != 0 {
                     sleep_delay = Duration::from_millis(100);
@@ -481,6 +611,19 @@ impl SessionInner {
                     SessionRequest::Exec(exec, reply) => {
                         dispatch(reply, || self.exec(sess, exec), "exec")
                     }
+                    SessionRequest::DirectTcpIp(msg, reply) => {
+                        dispatch(reply, || self.direct_tcpip(sess, msg), "direct_tcpip")
+                    }
+                    SessionRequest::ForwardSocket(msg, reply) => {
+                        dispatch(reply, || self.forward_socket(sess, msg), "forward_socket")
+                    }
+                    SessionRequest::ListenForward(msg, reply) => {
+                        dispatch(reply, || self.listen_forward(sess, msg), "listen_forward")
+                    }
+                    SessionRequest::CancelForward(msg) => {
+                        self.cancel_forward(sess, msg);
+                        Ok(true)
+                    }
                     SessionRequest::SignalChannel(info) => {
                         if let Err(err) = self.signal_channel(&info) {
                             log::error!("{:?} -> error: {:#}", info, err);
@@ -704,6 +847,7 @@ impl SessionInner {
             }
         }
 
+        self.request_agent_forwarding(&mut channel);
         channel.request_exec(&exec.command_line)?;
 
         let channel_id = self.next_channel_id;
@@ -758,6 +902,60 @@ impl SessionInner {
         Ok(result)
     }
 
+    /// Opens a channel through which the remote host connects to
+    /// `msg.host:msg.port`, returning our end of a socket that carries
+    /// the data of the channel
+    pub fn direct_tcpip(
+        &mut self,
+        sess: &mut SessionWrap,
+        msg: DirectTcpIp,
+    ) -> anyhow::Result<FileDescriptor> {
+        let channel = sess
+            .open_direct_tcpip(&msg.host, msg.port)
+            .with_context(|| format!("opening channel to {}:{}", msg.host, msg.port))?;
+
+        let (ours, theirs) = socketpair()?;
+        self.add_socket_channel(channel, ours)?;
+        Ok(theirs)
+    }
+
+    /// Registers a channel whose data is carried by `sock`, which is
+    /// both the stdin and the stdout of the channel
+    pub(crate) fn add_socket_channel(
+        &mut self,
+        channel: ChannelWrap,
+        mut sock: FileDescriptor,
+    ) -> anyhow::Result<()> {
+        let channel_id = self.next_channel_id;
+        self.next_channel_id += 1;
+
+        sock.set_non_blocking(true)?;
+
+        let info = ChannelInfo {
+            channel_id,
+            channel,
+            exit: None,
+            descriptors: [
+                DescriptorState {
+                    fd: Some(sock.try_clone()?),
+                    buf: VecDeque::with_capacity(8192),
+                },
+                DescriptorState {
+                    fd: Some(sock),
+                    buf: VecDeque::with_capacity(8192),
+                },
+                DescriptorState {
+                    fd: None,
+                    buf: VecDeque::new(),
+                },
+            ],
+        };
+
+        self.channels.insert(channel_id, info);
+
+        Ok(())
+    }
+
     /// Open a handle to a file.
     pub fn open_with_mode(
         &mut self,
@@ -811,6 +1009,47 @@ impl SessionInner {
     }
 }
 
+/// Connects to each of the addresses of `hostname` in turn, giving up
+/// on each after `timeout`, as `ConnectTimeout` does for OpenSSH
+fn connect_with_timeout(hostname: &str, port: u16, timeout: Duration) -> anyhow::Result<TcpStream> {
+    let address = format!("{}:{}", hostname, port);
+    let mut last_err = None;
+    let mut timed_out = false;
+    for addr in (hostname, port)
+        .to_socket_addrs()
+        .with_context(|| format!("resolving {}", address))?
+    {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(socket) => return Ok(socket),
+            Err(err) if err.kind() == std::io::ErrorKind::TimedOut => timed_out = true,
+            Err(err) => last_err = Some(err),
+        }
+    }
+    if timed_out {
+        return Err(SessionError::ConnectTimeout { address, timeout }.into());
+    }
+    match last_err {
+        Some(err) => Err(err).with_context(|| format!("connecting to {}", address)),
+        None => anyhow::bail!("{} has no addresses", hostname),
+    }
+}
+
+/// Takes ownership of a connected socket, such as the tunnel through a
+/// jump host or one end of the socketpair connected to a ProxyCommand,
+/// so that it can be used as the transport for ssh2
+fn socket_into_tcp_stream(sock: FileDescriptor) -> TcpStream {
+    #[cfg(unix)]
+    unsafe {
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+        TcpStream::from_raw_fd(sock.into_raw_fd())
+    }
+    #[cfg(windows)]
+    unsafe {
+        use std::os::windows::io::{FromRawSocket, IntoRawSocket};
+        TcpStream::from_raw_socket(sock.into_raw_socket())
+    }
+}
+
 fn write_from_buf<W: Write>(w: &mut W, buf: &mut VecDeque<u8>) -> std::io::Result<()> {
     match w.write(buf.make_contiguous()) {
         Ok(len) => {
@@ -7,6 +7,9 @@ use ssh2::BlockDirections;
 pub(crate) struct Ssh2Session {
     pub sess: ssh2::Session,
     pub sftp: Option<SftpWrap>,
+    /// The remote port forward listeners, along with the port
+    /// that each of them is listening on
+    pub listeners: Vec<(u16, ssh2::Listener)>,
 }
 
 pub(crate) struct LibSshSession {
@@ -21,7 +24,11 @@ pub(crate) enum SessionWrap {
 
 impl SessionWrap {
     pub fn with_ssh2(sess: ssh2::Session) -> Self {
-        Self::Ssh2(Ssh2Session { sess, sftp: None })
+        Self::Ssh2(Ssh2Session {
+            sess,
+            sftp: None,
+            listeners: vec![],
+        })
     }
 
     pub fn with_libssh(sess: libssh::Session) -> Self {
@@ -75,4 +82,78 @@ impl SessionWrap {
             }
         }
     }
+
+    pub fn open_direct_tcpip(&self, host: &str, port: u16) -> anyhow::Result<ChannelWrap> {
+        match self {
+            Self::Ssh2(sess) => {
+                let channel = sess.sess.channel_direct_tcpip(host, port, None)?;
+                Ok(ChannelWrap::Ssh2(channel))
+            }
+            Self::LibSsh(sess) => {
+                let channel = sess.sess.new_channel()?;
+                channel.open_forward(host, port, "127.0.0.1", 0)?;
+                Ok(ChannelWrap::LibSsh(channel))
+            }
+        }
+    }
+
+    /// Asks the remote host to listen on `bind_host:bind_port`,
+    /// returning the port that it is listening on
+    pub fn listen_forward(&mut self, bind_host: &str, bind_port: u16) -> anyhow::Result<u16> {
+        match self {
+            Self::Ssh2(sess) => {
+                let (listener, port) =
+                    sess.sess
+                        .channel_forward_listen(bind_port, Some(bind_host), None)?;
+                sess.listeners.push((port, listener));
+                Ok(port)
+            }
+            // libssh-rs doesn't expose remote port forwarding
+            Self::LibSsh(_) => {
+                anyhow::bail!("remote port forwarding is not supported by the libssh backend")
+            }
+        }
+    }
+
+    /// Accepts a channel that the remote host has opened for a
+    /// connection to one of our remote port forwards, returning it
+    /// along with the port on which the connection was accepted
+    pub fn accept_forward(&mut self) -> Option<(u16, ChannelWrap)> {
+        match self {
+            Self::Ssh2(sess) => sess.listeners.iter_mut().find_map(|(port, listener)| {
+                listener
+                    .accept()
+                    .ok()
+                    .map(|channel| (*port, ChannelWrap::Ssh2(channel)))
+            }),
+            Self::LibSsh(_) => None,
+        }
+    }
+
+    /// Stops the remote host from listening on a port that was set
+    /// up by `listen_forward`, where the backend supports it
+    pub fn cancel_forward(&mut self, bind_port: u16) {
+        match self {
+            // Dropping the listener cancels the forward
+            Self::Ssh2(sess) => sess.listeners.retain(|(port, _)| *port != bind_port),
+            // There are no forwards to cancel, as listen_forward fails
+            Self::LibSsh(_) => {}
+        }
+    }
+
+    /// Sends a keepalive request to the remote host, which asks it to
+    /// reply so that we can tell that the connection is still alive
+    pub fn send_keepalive(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Ssh2(sess) => {
+                // libssh2 only sends the keepalive if the interval has
+                // elapsed since it last sent one; the caller keeps the
+                // real schedule, so use the shortest interval here
+                sess.sess.set_keepalive(true, 1);
+                sess.sess.keepalive_send()?;
+            }
+            Self::LibSsh(sess) => sess.sess.send_keepalive()?,
+        }
+        Ok(())
+    }
 }
@@ -92,7 +92,8 @@ async fn show_notif_impl(notif: ToastNotification) -> Result<(), Box<dyn std::er
     let proxy = NotificationsProxy::new(&connection).await?;
     let caps = proxy.get_capabilities().await?;
 
-    if notif.url.is_some() && !caps.iter().any(|cap| cap == "actions") {
+    let has_actions = caps.iter().any(|cap| cap == "actions");
+    if notif.url.is_some() && !has_actions {
         // Server doesn't support actions, so skip showing this notification
         // because it might have text that says "click to see more"
         // and that just wouldn't work.
@@ -108,7 +109,7 @@ async fn show_notif_impl(notif: ToastNotification) -> Result<(), Box<dyn std::er
             "org.wezfurlong.wezterm",
             &notif.title,
             &notif.message,
-            if notif.url.is_some() {
+            if notif.is_clickable() && has_actions {
                 &["show", "Show"]
             } else {
                 &[]
@@ -125,12 +126,10 @@ async fn show_notif_impl(notif: ToastNotification) -> Result<(), Box<dyn std::er
         async {
             while let Some(signal) = invoked_stream.next().await {
                 let args = signal.args()?;
-                if args.nid == notification {
-                    if let Some(url) = notif.url.as_ref() {
-                        let _ = open::that(url);
-                        abort_closed.abort();
-                        break;
-                    }
+                if args.nid == notification && notif.is_clickable() {
+                    notif.clicked();
+                    abort_closed.abort();
+                    break;
                 }
             }
             Ok::<(), zbus::Error>(())
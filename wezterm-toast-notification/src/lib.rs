@@ -2,18 +2,57 @@ mod dbus;
 mod macos;
 mod windows;
 
+use std::sync::Arc;
+
+/// A function that is called when a notification is clicked
+#[derive(Clone)]
+pub struct ClickHandler(Arc<dyn Fn() + Send + Sync>);
+
+impl ClickHandler {
+    pub fn new<F: Fn() + Send + Sync + 'static>(func: F) -> Self {
+        Self(Arc::new(func))
+    }
+
+    fn call(&self) {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for ClickHandler {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("ClickHandler")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ToastNotification {
     pub title: String,
     pub message: String,
     pub url: Option<String>,
     pub timeout: Option<std::time::Duration>,
+    /// Called when the notification is clicked, after opening `url`
+    pub on_click: Option<ClickHandler>,
 }
 
 impl ToastNotification {
     pub fn show(self) {
         show(self)
     }
+
+    /// Whether clicking the notification does anything
+    fn is_clickable(&self) -> bool {
+        self.url.is_some() || self.on_click.is_some()
+    }
+
+    /// Called when the notification is clicked
+    fn clicked(&self) {
+        if let Some(url) = self.url.as_ref() {
+            let _ = open::that(url);
+        }
+        if let Some(on_click) = self.on_click.as_ref() {
+            on_click.call();
+        }
+    }
 }
 
 #[cfg(windows)]
@@ -44,6 +83,7 @@ pub fn persistent_toast_notification_with_click_to_open_url(title: &str, message
         message: message.to_string(),
         url: Some(url.to_string()),
         timeout: None,
+        on_click: None,
     });
 }
 
@@ -53,5 +93,6 @@ pub fn persistent_toast_notification(title: &str, message: &str) {
         message: message.to_string(),
         url: None,
         timeout: None,
+        on_click: None,
     });
 }
@@ -1,6 +1,6 @@
 #![cfg(target_os = "macos")]
 
-use crate::ToastNotification;
+use crate::{ClickHandler, ToastNotification};
 use cocoa::base::*;
 use cocoa::foundation::{NSDictionary, NSString};
 use core_foundation::dictionary::CFMutableDictionary;
@@ -9,9 +9,39 @@ use objc::declare::ClassDecl;
 use objc::rc::StrongPtr;
 use objc::runtime::{Class, Object, Protocol, Sel};
 use objc::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 const DELEGATE_CLS_NAME: &str = "WezTermNotifDelegate";
 
+lazy_static::lazy_static! {
+    /// The click handlers of the notifications that are being shown,
+    /// which are referenced by the "click" key of their userInfo
+    static ref CLICK_HANDLERS: Mutex<HashMap<String, ClickHandler>> = Mutex::new(HashMap::new());
+}
+static NEXT_CLICK_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the value of `key` in the userInfo of `notif`
+unsafe fn user_info_value(notif: id, key: &str) -> Option<String> {
+    let info: *mut Object = msg_send![notif, userInfo];
+    if info.is_null() {
+        return None;
+    }
+    let value = info.valueForKey_(*nsstring(key));
+    if value.is_null() {
+        return None;
+    }
+    let value = std::slice::from_raw_parts(value.UTF8String() as *const u8, value.len());
+    Some(String::from_utf8_lossy(value).into_owned())
+}
+
+/// Removes and returns the click handler of `notif`
+unsafe fn take_click_handler(notif: id) -> Option<ClickHandler> {
+    let click_id = user_info_value(notif, "click")?;
+    CLICK_HANDLERS.lock().unwrap().remove(&click_id)
+}
+
 struct NotifDelegate {}
 
 impl NotifDelegate {
@@ -50,6 +80,7 @@ impl NotifDelegate {
 
     extern "C" fn did_dismiss_alert(_: &mut Object, _sel: Sel, center: id, notif: id) {
         unsafe {
+            take_click_handler(notif);
             let () = msg_send![center, removeDeliveredNotification: notif];
         }
     }
@@ -58,14 +89,12 @@ impl NotifDelegate {
 
     extern "C" fn did_activate_notif(_: &mut Object, _sel: Sel, center: id, notif: id) {
         unsafe {
-            let info: *mut Object = msg_send![notif, userInfo];
-
             // If the notification had an associated URL, open it!
-            let url = info.valueForKey_(*nsstring("url"));
-            if !url.is_null() {
-                let url = std::slice::from_raw_parts(url.UTF8String() as *const u8, url.len());
-                let url = String::from_utf8_lossy(url);
-                let _ = open::that(&*url);
+            if let Some(url) = user_info_value(notif, "url") {
+                let _ = open::that(&url);
+            }
+            if let Some(on_click) = take_click_handler(notif) {
+                on_click.call();
             }
             let () = msg_send![center, removeDeliveredNotification: notif];
         }
@@ -115,8 +144,22 @@ pub fn show_notif(toast: ToastNotification) -> Result<(), Box<dyn std::error::Er
         let () = msg_send![*notif, setInformativeText: nsstring(&toast.message)];
 
         let mut info = CFMutableDictionary::new();
-        if let Some(url) = toast.url {
-            info.set(CFString::from_static_string("url"), CFString::new(&url));
+        if let Some(url) = &toast.url {
+            info.set(CFString::from_static_string("url"), CFString::new(url));
+        }
+        let click_id = toast.on_click.map(|on_click| {
+            let click_id = NEXT_CLICK_ID.fetch_add(1, Ordering::Relaxed).to_string();
+            CLICK_HANDLERS
+                .lock()
+                .unwrap()
+                .insert(click_id.clone(), on_click);
+            info.set(
+                CFString::from_static_string("click"),
+                CFString::new(&click_id),
+            );
+            click_id
+        });
+        if toast.url.is_some() || click_id.is_some() {
             let () = msg_send![*notif, setUserInfo: info];
         }
 
@@ -134,6 +177,9 @@ pub fn show_notif(toast: ToastNotification) -> Result<(), Box<dyn std::error::Er
             // from the window crate here, so we just do this basic take.
             std::thread::spawn(move || {
                 std::thread::sleep(timeout);
+                if let Some(click_id) = click_id {
+                    CLICK_HANDLERS.lock().unwrap().remove(&click_id);
+                }
                 let () = msg_send![center.0, removeDeliveredNotification: *notif.0];
             });
         }
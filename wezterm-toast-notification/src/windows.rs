@@ -26,7 +26,7 @@ fn unwrap_arg<T>(a: &Option<T>) -> Result<&T, WinError> {
 fn show_notif_impl(toast: TN) -> Result<(), Box<dyn std::error::Error>> {
     let xml = XmlDocument::new()?;
 
-    let url_actions = if toast.url.is_some() {
+    let url_actions = if toast.is_clickable() {
         r#"
         <actions>
            <action content="Show" arguments="show" />
@@ -61,9 +61,7 @@ fn show_notif_impl(toast: TN) -> Result<(), Box<dyn std::error::Error>> {
             let args = result.Arguments()?;
 
             if args == "show" {
-                if let Some(url) = toast.url.as_ref() {
-                    let _ = open::that(url);
-                }
+                toast.clicked();
             }
 
             Ok(())
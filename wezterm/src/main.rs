@@ -1,20 +1,23 @@
 use anyhow::{anyhow, Context};
-use config::keyassignment::SpawnTabDomain;
+use config::keyassignment::{PaneDirection, SpawnTabDomain};
 use config::wezterm_version;
 use mux::activity::Activity;
 use mux::pane::PaneId;
-use mux::tab::SplitDirection;
+use mux::sshforward::{SshForwardKind, SshForwardState};
+use mux::tab::{SplitDirection, TabId};
 use mux::window::WindowId;
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
 use std::ffi::OsString;
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
 use structopt::StructOpt;
 use tabout::{tabulate_output, Alignment, Column};
 use umask::UmaskSaver;
 use wezterm_client::client::{unix_connect_with_retry, Client};
 use wezterm_gui_subcommands::*;
+use wezterm_term::ScrollbackStats;
 
 //    let message = "; ❤ 😍🤢\n\x1b[91;mw00t\n\x1b[37;104;m bleet\x1b[0;m.";
 
@@ -67,6 +70,9 @@ enum SubCommand {
     #[structopt(name = "ls-fonts", about = "Display information about fonts")]
     LsFonts(LsFontsCommand),
 
+    #[structopt(name = "show-keys", about = "Show key assignments")]
+    ShowKeys(ShowKeysCommand),
+
     #[structopt(name = "cli", about = "Interact with experimental mux server")]
     Cli(CliCommand),
 
@@ -94,7 +100,12 @@ struct CliCommand {
 #[derive(Debug, StructOpt, Clone)]
 enum CliSubCommand {
     #[structopt(name = "list", about = "list windows, tabs and panes")]
-    List,
+    List {
+        /// How to format the panes; `json` emits an object holding
+        /// the panes and the total scrollback usage across them
+        #[structopt(long = "format", default_value = "text", possible_values = &["text", "json"])]
+        format: String,
+    },
 
     #[structopt(name = "proxy", about = "start rpc proxy pipe")]
     Proxy,
@@ -168,6 +179,225 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(parse(from_os_str))]
         prog: Vec<OsString>,
     },
+
+    #[structopt(
+        name = "move-pane-to-new-tab",
+        about = "Move a pane into a new tab.
+Outputs the tab-id for the newly created tab on success"
+    )]
+    MovePaneToNewTab {
+        /// Specify the pane that should be moved.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Specify the window into which to move the pane.
+        /// If omitted, the window that contains the pane is used.
+        #[structopt(long = "window-id")]
+        window_id: Option<WindowId>,
+
+        /// Move the pane into a new window, rather than a new tab
+        #[structopt(long = "new-window", conflicts_with = "window_id")]
+        new_window: bool,
+    },
+
+    #[structopt(
+        name = "move-pane",
+        about = "Move a pane into an existing tab, alongside the active pane of that tab"
+    )]
+    MovePane {
+        /// Specify the pane that should be moved.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Specify the tab into which to move the pane
+        #[structopt(long = "tab-id")]
+        tab_id: TabId,
+
+        /// Which side of the active pane of the tab to place the pane
+        #[structopt(
+            long = "direction",
+            default_value = "Right",
+            possible_values = PANE_DIRECTIONS,
+            parse(try_from_str = parse_pane_direction)
+        )]
+        direction: PaneDirection,
+    },
+
+    #[structopt(
+        name = "activate-pane",
+        about = "Activate a pane, along with its tab.
+Outputs the pane-id of the activated pane on success"
+    )]
+    ActivatePane {
+        /// Specify the pane that should be activated.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+    },
+
+    #[structopt(
+        name = "activate-pane-direction",
+        about = "Activate the pane that is adjacent to the current pane.
+Outputs the pane-id of the activated pane on success, and fails
+if there is no pane in that direction"
+    )]
+    ActivatePaneDirection {
+        /// Specify the current pane.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The direction in which to move
+        #[structopt(
+            possible_values = PANE_DIRECTIONS,
+            parse(try_from_str = parse_pane_direction)
+        )]
+        direction: PaneDirection,
+    },
+
+    #[structopt(
+        name = "activate-tab",
+        about = "Activate a tab, along with its active pane.
+Outputs the pane-id of the activated pane on success"
+    )]
+    ActivateTab {
+        /// Specify the current pane.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        /// --tab-relative is relative to the tab of this pane.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Specify the tab that should be activated
+        #[structopt(long = "tab-id", required_unless = "tab_relative")]
+        tab_id: Option<TabId>,
+
+        /// Activate the tab that is this many tabs away from the
+        /// current tab, wrapping around at the ends of the window.
+        /// For example, 1 is the next tab and -1 the previous tab.
+        #[structopt(
+            long = "tab-relative",
+            allow_hyphen_values = true,
+            conflicts_with = "tab_id"
+        )]
+        tab_relative: Option<isize>,
+    },
+
+    #[structopt(
+        name = "get-text",
+        about = "Print the text of a pane.
+Line 0 is the top line of the screen, and negative line numbers
+address the scrollback"
+    )]
+    GetText {
+        /// Specify the pane whose text should be printed.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The first line to print.
+        /// The default is the top of the screen.
+        #[structopt(long = "start-line", allow_hyphen_values = true)]
+        start_line: Option<isize>,
+
+        /// The last line to print.
+        /// The default is the bottom of the screen.
+        #[structopt(long = "end-line", allow_hyphen_values = true)]
+        end_line: Option<isize>,
+
+        /// Include the colors and styles of the text as escape sequences,
+        /// rather than printing plain text
+        #[structopt(long = "escapes")]
+        escapes: bool,
+    },
+
+    #[structopt(
+        name = "save-session",
+        about = "Save the windows, tabs and panes so that they can be
+recreated by restore-session"
+    )]
+    SaveSession {
+        /// The file in which to save the session.
+        /// The default is session.json in the wezterm data directory.
+        #[structopt(long = "file", parse(from_os_str))]
+        file: Option<PathBuf>,
+
+        /// Also record the command that is running in the foreground
+        /// of each pane, so that it is run again when the session
+        /// is restored
+        #[structopt(long = "include-commands")]
+        include_commands: bool,
+    },
+
+    #[structopt(
+        name = "restore-session",
+        about = "Recreate the windows, tabs and panes that were saved by save-session"
+    )]
+    RestoreSession {
+        /// The file from which to restore the session.
+        /// The default is session.json in the wezterm data directory.
+        #[structopt(long = "file", parse(from_os_str))]
+        file: Option<PathBuf>,
+    },
+
+    #[structopt(
+        name = "dump-logs",
+        about = "Print the log entries retained by the running wezterm instance"
+    )]
+    DumpLogs {
+        /// How to format the entries; `json` emits an array of objects
+        #[structopt(long = "format", default_value = "text", possible_values = &["text", "json"])]
+        format: String,
+    },
+
+    #[structopt(
+        name = "server-log",
+        about = "Print the log entries retained by the mux server"
+    )]
+    ServerLog {
+        /// Keep running and print new entries as they are logged
+        #[structopt(long = "follow", short = "f")]
+        follow: bool,
+    },
+
+    #[structopt(
+        name = "list-ssh-forwards",
+        about = "list the port forwards of the connected ssh domains"
+    )]
+    ListSshForwards {
+        /// How to format the forwards; `json` emits an array of objects
+        #[structopt(long = "format", default_value = "text", possible_values = &["text", "json"])]
+        format: String,
+    },
+
+    #[structopt(
+        name = "list-tls-certificates",
+        about = "list the certificates of the TLS listeners of the mux server, \
+and when they expire"
+    )]
+    ListTlsCertificates {
+        /// How to format the certificates; `json` emits an array of objects
+        #[structopt(long = "format", default_value = "text", possible_values = &["text", "json"])]
+        format: String,
+    },
+
+    #[structopt(
+        name = "list-clients",
+        about = "list the clients that are connected to the mux server, \
+and how much compression saves on their connections"
+    )]
+    ListClients {
+        /// How to format the clients; `json` emits an array of objects
+        #[structopt(long = "format", default_value = "text", possible_values = &["text", "json"])]
+        format: String,
+    },
 }
 
 use termwiz::escape::osc::{
@@ -313,6 +543,7 @@ fn run() -> anyhow::Result<()> {
     {
         SubCommand::Start(_)
         | SubCommand::LsFonts(_)
+        | SubCommand::ShowKeys(_)
         | SubCommand::Ssh(_)
         | SubCommand::Serial(_)
         | SubCommand::Connect(_) => delegate_to_gui(saver),
@@ -368,12 +599,76 @@ fn delegate_to_gui(saver: UmaskSaver) -> anyhow::Result<()> {
     }
 }
 
+/// Returns the window that contains the pane
+async fn window_id_for_pane(client: &Client, pane_id: PaneId) -> anyhow::Result<Option<WindowId>> {
+    let panes = client.list_panes().await?;
+    for tabroot in panes.tabs {
+        let mut cursor = tabroot.into_tree().cursor();
+
+        loop {
+            if let Some(entry) = cursor.leaf_mut() {
+                if entry.pane_id == pane_id {
+                    return Ok(Some(entry.window_id));
+                }
+            }
+            match cursor.preorder_next() {
+                Ok(c) => cursor = c,
+                Err(_) => break,
+            }
+        }
+    }
+    Ok(None)
+}
+
+const PANE_DIRECTIONS: &[&str] = &["Up", "Down", "Left", "Right"];
+
+/// Helper for parsing the direction arguments of the pane subcommands
+fn parse_pane_direction(arg: &str) -> Result<PaneDirection, String> {
+    match arg {
+        "Up" => Ok(PaneDirection::Up),
+        "Down" => Ok(PaneDirection::Down),
+        "Left" => Ok(PaneDirection::Left),
+        "Right" => Ok(PaneDirection::Right),
+        _ => Err(format!(
+            "Expected one of {}, but got {}",
+            PANE_DIRECTIONS.join(", "),
+            arg
+        )),
+    }
+}
+
+fn pane_id_from_env(pane_id: Option<PaneId>) -> anyhow::Result<PaneId> {
+    match pane_id {
+        Some(p) => Ok(p),
+        None => Ok(std::env::var("WEZTERM_PANE")
+            .map_err(|_| {
+                anyhow!(
+                    "--pane-id was not specified and $WEZTERM_PANE
+                    is not set in the environment"
+                )
+            })?
+            .parse()?),
+    }
+}
+
 async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow::Result<()> {
     let initial = true;
     let mut ui = mux::connui::ConnectionUI::new_headless();
-    let client = Client::new_default_unix_domain(initial, &mut ui, cli.no_auto_start)?;
+    // There are no logs to dump, nor forwards to list,
+    // from a server that we just started
+    let no_auto_start = cli.no_auto_start
+        || matches!(
+            cli.sub,
+            CliSubCommand::DumpLogs { .. }
+                | CliSubCommand::ServerLog { .. }
+                | CliSubCommand::ListSshForwards { .. }
+                | CliSubCommand::ListTlsCertificates { .. }
+                | CliSubCommand::ListClients { .. }
+        );
+    let client = Client::new_default_unix_domain(initial, &mut ui, no_auto_start)
+        .context("unable to connect to the running wezterm instance")?;
     match cli.sub {
-        CliSubCommand::List => {
+        CliSubCommand::List { format } => {
             let cols = vec![
                 Column {
                     name: "WINID".to_string(),
@@ -401,6 +696,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                 },
             ];
             let mut data = vec![];
+            let mut entries = vec![];
             let panes = client.list_panes().await?;
 
             for tabroot in panes.tabs {
@@ -408,6 +704,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
 
                 loop {
                     if let Some(entry) = cursor.leaf_mut() {
+                        entries.push(entry.clone());
                         data.push(vec![
                             entry.window_id.to_string(),
                             entry.tab_id.to_string(),
@@ -429,7 +726,22 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                 }
             }
 
-            tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+            if format == "json" {
+                let mut total = ScrollbackStats::default();
+                for stats in entries.iter().filter_map(|entry| entry.scrollback) {
+                    total.lines += stats.lines;
+                    total.estimated_bytes += stats.estimated_bytes;
+                    total.evicted_lines += stats.evicted_lines;
+                    total.evicted_bytes += stats.evicted_bytes;
+                }
+                let json = serde_json::json!({
+                    "panes": entries,
+                    "scrollback": total,
+                });
+                println!("{}", serde_json::to_string_pretty(&json)?);
+            } else {
+                tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+            }
         }
         CliSubCommand::SplitPane {
             pane_id,
@@ -497,25 +809,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                                 .parse()?,
                         };
 
-                        let panes = client.list_panes().await?;
-                        let mut window_id = None;
-                        'outer: for tabroot in panes.tabs {
-                            let mut cursor = tabroot.into_tree().cursor();
-
-                            loop {
-                                if let Some(entry) = cursor.leaf_mut() {
-                                    if entry.pane_id == pane_id {
-                                        window_id.replace(entry.window_id);
-                                        break 'outer;
-                                    }
-                                }
-                                match cursor.preorder_next() {
-                                    Ok(c) => cursor = c,
-                                    Err(_) => break,
-                                }
-                            }
-                        }
-                        window_id
+                        window_id_for_pane(&client, pane_id).await?
                     }
                 }
             };
@@ -546,6 +840,9 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             // ourselves into basically netcat.
             drop(client);
 
+            #[cfg(unix)]
+            update_forwarded_agent_link();
+
             let mux = Rc::new(mux::Mux::new(None));
             Mux::set_mux(&mux);
             let unix_dom = config.unix_domains.first().unwrap();
@@ -575,7 +872,327 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             let creds = client.get_tls_creds().await?;
             codec::Pdu::GetTlsCredsResponse(creds).encode(std::io::stdout().lock(), 0)?;
         }
+        CliSubCommand::MovePaneToNewTab {
+            pane_id,
+            window_id,
+            new_window,
+        } => {
+            let pane_id = pane_id_from_env(pane_id)?;
+            let window_id = if new_window {
+                None
+            } else {
+                match window_id {
+                    Some(w) => Some(w),
+                    None => window_id_for_pane(&client, pane_id).await?,
+                }
+            };
+
+            let moved = client
+                .move_pane_to_new_tab(codec::MovePaneToNewTab { pane_id, window_id })
+                .await?;
+
+            log::debug!("{:?}", moved);
+            println!("{}", moved.tab_id);
+        }
+        CliSubCommand::MovePane {
+            pane_id,
+            tab_id,
+            direction,
+        } => {
+            let pane_id = pane_id_from_env(pane_id)?;
+            client
+                .move_pane(codec::MovePane {
+                    pane_id,
+                    tab_id,
+                    direction,
+                })
+                .await?;
+        }
+        CliSubCommand::ActivatePane { pane_id } => {
+            let pane_id = pane_id_from_env(pane_id)?;
+            let activated = client
+                .activate_pane(codec::ActivatePane { pane_id })
+                .await?;
+            println!("{}", activated.pane_id);
+        }
+        CliSubCommand::ActivatePaneDirection { pane_id, direction } => {
+            let pane_id = pane_id_from_env(pane_id)?;
+            let activated = client
+                .activate_pane_direction(codec::ActivatePaneDirection { pane_id, direction })
+                .await?;
+            println!("{}", activated.pane_id);
+        }
+        CliSubCommand::ActivateTab {
+            pane_id,
+            tab_id,
+            tab_relative,
+        } => {
+            let pane_id = pane_id_from_env(pane_id)?;
+            let activated = client
+                .activate_tab(codec::ActivateTab {
+                    pane_id,
+                    tab_id,
+                    relative: tab_relative,
+                })
+                .await?;
+            println!("{}", activated.pane_id);
+        }
+        CliSubCommand::GetText {
+            pane_id,
+            start_line,
+            end_line,
+            escapes,
+        } => {
+            let pane_id = pane_id_from_env(pane_id)?;
+            let response = client
+                .get_pane_text(codec::GetPaneText {
+                    pane_id,
+                    start_line,
+                    end_line,
+                    escapes,
+                })
+                .await?;
+            let mut out = std::io::stdout();
+            out.write_all(response.text.as_bytes())?;
+            out.flush()?;
+        }
+        CliSubCommand::SaveSession {
+            file,
+            include_commands,
+        } => {
+            let file = file.unwrap_or_else(mux::session::default_session_file);
+            let saved = client
+                .save_session(codec::SaveSession { include_commands })
+                .await?;
+            mux::session::write_session_file(&file, &saved.session)?;
+        }
+        CliSubCommand::RestoreSession { file } => {
+            let file = file.unwrap_or_else(mux::session::default_session_file);
+            let session = mux::session::read_session_file(&file)?;
+            client
+                .restore_session(codec::RestoreSession { session })
+                .await?;
+        }
+        CliSubCommand::DumpLogs { format } => {
+            let logs = client
+                .get_log_entries(codec::GetLogEntries { since_seq: 0 })
+                .await?;
+            let mut out = std::io::stdout();
+            if format == "json" {
+                writeln!(
+                    out,
+                    "{}",
+                    env_bootstrap::ringlog::entries_to_json(&logs.entries)
+                )?;
+            } else {
+                print_log_entries(&logs.entries, &mut out)?;
+            }
+        }
+        CliSubCommand::ServerLog { follow } => {
+            let mut since_seq = 0;
+            loop {
+                let logs = client
+                    .get_log_entries(codec::GetLogEntries { since_seq })
+                    .await?;
+                print_log_entries(&logs.entries, &mut std::io::stdout())?;
+                if !follow {
+                    break;
+                }
+                since_seq = logs.latest_seq;
+                smol::Timer::after(std::time::Duration::from_millis(500)).await;
+            }
+        }
+        CliSubCommand::ListSshForwards { format } => {
+            let forwards = client.list_ssh_forwards().await?.forwards;
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&forwards)?);
+            } else {
+                let cols = vec![
+                    Column {
+                        name: "DOMAIN".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "KIND".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "BIND".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "TARGET".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "CONNECTIONS".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "STATUS".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                ];
+                let data: Vec<Vec<String>> = forwards
+                    .into_iter()
+                    .map(|forward| {
+                        vec![
+                            forward.domain,
+                            match forward.kind {
+                                SshForwardKind::Local => "local",
+                                SshForwardKind::Remote => "remote",
+                            }
+                            .to_string(),
+                            forward.bind,
+                            forward.target,
+                            forward.connections.to_string(),
+                            match forward.state {
+                                SshForwardState::Listening => "listening".to_string(),
+                                SshForwardState::Failed(reason) => format!("failed: {}", reason),
+                            },
+                        ]
+                    })
+                    .collect();
+                tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+            }
+        }
+        CliSubCommand::ListTlsCertificates { format } => {
+            let certificates = client.list_tls_certificates().await?.certificates;
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&certificates)?);
+            } else {
+                let cols = vec![
+                    Column {
+                        name: "BIND".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "SUBJECT".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "EXPIRES".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "DAYS".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "STATUS".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "CERTIFICATE".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                ];
+                let data: Vec<Vec<String>> = certificates
+                    .into_iter()
+                    .map(|cert| {
+                        let status = if cert.days_remaining < 0 {
+                            "expired"
+                        } else if cert.expiring {
+                            "expiring"
+                        } else {
+                            "ok"
+                        };
+                        vec![
+                            cert.bind_address,
+                            cert.subject,
+                            cert.not_after,
+                            cert.days_remaining.to_string(),
+                            match cert.reload_error {
+                                Some(err) => format!("{}; reload failed: {}", status, err),
+                                None => status.to_string(),
+                            },
+                            cert.cert_path,
+                        ]
+                    })
+                    .collect();
+                tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+            }
+        }
+        CliSubCommand::ListClients { format } => {
+            let clients = client.list_clients().await?.clients;
+            if format == "json" {
+                println!("{}", serde_json::to_string_pretty(&clients)?);
+            } else {
+                let cols = vec![
+                    Column {
+                        name: "USER".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "HOST".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "PID".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "CONNECTED".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "IDLE".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "IN".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "OUT".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "COMPRESSION".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                ];
+                let data: Vec<Vec<String>> = clients
+                    .into_iter()
+                    .map(|info| {
+                        vec![
+                            info.username,
+                            info.hostname,
+                            info.pid.to_string(),
+                            format!("{}s", info.connected_secs),
+                            format!("{}s", info.idle_secs),
+                            info.stats.bytes_in.to_string(),
+                            info.stats.bytes_out.to_string(),
+                            if info.compression {
+                                format!("on, {:.0}% of the size", info.compression_ratio * 100.)
+                            } else {
+                                "off".to_string()
+                            },
+                        ]
+                    })
+                    .collect();
+                tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_log_entries(
+    entries: &[env_bootstrap::ringlog::Entry],
+    out: &mut dyn Write,
+) -> anyhow::Result<()> {
+    for entry in entries {
+        writeln!(
+            out,
+            "{} {:<5} {} > {}",
+            entry.format_timestamp("%Y-%m-%dT%H:%M:%S%.3f"),
+            entry.level,
+            entry.target,
+            entry.msg
+        )?;
     }
+    out.flush()?;
     Ok(())
 }
 
@@ -595,6 +1212,31 @@ fn run_cli(config: config::ConfigHandle, cli: CliCommand) -> anyhow::Result<()>
     }
 }
 
+/// If the ssh session that runs the proxy has a forwarded agent, points
+/// the link through which the panes of the mux server reach an agent at
+/// it, so that panes spawned via this connection can use the agent
+#[cfg(unix)]
+fn update_forwarded_agent_link() {
+    let sock = match std::env::var_os("SSH_AUTH_SOCK") {
+        Some(sock) => PathBuf::from(sock),
+        None => return,
+    };
+    let link = config::forwarded_agent_path();
+    if sock == link {
+        // We're running in a pane of the mux server
+        return;
+    }
+    std::fs::remove_file(&link).ok();
+    if let Err(err) = std::os::unix::fs::symlink(&sock, &link) {
+        log::error!(
+            "failed to link {} to the forwarded agent {}: {:#}",
+            link.display(),
+            sock.display(),
+            err
+        );
+    }
+}
+
 fn consume_stream<F: Read, T: Write>(mut from_stream: F, mut to_stream: T) -> anyhow::Result<()> {
     let mut buf = [0u8; 8192];
 
@@ -42,6 +42,19 @@ fn main() {
         println!("cargo:rustc-link-lib=framework=Carbon");
     }
 
+    #[cfg(feature = "wayland")]
+    {
+        // The version of wayland-protocols that we use predates
+        // fractional-scale-v1, so we generate its bindings ourselves
+        let protocol = "protocols/fractional-scale-v1.xml";
+        println!("cargo:rerun-if-changed={}", protocol);
+        wayland_scanner::generate_code(
+            protocol,
+            dest.join("fractional_scale_v1.rs"),
+            wayland_scanner::Side::Client,
+        );
+    }
+
     if target.contains("windows") {
         let mut file = File::create(&dest.join("wgl_bindings.rs")).unwrap();
         let reg = Registry::new(Api::Wgl, (1, 0), Profile::Core, Fallbacks::All, []);
@@ -71,7 +71,10 @@ impl MyWindow {
                 }
             }
             WindowEvent::AppearanceChanged(_)
+            | WindowEvent::ScreenChanged(_)
             | WindowEvent::Notification(_)
+            | WindowEvent::ImeComposition(_)
+            | WindowEvent::KeyboardLayoutChanged(_)
             | WindowEvent::FocusChanged(_) => {}
         }
     }
@@ -465,6 +465,20 @@ impl GlState {
                         errors.push(format!("{}: {}", path.display(), e));
                     }
                 }
+                if let Some(err) = errors.last() {
+                    // Record each failed attempt so that problems with
+                    // the graphics drivers can be diagnosed from the
+                    // debug overlay even when a later attempt succeeds
+                    log::warn!(
+                        "EGL attempt using {} rendering failed: {}",
+                        if prefer_swrast {
+                            "software"
+                        } else {
+                            "hardware"
+                        },
+                        err
+                    );
+                }
             }
             // Since we didn't yet succeed, try enabling software rasterization.
             // However, don't do this on Windows; the EGL implementation on
@@ -478,6 +492,7 @@ impl GlState {
             if prefer_swrast {
                 break;
             }
+            log::warn!("Falling back to software rendering via LIBGL_ALWAYS_SOFTWARE");
             prefer_swrast = true;
         }
         bail!("with_egl_lib failed: {}", errors.join(", "))
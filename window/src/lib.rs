@@ -114,6 +114,16 @@ impl WindowState {
     }
 }
 
+/// The state of the text being composed by an input method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImeCompositionStatus {
+    /// There is no composition in progress
+    None,
+    /// Composition is in progress; this is the preedit text
+    /// that has not yet been committed
+    Composing(String),
+}
+
 #[derive(Debug)]
 pub enum WindowEvent {
     /// Called when the window close button is clicked.
@@ -150,7 +160,22 @@ pub enum WindowEvent {
 
     AppearanceChanged(Appearance),
 
+    /// Called when the window has moved to a different screen.
+    /// The parameter is a name identifying that screen, as
+    /// reported by the windowing system.
+    ScreenChanged(String),
+
     Notification(Box<dyn Any + Send + Sync>),
+
+    /// Called when the input method starts, updates or finishes
+    /// composing text.  The committed text is delivered separately
+    /// as a KeyEvent with KeyCode::Composed.
+    ImeComposition(ImeCompositionStatus),
+
+    /// Called when the active keyboard layout changes.
+    /// The parameter is the name of the new layout, as reported
+    /// by the windowing system.
+    KeyboardLayoutChanged(String),
 }
 
 pub struct WindowEventSender {
@@ -250,6 +275,14 @@ pub trait WindowOps {
     /// and/or in the task manager/task switcher
     fn set_icon(&self, _image: Image) {}
 
+    /// Ask the desktop environment to draw the attention of the user
+    /// to the window, eg: by setting its urgency hint or flashing its
+    /// taskbar entry.  The request is withdrawn when the window is
+    /// next focused.
+    /// This may not be supported or respected by the desktop
+    /// environment.
+    fn request_attention(&self) {}
+
     fn toggle_fullscreen(&self) {}
 
     fn config_did_change(&self, _config: &config::ConfigHandle) {}
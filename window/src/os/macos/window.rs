@@ -4,9 +4,9 @@
 use super::{nsstring, nsstring_to_str};
 use crate::connection::ConnectionOps;
 use crate::{
-    Clipboard, Connection, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor,
-    MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint, Size, WindowDecorations,
-    WindowEvent, WindowEventSender, WindowOps, WindowState,
+    Clipboard, Connection, Dimensions, ImeCompositionStatus, KeyCode, KeyEvent, Modifiers,
+    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint,
+    Size, WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
 };
 use anyhow::{anyhow, bail, ensure};
 use async_trait::async_trait;
@@ -409,6 +409,7 @@ impl Window {
                 last_wheel: Instant::now(),
                 key_is_down: None,
                 dead_pending: None,
+                keyboard_layout: None,
                 fullscreen: None,
                 config: config.clone(),
             }));
@@ -645,6 +646,13 @@ impl WindowOps for Window {
         });
     }
 
+    fn request_attention(&self) {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.request_attention();
+            Ok(())
+        });
+    }
+
     fn set_resize_increments(&self, x: u16, y: u16) {
         Connection::with_window_inner(self.id, move |inner| {
             inner.set_resize_increments(x, y);
@@ -941,6 +949,16 @@ impl WindowInner {
         }
     }
 
+    fn request_attention(&self) {
+        // NSInformationalRequest bounces the dock icon once; the
+        // request is cancelled automatically when the app is activated
+        const NS_INFORMATIONAL_REQUEST: NSUInteger = 10;
+        unsafe {
+            let app = appkit::NSApp();
+            let _: NSInteger = msg_send![app, requestUserAttention: NS_INFORMATIONAL_REQUEST];
+        }
+    }
+
     fn set_resize_increments(&self, x: u16, y: u16) {
         unsafe {
             self.window
@@ -1017,6 +1035,26 @@ fn decoration_to_mask(decorations: WindowDecorations) -> NSWindowStyleMask {
     }
 }
 
+/// Returns the localized name of the screen that the window is on.
+/// That name is only available on macOS 10.15 and later.
+fn screen_name(window: id) -> Option<String> {
+    unsafe {
+        let screen: id = msg_send![window, screen];
+        if screen.is_null() {
+            return None;
+        }
+        let responds: BOOL = msg_send![screen, respondsToSelector: sel!(localizedName)];
+        if responds == NO {
+            return None;
+        }
+        let name: id = msg_send![screen, localizedName];
+        if name.is_null() {
+            return None;
+        }
+        Some(nsstring_to_str(name).to_string())
+    }
+}
+
 struct Inner {
     events: WindowEventSender,
     view_id: Option<WeakPtr>,
@@ -1034,6 +1072,10 @@ struct Inner {
     /// First in a dead-key sequence
     dead_pending: Option<(u16, u32)>,
 
+    /// The identifier of the input source that was active for
+    /// the most recent key event
+    keyboard_layout: Option<String>,
+
     /// When using simple fullscreen mode, this tracks
     /// the window dimensions that need to be restored
     fullscreen: Option<NSRect>,
@@ -1076,6 +1118,7 @@ extern "C" {
     fn TISGetInputSourceProperty(source: InputSourceRef, propertyKey: CFStringRef) -> CFDataRef;
 
     static kTISPropertyUnicodeKeyLayoutData: CFStringRef;
+    static kTISPropertyInputSourceID: CFStringRef;
 
     fn UCKeyTranslate(
         layout: *const UCKeyboardLayout,
@@ -1093,6 +1136,18 @@ extern "C" {
     fn LMGetKbdType() -> u8;
 }
 
+/// Returns the identifier of the input source,
+/// eg: "com.apple.keylayout.French"
+fn input_source_id(source: &InputSource) -> String {
+    unsafe {
+        let id = TISGetInputSourceProperty(source.as_concrete_TypeRef(), kTISPropertyInputSourceID);
+        if id.is_null() {
+            return String::new();
+        }
+        CFString::wrap_under_get_rule(id as CFStringRef).to_string()
+    }
+}
+
 impl Inner {
     fn enable_opengl(&mut self) -> anyhow::Result<Rc<glium::backend::Context>> {
         let view = self.view_id.as_ref().unwrap().load();
@@ -1137,9 +1192,19 @@ impl Inner {
 
         let mods = key_modifiers(modifier_flags);
 
+        // The layout is checked on every key press, as the user may
+        // have switched input sources since the last one
+        let layout = input_source_id(&kbd);
+        if self.keyboard_layout.as_ref() != Some(&layout) {
+            if self.keyboard_layout.replace(layout.clone()).is_some() {
+                self.events
+                    .dispatch(WindowEvent::KeyboardLayoutChanged(layout.clone()));
+            }
+        }
+
         let config = &self.config;
 
-        let use_dead_keys = if !config.use_dead_keys {
+        let use_dead_keys = if !config.use_dead_keys.enabled_for_layout(&layout) {
             false
         } else if mods.contains(Modifiers::LEFT_ALT) {
             config.send_composed_key_when_left_alt_is_pressed
@@ -1221,6 +1286,17 @@ impl Inner {
 const VIEW_CLS_NAME: &str = "WezTermWindowView";
 const WINDOW_CLS_NAME: &str = "WezTermWindow";
 
+/// The IME may pass either an NSString or an NSAttributedString
+unsafe fn ime_string_to_str<'a>(astring: id) -> &'a str {
+    let is_attributed: BOOL = msg_send![astring, isKindOfClass: class!(NSAttributedString)];
+    if is_attributed == YES {
+        let plain: id = msg_send![astring, string];
+        nsstring_to_str(plain)
+    } else {
+        nsstring_to_str(astring)
+    }
+}
+
 struct WindowView {
     inner: Rc<RefCell<Inner>>,
 }
@@ -1402,11 +1478,15 @@ impl WindowView {
         astring: id,
         _replacement_range: NSRange,
     ) {
-        let s = unsafe { nsstring_to_str(astring) };
+        let s = unsafe { ime_string_to_str(astring) };
         if let Some(myself) = Self::get_this(this) {
             let mut inner = myself.inner.borrow_mut();
             let key_is_down = inner.key_is_down.take().unwrap_or(true);
 
+            inner
+                .events
+                .dispatch(WindowEvent::ImeComposition(ImeCompositionStatus::None));
+
             let event = KeyEvent {
                 key: KeyCode::Composed(s.to_string()),
                 raw_key: None,
@@ -1422,22 +1502,33 @@ impl WindowView {
         }
     }
 
+    // Called by the IME to update the preedit text while composing
     extern "C" fn set_marked_text_selected_range_replacement_range(
-        _this: &mut Object,
+        this: &mut Object,
         _sel: Sel,
-        _astring: id,
-        selected_range: NSRange,
-        replacement_range: NSRange,
+        astring: id,
+        _selected_range: NSRange,
+        _replacement_range: NSRange,
     ) {
-        let s = unsafe { nsstring_to_str(_astring) };
-        eprintln!(
-            "set_marked_text_selected_range_replacement_range {} {:?} {:?}",
-            s, selected_range, replacement_range
-        );
+        let s = unsafe { ime_string_to_str(astring) };
+        let status = if s.is_empty() {
+            ImeCompositionStatus::None
+        } else {
+            ImeCompositionStatus::Composing(s.to_string())
+        };
+        if let Some(myself) = Self::get_this(this) {
+            let mut inner = myself.inner.borrow_mut();
+            inner.events.dispatch(WindowEvent::ImeComposition(status));
+        }
     }
 
-    extern "C" fn unmark_text(_this: &mut Object, _sel: Sel) {
-        eprintln!("unmarkText");
+    extern "C" fn unmark_text(this: &mut Object, _sel: Sel) {
+        if let Some(myself) = Self::get_this(this) {
+            let mut inner = myself.inner.borrow_mut();
+            inner
+                .events
+                .dispatch(WindowEvent::ImeComposition(ImeCompositionStatus::None));
+        }
     }
 
     extern "C" fn valid_attributes_for_marked_text(_this: &mut Object, _sel: Sel) -> id {
@@ -2012,6 +2103,13 @@ impl WindowView {
                 // event that will in turn trigger an invalidation
                 // and a repaint.
                 inner.screen_changed = false;
+                if let Some(name) = inner
+                    .window
+                    .as_ref()
+                    .and_then(|window| screen_name(*window.load()))
+                {
+                    inner.events.dispatch(WindowEvent::ScreenChanged(name));
+                }
                 drop(inner);
                 Self::did_resize(view, sel, nil);
                 return;
@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use super::fractional_scale::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1;
 use super::pointer::*;
 use super::window::*;
 use crate::connection::ConnectionOps;
@@ -15,13 +16,23 @@ use std::os::unix::fs::FileExt;
 use std::os::unix::io::FromRawFd;
 use std::rc::Rc;
 use std::sync::atomic::AtomicUsize;
-use toolkit::environment::Environment;
+use toolkit::environment::{Environment, SimpleGlobal};
 use toolkit::reexports::client::Display;
 use toolkit::seat::SeatListener;
 use wayland_client::protocol::wl_keyboard::{Event as WlKeyboardEvent, KeymapFormat, WlKeyboard};
 use wayland_client::{EventQueue, Main};
+use wayland_protocols::viewporter::client::wp_viewporter::WpViewporter;
 
-toolkit::default_environment!(MyEnvironment, desktop);
+toolkit::default_environment!(MyEnvironment, desktop,
+    fields = [
+        viewporter: SimpleGlobal<WpViewporter>,
+        fractional_scale_manager: SimpleGlobal<WpFractionalScaleManagerV1>,
+    ],
+    singles = [
+        WpViewporter => viewporter,
+        WpFractionalScaleManagerV1 => fractional_scale_manager,
+    ],
+);
 
 pub struct WaylandConnection {
     should_terminate: RefCell<bool>,
@@ -56,8 +67,14 @@ pub struct WaylandConnection {
 
 impl WaylandConnection {
     pub fn create_new() -> anyhow::Result<Self> {
-        let (environment, display, event_q) =
-            toolkit::new_default_environment!(MyEnvironment, desktop)?;
+        let (environment, display, event_q) = toolkit::new_default_environment!(
+            MyEnvironment,
+            desktop,
+            fields = [
+                viewporter: SimpleGlobal::new(),
+                fractional_scale_manager: SimpleGlobal::new(),
+            ]
+        )?;
 
         let mut pointer = None;
 
@@ -0,0 +1,18 @@
+//! Client bindings for the wp_fractional_scale_v1 protocol, which
+//! are generated by build.rs from `protocols/fractional-scale-v1.xml`
+//! as our version of wayland-protocols doesn't yet include it.
+#![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+#![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+#![allow(clippy::all)]
+
+pub(crate) use wayland_client::protocol::wl_surface;
+pub(crate) use wayland_client::sys;
+pub(crate) use wayland_client::{
+    AnonymousObject, Attached, DispatchData, Display, Main, Proxy, ProxyMap,
+};
+pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+pub(crate) use wayland_commons::smallvec;
+pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+pub(crate) use wayland_commons::{Interface, MessageGroup};
+
+include!(concat!(env!("OUT_DIR"), "/fractional_scale_v1.rs"));
@@ -5,5 +5,6 @@ pub mod window;
 pub use self::window::*;
 pub use connection::*;
 mod copy_and_paste;
+mod fractional_scale;
 mod frame;
 mod pointer;
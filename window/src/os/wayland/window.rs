@@ -1,4 +1,8 @@
 use super::copy_and_paste::*;
+use super::fractional_scale::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1;
+use super::fractional_scale::wp_fractional_scale_v1::{
+    Event as FractionalScaleEvent, WpFractionalScaleV1,
+};
 use super::frame::{ConceptConfig, ConceptFrame};
 use super::pointer::*;
 use crate::connection::ConnectionOps;
@@ -25,16 +29,19 @@ use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use toolkit::get_surface_scale_factor;
+use toolkit::output::with_output_info;
 use toolkit::reexports::client::protocol::wl_data_source::Event as DataSourceEvent;
 use toolkit::reexports::client::protocol::wl_pointer::ButtonState;
 use toolkit::reexports::client::protocol::wl_surface::WlSurface;
 use toolkit::window::{Decorations, Event as SCTKWindowEvent, State};
+use toolkit::{get_surface_outputs, get_surface_scale_factor};
 use wayland_client::protocol::wl_callback::WlCallback;
 use wayland_client::protocol::wl_data_device_manager::WlDataDeviceManager;
 use wayland_client::protocol::wl_keyboard::{Event as WlKeyboardEvent, KeyState};
 use wayland_client::{Attached, Main};
 use wayland_egl::{is_available as egl_is_available, WlEglSurface};
+use wayland_protocols::viewporter::client::wp_viewport::WpViewport;
+use wayland_protocols::viewporter::client::wp_viewporter::WpViewporter;
 use wezterm_font::FontConfiguration;
 use wezterm_input_types::*;
 
@@ -139,6 +146,17 @@ pub struct WaylandWindowInner {
     // libraries will segfault on shutdown
     wegl_surface: Option<WlEglSurface>,
     gl_state: Option<Rc<glium::backend::Context>>,
+    /// When the compositor supports wp_fractional_scale_v1 and
+    /// wp_viewporter, these are used to render at the preferred
+    /// fractional scale rather than the integer buffer scale
+    fractional_scale: Option<Main<WpFractionalScaleV1>>,
+    viewport: Option<Main<WpViewport>>,
+    /// The most recent preferred scale, in 120ths
+    preferred_scale: Option<u32>,
+    /// The scale factor that is currently applied to the surface
+    scale_factor: f64,
+    /// The name of the output that the surface was last seen on
+    screen_name: Option<String>,
 }
 
 #[derive(Default, Clone, Debug)]
@@ -148,6 +166,8 @@ struct PendingEvent {
     refresh_decorations: bool,
     configure: Option<(u32, u32)>,
     dpi: Option<i32>,
+    /// The preferred fractional scale, in 120ths
+    fractional_scale: Option<u32>,
     window_state: Option<WindowState>,
 }
 
@@ -263,6 +283,42 @@ impl WaylandWindow {
             .borrow_mut()
             .insert(surface.as_ref().id(), window_id);
 
+        let (fractional_scale, viewport) = {
+            let env = conn.environment.borrow();
+            match (
+                env.get_global::<WpFractionalScaleManagerV1>(),
+                env.get_global::<WpViewporter>(),
+            ) {
+                (Some(manager), Some(viewporter)) => {
+                    let fractional_scale = manager.get_fractional_scale(&surface);
+                    fractional_scale.quick_assign({
+                        let pending_event = Arc::clone(&pending_event);
+                        move |_, event, _| match event {
+                            FractionalScaleEvent::PreferredScale { scale } => {
+                                pending_event
+                                    .lock()
+                                    .unwrap()
+                                    .fractional_scale
+                                    .replace(scale);
+                                log::debug!(
+                                    "window id={} preferred fractional scale changed to {}",
+                                    window_id,
+                                    scale as f64 / 120.
+                                );
+                                WaylandConnection::with_window_inner(window_id, move |inner| {
+                                    inner.dispatch_pending_event();
+                                    Ok(())
+                                });
+                            }
+                        }
+                    });
+                    let viewport = viewporter.get_viewport(&surface);
+                    (Some(fractional_scale), Some(viewport))
+                }
+                _ => (None, None),
+            }
+        };
+
         let dimensions = Dimensions {
             pixel_width: width,
             pixel_height: height,
@@ -350,6 +406,11 @@ impl WaylandWindow {
             title: None,
             gl_state: None,
             wegl_surface: None,
+            fractional_scale,
+            viewport,
+            preferred_scale: None,
+            scale_factor: 1.0,
+            screen_name: None,
         }));
 
         let window_handle = Window::Wayland(WaylandWindow(window_id));
@@ -425,6 +486,10 @@ impl WaylandWindowInner {
             } => {
                 mapper.update_modifier_state(mods_depressed, mods_latched, mods_locked, group);
                 self.modifiers = mapper.get_key_modifiers();
+                if let Some(layout) = mapper.take_layout_change() {
+                    self.events
+                        .dispatch(WindowEvent::KeyboardLayoutChanged(layout));
+                }
             }
             _ => {}
         }
@@ -448,10 +513,10 @@ impl WaylandWindowInner {
         let pending_mouse = Arc::clone(&self.pending_mouse);
 
         if let Some((x, y)) = PendingMouse::coords(&pending_mouse) {
-            let coords = Point::new(
-                self.surface_to_pixels(x as i32) as isize,
-                self.surface_to_pixels(y as i32) as isize,
-            );
+            // Scale before truncating so that we don't lose
+            // precision when the scale is fractional
+            let factor = self.get_dpi_factor();
+            let coords = Point::new((x * factor) as isize, (y * factor) as isize);
             self.last_mouse_coords = coords;
             let event = MouseEvent {
                 kind: MouseEventKind::Move,
@@ -498,7 +563,7 @@ impl WaylandWindowInner {
         }
 
         if let Some((value_x, value_y)) = PendingMouse::scroll(&pending_mouse) {
-            let factor = self.get_dpi_factor() as f64;
+            let factor = self.get_dpi_factor();
             let discrete_x = value_x.trunc() * factor;
             if discrete_x != 0. {
                 let event = MouseEvent {
@@ -531,19 +596,57 @@ impl WaylandWindowInner {
         }
     }
 
-    fn get_dpi_factor(&self) -> i32 {
-        self.dimensions.dpi as i32 / crate::DEFAULT_DPI as i32
+    fn get_dpi_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Returns the scale factor that should be applied to the surface.
+    /// This is the preferred fractional scale if the compositor supports
+    /// wp_fractional_scale_v1, otherwise the integer scale of the outputs
+    /// that the surface is on.
+    fn compute_scale_factor(&self) -> f64 {
+        match (self.preferred_scale, &self.viewport) {
+            (Some(scale), Some(_)) => scale as f64 / 120.,
+            _ => get_surface_scale_factor(&self.surface) as f64,
+        }
+    }
+
+    fn scale_factor_to_dpi(factor: f64) -> usize {
+        (factor * crate::DEFAULT_DPI).round() as usize
     }
 
     fn surface_to_pixels(&self, surface: i32) -> i32 {
-        surface * self.get_dpi_factor()
+        // Toplevel sizes are rounded halfway away from zero
+        // by the fractional scale protocol
+        ((surface as f64) * self.get_dpi_factor()).round() as i32
     }
 
     fn pixels_to_surface(&self, pixels: i32) -> i32 {
         // Take care to round up, otherwise we can lose a pixel
         // and that can effectively lose the final row of the
         // terminal
-        ((pixels as f64) / (self.get_dpi_factor() as f64)).ceil() as i32
+        ((pixels as f64) / self.get_dpi_factor()).ceil() as i32
+    }
+
+    /// Check whether the surface has moved on to a different output,
+    /// and generate a ScreenChanged event if so.
+    /// The wl_output protocol version that we bind doesn't provide
+    /// a connector name, so the output is identified by its make
+    /// and model.
+    fn check_and_notify_screen_change(&mut self) {
+        let name = match get_surface_outputs(&self.surface).first() {
+            Some(output) => {
+                match with_output_info(output, |info| format!("{} {}", info.make, info.model)) {
+                    Some(name) => name,
+                    None => return,
+                }
+            }
+            None => return,
+        };
+        if self.screen_name.as_ref() != Some(&name) {
+            self.screen_name.replace(name.clone());
+            self.events.dispatch(WindowEvent::ScreenChanged(name));
+        }
     }
 
     fn dispatch_pending_event(&mut self) {
@@ -566,8 +669,18 @@ impl WaylandWindowInner {
             self.window_state = window_state;
         }
 
+        self.check_and_notify_screen_change();
+
+        let mut fractional_scale_changed = false;
+        if let Some(scale) = pending.fractional_scale.take() {
+            if self.preferred_scale != Some(scale) {
+                self.preferred_scale.replace(scale);
+                fractional_scale_changed = true;
+            }
+        }
+
         if pending.configure.is_none() {
-            if pending.dpi.is_some() {
+            if pending.dpi.is_some() || fractional_scale_changed {
                 // Synthesize a pending configure event for the dpi change
                 pending.configure.replace((
                     self.pixels_to_surface(self.dimensions.pixel_width as i32) as u32,
@@ -579,10 +692,11 @@ impl WaylandWindowInner {
 
         if let Some((mut w, mut h)) = pending.configure.take() {
             if self.window.is_some() {
-                let factor = get_surface_scale_factor(&self.surface);
+                let factor = self.compute_scale_factor();
 
                 // Do this early because this affects surface_to_pixels/pixels_to_surface below!
-                self.dimensions.dpi = factor as usize * crate::DEFAULT_DPI as usize;
+                self.scale_factor = factor;
+                self.dimensions.dpi = Self::scale_factor_to_dpi(factor);
 
                 let mut pixel_width = self.surface_to_pixels(w.try_into().unwrap());
                 let mut pixel_height = self.surface_to_pixels(h.try_into().unwrap());
@@ -601,8 +715,19 @@ impl WaylandWindowInner {
                 // Avoid blurring by matching the scaling factor of the
                 // compositor; if it is going to double the size then
                 // we render at double the size anyway and tell it that
-                // the buffer is already doubled
-                self.surface.set_buffer_scale(factor);
+                // the buffer is already doubled.
+                // With a fractional scale, the buffer scale remains at
+                // 1 and the viewport maps our larger buffer back on to
+                // the surface size instead.
+                match self.viewport.as_ref() {
+                    Some(viewport) if self.preferred_scale.is_some() => {
+                        self.surface.set_buffer_scale(1);
+                        viewport.set_destination(w as i32, h as i32);
+                    }
+                    _ => {
+                        self.surface.set_buffer_scale(factor as i32);
+                    }
+                }
 
                 // Update the window decoration size
                 self.window.as_mut().unwrap().resize(w, h);
@@ -611,7 +736,7 @@ impl WaylandWindowInner {
                 let new_dimensions = Dimensions {
                     pixel_width: pixel_width.try_into().unwrap(),
                     pixel_height: pixel_height.try_into().unwrap(),
-                    dpi: factor as usize * crate::DEFAULT_DPI as usize,
+                    dpi: Self::scale_factor_to_dpi(factor),
                 };
                 // Only trigger a resize if the new dimensions are different;
                 // this makes things more efficient and a little more smooth
@@ -972,6 +1097,12 @@ fn read_pipe_with_timeout(mut file: FileDescriptor) -> anyhow::Result<String> {
 impl WaylandWindowInner {
     fn close(&mut self) {
         self.events.dispatch(WindowEvent::Destroyed);
+        if let Some(fractional_scale) = self.fractional_scale.take() {
+            fractional_scale.destroy();
+        }
+        if let Some(viewport) = self.viewport.take() {
+            viewport.destroy();
+        }
         self.window.take();
     }
 
@@ -1048,11 +1179,10 @@ impl WaylandWindowInner {
             window.surface().commit();
         }
 
-        let factor = get_surface_scale_factor(&self.surface);
         Dimensions {
             pixel_width: pixel_width as _,
             pixel_height: pixel_height as _,
-            dpi: factor as usize * crate::DEFAULT_DPI as usize,
+            dpi: Self::scale_factor_to_dpi(self.get_dpi_factor()),
         }
     }
 
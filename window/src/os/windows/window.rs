@@ -2,9 +2,9 @@ use super::*;
 use crate::connection::ConnectionOps;
 use crate::Appearance;
 use crate::{
-    Clipboard, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor, MouseEvent,
-    MouseEventKind, MousePress, Point, Rect, ScreenPoint, WindowDecorations, WindowEvent,
-    WindowEventSender, WindowOps, WindowState,
+    Clipboard, Dimensions, ImeCompositionStatus, KeyCode, KeyEvent, Modifiers, MouseButtons,
+    MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint,
+    WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
 };
 use anyhow::{bail, Context};
 use async_trait::async_trait;
@@ -32,6 +32,7 @@ use winapi::um::libloaderapi::GetModuleHandleW;
 use winapi::um::winuser::*;
 use winreg::{enums::HKEY_CURRENT_USER, RegKey};
 
+const GCS_COMPSTR: DWORD = 0x8;
 const GCS_RESULTSTR: DWORD = 0x800;
 extern "system" {
     pub fn ImmGetCompositionStringW(himc: HIMC, index: DWORD, buf: LPVOID, buflen: DWORD) -> LONG;
@@ -58,6 +59,8 @@ pub(crate) struct WindowInner {
 
     keyboard_info: KeyboardLayoutInfo,
     appearance: Appearance,
+    /// The device name of the monitor that the window was last seen on
+    screen_name: Option<String>,
 
     config: ConfigHandle,
 }
@@ -176,6 +179,19 @@ impl WindowInner {
         Ok(gl_state)
     }
 
+    /// Check if the window has moved to a different monitor,
+    /// and generate a ScreenChanged event if so.
+    fn check_and_notify_screen_change(&mut self) {
+        let name = match unsafe { monitor_name(self.hwnd.0) } {
+            Some(name) => name,
+            None => return,
+        };
+        if self.screen_name.as_ref() != Some(&name) {
+            self.screen_name.replace(name.clone());
+            self.events.dispatch(WindowEvent::ScreenChanged(name));
+        }
+    }
+
     /// Check if we need to generate a resize callback.
     /// Calls resize if needed.
     /// Returns true if we did.
@@ -399,6 +415,7 @@ impl Window {
         let inner = Rc::new(RefCell::new(WindowInner {
             hwnd: HWindow(null_mut()),
             appearance,
+            screen_name: None,
             events,
             gl_state: None,
             vscroll_remainder: 0,
@@ -513,6 +530,20 @@ impl WindowInner {
         self.apply_decoration();
     }
 
+    fn request_attention(&mut self) {
+        // Flash the taskbar button until the window comes to the foreground
+        let mut info = FLASHWINFO {
+            cbSize: std::mem::size_of::<FLASHWINFO>() as u32,
+            hwnd: self.hwnd.0,
+            dwFlags: FLASHW_TRAY | FLASHW_TIMERNOFG,
+            uCount: 0,
+            dwTimeout: 0,
+        };
+        unsafe {
+            FlashWindowEx(&mut info);
+        }
+    }
+
     fn toggle_fullscreen(&mut self) {
         unsafe {
             let hwnd = self.hwnd.0;
@@ -646,6 +677,13 @@ impl WindowOps for Window {
         });
     }
 
+    fn request_attention(&self) {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.request_attention();
+            Ok(())
+        });
+    }
+
     fn config_did_change(&self, config: &ConfigHandle) {
         let config = config.clone();
         Connection::with_window_inner(self.0, move |inner| {
@@ -868,10 +906,33 @@ unsafe fn wm_windowposchanged(
     _lparam: LPARAM,
 ) -> Option<LRESULT> {
     // let pos = &*(lparam as *const WINDOWPOS);
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        inner.borrow_mut().check_and_notify_screen_change();
+    }
     wm_size(hwnd, 0, 0, 0)?;
     Some(0)
 }
 
+/// Returns the device name of the monitor that most of the window is on
+unsafe fn monitor_name(hwnd: HWND) -> Option<String> {
+    let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+    if monitor.is_null() {
+        return None;
+    }
+    let mut info: MONITORINFOEXW = std::mem::zeroed();
+    info.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    let info_ptr = &mut info as *mut MONITORINFOEXW as *mut MONITORINFO;
+    if GetMonitorInfoW(monitor, info_ptr) == 0 {
+        return None;
+    }
+    let len = info
+        .szDevice
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(info.szDevice.len());
+    Some(String::from_utf16_lossy(&info.szDevice[..len]))
+}
+
 unsafe fn wm_size(hwnd: HWND, _msg: UINT, _wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
     let mut should_paint = false;
     let mut should_pump = false;
@@ -1121,7 +1182,9 @@ unsafe fn mouse_wheel(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
                     inner.hscroll_remainder,
                     position
                 );
-                MouseEventKind::HorzWheel(position)
+                // WM_MOUSEHWHEEL uses positive values for the right,
+                // which is the opposite of our convention
+                MouseEventKind::HorzWheel(-position)
             } else {
                 let mut inner = inner.borrow_mut();
                 inner.vscroll_remainder += remainder;
@@ -1184,6 +1247,31 @@ impl ImmContext {
             ImmSetCompositionWindow(self.imc, &mut cf);
         }
     }
+
+    /// Returns the composition string identified by `index`, which
+    /// is one of the GCS_XXX constants
+    unsafe fn get_composition_string(&self, index: DWORD) -> Option<String> {
+        // This returns a size in bytes even though it is for a buffer of u16!
+        let byte_size = ImmGetCompositionStringW(self.imc, index, std::ptr::null_mut(), 0);
+        if byte_size < 0 {
+            return None;
+        }
+        let word_size = byte_size as usize / 2;
+        let mut wide_buf = vec![0u16; word_size];
+        ImmGetCompositionStringW(
+            self.imc,
+            index,
+            wide_buf.as_mut_ptr() as *mut _,
+            byte_size as u32,
+        );
+        match OsString::from_wide(&wide_buf).into_string() {
+            Ok(s) => Some(s),
+            Err(_) => {
+                eprintln!("cannot represent IME as unicode string!?");
+                None
+            }
+        }
+    }
 }
 
 impl Drop for ImmContext {
@@ -1202,55 +1290,90 @@ unsafe fn ime_composition(
 ) -> Option<LRESULT> {
     if let Some(inner) = rc_from_hwnd(hwnd) {
         let mut inner = inner.borrow_mut();
+        let imc = ImmContext::get(hwnd);
 
         if (lparam as DWORD) & GCS_RESULTSTR == 0 {
+            if (lparam as DWORD) & GCS_COMPSTR != 0 {
+                // The preedit text changed; we render it ourselves
+                // rather than allowing the IME to draw it
+                if let Some(s) = imc.get_composition_string(GCS_COMPSTR) {
+                    let status = if s.is_empty() {
+                        ImeCompositionStatus::None
+                    } else {
+                        ImeCompositionStatus::Composing(s)
+                    };
+                    inner.events.dispatch(WindowEvent::ImeComposition(status));
+                    return Some(1);
+                }
+            }
             // No finished result; continue with the default
             // processing
             return None;
         }
 
-        let imc = ImmContext::get(hwnd);
-
-        // This returns a size in bytes even though it is for a buffer of u16!
-        let byte_size = ImmGetCompositionStringW(imc.imc, GCS_RESULTSTR, std::ptr::null_mut(), 0);
-        if byte_size > 0 {
-            let word_size = byte_size as usize / 2;
-            let mut wide_buf = vec![0u16; word_size];
-            ImmGetCompositionStringW(
-                imc.imc,
-                GCS_RESULTSTR,
-                wide_buf.as_mut_ptr() as *mut _,
-                byte_size as u32,
-            );
-            match OsString::from_wide(&wide_buf).into_string() {
-                Ok(s) => {
-                    let key = KeyEvent {
-                        key: KeyCode::Composed(s),
-                        raw_key: None,
-                        raw_modifiers: Modifiers::NONE,
-                        raw_code: None,
-                        modifiers: Modifiers::NONE,
-                        repeat_count: 1,
-                        key_is_down: true,
-                    }
-                    .normalize_shift();
-                    inner.events.dispatch(WindowEvent::KeyEvent(key));
-
-                    return Some(1);
+        if let Some(s) = imc.get_composition_string(GCS_RESULTSTR) {
+            if !s.is_empty() {
+                inner
+                    .events
+                    .dispatch(WindowEvent::ImeComposition(ImeCompositionStatus::None));
+                let key = KeyEvent {
+                    key: KeyCode::Composed(s),
+                    raw_key: None,
+                    raw_modifiers: Modifiers::NONE,
+                    raw_code: None,
+                    modifiers: Modifiers::NONE,
+                    repeat_count: 1,
+                    key_is_down: true,
                 }
-                Err(_) => eprintln!("cannot represent IME as unicode string!?"),
-            };
+                .normalize_shift();
+                inner.events.dispatch(WindowEvent::KeyEvent(key));
+
+                return Some(1);
+            }
         }
     }
     None
 }
 
+unsafe fn input_lang_change(
+    hwnd: HWND,
+    _msg: UINT,
+    _wparam: WPARAM,
+    _lparam: LPARAM,
+) -> Option<LRESULT> {
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        let mut inner = inner.borrow_mut();
+        let layout = inner.keyboard_info.layout_name();
+        inner
+            .events
+            .dispatch(WindowEvent::KeyboardLayoutChanged(layout));
+    }
+    None
+}
+
+unsafe fn ime_end_composition(
+    hwnd: HWND,
+    _msg: UINT,
+    _wparam: WPARAM,
+    _lparam: LPARAM,
+) -> Option<LRESULT> {
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        inner
+            .borrow_mut()
+            .events
+            .dispatch(WindowEvent::ImeComposition(ImeCompositionStatus::None));
+    }
+    None
+}
+
 /// Holds information about the current keyboard layout.
 /// This is used to determine whether the layout includes
 /// an AltGr key or just has a regular Right-Alt key,
 /// as well as to build out information about dead keys.
 struct KeyboardLayoutInfo {
     layout: HKL,
+    /// The identifier of the layout, eg: "0000040C"
+    name: String,
     has_alt_gr: bool,
     dead_keys: HashMap<(Modifiers, u8), DeadKey>,
 }
@@ -1274,6 +1397,7 @@ impl KeyboardLayoutInfo {
     pub fn new() -> Self {
         Self {
             layout: std::ptr::null_mut(),
+            name: String::new(),
             has_alt_gr: false,
             dead_keys: HashMap::new(),
         }
@@ -1484,6 +1608,23 @@ impl KeyboardLayoutInfo {
 
         SetKeyboardState(saved_state.as_mut_ptr());
         self.layout = current_layout;
+
+        let mut name = [0u16; KL_NAMELENGTH];
+        self.name = if GetKeyboardLayoutNameW(name.as_mut_ptr()) != 0 {
+            let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            OsString::from_wide(&name[..len])
+                .to_string_lossy()
+                .into_owned()
+        } else {
+            String::new()
+        };
+    }
+
+    pub fn layout_name(&mut self) -> String {
+        unsafe {
+            self.update();
+        }
+        self.name.clone()
     }
 
     pub fn has_alt_gr(&mut self) -> bool {
@@ -1824,7 +1965,8 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
                     // They pressed a dead key.
                     // If they want dead key processing, then record that and
                     // wait for a subsequent keypress.
-                    if inner.config.use_dead_keys {
+                    let layout = inner.keyboard_info.layout_name();
+                    if inner.config.use_dead_keys.enabled_for_layout(&layout) {
                         inner.dead_pending.replace((modifiers, vk));
                         return Some(0);
                     }
@@ -1926,6 +2068,8 @@ unsafe fn do_wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
         }
         WM_SETTINGCHANGE => apply_theme(hwnd),
         WM_IME_COMPOSITION => ime_composition(hwnd, msg, wparam, lparam),
+        WM_IME_ENDCOMPOSITION => ime_end_composition(hwnd, msg, wparam, lparam),
+        WM_INPUTLANGCHANGE => input_lang_change(hwnd, msg, wparam, lparam),
         WM_MOUSEMOVE => mouse_move(hwnd, msg, wparam, lparam),
         WM_MOUSEHWHEEL | WM_MOUSEWHEEL => mouse_wheel(hwnd, msg, wparam, lparam),
         WM_LBUTTONDBLCLK | WM_RBUTTONDBLCLK | WM_MBUTTONDBLCLK | WM_LBUTTONDOWN | WM_LBUTTONUP
@@ -23,6 +23,8 @@ pub struct XConnection {
     pub root: xcb::xproto::Window,
     pub keyboard: Keyboard,
     pub kbd_ev: u8,
+    /// The first event number of the RandR extension, if it is available
+    randr_ev: Option<u8>,
     pub atom_protocols: xcb::Atom,
     pub cursor_font_id: xcb::ffi::xcb_font_t,
     pub atom_delete: xcb::Atom,
@@ -323,11 +325,43 @@ impl XConnection {
                 //    - update modifiers state
                 //    - update keymap/state on keyboard changes
                 self.keyboard.process_xkb_event(&self.conn, event)?;
+                if let Some(layout) = self.keyboard.take_layout_change() {
+                    for window in self.windows.borrow().values() {
+                        window.lock().unwrap().keyboard_layout_changed(&layout);
+                    }
+                }
+            } else if let Some(randr_ev) = self.randr_ev {
+                if r == randr_ev + xcb::randr::SCREEN_CHANGE_NOTIFY
+                    || r == randr_ev + xcb::randr::NOTIFY
+                {
+                    // Monitors were added, removed or rearranged
+                    for window in self.windows.borrow().values() {
+                        window.lock().unwrap().check_and_notify_screen_change();
+                    }
+                }
             }
         }
         Ok(())
     }
 
+    /// Returns the name of the RandR monitor that contains the
+    /// specified point on the root window
+    pub(crate) fn monitor_name_at(&self, x: i16, y: i16) -> Option<String> {
+        self.randr_ev?;
+        let reply = xcb::randr::get_monitors(&self.conn, self.root, true)
+            .get_reply()
+            .ok()?;
+        let monitor = reply.monitors().find(|m| {
+            let (mx, my) = (i32::from(m.x()), i32::from(m.y()));
+            let (x, y) = (i32::from(x), i32::from(y));
+            x >= mx && x < mx + i32::from(m.width()) && y >= my && y < my + i32::from(m.height())
+        })?;
+        let name = xcb::get_atom_name(&self.conn, monitor.name())
+            .get_reply()
+            .ok()?;
+        Some(name.name().to_string())
+    }
+
     pub(crate) fn window_by_id(
         &self,
         window_id: xcb::xproto::Window,
@@ -451,6 +485,22 @@ impl XConnection {
         );
         let (keyboard, kbd_ev) = Keyboard::new(&conn)?;
 
+        conn.prefetch_extension_data(xcb::randr::id());
+        let randr_ev = conn
+            .get_extension_data(xcb::randr::id())
+            .filter(|r| r.present())
+            .map(|r| r.first_event());
+        if randr_ev.is_some() {
+            xcb::randr::select_input(
+                &conn,
+                screen.root(),
+                (xcb::randr::NOTIFY_MASK_SCREEN_CHANGE | xcb::randr::NOTIFY_MASK_CRTC_CHANGE)
+                    as u16,
+            );
+        } else {
+            log::trace!("RandR is not available; screen changes will not be reported");
+        }
+
         let cursor_font_id = conn.generate_id();
         let cursor_font_name = "cursor";
         xcb::open_font_checked(&conn, cursor_font_id, cursor_font_name)
@@ -505,6 +555,7 @@ impl XConnection {
             keysyms,
             keyboard,
             kbd_ev,
+            randr_ev,
             atom_utf8_string,
             atom_xsel_data,
             atom_targets,
@@ -14,6 +14,9 @@ pub struct Keyboard {
 
     state: RefCell<xkb::State>,
     compose_state: RefCell<xkb::compose::State>,
+    /// The name of the layout that was most recently reported
+    /// by take_layout_change
+    last_layout: RefCell<String>,
 }
 
 impl Keyboard {
@@ -38,12 +41,14 @@ impl Keyboard {
         .map_err(|_| anyhow!("Failed to acquire compose table from locale"))?;
         let compose_state = xkb::compose::State::new(&table, xkb::compose::STATE_NO_FLAGS);
 
+        let last_layout = RefCell::new(effective_layout_name(&keymap, &state));
         Ok(Self {
             context,
             device_id: -1,
             keymap: RefCell::new(keymap),
             state: RefCell::new(state),
             compose_state: RefCell::new(compose_state),
+            last_layout,
         })
     }
 
@@ -121,12 +126,33 @@ impl Keyboard {
             cookie.request_check()?;
         }
 
+        {
+            // Ask for auto-repeated keys to be reported as a sequence of
+            // presses followed by a single release, rather than as pairs
+            // of release and press events, so that the release of a held
+            // key can be distinguished from it repeating.
+            let flag = xcb::xkb::PER_CLIENT_FLAG_DETECTABLE_AUTO_REPEAT;
+            let cookie =
+                xcb::xkb::per_client_flags(&connection, device_id as u16, flag, flag, 0, 0, 0);
+            match cookie.get_reply() {
+                Ok(r) if r.value() & flag == 0 => {
+                    log::debug!("detectable auto repeat is not supported");
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    log::error!("Failed to enable detectable auto repeat: {:?}", err);
+                }
+            }
+        }
+
+        let last_layout = RefCell::new(effective_layout_name(&keymap, &state));
         let kbd = Keyboard {
             context,
             device_id,
             keymap: RefCell::new(keymap),
             state: RefCell::new(state),
             compose_state: RefCell::new(compose_state),
+            last_layout,
         };
 
         Ok((kbd, first_ev))
@@ -155,6 +181,22 @@ impl Keyboard {
 
             let cstate = self.compose_state.borrow().status();
             match cstate {
+                ComposeStatus::Composing
+                    if is_dead_keysym(xsym)
+                        && !config::configuration()
+                            .use_dead_keys
+                            .enabled_for_layout(&self.layout_name()) =>
+                {
+                    // Ignore dead key sequences; feed a space to
+                    // elicit the underlying character, in the same
+                    // way that we do on macOS
+                    self.compose_state
+                        .borrow_mut()
+                        .feed(xkb::keysyms::KEY_space);
+                    let res = self.compose_state.borrow().keysym();
+                    self.compose_state.borrow_mut().reset();
+                    res.unwrap_or(xsym)
+                }
                 ComposeStatus::Composing => {
                     // eat
                     return None;
@@ -202,6 +244,24 @@ impl Keyboard {
         })
     }
 
+    /// Returns the name of the active keyboard layout, eg: "French"
+    pub fn layout_name(&self) -> String {
+        effective_layout_name(&self.keymap.borrow(), &self.state.borrow())
+    }
+
+    /// Returns the name of the active keyboard layout if it has
+    /// changed since the last time that this method was called
+    pub fn take_layout_change(&self) -> Option<String> {
+        let name = self.layout_name();
+        let mut last = self.last_layout.borrow_mut();
+        if *last == name {
+            None
+        } else {
+            *last = name.clone();
+            Some(name)
+        }
+    }
+
     fn mod_is_active(&self, modifier: &str) -> bool {
         // [TODO] consider state  Depressed & consumed mods
         self.state
@@ -303,6 +363,15 @@ impl Keyboard {
     }
 }
 
+fn effective_layout_name(keymap: &xkb::Keymap, state: &xkb::State) -> String {
+    let layout = state.serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE);
+    keymap.layout_get_name(layout).to_string()
+}
+
+fn is_dead_keysym(keysym: xkb::Keysym) -> bool {
+    (xkb::keysyms::KEY_dead_grave..=xkb::keysyms::KEY_dead_greek).contains(&keysym)
+}
+
 fn query_lc_ctype() -> anyhow::Result<&'static CStr> {
     let ptr = unsafe { libc::setlocale(libc::LC_CTYPE, std::ptr::null()) };
     ensure!(!ptr.is_null(), "failed to query locale");
@@ -67,6 +67,10 @@ pub(crate) struct XWindowInner {
     appearance: Appearance,
     title: String,
     has_focus: bool,
+    /// Whether we have set the urgency hint
+    urgent: bool,
+    /// The name of the monitor that the window was last seen on
+    screen_name: Option<String>,
     last_cursor_position: Rect,
     invalidated: bool,
     paint_throttled: bool,
@@ -169,6 +173,29 @@ impl XWindowInner {
         self.pending.push(event);
     }
 
+    /// Check whether the window has moved on to a different monitor,
+    /// and generate a ScreenChanged event if so.
+    /// The monitor is the one that contains the center of the window.
+    pub(crate) fn check_and_notify_screen_change(&mut self) {
+        let conn = self.conn();
+        let origin = match xcb::translate_coordinates(conn.conn(), self.window_id, conn.root, 0, 0)
+            .get_reply()
+        {
+            Ok(origin) => origin,
+            Err(_) => return,
+        };
+        let x = origin.dst_x().saturating_add((self.width / 2) as i16);
+        let y = origin.dst_y().saturating_add((self.height / 2) as i16);
+        let name = match conn.monitor_name_at(x, y) {
+            Some(name) => name,
+            None => return,
+        };
+        if self.screen_name.as_ref() != Some(&name) {
+            self.screen_name.replace(name.clone());
+            self.queue_pending(WindowEvent::ScreenChanged(name));
+        }
+    }
+
     pub fn dispatch_pending_events(&mut self) -> anyhow::Result<()> {
         if self.pending.is_empty() {
             return Ok(());
@@ -245,6 +272,8 @@ impl XWindowInner {
                 let height = cfg.height();
                 let dpi = conn.default_dpi();
 
+                self.check_and_notify_screen_change();
+
                 if width == self.width && height == self.height && dpi == self.dpi {
                     // Effectively unchanged; perhaps it was simply moved?
                     // Do nothing!
@@ -327,6 +356,23 @@ impl XWindowInner {
                             -LINES_PER_TICK
                         })
                     }
+                    b @ 6..=7 => {
+                        if r == xcb::BUTTON_RELEASE {
+                            return Ok(());
+                        }
+
+                        // Tilt wheels and touchpads report horizontal
+                        // scrolling as buttons 6 (left) and 7 (right).
+                        // This matches the default number of columns
+                        // scrolled per notch on Windows.
+                        const COLUMNS_PER_TICK: i16 = 3;
+
+                        MouseEventKind::HorzWheel(if b == 6 {
+                            COLUMNS_PER_TICK
+                        } else {
+                            -COLUMNS_PER_TICK
+                        })
+                    }
                     _ => {
                         eprintln!("button {} is not implemented", button_press.detail());
                         return Ok(());
@@ -405,6 +451,9 @@ impl XWindowInner {
             }
             xcb::FOCUS_IN => {
                 self.has_focus = true;
+                if self.urgent {
+                    self.set_urgency_hint(false);
+                }
                 self.update_ime_position();
                 log::trace!("Calling focus_change(true)");
                 self.events.dispatch(WindowEvent::FocusChanged(true));
@@ -422,6 +471,15 @@ impl XWindowInner {
         Ok(())
     }
 
+    /// Reports the new keyboard layout to the window if it has focus,
+    /// as that is the window whose keyboard input is affected
+    pub fn keyboard_layout_changed(&mut self, layout: &str) {
+        if self.has_focus {
+            self.events
+                .dispatch(WindowEvent::KeyboardLayoutChanged(layout.to_string()));
+        }
+    }
+
     pub fn dispatch_ime_text(&mut self, text: &str) {
         let key_event = KeyEvent {
             key: KeyCode::Composed(text.into()),
@@ -849,6 +907,8 @@ impl XWindow {
                 cursors: CursorInfo::new(&conn),
                 config: config.clone(),
                 has_focus: false,
+                urgent: false,
+                screen_name: None,
                 last_cursor_position: Rect::default(),
                 paint_throttled: false,
                 invalidated: false,
@@ -1008,6 +1068,42 @@ impl XWindowInner {
         );
     }
 
+    /// Sets or clears the ICCCM urgency hint.
+    /// The other WM_HINTS fields are read back and preserved, so that
+    /// only the urgency flag is changed.
+    fn set_urgency_hint(&mut self, urgent: bool) {
+        self.urgent = urgent;
+        unsafe {
+            let conn = self.conn().conn().get_raw_conn();
+            let mut hints: xcb_util::ffi::icccm::xcb_icccm_wm_hints_t = std::mem::zeroed();
+            let cookie = xcb_util::ffi::icccm::xcb_icccm_get_wm_hints(conn, self.window_id);
+            if xcb_util::ffi::icccm::xcb_icccm_get_wm_hints_reply(
+                conn,
+                cookie,
+                &mut hints,
+                std::ptr::null_mut(),
+            ) == 0
+            {
+                // No WM_HINTS yet
+                hints = std::mem::zeroed();
+                xcb_util::ffi::icccm::xcb_icccm_wm_hints_set_input(&mut hints, 1);
+            }
+            if urgent {
+                xcb_util::ffi::icccm::xcb_icccm_wm_hints_set_urgency(&mut hints);
+            } else {
+                // XUrgencyHint
+                hints.flags &= !(1 << 8);
+            }
+            xcb_util::ffi::icccm::xcb_icccm_set_wm_hints(conn, self.window_id, &hints);
+        }
+    }
+
+    fn request_attention(&mut self) {
+        if !self.has_focus {
+            self.set_urgency_hint(true);
+        }
+    }
+
     fn set_resize_increments(&mut self, x: u16, y: u16) -> anyhow::Result<()> {
         let mut hints = SizeHints::empty();
         hints = hints.resize(x.into(), y.into());
@@ -1150,6 +1246,13 @@ impl WindowOps for XWindow {
         });
     }
 
+    fn request_attention(&self) {
+        XConnection::with_window_inner(self.0, move |inner| {
+            inner.request_attention();
+            Ok(())
+        });
+    }
+
     fn set_resize_increments(&self, x: u16, y: u16) {
         XConnection::with_window_inner(self.0, move |inner| {
             if let Err(err) = inner.set_resize_increments(x, y) {
@@ -293,6 +293,14 @@ impl WindowOps for Window {
         }
     }
 
+    fn request_attention(&self) {
+        match self {
+            Self::X11(x) => x.request_attention(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.request_attention(),
+        }
+    }
+
     fn set_inner_size(&self, width: usize, height: usize) {
         match self {
             Self::X11(x) => x.set_inner_size(width, height),
@@ -84,7 +84,7 @@ pub fn keysym_to_keycode(keysym: u32) -> Option<KeyCode> {
 
         KEY_VoidSymbol => KeyCode::VoidSymbol,
 
-        i @ KEY_F1..=KEY_F12 => KeyCode::Function((1 + i - KEY_F1) as u8),
+        i @ KEY_F1..=KEY_F24 => KeyCode::Function((1 + i - KEY_F1) as u8),
 
         // numeric and function keypad keys
         KEY_KP_Enter => KeyCode::Char(0xdu8 as char),
@@ -115,10 +115,16 @@ pub fn keysym_to_keycode(keysym: u32) -> Option<KeyCode> {
         KEY_XF86Refresh => KeyCode::BrowserRefresh,
         KEY_XF86Favorites => KeyCode::BrowserFavorites,
         KEY_XF86HomePage => KeyCode::BrowserHome,
+        KEY_XF86Search => KeyCode::BrowserSearch,
 
         KEY_XF86AudioLowerVolume => KeyCode::VolumeDown,
         KEY_XF86AudioMute => KeyCode::VolumeMute,
         KEY_XF86AudioRaiseVolume => KeyCode::VolumeUp,
+        KEY_XF86AudioNext => KeyCode::MediaNextTrack,
+        KEY_XF86AudioPrev => KeyCode::MediaPrevTrack,
+        KEY_XF86AudioStop => KeyCode::MediaStop,
+        KEY_XF86AudioPlay => KeyCode::MediaPlayPause,
+        KEY_XF86AudioPause => KeyCode::MediaPlayPause,
         _ => return None,
     })
 }